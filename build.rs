@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // needs protoc on the PATH, or PROTOC pointing at it
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/liquidator.proto")?;
+    Ok(())
+}
@@ -14,7 +14,22 @@ pub mod liquidator {
     fn __abi() -> ::ethers::core::abi::Abi {
         ::ethers::core::abi::ethabi::Contract {
             constructor: ::core::option::Option::Some(::ethers::core::abi::ethabi::Constructor {
-                inputs: ::std::vec![],
+                inputs: ::std::vec![
+                    ::ethers::core::abi::ethabi::Param {
+                        name: ::std::borrow::ToOwned::to_owned("_pool"),
+                        kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                        internal_type: ::core::option::Option::Some(
+                            ::std::borrow::ToOwned::to_owned("contract IL2Pool"),
+                        ),
+                    },
+                    ::ethers::core::abi::ethabi::Param {
+                        name: ::std::borrow::ToOwned::to_owned("_uniswapV3Factory"),
+                        kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                        internal_type: ::core::option::Option::Some(
+                            ::std::borrow::ToOwned::to_owned("address"),
+                        ),
+                    },
+                ],
             }),
             functions: ::core::convert::From::from([
                 (
@@ -37,6 +52,112 @@ pub mod liquidator {
                         },
                     ],
                 ),
+                (
+                    ::std::borrow::ToOwned::to_owned("approvePoolBatch"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("approvePoolBatch"),
+                            inputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("tokens"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Array(
+                                        ::std::boxed::Box::new(
+                                            ::ethers::core::abi::ethabi::ParamType::Address,
+                                        ),
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address[]"),
+                                    ),
+                                },
+                            ],
+                            outputs: ::std::vec![],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::NonPayable,
+                        },
+                    ],
+                ),
+                (
+                    ::std::borrow::ToOwned::to_owned("balancerVault"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("balancerVault"),
+                            inputs: ::std::vec![],
+                            outputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::string::String::new(),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned(
+                                            "contract IBalancerVault",
+                                        ),
+                                    ),
+                                },
+                            ],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::View,
+                        },
+                    ],
+                ),
+                (
+                    ::std::borrow::ToOwned::to_owned("executeOperation"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("executeOperation"),
+                            inputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::string::String::new(),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::string::String::new(),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Uint(
+                                        256usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("uint256"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::string::String::new(),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Uint(
+                                        256usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("uint256"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("initiator"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("params"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Bytes,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes"),
+                                    ),
+                                },
+                            ],
+                            outputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::string::String::new(),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Bool,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bool"),
+                                    ),
+                                },
+                            ],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::NonPayable,
+                        },
+                    ],
+                ),
                 (
                     ::std::borrow::ToOwned::to_owned("liquidate"),
                     ::std::vec![
@@ -106,6 +227,208 @@ pub mod liquidator {
                         },
                     ],
                 ),
+                (
+                    ::std::borrow::ToOwned::to_owned("liquidateBatch"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("liquidateBatch"),
+                            inputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("legs"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Array(
+                                        ::std::boxed::Box::new(
+                                            ::ethers::core::abi::ethabi::ParamType::Tuple(
+                                                ::std::vec![
+                                                    ::ethers::core::abi::ethabi::ParamType::Address,
+                                                    ::ethers::core::abi::ethabi::ParamType::Address,
+                                                    ::ethers::core::abi::ethabi::ParamType::Uint(
+                                                        24usize,
+                                                    ),
+                                                    ::ethers::core::abi::ethabi::ParamType::Uint(
+                                                        256usize,
+                                                    ),
+                                                    ::ethers::core::abi::ethabi::ParamType::FixedBytes(
+                                                        32usize,
+                                                    ),
+                                                    ::ethers::core::abi::ethabi::ParamType::FixedBytes(
+                                                        32usize,
+                                                    ),
+                                                ],
+                                            ),
+                                        ),
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned(
+                                            "struct Liquidator.Leg[]",
+                                        ),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("user"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                            ],
+                            outputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("collateralGains"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Array(
+                                        ::std::boxed::Box::new(
+                                            ::ethers::core::abi::ethabi::ParamType::Int(
+                                                256usize,
+                                            ),
+                                        ),
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("int256[]"),
+                                    ),
+                                },
+                            ],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::NonPayable,
+                        },
+                    ],
+                ),
+                (
+                    ::std::borrow::ToOwned::to_owned("liquidateFlashLoan"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("liquidateFlashLoan"),
+                            inputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("collateral"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("debt"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("debtToCover"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Uint(
+                                        256usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("uint256"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("liquidationArg1"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::FixedBytes(
+                                        32usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes32"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("liquidationArg2"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::FixedBytes(
+                                        32usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes32"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("swap"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Bytes,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes"),
+                                    ),
+                                },
+                            ],
+                            outputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("collateralGain"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Int(256usize),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("int256"),
+                                    ),
+                                },
+                            ],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::NonPayable,
+                        },
+                    ],
+                ),
+                (
+                    ::std::borrow::ToOwned::to_owned("liquidatePath"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("liquidatePath"),
+                            inputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("collateral"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("debt"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("path"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Bytes,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("debtToCover"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Uint(
+                                        256usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("uint256"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("liquidationArg1"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::FixedBytes(
+                                        32usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes32"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("liquidationArg2"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::FixedBytes(
+                                        32usize,
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes32"),
+                                    ),
+                                },
+                            ],
+                            outputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("collateralGain"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Int(256usize),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("int256"),
+                                    ),
+                                },
+                            ],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::NonPayable,
+                        },
+                    ],
+                ),
                 (
                     ::std::borrow::ToOwned::to_owned("owner"),
                     ::std::vec![
@@ -146,6 +469,63 @@ pub mod liquidator {
                         },
                     ],
                 ),
+                (
+                    ::std::borrow::ToOwned::to_owned("receiveFlashLoan"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("receiveFlashLoan"),
+                            inputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("tokens"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Array(
+                                        ::std::boxed::Box::new(
+                                            ::ethers::core::abi::ethabi::ParamType::Address,
+                                        ),
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address[]"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("amounts"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Array(
+                                        ::std::boxed::Box::new(
+                                            ::ethers::core::abi::ethabi::ParamType::Uint(
+                                                256usize,
+                                            ),
+                                        ),
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("uint256[]"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("feeAmounts"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Array(
+                                        ::std::boxed::Box::new(
+                                            ::ethers::core::abi::ethabi::ParamType::Uint(
+                                                256usize,
+                                            ),
+                                        ),
+                                    ),
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("uint256[]"),
+                                    ),
+                                },
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::borrow::ToOwned::to_owned("userData"),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Bytes,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("bytes"),
+                                    ),
+                                },
+                            ],
+                            outputs: ::std::vec![],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::NonPayable,
+                        },
+                    ],
+                ),
                 (
                     ::std::borrow::ToOwned::to_owned("recover"),
                     ::std::vec![
@@ -195,6 +575,26 @@ pub mod liquidator {
                         },
                     ],
                 ),
+                (
+                    ::std::borrow::ToOwned::to_owned("uniswapV3Factory"),
+                    ::std::vec![
+                        ::ethers::core::abi::ethabi::Function {
+                            name: ::std::borrow::ToOwned::to_owned("uniswapV3Factory"),
+                            inputs: ::std::vec![],
+                            outputs: ::std::vec![
+                                ::ethers::core::abi::ethabi::Param {
+                                    name: ::std::string::String::new(),
+                                    kind: ::ethers::core::abi::ethabi::ParamType::Address,
+                                    internal_type: ::core::option::Option::Some(
+                                        ::std::borrow::ToOwned::to_owned("address"),
+                                    ),
+                                },
+                            ],
+                            constant: ::core::option::Option::None,
+                            state_mutability: ::ethers::core::abi::ethabi::StateMutability::View,
+                        },
+                    ],
+                ),
                 (
                     ::std::borrow::ToOwned::to_owned("uniswapV3SwapCallback"),
                     ::std::vec![
@@ -363,6 +763,39 @@ pub mod liquidator {
                 .method_hash([66, 76, 38, 91], token)
                 .expect("method not found (this should never happen)")
         }
+        ///Calls the contract's `approvePoolBatch` (0xb23a92e1) function
+        pub fn approve_pool_batch(
+            &self,
+            tokens: ::std::vec::Vec<::ethers::core::types::Address>,
+        ) -> ::ethers::contract::builders::ContractCall<M, ()> {
+            self.0
+                .method_hash([178, 58, 146, 225], tokens)
+                .expect("method not found (this should never happen)")
+        }
+        ///Calls the contract's `balancerVault` (0x158274a5) function
+        pub fn balancer_vault(
+            &self,
+        ) -> ::ethers::contract::builders::ContractCall<
+            M,
+            ::ethers::core::types::Address,
+        > {
+            self.0
+                .method_hash([21, 130, 116, 165], ())
+                .expect("method not found (this should never happen)")
+        }
+        ///Calls the contract's `executeOperation` (0x1b11d0ff) function
+        pub fn execute_operation(
+            &self,
+            p0: ::ethers::core::types::Address,
+            p1: ::ethers::core::types::U256,
+            p2: ::ethers::core::types::U256,
+            initiator: ::ethers::core::types::Address,
+            params: ::ethers::core::types::Bytes,
+        ) -> ::ethers::contract::builders::ContractCall<M, bool> {
+            self.0
+                .method_hash([27, 17, 208, 255], (p0, p1, p2, initiator, params))
+                .expect("method not found (this should never happen)")
+        }
         ///Calls the contract's `liquidate` (0xb845d3c1) function
         pub fn liquidate(
             &self,
@@ -387,6 +820,67 @@ pub mod liquidator {
                 )
                 .expect("method not found (this should never happen)")
         }
+        ///Calls the contract's `liquidateBatch` (0xc13e81b0) function
+        pub fn liquidate_batch(
+            &self,
+            legs: ::std::vec::Vec<Leg>,
+            user: ::ethers::core::types::Address,
+        ) -> ::ethers::contract::builders::ContractCall<
+            M,
+            ::std::vec::Vec<::ethers::core::types::I256>,
+        > {
+            self.0
+                .method_hash([193, 62, 129, 176], (legs, user))
+                .expect("method not found (this should never happen)")
+        }
+        ///Calls the contract's `liquidateFlashLoan` (0x9d544c79) function
+        pub fn liquidate_flash_loan(
+            &self,
+            collateral: ::ethers::core::types::Address,
+            debt: ::ethers::core::types::Address,
+            debt_to_cover: ::ethers::core::types::U256,
+            liquidation_arg_1: [u8; 32],
+            liquidation_arg_2: [u8; 32],
+            swap: ::ethers::core::types::Bytes,
+        ) -> ::ethers::contract::builders::ContractCall<M, ::ethers::core::types::I256> {
+            self.0
+                .method_hash(
+                    [157, 84, 76, 121],
+                    (
+                        collateral,
+                        debt,
+                        debt_to_cover,
+                        liquidation_arg_1,
+                        liquidation_arg_2,
+                        swap,
+                    ),
+                )
+                .expect("method not found (this should never happen)")
+        }
+        ///Calls the contract's `liquidatePath` (0x72dc6dc1) function
+        pub fn liquidate_path(
+            &self,
+            collateral: ::ethers::core::types::Address,
+            debt: ::ethers::core::types::Address,
+            path: ::ethers::core::types::Bytes,
+            debt_to_cover: ::ethers::core::types::U256,
+            liquidation_arg_1: [u8; 32],
+            liquidation_arg_2: [u8; 32],
+        ) -> ::ethers::contract::builders::ContractCall<M, ::ethers::core::types::I256> {
+            self.0
+                .method_hash(
+                    [114, 220, 109, 193],
+                    (
+                        collateral,
+                        debt,
+                        path,
+                        debt_to_cover,
+                        liquidation_arg_1,
+                        liquidation_arg_2,
+                    ),
+                )
+                .expect("method not found (this should never happen)")
+        }
         ///Calls the contract's `owner` (0x8da5cb5b) function
         pub fn owner(
             &self,
@@ -409,6 +903,18 @@ pub mod liquidator {
                 .method_hash([22, 240, 17, 91], ())
                 .expect("method not found (this should never happen)")
         }
+        ///Calls the contract's `receiveFlashLoan` (0xf04f2707) function
+        pub fn receive_flash_loan(
+            &self,
+            tokens: ::std::vec::Vec<::ethers::core::types::Address>,
+            amounts: ::std::vec::Vec<::ethers::core::types::U256>,
+            fee_amounts: ::std::vec::Vec<::ethers::core::types::U256>,
+            user_data: ::ethers::core::types::Bytes,
+        ) -> ::ethers::contract::builders::ContractCall<M, ()> {
+            self.0
+                .method_hash([240, 79, 39, 7], (tokens, amounts, fee_amounts, user_data))
+                .expect("method not found (this should never happen)")
+        }
         ///Calls the contract's `recover` (0x5705ae43) function
         pub fn recover(
             &self,
@@ -422,10 +928,21 @@ pub mod liquidator {
         ///Calls the contract's `transferOwnership` (0xf2fde38b) function
         pub fn transfer_ownership(
             &self,
-            new_owner: ::ethers::core::types::Address,
-        ) -> ::ethers::contract::builders::ContractCall<M, ()> {
+            new_owner: ::ethers::core::types::Address,
+        ) -> ::ethers::contract::builders::ContractCall<M, ()> {
+            self.0
+                .method_hash([242, 253, 227, 139], new_owner)
+                .expect("method not found (this should never happen)")
+        }
+        ///Calls the contract's `uniswapV3Factory` (0x5b549182) function
+        pub fn uniswap_v3_factory(
+            &self,
+        ) -> ::ethers::contract::builders::ContractCall<
+            M,
+            ::ethers::core::types::Address,
+        > {
             self.0
-                .method_hash([242, 253, 227, 139], new_owner)
+                .method_hash([91, 84, 145, 130], ())
                 .expect("method not found (this should never happen)")
         }
         ///Calls the contract's `uniswapV3SwapCallback` (0xfa461e33) function
@@ -501,6 +1018,56 @@ pub mod liquidator {
     pub struct ApprovePoolCall {
         pub token: ::ethers::core::types::Address,
     }
+    ///Container type for all input parameters for the `approvePoolBatch` function with signature `approvePoolBatch(address[])` and selector `0xb23a92e1`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(name = "approvePoolBatch", abi = "approvePoolBatch(address[])")]
+    pub struct ApprovePoolBatchCall {
+        pub tokens: ::std::vec::Vec<::ethers::core::types::Address>,
+    }
+    ///Container type for all input parameters for the `balancerVault` function with signature `balancerVault()` and selector `0x158274a5`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(name = "balancerVault", abi = "balancerVault()")]
+    pub struct BalancerVaultCall;
+    ///Container type for all input parameters for the `executeOperation` function with signature `executeOperation(address,uint256,uint256,address,bytes)` and selector `0x1b11d0ff`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(
+        name = "executeOperation",
+        abi = "executeOperation(address,uint256,uint256,address,bytes)"
+    )]
+    pub struct ExecuteOperationCall {
+        pub p0: ::ethers::core::types::Address,
+        pub p1: ::ethers::core::types::U256,
+        pub p2: ::ethers::core::types::U256,
+        pub initiator: ::ethers::core::types::Address,
+        pub params: ::ethers::core::types::Bytes,
+    }
     ///Container type for all input parameters for the `liquidate` function with signature `liquidate(address,address,uint24,uint256,bytes32,bytes32)` and selector `0xb845d3c1`
     #[derive(
         Clone,
@@ -524,6 +1091,71 @@ pub mod liquidator {
         pub liquidation_arg_1: [u8; 32],
         pub liquidation_arg_2: [u8; 32],
     }
+    ///Container type for all input parameters for the `liquidateBatch` function with signature `liquidateBatch((address,address,uint24,uint256,bytes32,bytes32)[],address)` and selector `0xc13e81b0`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(
+        name = "liquidateBatch",
+        abi = "liquidateBatch((address,address,uint24,uint256,bytes32,bytes32)[],address)"
+    )]
+    pub struct LiquidateBatchCall {
+        pub legs: ::std::vec::Vec<Leg>,
+        pub user: ::ethers::core::types::Address,
+    }
+    ///Container type for all input parameters for the `liquidateFlashLoan` function with signature `liquidateFlashLoan(address,address,uint256,bytes32,bytes32,bytes)` and selector `0x9d544c79`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(
+        name = "liquidateFlashLoan",
+        abi = "liquidateFlashLoan(address,address,uint256,bytes32,bytes32,bytes)"
+    )]
+    pub struct LiquidateFlashLoanCall {
+        pub collateral: ::ethers::core::types::Address,
+        pub debt: ::ethers::core::types::Address,
+        pub debt_to_cover: ::ethers::core::types::U256,
+        pub liquidation_arg_1: [u8; 32],
+        pub liquidation_arg_2: [u8; 32],
+        pub swap: ::ethers::core::types::Bytes,
+    }
+    ///Container type for all input parameters for the `liquidatePath` function with signature `liquidatePath(address,address,bytes,uint256,bytes32,bytes32)` and selector `0x72dc6dc1`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(
+        name = "liquidatePath",
+        abi = "liquidatePath(address,address,bytes,uint256,bytes32,bytes32)"
+    )]
+    pub struct LiquidatePathCall {
+        pub collateral: ::ethers::core::types::Address,
+        pub debt: ::ethers::core::types::Address,
+        pub path: ::ethers::core::types::Bytes,
+        pub debt_to_cover: ::ethers::core::types::U256,
+        pub liquidation_arg_1: [u8; 32],
+        pub liquidation_arg_2: [u8; 32],
+    }
     ///Container type for all input parameters for the `owner` function with signature `owner()` and selector `0x8da5cb5b`
     #[derive(
         Clone,
@@ -550,6 +1182,27 @@ pub mod liquidator {
     )]
     #[ethcall(name = "pool", abi = "pool()")]
     pub struct PoolCall;
+    ///Container type for all input parameters for the `receiveFlashLoan` function with signature `receiveFlashLoan(address[],uint256[],uint256[],bytes)` and selector `0xf04f2707`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(
+        name = "receiveFlashLoan",
+        abi = "receiveFlashLoan(address[],uint256[],uint256[],bytes)"
+    )]
+    pub struct ReceiveFlashLoanCall {
+        pub tokens: ::std::vec::Vec<::ethers::core::types::Address>,
+        pub amounts: ::std::vec::Vec<::ethers::core::types::U256>,
+        pub fee_amounts: ::std::vec::Vec<::ethers::core::types::U256>,
+        pub user_data: ::ethers::core::types::Bytes,
+    }
     ///Container type for all input parameters for the `recover` function with signature `recover(address,uint256)` and selector `0x5705ae43`
     #[derive(
         Clone,
@@ -581,6 +1234,19 @@ pub mod liquidator {
     pub struct TransferOwnershipCall {
         pub new_owner: ::ethers::core::types::Address,
     }
+    ///Container type for all input parameters for the `uniswapV3Factory` function with signature `uniswapV3Factory()` and selector `0x5b549182`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthCall,
+        ::ethers::contract::EthDisplay,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    #[ethcall(name = "uniswapV3Factory", abi = "uniswapV3Factory()")]
+    pub struct UniswapV3FactoryCall;
     ///Container type for all input parameters for the `uniswapV3SwapCallback` function with signature `uniswapV3SwapCallback(int256,int256,bytes)` and selector `0xfa461e33`
     #[derive(
         Clone,
@@ -605,11 +1271,19 @@ pub mod liquidator {
     #[derive(Clone, ::ethers::contract::EthAbiType, Debug, PartialEq, Eq, Hash)]
     pub enum LiquidatorCalls {
         ApprovePool(ApprovePoolCall),
+        ApprovePoolBatch(ApprovePoolBatchCall),
+        BalancerVault(BalancerVaultCall),
+        ExecuteOperation(ExecuteOperationCall),
         Liquidate(LiquidateCall),
+        LiquidateBatch(LiquidateBatchCall),
+        LiquidateFlashLoan(LiquidateFlashLoanCall),
+        LiquidatePath(LiquidatePathCall),
         Owner(OwnerCall),
         Pool(PoolCall),
+        ReceiveFlashLoan(ReceiveFlashLoanCall),
         Recover(RecoverCall),
         TransferOwnership(TransferOwnershipCall),
+        UniswapV3Factory(UniswapV3FactoryCall),
         UniswapV3SwapCallback(UniswapV3SwapCallbackCall),
     }
     impl ::ethers::core::abi::AbiDecode for LiquidatorCalls {
@@ -621,10 +1295,40 @@ pub mod liquidator {
                 = <ApprovePoolCall as ::ethers::core::abi::AbiDecode>::decode(data) {
                 return Ok(Self::ApprovePool(decoded));
             }
+            if let Ok(decoded)
+                = <ApprovePoolBatchCall as ::ethers::core::abi::AbiDecode>::decode(
+                    data,
+                ) {
+                return Ok(Self::ApprovePoolBatch(decoded));
+            }
+            if let Ok(decoded)
+                = <BalancerVaultCall as ::ethers::core::abi::AbiDecode>::decode(data) {
+                return Ok(Self::BalancerVault(decoded));
+            }
+            if let Ok(decoded)
+                = <ExecuteOperationCall as ::ethers::core::abi::AbiDecode>::decode(
+                    data,
+                ) {
+                return Ok(Self::ExecuteOperation(decoded));
+            }
             if let Ok(decoded)
                 = <LiquidateCall as ::ethers::core::abi::AbiDecode>::decode(data) {
                 return Ok(Self::Liquidate(decoded));
             }
+            if let Ok(decoded)
+                = <LiquidateBatchCall as ::ethers::core::abi::AbiDecode>::decode(data) {
+                return Ok(Self::LiquidateBatch(decoded));
+            }
+            if let Ok(decoded)
+                = <LiquidateFlashLoanCall as ::ethers::core::abi::AbiDecode>::decode(
+                    data,
+                ) {
+                return Ok(Self::LiquidateFlashLoan(decoded));
+            }
+            if let Ok(decoded)
+                = <LiquidatePathCall as ::ethers::core::abi::AbiDecode>::decode(data) {
+                return Ok(Self::LiquidatePath(decoded));
+            }
             if let Ok(decoded)
                 = <OwnerCall as ::ethers::core::abi::AbiDecode>::decode(data) {
                 return Ok(Self::Owner(decoded));
@@ -633,6 +1337,12 @@ pub mod liquidator {
                 = <PoolCall as ::ethers::core::abi::AbiDecode>::decode(data) {
                 return Ok(Self::Pool(decoded));
             }
+            if let Ok(decoded)
+                = <ReceiveFlashLoanCall as ::ethers::core::abi::AbiDecode>::decode(
+                    data,
+                ) {
+                return Ok(Self::ReceiveFlashLoan(decoded));
+            }
             if let Ok(decoded)
                 = <RecoverCall as ::ethers::core::abi::AbiDecode>::decode(data) {
                 return Ok(Self::Recover(decoded));
@@ -643,6 +1353,12 @@ pub mod liquidator {
                 ) {
                 return Ok(Self::TransferOwnership(decoded));
             }
+            if let Ok(decoded)
+                = <UniswapV3FactoryCall as ::ethers::core::abi::AbiDecode>::decode(
+                    data,
+                ) {
+                return Ok(Self::UniswapV3Factory(decoded));
+            }
             if let Ok(decoded)
                 = <UniswapV3SwapCallbackCall as ::ethers::core::abi::AbiDecode>::decode(
                     data,
@@ -658,15 +1374,39 @@ pub mod liquidator {
                 Self::ApprovePool(element) => {
                     ::ethers::core::abi::AbiEncode::encode(element)
                 }
+                Self::ApprovePoolBatch(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
+                Self::BalancerVault(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
+                Self::ExecuteOperation(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
                 Self::Liquidate(element) => {
                     ::ethers::core::abi::AbiEncode::encode(element)
                 }
+                Self::LiquidateBatch(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
+                Self::LiquidateFlashLoan(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
+                Self::LiquidatePath(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
                 Self::Owner(element) => ::ethers::core::abi::AbiEncode::encode(element),
                 Self::Pool(element) => ::ethers::core::abi::AbiEncode::encode(element),
+                Self::ReceiveFlashLoan(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
                 Self::Recover(element) => ::ethers::core::abi::AbiEncode::encode(element),
                 Self::TransferOwnership(element) => {
                     ::ethers::core::abi::AbiEncode::encode(element)
                 }
+                Self::UniswapV3Factory(element) => {
+                    ::ethers::core::abi::AbiEncode::encode(element)
+                }
                 Self::UniswapV3SwapCallback(element) => {
                     ::ethers::core::abi::AbiEncode::encode(element)
                 }
@@ -677,11 +1417,21 @@ pub mod liquidator {
         fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
             match self {
                 Self::ApprovePool(element) => ::core::fmt::Display::fmt(element, f),
+                Self::ApprovePoolBatch(element) => ::core::fmt::Display::fmt(element, f),
+                Self::BalancerVault(element) => ::core::fmt::Display::fmt(element, f),
+                Self::ExecuteOperation(element) => ::core::fmt::Display::fmt(element, f),
                 Self::Liquidate(element) => ::core::fmt::Display::fmt(element, f),
+                Self::LiquidateBatch(element) => ::core::fmt::Display::fmt(element, f),
+                Self::LiquidateFlashLoan(element) => {
+                    ::core::fmt::Display::fmt(element, f)
+                }
+                Self::LiquidatePath(element) => ::core::fmt::Display::fmt(element, f),
                 Self::Owner(element) => ::core::fmt::Display::fmt(element, f),
                 Self::Pool(element) => ::core::fmt::Display::fmt(element, f),
+                Self::ReceiveFlashLoan(element) => ::core::fmt::Display::fmt(element, f),
                 Self::Recover(element) => ::core::fmt::Display::fmt(element, f),
                 Self::TransferOwnership(element) => ::core::fmt::Display::fmt(element, f),
+                Self::UniswapV3Factory(element) => ::core::fmt::Display::fmt(element, f),
                 Self::UniswapV3SwapCallback(element) => {
                     ::core::fmt::Display::fmt(element, f)
                 }
@@ -693,11 +1443,41 @@ pub mod liquidator {
             Self::ApprovePool(value)
         }
     }
+    impl ::core::convert::From<ApprovePoolBatchCall> for LiquidatorCalls {
+        fn from(value: ApprovePoolBatchCall) -> Self {
+            Self::ApprovePoolBatch(value)
+        }
+    }
+    impl ::core::convert::From<BalancerVaultCall> for LiquidatorCalls {
+        fn from(value: BalancerVaultCall) -> Self {
+            Self::BalancerVault(value)
+        }
+    }
+    impl ::core::convert::From<ExecuteOperationCall> for LiquidatorCalls {
+        fn from(value: ExecuteOperationCall) -> Self {
+            Self::ExecuteOperation(value)
+        }
+    }
     impl ::core::convert::From<LiquidateCall> for LiquidatorCalls {
         fn from(value: LiquidateCall) -> Self {
             Self::Liquidate(value)
         }
     }
+    impl ::core::convert::From<LiquidateBatchCall> for LiquidatorCalls {
+        fn from(value: LiquidateBatchCall) -> Self {
+            Self::LiquidateBatch(value)
+        }
+    }
+    impl ::core::convert::From<LiquidateFlashLoanCall> for LiquidatorCalls {
+        fn from(value: LiquidateFlashLoanCall) -> Self {
+            Self::LiquidateFlashLoan(value)
+        }
+    }
+    impl ::core::convert::From<LiquidatePathCall> for LiquidatorCalls {
+        fn from(value: LiquidatePathCall) -> Self {
+            Self::LiquidatePath(value)
+        }
+    }
     impl ::core::convert::From<OwnerCall> for LiquidatorCalls {
         fn from(value: OwnerCall) -> Self {
             Self::Owner(value)
@@ -708,6 +1488,11 @@ pub mod liquidator {
             Self::Pool(value)
         }
     }
+    impl ::core::convert::From<ReceiveFlashLoanCall> for LiquidatorCalls {
+        fn from(value: ReceiveFlashLoanCall) -> Self {
+            Self::ReceiveFlashLoan(value)
+        }
+    }
     impl ::core::convert::From<RecoverCall> for LiquidatorCalls {
         fn from(value: RecoverCall) -> Self {
             Self::Recover(value)
@@ -718,11 +1503,40 @@ pub mod liquidator {
             Self::TransferOwnership(value)
         }
     }
+    impl ::core::convert::From<UniswapV3FactoryCall> for LiquidatorCalls {
+        fn from(value: UniswapV3FactoryCall) -> Self {
+            Self::UniswapV3Factory(value)
+        }
+    }
     impl ::core::convert::From<UniswapV3SwapCallbackCall> for LiquidatorCalls {
         fn from(value: UniswapV3SwapCallbackCall) -> Self {
             Self::UniswapV3SwapCallback(value)
         }
     }
+    ///Container type for all return fields from the `balancerVault` function with signature `balancerVault()` and selector `0x158274a5`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct BalancerVaultReturn(pub ::ethers::core::types::Address);
+    ///Container type for all return fields from the `executeOperation` function with signature `executeOperation(address,uint256,uint256,address,bytes)` and selector `0x1b11d0ff`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct ExecuteOperationReturn(pub bool);
     ///Container type for all return fields from the `liquidate` function with signature `liquidate(address,address,uint24,uint256,bytes32,bytes32)` and selector `0xb845d3c1`
     #[derive(
         Clone,
@@ -737,6 +1551,48 @@ pub mod liquidator {
     pub struct LiquidateReturn {
         pub collateral_gain: ::ethers::core::types::I256,
     }
+    ///Container type for all return fields from the `liquidateBatch` function with signature `liquidateBatch((address,address,uint24,uint256,bytes32,bytes32)[],address)` and selector `0xc13e81b0`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct LiquidateBatchReturn {
+        pub collateral_gains: ::std::vec::Vec<::ethers::core::types::I256>,
+    }
+    ///Container type for all return fields from the `liquidateFlashLoan` function with signature `liquidateFlashLoan(address,address,uint256,bytes32,bytes32,bytes)` and selector `0x9d544c79`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct LiquidateFlashLoanReturn {
+        pub collateral_gain: ::ethers::core::types::I256,
+    }
+    ///Container type for all return fields from the `liquidatePath` function with signature `liquidatePath(address,address,bytes,uint256,bytes32,bytes32)` and selector `0x72dc6dc1`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct LiquidatePathReturn {
+        pub collateral_gain: ::ethers::core::types::I256,
+    }
     ///Container type for all return fields from the `owner` function with signature `owner()` and selector `0x8da5cb5b`
     #[derive(
         Clone,
@@ -761,4 +1617,35 @@ pub mod liquidator {
         Hash
     )]
     pub struct PoolReturn(pub ::ethers::core::types::Address);
+    ///Container type for all return fields from the `uniswapV3Factory` function with signature `uniswapV3Factory()` and selector `0x5b549182`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct UniswapV3FactoryReturn(pub ::ethers::core::types::Address);
+    ///`Leg(address,address,uint24,uint256,bytes32,bytes32)`
+    #[derive(
+        Clone,
+        ::ethers::contract::EthAbiType,
+        ::ethers::contract::EthAbiCodec,
+        Default,
+        Debug,
+        PartialEq,
+        Eq,
+        Hash
+    )]
+    pub struct Leg {
+        pub collateral: ::ethers::core::types::Address,
+        pub debt: ::ethers::core::types::Address,
+        pub uniswap_fee: u32,
+        pub debt_to_cover: ::ethers::core::types::U256,
+        pub liquidation_arg_1: [u8; 32],
+        pub liquidation_arg_2: [u8; 32],
+    }
 }
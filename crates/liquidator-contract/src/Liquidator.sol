@@ -3,9 +3,13 @@ pragma solidity ^0.8.13;
 
 import {Owned} from "solmate/auth/Owned.sol";
 import {ERC20} from "solmate/tokens/ERC20.sol";
+import {IBalancerVault} from "./interfaces/IBalancerVault.sol";
+import {ICurvePool} from "./interfaces/ICurvePool.sol";
 import {IL2Pool} from "./interfaces/IL2Pool.sol";
+import {IPool} from "./interfaces/IPool.sol";
 import {IUniswapV3SwapCallback} from "./interfaces/IUniswapV3SwapCallback.sol";
 import {IUniswapV3PoolActions} from "./interfaces/IUniswapV3PoolActions.sol";
+import {Path} from "./lib/Path.sol";
 import {PoolAddress} from "./lib/PoolAddress.sol";
 
 uint160 constant MIN_SQRT_RATIO = 4295128739;
@@ -13,13 +17,41 @@ uint160 constant MIN_SQRT_RATIO = 4295128739;
 uint160 constant MAX_SQRT_RATIO = 1461446703485210103287273052203988822378723970342;
 
 contract Liquidator is Owned(msg.sender), IUniswapV3SwapCallback {
+    using Path for bytes;
+
     // ERC20 constant weth = ERC20(0x4200000000000000000000000000000000000006);
     // ERC20 constant usdc = ERC20(0xd9aAEc86B65D86f6A7B5B1b0c42FFA531710b6CA);
     // IUniswapV3PoolActions constant uniswapPool = IUniswapV3PoolActions(0x4C36388bE6F416A29C8d8Eee81C771cE6bE14B18);
-    address private constant uniswapV3Factory = 0x33128a8fC17869897dcE68Ed026d694621f6FDfD;
-    IL2Pool public constant pool = IL2Pool(0x8F44Fd754285aa6A2b8B9B97739B79746e0475a7);
+    /// @dev Set per deployment, so one build serves every Aave V3 market
+    address public immutable uniswapV3Factory;
+    IL2Pool public immutable pool;
+    /// @dev Same address on every chain Balancer V2 is deployed on
+    IBalancerVault public constant balancerVault = IBalancerVault(0xBA12222222228d8Ba445958a75a0704d566BF2C8);
+
+    /// @dev Where the collateral is swapped for the debt owed to the Balancer Vault
+    enum FlashSwapVenue {
+        BALANCER,
+        CURVE,
+        AGGREGATOR
+    }
 
-    constructor() {}
+    /// @dev Set while this contract's own Balancer flash loan is out, anyone can flash-loan to it otherwise
+    bool private balancerFlashLoan;
+
+    /// @dev One (collateral, debt) pair of a batch, with its liquidation call args packed as L2Pool takes them
+    struct Leg {
+        address collateral;
+        address debt;
+        uint24 uniswapFee;
+        uint256 debtToCover;
+        bytes32 liquidationArg1;
+        bytes32 liquidationArg2;
+    }
+
+    constructor(IL2Pool _pool, address _uniswapV3Factory) {
+        pool = _pool;
+        uniswapV3Factory = _uniswapV3Factory;
+    }
 
     function liquidate(
         address collateral,
@@ -29,47 +61,252 @@ contract Liquidator is Owned(msg.sender), IUniswapV3SwapCallback {
         bytes32 liquidationArg1,
         bytes32 liquidationArg2
     ) external onlyOwner returns (int256 collateralGain) {
-        address uniswapPool =
-            PoolAddress.computeAddress(uniswapV3Factory, PoolAddress.getPoolKey(collateral, debt, uniswapFee));
+        collateralGain = _liquidate(collateral, debt, uniswapFee, debtToCover, liquidationArg1, liquidationArg2);
+    }
+
+    /// @notice Liquidate, flash-swapping the debt through a route of Uniswap V3 pools for pairs with no direct pool
+    /// @param path Exact output route from the debt back to the collateral, `debt, fee, token, fee, ..., collateral` packed
+    function liquidatePath(
+        address collateral,
+        address debt,
+        bytes calldata path,
+        uint256 debtToCover,
+        bytes32 liquidationArg1,
+        bytes32 liquidationArg2
+    ) external onlyOwner returns (int256 collateralGain) {
+        bytes memory route = path;
+        require(route.isValid() && route.firstToken() == debt && route.lastToken() == collateral, "invalid path");
+        uint256 collateralBalance = ERC20(collateral).balanceOf(address(this));
+        _swapExactOutput(debtToCover, address(this), route, debt, liquidationArg1, liquidationArg2);
+        collateralGain = int256(ERC20(collateral).balanceOf(address(this))) - int256(collateralBalance);
+    }
+
+    /// @notice Liquidate, flash-loaning the debt for pairs Uniswap V3 lacks liquidity for
+    /// @dev The Balancer Vault lends it, unless the collateral is swapped on a Balancer pool: the Vault can't be
+    /// reentered while its own loan is out, so the pool lends it then, for its premium
+    /// @param swap How the seized collateral repays the loan, `abi.encode(FlashSwapVenue, params)` with params
+    /// `(bytes32 poolId, uint256 maxAmountIn)` for a Balancer pool, `(address pool, int128 i, int128 j, uint256 amountIn)`
+    /// for a Curve pool or `(address router, address spender, uint256 amountIn, bytes data)` for swap calldata an
+    /// aggregator quoted for this contract
+    function liquidateFlashLoan(
+        address collateral,
+        address debt,
+        uint256 debtToCover,
+        bytes32 liquidationArg1,
+        bytes32 liquidationArg2,
+        bytes calldata swap
+    ) external onlyOwner returns (int256 collateralGain) {
+        uint256 collateralBalance = ERC20(collateral).balanceOf(address(this));
+        bytes memory userData = abi.encode(collateral, liquidationArg1, liquidationArg2, swap);
+
+        (FlashSwapVenue venue,) = abi.decode(swap, (FlashSwapVenue, bytes));
+        if (venue == FlashSwapVenue.BALANCER) {
+            pool.flashLoanSimple(address(this), debt, debtToCover, userData, 0);
+        } else {
+            address[] memory tokens = new address[](1);
+            tokens[0] = debt;
+            uint256[] memory amounts = new uint256[](1);
+            amounts[0] = debtToCover;
+
+            balancerFlashLoan = true;
+            balancerVault.flashLoan(address(this), tokens, amounts, userData);
+            balancerFlashLoan = false;
+        }
+
+        collateralGain = int256(ERC20(collateral).balanceOf(address(this))) - int256(collateralBalance);
+    }
+
+    /// @notice Balancer flash loan callback, liquidating with the loaned debt and swapping the collateral for the
+    /// loan and its fee
+    function receiveFlashLoan(
+        address[] calldata tokens,
+        uint256[] calldata amounts,
+        uint256[] calldata feeAmounts,
+        bytes calldata userData
+    ) external {
+        require(msg.sender == address(balancerVault) && balancerFlashLoan, "invalid flash loan");
+        (address collateral, bytes32 liquidationArg1, bytes32 liquidationArg2, bytes memory swap) =
+            abi.decode(userData, (address, bytes32, bytes32, bytes));
+
+        address debt = tokens[0];
+        _liquidationCall(collateral, debt, liquidationArg1, liquidationArg2);
+
+        uint256 owed = amounts[0] + feeAmounts[0];
+        _swapCollateral(collateral, debt, owed, swap);
+        ERC20(debt).transfer(address(balancerVault), owed);
+    }
+
+    /// @notice Liquidate several pairs of `user` in turn, stopping once it is healthy again
+    /// @dev Legs after the health factor recovers are skipped rather than reverted, their gain is 0
+    function liquidateBatch(Leg[] calldata legs, address user)
+        external
+        onlyOwner
+        returns (int256[] memory collateralGains)
+    {
+        collateralGains = new int256[](legs.length);
+        for (uint256 i = 0; i < legs.length; i++) {
+            if (i > 0) {
+                (,,,,, uint256 healthFactor) = pool.getUserAccountData(user);
+                if (healthFactor >= 1e18) {
+                    break;
+                }
+            }
+            Leg calldata leg = legs[i];
+            collateralGains[i] = _liquidate(
+                leg.collateral, leg.debt, leg.uniswapFee, leg.debtToCover, leg.liquidationArg1, leg.liquidationArg2
+            );
+        }
+    }
+
+    function _liquidate(
+        address collateral,
+        address debt,
+        uint24 uniswapFee,
+        uint256 debtToCover,
+        bytes32 liquidationArg1,
+        bytes32 liquidationArg2
+    ) internal returns (int256 collateralGain) {
         uint256 collateralBalance = ERC20(collateral).balanceOf(address(this));
 
-        // collateral in, debt out
-        bool zeroForOne = collateral < debt;
+        // no Uniswap pool pairs an asset with itself, so the debt is flash-loaned from Aave
+        if (collateral == debt) {
+            pool.flashLoanSimple(
+                address(this), debt, debtToCover, abi.encode(collateral, liquidationArg1, liquidationArg2, ""), 0
+            );
+            return int256(ERC20(collateral).balanceOf(address(this))) - int256(collateralBalance);
+        }
 
-        IUniswapV3PoolActions(uniswapPool).swap(
+        // a route of the single pool pairing the collateral with the debt
+        _swapExactOutput(
+            debtToCover,
             address(this),
+            abi.encodePacked(debt, uniswapFee, collateral),
+            debt,
+            liquidationArg1,
+            liquidationArg2
+        );
+
+        collateralGain = int256(ERC20(collateral).balanceOf(address(this))) - int256(collateralBalance);
+    }
+
+    /// @dev Swap the seized collateral for the `owed` debt of a flash loan on the venue `swap` picks
+    function _swapCollateral(address collateral, address debt, uint256 owed, bytes memory swap) internal {
+        (FlashSwapVenue venue, bytes memory params) = abi.decode(swap, (FlashSwapVenue, bytes));
+        if (venue == FlashSwapVenue.BALANCER) {
+            (bytes32 poolId, uint256 maxAmountIn) = abi.decode(params, (bytes32, uint256));
+            ERC20(collateral).approve(address(balancerVault), maxAmountIn);
+            balancerVault.swap(
+                IBalancerVault.SingleSwap(poolId, IBalancerVault.SwapKind.GIVEN_OUT, collateral, debt, owed, ""),
+                IBalancerVault.FundManagement(address(this), false, payable(address(this)), false),
+                maxAmountIn,
+                block.timestamp
+            );
+        } else if (venue == FlashSwapVenue.CURVE) {
+            // Curve only swaps exact inputs, anything over what is owed stays here
+            (address curvePool, int128 i, int128 j, uint256 amountIn) =
+                abi.decode(params, (address, int128, int128, uint256));
+            ERC20(collateral).approve(curvePool, amountIn);
+            ICurvePool(curvePool).exchange(i, j, amountIn, owed);
+        } else {
+            // aggregators sell exact inputs too, their calldata carries its own minimum out
+            (address router, address spender, uint256 amountIn, bytes memory data) =
+                abi.decode(params, (address, address, uint256, bytes));
+            require(router != address(pool) && router != address(balancerVault), "invalid router");
+            ERC20(collateral).approve(spender, amountIn);
+            (bool success,) = router.call(data);
+            require(success, "aggregator swap failed");
+            ERC20(collateral).approve(spender, 0);
+            require(ERC20(debt).balanceOf(address(this)) >= owed, "aggregator swap short");
+        }
+    }
+
+    /// @dev Swap for exactly `amountOut` of the first token of `path` out of its first pool, to `recipient`.
+    /// The pool is paid back in the callback, which liquidates `debt` once the route reaches the collateral.
+    function _swapExactOutput(
+        uint256 amountOut,
+        address recipient,
+        bytes memory path,
+        address debt,
+        bytes32 liquidationArg1,
+        bytes32 liquidationArg2
+    ) internal {
+        (address tokenOut, address tokenIn, uint24 fee) = path.decodeFirstPool();
+        bool zeroForOne = tokenIn < tokenOut;
+
+        IUniswapV3PoolActions(
+            PoolAddress.computeAddress(uniswapV3Factory, PoolAddress.getPoolKey(tokenIn, tokenOut, fee))
+        ).swap(
+            recipient,
             zeroForOne,
-            // debtToCover is the amount of outputs we need so we do exact output
-            -int256(debtToCover),
+            // we need an exact amount out
+            -int256(amountOut),
             // price is irrelevant
             zeroForOne ? MIN_SQRT_RATIO + 1 : MAX_SQRT_RATIO - 1,
-            abi.encode(collateral, debt, uniswapFee, liquidationArg1, liquidationArg2)
+            abi.encode(path, debt, liquidationArg1, liquidationArg2)
         );
-
-        collateralGain = int256(ERC20(collateral).balanceOf(address(this))) - int256(collateralBalance);
     }
 
     function uniswapV3SwapCallback(int256 amount0Delta, int256 amount1Delta, bytes calldata data) external override {
-        (address collateral, address debt, uint24 uniswapFee, bytes32 liquidationArg1, bytes32 liquidationArg2) =
-            abi.decode(data, (address, address, uint24, bytes32, bytes32));
+        (bytes memory path, address debt, bytes32 liquidationArg1, bytes32 liquidationArg2) =
+            abi.decode(data, (bytes, address, bytes32, bytes32));
+        (address tokenOut, address tokenIn, uint24 fee) = path.decodeFirstPool();
 
-        verifyCallback(uniswapV3Factory, PoolAddress.getPoolKey(collateral, debt, uniswapFee));
+        verifyCallback(uniswapV3Factory, PoolAddress.getPoolKey(tokenIn, tokenOut, fee));
 
-        // we expect to get the opposite token returned
-        pool.liquidationCall(liquidationArg1, liquidationArg2);
+        // the pool is owed the token swapped in
+        uint256 amountToPay = amount0Delta > 0 ? uint256(amount0Delta) : uint256(amount1Delta);
+        if (path.hasMultiplePools()) {
+            // swap for it out of the next pool of the route, straight to this one
+            _swapExactOutput(amountToPay, msg.sender, path.skipToken(), debt, liquidationArg1, liquidationArg2);
+        } else {
+            // the last pool is owed collateral, which the flash-swapped debt liquidates for
+            _liquidationCall(tokenIn, debt, liquidationArg1, liquidationArg2);
+            ERC20(tokenIn).transfer(msg.sender, amountToPay);
+        }
+    }
+
+    /// @notice Aave flash loan callback, of same-asset liquidations the seized collateral repays as is, and of flash
+    /// swaps on Balancer pools
+    /// @dev The pool pulls the loan and its premium through the allowance `approvePool` gave it
+    function executeOperation(address asset, uint256 amount, uint256 premium, address initiator, bytes calldata params)
+        external
+        returns (bool)
+    {
+        require(msg.sender == address(pool) && initiator == address(this), "invalid flash loan");
+        (address collateral, bytes32 liquidationArg1, bytes32 liquidationArg2, bytes memory swap) =
+            abi.decode(params, (address, bytes32, bytes32, bytes));
+        _liquidationCall(collateral, asset, liquidationArg1, liquidationArg2);
+        if (swap.length > 0) {
+            _swapCollateral(collateral, asset, amount + premium, swap);
+        }
+        return true;
+    }
 
-        (address token0, address token1) = collateral < debt ? (collateral, debt) : (debt, collateral);
-        if (amount0Delta > 0) {
-            ERC20(token0).transfer(msg.sender, uint256(amount0Delta));
-        } else if (amount1Delta > 0) {
-            ERC20(token1).transfer(msg.sender, uint256(amount1Delta));
+    /// @dev Liquidate through the five-argument `liquidationCall` every pool has, L2Pool or not, reading the user,
+    /// amount and receiveAToken out of the args packed as L2Pool takes them
+    function _liquidationCall(address collateral, address debt, bytes32 liquidationArg1, bytes32 liquidationArg2)
+        internal
+    {
+        address user = address(uint160(uint256(liquidationArg1) >> 32));
+        uint256 debtToCover = uint128(uint256(liquidationArg2));
+        if (debtToCover == type(uint128).max) {
+            debtToCover = type(uint256).max;
         }
+        bool receiveAToken = (uint256(liquidationArg2) >> 128) & 1 == 1;
+        IPool(address(pool)).liquidationCall(collateral, debt, user, debtToCover, receiveAToken);
     }
 
     function approvePool(address token) external onlyOwner {
         ERC20(token).approve(address(pool), type(uint256).max);
     }
 
+    /// @notice Approve the pool for every token in one tx, as `approvePool` does for one
+    function approvePoolBatch(address[] calldata tokens) external onlyOwner {
+        for (uint256 i = 0; i < tokens.length; i++) {
+            ERC20(tokens[i]).approve(address(pool), type(uint256).max);
+        }
+    }
+
     function recover(address token, uint256 amount) external onlyOwner {
         if (token == address(0)) {
             payable(msg.sender).transfer(amount);
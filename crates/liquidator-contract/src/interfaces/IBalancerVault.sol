@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+pragma solidity >=0.7.0;
+
+/// @title The parts of the Balancer V2 Vault the Liquidator flash-loans and swaps through
+interface IBalancerVault {
+    enum SwapKind {
+        GIVEN_IN,
+        GIVEN_OUT
+    }
+
+    struct SingleSwap {
+        bytes32 poolId;
+        SwapKind kind;
+        address assetIn;
+        address assetOut;
+        uint256 amount;
+        bytes userData;
+    }
+
+    struct FundManagement {
+        address sender;
+        bool fromInternalBalance;
+        address payable recipient;
+        bool toInternalBalance;
+    }
+
+    /// @notice Performs a flash loan of `amounts` of `tokens` to `recipient`, which is called back on
+    /// `receiveFlashLoan` and must have repaid the loan and its fees to the Vault by the time it returns
+    function flashLoan(address recipient, address[] memory tokens, uint256[] memory amounts, bytes memory userData)
+        external;
+
+    /// @notice Performs a swap with a single pool. With GIVEN_OUT, `limit` is the most `assetIn` sent
+    /// @return amountCalculated The amount of `assetIn` sent with GIVEN_OUT
+    function swap(SingleSwap memory singleSwap, FundManagement memory funds, uint256 limit, uint256 deadline)
+        external
+        payable
+        returns (uint256 amountCalculated);
+}
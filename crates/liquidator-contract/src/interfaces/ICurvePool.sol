@@ -0,0 +1,11 @@
+// SPDX-License-Identifier: MIT
+pragma solidity >=0.5.0;
+
+/// @title The swap of a Curve pool, whose coins are addressed by index
+interface ICurvePool {
+    /// @notice Swap `dx` of coin `i` for at least `min_dy` of coin `j`
+    function exchange(int128 i, int128 j, uint256 dx, uint256 min_dy) external returns (uint256);
+
+    /// @notice The amount of coin `j` swapping `dx` of coin `i` gets
+    function get_dy(int128 i, int128 j, uint256 dx) external view returns (uint256);
+}
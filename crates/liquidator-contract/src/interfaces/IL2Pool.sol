@@ -143,4 +143,16 @@ interface IL2Pool {
             uint256 ltv,
             uint256 healthFactor
         );
+
+    /**
+     * @notice Flash loan of a single asset, the receiver's `executeOperation` paying back the amount plus the
+     * premium through an allowance to the pool
+     */
+    function flashLoanSimple(
+        address receiverAddress,
+        address asset,
+        uint256 amount,
+        bytes calldata params,
+        uint16 referralCode
+    ) external;
 }
@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: GPL-2.0-or-later
+pragma solidity >=0.8.0;
+
+/// @title Functions for manipulating Uniswap V3 path data for multihop swaps
+/// @dev A path is `token, fee, token, fee, ..., token` packed, 20 bytes per token and 3 per fee
+library Path {
+    uint256 private constant ADDR_SIZE = 20;
+    uint256 private constant FEE_SIZE = 3;
+    /// @dev The offset of a single token address and pool fee
+    uint256 private constant NEXT_OFFSET = ADDR_SIZE + FEE_SIZE;
+    /// @dev The offset of an encoded pool key
+    uint256 private constant POP_OFFSET = NEXT_OFFSET + ADDR_SIZE;
+    /// @dev The minimum length of an encoding that contains 2 or more pools
+    uint256 private constant MULTIPLE_POOLS_MIN_LENGTH = POP_OFFSET + NEXT_OFFSET;
+
+    /// @notice Returns true iff the path contains two or more pools
+    function hasMultiplePools(bytes memory path) internal pure returns (bool) {
+        return path.length >= MULTIPLE_POOLS_MIN_LENGTH;
+    }
+
+    /// @notice Returns whether the path is one or more pools, with nothing left over
+    function isValid(bytes memory path) internal pure returns (bool) {
+        return path.length >= POP_OFFSET && (path.length - ADDR_SIZE) % NEXT_OFFSET == 0;
+    }
+
+    /// @notice Decodes the first pool in path
+    function decodeFirstPool(bytes memory path) internal pure returns (address tokenA, address tokenB, uint24 fee) {
+        tokenA = toAddress(path, 0);
+        fee = toUint24(path, ADDR_SIZE);
+        tokenB = toAddress(path, NEXT_OFFSET);
+    }
+
+    /// @notice Returns the first token of the path
+    function firstToken(bytes memory path) internal pure returns (address) {
+        return toAddress(path, 0);
+    }
+
+    /// @notice Returns the last token of the path
+    function lastToken(bytes memory path) internal pure returns (address) {
+        return toAddress(path, path.length - ADDR_SIZE);
+    }
+
+    /// @notice Skips a token + fee element from the path
+    function skipToken(bytes memory path) internal pure returns (bytes memory rest) {
+        uint256 length = path.length - NEXT_OFFSET;
+        rest = new bytes(length);
+        for (uint256 i = 0; i < length; i++) {
+            rest[i] = path[i + NEXT_OFFSET];
+        }
+    }
+
+    function toAddress(bytes memory path, uint256 start) private pure returns (address addr) {
+        require(path.length >= start + ADDR_SIZE, "toAddress_outOfBounds");
+        assembly {
+            addr := shr(96, mload(add(add(path, 0x20), start)))
+        }
+    }
+
+    function toUint24(bytes memory path, uint256 start) private pure returns (uint24 value) {
+        require(path.length >= start + FEE_SIZE, "toUint24_outOfBounds");
+        assembly {
+            value := shr(232, mload(add(add(path, 0x20), start)))
+        }
+    }
+}
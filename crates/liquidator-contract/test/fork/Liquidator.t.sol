@@ -4,10 +4,14 @@ pragma solidity ^0.8.13;
 import {ERC20} from "solmate/tokens/ERC20.sol";
 import {Test, console2} from "forge-std/Test.sol";
 import {MockOracle} from "../util/MockOracle.sol";
+import {MockBalancerPool, IBalancerVaultPools} from "../util/MockBalancerPool.sol";
+import {MockCurvePool} from "../util/MockCurvePool.sol";
+import {MockRouter} from "../util/MockRouter.sol";
 import {Liquidator} from "../../src/Liquidator.sol";
 import {IL2Pool} from "../../src/interfaces/IL2Pool.sol";
 import {IL2Encoder} from "../../src/interfaces/IL2Encoder.sol";
 import {IAaveOracle} from "../../src/interfaces/IAaveOracle.sol";
+import {IBalancerVault} from "../../src/interfaces/IBalancerVault.sol";
 import {IPoolAddressesProvider} from "../../src/interfaces/IAddressesProvider.sol";
 import {IPoolDataProvider} from "../../src/interfaces/IPoolDataProvider.sol";
 import {IQuoterV2} from "../../src/interfaces/IQuoterV2.sol";
@@ -17,7 +21,11 @@ contract LiquidatorTest is Test {
     ERC20 constant ausdc = ERC20(0x0a1d576f3eFeF75b330424287a95A366e8281D54);
     ERC20 constant weth = ERC20(0x4200000000000000000000000000000000000006);
     ERC20 constant usdc = ERC20(0xd9aAEc86B65D86f6A7B5B1b0c42FFA531710b6CA);
+    // native USDC, the hop of the multi-pool routes between USDbC and WETH
+    ERC20 constant nativeUsdc = ERC20(0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913);
     IQuoterV2 constant quoter = IQuoterV2(0x3d4e44Eb1374240CE5F1B871ab261CD16335B76a);
+    IL2Pool constant aavePool = IL2Pool(0x8F44Fd754285aa6A2b8B9B97739B79746e0475a7);
+    address constant uniswapV3Factory = 0x33128a8fC17869897dcE68Ed026d694621f6FDfD;
     IL2Encoder constant encoder = IL2Encoder(0x39e97c588B2907Fb67F44fea256Ae3BA064207C5);
     // IAaveOracle constant oracle = IAaveOracle(0x2Cc0Fc26eD4563A5ce5e8bdcfe1A2878676Ae156);
     IPoolDataProvider constant dataProvider = IPoolDataProvider(0x2d8A3C5677189723C4cB8873CfC9C8976FDF38Ac);
@@ -35,7 +43,7 @@ contract LiquidatorTest is Test {
         vm.createSelectFork(vm.envString("FORK_URL"));
         oracle = new MockOracle();
         user = makeAddr("user");
-        liquidator = new Liquidator();
+        liquidator = new Liquidator(aavePool, uniswapV3Factory);
         liquidator.approvePool(address(weth));
         liquidator.approvePool(address(usdc));
         pool = liquidator.pool();
@@ -146,6 +154,289 @@ contract LiquidatorTest is Test {
         assertEq(usdc.balanceOf(address(liquidator)), expectedGain);
     }
 
+    function testLiquidateBatchLiquidatesEachLeg() public {
+        openUnhealthyPosition();
+        uint256 debtBefore = variableDebt(address(usdc));
+
+        Liquidator.Leg[] memory legs = new Liquidator.Leg[](2);
+        legs[0] = usdcLeg(100 * usdcUnit);
+        legs[1] = usdcLeg(100 * usdcUnit);
+        int256[] memory gains = liquidator.liquidateBatch(legs, user);
+
+        // covering a tenth of the debt leaves the user under water, so the second leg runs too
+        assertGt(gains[0], 0);
+        assertGt(gains[1], 0);
+        assertEq(weth.balanceOf(address(liquidator)), uint256(gains[0] + gains[1]));
+        assertEq(variableDebt(address(usdc)), debtBefore - 200 * usdcUnit);
+    }
+
+    function testLiquidateBatchStopsOnceHealthy() public {
+        openUnhealthyPosition();
+        uint256 debt = variableDebt(address(usdc));
+
+        Liquidator.Leg[] memory legs = new Liquidator.Leg[](2);
+        legs[0] = usdcLeg(debt);
+        legs[1] = usdcLeg(usdcUnit);
+        int256[] memory gains = liquidator.liquidateBatch(legs, user);
+
+        // the first leg repays all the debt, so the health factor checked before the second skips it
+        (,,,,, uint256 healthFactor) = pool.getUserAccountData(user);
+        assertGe(healthFactor, 1e18);
+        assertGt(gains[0], 0);
+        assertEq(gains[1], 0);
+        assertEq(variableDebt(address(usdc)), 0);
+    }
+
+    function testLiquidationSameAsset() public {
+        vm.startPrank(user);
+        weth.approve(address(pool), type(uint256).max);
+        usdc.approve(address(pool), type(uint256).max);
+        pool.supply(encoder.encodeSupplyParams(address(weth), 1 ether, 0));
+        pool.supply(encoder.encodeSupplyParams(address(usdc), 2000 * usdcUnit, 0));
+        pool.borrow(encoder.encodeBorrowParams(address(weth), 1.5 ether, 2, 0));
+        vm.stopPrank();
+        // the USDC collateral losing its value puts the WETH borrowed against WETH under water
+        oracle.setAssetPrice(address(usdc), 0.1 ether);
+        uint256 debtBefore = variableDebt(address(weth));
+
+        // no Uniswap pool pairs WETH with itself, so the pool flash-loans it and calls executeOperation
+        uint256 debtToCover = 0.5 ether;
+        (bytes32 arg1, bytes32 arg2) =
+            encoder.encodeLiquidationCall(address(weth), address(weth), user, debtToCover, false);
+        int256 gain = liquidator.liquidate(address(weth), address(weth), 0, debtToCover, arg1, arg2);
+
+        // the bonus pays for the loan's premium
+        assertGt(gain, 0);
+        assertEq(weth.balanceOf(address(liquidator)), uint256(gain));
+        assertEq(variableDebt(address(weth)), debtBefore - debtToCover);
+    }
+
+    function testExecuteOperationOnlyFromOwnFlashLoans() public {
+        vm.expectRevert("invalid flash loan");
+        liquidator.executeOperation(address(weth), 1 ether, 0, address(liquidator), "");
+
+        // a flash loan to the liquidator someone else started
+        vm.expectRevert("invalid flash loan");
+        pool.flashLoanSimple(address(liquidator), address(weth), 1 ether, "", 0);
+    }
+
+    function testLiquidatePathMultiHop() public {
+        openUnhealthyPosition();
+        uint256 debtBefore = variableDebt(address(usdc));
+
+        // USDbC out of the stable pool with native USDC, itself bought with WETH
+        uint256 debtToCover = 500 * usdcUnit;
+        bytes memory path =
+            abi.encodePacked(address(usdc), uint24(100), address(nativeUsdc), uint24(500), address(weth));
+        (uint256 wethRequired,,,) = quoter.quoteExactOutput(path, debtToCover);
+        uint256 collateralBefore = aweth.balanceOf(user);
+
+        (bytes32 arg1, bytes32 arg2) =
+            encoder.encodeLiquidationCall(address(weth), address(usdc), user, debtToCover, false);
+        int256 gain = liquidator.liquidatePath(address(weth), address(usdc), path, debtToCover, arg1, arg2);
+
+        assertGt(gain, 0);
+        assertEq(weth.balanceOf(address(liquidator)), uint256(gain));
+        // the seized collateral paid the first pool of the route, and no hop token is left over
+        assertLe(uint256(gain) + wethRequired, collateralBefore - aweth.balanceOf(user));
+        assertEq(nativeUsdc.balanceOf(address(liquidator)), 0);
+        assertEq(variableDebt(address(usdc)), debtBefore - debtToCover);
+    }
+
+    function testLiquidatePathRejectsInvalidPath() public {
+        vm.expectRevert("invalid path");
+        liquidator.liquidatePath(
+            address(weth), address(usdc), abi.encodePacked(address(usdc), uint24(500)), 1, bytes32(0), bytes32(0)
+        );
+
+        // a route not ending in the collateral
+        vm.expectRevert("invalid path");
+        liquidator.liquidatePath(
+            address(weth),
+            address(usdc),
+            abi.encodePacked(address(usdc), uint24(100), address(nativeUsdc)),
+            1,
+            bytes32(0),
+            bytes32(0)
+        );
+    }
+
+    function testSwapCallbackOnlyFromPools() public {
+        bytes memory path = abi.encodePacked(address(usdc), uint24(500), address(weth));
+        vm.expectRevert("invalid pool");
+        liquidator.uniswapV3SwapCallback(1, -1, abi.encode(path, address(usdc), bytes32(0), bytes32(0)));
+    }
+
+    function testLiquidateFlashLoanRepaidOnBalancer() public {
+        openUnhealthyPosition();
+        uint256 debtBefore = variableDebt(address(usdc));
+        MockBalancerPool balancerPool = new MockBalancerPool(
+            IBalancerVaultPools(address(liquidator.balancerVault())), address(weth), address(usdc)
+        );
+        balancerPool.setRate(address(weth), address(usdc), wethRate());
+        deal(address(weth), address(balancerPool), 10 ether);
+        deal(address(usdc), address(balancerPool), 10000 * usdcUnit);
+        // in the pool's token order, WETH first
+        uint256[] memory amounts = new uint256[](2);
+        amounts[0] = 10 ether;
+        amounts[1] = 10000 * usdcUnit;
+        balancerPool.join(amounts);
+        uint256 reserveBefore = usdc.balanceOf(address(ausdc));
+
+        uint256 debtToCover = 500 * usdcUnit;
+        bytes memory swap =
+            abi.encode(Liquidator.FlashSwapVenue.BALANCER, abi.encode(balancerPool.poolId(), uint256(1 ether)));
+        int256 gain = flashLiquidate(debtToCover, swap);
+
+        // the Aave pool lent the debt, and the Vault swap got exactly the loan and its premium back
+        assertGt(gain, 0);
+        assertEq(weth.balanceOf(address(liquidator)), uint256(gain));
+        assertEq(usdc.balanceOf(address(liquidator)), 0);
+        assertGe(usdc.balanceOf(address(ausdc)), reserveBefore + debtToCover);
+        assertEq(variableDebt(address(usdc)), debtBefore - debtToCover);
+    }
+
+    function testLiquidateFlashLoanRepaidOnCurve() public {
+        openUnhealthyPosition();
+        uint256 debtBefore = variableDebt(address(usdc));
+        address[] memory coins = new address[](2);
+        coins[0] = address(weth);
+        coins[1] = address(usdc);
+        MockCurvePool curvePool = new MockCurvePool(coins);
+        curvePool.setRate(0, 1, wethRate());
+        deal(address(usdc), address(curvePool), 10000 * usdcUnit);
+
+        // Curve swaps exact inputs, so a bit more collateral than the loan needs is sold
+        uint256 debtToCover = 500 * usdcUnit;
+        uint256 amountIn = debtToCover * 1 ether / wethRate() * 101 / 100;
+        bytes memory swap = abi.encode(
+            Liquidator.FlashSwapVenue.CURVE, abi.encode(address(curvePool), int128(0), int128(1), amountIn)
+        );
+        uint256 vaultBefore = fundVault(debtToCover);
+        int256 gain = flashLiquidate(debtToCover, swap);
+
+        // the Vault got its loan back, and what the swap got over it stays in the liquidator
+        assertGt(gain, 0);
+        assertGe(usdc.balanceOf(address(liquidator.balancerVault())), vaultBefore);
+        assertGt(usdc.balanceOf(address(liquidator)), 0);
+        assertEq(variableDebt(address(usdc)), debtBefore - debtToCover);
+    }
+
+    function testLiquidateFlashLoanRepaidByAggregator() public {
+        openUnhealthyPosition();
+        uint256 debtBefore = variableDebt(address(usdc));
+        MockRouter router = new MockRouter();
+        deal(address(usdc), address(router), 10000 * usdcUnit);
+
+        uint256 debtToCover = 500 * usdcUnit;
+        uint256 amountIn = debtToCover * 1 ether / wethRate() * 101 / 100;
+        bytes memory data = abi.encodeCall(MockRouter.swap, (weth, usdc, amountIn, debtToCover));
+        bytes memory swap = abi.encode(
+            Liquidator.FlashSwapVenue.AGGREGATOR, abi.encode(address(router), address(router), amountIn, data)
+        );
+        uint256 vaultBefore = fundVault(debtToCover);
+        int256 gain = flashLiquidate(debtToCover, swap);
+
+        assertGt(gain, 0);
+        assertGe(usdc.balanceOf(address(liquidator.balancerVault())), vaultBefore);
+        assertEq(usdc.balanceOf(address(liquidator)), 0);
+        // the router can't pull anything more
+        assertEq(weth.allowance(address(liquidator), address(router)), 0);
+        assertEq(variableDebt(address(usdc)), debtBefore - debtToCover);
+    }
+
+    function testLiquidateFlashLoanRevertsOnShortAggregatorSwap() public {
+        openUnhealthyPosition();
+        MockRouter router = new MockRouter();
+        deal(address(usdc), address(router), 10000 * usdcUnit);
+
+        uint256 debtToCover = 500 * usdcUnit;
+        uint256 amountIn = debtToCover * 1 ether / wethRate();
+        bytes memory data = abi.encodeCall(MockRouter.swap, (weth, usdc, amountIn, debtToCover - 1));
+        bytes memory swap = abi.encode(
+            Liquidator.FlashSwapVenue.AGGREGATOR, abi.encode(address(router), address(router), amountIn, data)
+        );
+        fundVault(debtToCover);
+        (bytes32 arg1, bytes32 arg2) =
+            encoder.encodeLiquidationCall(address(weth), address(usdc), user, debtToCover, false);
+
+        vm.expectRevert("aggregator swap short");
+        liquidator.liquidateFlashLoan(address(weth), address(usdc), debtToCover, arg1, arg2, swap);
+    }
+
+    function testReceiveFlashLoanOnlyFromOwnFlashLoans() public {
+        address[] memory tokens = new address[](1);
+        tokens[0] = address(usdc);
+        uint256[] memory amounts = new uint256[](1);
+        amounts[0] = usdcUnit;
+        bytes memory userData =
+            abi.encode(address(weth), bytes32(0), bytes32(0), abi.encode(Liquidator.FlashSwapVenue.CURVE, bytes("")));
+
+        vm.expectRevert("invalid flash loan");
+        liquidator.receiveFlashLoan(tokens, amounts, new uint256[](1), userData);
+
+        // the Vault calling back on a flash loan to the liquidator someone else started
+        IBalancerVault vault = liquidator.balancerVault();
+        fundVault(usdcUnit);
+        vm.expectRevert("invalid flash loan");
+        vault.flashLoan(address(liquidator), tokens, amounts, userData);
+    }
+
+    function testLiquidationsOnlyByOwner() public {
+        vm.startPrank(user);
+        vm.expectRevert("UNAUTHORIZED");
+        liquidator.liquidate(address(weth), address(usdc), 500, 1, bytes32(0), bytes32(0));
+        vm.expectRevert("UNAUTHORIZED");
+        liquidator.liquidatePath(address(weth), address(usdc), "", 1, bytes32(0), bytes32(0));
+        vm.expectRevert("UNAUTHORIZED");
+        liquidator.liquidateFlashLoan(address(weth), address(usdc), 1, bytes32(0), bytes32(0), "");
+        vm.expectRevert("UNAUTHORIZED");
+        liquidator.liquidateBatch(new Liquidator.Leg[](0), user);
+        vm.stopPrank();
+    }
+
+    // 1 WETH backing 1000 USDC, the WETH price then lowered for a 0.94 health factor, under the 0.95 threshold
+    // of the full close factor but high enough that all of the debt can be liquidated
+    function openUnhealthyPosition() internal {
+        vm.startPrank(user);
+        weth.approve(address(pool), type(uint256).max);
+        pool.supply(encoder.encodeSupplyParams(address(weth), 1 ether, 0));
+        pool.borrow(encoder.encodeBorrowParams(address(usdc), 1000 * usdcUnit, 2, 0));
+        vm.stopPrank();
+        (,, uint256 liquidationThreshold,,,,,,,) = dataProvider.getReserveConfigurationData(address(weth));
+        oracle.setAssetPrice(address(weth), (1000 ether * 9400) / liquidationThreshold);
+    }
+
+    // a WETH collateral, USDC debt leg of the batch
+    function usdcLeg(uint256 debtToCover) internal view returns (Liquidator.Leg memory) {
+        (bytes32 arg1, bytes32 arg2) =
+            encoder.encodeLiquidationCall(address(weth), address(usdc), user, debtToCover, false);
+        return Liquidator.Leg(address(weth), address(usdc), 500, debtToCover, arg1, arg2);
+    }
+
+    function variableDebt(address asset) internal view returns (uint256 debt) {
+        (,, debt,,,,,,) = dataProvider.getUserReserveData(asset, user);
+    }
+
+    // liquidate `debtToCover` of the user's USDC debt through a flash loan repaid as `swap` says
+    function flashLiquidate(uint256 debtToCover, bytes memory swap) internal returns (int256) {
+        (bytes32 arg1, bytes32 arg2) =
+            encoder.encodeLiquidationCall(address(weth), address(usdc), user, debtToCover, false);
+        return liquidator.liquidateFlashLoan(address(weth), address(usdc), debtToCover, arg1, arg2, swap);
+    }
+
+    // USDC paid for 1e18 of WETH at the oracle price
+    function wethRate() internal view returns (uint256) {
+        return oracle.getAssetPrice(address(weth)) * usdcUnit / 1 ether;
+    }
+
+    // the Vault lends what it holds, so make sure it holds `amount` of USDC more, returning its balance
+    function fundVault(uint256 amount) internal returns (uint256 balance) {
+        address vault = address(liquidator.balancerVault());
+        balance = usdc.balanceOf(vault) + amount;
+        deal(address(usdc), vault, balance);
+    }
+
     function percentMul(uint256 a, uint256 bps) internal pure returns (uint256) {
         return (5000 + (a * bps)) / 10000;
     }
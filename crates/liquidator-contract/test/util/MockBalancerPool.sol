@@ -0,0 +1,89 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.13;
+
+import {ERC20} from "solmate/tokens/ERC20.sol";
+
+/// @dev The parts of the Balancer V2 Vault registering and funding a pool
+interface IBalancerVaultPools {
+    struct JoinPoolRequest {
+        address[] assets;
+        uint256[] maxAmountsIn;
+        bytes userData;
+        bool fromInternalBalance;
+    }
+
+    function registerPool(uint8 specialization) external returns (bytes32);
+
+    function registerTokens(bytes32 poolId, address[] memory tokens, address[] memory assetManagers) external;
+
+    function joinPool(bytes32 poolId, address sender, address recipient, JoinPoolRequest memory request)
+        external
+        payable;
+}
+
+/// @dev A two-token Balancer pool swapping at fixed rates, registered with the Vault so swaps go through it
+contract MockBalancerPool {
+    struct SwapRequest {
+        uint8 kind;
+        address tokenIn;
+        address tokenOut;
+        uint256 amount;
+        bytes32 poolId;
+        uint256 lastChangeBlock;
+        address from;
+        address to;
+        bytes userData;
+    }
+
+    uint8 constant MINIMAL_SWAP_INFO = 1;
+    uint8 constant GIVEN_IN = 0;
+
+    IBalancerVaultPools immutable vault;
+    bytes32 public immutable poolId;
+    address[] tokens;
+    // tokenIn => tokenOut => tokenOut paid for 1e18 of tokenIn
+    mapping(address => mapping(address => uint256)) internal rates;
+
+    constructor(IBalancerVaultPools _vault, address tokenA, address tokenB) {
+        vault = _vault;
+        bytes32 id = _vault.registerPool(MINIMAL_SWAP_INFO);
+        poolId = id;
+        tokens = new address[](2);
+        (tokens[0], tokens[1]) = tokenA < tokenB ? (tokenA, tokenB) : (tokenB, tokenA);
+        _vault.registerTokens(id, tokens, new address[](2));
+    }
+
+    function setRate(address tokenIn, address tokenOut, uint256 rate) external {
+        rates[tokenIn][tokenOut] = rate;
+    }
+
+    /// @dev Move the `amounts` of the pool's sorted tokens this contract holds into the Vault
+    function join(uint256[] memory amounts) external {
+        for (uint256 i = 0; i < tokens.length; i++) {
+            ERC20(tokens[i]).approve(address(vault), amounts[i]);
+        }
+        vault.joinPool(
+            poolId,
+            address(this),
+            address(this),
+            IBalancerVaultPools.JoinPoolRequest(tokens, amounts, abi.encode(amounts), false)
+        );
+    }
+
+    function onJoinPool(bytes32, address, address, uint256[] memory, uint256, uint256, bytes memory userData)
+        external
+        view
+        returns (uint256[] memory amountsIn, uint256[] memory dueProtocolFeeAmounts)
+    {
+        amountsIn = abi.decode(userData, (uint256[]));
+        dueProtocolFeeAmounts = new uint256[](tokens.length);
+    }
+
+    function onSwap(SwapRequest memory request, uint256, uint256) external view returns (uint256) {
+        uint256 rate = rates[request.tokenIn][request.tokenOut];
+        if (request.kind == GIVEN_IN) {
+            return request.amount * rate / 1e18;
+        }
+        return (request.amount * 1e18 + rate - 1) / rate;
+    }
+}
@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.13;
+
+import {ERC20} from "solmate/tokens/ERC20.sol";
+
+/// @dev A Curve pool swapping its coins at fixed rates, out of the balances dealt to it
+contract MockCurvePool {
+    address[] public coins;
+    // i => j => coin j paid for 1e18 of coin i
+    mapping(int128 => mapping(int128 => uint256)) internal rates;
+
+    constructor(address[] memory _coins) {
+        coins = _coins;
+    }
+
+    function setRate(int128 i, int128 j, uint256 rate) external {
+        rates[i][j] = rate;
+    }
+
+    function get_dy(int128 i, int128 j, uint256 dx) public view returns (uint256) {
+        return dx * rates[i][j] / 1e18;
+    }
+
+    function exchange(int128 i, int128 j, uint256 dx, uint256 min_dy) external returns (uint256 dy) {
+        dy = get_dy(i, j, dx);
+        require(dy >= min_dy, "Exchange resulted in fewer coins than expected");
+        ERC20(coins[uint128(i)]).transferFrom(msg.sender, address(this), dx);
+        ERC20(coins[uint128(j)]).transfer(msg.sender, dy);
+    }
+}
@@ -0,0 +1,12 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.13;
+
+import {ERC20} from "solmate/tokens/ERC20.sol";
+
+/// @dev An aggregator router filling its calldata's swap out of the balances dealt to it
+contract MockRouter {
+    function swap(ERC20 tokenIn, ERC20 tokenOut, uint256 amountIn, uint256 amountOut) external {
+        tokenIn.transferFrom(msg.sender, address(this), amountIn);
+        tokenOut.transfer(msg.sender, amountOut);
+    }
+}
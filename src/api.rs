@@ -0,0 +1,560 @@
+use crate::circuit_breaker::BreakerStatus;
+use crate::control::{ControlCommand, ControlHandle, ControlRequest, SubmitResult};
+use crate::exposure::ExposureStatus;
+use crate::kill_switch::KillSwitch;
+use crate::liveness::Liveness;
+use crate::strategies::aave_strategy::Borrower;
+use crate::strategies::approval::PendingApproval;
+use crate::strategies::competitors::CompetitorReport;
+use crate::strategies::health_histogram::HealthFactorHistogram;
+use crate::strategies::sync_progress::SyncProgress;
+use crate::strategies::types::TickSummary;
+use crate::strategies::wallet::WalletHealth;
+use crate::tunables::Tunables;
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::iter::zip;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+/// Number of recent opportunities kept for inspection.
+pub const MAX_RECENT_OPPORTUNITIES: usize = 100;
+
+/// What the strategy last saw, published for the admin API.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StrategyStatus {
+    pub paused: bool,
+    pub last_block_number: u64,
+    /// Unix time of the last state update, 0 until the first sync.
+    pub last_update_timestamp: u64,
+    /// Known borrowers, only serialized when `/borrowers` is requested.
+    #[serde(skip)]
+    pub borrowers: Arc<HashMap<Address, Borrower>>,
+    /// Borrowers under water in the last scan, with their health factor.
+    pub underwater: Vec<(Address, U256)>,
+    /// Most recent opportunities first.
+    pub opportunities: Vec<OpportunityStatus>,
+    pub config: Value,
+    /// Summary of the last full scan tick.
+    pub last_tick: Option<TickSummary>,
+    /// Unix time the last full scan tick that updated the state and scanned the borrowers
+    /// started at, 0 until one does.
+    pub last_successful_tick_at: u64,
+    /// The L2 sequencer is down or in its grace period.
+    pub sequencer_down: bool,
+    /// Borrowers left with bad debt in the last scan, not evaluated any further.
+    pub bad_debt: Vec<BadDebtStatus>,
+    /// Liquidations won and lost against competitors.
+    pub competitors: CompetitorReport,
+    /// Outcome of the last check of the sender's wallet.
+    pub wallet: WalletHealth,
+    /// Whether the circuit breaker holds submission, and why.
+    pub circuit_breaker: BreakerStatus,
+    /// Debt committed to liquidations in flight, and its cap.
+    pub exposure: ExposureStatus,
+    /// Borrowers of the last full scan bucketed by health factor.
+    pub health_factors: HealthFactorHistogram,
+    /// Liquidations waiting for an operator's approval, the longest waiting first.
+    pub pending_approvals: Vec<PendingApproval>,
+    /// Progress of the last state update, of the initial sync until it's done.
+    pub sync: SyncProgress,
+}
+
+/// An opportunity the strategy considered for submission.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpportunityStatus {
+    pub correlation_id: String,
+    pub timestamp: u64,
+    pub borrower: Address,
+    pub collateral: Address,
+    pub debt: Address,
+    pub debt_to_cover: U256,
+    pub profit_eth: String,
+    /// In units of the profit currency, none without its price.
+    pub profit: Option<String>,
+    pub confidence: u64,
+}
+
+/// An insolvent borrower whose collateral can't pay for their liquidation.
+#[derive(Debug, Clone, Serialize)]
+pub struct BadDebtStatus {
+    pub borrower: Address,
+    /// In the pool's base currency.
+    pub collateral_base: U256,
+    /// In the pool's base currency.
+    pub debt_base: U256,
+    /// Block the borrower was first found with bad debt at.
+    pub since_block: u64,
+}
+
+pub type SharedStatus = Arc<RwLock<StrategyStatus>>;
+
+impl StrategyStatus {
+    pub fn push_opportunity(&mut self, opportunity: OpportunityStatus) {
+        self.opportunities.insert(0, opportunity);
+        self.opportunities.truncate(MAX_RECENT_OPPORTUNITIES);
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    status: SharedStatus,
+    liveness: Liveness,
+    control: ControlHandle,
+    kill_switch: KillSwitch,
+    token: Option<String>,
+}
+
+/// Who sends a command, and the key making it safe to retry.
+#[derive(Debug, Deserialize)]
+struct CommandRequest {
+    idempotency_key: String,
+    actor: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MinProfitRequest {
+    #[serde(flatten)]
+    request: CommandRequest,
+    min_profit_eth: U256,
+}
+
+#[derive(Debug, Deserialize)]
+struct KillSwitchRequest {
+    #[serde(flatten)]
+    request: CommandRequest,
+    engaged: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BorrowerRequest {
+    #[serde(flatten)]
+    request: CommandRequest,
+    borrower: Address,
+}
+
+#[derive(Debug, Deserialize)]
+struct LiquidateRequest {
+    #[serde(flatten)]
+    request: CommandRequest,
+    borrower: Address,
+    collateral: Address,
+    debt: Address,
+    debt_to_cover: Option<U256>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TunablesRequest {
+    #[serde(flatten)]
+    request: CommandRequest,
+    #[serde(flatten)]
+    tunables: Tunables,
+}
+
+enum ApiError {
+    Unauthorized,
+    Unavailable,
+    BadRequest(anyhow::Error),
+    Internal(anyhow::Error),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "unauthorized".to_string()),
+            ApiError::Unavailable => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "status unavailable".to_string(),
+            ),
+            ApiError::BadRequest(e) => (StatusCode::BAD_REQUEST, e.to_string()),
+            ApiError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+type ApiResult = std::result::Result<Response, ApiError>;
+
+/// Serve the admin API until the process exits.
+pub async fn serve(
+    address: SocketAddr,
+    status: SharedStatus,
+    liveness: Liveness,
+    control: ControlHandle,
+    kill_switch: KillSwitch,
+    token: Option<String>,
+) -> Result<()> {
+    let state = ApiState {
+        status,
+        liveness,
+        control,
+        kill_switch,
+        token,
+    };
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/health", get(health))
+        .route("/borrowers", get(borrowers))
+        .route("/underwater", get(underwater))
+        .route("/opportunities", get(opportunities))
+        .route("/bad-debt", get(bad_debt))
+        .route("/health-factors", get(health_factors))
+        .route("/competitors", get(competitors))
+        .route("/wallet", get(wallet))
+        .route("/circuit-breaker", get(circuit_breaker))
+        .route("/approvals", get(approvals))
+        .route("/exposure", get(exposure))
+        .route("/kill-switch", get(kill_switch).post(set_kill_switch))
+        .route("/config", get(config))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/resync", post(resync))
+        .route("/min-profit", post(min_profit))
+        .route("/tunables", post(tunables))
+        .route("/approve", post(approve))
+        .route("/reject", post(reject))
+        .route("/liquidate", post(liquidate))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(address).await?;
+    info!("Admin API listening on {}", address);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+/// Refuse to serve the APIs beyond the host without a token, anyone reaching the port
+/// could pause the bot or read its positions.
+pub(crate) fn check_listen_address(address: SocketAddr, token: Option<&str>) -> Result<()> {
+    if token.is_none() && !address.ip().is_loopback() {
+        return Err(anyhow!(
+            "Refusing to listen on {} without --api-token, only loopback addresses are served without one",
+            address
+        ));
+    }
+    Ok(())
+}
+
+// a bearer token, if configured, is required on every route
+pub(crate) fn is_authorized(token: Option<&str>, authorization: Option<&str>) -> bool {
+    match token {
+        Some(token) => authorization
+            .and_then(|a| a.strip_prefix("Bearer "))
+            .is_some_and(|given| constant_time_eq(given.as_bytes(), token.as_bytes())),
+        None => true,
+    }
+}
+
+// compare without returning at the first differing byte, so response times don't leak
+// how much of a guessed token is right
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && zip(a, b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// read the status after checking the caller
+fn read_status<T>(
+    state: &ApiState,
+    headers: &HeaderMap,
+    f: impl FnOnce(&StrategyStatus) -> T,
+) -> std::result::Result<T, ApiError> {
+    let authorization = headers.get(AUTHORIZATION).and_then(|a| a.to_str().ok());
+    if !is_authorized(state.token.as_deref(), authorization) {
+        return Err(ApiError::Unauthorized);
+    }
+    let status = state.status.read().map_err(|_| ApiError::Unavailable)?;
+    Ok(f(&status))
+}
+
+async fn health(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    let (last_block_number, last_update_timestamp, paused, sequencer_down, last_tick, sync) =
+        read_status(&state, &headers, |s| {
+            (
+                s.last_block_number,
+                s.last_update_timestamp,
+                s.paused,
+                s.sequencer_down,
+                s.last_tick.clone(),
+                s.sync.clone(),
+            )
+        })?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Invalid timestamp")
+        .as_secs();
+    let body = Json(json!({
+        "synced": last_update_timestamp > 0,
+        "paused": paused,
+        "sequencer_down": sequencer_down,
+        "last_block_number": last_block_number,
+        "seconds_since_update": now.saturating_sub(last_update_timestamp),
+        "last_tick": last_tick,
+        "sync": sync,
+    }));
+    // not ready until the strategy has synced its state
+    if last_update_timestamp == 0 {
+        return Ok((StatusCode::SERVICE_UNAVAILABLE, body).into_response());
+    }
+    Ok(body.into_response())
+}
+
+// open to liveness probes without the token, it shows nothing that isn't on chain
+async fn healthz(State(state): State<ApiState>) -> Response {
+    let report = state.liveness.report();
+    let status = if report.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report)).into_response()
+}
+
+async fn borrowers(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    // serialized outside the lock, the strategy replaces the map rather than changing it
+    let borrowers = read_status(&state, &headers, |s| s.borrowers.clone())?;
+    Ok(Json(&*borrowers).into_response())
+}
+
+async fn underwater(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.underwater.clone())?).into_response())
+}
+
+async fn opportunities(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.opportunities.clone())?).into_response())
+}
+
+async fn bad_debt(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    let positions = read_status(&state, &headers, |s| s.bad_debt.clone())?;
+    let (collateral_base, debt_base) = positions
+        .iter()
+        .fold((U256::zero(), U256::zero()), |(collateral, debt), p| {
+            (collateral + p.collateral_base, debt + p.debt_base)
+        });
+    Ok(Json(json!({
+        "count": positions.len(),
+        "collateral_base": collateral_base,
+        "debt_base": debt_base,
+        "positions": positions,
+    }))
+    .into_response())
+}
+
+async fn competitors(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.competitors.clone())?).into_response())
+}
+
+async fn health_factors(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.health_factors.clone())?).into_response())
+}
+
+async fn wallet(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.wallet.clone())?).into_response())
+}
+
+async fn circuit_breaker(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| {
+        s.circuit_breaker.clone()
+    })?)
+    .into_response())
+}
+
+async fn exposure(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.exposure.clone())?).into_response())
+}
+
+async fn kill_switch(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    read_status(&state, &headers, |_| ())?;
+    Ok(Json(state.kill_switch.status()).into_response())
+}
+
+async fn approvals(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| {
+        s.pending_approvals.clone()
+    })?)
+    .into_response())
+}
+
+async fn config(State(state): State<ApiState>, headers: HeaderMap) -> ApiResult {
+    Ok(Json(read_status(&state, &headers, |s| s.config.clone())?).into_response())
+}
+
+// forward a command through the audited control handle
+fn submit(
+    state: &ApiState,
+    headers: &HeaderMap,
+    request: CommandRequest,
+    command: ControlCommand,
+) -> ApiResult {
+    read_status(state, headers, |_| ())?;
+    let result = state
+        .control
+        .submit(ControlRequest {
+            idempotency_key: request.idempotency_key,
+            actor: request.actor,
+            command,
+        })
+        .map_err(ApiError::Internal)?;
+    Ok(match result {
+        SubmitResult::Accepted(entry) => (StatusCode::ACCEPTED, Json(entry)).into_response(),
+        SubmitResult::Replayed(entry) => (StatusCode::OK, Json(entry)).into_response(),
+    })
+}
+
+async fn pause(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<CommandRequest>,
+) -> ApiResult {
+    submit(&state, &headers, request, ControlCommand::Pause)
+}
+
+async fn resume(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<CommandRequest>,
+) -> ApiResult {
+    submit(&state, &headers, request, ControlCommand::Resume)
+}
+
+async fn resync(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<CommandRequest>,
+) -> ApiResult {
+    submit(&state, &headers, request, ControlCommand::Resync)
+}
+
+async fn min_profit(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<MinProfitRequest>,
+) -> ApiResult {
+    submit(
+        &state,
+        &headers,
+        request.request,
+        ControlCommand::SetMinProfit {
+            min_profit_eth: request.min_profit_eth,
+        },
+    )
+}
+
+async fn tunables(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<TunablesRequest>,
+) -> ApiResult {
+    request.tunables.validate().map_err(ApiError::BadRequest)?;
+    submit(
+        &state,
+        &headers,
+        request.request,
+        ControlCommand::SetTunables {
+            tunables: request.tunables,
+        },
+    )
+}
+
+// engaged or released right away, not once the strategy gets to the command
+async fn set_kill_switch(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<KillSwitchRequest>,
+) -> ApiResult {
+    let engaged = request.engaged;
+    let response = submit(
+        &state,
+        &headers,
+        request.request,
+        ControlCommand::SetKillSwitch { engaged },
+    )?;
+    // a replayed command isn't executed again
+    if response.status() == StatusCode::ACCEPTED {
+        state.kill_switch.set_engaged(engaged);
+    }
+    Ok(response)
+}
+
+async fn approve(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<BorrowerRequest>,
+) -> ApiResult {
+    submit(
+        &state,
+        &headers,
+        request.request,
+        ControlCommand::Approve {
+            borrower: request.borrower,
+        },
+    )
+}
+
+async fn reject(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<BorrowerRequest>,
+) -> ApiResult {
+    submit(
+        &state,
+        &headers,
+        request.request,
+        ControlCommand::Reject {
+            borrower: request.borrower,
+        },
+    )
+}
+
+async fn liquidate(
+    State(state): State<ApiState>,
+    headers: HeaderMap,
+    Json(request): Json<LiquidateRequest>,
+) -> ApiResult {
+    submit(
+        &state,
+        &headers,
+        request.request,
+        ControlCommand::Liquidate {
+            borrower: request.borrower,
+            collateral: request.collateral,
+            debt: request.debt,
+            debt_to_cover: request.debt_to_cover,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(None, None));
+        assert!(is_authorized(Some("secret"), Some("Bearer secret")));
+        assert!(!is_authorized(Some("secret"), Some("Bearer other")));
+        assert!(!is_authorized(Some("secret"), Some("secret")));
+        assert!(!is_authorized(Some("secret"), None));
+        assert!(!is_authorized(Some("secret"), Some("Bearer secret2")));
+        assert!(!is_authorized(Some("secret"), Some("Bearer ")));
+    }
+
+    #[test]
+    fn test_check_listen_address() {
+        let loopback: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let public: SocketAddr = "0.0.0.0:8080".parse().unwrap();
+        assert!(check_listen_address(loopback, None).is_ok());
+        assert!(check_listen_address(public, None).is_err());
+        assert!(check_listen_address(public, Some("secret")).is_ok());
+        assert!(check_listen_address("[::1]:8080".parse().unwrap(), None).is_ok());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::history::LiquidationStatus;
+use ethers::types::{Address, I256, U256};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Limits past which submission stops, each disabled if unset.
+#[derive(Debug, Clone, Default)]
+pub struct BreakerLimits {
+    /// Liquidations reverting in a row.
+    pub max_consecutive_reverts: Option<u32>,
+    /// Landed liquidations losing money in a row.
+    pub max_consecutive_losses: Option<u32>,
+    /// Most an oracle price may deviate from the reference oracle's, in bps.
+    pub max_price_deviation_bps: Option<u64>,
+    /// Submission resumes on its own this long after tripping, only by an operator if unset.
+    pub cooldown_secs: Option<u64>,
+}
+
+/// State of the breaker, published for the admin API.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BreakerStatus {
+    pub tripped: bool,
+    /// Why it last tripped.
+    pub reason: Option<String>,
+    /// Unix time it last tripped at.
+    pub tripped_at: Option<u64>,
+    pub consecutive_reverts: u32,
+    pub consecutive_losses: u32,
+}
+
+/// Stops submission, while scanning goes on, once liquidations keep failing or losing
+/// money or oracle prices can't be trusted. Shared between the executor settling txs and
+/// the strategy deciding whether to submit.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+    limits: BreakerLimits,
+    status: Arc<Mutex<BreakerStatus>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(limits: BreakerLimits) -> Self {
+        Self {
+            limits,
+            status: Arc::new(Mutex::new(BreakerStatus::default())),
+        }
+    }
+
+    pub fn limits(&self) -> &BreakerLimits {
+        &self.limits
+    }
+
+    /// Count the outcome of a settled liquidation, `realized_profit` in wei.
+    pub fn record_outcome(&self, status: LiquidationStatus, realized_profit: I256) {
+        let mut breaker = self.status.lock().unwrap();
+        match status {
+            LiquidationStatus::Landed => {
+                breaker.consecutive_reverts = 0;
+                if realized_profit < I256::zero() {
+                    breaker.consecutive_losses += 1;
+                } else {
+                    breaker.consecutive_losses = 0;
+                }
+            }
+            LiquidationStatus::Reverted => breaker.consecutive_reverts += 1,
+            // nothing was paid, so nothing went wrong
+            LiquidationStatus::Attempted | LiquidationStatus::Dropped => return,
+        }
+        let reverts = breaker.consecutive_reverts;
+        let losses = breaker.consecutive_losses;
+        drop(breaker);
+
+        if self
+            .limits
+            .max_consecutive_reverts
+            .is_some_and(|max| reverts >= max)
+        {
+            self.trip(format!("{} liquidations reverted in a row", reverts));
+        } else if self
+            .limits
+            .max_consecutive_losses
+            .is_some_and(|max| losses >= max)
+        {
+            self.trip(format!("{} liquidations lost money in a row", losses));
+        }
+    }
+
+    /// Compare an oracle price with the reference oracle's, tripping if they're too far apart.
+    pub fn check_price(&self, asset: Address, price: U256, reference: U256) {
+        let Some(max) = self.limits.max_price_deviation_bps else {
+            return;
+        };
+        let deviation = deviation_bps(price, reference);
+        if deviation > max {
+            self.trip(format!(
+                "Price {} of {:?} deviates {} bps from the reference {}",
+                price, asset, deviation, reference
+            ));
+        }
+    }
+
+    pub fn trip(&self, reason: String) {
+        let mut breaker = self.status.lock().unwrap();
+        if !breaker.tripped {
+            warn!("Circuit breaker tripped, pausing submission: {}", reason);
+        }
+        breaker.tripped = true;
+        breaker.reason = Some(reason);
+        breaker.tripped_at = Some(now());
+    }
+
+    /// Resume submission, as an operator does.
+    pub fn reset(&self) {
+        let mut breaker = self.status.lock().unwrap();
+        if breaker.tripped {
+            info!("Circuit breaker reset");
+        }
+        *breaker = BreakerStatus {
+            reason: breaker.reason.take(),
+            tripped_at: breaker.tripped_at,
+            ..Default::default()
+        };
+    }
+
+    /// Whether submission is stopped, resetting once the cooldown has passed.
+    pub fn is_tripped(&self) -> bool {
+        self.is_tripped_at(now())
+    }
+
+    fn is_tripped_at(&self, now: u64) -> bool {
+        let status = self.status();
+        if !status.tripped {
+            return false;
+        }
+        let cooled_down = self
+            .limits
+            .cooldown_secs
+            .zip(status.tripped_at)
+            .is_some_and(|(cooldown, tripped_at)| now >= tripped_at + cooldown);
+        if cooled_down {
+            info!("Circuit breaker cooled down");
+            self.reset();
+        }
+        !cooled_down
+    }
+
+    pub fn status(&self) -> BreakerStatus {
+        self.status.lock().unwrap().clone()
+    }
+}
+
+/// How far `price` is from `reference`, in bps of the reference.
+pub fn deviation_bps(price: U256, reference: U256) -> u64 {
+    if reference.is_zero() {
+        return u64::MAX;
+    }
+    let difference = if price > reference {
+        price - reference
+    } else {
+        reference - price
+    };
+    (difference.saturating_mul(U256::from(10_000)) / reference)
+        .try_into()
+        .unwrap_or(u64::MAX)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Invalid timestamp")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_outcome() {
+        let breaker = CircuitBreaker::new(BreakerLimits {
+            max_consecutive_reverts: Some(2),
+            max_consecutive_losses: Some(3),
+            ..Default::default()
+        });
+        breaker.record_outcome(LiquidationStatus::Reverted, I256::from(-1));
+        // a landed liquidation breaks the streak
+        breaker.record_outcome(LiquidationStatus::Landed, I256::from(10));
+        breaker.record_outcome(LiquidationStatus::Reverted, I256::from(-1));
+        breaker.record_outcome(LiquidationStatus::Dropped, I256::zero());
+        assert!(!breaker.is_tripped());
+        breaker.record_outcome(LiquidationStatus::Reverted, I256::from(-1));
+        assert!(breaker.is_tripped());
+        assert_eq!(breaker.status().consecutive_reverts, 2);
+
+        // only an operator resumes without a cooldown
+        breaker.reset();
+        assert!(!breaker.is_tripped());
+        for _ in 0..3 {
+            breaker.record_outcome(LiquidationStatus::Landed, I256::from(-5));
+        }
+        assert!(breaker.is_tripped());
+        assert!(breaker.status().reason.unwrap().contains("lost money"));
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let breaker = CircuitBreaker::new(BreakerLimits {
+            cooldown_secs: Some(60),
+            ..Default::default()
+        });
+        breaker.trip("test".to_string());
+        let tripped_at = breaker.status().tripped_at.unwrap();
+        assert!(breaker.is_tripped_at(tripped_at + 59));
+        assert!(!breaker.is_tripped_at(tripped_at + 60));
+        assert!(!breaker.is_tripped());
+    }
+
+    #[test]
+    fn test_check_price() {
+        let breaker = CircuitBreaker::new(BreakerLimits {
+            max_price_deviation_bps: Some(500),
+            ..Default::default()
+        });
+        breaker.check_price(Address::zero(), U256::from(1040), U256::from(1000));
+        assert!(!breaker.is_tripped());
+        breaker.check_price(Address::zero(), U256::from(940), U256::from(1000));
+        assert!(breaker.is_tripped());
+
+        assert_eq!(deviation_bps(U256::from(1050), U256::from(1000)), 500);
+        assert_eq!(deviation_bps(U256::from(1), U256::zero()), u64::MAX);
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use bindings_aave::pool::LiquidationCallFilter;
+use ethers::{
+    contract::{parse_log, EthEvent},
+    prelude::Middleware,
+    providers::PubsubClient,
+    types::{Address, Filter, Log, H256, U256},
+};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_stream::StreamExt;
+
+/// A collector that subscribes to the `LiquidationCall` logs of the pool, and generates
+/// a stream of [events](LiquidationCall) for every liquidation, whoever sent it.
+pub struct LiquidationCallCollector<M> {
+    provider: Arc<M>,
+    pool_address: Address,
+}
+
+/// A liquidation that landed on the pool, at the unix time the log was seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiquidationCall {
+    pub tx_hash: H256,
+    pub block_number: u64,
+    /// Account that called the pool, a liquidator contract or the tx sender.
+    pub liquidator: Address,
+    pub borrower: Address,
+    pub collateral: Address,
+    pub debt: Address,
+    pub debt_covered: U256,
+    pub collateral_seized: U256,
+    pub timestamp: u64,
+}
+
+impl<M> LiquidationCallCollector<M> {
+    pub fn new(provider: Arc<M>, pool_address: Address) -> Self {
+        Self {
+            provider,
+            pool_address,
+        }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [LiquidationCallCollector](LiquidationCallCollector).
+/// This implementation uses the [PubsubClient](PubsubClient) to subscribe to pool logs.
+#[async_trait]
+impl<M> Collector<LiquidationCall> for LiquidationCallCollector<M>
+where
+    M: Middleware,
+    M::Provider: PubsubClient,
+    M::Error: 'static,
+{
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, LiquidationCall>> {
+        let filter = Filter::new()
+            .address(self.pool_address)
+            .topic0(LiquidationCallFilter::signature());
+        let stream = self.provider.subscribe_logs(&filter).await?;
+        let stream = stream.filter_map(|log| {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs();
+            decode_liquidation_call(log, timestamp)
+        });
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Decode a `LiquidationCall` log, skipping removed and pending ones.
+pub fn decode_liquidation_call(log: Log, timestamp: u64) -> Option<LiquidationCall> {
+    if log.removed == Some(true) {
+        return None;
+    }
+    let tx_hash = log.transaction_hash?;
+    let block_number = log.block_number?.as_u64();
+    let event: LiquidationCallFilter = parse_log(log).ok()?;
+    Some(LiquidationCall {
+        tx_hash,
+        block_number,
+        liquidator: event.liquidator,
+        borrower: event.user,
+        collateral: event.collateral_asset,
+        debt: event.debt_asset,
+        debt_covered: event.debt_to_cover,
+        collateral_seized: event.liquidated_collateral_amount,
+        timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{self, Token};
+
+    #[test]
+    fn test_decode_liquidation_call() {
+        let collateral = Address::repeat_byte(1);
+        let debt = Address::repeat_byte(2);
+        let borrower = Address::repeat_byte(3);
+        let liquidator = Address::repeat_byte(4);
+        let log = Log {
+            topics: vec![
+                LiquidationCallFilter::signature(),
+                H256::from(collateral),
+                H256::from(debt),
+                H256::from(borrower),
+            ],
+            data: abi::encode(&[
+                Token::Uint(U256::from(1000)),
+                Token::Uint(U256::from(500)),
+                Token::Address(liquidator),
+                Token::Bool(false),
+            ])
+            .into(),
+            transaction_hash: Some(H256::repeat_byte(5)),
+            block_number: Some(100.into()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            decode_liquidation_call(log.clone(), 1_700_000_000),
+            Some(LiquidationCall {
+                tx_hash: H256::repeat_byte(5),
+                block_number: 100,
+                liquidator,
+                borrower,
+                collateral,
+                debt,
+                debt_covered: U256::from(1000),
+                collateral_seized: U256::from(500),
+                timestamp: 1_700_000_000,
+            })
+        );
+        // logs dropped by a reorg never landed
+        let removed = Log {
+            removed: Some(true),
+            ..log
+        };
+        assert_eq!(decode_liquidation_call(removed, 1_700_000_000), None);
+    }
+}
@@ -0,0 +1,139 @@
+use super::price_update_collector::{decode_transmit_answer, get_aggregators, PendingPriceUpdate};
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use ethers::{
+    prelude::Middleware,
+    types::{Address, H256, I256, U256},
+    utils::keccak256,
+};
+use mev_share_sse::{EventClient, EventTransactionLog};
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+use tracing::{error, info};
+
+/// Default Flashbots MEV-Share event stream.
+pub const MEV_SHARE_SSE_URL: &str = "https://mev-share.flashbots.net";
+
+/// A collector that subscribes to MEV-Share hints and generates a stream of
+/// [events](PendingPriceUpdate) for the ones updating the chainlink aggregators
+/// backing the Aave oracle. The new answer is read from the calldata when it
+/// is shared, otherwise from the `AnswerUpdated` log hint.
+pub struct MevShareCollector<M> {
+    provider: Arc<M>,
+    pool_data_provider: Address,
+    oracle_address: Address,
+    url: String,
+}
+
+impl<M> MevShareCollector<M> {
+    pub fn new(
+        provider: Arc<M>,
+        pool_data_provider: Address,
+        oracle_address: Address,
+        url: String,
+    ) -> Self {
+        Self {
+            provider,
+            pool_data_provider,
+            oracle_address,
+            url,
+        }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [MevShareCollector](MevShareCollector).
+/// This implementation uses the [EventClient](EventClient) to subscribe to the MEV-Share event stream.
+#[async_trait]
+impl<M> Collector<PendingPriceUpdate> for MevShareCollector<M>
+where
+    M: Middleware + 'static,
+    M::Error: 'static,
+{
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, PendingPriceUpdate>> {
+        let aggregators = get_aggregators(
+            self.provider.clone(),
+            self.pool_data_provider,
+            self.oracle_address,
+        )
+        .await?;
+        info!(
+            "Watching {} chainlink aggregators on MEV-Share",
+            aggregators.len()
+        );
+
+        let client = EventClient::default();
+        let stream = client.events(&self.url).await?;
+        let stream = stream.filter_map(move |event| {
+            let event = event
+                .map_err(|e| error!("MEV-Share stream error: {}", e))
+                .ok()?;
+            // the calldata is usually hidden, the log hint still carries the answer
+            let (aggregator, price) = event
+                .transactions
+                .iter()
+                .find_map(|tx| {
+                    let to = tx.to.filter(|to| aggregators.contains_key(to))?;
+                    Some((to, decode_transmit_answer(tx.calldata.as_ref()?)?))
+                })
+                .or_else(|| {
+                    event.logs.iter().find_map(|log| {
+                        aggregators.get(&log.address)?;
+                        Some((log.address, decode_answer_updated(log)?))
+                    })
+                })?;
+            Some(PendingPriceUpdate {
+                assets: aggregators.get(&aggregator)?.clone(),
+                price,
+                tx_hash: event.hash,
+            })
+        });
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Decode the new answer from an `AnswerUpdated(int256 indexed current, uint256 indexed roundId, uint256 updatedAt)` log.
+pub fn decode_answer_updated(log: &EventTransactionLog) -> Option<U256> {
+    let signature = H256::from(keccak256("AnswerUpdated(int256,uint256,uint256)"));
+    if log.topics.first() != Some(&signature) {
+        return None;
+    }
+    let answer = I256::from_raw(U256::from_big_endian(log.topics.get(1)?.as_bytes()));
+    if answer.is_negative() {
+        return None;
+    }
+    Some(answer.into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_answer_updated() {
+        let signature = H256::from(keccak256("AnswerUpdated(int256,uint256,uint256)"));
+        let mut answer = [0u8; 32];
+        U256::from(200_000_000_000u64).to_big_endian(&mut answer);
+        let log = EventTransactionLog {
+            address: Address::repeat_byte(1),
+            topics: vec![signature, H256::from(answer), H256::zero()],
+            data: Default::default(),
+        };
+        assert_eq!(
+            decode_answer_updated(&log),
+            Some(U256::from(200_000_000_000u64))
+        );
+
+        let negative = EventTransactionLog {
+            topics: vec![signature, H256::repeat_byte(0xff), H256::zero()],
+            ..log.clone()
+        };
+        assert_eq!(decode_answer_updated(&negative), None);
+
+        let other = EventTransactionLog {
+            topics: vec![H256::zero(), H256::from(answer)],
+            ..log
+        };
+        assert_eq!(decode_answer_updated(&other), None);
+    }
+}
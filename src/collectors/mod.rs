@@ -1,2 +1,7 @@
 pub mod block_collector;
+pub mod liquidation_call_collector;
+pub mod mev_share_collector;
+pub mod opportunity_queue_collector;
+pub mod price_update_collector;
+pub mod reserve_data_collector;
 pub mod time_collector;
@@ -0,0 +1,32 @@
+use crate::strategies::shared_state::SharedState;
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use tokio_stream::StreamExt;
+
+/// A collector that takes the liquidation opportunities detect-only instances queued on
+/// Redis, and generates a stream of [events](QueuedOpportunity) for an execute-only one.
+pub struct OpportunityQueueCollector {
+    shared_state: SharedState,
+}
+
+/// An opportunity as a detect-only instance queued it, serialized as JSON.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueuedOpportunity(pub String);
+
+impl OpportunityQueueCollector {
+    pub fn new(shared_state: SharedState) -> Self {
+        Self { shared_state }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the
+/// [OpportunityQueueCollector](OpportunityQueueCollector). The stream ends if Redis fails,
+/// for the supervisor to restart it.
+#[async_trait]
+impl Collector<QueuedOpportunity> for OpportunityQueueCollector {
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, QueuedOpportunity>> {
+        let stream = self.shared_state.queued_opportunities().await?;
+        Ok(Box::pin(stream.map(QueuedOpportunity)))
+    }
+}
@@ -0,0 +1,242 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use bindings_aave::{i_aave_oracle::IAaveOracle, i_pool_data_provider::IPoolDataProvider};
+use ethers::{
+    abi::{self, ParamType},
+    contract::abigen,
+    prelude::Middleware,
+    providers::PubsubClient,
+    types::{Address, Bytes, H256, I256, U256},
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio_stream::StreamExt;
+use tracing::info;
+
+abigen!(
+    AggregatorProxy,
+    r#"[
+        function aggregator() external view returns (address)
+    ]"#
+);
+
+// OCR2 `transmit(bytes32[3],bytes,bytes32[],bytes32[],bytes32)`
+const OCR2_TRANSMIT_SELECTOR: [u8; 4] = [0xb1, 0xdc, 0x65, 0xa4];
+// OCR1 `transmit(bytes,bytes32[],bytes32[],bytes32)`
+const OCR1_TRANSMIT_SELECTOR: [u8; 4] = [0xc9, 0x80, 0x75, 0x39];
+
+/// A collector that watches the mempool for pending Chainlink `transmit` transactions
+/// to the aggregators backing the Aave oracle, and generates a stream of
+/// [events](PendingPriceUpdate) containing the answer about to be reported.
+pub struct PriceUpdateCollector<M> {
+    provider: Arc<M>,
+    pool_data_provider: Address,
+    oracle_address: Address,
+}
+
+/// A pending oracle update, containing the priced assets and their new price.
+#[derive(Debug, Clone)]
+pub struct PendingPriceUpdate {
+    pub assets: Vec<Address>,
+    pub price: U256,
+    pub tx_hash: H256,
+}
+
+impl<M> PriceUpdateCollector<M> {
+    pub fn new(provider: Arc<M>, pool_data_provider: Address, oracle_address: Address) -> Self {
+        Self {
+            provider,
+            pool_data_provider,
+            oracle_address,
+        }
+    }
+}
+
+/// Map each chainlink aggregator to the reserves it prices, skipping sources
+/// that are not plain aggregator proxies (e.g. rate adapters).
+pub async fn get_aggregators<M: Middleware + 'static>(
+    provider: Arc<M>,
+    pool_data_provider: Address,
+    oracle_address: Address,
+) -> Result<HashMap<Address, Vec<Address>>> {
+    let pool_data = IPoolDataProvider::new(pool_data_provider, provider.clone());
+    let oracle = IAaveOracle::new(oracle_address, provider.clone());
+
+    let mut aggregators: HashMap<Address, Vec<Address>> = HashMap::new();
+    for token in pool_data.get_all_reserves_tokens().await? {
+        let source = oracle.get_source_of_asset(token.token_address).await?;
+        match AggregatorProxy::new(source, provider.clone())
+            .aggregator()
+            .await
+        {
+            Ok(aggregator) => aggregators
+                .entry(aggregator)
+                .or_default()
+                .push(token.token_address),
+            Err(_) => info!(
+                "Price source {:?} of {} is not an aggregator proxy, skipping",
+                source, token.symbol
+            ),
+        }
+    }
+
+    Ok(aggregators)
+}
+
+/// Implementation of the [Collector](Collector) trait for the [PriceUpdateCollector](PriceUpdateCollector).
+/// This implementation uses the [PubsubClient](PubsubClient) to subscribe to pending transactions.
+#[async_trait]
+impl<M> Collector<PendingPriceUpdate> for PriceUpdateCollector<M>
+where
+    M: Middleware + 'static,
+    M::Provider: PubsubClient,
+    M::Error: 'static,
+{
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, PendingPriceUpdate>> {
+        let aggregators = get_aggregators(
+            self.provider.clone(),
+            self.pool_data_provider,
+            self.oracle_address,
+        )
+        .await?;
+        info!("Watching {} chainlink aggregators", aggregators.len());
+
+        let stream = self.provider.subscribe_pending_txs().await?;
+        let stream = stream.transactions_unordered(256).filter_map(move |tx| {
+            let tx = tx.ok()?;
+            let assets = aggregators.get(&tx.to?)?;
+            decode_transmit_answer(&tx.input).map(|price| PendingPriceUpdate {
+                assets: assets.clone(),
+                price,
+                tx_hash: tx.hash,
+            })
+        });
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Decode the median answer from the calldata of an OCR1 or OCR2 `transmit` call.
+pub fn decode_transmit_answer(input: &Bytes) -> Option<U256> {
+    if input.len() < 4 {
+        return None;
+    }
+    let (selector, args) = input.split_at(4);
+
+    let report = if selector == OCR2_TRANSMIT_SELECTOR {
+        let tokens = abi::decode(
+            &[
+                ParamType::FixedArray(Box::new(ParamType::FixedBytes(32)), 3),
+                ParamType::Bytes,
+                ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+                ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+                ParamType::FixedBytes(32),
+            ],
+            args,
+        )
+        .ok()?;
+        let report = tokens.get(1)?.clone().into_bytes()?;
+        // (uint32 observationsTimestamp, bytes32 rawObservers, int192[] observations, int192 juelsPerFeeCoin)
+        abi::decode(
+            &[
+                ParamType::Uint(32),
+                ParamType::FixedBytes(32),
+                ParamType::Array(Box::new(ParamType::Int(192))),
+                ParamType::Int(192),
+            ],
+            &report,
+        )
+        .ok()?
+    } else if selector == OCR1_TRANSMIT_SELECTOR {
+        let tokens = abi::decode(
+            &[
+                ParamType::Bytes,
+                ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+                ParamType::Array(Box::new(ParamType::FixedBytes(32))),
+                ParamType::FixedBytes(32),
+            ],
+            args,
+        )
+        .ok()?;
+        let report = tokens.first()?.clone().into_bytes()?;
+        // (bytes32 rawReportContext, bytes32 rawObservers, int192[] observations)
+        abi::decode(
+            &[
+                ParamType::FixedBytes(32),
+                ParamType::FixedBytes(32),
+                ParamType::Array(Box::new(ParamType::Int(192))),
+            ],
+            &report,
+        )
+        .ok()?
+    } else {
+        return None;
+    };
+
+    // observations are sorted, the aggregator reports the median
+    let observations = report.get(2)?.clone().into_array()?;
+    let median = observations
+        .get(observations.len() / 2)?
+        .clone()
+        .into_int()?;
+    let median = I256::from_raw(median);
+    if median.is_negative() {
+        return None;
+    }
+    Some(median.into_raw())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::Token;
+
+    fn observations(values: &[u64]) -> Token {
+        Token::Array(values.iter().map(|v| Token::Int(U256::from(*v))).collect())
+    }
+
+    #[test]
+    fn test_decode_ocr2_transmit() {
+        let report = abi::encode(&[
+            Token::Uint(U256::from(1_700_000_000u64)),
+            Token::FixedBytes(vec![0; 32]),
+            observations(&[199_000_000_000, 200_000_000_000, 201_000_000_000]),
+            Token::Int(U256::zero()),
+        ]);
+        let args = abi::encode(&[
+            Token::FixedArray(vec![Token::FixedBytes(vec![0; 32]); 3]),
+            Token::Bytes(report),
+            Token::Array(vec![]),
+            Token::Array(vec![]),
+            Token::FixedBytes(vec![0; 32]),
+        ]);
+        let input = Bytes::from([OCR2_TRANSMIT_SELECTOR.to_vec(), args].concat());
+
+        assert_eq!(
+            decode_transmit_answer(&input),
+            Some(U256::from(200_000_000_000u64))
+        );
+    }
+
+    #[test]
+    fn test_decode_ocr1_transmit() {
+        let report = abi::encode(&[
+            Token::FixedBytes(vec![0; 32]),
+            Token::FixedBytes(vec![0; 32]),
+            observations(&[99_000_000, 100_000_000]),
+        ]);
+        let args = abi::encode(&[
+            Token::Bytes(report),
+            Token::Array(vec![]),
+            Token::Array(vec![]),
+            Token::FixedBytes(vec![0; 32]),
+        ]);
+        let input = Bytes::from([OCR1_TRANSMIT_SELECTOR.to_vec(), args].concat());
+
+        assert_eq!(
+            decode_transmit_answer(&input),
+            Some(U256::from(100_000_000u64))
+        );
+        assert_eq!(decode_transmit_answer(&Bytes::from(vec![0u8; 4])), None);
+    }
+}
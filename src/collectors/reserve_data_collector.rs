@@ -0,0 +1,124 @@
+use anyhow::Result;
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use bindings_aave::pool::ReserveDataUpdatedFilter;
+use ethers::{
+    contract::{parse_log, EthEvent},
+    prelude::Middleware,
+    providers::PubsubClient,
+    types::{Address, Filter, Log, U256},
+};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_stream::StreamExt;
+
+/// A collector that subscribes to the `ReserveDataUpdated` logs of the pool, and generates
+/// a stream of [events](ReserveDataUpdate) with the new rates and indexes of a reserve.
+pub struct ReserveDataCollector<M> {
+    provider: Arc<M>,
+    pool_address: Address,
+}
+
+/// The rates and indexes a reserve was updated to, at the unix time the log was seen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReserveDataUpdate {
+    pub reserve: Address,
+    pub liquidity_rate: U256,
+    pub variable_borrow_rate: U256,
+    pub liquidity_index: U256,
+    pub variable_borrow_index: U256,
+    pub timestamp: u64,
+}
+
+impl<M> ReserveDataCollector<M> {
+    pub fn new(provider: Arc<M>, pool_address: Address) -> Self {
+        Self {
+            provider,
+            pool_address,
+        }
+    }
+}
+
+/// Implementation of the [Collector](Collector) trait for the [ReserveDataCollector](ReserveDataCollector).
+/// This implementation uses the [PubsubClient](PubsubClient) to subscribe to pool logs.
+#[async_trait]
+impl<M> Collector<ReserveDataUpdate> for ReserveDataCollector<M>
+where
+    M: Middleware,
+    M::Provider: PubsubClient,
+    M::Error: 'static,
+{
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, ReserveDataUpdate>> {
+        let filter = Filter::new()
+            .address(self.pool_address)
+            .topic0(ReserveDataUpdatedFilter::signature());
+        let stream = self.provider.subscribe_logs(&filter).await?;
+        let stream = stream.filter_map(|log| {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs();
+            decode_reserve_data_updated(log, timestamp)
+        });
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Decode a `ReserveDataUpdated` log, skipping removed ones.
+pub fn decode_reserve_data_updated(log: Log, timestamp: u64) -> Option<ReserveDataUpdate> {
+    if log.removed == Some(true) {
+        return None;
+    }
+    let event: ReserveDataUpdatedFilter = parse_log(log).ok()?;
+    Some(ReserveDataUpdate {
+        reserve: event.reserve,
+        liquidity_rate: event.liquidity_rate,
+        variable_borrow_rate: event.variable_borrow_rate,
+        liquidity_index: event.liquidity_index,
+        variable_borrow_index: event.variable_borrow_index,
+        timestamp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::{self, Token};
+    use ethers::types::H256;
+
+    #[test]
+    fn test_decode_reserve_data_updated() {
+        let reserve = Address::repeat_byte(1);
+        let ray = U256::exp10(27);
+        let log = Log {
+            topics: vec![ReserveDataUpdatedFilter::signature(), H256::from(reserve)],
+            data: abi::encode(&[
+                Token::Uint(U256::exp10(25)),
+                Token::Uint(U256::exp10(26)),
+                Token::Uint(U256::exp10(26) * 2),
+                Token::Uint(ray * 11 / 10),
+                Token::Uint(ray * 12 / 10),
+            ])
+            .into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            decode_reserve_data_updated(log.clone(), 1_700_000_000),
+            Some(ReserveDataUpdate {
+                reserve,
+                liquidity_rate: U256::exp10(25),
+                variable_borrow_rate: U256::exp10(26) * 2,
+                liquidity_index: ray * 11 / 10,
+                variable_borrow_index: ray * 12 / 10,
+                timestamp: 1_700_000_000,
+            })
+        );
+        // logs dropped by a reorg carry stale rates
+        let removed = Log {
+            removed: Some(true),
+            ..log
+        };
+        assert_eq!(decode_reserve_data_updated(removed, 1_700_000_000), None);
+    }
+}
@@ -1,15 +1,18 @@
 use anyhow::Result;
 use artemis_core::types::{Collector, CollectorStream};
 use async_trait::async_trait;
-use futures::StreamExt;
+use ethers::core::rand::{thread_rng, Rng};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::time::Duration;
-use tokio_stream::wrappers::IntervalStream;
+use tokio::time::{Duration, Instant};
 
 /// A collector that listens for new blocks, and generates a stream of
 /// [events](NewBlock) which contain the block number and hash.
 pub struct TimeCollector {
-    pub poll_secs: u64,
+    pub poll_secs: PollInterval,
+    /// Upper bound of the random delay added to each tick, capped at half the interval.
+    pub jitter_millis: u64,
 }
 
 /// A new block event, containing the block number and hash.
@@ -18,28 +21,101 @@ pub struct NewTick {
     pub timestamp: u64,
 }
 
+/// Seconds between a [TimeCollector]'s ticks, shared so it can be changed while it runs.
+#[derive(Debug, Clone)]
+pub struct PollInterval(Arc<AtomicU64>);
+
+impl PollInterval {
+    pub fn secs(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Tick every `secs` from the next tick on.
+    pub fn set(&self, secs: u64) {
+        self.0.store(secs.max(1), Ordering::Relaxed);
+    }
+}
+
 impl TimeCollector {
     pub fn new(poll_secs: u64) -> Self {
-        Self { poll_secs }
+        Self {
+            poll_secs: PollInterval(Arc::new(AtomicU64::new(poll_secs.max(1)))),
+            jitter_millis: 0,
+        }
+    }
+
+    /// Handle changing the interval of the running collector.
+    pub fn interval(&self) -> PollInterval {
+        self.poll_secs.clone()
+    }
+
+    /// Delay each tick by a random amount, so bots running the same settings don't scan in lockstep.
+    pub fn with_jitter_millis(mut self, jitter_millis: u64) -> Self {
+        // keep ticks in order and at least half an interval apart
+        self.jitter_millis = jitter_millis.min(self.poll_secs.secs() * 1000 / 2);
+        self
     }
 }
 
+/// A random delay between zero and `max_millis`.
+pub fn jitter(max_millis: u64) -> Duration {
+    if max_millis == 0 {
+        return Duration::ZERO;
+    }
+    Duration::from_millis(thread_rng().gen_range(0..=max_millis))
+}
+
 /// Implementation of the [Collector](Collector) trait for the [BlockCollector](BlockCollector).
 /// This implementation uses the [PubsubClient](PubsubClient) to subscribe to new blocks.
 #[async_trait]
 impl Collector<NewTick> for TimeCollector {
     async fn get_event_stream(&self) -> Result<CollectorStream<'_, NewTick>> {
-        let stream = IntervalStream::new(tokio::time::interval(Duration::from_secs(
-            self.poll_secs,
-        )))
-        .then(|_| async {
-            let timestamp: u64 = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Invalid timestamp")
-                .as_secs();
-            return NewTick { timestamp };
+        let jitter_millis = self.jitter_millis;
+        let poll_secs = self.poll_secs.clone();
+        let start = (Instant::now(), poll_secs.secs());
+        let stream = futures::stream::unfold(start, move |(next, secs)| {
+            let poll_secs = poll_secs.clone();
+            async move {
+                tokio::time::sleep_until(next).await;
+                // keep ticks at least half an interval apart
+                tokio::time::sleep(jitter(jitter_millis.min(secs * 1000 / 2))).await;
+                let timestamp: u64 = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Invalid timestamp")
+                    .as_secs();
+                // a changed interval applies from the next tick
+                let secs = poll_secs.secs();
+                Some((
+                    NewTick { timestamp },
+                    (next + Duration::from_secs(secs), secs),
+                ))
+            }
         });
 
         Ok(Box::pin(stream))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jitter_bounds() {
+        assert_eq!(jitter(0), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter(50) <= Duration::from_millis(50));
+        }
+
+        assert_eq!(
+            TimeCollector::new(5).with_jitter_millis(1000).jitter_millis,
+            1000
+        );
+        assert_eq!(
+            TimeCollector::new(5)
+                .with_jitter_millis(10_000)
+                .jitter_millis,
+            2500
+        );
+    }
+}
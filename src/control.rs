@@ -0,0 +1,281 @@
+use crate::tunables::Tunables;
+use anyhow::{anyhow, Result};
+use artemis_core::types::{Collector, CollectorStream};
+use async_trait::async_trait;
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::{info, warn};
+
+pub const AUDIT_LOG_FILE: &str = "audit.jsonl";
+
+/// An operator action on the running liquidator.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Stop submitting transactions, scanning continues.
+    Pause,
+    /// Resume submitting transactions.
+    Resume,
+    /// Refresh reserve configs and partner accounts, and reconcile every borrower on-chain.
+    Resync,
+    /// Skip opportunities making less than this (in wei).
+    SetMinProfit { min_profit_eth: U256 },
+    /// Change the bid, min profit, filters and poll intervals set in `tunables`.
+    SetTunables { tunables: Tunables },
+    /// Halt every submission until released, or release the kill switch. A kill switch
+    /// file still halts it while it exists.
+    SetKillSwitch { engaged: bool },
+    /// Submit liquidations of a borrower over the approval notional, for the approval TTL.
+    Approve { borrower: Address },
+    /// Pass on liquidations of a borrower over the approval notional, for the approval TTL.
+    Reject { borrower: Address },
+    /// Liquidate a specific position, covering the maximum allowed debt if no amount is given.
+    Liquidate {
+        borrower: Address,
+        collateral: Address,
+        debt: Address,
+        debt_to_cover: Option<U256>,
+    },
+}
+
+/// A command as submitted by an operator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlRequest {
+    /// Client-chosen key, submitting the same key twice only executes the command once.
+    pub idempotency_key: String,
+    /// Who triggered the command.
+    pub actor: String,
+    pub command: ControlCommand,
+}
+
+/// A record of an executed command.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: u64,
+    pub idempotency_key: String,
+    pub actor: String,
+    pub command: ControlCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitResult {
+    /// The command was recorded and forwarded to the strategy.
+    Accepted(AuditEntry),
+    /// The key was used before, the original entry is returned and nothing is executed.
+    Replayed(AuditEntry),
+}
+
+/// Append-only audit log of control commands, indexed by idempotency key.
+#[derive(Debug)]
+struct AuditLog {
+    path: PathBuf,
+    entries: HashMap<String, AuditEntry>,
+}
+
+impl AuditLog {
+    // read previous entries so replay protection survives restarts. A crash mid-write
+    // leaves a partial last line, which is cut off so the next entry starts on its own line.
+    fn open(path: &Path) -> Result<Self> {
+        let mut entries = HashMap::new();
+        let contents = std::fs::read_to_string(path).unwrap_or_default();
+        let mut valid_len = 0;
+        for line in contents.split_inclusive('\n') {
+            match serde_json::from_str::<AuditEntry>(line) {
+                Ok(entry) => {
+                    entries.insert(entry.idempotency_key.clone(), entry);
+                }
+                Err(e) if line.ends_with('\n') => {
+                    warn!(
+                        "Skipping corrupt audit log entry {:?}: {}",
+                        line.trim_end(),
+                        e
+                    );
+                }
+                Err(e) => {
+                    warn!("Truncating partial audit log entry {:?}: {}", line, e);
+                    OpenOptions::new()
+                        .write(true)
+                        .open(path)?
+                        .set_len(valid_len as u64)?;
+                    break;
+                }
+            }
+            valid_len += line.len();
+        }
+        info!("read {} audit log entries", entries.len());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries,
+        })
+    }
+
+    fn append(&mut self, entry: AuditEntry) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        self.entries.insert(entry.idempotency_key.clone(), entry);
+        Ok(())
+    }
+}
+
+/// Cloneable handle used by control interfaces to submit commands.
+#[derive(Debug, Clone)]
+pub struct ControlHandle {
+    audit_log: Arc<Mutex<AuditLog>>,
+    sender: UnboundedSender<ControlCommand>,
+}
+
+impl ControlHandle {
+    /// Record and forward a command, unless its idempotency key was already used.
+    pub fn submit(&self, request: ControlRequest) -> Result<SubmitResult> {
+        let mut audit_log = self
+            .audit_log
+            .lock()
+            .map_err(|_| anyhow!("audit log poisoned"))?;
+        if let Some(entry) = audit_log.entries.get(&request.idempotency_key) {
+            return Ok(SubmitResult::Replayed(entry.clone()));
+        }
+
+        let entry = AuditEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs(),
+            idempotency_key: request.idempotency_key,
+            actor: request.actor,
+            command: request.command,
+        };
+        info!("control command: {:?}", entry);
+        // recorded once it reaches the strategy, a command that never went out can be retried
+        self.sender.send(entry.command.clone())?;
+        audit_log.append(entry.clone())?;
+
+        Ok(SubmitResult::Accepted(entry))
+    }
+}
+
+/// A collector that generates a stream of [commands](ControlCommand) submitted
+/// through a [ControlHandle](ControlHandle).
+pub struct ControlCollector {
+    receiver: Mutex<Option<UnboundedReceiver<ControlCommand>>>,
+}
+
+/// Create a control handle and the collector receiving its commands.
+pub fn channel(audit_log_path: &Path) -> Result<(ControlHandle, ControlCollector)> {
+    let (sender, receiver) = unbounded_channel();
+    let handle = ControlHandle {
+        audit_log: Arc::new(Mutex::new(AuditLog::open(audit_log_path)?)),
+        sender,
+    };
+    let collector = ControlCollector {
+        receiver: Mutex::new(Some(receiver)),
+    };
+    Ok((handle, collector))
+}
+
+#[async_trait]
+impl Collector<ControlCommand> for ControlCollector {
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, ControlCommand>> {
+        let receiver = self
+            .receiver
+            .lock()
+            .map_err(|_| anyhow!("control receiver poisoned"))?
+            .take()
+            .ok_or(anyhow!("control event stream already taken"))?;
+        Ok(Box::pin(UnboundedReceiverStream::new(receiver)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotent_submit() {
+        let path = std::env::temp_dir().join(format!("audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (handle, collector) = channel(&path).unwrap();
+        let request = ControlRequest {
+            idempotency_key: "pause-1".to_string(),
+            actor: "alice".to_string(),
+            command: ControlCommand::Pause,
+        };
+
+        let accepted = match handle.submit(request.clone()).unwrap() {
+            SubmitResult::Accepted(entry) => entry,
+            result => panic!("unexpected result {:?}", result),
+        };
+        assert_eq!(
+            handle.submit(request.clone()).unwrap(),
+            SubmitResult::Replayed(accepted.clone())
+        );
+
+        // only the first submission reaches the strategy
+        let mut receiver = collector.receiver.lock().unwrap().take().unwrap();
+        assert_eq!(receiver.try_recv().unwrap(), ControlCommand::Pause);
+        assert!(receiver.try_recv().is_err());
+
+        // replay protection survives a restart
+        let (handle, _) = channel(&path).unwrap();
+        assert_eq!(
+            handle.submit(request).unwrap(),
+            SubmitResult::Replayed(accepted)
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_partial_tail() {
+        let path = std::env::temp_dir().join(format!("audit-tail-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (handle, _collector) = channel(&path).unwrap();
+        let request = ControlRequest {
+            idempotency_key: "pause-1".to_string(),
+            actor: "alice".to_string(),
+            command: ControlCommand::Pause,
+        };
+        let SubmitResult::Accepted(accepted) = handle.submit(request.clone()).unwrap() else {
+            panic!("not accepted");
+        };
+        // a crash mid-write
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(file, "{{\"timestamp\":1,\"idempotency_key\":\"res").unwrap();
+
+        let (handle, _collector) = channel(&path).unwrap();
+        assert_eq!(
+            handle.submit(request).unwrap(),
+            SubmitResult::Replayed(accepted)
+        );
+        let resume = ControlRequest {
+            idempotency_key: "resume-1".to_string(),
+            actor: "alice".to_string(),
+            command: ControlCommand::Resume,
+        };
+        assert!(matches!(
+            handle.submit(resume).unwrap(),
+            SubmitResult::Accepted(_)
+        ));
+        // the partial line is gone and the new entry is on a line of its own
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert!(contents
+            .lines()
+            .all(|line| serde_json::from_str::<AuditEntry>(line).is_ok()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
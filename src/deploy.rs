@@ -0,0 +1,100 @@
+use anyhow::{anyhow, Result};
+use bindings_liquidator::liquidator::{Liquidator, LIQUIDATOR_ABI, LIQUIDATOR_DEPLOYED_BYTECODE};
+use ethers::abi::Abi;
+use ethers::providers::Middleware;
+use ethers::types::Address;
+use std::sync::Arc;
+use tracing::info;
+
+/// Signatures of the functions of `abi` whose selector `deployed_bytecode` doesn't dispatch
+/// on, so the bytecode was compiled from an older Liquidator.sol than the ABI.
+pub fn missing_selectors(abi: &Abi, deployed_bytecode: &[u8]) -> Vec<String> {
+    abi.functions()
+        .filter(|function| {
+            !deployed_bytecode
+                .windows(4)
+                .any(|word| word == function.short_signature())
+        })
+        .map(|function| function.signature())
+        .collect()
+}
+
+/// Deploy the bundled Liquidator contract from `sender` for the market of `pool`, swapping
+/// through the Uniswap V3 pools of `uniswap_factory`, and check `sender` owns it. Returns
+/// the address for --liquidator-address.
+pub async fn deploy_liquidator<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool: Address,
+    uniswap_factory: Address,
+    sender: Address,
+) -> Result<Address> {
+    let missing = missing_selectors(&LIQUIDATOR_ABI, &LIQUIDATOR_DEPLOYED_BYTECODE);
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "The bundled Liquidator bytecode is older than its ABI and lacks {}, regenerate the bindings with `forge bind`",
+            missing.join(", ")
+        ));
+    }
+    if uniswap_factory == Address::zero() {
+        info!(
+            "Uniswap V3 isn't deployed, the Liquidator can only flash-loan from Balancer and Aave"
+        );
+    }
+
+    // the deployer becomes the owner
+    let (liquidator, receipt) = Liquidator::deploy(client, (pool, uniswap_factory))?
+        .send_with_receipt()
+        .await?;
+    info!(
+        "Deployed Liquidator at {:?} in tx {:?}",
+        liquidator.address(),
+        receipt.transaction_hash
+    );
+
+    let owner = liquidator.owner().call().await?;
+    if owner != sender {
+        return Err(anyhow!(
+            "Liquidator at {:?} is owned by {:?}, not the sender {:?}",
+            liquidator.address(),
+            owner,
+            sender
+        ));
+    }
+    let liquidator_pool = liquidator.pool().call().await?;
+    if liquidator_pool != pool {
+        return Err(anyhow!(
+            "Liquidator at {:?} liquidates on pool {:?}, not {:?}",
+            liquidator.address(),
+            liquidator_pool,
+            pool
+        ));
+    }
+    Ok(liquidator.address())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::parse_abi;
+
+    #[test]
+    fn test_missing_selectors() {
+        let abi = parse_abi(&[
+            "function pool() returns (address)",
+            "function approvePoolBatch(address[])",
+        ])
+        .unwrap();
+        // PUSH4 <selector> of the dispatcher, for `pool()` only
+        let mut bytecode = vec![0x60, 0x80, 0x63];
+        bytecode.extend(abi.function("pool").unwrap().short_signature());
+        bytecode.push(0x14);
+        assert_eq!(
+            missing_selectors(&abi, &bytecode),
+            vec!["approvePoolBatch(address[])".to_string()]
+        );
+
+        bytecode.push(0x63);
+        bytecode.extend(abi.function("approvePoolBatch").unwrap().short_signature());
+        assert!(missing_selectors(&abi, &bytecode).is_empty());
+    }
+}
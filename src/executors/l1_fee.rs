@@ -0,0 +1,163 @@
+use super::protect_executor::net_gas_cost;
+use anyhow::Result;
+use ethers::{
+    contract::abigen,
+    providers::Middleware,
+    types::{transaction::eip2718::TypedTransaction, Address, Bytes, Signature, U256},
+};
+use std::str::FromStr;
+use std::sync::Arc;
+
+abigen!(
+    GasPriceOracle,
+    r#"[
+        function getL1Fee(bytes _data) external view returns (uint256)
+    ]"#
+);
+
+abigen!(
+    NodeInterface,
+    r#"[
+        function gasEstimateComponents(address to, bool contractCreation, bytes data) external payable returns (uint64 gasEstimate, uint64 gasEstimateForL1, uint256 baseFee, uint256 l1BaseFeeEstimate)
+    ]"#
+);
+
+// OP stack predeploy pricing the L1 data fee
+pub const GAS_PRICE_ORACLE: &str = "0x420000000000000000000000000000000000000F";
+// Arbitrum virtual contract, only callable through eth_call
+pub const NODE_INTERFACE: &str = "0x00000000000000000000000000000000000000C8";
+
+/// How a chain charges for posting tx data to L1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum L1FeeModel {
+    /// No L1 data fee.
+    #[default]
+    None,
+    /// A fee in wei charged on top of L2 gas.
+    OpStack,
+    /// Extra L2 gas units, already counted by eth_estimateGas.
+    Arbitrum,
+}
+
+/// L1 data cost of a tx.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum L1Cost {
+    /// Fee in wei charged on top of L2 gas.
+    Fee(U256),
+    /// Gas units of the estimate paying for L1 calldata.
+    Gas(U256),
+}
+
+impl L1FeeModel {
+    pub fn for_chain(chain_id: u64) -> Self {
+        match chain_id {
+            // Optimism, Base
+            10 | 8453 => L1FeeModel::OpStack,
+            42161 => L1FeeModel::Arbitrum,
+            _ => L1FeeModel::None,
+        }
+    }
+
+    /// Estimate the L1 data cost of sending `tx`.
+    pub async fn estimate<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        tx: &TypedTransaction,
+    ) -> Result<Option<L1Cost>> {
+        match self {
+            L1FeeModel::None => Ok(None),
+            L1FeeModel::OpStack => {
+                let oracle = GasPriceOracle::new(Address::from_str(GAS_PRICE_ORACLE)?, client);
+                let fee = oracle.get_l1_fee(signed_size_rlp(tx)).call().await?;
+                Ok(Some(L1Cost::Fee(fee)))
+            }
+            L1FeeModel::Arbitrum => {
+                let node_interface = NodeInterface::new(Address::from_str(NODE_INTERFACE)?, client);
+                let to = tx.to_addr().copied().unwrap_or_default();
+                let data = tx.data().cloned().unwrap_or_default();
+                let (_, gas_for_l1, _, _) = node_interface
+                    .gas_estimate_components(to, false, data)
+                    .call()
+                    .await?;
+                Ok(Some(L1Cost::Gas(U256::from(gas_for_l1))))
+            }
+        }
+    }
+}
+
+impl L1Cost {
+    /// Cost in wei, L1 gas units paid for at `gas_price`.
+    pub fn wei(&self, gas_price: U256) -> U256 {
+        match self {
+            L1Cost::Fee(fee) => *fee,
+            L1Cost::Gas(l1_gas) => l1_gas * gas_price,
+        }
+    }
+}
+
+// the fee is charged on the signed tx, so price it with a signature of full size
+fn signed_size_rlp(tx: &TypedTransaction) -> Bytes {
+    let placeholder = Signature {
+        r: U256::MAX,
+        s: U256::MAX,
+        v: 28,
+    };
+    tx.rlp_signed(&placeholder)
+}
+
+/// Total cost in wei of `gas_usage` at `gas_price`, with the L1 data cost. Only L2
+/// execution is eligible for the chain's fee rebate.
+pub fn total_gas_cost(
+    gas_usage: U256,
+    gas_price: U256,
+    l1_cost: Option<L1Cost>,
+    fee_rebate_bps: u64,
+) -> U256 {
+    let net = |gas: U256| net_gas_cost(gas * gas_price, fee_rebate_bps);
+    match l1_cost {
+        None => net(gas_usage),
+        Some(L1Cost::Fee(fee)) => net(gas_usage) + fee,
+        Some(L1Cost::Gas(l1_gas)) => {
+            let l1_gas = l1_gas.min(gas_usage);
+            net(gas_usage - l1_gas) + l1_gas * gas_price
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_size_rlp() {
+        let tx: TypedTransaction = ethers::types::Eip1559TransactionRequest::new()
+            .to(Address::repeat_byte(1))
+            .data(vec![1u8; 100])
+            .into();
+        // r and s take 33 bytes each once signed
+        assert!(signed_size_rlp(&tx).len() >= tx.rlp().len() + 66);
+    }
+
+    #[test]
+    fn test_l1_cost_wei() {
+        let gas_price = U256::from(10);
+        assert_eq!(L1Cost::Fee(U256::from(5)).wei(gas_price), U256::from(5));
+        assert_eq!(L1Cost::Gas(U256::from(5)).wei(gas_price), U256::from(50));
+    }
+
+    #[test]
+    fn test_total_gas_cost() {
+        let gas = U256::from(1_000_000);
+        let price = U256::from(10);
+        assert_eq!(total_gas_cost(gas, price, None, 0), U256::from(10_000_000));
+        assert_eq!(
+            total_gas_cost(gas, price, Some(L1Cost::Fee(U256::from(5))), 9000),
+            U256::from(1_000_005)
+        );
+        // the L1 part of the estimate isn't rebated
+        assert_eq!(
+            total_gas_cost(gas, price, Some(L1Cost::Gas(U256::from(400_000))), 5000),
+            U256::from(7_000_000)
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, instrument, Instrument, Span};
+
+use super::protect_executor::{settle, settlement_prices};
+use crate::history::{
+    LiquidationHistory, LiquidationInfo, LiquidationRecord, LiquidationStatus, Valuation,
+};
+use crate::kill_switch::KillSwitch;
+use crate::shutdown::Shutdown;
+use anyhow::{anyhow, Context, Result};
+use artemis_core::types::Executor;
+use async_trait::async_trait;
+use ethers::{
+    providers::Middleware,
+    signers::{LocalWallet, Signer},
+    types::{transaction::eip2718::TypedTransaction, TransactionReceipt, H256, U64},
+    utils::keccak256,
+};
+use serde_json::{json, Value};
+
+/// Default Flashbots MEV-Share bundle relay.
+pub const MEV_SHARE_RELAY_URL: &str = "https://relay.flashbots.net";
+
+/// Gas limit of a backrun, it can't be estimated before the oracle update lands.
+pub const BACKRUN_GAS_LIMIT: u64 = 1_500_000;
+
+/// Number of blocks after the next one the bundle stays valid for.
+pub const BACKRUN_BLOCK_RANGE: u64 = 2;
+
+/// Seconds between checks of whether a bundle landed.
+const BUNDLE_POLL_SECS: u64 = 2;
+
+/// A transaction to land right after a transaction seen on MEV-Share.
+#[derive(Debug, Clone)]
+pub struct SubmitBackrunBundle {
+    pub correlation_id: String,
+    /// Hash of the transaction to backrun.
+    pub target_tx: H256,
+    pub tx: TypedTransaction,
+    /// Set for liquidations, so their outcome is recorded in the history.
+    pub liquidation: Option<LiquidationInfo>,
+}
+
+/// An executor that sends backrun bundles to a MEV-Share relay.
+pub struct MevShareExecutor<M> {
+    client: Arc<M>,
+    auth_signer: LocalWallet,
+    relay_url: String,
+    http: reqwest::Client,
+    /// Bundles are dropped instead of sent while it's engaged.
+    kill_switch: KillSwitch,
+    /// Where liquidation outcomes are recorded, along with the chain they ran on.
+    history: Option<(LiquidationHistory, u64)>,
+    /// Values landed liquidations at the prices of their block.
+    valuation: Valuation,
+    shutdown: Shutdown,
+}
+
+impl<M: Middleware> MevShareExecutor<M> {
+    pub fn new(client: Arc<M>, auth_signer: LocalWallet, relay_url: String) -> Self {
+        Self {
+            client,
+            auth_signer,
+            relay_url,
+            http: reqwest::Client::new(),
+            kill_switch: KillSwitch::default(),
+            history: None,
+            valuation: Valuation::default(),
+            shutdown: Shutdown::new(),
+        }
+    }
+
+    /// Stop sending bundles once shutdown is requested.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Drop every bundle instead of sending it while `kill_switch` is engaged.
+    pub fn with_kill_switch(mut self, kill_switch: KillSwitch) -> Self {
+        self.kill_switch = kill_switch;
+        self
+    }
+
+    /// Record every backrun sent and its outcome, valued with `valuation`.
+    pub fn with_history(
+        mut self,
+        history: LiquidationHistory,
+        chain_id: u64,
+        valuation: Valuation,
+    ) -> Self {
+        self.history = Some((history, chain_id));
+        self.valuation = valuation;
+        self
+    }
+}
+
+#[async_trait]
+impl<M> Executor<SubmitBackrunBundle> for MevShareExecutor<M>
+where
+    M: Middleware + 'static,
+    M::Error: 'static,
+{
+    /// Sign the backrun and send it bundled behind its target transaction.
+    #[instrument(skip_all, fields(correlation_id = %action.correlation_id))]
+    async fn execute(&self, mut action: SubmitBackrunBundle) -> Result<()> {
+        let Some(in_flight) = self.shutdown.start() else {
+            return Err(anyhow!("Shutting down, not sending bundle"));
+        };
+        info!(
+            "Executing backrun of {:?}: {:?}",
+            action.target_tx, action.tx
+        );
+        if action.tx.gas().is_none() {
+            action.tx.set_gas(BACKRUN_GAS_LIMIT);
+        }
+        self.client
+            .fill_transaction(&mut action.tx, None)
+            .await
+            .context("Error filling backrun tx")?;
+        let from = *action
+            .tx
+            .from()
+            .ok_or(anyhow!("Backrun tx has no sender"))?;
+        let signature = self
+            .client
+            .sign_transaction(&action.tx, from)
+            .await
+            .context("Error signing backrun tx")?;
+        let raw_tx = action.tx.rlp_signed(&signature);
+
+        let block = self
+            .client
+            .get_block_number()
+            .await
+            .context("Error getting block number")?
+            + 1;
+        let body = bundle_request(action.target_tx, &raw_tx, block);
+        let body = serde_json::to_vec(&body)?;
+
+        // the relay authenticates searchers by a signature over the request body
+        let body_hash = format!("0x{}", hex::encode(keccak256(&body)));
+        let auth = self.auth_signer.sign_message(body_hash).await?;
+        if let Some(reason) = self.kill_switch.reason() {
+            return Err(anyhow!("Halted, not sending bundle: {}", reason));
+        }
+        let response: Value = self
+            .http
+            .post(&self.relay_url)
+            .header("Content-Type", "application/json")
+            .header(
+                "X-Flashbots-Signature",
+                format!("{:?}:0x{}", self.auth_signer.address(), auth),
+            )
+            .body(body)
+            .send()
+            .await?
+            .json()
+            .await?;
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!("Error sending bundle: {}", error));
+        }
+        info!("Sent backrun bundle: {}", response);
+
+        // only liquidations are recorded
+        let Some(((history, chain_id), info)) = self.history.clone().zip(action.liquidation) else {
+            return Ok(());
+        };
+        let tx_hash = H256::from(keccak256(&raw_tx));
+        let record = |status| {
+            LiquidationRecord::new(
+                &info,
+                action.correlation_id.clone(),
+                chain_id,
+                tx_hash,
+                status,
+            )
+        };
+        if let Err(e) = history.append(&record(LiquidationStatus::Attempted)) {
+            error!("Error recording backrun {:?}: {}", tx_hash, e);
+        }
+        let mut outcome = record(LiquidationStatus::Dropped);
+        let client = self.client.clone();
+        let valuation = self.valuation;
+        let gas_price = action.tx.gas_price().unwrap_or_default();
+        tokio::spawn(
+            async move {
+                // shutdown waits until the outcome is recorded
+                let _in_flight = in_flight;
+                match wait_for_bundle(client.clone(), tx_hash, block + BACKRUN_BLOCK_RANGE).await {
+                    Ok(Some(receipt)) => {
+                        let prices = settlement_prices(client, valuation, &info, &receipt).await;
+                        // bundles carry no fee rebate program's registration
+                        settle(&mut outcome, &info, &receipt, gas_price, 0, prices);
+                    }
+                    Ok(None) => info!("Backrun {:?} didn't land", tx_hash),
+                    Err(e) => {
+                        error!("Error waiting for backrun {:?}: {}", tx_hash, e);
+                        return;
+                    }
+                }
+                info!("Backrun outcome: {:?}", outcome);
+                if let Err(e) = history.append(&outcome) {
+                    error!("Error recording backrun {:?}: {}", tx_hash, e);
+                }
+            }
+            .instrument(Span::current()),
+        );
+        Ok(())
+    }
+}
+
+// the backrun's receipt, none once the bundle's last block has passed without it
+async fn wait_for_bundle<M: Middleware>(
+    client: Arc<M>,
+    tx_hash: H256,
+    max_block: U64,
+) -> Result<Option<TransactionReceipt>> {
+    loop {
+        if let Some(receipt) = client
+            .get_transaction_receipt(tx_hash)
+            .await
+            .map_err(|e| anyhow!("Error getting receipt: {}", e))?
+        {
+            return Ok(Some(receipt));
+        }
+        let block = client
+            .get_block_number()
+            .await
+            .map_err(|e| anyhow!("Error getting block number: {}", e))?;
+        if block > max_block {
+            return Ok(None);
+        }
+        tokio::time::sleep(Duration::from_secs(BUNDLE_POLL_SECS)).await;
+    }
+}
+
+// `mev_sendBundle` request landing `raw_tx` right after `target_tx`
+fn bundle_request(target_tx: H256, raw_tx: &[u8], block: U64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "mev_sendBundle",
+        "params": [{
+            "version": "v0.1",
+            "inclusion": {
+                "block": block,
+                "maxBlock": block + BACKRUN_BLOCK_RANGE,
+            },
+            "body": [
+                { "hash": target_tx },
+                { "tx": format!("0x{}", hex::encode(raw_tx)), "canRevert": false },
+            ],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_request() {
+        let request = bundle_request(H256::repeat_byte(1), &[0xab, 0xcd], U64::from(100));
+        let params = &request["params"][0];
+
+        assert_eq!(request["method"], "mev_sendBundle");
+        assert_eq!(params["inclusion"]["block"], "0x64");
+        assert_eq!(params["inclusion"]["maxBlock"], "0x66");
+        assert_eq!(
+            params["body"][0]["hash"],
+            format!("{:?}", H256::repeat_byte(1))
+        );
+        assert_eq!(params["body"][1]["tx"], "0xabcd");
+    }
+}
@@ -1 +1,4 @@
+pub mod l1_fee;
+pub mod mev_share_executor;
+pub mod private_tx;
 pub mod protect_executor;
@@ -0,0 +1,273 @@
+use anyhow::{anyhow, Result};
+use ethers::types::U64;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info};
+
+pub const PRIVATE_RELAYS_FILE: &str = "private_relays.json";
+
+/// Blocks a private tx is given to land before it is sent to the public mempool.
+pub const DEFAULT_FALLBACK_BLOCKS: u64 = 3;
+
+pub const POLYGON_CHAIN_ID: u64 = 137;
+pub const BNB_CHAIN_ID: u64 = 56;
+
+/// Block time of BNB Chain, Puissant bundles expire by timestamp rather than block.
+pub const BNB_BLOCK_TIME_SECS: u64 = 3;
+
+/// Private endpoints take different requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayKind {
+    /// bloXroute protect, `blxr_private_tx` or its BNB Chain and Polygon variants.
+    Bloxroute,
+    /// A builder or protect RPC taking `eth_sendPrivateTransaction`.
+    Builder,
+    /// A relay taking single-tx `eth_sendBundle`, like Marlin on Polygon or the BNB Chain
+    /// builders.
+    Bundle,
+    /// BNB48 Puissant, `eth_sendPuissant`, BNB Chain only.
+    Puissant,
+}
+
+impl RelayKind {
+    pub fn supports_chain(&self, chain_id: u64) -> bool {
+        match self {
+            RelayKind::Puissant => chain_id == BNB_CHAIN_ID,
+            _ => true,
+        }
+    }
+}
+
+/// A private endpoint txs are sent to instead of the public mempool.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrivateRelay {
+    pub kind: RelayKind,
+    pub url: String,
+    /// Sent as the `Authorization` header, bloXroute requires one.
+    #[serde(default)]
+    pub auth_header: Option<String>,
+}
+
+/// Private endpoints of a chain, and how long txs sent to them have to land.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrivateRelays {
+    #[serde(skip)]
+    pub chain_id: u64,
+    pub relays: Vec<PrivateRelay>,
+    #[serde(default = "default_fallback_blocks")]
+    pub fallback_blocks: u64,
+}
+
+fn default_fallback_blocks() -> u64 {
+    DEFAULT_FALLBACK_BLOCKS
+}
+
+/// Read the private endpoints of `chain_id` from a JSON file keyed by chain id, `None`
+/// if there is no file or it has none for the chain. Relays the chain doesn't have are
+/// an error.
+pub fn read_private_relays(path: &Path, chain_id: u64) -> Result<Option<PrivateRelays>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut chains: HashMap<String, PrivateRelays> = serde_json::from_reader(file)
+        .map_err(|e| anyhow!("Failed to parse private relays: {}", e))?;
+    let Some(mut relays) = chains
+        .remove(&chain_id.to_string())
+        .filter(|chain| !chain.relays.is_empty())
+    else {
+        return Ok(None);
+    };
+    if let Some(relay) = relays
+        .relays
+        .iter()
+        .find(|relay| !relay.kind.supports_chain(chain_id))
+    {
+        return Err(anyhow!(
+            "{:?} relay {} isn't available on chain {}",
+            relay.kind,
+            relay.url,
+            chain_id
+        ));
+    }
+    relays.chain_id = chain_id;
+    Ok(Some(relays))
+}
+
+/// Send a signed tx to every private endpoint, succeeding if any of them accepted it.
+pub async fn send_private(
+    http: &reqwest::Client,
+    relays: &PrivateRelays,
+    raw_tx: &[u8],
+    max_block: U64,
+) -> Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Invalid timestamp")
+        .as_secs();
+    let expiry = Expiry {
+        block: max_block,
+        timestamp: now + relays.fallback_blocks * BNB_BLOCK_TIME_SECS,
+    };
+    let mut accepted = 0;
+    for relay in relays.relays.iter() {
+        let mut request = http
+            .post(&relay.url)
+            .header("Content-Type", "application/json")
+            .json(&relay_request(relay.kind, relays.chain_id, raw_tx, expiry));
+        if let Some(auth_header) = &relay.auth_header {
+            request = request.header("Authorization", auth_header);
+        }
+        let response: Result<Value> = async { Ok(request.send().await?.json().await?) }.await;
+        match response {
+            Ok(response) if response.get("error").is_none() => {
+                info!("Sent private tx to {}: {}", relay.url, response);
+                accepted += 1;
+            }
+            Ok(response) => error!("Error sending private tx to {}: {}", relay.url, response),
+            Err(e) => error!("Error sending private tx to {}: {}", relay.url, e),
+        }
+    }
+    if accepted == 0 {
+        return Err(anyhow!("No private relay accepted the tx"));
+    }
+    Ok(())
+}
+
+// last block, or unix time for endpoints counting in time, a private tx may land in
+#[derive(Debug, Clone, Copy)]
+struct Expiry {
+    block: U64,
+    timestamp: u64,
+}
+
+// request sending `raw_tx` privately on `chain_id`, until `expiry` where the endpoint supports it
+fn relay_request(kind: RelayKind, chain_id: u64, raw_tx: &[u8], expiry: Expiry) -> Value {
+    let tx = format!("0x{}", hex::encode(raw_tx));
+    match kind {
+        RelayKind::Bloxroute => json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": match chain_id {
+                BNB_CHAIN_ID => "bsc_private_tx",
+                POLYGON_CHAIN_ID => "polygon_private_tx",
+                _ => "blxr_private_tx",
+            },
+            "params": {
+                "transaction": hex::encode(raw_tx),
+            },
+        }),
+        RelayKind::Builder => json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendPrivateTransaction",
+            "params": [{
+                "tx": tx,
+                "maxBlockNumber": expiry.block,
+            }],
+        }),
+        RelayKind::Bundle => json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendBundle",
+            "params": [{
+                "txs": [tx],
+                "maxBlockNumber": expiry.block.as_u64(),
+            }],
+        }),
+        RelayKind::Puissant => json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "eth_sendPuissant",
+            "params": [{
+                "txs": [tx],
+                "maxTimestamp": expiry.timestamp,
+                "acceptReverting": [],
+            }],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_request() {
+        let raw_tx = [0xab, 0xcd];
+        let expiry = Expiry {
+            block: U64::from(100),
+            timestamp: 1_700_000_000,
+        };
+        let request = relay_request(RelayKind::Builder, 1, &raw_tx, expiry);
+        assert_eq!(request["method"], "eth_sendPrivateTransaction");
+        assert_eq!(request["params"][0]["tx"], "0xabcd");
+        assert_eq!(request["params"][0]["maxBlockNumber"], "0x64");
+
+        let request = relay_request(RelayKind::Bloxroute, 1, &raw_tx, expiry);
+        assert_eq!(request["method"], "blxr_private_tx");
+        assert_eq!(request["params"]["transaction"], "abcd");
+        // bloXroute has a method per chain
+        let request = relay_request(RelayKind::Bloxroute, BNB_CHAIN_ID, &raw_tx, expiry);
+        assert_eq!(request["method"], "bsc_private_tx");
+        let request = relay_request(RelayKind::Bloxroute, POLYGON_CHAIN_ID, &raw_tx, expiry);
+        assert_eq!(request["method"], "polygon_private_tx");
+
+        let request = relay_request(RelayKind::Bundle, POLYGON_CHAIN_ID, &raw_tx, expiry);
+        assert_eq!(request["method"], "eth_sendBundle");
+        assert_eq!(request["params"][0]["txs"][0], "0xabcd");
+        assert_eq!(request["params"][0]["maxBlockNumber"], 100);
+
+        let request = relay_request(RelayKind::Puissant, BNB_CHAIN_ID, &raw_tx, expiry);
+        assert_eq!(request["method"], "eth_sendPuissant");
+        assert_eq!(request["params"][0]["txs"][0], "0xabcd");
+        assert_eq!(request["params"][0]["maxTimestamp"], 1_700_000_000);
+    }
+
+    #[test]
+    fn test_read_private_relays() {
+        let path = std::env::temp_dir().join(format!("private-relays-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{
+                "1": {
+                    "relays": [
+                        {"kind": "bloxroute", "url": "https://api.blxrbdn.com", "auth_header": "key"},
+                        {"kind": "builder", "url": "https://rpc.beaverbuild.org"}
+                    ],
+                    "fallback_blocks": 5
+                },
+                "8453": {"relays": [{"kind": "builder", "url": "https://builder.example"}]},
+                "10": {"relays": []},
+                "56": {"relays": [{"kind": "puissant", "url": "https://puissant.example"}]},
+                "137": {"relays": [{"kind": "puissant", "url": "https://puissant.example"}]}
+            }"#,
+        )
+        .unwrap();
+
+        let mainnet = read_private_relays(&path, 1).unwrap().unwrap();
+        assert_eq!(mainnet.chain_id, 1);
+        assert_eq!(mainnet.relays.len(), 2);
+        assert_eq!(mainnet.relays[0].kind, RelayKind::Bloxroute);
+        assert_eq!(mainnet.relays[0].auth_header.as_deref(), Some("key"));
+        assert_eq!(mainnet.fallback_blocks, 5);
+        let base = read_private_relays(&path, 8453).unwrap().unwrap();
+        assert_eq!(base.fallback_blocks, DEFAULT_FALLBACK_BLOCKS);
+        // a chain without relays sends publicly
+        assert!(read_private_relays(&path, 10).unwrap().is_none());
+        assert!(read_private_relays(&path, 43114).unwrap().is_none());
+        assert!(read_private_relays(&path, BNB_CHAIN_ID).unwrap().is_some());
+        // Puissant is BNB Chain only
+        assert!(read_private_relays(&path, POLYGON_CHAIN_ID).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_private_relays(&path, 1).unwrap().is_none());
+    }
+}
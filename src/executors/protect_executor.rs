@@ -1,37 +1,283 @@
-use std::{ops::Mul, sync::Arc};
-use tracing::info;
+use std::{
+    ops::Mul,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, info, instrument, Instrument, Span};
 
-use anyhow::{Context, Result};
-use artemis_core::executors::mempool_executor::SubmitTxToMempool;
+use super::l1_fee::{total_gas_cost, L1Cost, L1FeeModel};
+use super::private_tx::{send_private, PrivateRelays};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::collectors::time_collector::jitter;
+use crate::exposure::ExposureLimiter;
+use crate::history::{
+    estimated_profit, realized_profit, LiquidationHistory, LiquidationInfo, LiquidationRecord,
+    LiquidationStatus, SettlementPrices, Valuation,
+};
+use crate::kill_switch::KillSwitch;
+use crate::shutdown::Shutdown;
+
+use anyhow::{anyhow, Context, Result};
+use artemis_core::executors::mempool_executor::{GasBidInfo, SubmitTxToMempool};
 use artemis_core::types::Executor;
 use async_trait::async_trait;
-use ethers::providers::Middleware;
+use bindings_aave::pool::LiquidationCallFilter;
+use ethers::{
+    contract::parse_log,
+    providers::{Middleware, PendingTransaction},
+    types::{transaction::eip2718::TypedTransaction, Bytes, TransactionReceipt, H256, I256, U256},
+    utils::keccak256,
+};
+use serde::{Deserialize, Serialize};
+
+/// A transaction to send, tagged with the opportunity it came from so its
+/// lifecycle can be traced in the logs.
+#[derive(Debug, Clone)]
+pub struct SubmitTx {
+    pub correlation_id: String,
+    pub submission: SubmitTxToMempool,
+    /// Set for liquidations, so their outcome is recorded in the history.
+    pub liquidation: Option<LiquidationInfo>,
+    /// Sent through the chain's private relays, if it has any, instead of the public mempool.
+    pub private: bool,
+    /// Chain head the opportunity was valued at, the tx is dropped once the chain has
+    /// moved on too far from it. Never stale if unset.
+    pub valued_at: Option<ValuedAt>,
+}
+
+/// Block and time an opportunity was valued at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValuedAt {
+    pub block_number: u64,
+    /// Unix time.
+    pub timestamp: u64,
+}
+
+/// Seconds an opportunity's tx stays fresh by default, three Ethereum slots. Block times
+/// differ too much across chains for a default in blocks.
+pub const DEFAULT_MAX_OPPORTUNITY_AGE_SECS: u64 = 36;
+
+/// How far the chain may move past an opportunity before its tx is stale, prices may
+/// have moved since. Each bound is off if unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MaxOpportunityAge {
+    pub blocks: Option<u64>,
+    pub secs: Option<u64>,
+}
+
+impl MaxOpportunityAge {
+    /// Why an opportunity valued at `valued_at` is stale at `block_number` and `now`, if
+    /// it is.
+    pub fn stale(&self, valued_at: ValuedAt, block_number: u64, now: u64) -> Option<String> {
+        let blocks = block_number.saturating_sub(valued_at.block_number);
+        let secs = now.saturating_sub(valued_at.timestamp);
+        if let Some(max_blocks) = self.blocks.filter(|max_blocks| blocks > *max_blocks) {
+            return Some(format!(
+                "valued {} blocks ago, more than {}",
+                blocks, max_blocks
+            ));
+        }
+        if let Some(max_secs) = self.secs.filter(|max_secs| secs > *max_secs) {
+            return Some(format!("valued {}s ago, more than {}s", secs, max_secs));
+        }
+        None
+    }
+}
 
 /// An executor that sends transactions to the mempool.
 pub struct ProtectExecutor<M, N> {
     client: Arc<M>,
     sender_client: Arc<N>,
+    /// Upper bound of the random delay before sending, in millis.
+    jitter_millis: u64,
+    /// Share of gas fees refunded by the chain, in bps.
+    fee_rebate_bps: u64,
+    /// Where liquidation outcomes are recorded, along with the chain they ran on.
+    history: Option<(LiquidationHistory, u64)>,
+    /// Values landed liquidations at the prices of their block.
+    valuation: Valuation,
+    /// How the chain charges for posting tx data to L1.
+    l1_fee: L1FeeModel,
+    /// Private endpoints of the chain, private txs go public if unset.
+    private_relays: Option<PrivateRelays>,
+    http: reqwest::Client,
+    /// Counts reverted and losing liquidations, stopping submission past its limits.
+    breaker: CircuitBreaker,
+    /// Txs of opportunities older than this are dropped instead of sent.
+    max_age: MaxOpportunityAge,
+    /// Txs are dropped instead of sent while it's engaged.
+    kill_switch: KillSwitch,
+    /// Debt of the liquidations sent and not settled yet, capped.
+    exposure: ExposureLimiter,
+    shutdown: Shutdown,
 }
 
+/// Wei per unit of the strategy's profits, which are valued in the native token with 8
+/// decimals.
+pub const PROFIT_UNIT_WEI: u64 = 10_000_000_000;
+
+/// Longest random delay before sending, liquidations are races so this stays short.
+pub const MAX_SUBMISSION_JITTER_MILLIS: u64 = 1000;
+
 impl<M: Middleware, N: Middleware> ProtectExecutor<M, N> {
     pub fn new(client: Arc<M>, sender_client: Arc<N>) -> Self {
         Self {
             client,
             sender_client,
+            jitter_millis: 0,
+            fee_rebate_bps: 0,
+            history: None,
+            valuation: Valuation::default(),
+            l1_fee: L1FeeModel::None,
+            private_relays: None,
+            http: reqwest::Client::new(),
+            breaker: CircuitBreaker::default(),
+            max_age: MaxOpportunityAge::default(),
+            kill_switch: KillSwitch::default(),
+            exposure: ExposureLimiter::default(),
+            shutdown: Shutdown::new(),
         }
     }
+
+    /// Delay each submission by a random amount, so bots running the same settings don't send in lockstep.
+    pub fn with_jitter_millis(mut self, jitter_millis: u64) -> Self {
+        self.jitter_millis = jitter_millis.min(MAX_SUBMISSION_JITTER_MILLIS);
+        self
+    }
+
+    /// Count gas net of the chain's fee rebate when checking profitability.
+    pub fn with_fee_rebate_bps(mut self, fee_rebate_bps: u64) -> Self {
+        self.fee_rebate_bps = fee_rebate_bps.min(10000);
+        self
+    }
+
+    /// Count the L1 data fee of rollups when checking profitability.
+    pub fn with_l1_fee(mut self, l1_fee: L1FeeModel) -> Self {
+        self.l1_fee = l1_fee;
+        self
+    }
+
+    /// Stop sending once shutdown is requested, and hold it until sent txs are settled.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Record every liquidation sent and its outcome.
+    pub fn with_history(mut self, history: LiquidationHistory, chain_id: u64) -> Self {
+        self.history = Some((history, chain_id));
+        self
+    }
+
+    /// Value landed liquidations with this oracle at the prices of their block.
+    pub fn with_valuation(mut self, valuation: Valuation) -> Self {
+        self.valuation = valuation;
+        self
+    }
+
+    /// Send private txs through these endpoints, and publicly if they don't land in time.
+    pub fn with_private_relays(mut self, private_relays: Option<PrivateRelays>) -> Self {
+        self.private_relays = private_relays;
+        self
+    }
+
+    /// Report the outcome of every liquidation to the breaker.
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    /// Drop txs of opportunities the chain has moved on too far from by the time they
+    /// would be sent, such as those of a slow tick.
+    pub fn with_max_opportunity_age(mut self, max_age: MaxOpportunityAge) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Drop every tx instead of sending it while `kill_switch` is engaged.
+    pub fn with_kill_switch(mut self, kill_switch: KillSwitch) -> Self {
+        self.kill_switch = kill_switch;
+        self
+    }
+
+    /// Commit the debt of every liquidation to `exposure` until it settles, dropping those
+    /// past its cap.
+    pub fn with_exposure_limiter(mut self, exposure: ExposureLimiter) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    // fail if the opportunity went stale while its tx was being prepared
+    async fn check_fresh(&self, valued_at: Option<ValuedAt>) -> Result<()> {
+        let Some(valued_at) = valued_at else {
+            return Ok(());
+        };
+        if self.max_age == MaxOpportunityAge::default() {
+            return Ok(());
+        }
+        let block = self
+            .client
+            .get_block_number()
+            .await
+            .map_err(|e| anyhow!("Error getting block number: {}", e))?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        match self.max_age.stale(valued_at, block.as_u64(), now) {
+            Some(reason) => Err(anyhow!("Opportunity stale, not sending tx: {}", reason)),
+            None => Ok(()),
+        }
+    }
+
+    // sign the tx and send it to the private relays, returning it to go public later
+    async fn send_private(
+        &self,
+        tx: &mut TypedTransaction,
+        relays: &PrivateRelays,
+    ) -> Result<Bytes> {
+        self.sender_client
+            .fill_transaction(tx, None)
+            .await
+            .map_err(|e| anyhow!("Error filling private tx: {}", e))?;
+        let from = *tx.from().ok_or(anyhow!("Private tx has no sender"))?;
+        let signature = self
+            .sender_client
+            .sign_transaction(tx, from)
+            .await
+            .map_err(|e| anyhow!("Error signing private tx: {}", e))?;
+        let raw_tx = tx.rlp_signed(&signature);
+        let block = self
+            .client
+            .get_block_number()
+            .await
+            .map_err(|e| anyhow!("Error getting block number: {}", e))?;
+        send_private(&self.http, relays, &raw_tx, block + relays.fallback_blocks).await?;
+        Ok(raw_tx)
+    }
 }
 
 #[async_trait]
-impl<M, N> Executor<SubmitTxToMempool> for ProtectExecutor<M, N>
+impl<M, N> Executor<SubmitTx> for ProtectExecutor<M, N>
 where
-    M: Middleware,
+    M: Middleware + 'static,
     M::Error: 'static,
-    N: Middleware,
+    N: Middleware + 'static,
     N::Error: 'static,
 {
-    /// Send a transaction to the mempool.
-    async fn execute(&self, mut action: SubmitTxToMempool) -> Result<()> {
+    /// Send a transaction to the mempool, and log its receipt once mined.
+    #[instrument(skip_all, fields(correlation_id = %action.correlation_id))]
+    async fn execute(&self, action: SubmitTx) -> Result<()> {
+        let SubmitTx {
+            correlation_id,
+            submission: mut action,
+            liquidation,
+            private,
+            valued_at,
+        } = action;
+        let Some(in_flight) = self.shutdown.start() else {
+            anyhow::bail!("Shutting down, not sending tx");
+        };
         info!("Executing tx {:?}", action.tx);
         let gas_usage_result = self
             .client
@@ -42,43 +288,374 @@ where
         info!("Gas Usage {:?}", gas_usage_result);
         let gas_usage = gas_usage_result?;
 
-        let bid_gas_price;
-        if let Some(gas_bid_info) = action.gas_bid_info {
-            // old strategy - doesnt really make sense in l2 environment
-            // // gas price at which we'd break even, meaning 100% of profit goes to validator
-            // let breakeven_gas_price = gas_bid_info.total_profit / gas_usage;
-            // // gas price corresponding to bid percentage
-            // bid_gas_price = breakeven_gas_price
-            //     .mul(gas_bid_info.bid_percentage)
-            //     .div(100);
-            // info!(
-            //     "Gas bid info: {:?}, breakeven gas price: {}, bid gas price: {}",
-            //     gas_bid_info, breakeven_gas_price, bid_gas_price
-            // );
-
-            // Just use estimated gas price but throw if its too low
-            bid_gas_price = self
-                .client
-                .get_gas_price()
-                .await
-                .context("Error getting gas price: {}")?;
-            let estimated_cost = bid_gas_price.mul(gas_usage);
-            info!(
-                "Gas bid info: {:?}, estimated cost: {}, bid gas price: {}",
-                gas_bid_info, estimated_cost, bid_gas_price
+        let network_gas_price = self
+            .client
+            .get_gas_price()
+            .await
+            .context("Error getting gas price: {}")?;
+        let bid_gas_price = match action.gas_bid_info {
+            Some(gas_bid_info) => {
+                let l1_cost = self
+                    .l1_fee
+                    .estimate(self.client.clone(), &action.tx)
+                    .await
+                    .context("Error estimating L1 fee: {}")?;
+                // never under the network's price, the tx wouldn't be mined
+                let bid_gas_price =
+                    bid_gas_price(&gas_bid_info, gas_usage, l1_cost, self.fee_rebate_bps)
+                        .max(network_gas_price);
+                let estimated_cost =
+                    total_gas_cost(gas_usage, bid_gas_price, l1_cost, self.fee_rebate_bps);
+                info!(
+                    "Gas bid info: {:?}, L1 cost: {:?}, estimated cost net of {} bps rebate: {}, network gas price: {}, bid gas price: {}",
+                    gas_bid_info, l1_cost, self.fee_rebate_bps, estimated_cost, network_gas_price, bid_gas_price
+                );
+                if estimated_cost > gas_bid_info.total_profit.mul(PROFIT_UNIT_WEI) {
+                    anyhow::bail!("Estimated cost of tx is greater than total profit");
+                }
+                bid_gas_price
+            }
+            None => network_gas_price,
+        };
+        // sets both EIP-1559 fees, so the whole bid goes to the priority fee over the base fee
+        action.tx.set_gas_price(bid_gas_price);
+        tokio::time::sleep(jitter(self.jitter_millis)).await;
+        // last chance to drop it, estimating and bidding took time too
+        self.check_fresh(valued_at).await?;
+        if let Some(reason) = self.kill_switch.reason() {
+            anyhow::bail!("Halted, not sending tx: {}", reason);
+        }
+        // held until the tx settles, or fails to send
+        let exposure = match &liquidation {
+            Some(info) => Some(
+                self.exposure
+                    .commit(info.debt_value_base)
+                    .context("Not sending tx")?,
+            ),
+            None => None,
+        };
+        let private = match self.private_relays.as_ref().filter(|_| private) {
+            Some(relays) => match self.send_private(&mut action.tx, relays).await {
+                Ok(raw_tx) => Some((raw_tx, relays.fallback_blocks)),
+                Err(e) => {
+                    error!("Error sending private tx, sending publicly: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let tx_hash = match &private {
+            Some((raw_tx, _)) => H256::from(keccak256(raw_tx)),
+            None => self
+                .sender_client
+                .send_transaction(action.tx, None)
+                .await?
+                .tx_hash(),
+        };
+        info!("Submitted tx {:?}, private: {}", tx_hash, private.is_some());
+
+        // only liquidations are recorded
+        let history = self.history.clone().zip(liquidation);
+        if let Some(((history, chain_id), info)) = &history {
+            let record = LiquidationRecord::new(
+                info,
+                correlation_id.clone(),
+                *chain_id,
+                tx_hash,
+                LiquidationStatus::Attempted,
             );
-            if estimated_cost > gas_bid_info.total_profit.mul(10_000_000_000u64) {
-                anyhow::bail!("Estimated cost of tx is greater than total profit");
+            if let Err(e) = history.append(&record) {
+                error!("Error recording liquidation {:?}: {}", tx_hash, e);
             }
-        } else {
-            bid_gas_price = self
-                .client
-                .get_gas_price()
-                .await
-                .context("Error getting gas price: {}")?;
         }
-        action.tx.set_gas_price(bid_gas_price);
-        self.sender_client.send_transaction(action.tx, None).await?;
+
+        // don't hold up the next submission while waiting to be mined
+        let client = self.sender_client.clone();
+        let fee_rebate_bps = self.fee_rebate_bps;
+        let breaker = self.breaker.clone();
+        let valuation = self.valuation;
+        tokio::spawn(
+            async move {
+                // shutdown waits until the outcome is recorded
+                let _in_flight = in_flight;
+                let _exposure = exposure;
+                let receipt = match wait_for_receipt(client.clone(), tx_hash, private).await {
+                    Ok(Some(receipt)) => {
+                        info!(
+                            "Tx {:?} mined in block {:?}, status: {:?}, gas used: {:?}",
+                            tx_hash, receipt.block_number, receipt.status, receipt.gas_used
+                        );
+                        Some(receipt)
+                    }
+                    Ok(None) => {
+                        info!("Tx {:?} dropped", tx_hash);
+                        None
+                    }
+                    Err(e) => {
+                        error!("Error waiting for tx {:?}: {}", tx_hash, e);
+                        return;
+                    }
+                };
+
+                let Some(((history, chain_id), info)) = history else {
+                    return;
+                };
+                let mut record = LiquidationRecord::new(
+                    &info,
+                    correlation_id,
+                    chain_id,
+                    tx_hash,
+                    LiquidationStatus::Dropped,
+                );
+                if let Some(receipt) = receipt {
+                    let prices = settlement_prices(client, valuation, &info, &receipt).await;
+                    settle(
+                        &mut record,
+                        &info,
+                        &receipt,
+                        bid_gas_price,
+                        fee_rebate_bps,
+                        prices,
+                    );
+                }
+                breaker.record_outcome(record.status, record.realized_profit);
+                info!("Liquidation outcome: {:?}", record);
+                if let Err(e) = history.append(&record) {
+                    error!("Error recording liquidation {:?}: {}", tx_hash, e);
+                }
+            }
+            .instrument(Span::current()),
+        );
         Ok(())
     }
 }
+
+// wait for a tx to be mined, sending a private one publicly if it didn't land in time
+async fn wait_for_receipt<N: Middleware>(
+    client: Arc<N>,
+    tx_hash: H256,
+    private: Option<(Bytes, u64)>,
+) -> Result<Option<TransactionReceipt>> {
+    if let Some((raw_tx, fallback_blocks)) = private {
+        let deadline = client
+            .get_block_number()
+            .await
+            .map_err(|e| anyhow!("Error getting block number: {}", e))?
+            + fallback_blocks;
+        loop {
+            if let Some(receipt) = client
+                .get_transaction_receipt(tx_hash)
+                .await
+                .map_err(|e| anyhow!("Error getting receipt: {}", e))?
+            {
+                return Ok(Some(receipt));
+            }
+            let block = client
+                .get_block_number()
+                .await
+                .map_err(|e| anyhow!("Error getting block number: {}", e))?;
+            if block >= deadline {
+                break;
+            }
+            tokio::time::sleep(client.provider().get_interval()).await;
+        }
+        info!(
+            "Private tx {:?} didn't land in {} blocks, sending publicly",
+            tx_hash, fallback_blocks
+        );
+        client
+            .send_raw_transaction(raw_tx)
+            .await
+            .map_err(|e| anyhow!("Error sending tx publicly: {}", e))?;
+    }
+    Ok(PendingTransaction::new(tx_hash, client.provider()).await?)
+}
+
+/// Oracle prices of a mined liquidation's assets in its block, none if they can't be read.
+pub(crate) async fn settlement_prices<N: Middleware + 'static>(
+    client: Arc<N>,
+    valuation: Valuation,
+    info: &LiquidationInfo,
+    receipt: &TransactionReceipt,
+) -> Option<SettlementPrices> {
+    let block = receipt.block_number?;
+    match valuation.prices(client, info, block.into()).await {
+        Ok(prices) => Some(prices),
+        Err(e) => {
+            error!(
+                "Error reading prices of block {}, estimating profit: {}",
+                block, e
+            );
+            None
+        }
+    }
+}
+
+/// Fill in what the mined liquidation actually covered, seized and paid, valuing it at
+/// `prices` if read.
+pub(crate) fn settle(
+    record: &mut LiquidationRecord,
+    info: &LiquidationInfo,
+    receipt: &TransactionReceipt,
+    gas_price: U256,
+    fee_rebate_bps: u64,
+    prices: Option<SettlementPrices>,
+) {
+    let gas_cost =
+        receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or(gas_price);
+    // OP stack receipts carry the L1 data fee paid on top of gas
+    let l1_fee: U256 = receipt
+        .other
+        .get_deserialized("l1Fee")
+        .and_then(|fee| fee.ok())
+        .unwrap_or_default();
+    record.gas_paid = net_gas_cost(gas_cost, fee_rebate_bps) + l1_fee;
+
+    let liquidation = receipt
+        .logs
+        .iter()
+        .filter_map(|log| parse_log::<LiquidationCallFilter>(log.clone()).ok())
+        .find(|event| event.user == info.borrower);
+    match liquidation {
+        Some(event) if receipt.status == Some(1.into()) => {
+            record.status = LiquidationStatus::Landed;
+            record.debt_covered = event.debt_to_cover;
+            record.collateral_seized = event.liquidated_collateral_amount;
+            record.realized_profit = prices
+                .and_then(|prices| {
+                    realized_profit(
+                        info,
+                        event.debt_to_cover,
+                        event.liquidated_collateral_amount,
+                        &prices,
+                        record.gas_paid,
+                    )
+                })
+                .unwrap_or_else(|| estimated_profit(info, event.debt_to_cover, record.gas_paid));
+        }
+        _ => {
+            record.status = LiquidationStatus::Reverted;
+            record.realized_profit = -I256::try_from(record.gas_paid).unwrap_or(I256::MAX);
+        }
+    }
+}
+
+/// Gas price spending `bid_percentage` of the profit on the tx: the profit share left after
+/// the L1 data fee, over the gas it pays for net of the chain's fee rebate. Zero if the
+/// share doesn't cover the L1 fee or the gas is free.
+pub fn bid_gas_price(
+    gas_bid_info: &GasBidInfo,
+    gas_usage: U256,
+    l1_cost: Option<L1Cost>,
+    fee_rebate_bps: u64,
+) -> U256 {
+    let budget = gas_bid_info
+        .total_profit
+        .saturating_mul(PROFIT_UNIT_WEI.into())
+        * gas_bid_info.bid_percentage.min(100)
+        / 100;
+    let net_bps = U256::from(10000 - fee_rebate_bps.min(10000));
+    // gas units paid for, in bps of the price
+    let (fee, paid_gas_bps) = match l1_cost {
+        None => (U256::zero(), gas_usage * net_bps),
+        Some(L1Cost::Fee(fee)) => (fee, gas_usage * net_bps),
+        Some(L1Cost::Gas(l1_gas)) => {
+            let l1_gas = l1_gas.min(gas_usage);
+            (
+                U256::zero(),
+                (gas_usage - l1_gas) * net_bps + l1_gas * 10000,
+            )
+        }
+    };
+    (budget.saturating_sub(fee) * 10000)
+        .checked_div(paid_gas_bps)
+        .unwrap_or_default()
+}
+
+/// Gas cost left after the chain refunds `fee_rebate_bps` of it.
+pub fn net_gas_cost(gas_cost: U256, fee_rebate_bps: u64) -> U256 {
+    gas_cost - gas_cost * fee_rebate_bps.min(10000) / 10000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_net_gas_cost() {
+        let cost = U256::from(1_000_000);
+        assert_eq!(net_gas_cost(cost, 0), cost);
+        assert_eq!(net_gas_cost(cost, 9000), U256::from(100_000));
+        assert_eq!(net_gas_cost(cost, 20000), U256::zero());
+    }
+
+    #[test]
+    fn test_bid_gas_price() {
+        // 0.001 ETH of profit on 500k gas
+        let bid = |bid_percentage: u64| GasBidInfo {
+            total_profit: U256::from(100_000),
+            bid_percentage,
+        };
+        let gas = U256::from(500_000);
+        assert_eq!(
+            bid_gas_price(&bid(50), gas, None, 0),
+            U256::from(1_000_000_000)
+        );
+        // a raised bid raises the gas price
+        assert_eq!(
+            bid_gas_price(&bid(90), gas, None, 0),
+            U256::from(1_800_000_000)
+        );
+        assert_eq!(
+            bid_gas_price(&bid(150), gas, None, 0),
+            U256::from(2_000_000_000)
+        );
+
+        // the L1 fee comes out of the bid first
+        let fee = Some(L1Cost::Fee(U256::exp10(14)));
+        assert_eq!(
+            bid_gas_price(&bid(50), gas, fee, 0),
+            U256::from(800_000_000)
+        );
+        assert_eq!(bid_gas_price(&bid(5), gas, fee, 0), U256::zero());
+        // a rebate makes gas cheaper, so the same spend bids more
+        assert_eq!(
+            bid_gas_price(&bid(50), gas, None, 5000),
+            U256::from(2_000_000_000)
+        );
+        assert_eq!(bid_gas_price(&bid(50), gas, None, 10000), U256::zero());
+
+        // whatever the bid, it spends its share of the profit
+        for l1_cost in [None, fee, Some(L1Cost::Gas(U256::from(100_000)))] {
+            let price = bid_gas_price(&bid(60), gas, l1_cost, 0);
+            let cost = total_gas_cost(gas, price, l1_cost, 0);
+            let share = U256::from(60) * U256::exp10(13);
+            assert!(cost <= share && share - cost < gas);
+        }
+    }
+
+    #[test]
+    fn test_stale() {
+        let valued_at = ValuedAt {
+            block_number: 100,
+            timestamp: 1_000,
+        };
+        let max_age = MaxOpportunityAge {
+            blocks: Some(2),
+            secs: Some(10),
+        };
+        assert_eq!(max_age.stale(valued_at, 102, 1_004), None);
+        assert_eq!(
+            max_age.stale(valued_at, 103, 1_004),
+            Some("valued 3 blocks ago, more than 2".to_string())
+        );
+        assert_eq!(
+            max_age.stale(valued_at, 101, 1_011),
+            Some("valued 11s ago, more than 10s".to_string())
+        );
+        assert_eq!(
+            MaxOpportunityAge::default().stale(valued_at, 1_000, 10_000),
+            None
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+use bindings_aave::{i_aave_oracle::IAaveOracle, pool::LiquidationCallFilter};
+use bindings_liquidator::erc20::ERC20;
+use ethers::{
+    contract::EthEvent,
+    providers::Middleware,
+    types::{Address, BlockId, Filter, U256},
+};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::collectors::liquidation_call_collector::{decode_liquidation_call, LiquidationCall};
+use crate::strategies::full_math::product_div;
+use crate::strategies::log_range::LogRange;
+
+/// Columns of the `export-liquidations` CSV, in order.
+pub const CSV_HEADER: &str = "block_number,timestamp,tx_hash,sender,liquidator,gas_price,borrower,collateral,collateral_symbol,debt,debt_symbol,collateral_seized,debt_covered,collateral_price,debt_price,collateral_value_base,debt_value_base,bonus_bps";
+
+/// Rows exported between progress logs.
+const PROGRESS_EVERY: usize = 100;
+
+/// A past liquidation on the pool joined with the oracle prices of its block, one row of
+/// the `export-liquidations` dataset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiquidationRow {
+    pub call: LiquidationCall,
+    /// EOA that sent the tx, `call.liquidator` may be its contract.
+    pub sender: Address,
+    /// Gas price the tx paid, in wei.
+    pub gas_price: U256,
+    pub collateral_symbol: String,
+    pub collateral_decimals: u32,
+    pub debt_symbol: String,
+    pub debt_decimals: u32,
+    /// Oracle prices at the block, in base currency.
+    pub collateral_price: U256,
+    pub debt_price: U256,
+}
+
+impl LiquidationRow {
+    /// Collateral seized, valued in base currency.
+    pub fn collateral_value_base(&self) -> U256 {
+        value_base(
+            self.call.collateral_seized,
+            self.collateral_price,
+            self.collateral_decimals,
+        )
+    }
+
+    /// Debt covered, valued in base currency.
+    pub fn debt_value_base(&self) -> U256 {
+        value_base(self.call.debt_covered, self.debt_price, self.debt_decimals)
+    }
+
+    /// Bonus the liquidator realized at oracle prices, before swap and gas costs: the
+    /// collateral seized over the debt covered, less one, in bps. None if the debt has no
+    /// value.
+    pub fn bonus_bps(&self) -> Option<i128> {
+        let ratio_bps = product_div(
+            &[self.collateral_value_base(), U256::from(10000)],
+            &[self.debt_value_base()],
+        )?;
+        Some(i128::try_from(u128::try_from(ratio_bps).ok()?).ok()? - 10000)
+    }
+
+    pub fn to_csv(&self) -> String {
+        [
+            self.call.block_number.to_string(),
+            self.call.timestamp.to_string(),
+            format!("{:?}", self.call.tx_hash),
+            format!("{:?}", self.sender),
+            format!("{:?}", self.call.liquidator),
+            self.gas_price.to_string(),
+            format!("{:?}", self.call.borrower),
+            format!("{:?}", self.call.collateral),
+            csv_field(&self.collateral_symbol),
+            format!("{:?}", self.call.debt),
+            csv_field(&self.debt_symbol),
+            self.call.collateral_seized.to_string(),
+            self.call.debt_covered.to_string(),
+            self.collateral_price.to_string(),
+            self.debt_price.to_string(),
+            self.collateral_value_base().to_string(),
+            self.debt_value_base().to_string(),
+            self.bonus_bps()
+                .map(|bps| bps.to_string())
+                .unwrap_or_default(),
+        ]
+        .join(",")
+    }
+}
+
+fn value_base(amount: U256, price: U256, decimals: u32) -> U256 {
+    product_div(&[amount, price], &[U256::exp10(decimals as usize)]).unwrap_or(U256::MAX)
+}
+
+// quote a field holding a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Write the `LiquidationCall`s of `pool` from `from_block` to `to_block` to `writer` as
+/// CSV, each joined with the prices `oracle` gave at its block, which takes an archive
+/// node for old blocks. The number of liquidations written.
+pub async fn export_liquidations<M: Middleware + 'static>(
+    client: Arc<M>,
+    pool: Address,
+    oracle: Address,
+    from_block: u64,
+    to_block: u64,
+    log_range: &LogRange,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let logs = log_range
+        .query(
+            from_block.into(),
+            to_block.into(),
+            |start_block, end_block| {
+                let filter = Filter::new()
+                    .address(pool)
+                    .topic0(LiquidationCallFilter::signature())
+                    .from_block(start_block)
+                    .to_block(end_block);
+                let client = client.clone();
+                async move { client.get_logs(&filter).await }
+            },
+        )
+        .await?;
+    info!(
+        "Exporting {} liquidations from block {} to {}",
+        logs.len(),
+        from_block,
+        to_block
+    );
+
+    let oracle = IAaveOracle::new(oracle, client.clone());
+    let mut tokens: HashMap<Address, (String, u32)> = HashMap::new();
+    let mut prices: HashMap<(u64, Address), U256> = HashMap::new();
+    let mut timestamps: HashMap<u64, u64> = HashMap::new();
+    writeln!(writer, "{}", CSV_HEADER)?;
+    let mut count = 0;
+    for log in logs {
+        let Some(mut call) = decode_liquidation_call(log, 0) else {
+            continue;
+        };
+        let block = BlockId::from(call.block_number);
+        if !timestamps.contains_key(&call.block_number) {
+            let timestamp = client
+                .get_block(call.block_number)
+                .await?
+                .ok_or(anyhow!("Block {} not found", call.block_number))?
+                .timestamp
+                .as_u64();
+            timestamps.insert(call.block_number, timestamp);
+        }
+        call.timestamp = timestamps[&call.block_number];
+        for asset in [call.collateral, call.debt] {
+            if !tokens.contains_key(&asset) {
+                let token = ERC20::new(asset, client.clone());
+                // some tokens return their symbol as bytes32
+                let symbol = token.symbol().call().await.unwrap_or_default();
+                let decimals = token.decimals().call().await?;
+                tokens.insert(asset, (symbol, decimals.into()));
+            }
+            if !prices.contains_key(&(call.block_number, asset)) {
+                let price = oracle.get_asset_price(asset).block(block).call().await?;
+                prices.insert((call.block_number, asset), price);
+            }
+        }
+        let tx = client
+            .get_transaction(call.tx_hash)
+            .await?
+            .ok_or(anyhow!("Tx {:?} not found", call.tx_hash))?;
+        let (collateral_symbol, collateral_decimals) = tokens[&call.collateral].clone();
+        let (debt_symbol, debt_decimals) = tokens[&call.debt].clone();
+        let row = LiquidationRow {
+            sender: tx.from,
+            gas_price: tx.gas_price.unwrap_or_default(),
+            collateral_symbol,
+            collateral_decimals,
+            debt_symbol,
+            debt_decimals,
+            collateral_price: prices[&(call.block_number, call.collateral)],
+            debt_price: prices[&(call.block_number, call.debt)],
+            call,
+        };
+        writeln!(writer, "{}", row.to_csv())?;
+        count += 1;
+        if count.is_multiple_of(PROGRESS_EVERY) {
+            info!(
+                "Exported {} liquidations, up to block {}",
+                count, row.call.block_number
+            );
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::H256;
+
+    fn row() -> LiquidationRow {
+        LiquidationRow {
+            call: LiquidationCall {
+                tx_hash: H256::repeat_byte(7),
+                block_number: 100,
+                liquidator: Address::repeat_byte(1),
+                borrower: Address::repeat_byte(2),
+                collateral: Address::repeat_byte(3),
+                debt: Address::repeat_byte(4),
+                // 1000 USDC for 0.35 WETH
+                debt_covered: U256::from(1000) * U256::exp10(6),
+                collateral_seized: U256::from(35) * U256::exp10(16),
+                timestamp: 1700000000,
+            },
+            sender: Address::repeat_byte(5),
+            gas_price: U256::from(1_000_000_000u64),
+            collateral_symbol: "WETH".to_string(),
+            collateral_decimals: 18,
+            debt_symbol: "USDC".to_string(),
+            debt_decimals: 6,
+            collateral_price: U256::from(3000) * U256::exp10(8),
+            debt_price: U256::exp10(8),
+        }
+    }
+
+    #[test]
+    fn test_bonus() {
+        let row = row();
+        assert_eq!(
+            row.collateral_value_base(),
+            U256::from(1050) * U256::exp10(8)
+        );
+        assert_eq!(row.debt_value_base(), U256::from(1000) * U256::exp10(8));
+        assert_eq!(row.bonus_bps(), Some(500));
+        let unpriced = LiquidationRow {
+            debt_price: U256::zero(),
+            ..row
+        };
+        assert_eq!(unpriced.bonus_bps(), None);
+    }
+
+    #[test]
+    fn test_csv() {
+        let line = row().to_csv();
+        assert_eq!(line.split(',').count(), CSV_HEADER.split(',').count());
+        assert!(line.starts_with("100,1700000000,0x0707"));
+        assert!(line.ends_with(",500"));
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("USDC"), "USDC");
+    }
+}
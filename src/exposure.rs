@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use ethers::types::U256;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// Capital committed to liquidations in flight, published for the admin API.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExposureStatus {
+    /// Most debt in flight at once, in base currency. Unlimited if unset.
+    pub max_base: Option<U256>,
+    /// Debt of the liquidations sent and not settled yet, in base currency.
+    pub in_flight_base: U256,
+    pub in_flight_txs: usize,
+}
+
+#[derive(Debug, Default)]
+struct Exposure {
+    max_base: Option<U256>,
+    /// Debt value of each liquidation in flight, by commitment.
+    in_flight: HashMap<u64, U256>,
+    next_id: u64,
+}
+
+impl Exposure {
+    fn total(&self) -> U256 {
+        self.in_flight
+            .values()
+            .fold(U256::zero(), |total, value| total.saturating_add(*value))
+    }
+}
+
+/// Caps the debt committed to liquidations sent and not yet mined, refusing new ones past
+/// the cap until earlier ones settle, so one bad tick can't commit the whole wallet.
+/// Shared between the executor committing txs as it sends them and the strategy, which
+/// passes on opportunities that wouldn't fit.
+#[derive(Debug, Clone, Default)]
+pub struct ExposureLimiter {
+    /// The cap in USD, none for no cap.
+    max_usd: Option<u64>,
+    exposure: Arc<Mutex<Exposure>>,
+}
+
+impl ExposureLimiter {
+    pub fn new(max_usd: Option<u64>) -> Self {
+        Self {
+            max_usd,
+            exposure: Default::default(),
+        }
+    }
+
+    pub fn max_usd(&self) -> Option<u64> {
+        self.max_usd
+    }
+
+    /// Set the cap in base currency from the oracle's unit of one USD.
+    pub fn set_base_currency_unit(&self, base_currency_unit: U256) {
+        let Some(max_usd) = self.max_usd else {
+            return;
+        };
+        let max_base = base_currency_unit * max_usd;
+        self.exposure.lock().unwrap().max_base = Some(max_base);
+        info!(
+            "Committing at most ${} ({} in base currency) to liquidations in flight",
+            max_usd, max_base
+        );
+    }
+
+    /// Why committing `value` (in base currency) more would go over the cap, none if it
+    /// fits.
+    pub fn check(&self, value: U256) -> Option<String> {
+        check(&self.exposure.lock().unwrap(), self.max_usd, value)
+    }
+
+    /// Commit `value` (in base currency) to a liquidation about to be sent, until the
+    /// returned guard is dropped once it settles. Errors if it would go over the cap.
+    pub fn commit(&self, value: U256) -> Result<ExposureGuard> {
+        let mut exposure = self.exposure.lock().unwrap();
+        if let Some(reason) = check(&exposure, self.max_usd, value) {
+            return Err(anyhow!(reason));
+        }
+        let id = exposure.next_id;
+        exposure.next_id += 1;
+        exposure.in_flight.insert(id, value);
+        Ok(ExposureGuard {
+            limiter: self.clone(),
+            id,
+        })
+    }
+
+    pub fn status(&self) -> ExposureStatus {
+        let exposure = self.exposure.lock().unwrap();
+        ExposureStatus {
+            max_base: exposure.max_base,
+            in_flight_base: exposure.total(),
+            in_flight_txs: exposure.in_flight.len(),
+        }
+    }
+}
+
+fn check(exposure: &Exposure, max_usd: Option<u64>, value: U256) -> Option<String> {
+    max_usd?;
+    // the cap is unknown until the oracle's base currency is
+    let Some(max_base) = exposure.max_base else {
+        return Some("exposure cap not loaded".to_string());
+    };
+    let in_flight = exposure.total();
+    (in_flight.saturating_add(value) > max_base).then(|| {
+        format!(
+            "{} in flight plus {} would exceed the exposure cap of {}",
+            in_flight, value, max_base
+        )
+    })
+}
+
+/// Capital committed to a liquidation in flight, released when dropped.
+#[derive(Debug)]
+pub struct ExposureGuard {
+    limiter: ExposureLimiter,
+    id: u64,
+}
+
+impl Drop for ExposureGuard {
+    fn drop(&mut self) {
+        if let Ok(mut exposure) = self.limiter.exposure.lock() {
+            exposure.in_flight.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposure_limiter() {
+        let usd = |amount: u64| U256::from(amount) * U256::exp10(8);
+        let limiter = ExposureLimiter::new(Some(1000));
+        assert!(limiter.check(U256::one()).is_some());
+        assert!(limiter.commit(U256::one()).is_err());
+        limiter.set_base_currency_unit(U256::exp10(8));
+
+        let first = limiter.commit(usd(600)).unwrap();
+        let second = limiter.commit(usd(400)).unwrap();
+        assert_eq!(limiter.status().in_flight_base, usd(1000));
+        assert_eq!(limiter.status().in_flight_txs, 2);
+        assert!(limiter.check(U256::one()).is_some());
+        assert!(limiter.commit(U256::one()).is_err());
+
+        // settling releases the capital
+        drop(first);
+        assert!(limiter.check(usd(600)).is_none());
+        assert!(limiter.check(usd(601)).is_some());
+        drop(second);
+        assert_eq!(limiter.status().in_flight_base, U256::zero());
+        assert_eq!(limiter.status().max_base, Some(usd(1000)));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let limiter = ExposureLimiter::default();
+        let _guard = limiter.commit(U256::MAX).unwrap();
+        let _guard = limiter.commit(U256::MAX).unwrap();
+        assert!(limiter.check(U256::MAX).is_none());
+        assert_eq!(limiter.status().in_flight_base, U256::MAX);
+    }
+}
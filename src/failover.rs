@@ -0,0 +1,384 @@
+use crate::retry::{classify, ErrorKind};
+use async_trait::async_trait;
+use ethers::providers::JsonRpcClient;
+use ethers::types::U64;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Consecutive failed requests after which an endpoint is skipped until it recovers.
+pub const UNHEALTHY_AFTER_ERRORS: u32 = 3;
+/// Blocks an endpoint may trail the best one of its pool by before it's skipped.
+pub const MAX_BLOCK_LAG: u64 = 5;
+
+// methods sent to the submission endpoints, when there are any
+const SUBMIT_METHODS: [&str; 2] = ["eth_sendRawTransaction", "eth_sendTransaction"];
+
+// methods sent to the heavy read endpoints, when there are any: log scans, multicalls
+// and simulations
+const HEAVY_READ_METHODS: [&str; 2] = ["eth_getLogs", "eth_call"];
+
+#[derive(Debug)]
+struct Endpoint<T> {
+    /// Host of the url, the full url may hold an API key.
+    name: String,
+    client: T,
+    consecutive_errors: AtomicU32,
+    /// Moving average of the response time.
+    latency_ms: AtomicU64,
+    /// Too far behind the chain head at the last health check.
+    lagging: AtomicBool,
+}
+
+impl<T> Endpoint<T> {
+    fn is_healthy(&self) -> bool {
+        self.consecutive_errors.load(Ordering::Relaxed) < UNHEALTHY_AFTER_ERRORS
+            && !self.lagging.load(Ordering::Relaxed)
+    }
+
+    fn record_success(&self, latency: Duration) {
+        self.consecutive_errors.store(0, Ordering::Relaxed);
+        let sample = latency.as_millis() as u64;
+        let average = self.latency_ms.load(Ordering::Relaxed);
+        let average = if average == 0 {
+            sample
+        } else {
+            (average * 7 + sample) / 8
+        };
+        self.latency_ms.store(average, Ordering::Relaxed);
+    }
+
+    fn record_error(&self) {
+        self.consecutive_errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The read endpoints as of the last health check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RpcHealth {
+    /// Unix time of the check, 0 until the first one.
+    pub checked_at: u64,
+    /// Highest block number an endpoint returned, 0 if none answered.
+    pub head: u64,
+    pub healthy_endpoints: usize,
+    pub endpoints: usize,
+}
+
+pub type SharedRpcHealth = Arc<RwLock<RpcHealth>>;
+
+/// Endpoints serving the same traffic, the first healthy one in configured order is used.
+#[derive(Debug)]
+struct Pool<T> {
+    kind: &'static str,
+    endpoints: Vec<Endpoint<T>>,
+    active: AtomicUsize,
+    /// Failovers of every pool of the transport.
+    failovers: Arc<AtomicU64>,
+}
+
+impl<T> Pool<T> {
+    fn new(kind: &'static str, endpoints: Vec<(String, T)>, failovers: Arc<AtomicU64>) -> Self {
+        Self {
+            kind,
+            endpoints: endpoints
+                .into_iter()
+                .map(|(name, client)| Endpoint {
+                    name,
+                    client,
+                    consecutive_errors: AtomicU32::new(0),
+                    latency_ms: AtomicU64::new(0),
+                    lagging: AtomicBool::new(false),
+                })
+                .collect(),
+            active: AtomicUsize::new(0),
+            failovers,
+        }
+    }
+
+    // healthy endpoints first, so the pool fails back to the primary once it recovers,
+    // unhealthy ones are still tried as a last resort
+    fn order(&self) -> Vec<usize> {
+        let healthy = |healthy: bool| {
+            (0..self.endpoints.len()).filter(move |i| self.endpoints[*i].is_healthy() == healthy)
+        };
+        let order: Vec<usize> = healthy(true).chain(healthy(false)).collect();
+
+        let active = self.active.swap(order[0], Ordering::Relaxed);
+        if active != order[0] {
+            self.failovers.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "{} RPC failing over from {} to {}",
+                self.kind, self.endpoints[active].name, self.endpoints[order[0]].name
+            );
+        }
+        order
+    }
+}
+
+/// A transport spreading requests over several endpoints, failing over to the next one
+/// on rate limits and transient errors and back once health checks see it recover.
+/// Transactions can be sent through separate endpoints from reads, and heavy reads
+/// served by separate endpoints from the rest.
+#[derive(Debug)]
+pub struct FailoverTransport<T> {
+    read: Arc<Pool<T>>,
+    heavy_read: Option<Arc<Pool<T>>>,
+    submit: Option<Arc<Pool<T>>>,
+    failovers: Arc<AtomicU64>,
+    health: SharedRpcHealth,
+}
+
+impl<T> FailoverTransport<T> {
+    /// `endpoints` by name, in order of preference. Panics if there are none.
+    pub fn new(endpoints: Vec<(String, T)>) -> Self {
+        assert!(!endpoints.is_empty(), "no RPC endpoints");
+        let failovers = Arc::new(AtomicU64::new(0));
+        Self {
+            read: Arc::new(Pool::new("read", endpoints, failovers.clone())),
+            heavy_read: None,
+            submit: None,
+            failovers,
+            health: SharedRpcHealth::default(),
+        }
+    }
+
+    /// Send transactions through `endpoints` instead, e.g. a private or low latency RPC.
+    pub fn with_submit_endpoints(mut self, endpoints: Vec<(String, T)>) -> Self {
+        self.submit = (!endpoints.is_empty())
+            .then(|| Arc::new(Pool::new("submit", endpoints, self.failovers.clone())));
+        self
+    }
+
+    /// Serve `eth_getLogs` and `eth_call` from `endpoints` instead, e.g. a cheap archive
+    /// provider, leaving the others for what the executors need.
+    pub fn with_heavy_read_endpoints(mut self, endpoints: Vec<(String, T)>) -> Self {
+        // not counted with the others, these endpoints don't see the sender's nonce
+        self.heavy_read = (!endpoints.is_empty()).then(|| {
+            Arc::new(Pool::new(
+                "heavy read",
+                endpoints,
+                Arc::new(AtomicU64::new(0)),
+            ))
+        });
+        self
+    }
+
+    /// Counts every switch to another endpoint, so state a node keeps for us (like the
+    /// sender's pending nonce) can be re-read after one.
+    pub fn failovers(&self) -> Arc<AtomicU64> {
+        self.failovers.clone()
+    }
+
+    /// The read endpoints as of the last health check.
+    pub fn health(&self) -> SharedRpcHealth {
+        self.health.clone()
+    }
+
+    fn pool(&self, method: &str) -> &Pool<T> {
+        match (&self.submit, &self.heavy_read) {
+            (Some(submit), _) if SUBMIT_METHODS.contains(&method) => submit,
+            (_, Some(heavy_read)) if HEAVY_READ_METHODS.contains(&method) => heavy_read,
+            _ => &self.read,
+        }
+    }
+}
+
+impl<T> FailoverTransport<T>
+where
+    T: JsonRpcClient + 'static,
+{
+    /// Poll every endpoint's block number every `interval`, recording its latency and
+    /// whether it errors or trails the others.
+    pub fn spawn_health_checks(&self, interval: Duration) {
+        let read = self.read.clone();
+        let pools: Vec<Arc<Pool<T>>> = self
+            .heavy_read
+            .clone()
+            .into_iter()
+            .chain(self.submit.clone())
+            .collect();
+        let health = self.health.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let read_health = check_health(&read).await;
+                if let Ok(mut health) = health.write() {
+                    *health = read_health;
+                }
+                for pool in &pools {
+                    check_health(pool).await;
+                }
+            }
+        });
+    }
+}
+
+async fn check_health<T: JsonRpcClient>(pool: &Pool<T>) -> RpcHealth {
+    let mut block_numbers = Vec::new();
+    for endpoint in &pool.endpoints {
+        let started = Instant::now();
+        match endpoint
+            .client
+            .request::<_, U64>("eth_blockNumber", ())
+            .await
+        {
+            Ok(block_number) => {
+                endpoint.record_success(started.elapsed());
+                block_numbers.push(Some(block_number.as_u64()));
+            }
+            Err(e) => {
+                endpoint.record_error();
+                warn!(
+                    "{} RPC {} health check failed: {}",
+                    pool.kind, endpoint.name, e
+                );
+                block_numbers.push(None);
+            }
+        }
+    }
+
+    let head = block_numbers
+        .iter()
+        .flatten()
+        .max()
+        .copied()
+        .unwrap_or_default();
+    for (endpoint, block_number) in pool.endpoints.iter().zip(block_numbers) {
+        let lagging = block_number.is_some_and(|block_number| is_lagging(block_number, head));
+        if endpoint.lagging.swap(lagging, Ordering::Relaxed) != lagging {
+            info!(
+                "{} RPC {} is {} at block {:?}, head {}",
+                pool.kind,
+                endpoint.name,
+                if lagging { "lagging" } else { "caught up" },
+                block_number,
+                head
+            );
+        }
+        info!(
+            rpc = endpoint.name,
+            healthy = endpoint.is_healthy(),
+            latency_ms = endpoint.latency_ms.load(Ordering::Relaxed),
+            "{} RPC health",
+            pool.kind
+        );
+    }
+
+    RpcHealth {
+        checked_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs(),
+        head,
+        healthy_endpoints: pool
+            .endpoints
+            .iter()
+            .filter(|endpoint| endpoint.is_healthy())
+            .count(),
+        endpoints: pool.endpoints.len(),
+    }
+}
+
+/// Whether an endpoint at `block_number` trails the best one at `head` too far.
+pub fn is_lagging(block_number: u64, head: u64) -> bool {
+    head.saturating_sub(block_number) > MAX_BLOCK_LAG
+}
+
+#[async_trait]
+impl<T> JsonRpcClient for FailoverTransport<T>
+where
+    T: JsonRpcClient + 'static,
+{
+    type Error = T::Error;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        // a zero sized `()` is sent without params, as the inner transport does
+        let params = (std::mem::size_of::<P>() != 0).then_some(params);
+        let pool = self.pool(method);
+        let mut last_error = None;
+        for i in pool.order() {
+            let endpoint = &pool.endpoints[i];
+            let started = Instant::now();
+            let result = match &params {
+                Some(params) => endpoint.client.request(method, params).await,
+                None => endpoint.client.request(method, ()).await,
+            };
+            let error = match result {
+                Ok(response) => {
+                    endpoint.record_success(started.elapsed());
+                    return Ok(response);
+                }
+                Err(error) => error,
+            };
+            // a revert or bad request fails the same way on every endpoint
+            if classify(&error) == ErrorKind::Fatal {
+                return Err(error);
+            }
+            endpoint.record_error();
+            warn!(
+                "{} on {} RPC {} failed: {}",
+                method, pool.kind, endpoint.name, error
+            );
+            last_error = Some(error);
+        }
+        Err(last_error.expect("no RPC endpoints"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockError, MockProvider};
+
+    #[tokio::test]
+    async fn test_failover() {
+        let primary = MockProvider::new();
+        let backup = MockProvider::new();
+        let transport = FailoverTransport::new(vec![
+            ("primary".to_string(), primary.clone()),
+            ("backup".to_string(), backup.clone()),
+        ]);
+
+        // the mock errors when it has no response queued
+        backup.push(U64::from(2)).unwrap();
+        let block_number: Result<U64, MockError> = transport.request("eth_blockNumber", ()).await;
+        assert_eq!(block_number.unwrap(), U64::from(2));
+        assert_eq!(transport.failovers().load(Ordering::Relaxed), 0);
+        assert_eq!(
+            transport.read.endpoints[0]
+                .consecutive_errors
+                .load(Ordering::Relaxed),
+            1
+        );
+
+        // transactions go through the submission endpoints
+        let submit = MockProvider::new();
+        submit.push(U64::from(3)).unwrap();
+        let transport = transport.with_submit_endpoints(vec![("submit".to_string(), submit)]);
+        let sent: Result<U64, MockError> = transport.request("eth_sendRawTransaction", ()).await;
+        assert_eq!(sent.unwrap(), U64::from(3));
+
+        // and logs through the heavy read endpoints, without counting as a failover
+        let heavy_read = MockProvider::new();
+        heavy_read.push(U64::from(4)).unwrap();
+        let transport =
+            transport.with_heavy_read_endpoints(vec![("archive".to_string(), heavy_read)]);
+        let logs: Result<U64, MockError> = transport.request("eth_getLogs", ()).await;
+        assert_eq!(logs.unwrap(), U64::from(4));
+        assert_eq!(transport.failovers().load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_is_lagging() {
+        assert!(!is_lagging(100, 100));
+        assert!(!is_lagging(95, 100));
+        assert!(is_lagging(94, 100));
+    }
+}
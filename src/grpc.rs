@@ -0,0 +1,163 @@
+use crate::api::{is_authorized, OpportunityStatus};
+use crate::opportunity_feed::OpportunityFeed;
+use anyhow::Result;
+use ethers::types::I256;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{info, warn};
+
+pub mod proto {
+    tonic::include_proto!("liquidator.v1");
+}
+
+use proto::opportunities_server::{Opportunities, OpportunitiesServer};
+use proto::{AckReply, ExecutionAck, Opportunity, SubscribeRequest};
+
+impl From<OpportunityStatus> for Opportunity {
+    fn from(opportunity: OpportunityStatus) -> Self {
+        Self {
+            correlation_id: opportunity.correlation_id,
+            timestamp: opportunity.timestamp,
+            borrower: format!("{:?}", opportunity.borrower),
+            collateral: format!("{:?}", opportunity.collateral),
+            debt: format!("{:?}", opportunity.debt),
+            debt_to_cover: opportunity.debt_to_cover.to_string(),
+            profit_eth: opportunity.profit_eth,
+            profit: opportunity.profit.unwrap_or_default(),
+            confidence: opportunity.confidence,
+        }
+    }
+}
+
+impl SubscribeRequest {
+    fn allows(&self, opportunity: &OpportunityStatus) -> bool {
+        opportunity.confidence >= self.min_confidence
+            && (!self.profitable_only
+                || I256::from_dec_str(&opportunity.profit_eth)
+                    .is_ok_and(|profit| profit > I256::zero()))
+    }
+}
+
+struct OpportunityService {
+    feed: OpportunityFeed,
+}
+
+#[tonic::async_trait]
+impl Opportunities for OpportunityService {
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<Opportunity, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let filter = request.into_inner();
+        let stream = BroadcastStream::new(self.feed.subscribe()).filter_map(move |opportunity| {
+            match opportunity {
+                Ok(opportunity) => filter.allows(&opportunity).then(|| Ok(opportunity.into())),
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    warn!(
+                        "gRPC subscriber fell behind, skipped {} opportunities",
+                        skipped
+                    );
+                    None
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn ack(&self, request: Request<ExecutionAck>) -> Result<Response<AckReply>, Status> {
+        let ack = request.into_inner();
+        if ack.correlation_id.is_empty() {
+            return Err(Status::invalid_argument("correlation_id is required"));
+        }
+        let known = self.feed.is_recent(&ack.correlation_id);
+        info!(
+            correlation_id = %ack.correlation_id,
+            "Execution ack: {} tx {:?} reason {:?} known {}",
+            ack.outcome().as_str_name(),
+            ack.tx_hash,
+            ack.reason,
+            known
+        );
+        Ok(Response::new(AckReply { known }))
+    }
+}
+
+/// Serve the gRPC API on `address` until it fails. If `token` is set, every call needs it
+/// as a bearer token in its `authorization` metadata, like the admin API.
+pub async fn serve(
+    address: SocketAddr,
+    feed: OpportunityFeed,
+    token: Option<String>,
+) -> Result<()> {
+    let service = OpportunitiesServer::with_interceptor(
+        OpportunityService { feed },
+        move |request: Request<()>| {
+            let authorization = request
+                .metadata()
+                .get("authorization")
+                .and_then(|value| value.to_str().ok());
+            if is_authorized(token.as_deref(), authorization) {
+                Ok(request)
+            } else {
+                Err(Status::unauthenticated("invalid token"))
+            }
+        },
+    );
+    info!("gRPC API listening on {}", address);
+    Server::builder()
+        .add_service(service)
+        .serve(address)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    fn opportunity(correlation_id: &str, profit_eth: &str, confidence: u64) -> OpportunityStatus {
+        OpportunityStatus {
+            correlation_id: correlation_id.to_string(),
+            timestamp: 1700000000,
+            borrower: Address::repeat_byte(1),
+            collateral: Address::repeat_byte(2),
+            debt: Address::repeat_byte(3),
+            debt_to_cover: U256::exp10(18),
+            profit_eth: profit_eth.to_string(),
+            profit: None,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn test_subscribe_filter() {
+        let all = SubscribeRequest::default();
+        assert!(all.allows(&opportunity("a", "-5", 0)));
+        let filter = SubscribeRequest {
+            min_confidence: 5000,
+            profitable_only: true,
+        };
+        assert!(filter.allows(&opportunity("a", "5", 5000)));
+        assert!(!filter.allows(&opportunity("a", "5", 4999)));
+        assert!(!filter.allows(&opportunity("a", "0", 5000)));
+        assert!(!filter.allows(&opportunity("a", "-5", 5000)));
+    }
+
+    #[test]
+    fn test_opportunity_message() {
+        let message = Opportunity::from(opportunity("a", "-5", 9000));
+        assert_eq!(
+            message.borrower,
+            "0x0101010101010101010101010101010101010101"
+        );
+        assert_eq!(message.debt_to_cover, "1000000000000000000");
+        assert_eq!(message.profit_eth, "-5");
+        assert_eq!(message.profit, "");
+    }
+}
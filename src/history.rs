@@ -0,0 +1,469 @@
+use anyhow::{anyhow, Result};
+use bindings_aave::i_aave_oracle::IAaveOracle;
+use clap::{Parser, ValueEnum};
+use ethers::{
+    providers::Middleware,
+    types::{Address, BlockId, H256, I256, U256, U512},
+    utils::format_ether,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const HISTORY_FILE: &str = "liquidations.jsonl";
+
+// the strategy's ETH values have 8 decimals, wei have 18
+const PRICE_TO_WEI: u64 = 10_000_000_000;
+
+/// What a liquidation tx is meant to do, attached to its submission.
+#[derive(Debug, Clone)]
+pub struct LiquidationInfo {
+    pub borrower: Address,
+    pub collateral: Address,
+    pub debt: Address,
+    pub collateral_symbol: String,
+    pub debt_symbol: String,
+    pub collateral_decimals: u64,
+    pub debt_decimals: u64,
+    pub debt_to_cover: U256,
+    /// Value of the debt covered, in the oracle's base currency.
+    pub debt_value_base: U256,
+    /// Expected profit before gas, in ETH with 8 decimals.
+    pub expected_profit_eth: I256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LiquidationStatus {
+    /// Sent, not mined yet.
+    Attempted,
+    Landed,
+    Reverted,
+    /// Never mined.
+    Dropped,
+}
+
+/// One line of the liquidation history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiquidationRecord {
+    pub timestamp: u64,
+    pub chain_id: u64,
+    pub correlation_id: String,
+    pub tx_hash: H256,
+    pub status: LiquidationStatus,
+    pub borrower: Address,
+    pub collateral: Address,
+    pub debt: Address,
+    pub collateral_symbol: String,
+    pub debt_symbol: String,
+    pub debt_covered: U256,
+    pub collateral_seized: U256,
+    /// Gas paid net of the chain's fee rebate, in wei.
+    pub gas_paid: U256,
+    /// Profit after gas, in wei.
+    #[serde(with = "decimal")]
+    pub realized_profit: I256,
+}
+
+impl LiquidationRecord {
+    pub fn new(
+        info: &LiquidationInfo,
+        correlation_id: String,
+        chain_id: u64,
+        tx_hash: H256,
+        status: LiquidationStatus,
+    ) -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs(),
+            chain_id,
+            correlation_id,
+            tx_hash,
+            status,
+            borrower: info.borrower,
+            collateral: info.collateral,
+            debt: info.debt,
+            collateral_symbol: info.collateral_symbol.clone(),
+            debt_symbol: info.debt_symbol.clone(),
+            debt_covered: U256::zero(),
+            collateral_seized: U256::zero(),
+            gas_paid: U256::zero(),
+            realized_profit: I256::zero(),
+        }
+    }
+}
+
+// signed amounts are kept as decimal strings so the file stays readable
+mod decimal {
+    use ethers::types::I256;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &I256, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&value.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<I256, D::Error> {
+        I256::from_dec_str(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Where landed liquidations are valued: the Aave oracle, and WETH to convert its base
+/// currency into the native token.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Valuation {
+    pub oracle: Address,
+    pub weth: Address,
+}
+
+/// Oracle prices of a liquidation's assets and of WETH, in the oracle's base currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettlementPrices {
+    pub collateral: U256,
+    pub debt: U256,
+    pub weth: U256,
+}
+
+impl Valuation {
+    /// Oracle prices of the liquidation's collateral and debt, and of WETH, at `block`.
+    pub async fn prices<M: Middleware + 'static>(
+        &self,
+        client: Arc<M>,
+        info: &LiquidationInfo,
+        block: BlockId,
+    ) -> Result<SettlementPrices> {
+        if self.oracle.is_zero() {
+            return Err(anyhow!("No oracle to value liquidations with"));
+        }
+        let oracle = IAaveOracle::new(self.oracle, client);
+        let prices = oracle
+            .get_assets_prices(vec![info.collateral, info.debt, self.weth])
+            .block(block)
+            .call()
+            .await?;
+        match prices[..] {
+            [collateral, debt, weth] => Ok(SettlementPrices {
+                collateral,
+                debt,
+                weth,
+            }),
+            _ => Err(anyhow!(
+                "Oracle returned {} prices for 3 assets",
+                prices.len()
+            )),
+        }
+    }
+}
+
+/// Profit in wei of a landed liquidation: the collateral seized less the debt covered,
+/// both valued at the oracle's prices of the block it landed in, less gas. None without
+/// a WETH price.
+pub fn realized_profit(
+    info: &LiquidationInfo,
+    debt_covered: U256,
+    collateral_seized: U256,
+    prices: &SettlementPrices,
+    gas_paid: U256,
+) -> Option<I256> {
+    if prices.weth.is_zero() {
+        return None;
+    }
+    let value_wei = |amount: U256, price: U256, decimals: u64| {
+        let value = amount.full_mul(price) * U512::exp10(18)
+            / U256::exp10(decimals as usize).full_mul(prices.weth);
+        U256::try_from(value).map_or(I256::MAX, to_i256)
+    };
+    let seized = value_wei(
+        collateral_seized,
+        prices.collateral,
+        info.collateral_decimals,
+    );
+    let covered = value_wei(debt_covered, prices.debt, info.debt_decimals);
+    Some(seized - covered - to_i256(gas_paid))
+}
+
+/// Profit in wei of a landed liquidation when its block's prices can't be read: the
+/// expected profit scaled to the debt the pool actually let us cover, less gas.
+pub fn estimated_profit(info: &LiquidationInfo, debt_covered: U256, gas_paid: U256) -> I256 {
+    let mut profit = info.expected_profit_eth * I256::from(PRICE_TO_WEI);
+    if !info.debt_to_cover.is_zero() && debt_covered < info.debt_to_cover {
+        profit = profit * to_i256(debt_covered) / to_i256(info.debt_to_cover);
+    }
+    profit - to_i256(gas_paid)
+}
+
+fn to_i256(value: U256) -> I256 {
+    I256::try_from(value).unwrap_or(I256::MAX)
+}
+
+/// Append-only log of attempted and landed liquidations.
+#[derive(Debug, Clone)]
+pub struct LiquidationHistory {
+    path: PathBuf,
+}
+
+impl LiquidationHistory {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, record: &LiquidationRecord) -> Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    pub fn read(&self) -> Result<Vec<LiquidationRecord>> {
+        let mut records = Vec::new();
+        if let Ok(file) = File::open(&self.path) {
+            for line in BufReader::new(file).lines() {
+                records.push(serde_json::from_str(&line?)?);
+            }
+        }
+        Ok(records)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PnlGroup {
+    /// UTC day the tx was mined on.
+    Day,
+    /// Collateral seized.
+    Asset,
+    Chain,
+}
+
+/// Realized profit of one group of liquidations.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PnlRow {
+    pub key: String,
+    /// Mined or dropped txs.
+    pub attempts: u64,
+    pub landed: u64,
+    pub gas_paid: U256,
+    pub realized_profit: I256,
+}
+
+/// Sum up the final outcome of each liquidation, grouped by `group`.
+pub fn summarize(records: &[LiquidationRecord], group: PnlGroup) -> Vec<PnlRow> {
+    let mut rows: BTreeMap<String, PnlRow> = BTreeMap::new();
+    for record in records
+        .iter()
+        .filter(|r| r.status != LiquidationStatus::Attempted)
+    {
+        let key = match group {
+            PnlGroup::Day => chrono::DateTime::from_timestamp(record.timestamp as i64, 0)
+                .map(|time| time.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            PnlGroup::Asset => record.collateral_symbol.clone(),
+            PnlGroup::Chain => record.chain_id.to_string(),
+        };
+        let row = rows.entry(key.clone()).or_insert_with(|| PnlRow {
+            key,
+            ..Default::default()
+        });
+        row.attempts += 1;
+        if record.status == LiquidationStatus::Landed {
+            row.landed += 1;
+        }
+        row.gas_paid += record.gas_paid;
+        row.realized_profit += record.realized_profit;
+    }
+    rows.into_values().collect()
+}
+
+/// Summarize realized profit from the liquidation history.
+#[derive(Parser, Debug)]
+#[command(name = "pnl")]
+pub struct PnlArgs {
+    /// File recording every attempted and landed liquidation, `liquidations.jsonl` in
+    /// `--state-dir` if unset.
+    #[arg(long, env = "HISTORY_FILE")]
+    pub history_file: Option<PathBuf>,
+
+    /// Directory the bot keeps its state in.
+    #[arg(long, env = "STATE_DIR", default_value = ".")]
+    pub state_dir: PathBuf,
+
+    #[arg(long, value_enum, default_value_t = PnlGroup::Day)]
+    pub group_by: PnlGroup,
+}
+
+/// Print the PnL report of the `pnl` subcommand.
+pub fn print_pnl(args: &PnlArgs) -> Result<()> {
+    let history_file = args
+        .history_file
+        .clone()
+        .unwrap_or_else(|| args.state_dir.join(HISTORY_FILE));
+    let records = LiquidationHistory::new(&history_file).read()?;
+    let rows = summarize(&records, args.group_by);
+    println!(
+        "{:<12} {:>8} {:>8} {:>24} {:>24}",
+        "group", "attempts", "landed", "gas paid (ETH)", "profit (ETH)"
+    );
+    for row in rows.iter() {
+        println!(
+            "{:<12} {:>8} {:>8} {:>24} {:>24}",
+            row.key,
+            row.attempts,
+            row.landed,
+            format_ether(row.gas_paid),
+            format_signed_ether(row.realized_profit)
+        );
+    }
+    let total = rows
+        .iter()
+        .fold(I256::zero(), |total, row| total + row.realized_profit);
+    println!("total profit: {} ETH", format_signed_ether(total));
+    Ok(())
+}
+
+fn format_signed_ether(value: I256) -> String {
+    let sign = if value.is_negative() { "-" } else { "" };
+    format!("{}{}", sign, format_ether(value.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: u64, status: LiquidationStatus, profit: i64) -> LiquidationRecord {
+        let info = LiquidationInfo {
+            borrower: Address::repeat_byte(1),
+            collateral: Address::repeat_byte(2),
+            debt: Address::repeat_byte(3),
+            collateral_symbol: "WETH".to_string(),
+            debt_symbol: "USDC".to_string(),
+            collateral_decimals: 18,
+            debt_decimals: 6,
+            debt_to_cover: U256::from(100),
+            debt_value_base: U256::zero(),
+            expected_profit_eth: I256::zero(),
+        };
+        LiquidationRecord {
+            timestamp,
+            gas_paid: U256::from(10),
+            realized_profit: I256::from(profit),
+            ..LiquidationRecord::new(&info, String::new(), 8453, H256::zero(), status)
+        }
+    }
+
+    #[test]
+    fn test_realized_profit() {
+        let info = LiquidationInfo {
+            borrower: Address::zero(),
+            collateral: Address::zero(),
+            debt: Address::zero(),
+            collateral_symbol: String::new(),
+            debt_symbol: String::new(),
+            collateral_decimals: 18,
+            debt_decimals: 6,
+            debt_to_cover: U256::from(1000),
+            debt_value_base: U256::zero(),
+            expected_profit_eth: I256::zero(),
+        };
+        // ETH at $2000, USDC at $1, 8 decimals
+        let prices = SettlementPrices {
+            collateral: U256::from(200_000_000_000u64),
+            debt: U256::from(100_000_000),
+            weth: U256::from(200_000_000_000u64),
+        };
+        let gas = U256::from(1_000_000_000_000_000u64);
+        // 1.05 ETH seized for 2000 USDC, 0.05 ETH less gas
+        assert_eq!(
+            realized_profit(
+                &info,
+                U256::from(2_000_000_000u64),
+                U256::from(1_050_000_000_000_000_000u64),
+                &prices,
+                gas
+            ),
+            Some(I256::from(49_000_000_000_000_000i64))
+        );
+        // ETH fell to $1900 by the block it landed in, the bonus is gone
+        let prices = SettlementPrices {
+            collateral: U256::from(190_000_000_000u64),
+            weth: U256::from(190_000_000_000u64),
+            ..prices
+        };
+        assert_eq!(
+            realized_profit(
+                &info,
+                U256::from(1_900_000_000u64),
+                U256::from(1_000_000_000_000_000_000u64),
+                &prices,
+                gas
+            ),
+            Some(I256::from(-1_000_000_000_000_000i64))
+        );
+        let prices = SettlementPrices {
+            weth: U256::zero(),
+            ..prices
+        };
+        assert_eq!(
+            realized_profit(&info, U256::one(), U256::one(), &prices, gas),
+            None
+        );
+    }
+
+    #[test]
+    fn test_estimated_profit() {
+        let info = LiquidationInfo {
+            borrower: Address::zero(),
+            collateral: Address::zero(),
+            debt: Address::zero(),
+            collateral_symbol: String::new(),
+            debt_symbol: String::new(),
+            collateral_decimals: 18,
+            debt_decimals: 18,
+            debt_to_cover: U256::from(1000),
+            debt_value_base: U256::zero(),
+            // 0.01 ETH
+            expected_profit_eth: I256::from(1_000_000),
+        };
+        let gas = U256::from(1_000_000_000_000_000u64);
+        assert_eq!(
+            estimated_profit(&info, U256::from(1000), gas),
+            I256::from(9_000_000_000_000_000i64)
+        );
+        // the pool only let us cover half the debt
+        assert_eq!(
+            estimated_profit(&info, U256::from(500), gas),
+            I256::from(4_000_000_000_000_000i64)
+        );
+    }
+
+    #[test]
+    fn test_summarize() {
+        let day = 1_700_000_000;
+        let records = vec![
+            record(day, LiquidationStatus::Attempted, 0),
+            record(day, LiquidationStatus::Landed, 100),
+            record(day, LiquidationStatus::Reverted, -10),
+            record(day + 86_400, LiquidationStatus::Landed, 50),
+        ];
+
+        let rows = summarize(&records, PnlGroup::Day);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].key, "2023-11-14");
+        assert_eq!(rows[0].attempts, 2);
+        assert_eq!(rows[0].landed, 1);
+        assert_eq!(rows[0].gas_paid, U256::from(20));
+        assert_eq!(rows[0].realized_profit, I256::from(90));
+
+        let rows = summarize(&records, PnlGroup::Chain);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key, "8453");
+        assert_eq!(rows[0].realized_profit, I256::from(140));
+    }
+}
@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// State of the kill switch, published for the admin API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KillSwitchStatus {
+    pub engaged: bool,
+    /// What engaged it.
+    pub reason: Option<String>,
+    pub file: Option<PathBuf>,
+}
+
+/// Halts every submission while engaged, checked by the strategy before it builds a tx and
+/// by the executors right before they send one, so an operator can stop trading during an
+/// incident while indexing goes on. Engaged by a flag, set at startup or through the admin
+/// API, or while a file exists.
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch {
+    file: Option<PathBuf>,
+    engaged: Arc<AtomicBool>,
+}
+
+impl KillSwitch {
+    pub fn new(file: Option<PathBuf>, engaged: bool) -> Self {
+        Self {
+            file,
+            engaged: Arc::new(AtomicBool::new(engaged)),
+        }
+    }
+
+    pub fn set_engaged(&self, engaged: bool) {
+        self.engaged.store(engaged, Ordering::SeqCst);
+    }
+
+    /// Why submission is halted, none while it isn't.
+    pub fn reason(&self) -> Option<String> {
+        if self.engaged.load(Ordering::SeqCst) {
+            return Some("kill switch engaged".to_string());
+        }
+        self.file
+            .as_ref()
+            .filter(|file| file.exists())
+            .map(|file| format!("kill switch file {:?} exists", file))
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.reason().is_some()
+    }
+
+    pub fn status(&self) -> KillSwitchStatus {
+        let reason = self.reason();
+        KillSwitchStatus {
+            engaged: reason.is_some(),
+            reason,
+            file: self.file.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kill_switch() {
+        let file = std::env::temp_dir().join(format!("kill-switch-{}", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+        let kill_switch = KillSwitch::new(Some(file.clone()), false);
+        assert!(!kill_switch.is_engaged());
+
+        // clones share the flag
+        kill_switch.clone().set_engaged(true);
+        assert_eq!(
+            kill_switch.reason(),
+            Some("kill switch engaged".to_string())
+        );
+        kill_switch.set_engaged(false);
+        assert!(!kill_switch.is_engaged());
+
+        std::fs::write(&file, "").unwrap();
+        assert!(kill_switch.is_engaged());
+        assert!(kill_switch.status().reason.unwrap().contains("file"));
+        std::fs::remove_file(&file).unwrap();
+        assert!(!kill_switch.status().engaged);
+
+        assert!(KillSwitch::new(None, true).is_engaged());
+        assert!(!KillSwitch::default().is_engaged());
+    }
+}
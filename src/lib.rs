@@ -1,3 +1,27 @@
+pub mod api;
+pub mod circuit_breaker;
 pub mod collectors;
+pub mod control;
+pub mod deploy;
 pub mod executors;
+pub mod export;
+pub mod exposure;
+pub mod failover;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod history;
+pub mod kill_switch;
+pub mod liveness;
+pub mod nonce;
+pub mod opportunity_feed;
+pub mod rate_limit;
+pub mod replay;
+pub mod retry;
+pub mod scan;
+pub mod shutdown;
+pub mod signer;
+pub mod state;
 pub mod strategies;
+pub mod supervisor;
+pub mod tunables;
+pub mod webhook;
@@ -0,0 +1,183 @@
+use crate::api::SharedStatus;
+use crate::failover::{RpcHealth, SharedRpcHealth};
+use crate::supervisor::Supervisor;
+use ethers::types::U256;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between checks of the liveness watchdog.
+pub const LIVENESS_CHECK_INTERVAL_SECS: u64 = 10;
+
+/// Whether the bot is making progress, for `/healthz` and the liveness watchdog.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LivenessReport {
+    pub healthy: bool,
+    /// Unix time of the last full scan tick that got through, 0 until one does.
+    pub last_successful_tick_at: u64,
+    /// Since the last successful tick, or since startup before the first one.
+    pub seconds_since_successful_tick: u64,
+    pub last_indexed_block: u64,
+    /// Chain head at the last RPC health check, 0 until the first one.
+    pub chain_head: u64,
+    pub blocks_behind: u64,
+    pub rpc: RpcHealth,
+    /// Native balance of the sender at the last wallet check, in wei.
+    pub gas_balance: U256,
+    /// Why the bot isn't healthy, empty if it is.
+    pub problems: Vec<String>,
+}
+
+/// Judges liveness from the strategy's status and the RPC health checks. Only a stalled
+/// tick or unreachable RPC make the bot unhealthy: restarting doesn't top up a wallet.
+#[derive(Debug, Clone)]
+pub struct Liveness {
+    status: SharedStatus,
+    rpc: SharedRpcHealth,
+    started_at: u64,
+    /// Longest time without a successful tick, unchecked if unset.
+    timeout: Option<Duration>,
+}
+
+impl Liveness {
+    pub fn new(status: SharedStatus, rpc: SharedRpcHealth, timeout: Option<Duration>) -> Self {
+        Self {
+            status,
+            rpc,
+            started_at: now(),
+            timeout,
+        }
+    }
+
+    pub fn report(&self) -> LivenessReport {
+        let (last_successful_tick_at, last_indexed_block, gas_balance) = self
+            .status
+            .read()
+            .map(|s| {
+                (
+                    s.last_successful_tick_at,
+                    s.last_block_number,
+                    s.wallet.gas_balance,
+                )
+            })
+            .unwrap_or_default();
+        let rpc = self.rpc.read().map(|rpc| *rpc).unwrap_or_default();
+        assess(
+            now(),
+            self.started_at,
+            self.timeout,
+            last_successful_tick_at,
+            last_indexed_block,
+            rpc,
+            gas_balance,
+        )
+    }
+
+    /// Give up on the bot through `supervisor` once it has gone without a successful tick
+    /// for longer than the timeout, so it exits non-zero and its orchestrator restarts it.
+    /// Returns at once if there is no timeout.
+    pub async fn watch(self, supervisor: Supervisor) {
+        let Some(timeout) = self.timeout else {
+            return;
+        };
+        let mut interval = tokio::time::interval(Duration::from_secs(LIVENESS_CHECK_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let report = self.report();
+            if report.seconds_since_successful_tick > timeout.as_secs() {
+                supervisor.fail(format!(
+                    "no successful tick for {}s, the liveness timeout is {}s",
+                    report.seconds_since_successful_tick,
+                    timeout.as_secs()
+                ));
+                return;
+            }
+        }
+    }
+}
+
+/// The liveness of a bot started at `started_at`, at `now`.
+pub fn assess(
+    now: u64,
+    started_at: u64,
+    timeout: Option<Duration>,
+    last_successful_tick_at: u64,
+    last_indexed_block: u64,
+    rpc: RpcHealth,
+    gas_balance: U256,
+) -> LivenessReport {
+    let seconds_since_successful_tick = now.saturating_sub(last_successful_tick_at.max(started_at));
+    let mut problems = Vec::new();
+    if let Some(timeout) = timeout {
+        if seconds_since_successful_tick > timeout.as_secs() {
+            problems.push(format!(
+                "No successful tick for {}s",
+                seconds_since_successful_tick
+            ));
+        }
+    }
+    // before the first health check connectivity is unknown
+    if rpc.checked_at > 0 && rpc.healthy_endpoints == 0 {
+        problems.push(format!(
+            "None of the {} read RPC endpoints is healthy",
+            rpc.endpoints
+        ));
+    }
+    LivenessReport {
+        healthy: problems.is_empty(),
+        last_successful_tick_at,
+        seconds_since_successful_tick,
+        last_indexed_block,
+        chain_head: rpc.head,
+        blocks_behind: rpc.head.saturating_sub(last_indexed_block),
+        rpc,
+        gas_balance,
+        problems,
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Invalid timestamp")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess() {
+        let rpc = RpcHealth {
+            checked_at: 1_000,
+            head: 120,
+            healthy_endpoints: 1,
+            endpoints: 2,
+        };
+        let timeout = Some(Duration::from_secs(600));
+
+        // measured from startup before the first tick
+        let report = assess(1_500, 1_000, timeout, 0, 100, rpc, U256::zero());
+        assert!(report.healthy);
+        assert_eq!(report.seconds_since_successful_tick, 500);
+        assert_eq!(report.blocks_behind, 20);
+
+        let report = assess(2_000, 1_000, timeout, 1_300, 100, rpc, U256::zero());
+        assert_eq!(
+            report.problems,
+            vec!["No successful tick for 700s".to_string()]
+        );
+        // never stale without a timeout
+        assert!(assess(2_000, 1_000, None, 1_300, 100, rpc, U256::zero()).healthy);
+
+        let down = RpcHealth {
+            healthy_endpoints: 0,
+            ..rpc
+        };
+        let report = assess(1_500, 1_000, timeout, 1_300, 100, down, U256::zero());
+        assert_eq!(
+            report.problems,
+            vec!["None of the 2 read RPC endpoints is healthy".to_string()]
+        );
+    }
+}
@@ -1,46 +1,259 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use ethers::providers::Middleware;
 use std::str::FromStr;
 
+use api::SharedStatus;
 use artemis_core::engine::Engine;
-use artemis_core::types::{CollectorMap, ExecutorMap};
-use collectors::time_collector::TimeCollector;
+use artemis_core::types::{CollectorMap, Executor, ExecutorMap};
+use circuit_breaker::{BreakerLimits, CircuitBreaker};
+use collectors::liquidation_call_collector::LiquidationCallCollector;
+use collectors::mev_share_collector::{MevShareCollector, MEV_SHARE_SSE_URL};
+use collectors::opportunity_queue_collector::OpportunityQueueCollector;
+use collectors::price_update_collector::PriceUpdateCollector;
+use collectors::reserve_data_collector::ReserveDataCollector;
+use collectors::time_collector::{PollInterval, TimeCollector};
+use control::ControlHandle;
 use ethers::{
     prelude::MiddlewareBuilder,
-    providers::{Http, Provider},
+    providers::{Http, Provider, Ws},
     signers::{LocalWallet, Signer},
+    types::{Address, U256},
 };
-use executors::protect_executor::ProtectExecutor;
+use executors::l1_fee::L1FeeModel;
+use executors::mev_share_executor::{MevShareExecutor, MEV_SHARE_RELAY_URL};
+use executors::private_tx::{read_private_relays, PRIVATE_RELAYS_FILE};
+use executors::protect_executor::{
+    MaxOpportunityAge, ProtectExecutor, DEFAULT_MAX_OPPORTUNITY_AGE_SECS,
+};
+use exposure::ExposureLimiter;
+use failover::{FailoverTransport, SharedRpcHealth};
+use history::{LiquidationHistory, PnlArgs};
+use kill_switch::KillSwitch;
+use liveness::Liveness;
+use nonce::NonceManager;
+use opportunity_feed::OpportunityFeed;
+use rate_limit::{RateLimitedTransport, RateLimits};
+use replay::ReplayTransport;
+use retry::{RetryPolicy, RetryTransport};
+use shutdown::{wait_for_signal, Shutdown, SHUTDOWN_TIMEOUT_SECS};
+use signer::{build_signer, SignerConfig, SignerKind};
+use state::StateArgs;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use strategies::{
-    aave_strategy::{AaveStrategy, Deployment},
+    aave_strategy::{
+        creation_block, get_deployment_config, resolve_deployment_addresses, AaveStrategy,
+        Deployment, DeploymentConfig,
+    },
+    approval::ApprovalConfig,
+    debt_swap::{PARASWAP_API_URL, PARTNER_ACCOUNTS_FILE},
+    filters::{LiquidationFilter, Shard},
+    flash_liquidity::FlashLiquidityPool,
+    harvest::HarvestConfig,
+    log_range::{LogRange, LOG_BLOCK_RANGE},
+    price_check::{self, PriceSource},
+    profit_currency::ProfitCurrency,
+    reserve_cache::DEFAULT_RESERVE_CACHE_TTL_SECS,
+    scoring::ScoringWeights,
+    shared_state::{Role, SharedState},
+    simulation_trace::TraceBackend,
     types::{Action, Config, Event},
 };
-use tracing::{info, Level};
+use supervisor::{
+    RestartLimits, Supervisor, ALERT_TIMEOUT_SECS, MAX_TASK_RESTARTS, TASK_RESTART_WINDOW_SECS,
+};
+use tracing::{error, info, warn, Level};
 use tracing_subscriber::{filter, prelude::*};
+use tunables::TUNABLES_CHECK_INTERVAL_SECS;
+use webhook::OpportunityWebhook;
 
+pub mod api;
+pub mod circuit_breaker;
 pub mod collectors;
+pub mod control;
+pub mod deploy;
 pub mod executors;
+pub mod export;
+pub mod exposure;
+pub mod failover;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod history;
+pub mod kill_switch;
+pub mod liveness;
+pub mod nonce;
+pub mod opportunity_feed;
+pub mod rate_limit;
+pub mod replay;
+pub mod retry;
+pub mod scan;
+pub mod shutdown;
+pub mod signer;
+pub mod state;
 pub mod strategies;
+pub mod supervisor;
+pub mod tunables;
+pub mod webhook;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, including span fields such as the opportunity's `correlation_id`.
+    Json,
+}
 
-static POLL_INTERVAL_SECS: u64 = 60 * 5;
+/// Where failed simulations are traced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TraceMode {
+    /// `debug_traceCall` on the RPC endpoints, which must serve it.
+    Local,
+    /// Tenderly's simulation API.
+    Tenderly,
+}
+
+/// What the min profit is set in and profits are reported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProfitDenomination {
+    /// The chain's native token, in wei.
+    Native,
+    /// The oracle's base currency, USD with 8 decimals.
+    Usd,
+    /// The reserve set with --profit-asset, in its own units.
+    Asset,
+}
 
 /// CLI Options.
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 pub struct Args {
-    /// Ethereum node WS endpoint.
-    #[arg(long, env = "RPC", value_name = "URL", required = true)]
-    pub rpc: String,
+    /// Ethereum node HTTP endpoints in order of preference, failed over between.
+    #[arg(
+        long,
+        env = "RPC",
+        value_name = "URL",
+        value_delimiter = ',',
+        required = true
+    )]
+    pub rpc: Vec<String>,
+
+    /// Endpoints txs are sent through instead, e.g. a private RPC.
+    #[arg(long, env = "SUBMIT_RPC", value_name = "URL", value_delimiter = ',')]
+    pub submit_rpc: Vec<String>,
+
+    /// Endpoints serving the strategy's heavy reads instead (log scans, multicalls and
+    /// simulations), e.g. a cheap archive provider. Nonces, gas and receipts stay on --rpc.
+    #[arg(long, env = "READ_RPC", value_name = "URL", value_delimiter = ',')]
+    pub read_rpc: Vec<String>,
+
+    /// Append every RPC response to this file, one JSON exchange per line, for --replay-rpc.
+    #[arg(
+        long,
+        env = "RECORD_RPC",
+        value_name = "FILE",
+        conflicts_with = "replay_rpc"
+    )]
+    pub record_rpc: Option<PathBuf>,
+
+    /// Answer RPC requests from a file written with --record-rpc instead of the endpoints,
+    /// to reproduce a run against the chain as it was recorded.
+    #[arg(long, env = "REPLAY_RPC", value_name = "FILE")]
+    pub replay_rpc: Option<PathBuf>,
+
+    /// Seconds between health checks of every RPC endpoint.
+    #[arg(long, env = "RPC_HEALTH_CHECK_INTERVAL_SECS", default_value_t = 10)]
+    pub rpc_health_check_interval_secs: u64,
+
+    /// Seconds between checks of the sender's nonce for gaps and stuck txs.
+    #[arg(long, env = "NONCE_CHECK_INTERVAL_SECS", default_value_t = 30)]
+    pub nonce_check_interval_secs: u64,
+
+    /// Replace txs stuck in the mempool, at startup or later, with empty self-sends at a
+    /// higher fee. They're only reported otherwise.
+    #[arg(long, env = "CANCEL_PENDING")]
+    pub cancel_pending: bool,
+
+    /// Ethereum node WS endpoint, used to watch the mempool for oracle updates.
+    #[arg(long, env = "WS_RPC", value_name = "URL")]
+    pub ws_rpc: Option<String>,
+
+    /// Tries per RPC request before a rate limited or transient error is given up on.
+    #[arg(long, env = "RPC_MAX_ATTEMPTS", default_value_t = 5)]
+    pub rpc_max_attempts: u32,
+
+    /// Backoff before the first retry, doubled on every retry after.
+    #[arg(long, env = "RPC_INITIAL_BACKOFF_MILLIS", default_value_t = 200)]
+    pub rpc_initial_backoff_millis: u64,
+
+    /// Longest backoff between retries.
+    #[arg(long, env = "RPC_MAX_BACKOFF_MILLIS", default_value_t = 10_000)]
+    pub rpc_max_backoff_millis: u64,
+
+    /// Requests a second each RPC endpoint is held to, unlimited if unset.
+    #[arg(long, env = "RPC_REQUESTS_PER_SEC")]
+    pub rpc_requests_per_sec: Option<u64>,
+
+    /// Compute units a second each RPC endpoint is held to, as Alchemy-style plans meter
+    /// them, unlimited if unset.
+    #[arg(long, env = "RPC_COMPUTE_UNITS_PER_SEC")]
+    pub rpc_compute_units_per_sec: Option<u64>,
+
+    /// Where the key for sending txs is kept.
+    #[arg(long, env = "SIGNER", value_enum, default_value_t = SignerKind::PrivateKey)]
+    pub signer: SignerKind,
 
     /// Private key for sending txs.
-    #[arg(long, env = "PRIVATE_KEY", value_name = "HEX", required = true)]
-    pub private_key: String,
+    #[arg(
+        long,
+        env = "PRIVATE_KEY",
+        value_name = "HEX",
+        required_if_eq("signer", "private-key")
+    )]
+    pub private_key: Option<String>,
+
+    /// Encrypted JSON keystore holding the key for sending txs.
+    #[arg(
+        long,
+        env = "KEYSTORE",
+        value_name = "PATH",
+        required_if_eq("signer", "keystore")
+    )]
+    pub keystore: Option<PathBuf>,
+
+    /// Password of the keystore.
+    #[arg(long, env = "KEYSTORE_PASSWORD", hide_env_values = true)]
+    pub keystore_password: Option<String>,
+
+    /// AWS KMS key id, or GCP KMS key version resource name.
+    #[arg(long, env = "KMS_KEY_ID", required_if_eq_any([("signer", "aws-kms"), ("signer", "gcp-kms")]))]
+    pub kms_key_id: Option<String>,
+
+    /// Ledger Live account index.
+    #[arg(long, env = "LEDGER_INDEX", default_value_t = 0)]
+    pub ledger_index: usize,
 
     /// Percentage of profit to pay in gas.
     #[arg(long, env = "BID_PERCENTAGE", value_name = "U64", default_value_t = 50)]
     pub bid_percentage: u64,
 
+    /// Lowest percentage of profit to pay in gas. The bid for similar profits falls toward
+    /// it from --bid-percentage when we win paying far more gas than competitors.
+    #[arg(long, env = "MIN_BID_PERCENTAGE", value_name = "U64")]
+    pub min_bid_percentage: Option<u64>,
+
+    /// Highest percentage of profit to pay in gas. The bid for similar profits rises toward
+    /// it from --bid-percentage when a competitor's liquidation lands first.
+    #[arg(long, env = "MAX_BID_PERCENTAGE", value_name = "U64")]
+    pub max_bid_percentage: Option<u64>,
+
+    /// Log output format.
+    #[arg(long, env = "LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
     #[arg(long, env = "DEPLOYMENT", required = true)]
     pub deployment: Deployment,
 
@@ -50,6 +263,610 @@ pub struct Args {
     /// Whether to use the Aave offical liquidator interface.
     #[arg(long, env = "USE_AAVE_LIQUIDATOR", default_value_t = false)]
     pub use_aave_liquidator: bool,
+
+    /// Maximum random delay (in millis) added to each tick, capped at half its interval, so
+    /// instances sharing an RPC don't all poll it at the same second.
+    #[arg(long, env = "TICK_JITTER_MILLIS", default_value_t = 0)]
+    pub tick_jitter_millis: u64,
+
+    /// Maximum random delay (in millis) before sending a tx, capped at 1000.
+    #[arg(long, env = "SUBMISSION_JITTER_MILLIS", default_value_t = 0)]
+    pub submission_jitter_millis: u64,
+
+    /// Blocks the chain may move past an opportunity before its tx is dropped as stale,
+    /// unchecked if unset.
+    #[arg(long, env = "MAX_OPPORTUNITY_AGE_BLOCKS")]
+    pub max_opportunity_age_blocks: Option<u64>,
+
+    /// Seconds after which an opportunity's tx is dropped as stale, 0 disables the check.
+    #[arg(
+        long,
+        env = "MAX_OPPORTUNITY_AGE_SECS",
+        default_value_t = DEFAULT_MAX_OPPORTUNITY_AGE_SECS
+    )]
+    pub max_opportunity_age_secs: u64,
+
+    /// The liquidator is registered with the deployment's fee rebate program (e.g. Sonic
+    /// FeeM), net its rebate out of gas costs. Gas is counted in full otherwise.
+    #[arg(long, env = "FEE_REBATE")]
+    pub fee_rebate: bool,
+
+    /// Share of gas fees (in bps) refunded by the chain, overriding the deployment's rebate
+    /// program.
+    #[arg(long, env = "FEE_REBATE_BPS")]
+    pub fee_rebate_bps: Option<u64>,
+
+    /// Seconds between full scans of every borrower.
+    #[arg(long, env = "POLL_INTERVAL_SECS", default_value_t = 300)]
+    pub poll_interval_secs: u64,
+
+    /// Seconds between health factor polls of watchlisted borrowers.
+    #[arg(long, env = "WATCHLIST_POLL_INTERVAL_SECS", default_value_t = 5)]
+    pub watchlist_poll_interval_secs: u64,
+
+    /// Health factor (in bps, 10000 = 1.0) below which a borrower is watchlisted.
+    #[arg(long, env = "WATCHLIST_HEALTH_FACTOR_BPS", default_value_t = 10500)]
+    pub watchlist_health_factor_bps: u64,
+
+    /// Health factor (in bps, 10000 = 1.0) below which a borrower's liquidation prices are indexed.
+    #[arg(long, env = "RISK_HEALTH_FACTOR_BPS", default_value_t = 15000)]
+    pub risk_health_factor_bps: u64,
+
+    /// Minimum opportunity confidence score (in bps) required to submit.
+    #[arg(long, env = "MIN_CONFIDENCE_BPS", default_value_t = 2500)]
+    pub min_confidence_bps: u64,
+
+    /// Minimum opportunity confidence score (in bps) to submit to the public mempool.
+    #[arg(long, env = "PUBLIC_CONFIDENCE_BPS", default_value_t = 7500)]
+    pub public_confidence_bps: u64,
+
+    /// How much (in bps) a borrower barely under water, or a low gas bid, discounts an
+    /// opportunity's profit when picking the best one.
+    #[arg(long, env = "SCORE_LANDING_WEIGHT_BPS", default_value_t = 0)]
+    pub score_landing_weight_bps: u64,
+
+    /// How much (in bps) an illiquid swap route out of the collateral discounts an
+    /// opportunity's profit when picking the best one.
+    #[arg(long, env = "SCORE_LIQUIDITY_WEIGHT_BPS", default_value_t = 0)]
+    pub score_liquidity_weight_bps: u64,
+
+    /// How much (in bps) an unsimulated or failed liquidation discounts an opportunity's
+    /// profit when picking the best one.
+    #[arg(long, env = "SCORE_REVERT_WEIGHT_BPS", default_value_t = 0)]
+    pub score_revert_weight_bps: u64,
+
+    /// Backrun oracle updates seen on MEV-Share with liquidation bundles.
+    #[arg(long, env = "MEV_SHARE", default_value_t = false)]
+    pub mev_share: bool,
+
+    /// MEV-Share event stream endpoint.
+    #[arg(long, env = "MEV_SHARE_SSE_URL", value_name = "URL", default_value = MEV_SHARE_SSE_URL)]
+    pub mev_share_sse_url: String,
+
+    /// MEV-Share bundle relay endpoint.
+    #[arg(long, env = "MEV_SHARE_RELAY_URL", value_name = "URL", default_value = MEV_SHARE_RELAY_URL)]
+    pub mev_share_relay_url: String,
+
+    /// Private key identifying us to the relay, a random one is used if not set.
+    #[arg(long, env = "FLASHBOTS_AUTH_KEY", value_name = "HEX")]
+    pub flashbots_auth_key: Option<String>,
+
+    /// JSON file listing partner accounts and their execution path, `partners.json` in
+    /// `--state-dir` if unset.
+    #[arg(long, env = "PARTNER_ACCOUNTS_FILE")]
+    pub partner_accounts_file: Option<PathBuf>,
+
+    /// JSON file listing, per chain id, the private relays liquidations are sent through
+    /// and the blocks they have to land before going to the public mempool.
+    #[arg(long, env = "PRIVATE_RELAYS_FILE", default_value = PRIVATE_RELAYS_FILE)]
+    pub private_relays_file: PathBuf,
+
+    /// ParaSwap repay adapter used to repay partner debt with collateral.
+    #[arg(long, env = "REPAY_ADAPTER_ADDRESS")]
+    pub repay_adapter_address: Option<String>,
+
+    /// ParaSwap API endpoint.
+    #[arg(long, env = "PARASWAP_API_URL", value_name = "URL", default_value = PARASWAP_API_URL)]
+    pub paraswap_api_url: String,
+
+    /// Health factor (in bps, 10000 = 1.0) partner accounts are brought back to when repaying with collateral.
+    #[arg(long, env = "TARGET_HEALTH_FACTOR_BPS", default_value_t = 11000)]
+    pub target_health_factor_bps: u64,
+
+    /// Maximum slippage (in bps) when swapping collateral for debt.
+    #[arg(long, env = "MAX_SLIPPAGE_BPS", default_value_t = 100)]
+    pub max_slippage_bps: u64,
+
+    /// Reconcile smart account borrowers with their on-chain reserves every this many scan ticks.
+    #[arg(long, env = "SMART_ACCOUNT_RECONCILE_TICKS", default_value_t = 12)]
+    pub smart_account_reconcile_ticks: u64,
+
+    /// Reconcile EOA borrowers with their on-chain reserves every this many scan ticks.
+    #[arg(long, env = "EOA_RECONCILE_TICKS", default_value_t = 288)]
+    pub eoa_reconcile_ticks: u64,
+
+    /// Opportunities making less than this (in wei, or the units of --profit-currency) are
+    /// not submitted, adjustable at runtime.
+    #[arg(long, env = "MIN_PROFIT_WEI", default_value_t = 0)]
+    pub min_profit_wei: u128,
+
+    /// What --min-profit-wei is set in and profits are reported in. Gas is bid in the native
+    /// token either way.
+    #[arg(long, env = "PROFIT_CURRENCY", value_enum, default_value_t = ProfitDenomination::Native)]
+    pub profit_currency: ProfitDenomination,
+
+    /// Reserve profits are denominated in with `--profit-currency asset`.
+    #[arg(long, env = "PROFIT_ASSET", required_if_eq("profit_currency", "asset"))]
+    pub profit_asset: Option<String>,
+
+    /// Borrowers owing less than this (in USD) are skipped as dust before any per-account calls.
+    #[arg(long, env = "MIN_DEBT_USD", default_value_t = 0)]
+    pub min_debt_usd: u64,
+
+    /// Underwater borrowers evaluated concurrently on each tick.
+    #[arg(long, env = "EVALUATION_CONCURRENCY", default_value_t = 8)]
+    pub evaluation_concurrency: usize,
+
+    /// Most (collateral, debt) pairs of one borrower liquidated in a single tx, needs a
+    /// Liquidator contract with `liquidateBatch`.
+    #[arg(long, env = "MAX_LIQUIDATION_LEGS", default_value_t = 1)]
+    pub max_liquidation_legs: usize,
+
+    /// Flash-swap through the wrapped native token when no Uniswap V3 pool pairs the
+    /// collateral with the debt, needs a Liquidator contract with `liquidatePath`.
+    #[arg(long, env = "MULTI_HOP_SWAPS")]
+    pub multi_hop_swaps: bool,
+
+    /// Balancer (`balancer:<pool id>`) and Curve (`curve:<pool address>`) pools to repay a
+    /// Balancer flash loan of the debt on when Uniswap V3 can't swap the collateral for it,
+    /// needs a Liquidator contract with `liquidateFlashLoan`.
+    #[arg(long, env = "FLASH_LIQUIDITY_POOLS", value_delimiter = ',')]
+    pub flash_liquidity_pools: Vec<String>,
+
+    /// Sell the collateral along the best ParaSwap, 1inch or 0x route for a Balancer flash
+    /// loan of the debt when no pool can swap it, needs a Liquidator contract taking
+    /// aggregator calldata in `liquidateFlashLoan`.
+    #[arg(long, env = "AGGREGATOR_FLASH_SWAPS")]
+    pub aggregator_flash_swaps: bool,
+
+    /// Only these collateral assets are seized, any if empty.
+    #[arg(long, env = "COLLATERAL_ALLOWLIST", value_delimiter = ',')]
+    pub collateral_allowlist: Vec<String>,
+
+    /// Collateral assets never seized, e.g. illiquid or fee-on-transfer tokens.
+    #[arg(long, env = "COLLATERAL_DENYLIST", value_delimiter = ',')]
+    pub collateral_denylist: Vec<String>,
+
+    /// Approve only each liquidation's debt plus this buffer (in bps) from the sender's wallet,
+    /// right before sending it, instead of unlimited approvals of every reserve at startup.
+    /// Only applies to --use-aave-liquidator, and delays each liquidation by its approval.
+    #[arg(long, env = "APPROVAL_BUFFER_BPS")]
+    pub approval_buffer_bps: Option<u64>,
+
+    /// Collateral assets seized as aTokens when liquidating through the Aave pool, for reserves
+    /// too illiquid to swap out of right away. The harvester withdraws them later.
+    #[arg(long, env = "RECEIVE_A_TOKEN", value_delimiter = ',')]
+    pub receive_a_token: Vec<String>,
+
+    /// Only these debt assets are repaid, any if empty.
+    #[arg(long, env = "DEBT_ALLOWLIST", value_delimiter = ',')]
+    pub debt_allowlist: Vec<String>,
+
+    /// Debt assets never repaid.
+    #[arg(long, env = "DEBT_DENYLIST", value_delimiter = ',')]
+    pub debt_denylist: Vec<String>,
+
+    /// Borrowers never scanned or liquidated.
+    #[arg(long, env = "BORROWER_BLACKLIST", value_delimiter = ',')]
+    pub borrower_blacklist: Vec<String>,
+
+    /// Only scan and liquidate shard `i` of `n` of the borrowers, split by address hash, to
+    /// run a large market on `n` instances. Each instance takes a different `i` from 0.
+    #[arg(long, env = "SHARD", value_name = "I/N")]
+    pub shard: Option<String>,
+
+    /// Redis shared by the instances of a cluster running the same market, for the borrower
+    /// cache, watchlists and liquidation claims. Instances run alone if unset.
+    #[arg(long, env = "REDIS_URL", value_name = "URL", hide_env_values = true)]
+    pub redis_url: Option<String>,
+
+    /// What this instance does in a cluster sharing --redis-url: `detect` indexes the market
+    /// and queues the opportunities worth submitting without holding a key, `execute` holds
+    /// the key and submits them.
+    #[arg(long, env = "ROLE", value_enum, default_value_t = Role::All)]
+    pub role: Role,
+
+    /// Seconds an instance holds its claim on a borrower's liquidation, the others skip the
+    /// borrower meanwhile.
+    #[arg(long, env = "CLAIM_LEASE_SECS", default_value_t = 60)]
+    pub claim_lease_secs: u64,
+
+    /// Address the admin API listens on, the API is disabled if unset. Only loopback
+    /// addresses are served without --api-token.
+    #[arg(long, env = "API_LISTEN_ADDRESS", value_name = "ADDR")]
+    pub api_listen_address: Option<SocketAddr>,
+
+    /// Bearer token required on every admin API request.
+    #[arg(long, env = "API_TOKEN", hide_env_values = true)]
+    pub api_token: Option<String>,
+
+    /// Start with the kill switch engaged, nothing is submitted until it's released
+    /// through the admin API.
+    #[arg(long, env = "KILL_SWITCH")]
+    pub kill_switch: bool,
+
+    /// Nothing is submitted while this file exists, indexing and scanning go on.
+    #[arg(long, env = "KILL_SWITCH_FILE", value_name = "PATH")]
+    pub kill_switch_file: Option<PathBuf>,
+
+    /// Address the gRPC API streaming opportunities listens on, disabled if unset. Calls
+    /// need the API token too.
+    #[arg(long, env = "GRPC_LISTEN_ADDRESS", value_name = "ADDR")]
+    pub grpc_listen_address: Option<SocketAddr>,
+
+    /// Webhook every opportunity evaluated for submission is posted to as JSON, with
+    /// whether it was submitted, queued or passed on and why.
+    #[arg(
+        long,
+        env = "OPPORTUNITY_WEBHOOK_URL",
+        value_name = "URL",
+        hide_env_values = true
+    )]
+    pub opportunity_webhook_url: Option<String>,
+
+    /// Webhook alerts are posted to, as `{"text": ...}`, when a task fails and is
+    /// restarted or the bot gives up. Alerts are only logged if unset.
+    #[arg(
+        long,
+        env = "ALERT_WEBHOOK_URL",
+        value_name = "URL",
+        hide_env_values = true
+    )]
+    pub alert_webhook_url: Option<String>,
+
+    /// Restarts a failing collector, strategy or executor may go through within the
+    /// restart window before the bot exits.
+    #[arg(long, env = "MAX_TASK_RESTARTS", default_value_t = MAX_TASK_RESTARTS)]
+    pub max_task_restarts: u32,
+
+    /// Seconds over which a task's restarts are counted.
+    #[arg(long, env = "TASK_RESTART_WINDOW_SECS", default_value_t = TASK_RESTART_WINDOW_SECS)]
+    pub task_restart_window_secs: u64,
+
+    /// Exit non-zero once no full scan tick has updated the state and scanned the borrowers
+    /// for this many seconds, counted from startup before the first one, so an orchestrator
+    /// restarts the bot. `/healthz` reports unhealthy past it too.
+    #[arg(long, env = "LIVENESS_TIMEOUT_SECS")]
+    pub liveness_timeout_secs: Option<u64>,
+
+    /// JSON file of runtime tunables (bid percentages, min profit, token filters, poll
+    /// intervals), applied at startup, whenever it changes and on SIGHUP.
+    #[arg(long, env = "TUNABLES_FILE")]
+    pub tunables_file: Option<PathBuf>,
+
+    /// File recording every control command and its idempotency key, `audit.jsonl` in
+    /// `--state-dir` if unset.
+    #[arg(long, env = "AUDIT_LOG_FILE")]
+    pub audit_log_file: Option<PathBuf>,
+
+    /// Directory the borrower state cache of each deployment is kept in.
+    #[arg(long, env = "STATE_DIR", default_value = ".")]
+    pub state_dir: PathBuf,
+
+    /// Seconds a reserve config or e-mode category cached in the state directory is reused
+    /// for on restart, unless the pool configurator changed it since. Zero disables the cache.
+    #[arg(long, env = "RESERVE_CACHE_TTL_SECS", default_value_t = DEFAULT_RESERVE_CACHE_TTL_SECS)]
+    pub reserve_cache_ttl_secs: u64,
+
+    /// Aave V3 subgraph the borrower set is bootstrapped from when there is no state cache,
+    /// instead of replaying every log since the pool's creation.
+    #[arg(long, env = "SUBGRAPH_URL", value_name = "URL", hide_env_values = true)]
+    pub subgraph_url: Option<String>,
+
+    /// UiPoolDataProvider reading a borrower's every reserve balance in one call, used
+    /// for position snapshots if set.
+    #[arg(long, env = "UI_POOL_DATA_PROVIDER")]
+    pub ui_pool_data_provider: Option<String>,
+
+    /// Multicall3 contract reads are batched through, where it isn't deployed at the usual
+    /// address. Reads go one by one if there is no contract there.
+    #[arg(long, env = "MULTICALL_ADDRESS")]
+    pub multicall_address: Option<String>,
+
+    /// Pool of an Aave V3 fork, required by the `aave-v3-fork` deployment. Its data provider
+    /// and oracle are read from its addresses provider.
+    #[arg(long, env = "POOL_ADDRESS")]
+    pub pool_address: Option<String>,
+
+    /// Wrapped native token of an Aave V3 fork profits are valued in, required by the
+    /// `aave-v3-fork` deployment.
+    #[arg(long, env = "WRAPPED_NATIVE_ADDRESS")]
+    pub wrapped_native_address: Option<String>,
+
+    /// Blocks of logs requested at once at first. The range is bisected when the provider
+    /// refuses a window and grows back while it keeps up.
+    #[arg(long, env = "LOG_BLOCK_RANGE", default_value_t = LOG_BLOCK_RANGE)]
+    pub log_block_range: u64,
+
+    /// File recording every attempted and landed liquidation, summarized by the `pnl`
+    /// subcommand, `liquidations.jsonl` in `--state-dir` if unset.
+    #[arg(long, env = "HISTORY_FILE")]
+    pub history_file: Option<PathBuf>,
+
+    /// Seconds between checks of the sender's gas balance, the Liquidator's owner and the
+    /// pool's allowances, which also run at startup.
+    #[arg(long, env = "WALLET_CHECK_INTERVAL_SECS", default_value_t = 300)]
+    pub wallet_check_interval_secs: u64,
+
+    /// Native balance (in wei) the sender is warned below, 0 disables the check.
+    #[arg(long, env = "MIN_GAS_BALANCE_WEI", default_value_t = 0)]
+    pub min_gas_balance_wei: u128,
+
+    /// Hold submission while the wallet check finds problems, instead of only warning.
+    #[arg(long, env = "PAUSE_ON_WALLET_PROBLEMS")]
+    pub pause_on_wallet_problems: bool,
+
+    /// Don't submit anything until the initial sync has indexed the pool's logs within this
+    /// many blocks of the chain head.
+    #[arg(long, env = "MAX_SYNC_LAG")]
+    pub max_sync_lag: Option<u64>,
+
+    /// Stop submitting after this many liquidations revert in a row, never if unset.
+    #[arg(long, env = "MAX_CONSECUTIVE_REVERTS")]
+    pub max_consecutive_reverts: Option<u32>,
+
+    /// Stop submitting after this many landed liquidations lose money in a row, never if unset.
+    #[arg(long, env = "MAX_CONSECUTIVE_LOSSES")]
+    pub max_consecutive_losses: Option<u32>,
+
+    /// Oracle exposing `getAssetPrice` (in the Aave oracle's base currency) the Aave oracle's
+    /// prices are checked against on every scan.
+    #[arg(long, env = "REFERENCE_ORACLE")]
+    pub reference_oracle: Option<String>,
+
+    /// Stop submitting once a price deviates more than this from the reference oracle's, in bps.
+    #[arg(long, env = "MAX_PRICE_DEVIATION_BPS")]
+    pub max_price_deviation_bps: Option<u64>,
+
+    /// Resume submitting this many seconds after the circuit breaker trips, only through
+    /// `/resume` if unset.
+    #[arg(long, env = "BREAKER_COOLDOWN_SECS")]
+    pub breaker_cooldown_secs: Option<u64>,
+
+    /// Most debt (in USD) the liquidations sent and not yet mined may repay between them,
+    /// new ones are passed on until earlier ones settle. Unlimited if unset.
+    #[arg(long, env = "MAX_EXPOSURE_USD")]
+    pub max_exposure_usd: Option<u64>,
+
+    /// Sources oracle prices are cross-checked with before submitting, tried in order until one
+    /// prices the asset. No check if empty.
+    #[arg(long, env = "PRICE_SOURCES", value_enum, value_delimiter = ',')]
+    pub price_sources: Vec<PriceSource>,
+
+    /// Skip opportunities whose oracle collateral/debt rate deviates more than this from the
+    /// price sources', in bps.
+    #[arg(
+        long,
+        env = "MAX_PRICE_SOURCE_DEVIATION_BPS",
+        default_value_t = price_check::DEFAULT_MAX_DEVIATION_BPS
+    )]
+    pub max_price_source_deviation_bps: u64,
+
+    /// Collect, convert and sweep liquidation proceeds every this many seconds, disabled if unset.
+    #[arg(long, env = "HARVEST_INTERVAL_SECS")]
+    pub harvest_interval_secs: Option<u64>,
+
+    /// Asset proceeds are converted into, WETH if unset.
+    #[arg(long, env = "HARVEST_TARGET")]
+    pub harvest_target: Option<String>,
+
+    /// Assets held by the sender that are never swapped. Defaults to the debt assets the Aave
+    /// path repays with, every one `--debt-allowlist` and `--debt-denylist` allow.
+    #[arg(long, env = "HARVEST_KEEP", value_delimiter = ',')]
+    pub harvest_keep: Vec<String>,
+
+    /// Balances worth less than this (in wei) are not swapped.
+    #[arg(
+        long,
+        env = "HARVEST_MIN_SWAP_WEI",
+        default_value_t = 1_000_000_000_000_000
+    )]
+    pub harvest_min_swap_wei: u128,
+
+    /// Wallet the harvest target is swept to.
+    #[arg(long, env = "COLD_WALLET")]
+    pub cold_wallet: Option<String>,
+
+    /// Balance of the harvest target (in its own units) kept when sweeping to the cold wallet.
+    #[arg(long, env = "SWEEP_THRESHOLD", default_value_t = 0)]
+    pub sweep_threshold: u128,
+
+    /// Liquidations repaying more debt than this (in USD) wait for an operator's approval,
+    /// through the admin API or `--approval-dir`. Smaller ones are submitted right away.
+    #[arg(long, env = "APPROVAL_NOTIONAL_USD")]
+    pub approval_notional_usd: Option<u64>,
+
+    /// Directory where creating a file named after a borrower, e.g. `0xab…`, approves its
+    /// liquidation once.
+    #[arg(long, env = "APPROVAL_DIR", requires = "approval_notional_usd")]
+    pub approval_dir: Option<PathBuf>,
+
+    /// Seconds an approval or rejection of a borrower lasts.
+    #[arg(long, env = "APPROVAL_TTL_SECS", default_value_t = 300)]
+    pub approval_ttl_secs: u64,
+
+    /// 1inch API key, 1inch routes are quoted for swaps if set.
+    #[arg(long, env = "ONE_INCH_API_KEY", hide_env_values = true)]
+    pub one_inch_api_key: Option<String>,
+
+    /// 0x API key, 0x routes are quoted for swaps if set.
+    #[arg(long, env = "ZERO_EX_API_KEY", hide_env_values = true)]
+    pub zero_ex_api_key: Option<String>,
+
+    /// Trace liquidation simulations that fail, logging their decoded call tree and revert
+    /// reason with the opportunity.
+    #[arg(long, env = "TRACE_SIMULATIONS", value_enum)]
+    pub trace_simulations: Option<TraceMode>,
+
+    /// Tenderly account the simulations are traced in.
+    #[arg(long, env = "TENDERLY_ACCOUNT")]
+    pub tenderly_account: Option<String>,
+
+    /// Tenderly project the simulations are traced in.
+    #[arg(long, env = "TENDERLY_PROJECT")]
+    pub tenderly_project: Option<String>,
+
+    /// Tenderly access key.
+    #[arg(long, env = "TENDERLY_ACCESS_KEY", hide_env_values = true)]
+    pub tenderly_access_key: Option<String>,
+}
+
+impl Args {
+    fn audit_log_file(&self) -> PathBuf {
+        self.audit_log_file
+            .clone()
+            .unwrap_or_else(|| self.state_dir.join(control::AUDIT_LOG_FILE))
+    }
+
+    fn history_file(&self) -> PathBuf {
+        self.history_file
+            .clone()
+            .unwrap_or_else(|| self.state_dir.join(history::HISTORY_FILE))
+    }
+
+    /// A copy safe to log, with keys, passwords and the URLs that may hold an API key
+    /// or a password redacted.
+    fn redacted(&self) -> Args {
+        let redact = |_: &String| "<redacted>".to_string();
+        let mut redacted = self.clone();
+        redacted.private_key = self.private_key.as_ref().map(redact);
+        redacted.keystore_password = self.keystore_password.as_ref().map(redact);
+        redacted.flashbots_auth_key = self.flashbots_auth_key.as_ref().map(redact);
+        redacted.api_token = self.api_token.as_ref().map(redact);
+        redacted.one_inch_api_key = self.one_inch_api_key.as_ref().map(redact);
+        redacted.zero_ex_api_key = self.zero_ex_api_key.as_ref().map(redact);
+        redacted.tenderly_access_key = self.tenderly_access_key.as_ref().map(redact);
+        // node and gateway urls usually embed an API key
+        redacted.rpc = self.rpc.iter().map(redact).collect();
+        redacted.submit_rpc = self.submit_rpc.iter().map(redact).collect();
+        redacted.read_rpc = self.read_rpc.iter().map(redact).collect();
+        redacted.ws_rpc = self.ws_rpc.as_ref().map(redact);
+        redacted.subgraph_url = self.subgraph_url.as_ref().map(redact);
+        redacted.alert_webhook_url = self.alert_webhook_url.as_ref().map(redact);
+        redacted.opportunity_webhook_url = self.opportunity_webhook_url.as_ref().map(redact);
+        // may hold a password
+        redacted.redis_url = self.redis_url.as_ref().map(redact);
+        redacted
+    }
+}
+
+/// Commands run instead of the bot, exiting when done.
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Scan once for liquidatable accounts, print them and exit.
+    Scan(ScanArgs),
+    /// Build, simulate and send a single liquidation.
+    Liquidate(LiquidateArgs),
+    /// Deploy the bundled Liquidator contract and print its address.
+    DeployLiquidator(DeployLiquidatorArgs),
+    /// Export the pool's past liquidations as CSV.
+    ExportLiquidations(ExportLiquidationsArgs),
+    /// Summarize realized profit from the liquidation history.
+    Pnl(PnlArgs),
+    /// Export, import or inspect the borrower state cache.
+    State(StateArgs),
+}
+
+/// Scan once for liquidatable accounts, print them and exit, taking the bot's args.
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
+    #[command(flatten)]
+    args: Args,
+
+    /// Print the accounts as JSON instead of a table.
+    #[arg(long)]
+    json: bool,
+}
+
+/// Build, simulate and send a single liquidation, taking the bot's args.
+#[derive(clap::Args, Debug)]
+struct LiquidateArgs {
+    #[command(flatten)]
+    args: Args,
+
+    /// Borrower to liquidate.
+    #[arg(long)]
+    borrower: String,
+
+    /// Collateral asset to seize.
+    #[arg(long)]
+    collateral: String,
+
+    /// Debt asset to repay.
+    #[arg(long)]
+    debt: String,
+
+    /// Debt to repay (in units of the debt asset), as much as the close factor allows if unset.
+    #[arg(long)]
+    amount: Option<u128>,
+}
+
+/// Deploy the bundled Liquidator contract for the deployment and print its address, taking
+/// the bot's args without --liquidator-address.
+#[derive(clap::Args, Debug)]
+#[command(mut_arg("liquidator_address", |arg| arg.required(false).default_value("")))]
+struct DeployLiquidatorArgs {
+    #[command(flatten)]
+    args: Args,
+}
+
+/// Export the pool's past liquidations as CSV, joined with the oracle prices of their
+/// block, taking the bot's args without --liquidator-address.
+#[derive(clap::Args, Debug)]
+#[command(mut_arg("liquidator_address", |arg| arg.required(false).default_value("")))]
+struct ExportLiquidationsArgs {
+    #[command(flatten)]
+    args: Args,
+
+    /// First block to export, the deployment's creation block if unset.
+    #[arg(long)]
+    from_block: Option<u64>,
+
+    /// Last block to export, the latest if unset.
+    #[arg(long)]
+    to_block: Option<u64>,
+
+    /// File to write the CSV to, stdout if unset.
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+// the bot's args, with the commands that can run instead, which take the bot's args again
+// where they need them
+fn cli() -> clap::Command {
+    CliCommand::augment_subcommands(Args::command())
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+}
+
+// what to run once the provider is up
+enum Command {
+    Run,
+    Scan {
+        json: bool,
+    },
+    DeployLiquidator,
+    ExportLiquidations {
+        from_block: Option<u64>,
+        to_block: Option<u64>,
+        output: Option<PathBuf>,
+    },
+    Liquidate {
+        borrower: String,
+        collateral: String,
+        debt: String,
+        amount: Option<u128>,
+    },
 }
 
 #[tokio::main]
@@ -59,64 +876,863 @@ async fn main() -> Result<()> {
         .with_target("artemis_core", Level::INFO)
         .with_target("aave_v3_liquidator", Level::INFO);
 
+    // the pnl report and state commands only read files, not needing the bot's args
+    let matches = cli().get_matches();
+    let (args, command) = match matches.subcommand_name() {
+        None => (
+            Args::from_arg_matches(&matches).unwrap_or_else(|e| e.format(&mut cli()).exit()),
+            Command::Run,
+        ),
+        Some(_) => match CliCommand::from_arg_matches(&matches)
+            .unwrap_or_else(|e| e.format(&mut cli()).exit())
+        {
+            CliCommand::Pnl(pnl) => return history::print_pnl(&pnl),
+            CliCommand::State(state) => return state::run(&state),
+            CliCommand::Scan(scan) => (scan.args, Command::Scan { json: scan.json }),
+            CliCommand::DeployLiquidator(deploy) => (deploy.args, Command::DeployLiquidator),
+            CliCommand::ExportLiquidations(export) => {
+                let command = Command::ExportLiquidations {
+                    from_block: export.from_block,
+                    to_block: export.to_block,
+                    output: export.output,
+                };
+                (export.args, command)
+            }
+            CliCommand::Liquidate(liquidate) => {
+                let command = Command::Liquidate {
+                    borrower: liquidate.borrower,
+                    collateral: liquidate.collateral,
+                    debt: liquidate.debt,
+                    amount: liquidate.amount,
+                };
+                (liquidate.args, command)
+            }
+        },
+    };
+
+    // the scan report, the deployed address and the export go to stdout, so their logs go
+    // to stderr
+    let to_stderr = matches!(
+        command,
+        Command::Scan { .. } | Command::DeployLiquidator | Command::ExportLiquidations { .. }
+    );
+    let writer = move || -> Box<dyn std::io::Write> {
+        if to_stderr {
+            Box::new(std::io::stderr())
+        } else {
+            Box::new(std::io::stdout())
+        }
+    };
+    let json = args.log_format == LogFormat::Json;
     tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
+        .with((!json).then(|| tracing_subscriber::fmt::layer().with_writer(writer)))
+        .with(json.then(|| tracing_subscriber::fmt::layer().json().with_writer(writer)))
         .with(filter)
         .init();
 
-    let args = Args::parse();
-    println!("{:?}", args);
+    info!("{:?}", args.redacted());
+    for address in [args.api_listen_address, args.grpc_listen_address]
+        .into_iter()
+        .flatten()
+    {
+        api::check_listen_address(address, args.api_token.as_deref())?;
+    }
+    #[cfg(not(feature = "grpc"))]
+    if args.grpc_listen_address.is_some() {
+        return Err(anyhow!(
+            "--grpc-listen-address: built without the `grpc` feature"
+        ));
+    }
 
     // Set up ethers provider.
-    let rpc = Http::from_str(&args.rpc)?;
+    let limits = RateLimits {
+        requests_per_sec: args.rpc_requests_per_sec,
+        compute_units_per_sec: args.rpc_compute_units_per_sec,
+    };
+    let failover = FailoverTransport::new(endpoints(&args.rpc, limits)?)
+        .with_heavy_read_endpoints(endpoints(&args.read_rpc, limits)?)
+        .with_submit_endpoints(endpoints(&args.submit_rpc, limits)?);
+    // nothing is sent to the endpoints when replaying
+    if args.replay_rpc.is_none() {
+        failover.spawn_health_checks(Duration::from_secs(args.rpc_health_check_interval_secs));
+    }
+    let failovers = failover.failovers();
+    let rpc_health = failover.health();
+    let rpc = RetryTransport::new(
+        failover,
+        RetryPolicy {
+            max_attempts: args.rpc_max_attempts.max(1),
+            initial_backoff: Duration::from_millis(args.rpc_initial_backoff_millis),
+            max_backoff: Duration::from_millis(args.rpc_max_backoff_millis),
+        },
+    );
+    let rpc = match (&args.record_rpc, &args.replay_rpc) {
+        (Some(path), _) => ReplayTransport::record(rpc, path)?,
+        (_, Some(path)) => ReplayTransport::replay(rpc, path)?,
+        _ => ReplayTransport::live(rpc),
+    };
     let provider = Provider::new(rpc);
 
     let chain_id = provider.get_chainid().await?.as_u64();
-    println!("Using chain ID: {}", chain_id);
+    info!("Using chain ID: {}", chain_id);
+    let config = build_config(&args, chain_id)?;
+    std::fs::create_dir_all(&config.state_dir)?;
+    let private_relays = read_private_relays(&args.private_relays_file, chain_id)?;
+    let shared_state = match &args.redis_url {
+        Some(url) => Some(
+            SharedState::connect(
+                url,
+                chain_id,
+                config.pool_address,
+                Duration::from_secs(args.claim_lease_secs),
+            )
+            .await?,
+        ),
+        None => None,
+    };
 
-    let wallet: LocalWallet = args
-        .private_key
-        .parse::<LocalWallet>()
-        .unwrap()
-        .with_chain_id(chain_id);
-    let address = wallet.address();
+    if let Command::Scan { json } = command {
+        let mut strategy = AaveStrategy::new(
+            Arc::new(provider),
+            config,
+            args.deployment,
+            args.liquidator_address,
+            args.use_aave_liquidator,
+        );
+        if let Some(shared_state) = shared_state {
+            strategy = strategy.with_shared_state(shared_state);
+        }
+        strategy.load_state().await?;
+        return scan::print_accounts(&strategy.scan_once().await?, json);
+    }
 
-    let provider = Arc::new(provider.nonce_manager(address).with_signer(wallet.clone()));
+    if let Command::ExportLiquidations {
+        from_block,
+        to_block,
+        output,
+    } = command
+    {
+        let client = Arc::new(provider);
+        let mut deployment = deployment_config(&args)?;
+        resolve_deployment_addresses(client.clone(), &mut deployment).await?;
+        let to_block = match to_block {
+            Some(block) => block,
+            None => client.get_block_number().await?.as_u64(),
+        };
+        let writer: Box<dyn std::io::Write> = match output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(std::io::stdout().lock()),
+        };
+        let count = export::export_liquidations(
+            client,
+            deployment.pool_address,
+            deployment.oracle_address,
+            match from_block {
+                Some(block) => block,
+                None => creation_block(&*client, &deployment).await,
+            },
+            to_block,
+            &LogRange::new(args.log_block_range),
+            writer,
+        )
+        .await?;
+        info!("Exported {} liquidations", count);
+        return Ok(());
+    }
 
-    // Set up engine.
-    let mut engine: Engine<Event, Action> = Engine::default();
+    // shared by the strategy and the executors, and engaged through the admin API
+    let kill_switch = KillSwitch::new(args.kill_switch_file.clone(), args.kill_switch);
+    if let Some(reason) = kill_switch.reason() {
+        warn!("Starting halted, {}", reason);
+    }
 
-    // Set up time collector.
-    let time_collector = Box::new(TimeCollector::new(POLL_INTERVAL_SECS));
-    let time_collector = CollectorMap::new(time_collector, Event::NewTick);
-    engine.add_collector(Box::new(time_collector));
+    // a detector holds no key, it queues what it finds for the executors to submit
+    if args.role == Role::Detect {
+        let shared_state = shared_state.ok_or(anyhow!("--role detect needs --redis-url"))?;
+        let mut engine: Engine<Event, Action> = Engine::default();
+        let supervisor = Supervisor::new(
+            RestartLimits {
+                max_restarts: args.max_task_restarts,
+                window: Duration::from_secs(args.task_restart_window_secs),
+            },
+            args.alert_webhook_url.clone(),
+        );
+        let (scan_interval, watchlist_interval) =
+            add_tick_collectors(&mut engine, &supervisor, &args);
+        let (control, control_collector) = control::channel(&args.audit_log_file())?;
+        let control_collector = CollectorMap::new(Box::new(control_collector), Event::Control);
+        engine.add_collector(Box::new(control_collector));
+        add_ws_collectors(&mut engine, &supervisor, &args).await?;
 
-    let config = Config {
-        bid_percentage: args.bid_percentage,
+        let shutdown = Shutdown::new();
+        let mut strategy = AaveStrategy::new(
+            Arc::new(provider),
+            config,
+            args.deployment.clone(),
+            args.liquidator_address,
+            args.use_aave_liquidator,
+        )
+        .with_poll_intervals(scan_interval, watchlist_interval)
+        .with_shutdown(shutdown.clone())
+        .with_shared_state(shared_state)
+        .with_role(Role::Detect)
+        .with_l1_fee(L1FeeModel::for_chain(chain_id))
+        .with_kill_switch(kill_switch.clone());
+        if let Some(feed) = spawn_grpc(&args) {
+            strategy = strategy.with_opportunity_feed(feed);
+        }
+        if let Some(url) = args.opportunity_webhook_url.clone() {
+            strategy = strategy.with_opportunity_webhook(OpportunityWebhook::new(url));
+        }
+        spawn_admin(
+            &args,
+            strategy.status(),
+            rpc_health,
+            control,
+            kill_switch,
+            &supervisor,
+        )?;
+        engine.add_strategy(Box::new(supervisor.supervise("strategy", strategy)));
+        return run_engine(engine, supervisor, shutdown).await;
+    }
+
+    let signer = build_signer(
+        &SignerConfig {
+            kind: args.signer,
+            private_key: args.private_key.clone(),
+            keystore: args.keystore.clone(),
+            keystore_password: args.keystore_password.clone(),
+            kms_key_id: args.kms_key_id.clone(),
+            ledger_index: args.ledger_index,
+        },
         chain_id,
-    };
+    )
+    .await?;
+    let address = signer.address();
+    info!("Sending txs from {:?}", address);
+
+    // numbered above the signer, so sends failing after signing re-sync the nonce
+    let provider = Arc::new(
+        NonceManager::new(provider.with_signer(signer), address).with_failovers(failovers),
+    );
+    provider.recover(args.cancel_pending).await?;
+
+    if let Command::DeployLiquidator = command {
+        let config = deployment_config(&args)?;
+        let liquidator = deploy::deploy_liquidator(
+            provider.clone(),
+            config.pool_address,
+            config.uniswap_factory,
+            address,
+        )
+        .await?;
+        println!("{:?}", liquidator);
+        return Ok(());
+    }
+
+    if let Command::Liquidate {
+        borrower,
+        collateral,
+        debt,
+        amount,
+    } = command
+    {
+        let mut strategy = AaveStrategy::new(
+            Arc::new(provider.clone()),
+            config,
+            args.deployment,
+            args.liquidator_address,
+            args.use_aave_liquidator,
+        );
+        strategy.load_reserves().await?;
+        let tx = strategy
+            .build_manual_liquidation(
+                Address::from_str(&borrower)?,
+                Address::from_str(&collateral)?,
+                Address::from_str(&debt)?,
+                amount.map(U256::from),
+            )
+            .await?;
+
+        // the executor records the outcome once mined, which shutdown waits for
+        let shutdown = Shutdown::new();
+        ProtectExecutor::new(provider.clone(), provider.clone())
+            .with_fee_rebate_bps(strategy.fee_rebate_bps())
+            .with_l1_fee(L1FeeModel::for_chain(chain_id))
+            .with_history(LiquidationHistory::new(&args.history_file()), chain_id)
+            .with_valuation(strategy.valuation())
+            .with_private_relays(private_relays)
+            .with_kill_switch(kill_switch)
+            .with_shutdown(shutdown.clone())
+            .execute(tx)
+            .await?;
+        if !shutdown
+            .drain(Duration::from_secs(SHUTDOWN_TIMEOUT_SECS))
+            .await
+        {
+            error!("Timed out waiting for the liquidation to be mined");
+        }
+        return Ok(());
+    }
+
+    provider.spawn_monitor(
+        Duration::from_secs(args.nonce_check_interval_secs),
+        args.cancel_pending,
+    );
+
+    // Set up engine, every task run under the supervisor.
+    let mut engine: Engine<Event, Action> = Engine::default();
+    let supervisor = Supervisor::new(
+        RestartLimits {
+            max_restarts: args.max_task_restarts,
+            window: Duration::from_secs(args.task_restart_window_secs),
+        },
+        args.alert_webhook_url.clone(),
+    );
+
+    // Set up time and watchlist collectors, the executor of a cluster only takes queued
+    // opportunities.
+    let poll_intervals =
+        (args.role != Role::Execute).then(|| add_tick_collectors(&mut engine, &supervisor, &args));
+
+    // Set up wallet check collector, making sure the sender can keep liquidating.
+    let wallet_check_collector = Box::new(
+        TimeCollector::new(args.wallet_check_interval_secs)
+            .with_jitter_millis(args.tick_jitter_millis),
+    );
+    let wallet_check_collector = CollectorMap::new(wallet_check_collector, Event::WalletCheckTick);
+    engine.add_collector(Box::new(
+        supervisor.supervise("wallet check collector", wallet_check_collector),
+    ));
+
+    // Set up harvest collector, collecting liquidation proceeds.
+    if let Some(harvest_interval_secs) = args.harvest_interval_secs {
+        let harvest_collector = Box::new(
+            TimeCollector::new(harvest_interval_secs).with_jitter_millis(args.tick_jitter_millis),
+        );
+        let harvest_collector = CollectorMap::new(harvest_collector, Event::HarvestTick);
+        engine.add_collector(Box::new(
+            supervisor.supervise("harvest collector", harvest_collector),
+        ));
+    }
+
+    // Set up control collector, the handle is what control interfaces submit commands through.
+    let (control, control_collector) = control::channel(&args.audit_log_file())?;
+    // not supervised, its receiver can only be taken once
+    let control_collector = CollectorMap::new(Box::new(control_collector), Event::Control);
+    engine.add_collector(Box::new(control_collector));
+
+    if args.role == Role::Execute {
+        let queue_collector = Box::new(OpportunityQueueCollector::new(
+            shared_state
+                .clone()
+                .ok_or(anyhow!("--role execute needs --redis-url"))?,
+        ));
+        let queue_collector = CollectorMap::new(queue_collector, Event::QueuedOpportunity);
+        engine.add_collector(Box::new(
+            supervisor.supervise("opportunity queue collector", queue_collector),
+        ));
+    } else {
+        add_ws_collectors(&mut engine, &supervisor, &args).await?;
+    }
+
+    // Set up MEV-Share collector, turning oracle update hints into backrun opportunities.
+    if args.mev_share && args.role == Role::All {
+        let mut deployment_config = deployment_config(&args)?;
+        resolve_deployment_addresses(provider.clone(), &mut deployment_config).await?;
+        let mev_share_collector = Box::new(MevShareCollector::new(
+            provider.clone(),
+            deployment_config.pool_data_provider,
+            deployment_config.oracle_address,
+            args.mev_share_sse_url.clone(),
+        ));
+        let mev_share_collector = CollectorMap::new(mev_share_collector, Event::BackrunPriceUpdate);
+        engine.add_collector(Box::new(
+            supervisor.supervise("mev share collector", mev_share_collector),
+        ));
+    }
 
-    let strategy = AaveStrategy::new(
+    let shutdown = Shutdown::new();
+    // shared by the executor counting outcomes and the strategy holding submission
+    let breaker = CircuitBreaker::new(BreakerLimits {
+        max_consecutive_reverts: args.max_consecutive_reverts,
+        max_consecutive_losses: args.max_consecutive_losses,
+        max_price_deviation_bps: args.max_price_deviation_bps,
+        cooldown_secs: args.breaker_cooldown_secs,
+    });
+    // shared by the executor committing debt as it sends and the strategy passing on more
+    let exposure = ExposureLimiter::new(args.max_exposure_usd);
+    let mut strategy = AaveStrategy::new(
         Arc::new(provider.clone()),
         config,
-        args.deployment,
+        args.deployment.clone(),
         args.liquidator_address,
         args.use_aave_liquidator,
-    );
-    engine.add_strategy(Box::new(strategy));
+    )
+    .with_circuit_breaker(breaker.clone())
+    .with_kill_switch(kill_switch.clone())
+    .with_exposure_limiter(exposure.clone())
+    .with_shutdown(shutdown.clone())
+    .with_role(args.role)
+    .with_l1_fee(L1FeeModel::for_chain(chain_id));
+    if let Some((scan_interval, watchlist_interval)) = poll_intervals {
+        strategy = strategy.with_poll_intervals(scan_interval, watchlist_interval);
+    }
+    if let Some(shared_state) = shared_state {
+        strategy = strategy.with_shared_state(shared_state);
+    }
+    if let Some(feed) = spawn_grpc(&args) {
+        strategy = strategy.with_opportunity_feed(feed);
+    }
+    if let Some(url) = args.opportunity_webhook_url.clone() {
+        strategy = strategy.with_opportunity_webhook(OpportunityWebhook::new(url));
+    }
+    let fee_rebate_bps = strategy.fee_rebate_bps();
+    let valuation = strategy.valuation();
+
+    spawn_admin(
+        &args,
+        strategy.status(),
+        rpc_health,
+        control,
+        kill_switch,
+        &supervisor,
+    )?;
+    engine.add_strategy(Box::new(supervisor.supervise("strategy", strategy)));
 
-    let executor = Box::new(ProtectExecutor::new(provider.clone(), provider.clone()));
+    let executor = Box::new(
+        ProtectExecutor::new(provider.clone(), provider.clone())
+            .with_jitter_millis(args.submission_jitter_millis)
+            .with_max_opportunity_age(MaxOpportunityAge {
+                blocks: args.max_opportunity_age_blocks,
+                secs: (args.max_opportunity_age_secs > 0).then_some(args.max_opportunity_age_secs),
+            })
+            .with_fee_rebate_bps(fee_rebate_bps)
+            .with_l1_fee(L1FeeModel::for_chain(chain_id))
+            .with_history(LiquidationHistory::new(&args.history_file()), chain_id)
+            .with_valuation(valuation)
+            .with_private_relays(private_relays)
+            .with_circuit_breaker(breaker)
+            .with_kill_switch(kill_switch.clone())
+            .with_exposure_limiter(exposure)
+            .with_shutdown(shutdown.clone()),
+    );
 
     let executor = ExecutorMap::new(executor, |action| match action {
         Action::SubmitTx(tx) => Some(tx),
+        _ => None,
     });
 
-    engine.add_executor(Box::new(executor));
-    // Start engine.
-    if let Ok(mut set) = engine.run().await {
-        while let Some(res) = set.join_next().await {
-            info!("res: {:?}", res);
+    engine.add_executor(Box::new(supervisor.supervise("executor", executor)));
+
+    if args.mev_share {
+        let auth_signer = match &args.flashbots_auth_key {
+            Some(key) => key.parse::<LocalWallet>()?,
+            None => LocalWallet::new(&mut ethers::core::rand::thread_rng()),
+        };
+        let mev_share_executor = Box::new(
+            MevShareExecutor::new(
+                provider.clone(),
+                auth_signer,
+                args.mev_share_relay_url.clone(),
+            )
+            .with_history(
+                LiquidationHistory::new(&args.history_file()),
+                chain_id,
+                valuation,
+            )
+            .with_kill_switch(kill_switch.clone())
+            .with_shutdown(shutdown.clone()),
+        );
+        let mev_share_executor = ExecutorMap::new(mev_share_executor, |action| match action {
+            Action::SubmitBundle(bundle) => Some(bundle),
+            _ => None,
+        });
+        engine.add_executor(Box::new(
+            supervisor.supervise("mev share executor", mev_share_executor),
+        ));
+    }
+    run_engine(engine, supervisor, shutdown).await
+}
+
+// the tunables watcher, liveness checks and admin API, reading the strategy's status and
+// submitting through the control handle
+fn spawn_admin(
+    args: &Args,
+    status: SharedStatus,
+    rpc_health: SharedRpcHealth,
+    control: ControlHandle,
+    kill_switch: KillSwitch,
+    supervisor: &Supervisor,
+) -> Result<()> {
+    // Apply the tunables file through the control handle, so changes are audited.
+    if let Some(path) = args.tunables_file.clone() {
+        let control = control.clone();
+        let check_interval = Duration::from_secs(TUNABLES_CHECK_INTERVAL_SECS);
+        tokio::spawn(async move {
+            if let Err(e) = tunables::watch(path, control, check_interval).await {
+                error!("Tunables watcher error: {}", e);
+            }
+        });
+    }
+
+    // every tick would look stalled
+    if args
+        .liveness_timeout_secs
+        .is_some_and(|timeout| timeout <= args.poll_interval_secs)
+    {
+        return Err(anyhow!(
+            "--liveness-timeout-secs must exceed --poll-interval-secs"
+        ));
+    }
+    // an executor has no ticks to go stale
+    let liveness = Liveness::new(
+        status.clone(),
+        rpc_health,
+        args.liveness_timeout_secs
+            .filter(|_| args.role != Role::Execute)
+            .map(Duration::from_secs),
+    );
+    tokio::spawn(liveness.clone().watch(supervisor.clone()));
+
+    if let Some(address) = args.api_listen_address {
+        let api_token = args.api_token.clone();
+        tokio::spawn(async move {
+            let serving = api::serve(address, status, liveness, control, kill_switch, api_token);
+            if let Err(e) = serving.await {
+                error!("Admin API error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+// the gRPC API, if enabled, returning the feed of opportunities it streams
+#[cfg(feature = "grpc")]
+fn spawn_grpc(args: &Args) -> Option<OpportunityFeed> {
+    let address = args.grpc_listen_address?;
+    let feed = OpportunityFeed::default();
+    let api_token = args.api_token.clone();
+    tokio::spawn({
+        let feed = feed.clone();
+        async move {
+            if let Err(e) = grpc::serve(address, feed, api_token).await {
+                error!("gRPC API error: {}", e);
+            }
         }
+    });
+    Some(feed)
+}
+
+#[cfg(not(feature = "grpc"))]
+fn spawn_grpc(_args: &Args) -> Option<OpportunityFeed> {
+    None
+}
+
+// the scan and watchlist tick collectors, returning handles on their intervals
+fn add_tick_collectors(
+    engine: &mut Engine<Event, Action>,
+    supervisor: &Supervisor,
+    args: &Args,
+) -> (PollInterval, PollInterval) {
+    // Set up time collector.
+    let time_collector = Box::new(
+        TimeCollector::new(args.poll_interval_secs).with_jitter_millis(args.tick_jitter_millis),
+    );
+    let scan_interval = time_collector.interval();
+    let time_collector = CollectorMap::new(time_collector, Event::NewTick);
+    engine.add_collector(Box::new(
+        supervisor.supervise("time collector", time_collector),
+    ));
+
+    // Set up watchlist collector, polling near-liquidatable borrowers between full scans.
+    let watchlist_collector = Box::new(
+        TimeCollector::new(args.watchlist_poll_interval_secs)
+            .with_jitter_millis(args.tick_jitter_millis),
+    );
+    let watchlist_interval = watchlist_collector.interval();
+    let watchlist_collector = CollectorMap::new(watchlist_collector, Event::WatchlistTick);
+    engine.add_collector(Box::new(
+        supervisor.supervise("watchlist collector", watchlist_collector),
+    ));
+
+    (scan_interval, watchlist_interval)
+}
+
+// oracle update, reserve data and liquidation collectors, if we have a websocket
+async fn add_ws_collectors(
+    engine: &mut Engine<Event, Action>,
+    supervisor: &Supervisor,
+    args: &Args,
+) -> Result<()> {
+    if let Some(ws_rpc) = &args.ws_rpc {
+        let ws_provider = Arc::new(Provider::<Ws>::connect(ws_rpc).await?);
+        let mut deployment_config = deployment_config(args)?;
+        resolve_deployment_addresses(ws_provider.clone(), &mut deployment_config).await?;
+        let price_update_collector = Box::new(PriceUpdateCollector::new(
+            ws_provider.clone(),
+            deployment_config.pool_data_provider,
+            deployment_config.oracle_address,
+        ));
+        let price_update_collector =
+            CollectorMap::new(price_update_collector, Event::PendingPriceUpdate);
+        engine.add_collector(Box::new(
+            supervisor.supervise("price update collector", price_update_collector),
+        ));
+
+        // keep reserve rates current from pool logs instead of polling every reserve
+        let reserve_data_collector = Box::new(ReserveDataCollector::new(
+            ws_provider.clone(),
+            deployment_config.pool_address,
+        ));
+        let reserve_data_collector =
+            CollectorMap::new(reserve_data_collector, Event::ReserveDataUpdate);
+        engine.add_collector(Box::new(
+            supervisor.supervise("reserve data collector", reserve_data_collector),
+        ));
+
+        // attribute every liquidation on the pool, to keep score against competitors
+        let liquidation_call_collector = Box::new(LiquidationCallCollector::new(
+            ws_provider,
+            deployment_config.pool_address,
+        ));
+        let liquidation_call_collector =
+            CollectorMap::new(liquidation_call_collector, Event::LiquidationCall);
+        engine.add_collector(Box::new(
+            supervisor.supervise("liquidation call collector", liquidation_call_collector),
+        ));
     }
+
     Ok(())
 }
+
+// run until a task fails for good, or a signal stops the engine once work in flight is done
+async fn run_engine(
+    engine: Engine<Event, Action>,
+    supervisor: Supervisor,
+    shutdown: Shutdown,
+) -> Result<()> {
+    // Start engine, on SIGINT or SIGTERM stop taking events and let work in flight finish.
+    // Supervised tasks never exit, one that does or a task given up on is unrecoverable.
+    let failure = match engine.run().await {
+        Ok(mut set) => tokio::select! {
+            res = set.join_next() => {
+                let reason = format!("engine task exited: {:?}", res);
+                supervisor.fail(reason.clone());
+                reason
+            }
+            reason = supervisor.failed() => reason,
+            result = wait_for_signal() => {
+                result?;
+                info!("Shutting down, waiting for work in flight");
+                if shutdown.drain(Duration::from_secs(SHUTDOWN_TIMEOUT_SECS)).await {
+                    info!("Shut down cleanly");
+                } else {
+                    error!("Timed out waiting for work in flight");
+                }
+                return Ok(());
+            }
+        },
+        // the strategy's state couldn't be synced, the supervisor gave up on it
+        Err(e) => format!("engine failed to start: {}", e),
+    };
+    supervisor
+        .flush_alerts(Duration::from_secs(ALERT_TIMEOUT_SECS))
+        .await;
+    Err(anyhow!(failure))
+}
+
+// the strategy's config from the command line
+// the deployment's addresses, with the pool and wrapped native token of a fork set on the
+// command line
+fn deployment_config(args: &Args) -> Result<DeploymentConfig> {
+    let parse = |address: &Option<String>| address.as_deref().map(Address::from_str).transpose();
+    let config = get_deployment_config(args.deployment.clone()).with_overrides(
+        parse(&args.pool_address)?,
+        parse(&args.wrapped_native_address)?,
+    );
+    if config.pool_address.is_zero() || config.weth_address.is_zero() {
+        return Err(anyhow!(
+            "A fork deployment needs --pool-address and --wrapped-native-address"
+        ));
+    }
+    Ok(config)
+}
+
+fn build_config(args: &Args, chain_id: u64) -> Result<Config> {
+    let addresses = |list: &[String]| -> Result<HashSet<Address>> {
+        Ok(list
+            .iter()
+            .map(|address| Address::from_str(address))
+            .collect::<Result<_, _>>()?)
+    };
+    let allowlist = |list: &[String]| -> Result<Option<HashSet<Address>>> {
+        (!list.is_empty()).then(|| addresses(list)).transpose()
+    };
+    Ok(Config {
+        bid_percentage: args.bid_percentage,
+        min_bid_percentage: args.min_bid_percentage,
+        max_bid_percentage: args.max_bid_percentage,
+        chain_id,
+        watchlist_health_factor_bps: args.watchlist_health_factor_bps,
+        risk_health_factor_bps: args.risk_health_factor_bps,
+        min_confidence_bps: args.min_confidence_bps,
+        public_confidence_bps: args.public_confidence_bps,
+        scoring: ScoringWeights {
+            landing_bps: args.score_landing_weight_bps,
+            liquidity_bps: args.score_liquidity_weight_bps,
+            revert_bps: args.score_revert_weight_bps,
+        },
+        partner_accounts_file: args
+            .partner_accounts_file
+            .clone()
+            .unwrap_or_else(|| args.state_dir.join(PARTNER_ACCOUNTS_FILE)),
+        repay_adapter: args
+            .repay_adapter_address
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()?,
+        paraswap_api_url: args.paraswap_api_url.clone(),
+        target_health_factor_bps: args.target_health_factor_bps,
+        max_slippage_bps: args.max_slippage_bps,
+        fee_rebate: args.fee_rebate,
+        fee_rebate_bps: args.fee_rebate_bps,
+        smart_account_reconcile_ticks: args.smart_account_reconcile_ticks,
+        eoa_reconcile_ticks: args.eoa_reconcile_ticks,
+        min_profit_eth: U256::from(args.min_profit_wei),
+        profit_currency: match args.profit_currency {
+            ProfitDenomination::Native => ProfitCurrency::Native,
+            ProfitDenomination::Usd => ProfitCurrency::Usd,
+            ProfitDenomination::Asset => ProfitCurrency::Asset(Address::from_str(
+                args.profit_asset
+                    .as_deref()
+                    .ok_or(anyhow!("--profit-currency asset needs --profit-asset"))?,
+            )?),
+        },
+        min_gas_balance_wei: U256::from(args.min_gas_balance_wei),
+        pause_on_wallet_problems: args.pause_on_wallet_problems,
+        max_sync_lag: args.max_sync_lag,
+        reference_oracle: args
+            .reference_oracle
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()?,
+        min_debt_usd: args.min_debt_usd,
+        evaluation_concurrency: args.evaluation_concurrency,
+        max_liquidation_legs: args.max_liquidation_legs,
+        multi_hop_swaps: args.multi_hop_swaps,
+        flash_liquidity_pools: args
+            .flash_liquidity_pools
+            .iter()
+            .map(|pool| FlashLiquidityPool::from_str(pool))
+            .collect::<Result<_>>()?,
+        aggregator_flash_swaps: args.aggregator_flash_swaps,
+        price_sources: args.price_sources.clone(),
+        max_price_source_deviation_bps: args.max_price_source_deviation_bps,
+        harvest: match args.harvest_interval_secs {
+            Some(_) => Some(HarvestConfig {
+                target: args
+                    .harvest_target
+                    .as_deref()
+                    .map(Address::from_str)
+                    .transpose()?,
+                keep: args
+                    .harvest_keep
+                    .iter()
+                    .map(|asset| Address::from_str(asset))
+                    .collect::<Result<_, _>>()?,
+                min_swap_wei: U256::from(args.harvest_min_swap_wei),
+                cold_wallet: args
+                    .cold_wallet
+                    .as_deref()
+                    .map(Address::from_str)
+                    .transpose()?,
+                sweep_threshold: U256::from(args.sweep_threshold),
+            }),
+            None => None,
+        },
+        approval: args
+            .approval_notional_usd
+            .map(|notional_usd| ApprovalConfig {
+                notional_usd,
+                dir: args.approval_dir.clone(),
+                ttl_secs: args.approval_ttl_secs,
+            }),
+        one_inch_api_key: args.one_inch_api_key.clone(),
+        zero_ex_api_key: args.zero_ex_api_key.clone(),
+        tracer: match args.trace_simulations {
+            Some(TraceMode::Local) => Some(TraceBackend::DebugTraceCall),
+            Some(TraceMode::Tenderly) => {
+                let required = |value: &Option<String>, arg: &str| {
+                    value
+                        .clone()
+                        .ok_or(anyhow!("Tracing with Tenderly needs --{}", arg))
+                };
+                Some(TraceBackend::Tenderly {
+                    account: required(&args.tenderly_account, "tenderly-account")?,
+                    project: required(&args.tenderly_project, "tenderly-project")?,
+                    access_key: required(&args.tenderly_access_key, "tenderly-access-key")?,
+                })
+            }
+            None => None,
+        },
+        state_dir: args.state_dir.clone(),
+        reserve_cache_ttl_secs: args.reserve_cache_ttl_secs,
+        subgraph_url: args.subgraph_url.clone(),
+        multicall_address: args
+            .multicall_address
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()?,
+        pool_address: args
+            .pool_address
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()?,
+        wrapped_native_address: args
+            .wrapped_native_address
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()?,
+        log_block_range: args.log_block_range,
+        ui_pool_data_provider: args
+            .ui_pool_data_provider
+            .as_deref()
+            .map(Address::from_str)
+            .transpose()?,
+        approval_buffer_bps: args.approval_buffer_bps,
+        receive_a_token: addresses(&args.receive_a_token)?,
+        filter: LiquidationFilter {
+            collateral_allowlist: allowlist(&args.collateral_allowlist)?,
+            collateral_denylist: addresses(&args.collateral_denylist)?,
+            debt_allowlist: allowlist(&args.debt_allowlist)?,
+            debt_denylist: addresses(&args.debt_denylist)?,
+            borrower_blacklist: addresses(&args.borrower_blacklist)?,
+            shard: args
+                .shard
+                .as_deref()
+                .map(Shard::from_str)
+                .transpose()?
+                .unwrap_or_default(),
+        },
+    })
+}
+
+// endpoints named by host, the full url may hold an API key, each within its own budget
+fn endpoints(
+    urls: &[String],
+    limits: RateLimits,
+) -> Result<Vec<(String, RateLimitedTransport<Http>)>> {
+    urls.iter()
+        .map(|url| {
+            let name = reqwest::Url::parse(url)?
+                .host_str()
+                .unwrap_or_default()
+                .to_string();
+            Ok((
+                name,
+                RateLimitedTransport::new(Http::from_str(url)?, limits),
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,429 @@
+use async_trait::async_trait;
+use ethers::providers::{Middleware, MiddlewareError, PendingTransaction};
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, Address, BlockId, BlockNumber, TransactionRequest,
+    H256, U256,
+};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// Checks in a row the chain's nonce may stay behind txs sitting in the mempool before
+/// they're reported as stuck.
+pub const STUCK_AFTER_CHECKS: u32 = 3;
+
+/// Multiple of the current gas price cancellations pay, comfortably above the 10% bump
+/// nodes require to replace a tx.
+pub const CANCEL_GAS_PRICE_MULTIPLE: u64 = 2;
+
+// errors of a tx sent with a nonce the chain doesn't expect
+const NONCE_ERRORS: [&str; 4] = [
+    "nonce too low",
+    "nonce too high",
+    "invalid nonce",
+    "replacement transaction underpriced",
+];
+
+// errors of a tx the node already has, e.g. sent again by a retrying transport
+const ALREADY_KNOWN_ERRORS: [&str; 2] = ["already known", "already imported"];
+
+/// Nonces of the sender as the chain sees them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChainNonces {
+    /// Nonce of the next tx to be mined.
+    pub latest: U256,
+    /// Nonce after the txs sitting in the mempool.
+    pub pending: U256,
+}
+
+impl ChainNonces {
+    /// Txs sent but not mined yet.
+    pub fn in_flight(&self) -> U256 {
+        self.pending.saturating_sub(self.latest)
+    }
+}
+
+/// Whether a send failed because of the nonce it was given.
+pub fn is_nonce_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    NONCE_ERRORS.iter().any(|pattern| message.contains(pattern))
+}
+
+/// Whether a send failed because the node already has the very same tx.
+pub fn is_already_known(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ALREADY_KNOWN_ERRORS
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+#[derive(Debug)]
+pub struct NonceManagerError<M: Middleware>(M::Error);
+
+impl<M: Middleware> fmt::Display for NonceManagerError<M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<M: Middleware> std::error::Error for NonceManagerError<M> {}
+
+impl<M: Middleware> MiddlewareError for NonceManagerError<M> {
+    type Inner = M::Error;
+
+    fn from_err(e: Self::Inner) -> Self {
+        Self(e)
+    }
+
+    fn as_inner(&self) -> Option<&Self::Inner> {
+        Some(&self.0)
+    }
+}
+
+/// Hands out the sender's nonces like ethers' `NonceManagerMiddleware`, but re-reads
+/// them from the chain whenever they may have drifted: after a send fails, a tx it
+/// numbered never reaches the mempool, or the transport fails over to another node.
+/// Sits above the signer, so sends that fail after signing are seen.
+#[derive(Debug)]
+pub struct NonceManager<M> {
+    inner: M,
+    address: Address,
+    /// Next nonce to hand out, read from the chain on first use
+    next: Mutex<Option<U256>>,
+    /// Failovers of the transport, and how many there were at the last sync
+    failovers: Option<(Arc<AtomicU64>, AtomicU64)>,
+}
+
+impl<M: Middleware> NonceManager<M> {
+    pub fn new(inner: M, address: Address) -> Self {
+        Self {
+            inner,
+            address,
+            next: Mutex::new(None),
+            failovers: None,
+        }
+    }
+
+    /// Re-sync the nonce once `failovers` moves, a new node may not have seen our txs.
+    pub fn with_failovers(mut self, failovers: Arc<AtomicU64>) -> Self {
+        let seen = failovers.load(Ordering::Relaxed);
+        self.failovers = Some((failovers, AtomicU64::new(seen)));
+        self
+    }
+
+    pub async fn chain_nonces(&self) -> Result<ChainNonces, NonceManagerError<M>> {
+        let count = |block: BlockNumber| {
+            self.inner
+                .get_transaction_count(self.address, Some(block.into()))
+        };
+        Ok(ChainNonces {
+            latest: count(BlockNumber::Latest)
+                .await
+                .map_err(NonceManagerError)?,
+            pending: count(BlockNumber::Pending)
+                .await
+                .map_err(NonceManagerError)?,
+        })
+    }
+
+    /// Read the next nonce from the chain, returning it.
+    pub async fn resync(&self) -> Result<U256, NonceManagerError<M>> {
+        let mut next = self.next.lock().await;
+        self.resync_locked(&mut next).await
+    }
+
+    async fn resync_locked(&self, next: &mut Option<U256>) -> Result<U256, NonceManagerError<M>> {
+        let pending = self.chain_nonces().await?.pending;
+        if let Some(expected) = *next {
+            if expected != pending {
+                warn!(
+                    "Nonce out of sync, expected {} but the chain is at {}",
+                    expected, pending
+                );
+            }
+        }
+        *next = Some(pending);
+        Ok(pending)
+    }
+
+    async fn next_nonce(&self) -> Result<U256, NonceManagerError<M>> {
+        let mut next = self.next.lock().await;
+        let failed_over = self.failovers.as_ref().is_some_and(|(failovers, seen)| {
+            let failovers = failovers.load(Ordering::Relaxed);
+            seen.swap(failovers, Ordering::Relaxed) != failovers
+        });
+        let nonce = match *next {
+            // a node just failed over to may not have seen our txs yet, so its pending
+            // nonce only moves ours forward
+            Some(nonce) if failed_over => nonce.max(self.chain_nonces().await?.pending),
+            Some(nonce) => nonce,
+            None => self.resync_locked(&mut next).await?,
+        };
+        *next = Some(nonce + 1);
+        Ok(nonce)
+    }
+
+    // only txs from the sender take its nonces
+    fn numbers(&self, tx: &TypedTransaction) -> bool {
+        tx.nonce().is_none() && tx.from().is_none_or(|from| *from == self.address)
+    }
+
+    // fill and sign a numbered tx here rather than in the signer below, so a node that
+    // already has it can be answered with its hash
+    async fn send_signed(
+        &self,
+        mut tx: TypedTransaction,
+        block: Option<BlockId>,
+    ) -> Result<PendingTransaction<'_, M::Provider>, M::Error> {
+        self.inner.fill_transaction(&mut tx, block).await?;
+        let signature = self.inner.sign_transaction(&tx, self.address).await?;
+        match self
+            .inner
+            .send_raw_transaction(tx.rlp_signed(&signature))
+            .await
+        {
+            Err(e) if is_already_known(&e.to_string()) => {
+                let tx_hash = tx.hash(&signature);
+                info!("Tx {:?} is already known to the node", tx_hash);
+                Ok(PendingTransaction::new(tx_hash, self.inner.provider()))
+            }
+            result => result,
+        }
+    }
+
+    /// Report txs stuck in the mempool at startup, cancelling them if `cancel` is set,
+    /// and start numbering after them.
+    pub async fn recover(&self, cancel: bool) -> Result<(), NonceManagerError<M>> {
+        let nonces = self.chain_nonces().await?;
+        if !nonces.in_flight().is_zero() {
+            warn!(
+                "{} txs pending from nonce {}",
+                nonces.in_flight(),
+                nonces.latest
+            );
+            if cancel {
+                self.cancel(nonces).await?;
+            }
+        }
+        self.resync().await?;
+        Ok(())
+    }
+
+    /// Replace every pending tx with an empty self-send at a higher fee.
+    pub async fn cancel(&self, nonces: ChainNonces) -> Result<Vec<H256>, NonceManagerError<M>> {
+        let gas_price = self
+            .inner
+            .get_gas_price()
+            .await
+            .map_err(NonceManagerError)?
+            * CANCEL_GAS_PRICE_MULTIPLE;
+        let mut tx_hashes = Vec::new();
+        let mut nonce = nonces.latest;
+        while nonce < nonces.pending {
+            let tx = TransactionRequest::new()
+                .from(self.address)
+                .to(self.address)
+                .value(0)
+                .gas(21_000)
+                .gas_price(gas_price)
+                .nonce(nonce);
+            let tx_hash = self
+                .inner
+                .send_transaction(tx, None)
+                .await
+                .map_err(NonceManagerError)?
+                .tx_hash();
+            info!("Cancelling tx at nonce {} with {:?}", nonce, tx_hash);
+            tx_hashes.push(tx_hash);
+            nonce += U256::one();
+        }
+        Ok(tx_hashes)
+    }
+}
+
+impl<M: Middleware + 'static> NonceManager<M> {
+    /// Check the sender's nonces every `interval`, re-syncing after a gap and reporting
+    /// txs stuck in the mempool, cancelling them if `cancel` is set.
+    pub fn spawn_monitor(self: &Arc<Self>, interval: Duration, cancel: bool) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut stuck_checks = 0;
+            let mut gap_checks = 0;
+            let mut last = ChainNonces::default();
+            loop {
+                tokio::time::sleep(interval).await;
+                let nonces = match manager.chain_nonces().await {
+                    Ok(nonces) => nonces,
+                    Err(e) => {
+                        error!("Error checking nonces: {}", e);
+                        continue;
+                    }
+                };
+
+                // a tx we numbered never reached the mempool, later ones can't be mined. A
+                // node just failed over to may lag behind our txs for a moment though
+                let next = *manager.next.lock().await;
+                gap_checks = if next.is_some_and(|next| next > nonces.pending) {
+                    gap_checks + 1
+                } else {
+                    0
+                };
+                if gap_checks >= STUCK_AFTER_CHECKS {
+                    if let Err(e) = manager.resync().await {
+                        error!("Error re-syncing nonce: {}", e);
+                    }
+                    gap_checks = 0;
+                }
+
+                stuck_checks = if nonces.in_flight().is_zero() || nonces.latest != last.latest {
+                    0
+                } else {
+                    stuck_checks + 1
+                };
+                last = nonces;
+                if stuck_checks < STUCK_AFTER_CHECKS {
+                    continue;
+                }
+                warn!(
+                    "{} txs stuck from nonce {}",
+                    nonces.in_flight(),
+                    nonces.latest
+                );
+                if cancel {
+                    if let Err(e) = manager.cancel(nonces).await {
+                        error!("Error cancelling stuck txs: {}", e);
+                    }
+                    stuck_checks = 0;
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl<M: Middleware> Middleware for NonceManager<M> {
+    type Error = NonceManagerError<M>;
+    type Provider = M::Provider;
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    async fn fill_transaction(
+        &self,
+        tx: &mut TypedTransaction,
+        block: Option<BlockId>,
+    ) -> Result<(), Self::Error> {
+        if self.numbers(tx) {
+            tx.set_nonce(self.next_nonce().await?);
+        }
+        self.inner
+            .fill_transaction(tx, block)
+            .await
+            .map_err(NonceManagerError)
+    }
+
+    async fn send_transaction<T: Into<TypedTransaction> + Send + Sync>(
+        &self,
+        tx: T,
+        block: Option<BlockId>,
+    ) -> Result<PendingTransaction<'_, Self::Provider>, Self::Error> {
+        let mut tx = tx.into();
+        if !self.numbers(&tx) {
+            return self
+                .inner
+                .send_transaction(tx, block)
+                .await
+                .map_err(NonceManagerError);
+        }
+        tx.set_nonce(self.next_nonce().await?);
+        let error = match self.send_signed(tx.clone(), block).await {
+            Ok(pending) => return Ok(pending),
+            Err(error) => error,
+        };
+
+        // the nonce handed out wasn't used, or the chain moved on without us
+        let nonce = self.resync().await?;
+        if !is_nonce_error(&error.to_string()) || tx.nonce() == Some(&nonce) {
+            return Err(NonceManagerError(error));
+        }
+        info!("Retrying tx with nonce {}: {}", nonce, error);
+        *self.next.lock().await = Some(nonce + 1);
+        tx.set_nonce(nonce);
+        self.send_signed(tx, block).await.map_err(NonceManagerError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockProvider, Provider};
+
+    #[tokio::test]
+    async fn test_next_nonce() {
+        let (provider, mock) = Provider::mocked();
+        let address = Address::repeat_byte(1);
+        let failovers = Arc::new(AtomicU64::new(0));
+        let manager = NonceManager::new(provider, address).with_failovers(failovers.clone());
+
+        // the mock answers the latest count last, as requests pop from the back
+        let push = |mock: &MockProvider, latest: u64, pending: u64| {
+            mock.push(U256::from(pending)).unwrap();
+            mock.push(U256::from(latest)).unwrap();
+        };
+        push(&mock, 5, 7);
+        assert_eq!(manager.next_nonce().await.unwrap(), U256::from(7));
+        assert_eq!(manager.next_nonce().await.unwrap(), U256::from(8));
+
+        // a failover moves the nonce up to the new node's
+        failovers.fetch_add(1, Ordering::Relaxed);
+        push(&mock, 10, 10);
+        assert_eq!(manager.next_nonce().await.unwrap(), U256::from(10));
+        assert_eq!(manager.next_nonce().await.unwrap(), U256::from(11));
+
+        // but not back down to a node that hasn't seen our txs yet
+        failovers.fetch_add(1, Ordering::Relaxed);
+        push(&mock, 7, 7);
+        assert_eq!(manager.next_nonce().await.unwrap(), U256::from(12));
+
+        // only the sender's own txs are numbered
+        let other: TypedTransaction = TransactionRequest::new()
+            .from(Address::repeat_byte(2))
+            .into();
+        assert!(!manager.numbers(&other));
+        assert!(manager.numbers(&TransactionRequest::new().into()));
+        assert!(!manager.numbers(&TransactionRequest::new().nonce(1).into()));
+    }
+
+    #[test]
+    fn test_is_nonce_error() {
+        assert!(is_nonce_error("Nonce too low: next nonce 8, tx nonce 7"));
+        assert!(!is_nonce_error("already known"));
+        assert!(!is_nonce_error(
+            "insufficient funds for gas * price + value"
+        ));
+    }
+
+    #[test]
+    fn test_is_already_known() {
+        assert!(is_already_known(
+            "(code: -32000, message: already known, data: None)"
+        ));
+        assert!(is_already_known("Transaction already imported"));
+        assert!(!is_already_known("nonce too low"));
+    }
+
+    #[test]
+    fn test_in_flight() {
+        let nonces = ChainNonces {
+            latest: U256::from(5),
+            pending: U256::from(7),
+        };
+        assert_eq!(nonces.in_flight(), U256::from(2));
+        assert!(ChainNonces::default().in_flight().is_zero());
+    }
+}
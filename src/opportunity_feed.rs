@@ -0,0 +1,86 @@
+use crate::api::OpportunityStatus;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Opportunities buffered for a subscriber before it skips the oldest.
+pub const FEED_CAPACITY: usize = 256;
+/// Opportunities remembered for acks, acks to older ones are reported unknown.
+pub const MAX_RECENT_OPPORTUNITIES: usize = 1000;
+
+/// The opportunities the strategy considers, streamed to every gRPC subscriber.
+#[derive(Debug, Clone)]
+pub struct OpportunityFeed {
+    sender: broadcast::Sender<OpportunityStatus>,
+    recent: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Default for OpportunityFeed {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(FEED_CAPACITY).0,
+            recent: Default::default(),
+        }
+    }
+}
+
+impl OpportunityFeed {
+    /// Stream an opportunity, dropped if nobody subscribed.
+    pub fn publish(&self, opportunity: OpportunityStatus) {
+        if let Ok(mut recent) = self.recent.lock() {
+            if recent.len() == MAX_RECENT_OPPORTUNITIES {
+                recent.pop_front();
+            }
+            recent.push_back(opportunity.correlation_id.clone());
+        }
+        let _ = self.sender.send(opportunity);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<OpportunityStatus> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn is_recent(&self, correlation_id: &str) -> bool {
+        self.recent
+            .lock()
+            .is_ok_and(|recent| recent.iter().any(|id| id == correlation_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    fn opportunity(correlation_id: &str) -> OpportunityStatus {
+        OpportunityStatus {
+            correlation_id: correlation_id.to_string(),
+            timestamp: 1700000000,
+            borrower: Address::repeat_byte(1),
+            collateral: Address::repeat_byte(2),
+            debt: Address::repeat_byte(3),
+            debt_to_cover: U256::exp10(18),
+            profit_eth: "1".to_string(),
+            profit: None,
+            confidence: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_feed() {
+        let feed = OpportunityFeed::default();
+        // nobody listening yet
+        feed.publish(opportunity("a"));
+        let mut receiver = feed.subscribe();
+        feed.publish(opportunity("b"));
+        assert_eq!(receiver.recv().await.unwrap().correlation_id, "b");
+        assert!(feed.is_recent("a") && feed.is_recent("b"));
+        assert!(!feed.is_recent("c"));
+
+        for i in 0..MAX_RECENT_OPPORTUNITIES {
+            feed.publish(opportunity(&i.to_string()));
+        }
+        assert!(!feed.is_recent("a"));
+        assert!(feed.is_recent("0"));
+    }
+}
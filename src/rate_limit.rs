@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use ethers::providers::JsonRpcClient;
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Compute units of a method not in [COMPUTE_UNITS].
+pub const DEFAULT_COMPUTE_UNITS: u64 = 20;
+
+/// Compute units each method costs, as Alchemy prices them. Infura's credits scale
+/// roughly alike.
+const COMPUTE_UNITS: [(&str, u64); 16] = [
+    ("eth_chainId", 0),
+    ("eth_blockNumber", 10),
+    ("eth_feeHistory", 10),
+    ("eth_maxPriorityFeePerGas", 10),
+    ("eth_getTransactionReceipt", 15),
+    ("eth_getBlockByNumber", 16),
+    ("eth_getStorageAt", 17),
+    ("eth_gasPrice", 19),
+    ("eth_getBalance", 19),
+    ("eth_getCode", 19),
+    ("eth_call", 26),
+    ("eth_getTransactionCount", 26),
+    ("eth_getLogs", 75),
+    ("eth_estimateGas", 87),
+    ("eth_sendTransaction", 250),
+    ("eth_sendRawTransaction", 250),
+];
+
+/// Compute units `method` costs.
+pub fn compute_units(method: &str) -> u64 {
+    COMPUTE_UNITS
+        .iter()
+        .find(|(name, _)| *name == method)
+        .map_or(DEFAULT_COMPUTE_UNITS, |(_, units)| *units)
+}
+
+/// Budget of a single endpoint, each limit off if unset.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub requests_per_sec: Option<u64>,
+    pub compute_units_per_sec: Option<u64>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Tokens refilling at `rate` a second, up to a second's worth.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        let rate = rate.max(1) as f64;
+        Self {
+            rate,
+            state: Mutex::new(BucketState {
+                tokens: rate,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take `cost` tokens at `now`, or return how long until there are enough. A cost
+    /// above the capacity waits for a full bucket.
+    pub fn try_take(&self, cost: u64, now: Instant) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.refilled_at);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        state.refilled_at = now;
+
+        let cost = (cost as f64).min(self.rate);
+        if state.tokens >= cost {
+            state.tokens -= cost;
+            return Ok(());
+        }
+        Err(Duration::from_secs_f64((cost - state.tokens) / self.rate))
+    }
+
+    /// Wait until `cost` tokens can be taken, and take them.
+    pub async fn take(&self, cost: u64) {
+        while let Err(wait) = self.try_take(cost, Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A transport holding requests to an endpoint within its plan's requests and compute
+/// units per second, instead of sending them to be refused with 429s.
+#[derive(Debug, Clone)]
+pub struct RateLimitedTransport<T> {
+    inner: T,
+    requests: Option<Arc<TokenBucket>>,
+    compute_units: Option<Arc<TokenBucket>>,
+}
+
+impl<T> RateLimitedTransport<T> {
+    pub fn new(inner: T, limits: RateLimits) -> Self {
+        Self {
+            inner,
+            requests: limits
+                .requests_per_sec
+                .map(|rate| Arc::new(TokenBucket::new(rate))),
+            compute_units: limits
+                .compute_units_per_sec
+                .map(|rate| Arc::new(TokenBucket::new(rate))),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> JsonRpcClient for RateLimitedTransport<T>
+where
+    T: JsonRpcClient + 'static,
+{
+    type Error = T::Error;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        if let Some(requests) = &self.requests {
+            requests.take(1).await;
+        }
+        if let Some(units) = &self.compute_units {
+            units.take(compute_units(method)).await;
+        }
+        self.inner.request(method, params).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::new(100);
+        let start = Instant::now();
+
+        // a second's worth of burst, then a wait for what's missing
+        assert!(bucket.try_take(75, start).is_ok());
+        assert_eq!(bucket.try_take(75, start), Err(Duration::from_millis(500)));
+        // refilled at 100 a second
+        assert!(bucket
+            .try_take(75, start + Duration::from_millis(500))
+            .is_ok());
+        // capped at the capacity
+        assert!(bucket.try_take(250, start + Duration::from_secs(5)).is_ok());
+
+        assert_eq!(compute_units("eth_getLogs"), 75);
+        assert_eq!(compute_units("debug_traceCall"), DEFAULT_COMPUTE_UNITS);
+    }
+}
@@ -0,0 +1,338 @@
+use async_trait::async_trait;
+use ethers::providers::{JsonRpcClient, JsonRpcError, ProviderError, RpcError};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use tracing::info;
+
+/// One RPC request and its response, a line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    /// Null for requests sent without params.
+    pub params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    /// The node's error response, e.g. for a reverted `eth_call`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl Exchange {
+    fn key(&self) -> String {
+        key(&self.method, &self.params)
+    }
+
+    fn response<R: DeserializeOwned>(self) -> Result<R, ReplayError> {
+        match self.error {
+            Some(error) => Err(ReplayError::JsonRpc(error)),
+            None => serde_json::from_value(self.result.unwrap_or(Value::Null))
+                .map_err(ReplayError::SerdeJson),
+        }
+    }
+}
+
+// object keys serialize sorted, so equal params give equal keys
+fn key(method: &str, params: &Value) -> String {
+    format!("{} {}", method, params)
+}
+
+/// Responses of a recording by request, served in the order they were recorded.
+#[derive(Debug, Default)]
+pub struct Recording {
+    exchanges: HashMap<String, Vec<Exchange>>,
+    served: HashMap<String, usize>,
+}
+
+impl Recording {
+    /// Read a recording, one JSON exchange per line.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let mut recording = Self::default();
+        for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let exchange = serde_json::from_str(&line).map_err(|e| {
+                anyhow::anyhow!("Invalid exchange on line {} of {:?}: {}", i + 1, path, e)
+            })?;
+            recording.push(exchange);
+        }
+        Ok(recording)
+    }
+
+    pub fn push(&mut self, exchange: Exchange) {
+        self.exchanges
+            .entry(exchange.key())
+            .or_default()
+            .push(exchange);
+    }
+
+    pub fn len(&self) -> usize {
+        self.exchanges.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exchanges.is_empty()
+    }
+
+    /// The next response to `method` with `params`. Requests sent more often than when
+    /// recorded, like a block number polled past the end of the recording, get the last
+    /// response again.
+    pub fn next(&mut self, method: &str, params: &Value) -> Option<Exchange> {
+        let key = key(method, params);
+        let exchanges = self.exchanges.get(&key)?;
+        let served = self.served.entry(key).or_default();
+        let exchange = exchanges[(*served).min(exchanges.len() - 1)].clone();
+        *served += 1;
+        Some(exchange)
+    }
+}
+
+#[derive(Debug)]
+enum Mode {
+    Live,
+    Record(Mutex<File>),
+    Replay(Mutex<Recording>),
+}
+
+/// A transport recording every response of the inner one to a file, or answering from a
+/// recording without sending anything, so a run can be reproduced offline against the
+/// chain as it was.
+#[derive(Debug)]
+pub struct ReplayTransport<T> {
+    inner: T,
+    mode: Mode,
+}
+
+impl<T> ReplayTransport<T> {
+    /// Pass requests through to `inner`.
+    pub fn live(inner: T) -> Self {
+        Self {
+            inner,
+            mode: Mode::Live,
+        }
+    }
+
+    /// Pass requests through to `inner`, appending every response to `path`.
+    pub fn record(inner: T, path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        info!("Recording RPC responses to {:?}", path);
+        Ok(Self {
+            inner,
+            mode: Mode::Record(Mutex::new(file)),
+        })
+    }
+
+    /// Answer from the recording at `path`, `inner` is never sent anything.
+    pub fn replay(inner: T, path: &Path) -> anyhow::Result<Self> {
+        let recording = Recording::load(path)?;
+        info!(
+            "Replaying {} RPC responses from {:?}",
+            recording.len(),
+            path
+        );
+        Ok(Self {
+            inner,
+            mode: Mode::Replay(Mutex::new(recording)),
+        })
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replay(_))
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The inner transport failed.
+    Transport(ProviderError),
+    /// The node's error response, as recorded.
+    JsonRpc(JsonRpcError),
+    SerdeJson(serde_json::Error),
+    Io(std::io::Error),
+    /// The recording has no response to the request.
+    NotRecorded(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "{}", e),
+            Self::JsonRpc(e) => write!(f, "{}", e),
+            Self::SerdeJson(e) => write!(f, "Deserialization error: {}", e),
+            Self::Io(e) => write!(f, "Failed to record RPC response: {}", e),
+            Self::NotRecorded(request) => write!(f, "No recorded response to {}", request),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl RpcError for ReplayError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            Self::Transport(e) => e.as_error_response(),
+            Self::JsonRpc(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            Self::Transport(e) => e.as_serde_error(),
+            Self::SerdeJson(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ReplayError> for ProviderError {
+    fn from(error: ReplayError) -> Self {
+        match error {
+            ReplayError::Transport(e) => e,
+            e => ProviderError::JsonRpcClientError(Box::new(e)),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> JsonRpcClient for ReplayTransport<T>
+where
+    T: JsonRpcClient + 'static,
+    T::Error: Sync + Send + 'static,
+{
+    type Error = ReplayError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let file = match &self.mode {
+            Mode::Live => {
+                return self
+                    .inner
+                    .request(method, params)
+                    .await
+                    .map_err(|e| ReplayError::Transport(e.into()))
+            }
+            Mode::Record(file) => file,
+            Mode::Replay(recording) => {
+                let params = request_params(&params)?;
+                let exchange = recording
+                    .lock()
+                    .expect("recording lock poisoned")
+                    .next(method, &params);
+                return exchange
+                    .ok_or_else(|| ReplayError::NotRecorded(key(method, &params)))?
+                    .response();
+            }
+        };
+
+        let recorded_params = request_params(&params)?;
+        let (result, error) = match self.inner.request::<P, Value>(method, params).await {
+            Ok(result) => (Some(result), None),
+            Err(e) => {
+                // only the node's answers are recorded, a dropped connection isn't the chain
+                let e: ProviderError = e.into();
+                match e.as_error_response() {
+                    Some(response) => (None, Some(response.clone())),
+                    None => return Err(ReplayError::Transport(e)),
+                }
+            }
+        };
+        let exchange = Exchange {
+            method: method.to_string(),
+            params: recorded_params,
+            result,
+            error,
+        };
+        let line = serde_json::to_string(&exchange).map_err(ReplayError::SerdeJson)?;
+        writeln!(file.lock().expect("recording lock poisoned"), "{}", line)
+            .map_err(ReplayError::Io)?;
+        exchange.response()
+    }
+}
+
+// a zero sized `()` is sent without params
+fn request_params<P: Serialize>(params: &P) -> Result<Value, ReplayError> {
+    if std::mem::size_of::<P>() == 0 {
+        Ok(Value::Null)
+    } else {
+        serde_json::to_value(params).map_err(ReplayError::SerdeJson)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{MockProvider, MockResponse};
+    use ethers::types::U64;
+
+    #[test]
+    fn test_recording_order() {
+        let exchange = |method: &str, result: u64| Exchange {
+            method: method.to_string(),
+            params: Value::Null,
+            result: Some(result.into()),
+            error: None,
+        };
+        let mut recording = Recording::default();
+        recording.push(exchange("eth_blockNumber", 1));
+        recording.push(exchange("eth_chainId", 137));
+        recording.push(exchange("eth_blockNumber", 2));
+        assert_eq!(recording.len(), 3);
+
+        let mut next = |method| recording.next(method, &Value::Null).unwrap().result;
+        assert_eq!(next("eth_blockNumber"), Some(1.into()));
+        assert_eq!(next("eth_blockNumber"), Some(2.into()));
+        // polled past the end of the recording
+        assert_eq!(next("eth_blockNumber"), Some(2.into()));
+        assert_eq!(next("eth_chainId"), Some(137.into()));
+        assert!(recording.next("eth_gasPrice", &Value::Null).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("rpc-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mock = MockProvider::new();
+        let transport = ReplayTransport::record(mock.clone(), &path).unwrap();
+        let revert = JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some("0x".into()),
+        };
+        // the mock answers the latest response pushed first
+        mock.push_response(MockResponse::Error(revert.clone()));
+        mock.push(U64::from(7)).unwrap();
+        let block_number: U64 = transport.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(block_number, U64::from(7));
+        let call: Result<U64, ReplayError> = transport.request("eth_call", ["0x01"]).await;
+        assert_eq!(call.unwrap_err().as_error_response(), Some(&revert));
+        // dropped requests aren't recorded
+        let failed: Result<U64, ReplayError> = transport.request("eth_gasPrice", ()).await;
+        assert!(failed.is_err());
+
+        // the mock has nothing queued, responses come from the recording
+        let transport = ReplayTransport::replay(MockProvider::new(), &path).unwrap();
+        assert!(transport.is_replaying());
+        let block_number: U64 = transport.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(block_number, U64::from(7));
+        let call: Result<U64, ReplayError> = transport.request("eth_call", ["0x01"]).await;
+        assert_eq!(call.unwrap_err().as_error_response(), Some(&revert));
+        let call: Result<U64, ReplayError> = transport.request("eth_call", ["0x02"]).await;
+        assert!(matches!(call, Err(ReplayError::NotRecorded(_))));
+        let failed: Result<U64, ReplayError> = transport.request("eth_gasPrice", ()).await;
+        assert!(matches!(failed, Err(ReplayError::NotRecorded(_))));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use ethers::core::rand::{thread_rng, Rng};
+use ethers::providers::{JsonRpcClient, RetryClientError, RpcError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt::Debug;
+use std::time::Duration;
+use tracing::warn;
+
+// rate limits take longer to clear than a dropped connection
+const RATE_LIMIT_BACKOFF_FACTOR: u32 = 4;
+
+/// How an RPC error is handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The node is throttling us, retried after a longer backoff.
+    RateLimited,
+    /// Connection drops, timeouts, lagging nodes, retried.
+    Transient,
+    /// Reverts and invalid requests, retrying won't help.
+    Fatal,
+}
+
+/// Exponential backoff with jitter, up to `max_attempts` tries per request.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Longest wait before retry number `attempt` (from 1), doubling every attempt.
+    pub fn max_delay(&self, attempt: u32, kind: ErrorKind) -> Duration {
+        let base = match kind {
+            ErrorKind::RateLimited => self.initial_backoff * RATE_LIMIT_BACKOFF_FACTOR,
+            _ => self.initial_backoff,
+        };
+        base.saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff)
+    }
+
+    /// Wait before retry number `attempt`, between half and all of [Self::max_delay] so
+    /// requests throttled together don't all come back at once.
+    pub fn delay(&self, attempt: u32, kind: ErrorKind) -> Duration {
+        self.max_delay(attempt, kind)
+            .mul_f64(thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// Classify an error from the transport.
+pub fn classify(error: &impl RpcError) -> ErrorKind {
+    match error.as_error_response() {
+        Some(response) => {
+            let message = response.message.to_lowercase();
+            if response.code == 429 || is_rate_limit(&message) {
+                ErrorKind::RateLimited
+            } else if ["header not found", "timeout", "timed out", "unavailable"]
+                .iter()
+                .any(|pattern| message.contains(pattern))
+            {
+                ErrorKind::Transient
+            } else {
+                ErrorKind::Fatal
+            }
+        }
+        // no JSON-RPC response at all: the connection failed or a gateway answered
+        None if is_rate_limit(&error.to_string().to_lowercase()) => ErrorKind::RateLimited,
+        None => ErrorKind::Transient,
+    }
+}
+
+fn is_rate_limit(message: &str) -> bool {
+    [
+        "429",
+        "rate limit",
+        "too many requests",
+        "request rate exceeded",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+}
+
+/// A transport retrying rate limited and transient errors of the one it wraps.
+#[derive(Debug)]
+pub struct RetryTransport<T> {
+    inner: T,
+    policy: RetryPolicy,
+}
+
+impl<T> RetryTransport<T> {
+    pub fn new(inner: T, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+}
+
+#[async_trait]
+impl<T> JsonRpcClient for RetryTransport<T>
+where
+    T: JsonRpcClient + 'static,
+    T::Error: Sync + Send + 'static,
+{
+    type Error = RetryClientError;
+
+    async fn request<P, R>(&self, method: &str, params: P) -> Result<R, Self::Error>
+    where
+        P: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        // a zero sized `()` is sent without params, anything else is kept for retries
+        let params = if std::mem::size_of::<P>() == 0 {
+            None
+        } else {
+            Some(serde_json::to_value(params).map_err(RetryClientError::SerdeJson)?)
+        };
+
+        let mut attempt = 1;
+        loop {
+            let result = match &params {
+                Some(params) => self.inner.request(method, params).await,
+                None => self.inner.request(method, ()).await,
+            };
+            let error = match result {
+                Ok(response) => return Ok(response),
+                Err(error) => error,
+            };
+            let kind = classify(&error);
+            if kind == ErrorKind::Fatal || attempt >= self.policy.max_attempts {
+                return Err(RetryClientError::ProviderError(error.into()));
+            }
+
+            let delay = self.policy.delay(attempt, kind);
+            warn!(
+                "{} failed ({:?}, attempt {}), retrying in {:?}: {}",
+                method, kind, attempt, delay, error
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{HttpClientError, JsonRpcError};
+
+    fn response(code: i64, message: &str) -> HttpClientError {
+        HttpClientError::JsonRpcError(JsonRpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        })
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            classify(&response(429, "Too Many Requests")),
+            ErrorKind::RateLimited
+        );
+        assert_eq!(
+            classify(&response(
+                -32005,
+                "project ID request rate exceeded, rate limited"
+            )),
+            ErrorKind::RateLimited
+        );
+        // too many logs for the range, the same query fails again
+        assert_eq!(
+            classify(&response(-32005, "query returned more than 10000 results")),
+            ErrorKind::Fatal
+        );
+        assert_eq!(
+            classify(&response(-32000, "header not found")),
+            ErrorKind::Transient
+        );
+        assert_eq!(
+            classify(&response(3, "execution reverted")),
+            ErrorKind::Fatal
+        );
+    }
+
+    #[test]
+    fn test_max_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        };
+        assert_eq!(
+            policy.max_delay(1, ErrorKind::Transient),
+            Duration::from_millis(100)
+        );
+        assert_eq!(
+            policy.max_delay(3, ErrorKind::Transient),
+            Duration::from_millis(400)
+        );
+        assert_eq!(
+            policy.max_delay(2, ErrorKind::RateLimited),
+            Duration::from_millis(800)
+        );
+        assert_eq!(
+            policy.max_delay(10, ErrorKind::RateLimited),
+            Duration::from_secs(1)
+        );
+        let delay = policy.delay(3, ErrorKind::Transient);
+        assert!(delay >= Duration::from_millis(200) && delay <= Duration::from_millis(400));
+    }
+}
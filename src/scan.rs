@@ -0,0 +1,77 @@
+use anyhow::Result;
+use ethers::types::{Address, I256, U256};
+use ethers::utils::format_units;
+use serde::Serialize;
+
+// profits are in ETH with 8 decimals, health factors in wad
+const PROFIT_DECIMALS: u32 = 8;
+const HEALTH_FACTOR_DECIMALS: u32 = 18;
+
+/// An underwater borrower found by the `scan` subcommand, with its best liquidation.
+#[derive(Debug, Clone, Serialize)]
+pub struct LiquidatableAccount {
+    pub borrower: Address,
+    pub health_factor: U256,
+    pub collateral: Address,
+    pub collateral_symbol: String,
+    pub debt: Address,
+    pub debt_symbol: String,
+    /// In units of the debt asset.
+    pub debt_to_cover: U256,
+    pub debt_decimals: u64,
+    /// Estimated profit in ETH, with 8 decimals.
+    pub profit_eth: I256,
+}
+
+/// Print the accounts found by the `scan` subcommand, as a table or as JSON.
+pub fn print_accounts(accounts: &[LiquidatableAccount], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(accounts)?);
+        return Ok(());
+    }
+
+    println!(
+        "{:<42} {:>14} {:<16} {:>24} {:>16}",
+        "borrower", "health factor", "pair", "debt to cover", "profit (ETH)"
+    );
+    for account in accounts {
+        println!(
+            "{:<42} {:>14} {:<16} {:>24} {:>16}",
+            format!("{:?}", account.borrower),
+            truncate(
+                format_units(account.health_factor, HEALTH_FACTOR_DECIMALS)?,
+                6
+            ),
+            format!("{}/{}", account.collateral_symbol, account.debt_symbol),
+            truncate(
+                format_units(account.debt_to_cover, account.debt_decimals as u32)?,
+                6
+            ),
+            format_units(account.profit_eth, PROFIT_DECIMALS)?
+        );
+    }
+    println!("{} liquidatable accounts", accounts.len());
+    Ok(())
+}
+
+// keep at most `decimals` digits after the point
+fn truncate(value: String, decimals: usize) -> String {
+    match value.split_once('.') {
+        Some((whole, fraction)) if fraction.len() > decimals => {
+            format!("{}.{}", whole, &fraction[..decimals])
+        }
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("0.987654321".to_string(), 6), "0.987654");
+        assert_eq!(truncate("12.5".to_string(), 6), "12.5");
+        assert_eq!(truncate("3".to_string(), 6), "3");
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::info;
+
+/// Longest wait for in-flight work once shutdown is requested.
+pub const SHUTDOWN_TIMEOUT_SECS: u64 = 120;
+
+#[derive(Debug, Default)]
+struct State {
+    requested: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+/// Coordinates a graceful shutdown: work in progress holds an [InFlight] guard,
+/// no new work starts once shutdown is requested, and the process exits once
+/// every guard is dropped.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    state: Arc<State>,
+}
+
+/// Held while an event is processed or a tx is in flight.
+#[derive(Debug)]
+pub struct InFlight {
+    state: Arc<State>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.state.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.state.requested.load(Ordering::SeqCst)
+    }
+
+    /// Start some work, unless shutdown was requested.
+    pub fn start(&self) -> Option<InFlight> {
+        self.state.in_flight.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlight {
+            state: self.state.clone(),
+        };
+        // checked after counting, so a request never misses work that got through
+        (!self.is_requested()).then_some(guard)
+    }
+
+    /// Stop new work and wait for work in flight, giving up after `timeout`.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.state.requested.store(true, Ordering::SeqCst);
+        let wait = async {
+            while self.state.in_flight.load(Ordering::SeqCst) > 0 {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.is_ok()
+    }
+}
+
+/// Wait for SIGINT or SIGTERM.
+pub async fn wait_for_signal() -> Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+    info!("received shutdown signal");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_drain() {
+        let shutdown = Shutdown::new();
+        let guard = shutdown.start().unwrap();
+
+        // not drained while the guard is held
+        assert!(!shutdown.drain(Duration::from_millis(200)).await);
+        assert!(shutdown.is_requested());
+        assert!(shutdown.start().is_none());
+
+        drop(guard);
+        assert!(shutdown.drain(Duration::from_millis(200)).await);
+    }
+}
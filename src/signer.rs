@@ -0,0 +1,404 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use clap::ValueEnum;
+use ethers::{
+    core::k256::ecdsa::{RecoveryId, Signature as KSig, VerifyingKey},
+    signers::{LocalWallet, Signer},
+    types::{
+        transaction::{eip2718::TypedTransaction, eip712::Eip712},
+        Address, Signature, H256, U256,
+    },
+    utils::{hash_message, keccak256},
+};
+use serde_json::{json, Value};
+use std::fmt;
+use std::path::PathBuf;
+
+const GCP_KMS_API_URL: &str = "https://cloudkms.googleapis.com/v1";
+const GCP_METADATA_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Where the transaction signing key lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SignerKind {
+    /// Raw hex private key.
+    PrivateKey,
+    /// Encrypted JSON keystore file.
+    Keystore,
+    /// AWS KMS key, requires the `aws` feature.
+    AwsKms,
+    /// GCP Cloud KMS key version.
+    GcpKms,
+    /// Ledger hardware wallet, requires the `ledger` feature.
+    Ledger,
+}
+
+/// Everything needed to set up any kind of signer.
+#[derive(Debug, Clone)]
+pub struct SignerConfig {
+    pub kind: SignerKind,
+    pub private_key: Option<String>,
+    pub keystore: Option<PathBuf>,
+    pub keystore_password: Option<String>,
+    /// AWS KMS key id, or GCP key version resource name.
+    pub kms_key_id: Option<String>,
+    /// Ledger Live account index.
+    pub ledger_index: usize,
+}
+
+#[derive(Debug)]
+pub struct SignerError(String);
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for SignerError {}
+
+fn signer_error(e: impl fmt::Display) -> SignerError {
+    SignerError(e.to_string())
+}
+
+/// A signer backed by any of the supported key stores.
+#[derive(Debug)]
+pub enum AnySigner {
+    Local(LocalWallet),
+    GcpKms(GcpKmsSigner),
+    #[cfg(feature = "aws")]
+    AwsKms(ethers::signers::AwsSigner),
+    #[cfg(feature = "ledger")]
+    Ledger(ethers::signers::Ledger),
+}
+
+/// Set up the signer described by the config.
+pub async fn build_signer(config: &SignerConfig, chain_id: u64) -> Result<AnySigner> {
+    match config.kind {
+        SignerKind::PrivateKey => {
+            let private_key = config.private_key.as_ref().ok_or(anyhow!(
+                "--private-key is required for the private-key signer"
+            ))?;
+            Ok(AnySigner::Local(
+                private_key.parse::<LocalWallet>()?.with_chain_id(chain_id),
+            ))
+        }
+        SignerKind::Keystore => {
+            let keystore = config
+                .keystore
+                .as_ref()
+                .ok_or(anyhow!("--keystore is required for the keystore signer"))?;
+            let password = config.keystore_password.as_deref().unwrap_or_default();
+            Ok(AnySigner::Local(
+                LocalWallet::decrypt_keystore(keystore, password)?.with_chain_id(chain_id),
+            ))
+        }
+        SignerKind::GcpKms => {
+            let key_name = config
+                .kms_key_id
+                .clone()
+                .ok_or(anyhow!("--kms-key-id is required for the gcp-kms signer"))?;
+            Ok(AnySigner::GcpKms(
+                GcpKmsSigner::new(key_name, chain_id).await?,
+            ))
+        }
+        #[cfg(feature = "aws")]
+        SignerKind::AwsKms => {
+            let key_id = config
+                .kms_key_id
+                .as_ref()
+                .ok_or(anyhow!("--kms-key-id is required for the aws-kms signer"))?;
+            let kms = rusoto_kms::KmsClient::new(rusoto_core::Region::default());
+            Ok(AnySigner::AwsKms(
+                ethers::signers::AwsSigner::new(kms, key_id, chain_id).await?,
+            ))
+        }
+        #[cfg(not(feature = "aws"))]
+        SignerKind::AwsKms => Err(anyhow!("built without the `aws` feature")),
+        #[cfg(feature = "ledger")]
+        SignerKind::Ledger => Ok(AnySigner::Ledger(
+            ethers::signers::Ledger::new(
+                ethers::signers::HDPath::LedgerLive(config.ledger_index),
+                chain_id,
+            )
+            .await?,
+        )),
+        #[cfg(not(feature = "ledger"))]
+        SignerKind::Ledger => Err(anyhow!("built without the `ledger` feature")),
+    }
+}
+
+// forward a call to whichever signer is in use
+macro_rules! delegate {
+    ($self:ident, $signer:ident => $call:expr) => {
+        match $self {
+            AnySigner::Local($signer) => $call.map_err(signer_error),
+            AnySigner::GcpKms($signer) => $call.map_err(signer_error),
+            #[cfg(feature = "aws")]
+            AnySigner::AwsKms($signer) => $call.map_err(signer_error),
+            #[cfg(feature = "ledger")]
+            AnySigner::Ledger($signer) => $call.map_err(signer_error),
+        }
+    };
+}
+
+#[async_trait]
+impl Signer for AnySigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        delegate!(self, signer => signer.sign_message(message).await)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        delegate!(self, signer => signer.sign_transaction(tx).await)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        delegate!(self, signer => signer.sign_typed_data(payload).await)
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            AnySigner::Local(signer) => signer.address(),
+            AnySigner::GcpKms(signer) => signer.address(),
+            #[cfg(feature = "aws")]
+            AnySigner::AwsKms(signer) => signer.address(),
+            #[cfg(feature = "ledger")]
+            AnySigner::Ledger(signer) => signer.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            AnySigner::Local(signer) => signer.chain_id(),
+            AnySigner::GcpKms(signer) => signer.chain_id(),
+            #[cfg(feature = "aws")]
+            AnySigner::AwsKms(signer) => signer.chain_id(),
+            #[cfg(feature = "ledger")]
+            AnySigner::Ledger(signer) => signer.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            AnySigner::Local(signer) => AnySigner::Local(signer.with_chain_id(chain_id)),
+            AnySigner::GcpKms(signer) => AnySigner::GcpKms(signer.with_chain_id(chain_id)),
+            #[cfg(feature = "aws")]
+            AnySigner::AwsKms(signer) => AnySigner::AwsKms(signer.with_chain_id(chain_id)),
+            #[cfg(feature = "ledger")]
+            AnySigner::Ledger(signer) => AnySigner::Ledger(signer.with_chain_id(chain_id)),
+        }
+    }
+}
+
+/// A signer using a secp256k1 key version held in GCP Cloud KMS, e.g.
+/// `projects/p/locations/l/keyRings/r/cryptoKeys/k/cryptoKeyVersions/1`.
+///
+/// Requests are authenticated with `GCP_ACCESS_TOKEN` if set, otherwise with the
+/// instance service account from the metadata server.
+#[derive(Debug, Clone)]
+pub struct GcpKmsSigner {
+    http: reqwest::Client,
+    key_name: String,
+    verifying_key: VerifyingKey,
+    address: Address,
+    chain_id: u64,
+}
+
+impl GcpKmsSigner {
+    /// Fetch the public key of the key version to derive its address.
+    pub async fn new(key_name: String, chain_id: u64) -> Result<Self> {
+        let http = reqwest::Client::new();
+        let token = access_token(&http).await?;
+        let response: Value = http
+            .get(format!("{}/{}/publicKey", GCP_KMS_API_URL, key_name))
+            .bearer_auth(token)
+            .send()
+            .await?
+            .json()
+            .await?;
+        let pem = response["pem"].as_str().ok_or(anyhow!(
+            "No public key for {}: {}",
+            key_name,
+            response
+        ))?;
+        let verifying_key = decode_pem_public_key(pem)?;
+
+        Ok(Self {
+            http,
+            key_name,
+            address: verifying_key_to_address(&verifying_key),
+            verifying_key,
+            chain_id,
+        })
+    }
+
+    async fn sign_digest(&self, digest: H256) -> Result<Signature> {
+        let token = access_token(&self.http).await?;
+        // secp256k1 keys sign the given digest as is, so a keccak hash can be passed as sha256
+        let response: Value = self
+            .http
+            .post(format!(
+                "{}/{}:asymmetricSign",
+                GCP_KMS_API_URL, self.key_name
+            ))
+            .bearer_auth(token)
+            .json(&json!({ "digest": { "sha256": BASE64.encode(digest) } }))
+            .send()
+            .await?
+            .json()
+            .await?;
+        let signature = response["signature"]
+            .as_str()
+            .ok_or(anyhow!("KMS did not sign: {}", response))?;
+        let signature = KSig::from_der(&BASE64.decode(signature)?)?;
+        recover_signature(signature, digest, &self.verifying_key)
+    }
+}
+
+#[async_trait]
+impl Signer for GcpKmsSigner {
+    type Error = SignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        let mut signature = self
+            .sign_digest(hash_message(message))
+            .await
+            .map_err(signer_error)?;
+        signature.v += 27;
+        Ok(signature)
+    }
+
+    async fn sign_transaction(&self, tx: &TypedTransaction) -> Result<Signature, Self::Error> {
+        let mut tx = tx.clone();
+        let chain_id = tx.chain_id().map(|id| id.as_u64()).unwrap_or(self.chain_id);
+        tx.set_chain_id(chain_id);
+
+        let mut signature = self.sign_digest(tx.sighash()).await.map_err(signer_error)?;
+        // eip155
+        signature.v += chain_id * 2 + 35;
+        Ok(signature)
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        let digest = payload.encode_eip712().map_err(signer_error)?;
+        let mut signature = self
+            .sign_digest(H256::from(digest))
+            .await
+            .map_err(signer_error)?;
+        signature.v += 27;
+        Ok(signature)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    fn with_chain_id<T: Into<u64>>(mut self, chain_id: T) -> Self {
+        self.chain_id = chain_id.into();
+        self
+    }
+}
+
+async fn access_token(http: &reqwest::Client) -> Result<String> {
+    if let Ok(token) = std::env::var("GCP_ACCESS_TOKEN") {
+        return Ok(token);
+    }
+    let response: Value = http
+        .get(GCP_METADATA_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .await?
+        .json()
+        .await?;
+    response["access_token"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or(anyhow!("No GCP access token: {}", response))
+}
+
+// the uncompressed point is the trailing bit string of the SubjectPublicKeyInfo
+fn decode_pem_public_key(pem: &str) -> Result<VerifyingKey> {
+    let der: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+    let der = BASE64.decode(der)?;
+    let point = der
+        .len()
+        .checked_sub(65)
+        .map(|start| &der[start..])
+        .ok_or(anyhow!("Public key too short"))?;
+    Ok(VerifyingKey::from_sec1_bytes(point)?)
+}
+
+fn verifying_key_to_address(key: &VerifyingKey) -> Address {
+    let point = key.to_encoded_point(false);
+    Address::from_slice(&keccak256(&point.as_bytes()[1..])[12..])
+}
+
+// KMS returns a plain ECDSA signature, find the recovery id by trial recovery
+fn recover_signature(
+    signature: KSig,
+    digest: H256,
+    verifying_key: &VerifyingKey,
+) -> Result<Signature> {
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let recovery_id = [0, 1]
+        .into_iter()
+        .filter_map(RecoveryId::from_byte)
+        .find(|recovery_id| {
+            VerifyingKey::recover_from_prehash(digest.as_bytes(), &signature, *recovery_id)
+                .is_ok_and(|key| key == *verifying_key)
+        })
+        .ok_or(anyhow!("Signature does not match the KMS public key"))?;
+
+    Ok(Signature {
+        r: U256::from_big_endian(&signature.r().to_bytes()),
+        s: U256::from_big_endian(&signature.s().to_bytes()),
+        v: recovery_id.to_byte() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::core::k256::ecdsa::SigningKey;
+
+    #[test]
+    fn test_recover_signature() {
+        let signing_key = SigningKey::from_slice(&[1u8; 32]).unwrap();
+        let verifying_key = *signing_key.verifying_key();
+        let digest = H256::from(keccak256("liquidate"));
+
+        // a DER signature as returned by KMS, without recovery id
+        let (signature, _) = signing_key
+            .sign_prehash_recoverable(digest.as_bytes())
+            .unwrap();
+        let mut signature = recover_signature(signature, digest, &verifying_key).unwrap();
+        signature.v += 27;
+
+        assert_eq!(
+            signature.recover(digest).unwrap(),
+            verifying_key_to_address(&verifying_key)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use clap::{Args, Parser, Subcommand};
+use ethers::types::Address;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::strategies::aave_strategy::{state_cache_path, Deployment, StateCache};
+
+/// Export, import or inspect the borrower state cache of a deployment.
+#[derive(Parser, Debug)]
+#[command(name = "state")]
+pub struct StateArgs {
+    #[command(subcommand)]
+    pub command: StateCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StateCommand {
+    /// Write the cache as plain JSON, to move it to another machine.
+    Export {
+        #[command(flatten)]
+        cache: CacheArgs,
+
+        /// File to write to, stdout if unset.
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the cache with an exported one of the same network and deployment.
+    Import {
+        #[command(flatten)]
+        cache: CacheArgs,
+
+        /// Exported cache to read.
+        #[arg(long)]
+        input: PathBuf,
+
+        /// Overwrite a cache indexed further than the imported one.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print what the cache holds about a borrower.
+    Show {
+        #[command(flatten)]
+        cache: CacheArgs,
+
+        #[arg(long)]
+        borrower: String,
+    },
+}
+
+/// Which cache to work on, as the bot would pick it.
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    /// Directory the borrower state cache of each deployment is kept in.
+    #[arg(long, env = "STATE_DIR", default_value = ".")]
+    pub state_dir: PathBuf,
+
+    #[arg(long, env = "CHAIN_ID")]
+    pub chain_id: u64,
+
+    #[arg(long, env = "DEPLOYMENT")]
+    pub deployment: Deployment,
+}
+
+impl CacheArgs {
+    fn path(&self) -> PathBuf {
+        state_cache_path(&self.state_dir, self.chain_id, &self.deployment)
+    }
+
+    fn read(&self) -> Result<StateCache> {
+        StateCache::read(&self.path())?
+            .ok_or(anyhow!("No state cache at {}", self.path().display()))
+    }
+}
+
+/// Run a `state` subcommand.
+pub fn run(args: &StateArgs) -> Result<()> {
+    match &args.command {
+        StateCommand::Export { cache, output } => {
+            let state = cache.read()?;
+            match output {
+                Some(output) => export(&state, BufWriter::new(File::create(output)?))?,
+                None => export(&state, std::io::stdout().lock())?,
+            }
+            eprintln!(
+                "exported {} borrowers indexed up to block {}",
+                state.borrower_count(),
+                state.last_block_number()
+            );
+        }
+        StateCommand::Import {
+            cache,
+            input,
+            force,
+        } => {
+            let state = import(
+                &cache.path(),
+                input,
+                cache.chain_id,
+                &cache.deployment,
+                *force,
+            )?;
+            println!(
+                "imported {} borrowers indexed up to block {} into {}",
+                state.borrower_count(),
+                state.last_block_number(),
+                cache.path().display()
+            );
+        }
+        StateCommand::Show { cache, borrower } => {
+            let state = cache.read()?;
+            let borrower = Address::from_str(borrower)?;
+            let borrower = state
+                .borrower(&borrower)
+                .ok_or(anyhow!("Borrower {:?} is not in the cache", borrower))?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&serde_json::json!({
+                    "last_block_number": state.last_block_number(),
+                    "borrower": borrower,
+                }))?
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Write `state` as plain JSON.
+pub fn export(state: &StateCache, mut writer: impl Write) -> Result<()> {
+    serde_json::to_writer_pretty(&mut writer, state)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Replace the cache at `path` with the exported one at `input`, refusing a cache of another
+/// network or version, and one older than the current cache unless `force` is set.
+pub fn import(
+    path: &Path,
+    input: &Path,
+    chain_id: u64,
+    deployment: &Deployment,
+    force: bool,
+) -> Result<StateCache> {
+    let state: StateCache = serde_json::from_reader(BufReader::new(File::open(input)?))
+        .map_err(|e| anyhow!("Failed to parse exported state: {}", e))?;
+    if !state.is_compatible(chain_id, deployment) {
+        return Err(anyhow!(
+            "Exported state is not a current cache of chain {} {:?}",
+            chain_id,
+            deployment
+        ));
+    }
+    if let Some(current) = StateCache::read(path)? {
+        if !force && current.last_block_number() > state.last_block_number() {
+            return Err(anyhow!(
+                "Current cache is indexed up to block {}, past the imported {}, use --force to overwrite it",
+                current.last_block_number(),
+                state.last_block_number()
+            ));
+        }
+    }
+    state.write(path)?;
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_import() {
+        let dir = std::env::temp_dir().join(format!("state-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = state_cache_path(&dir, 8453, &Deployment::AAVE);
+        let input = dir.join("export.json");
+        let exported = |last_block_number| {
+            let state = StateCache::new(
+                8453,
+                Deployment::AAVE,
+                Address::zero(),
+                last_block_number,
+                HashMap::new(),
+            );
+            export(&state, File::create(&input).unwrap()).unwrap();
+        };
+
+        exported(100);
+        import(&path, &input, 8453, &Deployment::AAVE, false).unwrap();
+        assert_eq!(
+            StateCache::read(&path)
+                .unwrap()
+                .unwrap()
+                .last_block_number(),
+            100
+        );
+        // another network's state is never imported
+        assert!(import(&path, &input, 10, &Deployment::AAVE, true).is_err());
+
+        // an older state only replaces the cache when forced
+        exported(50);
+        assert!(import(&path, &input, 8453, &Deployment::AAVE, false).is_err());
+        import(&path, &input, 8453, &Deployment::AAVE, true).unwrap();
+        assert_eq!(
+            StateCache::read(&path)
+                .unwrap()
+                .unwrap()
+                .last_block_number(),
+            50
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
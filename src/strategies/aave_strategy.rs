@@ -1,46 +1,142 @@
+use super::batch::LiquidationLeg;
+use super::bid_model::{bid_model_path, BidBounds, BidModel};
+use super::competitors::{CompetitorTracker, ObservedLiquidation, MATCH_WINDOW_SECS};
+use super::confidence::{
+    confidence_score, ConfidenceInputs, SimulationResult, SubmissionDecision, SubmissionPolicy,
+    SubmissionRoute, CONFIDENCE_ONE, MAX_HEALTH_CHECK_AGE_BLOCKS, MAX_PRICE_AGE_SECS,
+};
+use super::configurator_events::{
+    changed_e_mode_category, changed_reserve, e_mode_category_topics, reserve_config_topics,
+};
+use super::debt_swap::{
+    get_paraswap_buy, repay_amount_base, repay_beats_liquidation, ExecutionPath,
+    ParaSwapRepayAdapter, PartnerAccount, PermitSignature, VARIABLE_RATE_MODE,
+};
+use super::flash_liquidity::{
+    curve_amount_in, decode_curve_coin, decode_curve_get_dy, decode_pool_tokens,
+    decode_query_amount_in, encode_curve_coins, encode_curve_get_dy, encode_get_pool_tokens,
+    encode_query_given_out, FlashLiquidityPool, FlashLiquidityVenue, FlashSwap,
+    BALANCER_VAULT_ADDRESS, MAX_CURVE_COINS,
+};
+use super::full_math::product_div;
+use super::harvest::{sweep_amount, value_wei, withdraw_amount, HarvestConfig};
+use super::health_histogram::HealthFactorHistogram;
+use super::log_range::LogRange;
+use super::multicall::{MulticallSite, MULTICALL3_ADDRESS};
+use super::price_check::{OraclePrice, PriceChecker};
+use super::profit_currency::{ProfitCurrency, ProfitRate};
+use super::reserve_cache::{reserve_cache_path, ReserveCache};
+use super::revert_errors::{describe_error, revert_reason};
+use super::risk_engine::{AssetPosition, PositionSnapshot, RiskEngine};
+use super::scoring::{score, ScoreSignals, ScoringWeights};
+use super::shared_state::{Role, SharedState};
+use super::simulation_trace::SimulationTracer;
+use super::subgraph::get_subgraph_borrowers;
+use super::swap_router::{SwapRequest, SwapRouter};
+use super::sync_progress::{SyncProgress, SYNC_CHUNK_BLOCKS};
 use super::types::Config;
-use crate::collectors::time_collector::NewTick;
+use super::user_configuration::{decode_user_configuration, get_user_configurations};
+use super::wad_ray_math::{checked_ray_mul, compounded_interest, linear_interest};
+use super::wallet::WalletHealth;
+use crate::api::{BadDebtStatus, OpportunityStatus, SharedStatus};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::collectors::liquidation_call_collector::LiquidationCall;
+use crate::collectors::opportunity_queue_collector::QueuedOpportunity;
+use crate::collectors::price_update_collector::PendingPriceUpdate;
+use crate::collectors::reserve_data_collector::ReserveDataUpdate;
+use crate::collectors::time_collector::{NewTick, PollInterval};
+use crate::control::ControlCommand;
+use crate::executors::l1_fee::{L1Cost, L1FeeModel};
+use crate::executors::mev_share_executor::SubmitBackrunBundle;
+use crate::executors::protect_executor::{SubmitTx, ValuedAt, PROFIT_UNIT_WEI};
+use crate::exposure::ExposureLimiter;
+use crate::history::{LiquidationInfo, Valuation};
+use crate::kill_switch::KillSwitch;
+use crate::opportunity_feed::OpportunityFeed;
+use crate::scan::LiquidatableAccount;
 use anyhow::{anyhow, Result};
 use artemis_core::executors::mempool_executor::{GasBidInfo, SubmitTxToMempool};
 use artemis_core::types::Strategy;
 use async_trait::async_trait;
 use bindings_aave::{
+    aggregator_interface::AggregatorInterface,
     i_aave_oracle::IAaveOracle,
+    i_pool_addresses_provider::IPoolAddressesProvider,
     i_pool_data_provider::IPoolDataProvider,
+    i_price_oracle_sentinel::IPriceOracleSentinel,
+    i_sequencer_oracle::ISequencerOracle,
     ierc20::IERC20,
     l2_encoder::L2Encoder,
+    l2_pool::L2Pool,
     pool::{BorrowFilter, Pool, SupplyFilter},
 };
+use bindings_liquidator::i_quoter_v2::{IQuoterV2, QuoteExactOutputSingleParams};
 use bindings_liquidator::liquidator::Liquidator;
 use clap::{Parser, ValueEnum};
 use ethers::{
+    abi::Token,
     contract::builders::ContractCall,
-    providers::Middleware,
-    types::{transaction::eip2718::TypedTransaction, Address, ValueOrArray, H160, I256, U256, U64},
+    providers::{Http, Middleware, Provider},
+    types::{
+        transaction::eip2718::TypedTransaction, Address, Bytes, Filter, TransactionRequest,
+        ValueOrArray, H256, I256, U256, U64,
+    },
 };
-use ethers_contract::Multicall;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufReader, BufWriter, ErrorKind};
 use std::iter::zip;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
-use tracing::{error, info};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{error, info, instrument, warn};
 
-use super::types::{Action, Event};
+use super::approval::{Approval, ApprovalGate, PendingApproval};
+use super::data_source::{AaveDataSource, EthersDataSource, UserDebt};
+use super::filters::LiquidationFilter;
+use super::percentage_math::{percent_div, percent_mul};
+use super::types::{new_correlation_id, Action, Event, TickSummary};
+use super::uniswap::{pick_fee_tier, SwapPath, DEFAULT_UNISWAP_FEE, UNISWAP_FEE_TIERS};
+use crate::shutdown::Shutdown;
+use crate::tunables::{asset_override, AssetOverride, Tunables};
+use crate::webhook::{OpportunityReport, OpportunityWebhook, Verdict};
 
 #[derive(Debug)]
-struct DeploymentConfig {
-    pool_address: Address,
-    pool_data_provider: Address,
-    oracle_address: Address,
-    l2_encoder: Address,
-    creation_block: u64,
-    weth_address: Address,
+pub(crate) struct DeploymentConfig {
+    pub(crate) pool_address: Address,
+    pub(crate) pool_data_provider: Address,
+    pub(crate) oracle_address: Address,
+    pub(crate) l2_encoder: Address,
+    pub(crate) creation_block: u64,
+    pub(crate) weth_address: Address,
+    /// Uniswap V3 QuoterV2, zero where Uniswap V3 isn't deployed.
+    pub(crate) uniswap_quoter: Address,
+    /// Uniswap V3 factory the Liquidator contract derives its pools from, zero where Uniswap
+    /// V3 isn't deployed.
+    pub(crate) uniswap_factory: Address,
+    /// Share of gas fees refunded by the chain (in bps), e.g. Sonic's fee monetization.
+    pub(crate) fee_rebate_bps: u64,
+    pub(crate) liquidation_rules: LiquidationRules,
+    /// Multicall3, calls go one by one where it isn't deployed.
+    pub(crate) multicall_address: Address,
 }
 
-#[derive(Debug, Clone, Parser, ValueEnum)]
+/// Which liquidation rules a deployment's pool follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiquidationRules {
+    /// Up to v3.2: half the debt may be repaid above a 0.95 health factor, all of it below.
+    V3_0,
+    /// From v3.3: small positions may be fully liquidated whatever the health factor, and
+    /// liquidations may not leave dust of either reserve behind.
+    V3_3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Parser, ValueEnum, Serialize, Deserialize)]
 pub enum Deployment {
     AAVE,
     SEASHELL,
@@ -52,23 +148,89 @@ pub enum Deployment {
     AaveV3Arbitrum,
     AaveV3Avax,
     AaveV3Polygon,
+    /// The same market as `AAVE`.
+    AaveV3Base,
+    AaveV3Gnosis,
+    AaveV3Scroll,
+    AaveV3Metis,
+    AaveV3Linea,
+    AaveV3ZkSync,
+    AaveV3Soneium,
+    /// An Aave V3 fork, such as the markets on Mantle, at the pool set with `--pool-address`.
+    AaveV3Fork,
 }
 
 pub const WETH_ADDRESS: &str = "0x4200000000000000000000000000000000000006";
+pub const UNISWAP_QUOTER: &str = "0x61fFE014bA17989E743c5F6cB21bF9697530B21e";
+pub const BASE_UNISWAP_QUOTER: &str = "0x3d4e44Eb1374240CE5F1B871ab261CD16335B76a";
+pub const UNISWAP_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+pub const BASE_UNISWAP_FACTORY: &str = "0x33128a8fC17869897dcE68Ed026d694621f6FDfD";
+/// zkSync Era derives contract addresses differently, so Multicall3 isn't at the usual one.
+pub const ZKSYNC_MULTICALL3_ADDRESS: &str = "0xF9cda624FBC7e059355ce98a31693d299FACd963";
 
-pub const LIQUIDATION_CLOSE_FACTOR_THRESHOLD: &str = "950000000000000000";
+/// Above this health factor a liquidation may only repay the default close factor.
+pub const LIQUIDATION_CLOSE_FACTOR_THRESHOLD: u64 = 950000000000000000;
+/// Below this health factor the price oracle sentinel doesn't hold liquidations back.
+pub const MINIMUM_HEALTH_FACTOR_LIQUIDATION_THRESHOLD: u64 = 950000000000000000;
 pub const MAX_LIQUIDATION_CLOSE_FACTOR: u64 = 10000;
 pub const DEFAULT_LIQUIDATION_CLOSE_FACTOR: u64 = 5000;
+/// From v3.3, positions with a collateral or debt reserve worth less than this (in base
+/// currency) can be fully liquidated.
+pub const MIN_BASE_MAX_CLOSE_FACTOR_THRESHOLD: u64 = 2000 * PRICE_ONE;
+/// From v3.3, liquidations may not leave less than this (in base currency) of the debt or
+/// collateral reserve without taking all of it.
+pub const MIN_LEFTOVER_BASE: u64 = MIN_BASE_MAX_CLOSE_FACTOR_THRESHOLD / 2;
 
 // admin stuff
-pub const LOG_BLOCK_RANGE: u64 = 1024;
 pub const MULTICALL_CHUNK_SIZE: usize = 500;
-pub const STATE_CACHE_FILE: &str = "borrowers.json";
+// bump when the cache layout changes, older caches are then rebuilt from scratch
+pub const STATE_CACHE_VERSION: u32 = 2;
+
+pub const CODE_CHECK_CHUNK_SIZE: usize = 50;
 pub const PRICE_ONE: u64 = 100000000;
 
-fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
+// Sonic FeeM refunds 90% of the gas fees of txs calling a registered contract
+pub const SONIC_FEE_REBATE_BPS: u64 = 9000;
+
+// the rebate only applies to a liquidator registered with the program, so it's opted into
+fn fee_rebate_bps(configured_bps: Option<u64>, registered: bool, deployment_bps: u64) -> u64 {
+    match (configured_bps, registered) {
+        (Some(fee_rebate_bps), _) => fee_rebate_bps,
+        (None, true) => deployment_bps,
+        (None, false) => 0,
+    }
+}
+
+// blocks a partner repay is waited on before another one is sent for the account
+pub const PARTNER_REPAY_TIMEOUT_BLOCKS: u64 = 25;
+
+// route quality of the flash-swap path when no quoter is deployed, so the 0.05% pool is
+// used without checking liquidity
+pub const FLASH_SWAP_ROUTE_QUALITY_BPS: u64 = 9000;
+
+// `liquidationCall` args packed as L2Pool takes them, for the Liquidator contract. It reads the
+// borrower, amount and receiveAToken out of them and takes the assets as args, so the reserve
+// ids are left out and no L2Encoder is needed
+fn pack_liquidation_args(
+    borrower: Address,
+    debt_to_cover: U256,
+    receive_a_token: bool,
+) -> ([u8; 32], [u8; 32]) {
+    let args1 = U256::from_big_endian(borrower.as_bytes()) << 32;
+    // the contract expands the max shortened amount back to the max debt
+    let args2 =
+        debt_to_cover.min(U256::from(u128::MAX)) | (U256::from(receive_a_token as u8) << 128);
+    let word = |value: U256| {
+        let mut word = [0u8; 32];
+        value.to_big_endian(&mut word);
+        word
+    };
+    (word(args1), word(args2))
+}
+
+pub(crate) fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
     match deployment {
-        Deployment::AAVE => DeploymentConfig {
+        Deployment::AAVE | Deployment::AaveV3Base => DeploymentConfig {
             pool_address: Address::from_str("0xA238Dd80C259a72e81d7e4664a9801593F98d1c5").unwrap(),
             pool_data_provider: Address::from_str("0x2d8A3C5677189723C4cB8873CfC9C8976FDF38Ac")
                 .unwrap(),
@@ -77,6 +239,11 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::from_str("0x39e97c588B2907Fb67F44fea256Ae3BA064207C5").unwrap(),
             creation_block: 2963358,
             weth_address: Address::from_str(WETH_ADDRESS).unwrap(),
+            uniswap_quoter: Address::from_str(BASE_UNISWAP_QUOTER).unwrap(),
+            uniswap_factory: Address::from_str(BASE_UNISWAP_FACTORY).unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::SEASHELL => DeploymentConfig {
             pool_address: Address::from_str("0x8F44Fd754285aa6A2b8B9B97739B79746e0475a7").unwrap(),
@@ -87,6 +254,11 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::from_str("0xceceF475167f7BFD8995c0cbB577644b623cD7Cf").unwrap(),
             creation_block: 3318602,
             weth_address: Address::from_str(WETH_ADDRESS).unwrap(),
+            uniswap_quoter: Address::from_str(BASE_UNISWAP_QUOTER).unwrap(),
+            uniswap_factory: Address::from_str(BASE_UNISWAP_FACTORY).unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_0,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Sonic => DeploymentConfig {
             pool_address: Address::from_str("0x5362dBb1e601abF3a4c14c22ffEdA64042E5eAA3").unwrap(),
@@ -97,6 +269,11 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::zero(),
             creation_block: 7986580,
             weth_address: Address::from_str("0x039e2fB66102314Ce7b64Ce5Ce3E5183bc94aD38").unwrap(),
+            uniswap_quoter: Address::zero(),
+            uniswap_factory: Address::zero(),
+            fee_rebate_bps: SONIC_FEE_REBATE_BPS,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Celo => DeploymentConfig {
             pool_address: Address::from_str("0x3E59A31363E2ad014dcbc521c4a0d5757d9f3402").unwrap(),
@@ -107,6 +284,13 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::zero(),
             creation_block: 30390066,
             weth_address: Address::from_str("0x471EcE3750Da237f93B8E339c536989b8978a438").unwrap(),
+            uniswap_quoter: Address::from_str("0x82825d0554fA07f7FC52Ab63c961F330fdEFa8E8")
+                .unwrap(),
+            uniswap_factory: Address::from_str("0xAfE208a311B21f13EF87E33A90049fC17A7acDEc")
+                .unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Ethereum => DeploymentConfig {
             pool_address: Address::from_str("0x87870Bca3F3fD6335C3F4ce8392D69350B4fA4E2").unwrap(),
@@ -117,6 +301,11 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::zero(),
             creation_block: 16291126,
             weth_address: Address::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap(),
+            uniswap_quoter: Address::from_str(UNISWAP_QUOTER).unwrap(),
+            uniswap_factory: Address::from_str(UNISWAP_FACTORY).unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Optimism => DeploymentConfig {
             pool_address: Address::from_str("0x794a61358D6845594F94dc1DB02A252b5b4814aD").unwrap(),
@@ -127,6 +316,11 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::from_str("0x9abADECD08572e0eA5aF4d47A9C7984a5AA503dC").unwrap(),
             creation_block: 4365693,
             weth_address: Address::from_str(WETH_ADDRESS).unwrap(),
+            uniswap_quoter: Address::from_str(UNISWAP_QUOTER).unwrap(),
+            uniswap_factory: Address::from_str(UNISWAP_FACTORY).unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Bnb => DeploymentConfig {
             pool_address: Address::from_str("0x6807dc923806fE8Fd134338EABCA509979a7e0cB").unwrap(),
@@ -137,6 +331,13 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::zero(),
             creation_block: 33571625,
             weth_address: Address::from_str("0xbb4CdB9CBd36B01bD1cBaEBF2De08d9173bc095c").unwrap(),
+            uniswap_quoter: Address::from_str("0x78D78E420Da98ad378D7799bE8f4AF69033EB077")
+                .unwrap(),
+            uniswap_factory: Address::from_str("0xdB1d10011AD0Ff90774D0C6Bb92e5C5c8b4461F7")
+                .unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Arbitrum => DeploymentConfig {
             pool_address: Address::from_str("0x794a61358D6845594F94dc1DB02A252b5b4814aD").unwrap(),
@@ -147,6 +348,11 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::from_str("0x9abADECD08572e0eA5aF4d47A9C7984a5AA503dC").unwrap(),
             creation_block: 7742429,
             weth_address: Address::from_str("0x82aF49447D8a07e3bd95BD0d56f35241523fBab1").unwrap(),
+            uniswap_quoter: Address::from_str(UNISWAP_QUOTER).unwrap(),
+            uniswap_factory: Address::from_str(UNISWAP_FACTORY).unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Avax => DeploymentConfig {
             pool_address: Address::from_str("0x794a61358D6845594F94dc1DB02A252b5b4814aD").unwrap(),
@@ -157,6 +363,13 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::zero(),
             creation_block: 11970506,
             weth_address: Address::from_str("0xB31f66AA3C1e785363F0875A1B74E27b85FD66c7").unwrap(),
+            uniswap_quoter: Address::from_str("0xbe0F5544EC67e9B3b2D979aaA43f18Fd87E6257F")
+                .unwrap(),
+            uniswap_factory: Address::from_str("0x740b1c1de25031C31FF4fC9A62f554A55cdC1baD")
+                .unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
         Deployment::AaveV3Polygon => DeploymentConfig {
             pool_address: Address::from_str("0x794a61358D6845594F94dc1DB02A252b5b4814aD").unwrap(),
@@ -167,18 +380,326 @@ fn get_deployment_config(deployment: Deployment) -> DeploymentConfig {
             l2_encoder: Address::zero(),
             creation_block: 25826028,
             weth_address: Address::from_str("0x0d500B1d8E8eF31E21C99d1Db9A6444d3ADf1270").unwrap(),
+            uniswap_quoter: Address::from_str(UNISWAP_QUOTER).unwrap(),
+            uniswap_factory: Address::from_str(UNISWAP_FACTORY).unwrap(),
+            fee_rebate_bps: 0,
+            liquidation_rules: LiquidationRules::V3_3,
+            multicall_address: Address::from_str(MULTICALL3_ADDRESS).unwrap(),
         },
+        // the markets below are listed by pool and wrapped native token only, the rest is
+        // looked up by `resolve_deployment_addresses`
+        Deployment::AaveV3Gnosis => pool_only_deployment(
+            "0xb50201558B00496A145fE76f7424749556E326D8",
+            // WXDAI
+            "0xe91D153E0b41518A2Ce8Dd3D7944Fa863463a97d",
+            MULTICALL3_ADDRESS,
+        ),
+        Deployment::AaveV3Scroll => pool_only_deployment(
+            "0x11fCfe756c05AD438e312a7fd934381537D3cFfe",
+            "0x5300000000000000000000000000000000000004",
+            MULTICALL3_ADDRESS,
+        ),
+        Deployment::AaveV3Metis => pool_only_deployment(
+            "0x90df02551bB792286e8D4f13E0e357b4Bf1D6a57",
+            // WMETIS
+            "0x75cb093E4D61d2A2e65D8e0BBb01DE8d89b53481",
+            MULTICALL3_ADDRESS,
+        ),
+        Deployment::AaveV3Linea => pool_only_deployment(
+            "0xc47b8C00b0f69a36fa203Ffeac0334874574a8Ac",
+            "0xe5D7C2a44FfDDf6b295A15c148167daaAf5Cf34f",
+            MULTICALL3_ADDRESS,
+        ),
+        Deployment::AaveV3ZkSync => pool_only_deployment(
+            "0x78e30497a3c7527d953c6B1E3541b021A98Ac43c",
+            "0x5AEa5775959fBC2557Cc8789bC1bf90A239D9a91",
+            ZKSYNC_MULTICALL3_ADDRESS,
+        ),
+        Deployment::AaveV3Soneium => pool_only_deployment(
+            "0xDd3d7A7d03D9fD9ef45f3E587287922eF65CA38B",
+            WETH_ADDRESS,
+            MULTICALL3_ADDRESS,
+        ),
+        // the pool and wrapped native token are set with `with_overrides`
+        Deployment::AaveV3Fork => pool_only_deployment(
+            "0x0000000000000000000000000000000000000000",
+            "0x0000000000000000000000000000000000000000",
+            MULTICALL3_ADDRESS,
+        ),
+    }
+}
+
+// a v3.3 market known by its pool alone: no L2Encoder or Uniswap quoter, and its creation
+// block found on chain with `creation_block`
+fn pool_only_deployment(pool: &str, wrapped_native: &str, multicall: &str) -> DeploymentConfig {
+    DeploymentConfig {
+        pool_address: Address::from_str(pool).unwrap(),
+        pool_data_provider: Address::zero(),
+        oracle_address: Address::zero(),
+        l2_encoder: Address::zero(),
+        creation_block: 0,
+        weth_address: Address::from_str(wrapped_native).unwrap(),
+        uniswap_quoter: Address::zero(),
+        uniswap_factory: Address::zero(),
+        fee_rebate_bps: 0,
+        liquidation_rules: LiquidationRules::V3_3,
+        multicall_address: Address::from_str(multicall).unwrap(),
+    }
+}
+
+impl DeploymentConfig {
+    /// Liquidate on `pool` and value profits in `wrapped_native` instead, as set for a fork.
+    pub(crate) fn with_overrides(
+        mut self,
+        pool: Option<Address>,
+        wrapped_native: Option<Address>,
+    ) -> Self {
+        self.pool_address = pool.unwrap_or(self.pool_address);
+        self.weth_address = wrapped_native.unwrap_or(self.weth_address);
+        self
+    }
+}
+
+/// The block the deployment's pool was created in. Deployments that don't list it have it
+/// found by bisecting the chain for the first block the pool has code at, or are replayed
+/// from genesis if the node can't read code at old blocks.
+pub(crate) async fn creation_block<M: Middleware + 'static>(
+    client: &M,
+    config: &DeploymentConfig,
+) -> u64 {
+    if config.creation_block != 0 {
+        return config.creation_block;
+    }
+    match find_creation_block(client, config.pool_address).await {
+        Ok(block) => {
+            info!(
+                "Found pool {:?} created at block {}",
+                config.pool_address, block
+            );
+            block
+        }
+        Err(e) => {
+            warn!(
+                "Failed to find the creation block of pool {:?}, replaying from genesis: {}",
+                config.pool_address, e
+            );
+            0
+        }
+    }
+}
+
+async fn find_creation_block<M: Middleware + 'static>(client: &M, address: Address) -> Result<u64> {
+    let (mut low, mut high) = (0, client.get_block_number().await?.as_u64());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if client
+            .get_code(address, Some(middle.into()))
+            .await?
+            .is_empty()
+        {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(low)
+}
+
+/// Look up the data provider and oracle a deployment leaves unset from its pool's addresses
+/// provider, so they follow the market's upgrades.
+pub(crate) async fn resolve_deployment_addresses<M: Middleware + 'static>(
+    client: Arc<M>,
+    config: &mut DeploymentConfig,
+) -> Result<()> {
+    if config.pool_address.is_zero() || config.weth_address.is_zero() {
+        return Err(anyhow!(
+            "A fork deployment needs --pool-address and --wrapped-native-address"
+        ));
+    }
+    if !config.pool_data_provider.is_zero() && !config.oracle_address.is_zero() {
+        return Ok(());
+    }
+    let pool = Pool::<M>::new(config.pool_address, client.clone());
+    let addresses_provider =
+        IPoolAddressesProvider::<M>::new(pool.addresses_provider().call().await?, client);
+    if config.pool_data_provider.is_zero() {
+        config.pool_data_provider = addresses_provider.get_pool_data_provider().call().await?;
+    }
+    if config.oracle_address.is_zero() {
+        config.oracle_address = addresses_provider.get_price_oracle().call().await?;
+    }
+    info!(
+        "Resolved pool data provider {:?} and oracle {:?}",
+        config.pool_data_provider, config.oracle_address
+    );
+    Ok(())
+}
+
+// per-borrower reads of a deployment, straight from its contracts
+fn ethers_data_source<M: Middleware + 'static>(
+    client: Arc<M>,
+    config: &DeploymentConfig,
+    multicall: MulticallSite,
+    ui_pool_data_provider: Option<Address>,
+) -> EthersDataSource<M> {
+    let data_source = EthersDataSource::new(client, config.pool_address, config.pool_data_provider)
+        .with_multicall(multicall);
+    match ui_pool_data_provider {
+        Some(address) => data_source.with_ui_pool_data_provider(address),
+        None => data_source,
     }
 }
 
+// return values of IPoolDataProvider.getUserReserveData
+type UserReserveData = (U256, U256, U256, U256, U256, U256, U256, u64, bool);
+type ReserveData = (
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    U256,
+    u64,
+);
+
+// return values of IPoolDataProvider.getReserveConfigurationData
+type ReserveConfigurationData = (U256, U256, U256, U256, U256, bool, bool, bool, bool, bool);
+
+/// Borrower state cache of a deployment in `state_dir`, so instances for different
+/// networks can share a directory.
+pub fn state_cache_path(state_dir: &Path, chain_id: u64, deployment: &Deployment) -> PathBuf {
+    let name = deployment
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_else(|| format!("{:?}", deployment));
+    state_dir.join(format!("borrowers-{}-{}.json.gz", chain_id, name))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StateCache {
+    version: u32,
+    chain_id: u64,
+    deployment: Deployment,
+    /// Liquidator of the instance that wrote the cache.
+    liquidator: Address,
     last_block_number: u64,
     borrowers: HashMap<Address, Borrower>,
 }
 
+impl StateCache {
+    pub fn new(
+        chain_id: u64,
+        deployment: Deployment,
+        liquidator: Address,
+        last_block_number: u64,
+        borrowers: HashMap<Address, Borrower>,
+    ) -> Self {
+        Self {
+            version: STATE_CACHE_VERSION,
+            chain_id,
+            deployment,
+            liquidator,
+            last_block_number,
+            borrowers,
+        }
+    }
+
+    /// Read a gzipped cache, `None` if there is none at `path`.
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let cache = serde_json::from_reader(GzDecoder::new(BufReader::new(file)))
+            .map_err(|e| anyhow!("Failed to parse state cache: {}", e))?;
+        Ok(Some(cache))
+    }
+
+    /// Write the cache gzipped to a temp file renamed over `path`, so a crash mid-write
+    /// never leaves a truncated cache behind.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut encoder =
+            GzEncoder::new(BufWriter::new(File::create(&tmp)?), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Block borrowers were last indexed up to.
+    pub fn last_block_number(&self) -> u64 {
+        self.last_block_number
+    }
+
+    pub fn borrower(&self, address: &Address) -> Option<&Borrower> {
+        self.borrowers.get(address)
+    }
+
+    pub fn borrower_count(&self) -> usize {
+        self.borrowers.len()
+    }
+
+    /// Whether the cache was written by this version for the same network and deployment.
+    pub fn is_compatible(&self, chain_id: u64, deployment: &Deployment) -> bool {
+        self.version == STATE_CACHE_VERSION
+            && self.chain_id == chain_id
+            && &self.deployment == deployment
+    }
+}
+
+struct HealthFactorScan {
+    // borrowers with health factor < 1, sorted by health factor
+    underwater: Vec<(Address, U256)>,
+    // borrowers below the watchlist threshold
+    watchlist: HashSet<Address>,
+    // borrowers below the risk engine threshold
+    at_risk: HashSet<Address>,
+    // underwater borrowers with bad debt, with their collateral and debt in base currency
+    bad_debt: HashMap<Address, (U256, U256)>,
+    // borrowers scanned, bucketed by health factor
+    histogram: HealthFactorHistogram,
+}
+
 struct PoolState {
     prices: HashMap<Address, U256>,
+    // timestamp of the latest answer of each asset's price source, if it exposes one
+    price_timestamps: HashMap<Address, u64>,
+    block_number: u64,
+    timestamp: u64,
+    // prices include oracle updates that are still pending, so liquidations can't be simulated yet
+    pending: bool,
+}
+
+/// The amounts of a liquidation, before pricing its route.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LiquidationPlan {
+    pub collateral: Address,
+    pub debt: Address,
+    pub debt_to_cover: U256,
+    /// The variable and stable debt `debt_to_cover` repays, variable first.
+    pub repaid: UserDebt,
+    pub collateral_to_liquidate: U256,
+    /// The protocol's cut of the liquidation bonus, out of `collateral_to_liquidate`.
+    pub protocol_fee_amount: U256,
+    pub collateral_unit: U256,
+    pub debt_unit: U256,
+}
+
+impl LiquidationPlan {
+    /// Collateral the liquidator ends up with, once the protocol took its fee.
+    pub fn collateral_received(&self) -> U256 {
+        self.collateral_to_liquidate - self.protocol_fee_amount
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -186,9 +707,53 @@ pub struct Borrower {
     address: Address,
     collateral: HashSet<Address>,
     debt: HashSet<Address>,
+    /// Unknown until the code at the address is checked
+    #[serde(default)]
+    kind: Option<AccountKind>,
+    /// Debt in base currency at the last health factor check, unknown until one or once
+    /// the borrower's reserves are reconciled
+    #[serde(default)]
+    total_debt_base: Option<U256>,
+    /// The siloed asset they borrowed, the only debt they can hold
+    #[serde(default)]
+    siloed_debt: Option<Address>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Borrower {
+    // the pool lets a borrower of a siloed asset hold no other debt, so any other asset in
+    // their debt is stale
+    fn mark_siloed(&mut self, is_siloed: impl Fn(&Address) -> bool) {
+        self.siloed_debt = siloed_debt(&self.debt, is_siloed);
+    }
+
+    // the assets the borrower owes
+    fn debt_assets(&self) -> HashSet<Address> {
+        self.siloed_debt
+            .map_or_else(|| self.debt.clone(), |siloed| HashSet::from([siloed]))
+    }
+
+    fn owes(&self, asset: &Address) -> bool {
+        self.siloed_debt
+            .map_or_else(|| self.debt.contains(asset), |siloed| siloed == *asset)
+    }
+
+    // owed less than `min_debt_base` when last checked, not worth a getUserAccountData call
+    fn is_dust(&self, min_debt_base: U256) -> bool {
+        self.total_debt_base
+            .is_some_and(|total_debt_base| total_debt_base < min_debt_base)
+    }
+}
+
+/// Smart accounts can move their positions through internal calls the
+/// event tracker misses, so they are reconciled on-chain more often.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountKind {
+    Eoa,
+    SmartAccount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenConfig {
     address: Address,
     a_address: Address,
@@ -199,6 +764,49 @@ pub struct TokenConfig {
     reserve_factor: u64,
     protocol_fee: u64,
     symbol: String,
+    price_source: Address,
+    /// Isolation mode debt ceiling, zero unless the asset is isolated
+    debt_ceiling: U256,
+    /// Borrowers of a siloed asset can't borrow anything else
+    siloed_borrowing: bool,
+    active: bool,
+    /// Frozen reserves take no new supply or borrows but can still be liquidated
+    frozen: bool,
+    paused: bool,
+    /// Caps in whole tokens, zero if uncapped
+    supply_cap: U256,
+    borrow_cap: U256,
+    /// Share of each cap in use (in bps) at the last refresh, none if uncapped
+    supply_cap_utilization_bps: Option<u64>,
+    borrow_cap_utilization_bps: Option<u64>,
+    /// E-mode category of the reserve up to v3.1, zero if none
+    e_mode_category: u8,
+    /// Bonus of the reserve's e-mode category, set from the loaded categories
+    #[serde(skip)]
+    e_mode_liquidation_bonus: Option<u64>,
+}
+
+/// An e-mode category, whose borrowers are liquidated at its own bonus when they seize
+/// collateral of the category.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EModeCategory {
+    ltv: u64,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+    label: String,
+}
+
+impl TokenConfig {
+    /// Whether the pool accepts liquidations involving this reserve.
+    fn is_liquidatable(&self) -> bool {
+        self.active && !self.paused
+    }
+
+    /// Whether the pool lets liquidators seize this reserve, one with no liquidation
+    /// threshold backs no debt even where a borrower still has it enabled as collateral.
+    fn is_seizable(&self) -> bool {
+        self.liquidation_threshold != 0
+    }
 }
 
 #[derive(Debug)]
@@ -206,15 +814,171 @@ pub struct TokenConfig {
 pub struct AaveStrategy<M> {
     /// Ethers client.
     client: Arc<M>,
-    /// Amount of profits to bid in gas
-    bid_percentage: u64,
+    /// Decides whether and how to submit based on opportunity confidence
+    submission_policy: SubmissionPolicy,
+    /// How opportunities are ranked besides their profit
+    scoring: ScoringWeights,
+    /// Who wins the liquidations we race for, and at what gas price
+    competitors: CompetitorTracker,
+    /// Share of profit bid in gas, learned from those races
+    bid_model: BidModel,
+    bid_model_file: PathBuf,
     last_block_number: u64,
+    /// Block at which health factors were last checked
+    last_health_check_block: u64,
     borrowers: HashMap<Address, Borrower>,
     tokens: HashMap<Address, TokenConfig>,
+    /// Reserves in pool order, the order of the user configuration bitmap
+    reserves: Vec<Address>,
+    /// Every reserve is paused, as after a pool-wide pause
+    pool_paused: bool,
+    /// The pool still has stable debt, which v3.2 removed
+    stable_debt: bool,
+    /// Emits the events reserve configs are refreshed on, zero until the reserves are loaded
+    pool_configurator: Address,
+    /// Premium (in bps) of the pool's flash loans, which same-asset liquidations pay
+    flash_loan_premium: u64,
+    /// Chainlink sequencer uptime feed of L2 pools, zero elsewhere
+    sequencer_oracle: Address,
+    /// Time after the sequencer comes back up during which liquidations revert
+    sequencer_grace_period: U256,
+    sequencer_down: bool,
     chain_id: u64,
+    deployment: Deployment,
+    state_cache_file: PathBuf,
+    /// Reserve configs of earlier runs, reused within `reserve_cache_ttl_secs`
+    reserve_cache_file: PathBuf,
+    /// Seconds a cached reserve config is trusted for, nothing is cached if zero
+    reserve_cache_ttl_secs: u64,
+    /// Unix time each reserve's config was fetched at
+    reserves_fetched_at: HashMap<Address, u64>,
+    /// E-mode categories of the reserves by id
+    e_mode_categories: HashMap<u8, EModeCategory>,
+    /// Unix time each e-mode category was fetched at
+    e_mode_categories_fetched_at: HashMap<u8, u64>,
+    /// Aave V3 subgraph the borrower set is bootstrapped from, if there is no cache
+    subgraph_url: Option<String>,
+    /// Progress of the last state update
+    sync: SyncProgress,
+    /// Submission waits until an update leaves the state within this many blocks of the head
+    max_sync_lag: Option<u64>,
+    /// An update left the state within `max_sync_lag` blocks of the head
+    synced: bool,
     config: DeploymentConfig,
     liquidator: Address,
     use_aave_liquidator: bool,
+    /// Pairs of one borrower liquidated in the same tx through the Liquidator contract
+    max_liquidation_legs: usize,
+    /// Route flash swaps through the wrapped native token when no pool pairs the collateral
+    /// with the debt, needs a Liquidator contract with `liquidatePath`
+    multi_hop_swaps: bool,
+    /// Balancer and Curve pools liquidations fall back to when Uniswap V3 can't swap the
+    /// collateral for the debt, and their tokens once read
+    flash_liquidity_pools: Vec<FlashLiquidityPool>,
+    flash_liquidity: Vec<FlashLiquidityVenue>,
+    /// Repay a Balancer flash loan of the debt by selling the collateral along the best
+    /// aggregator route when no pool can swap it
+    aggregator_flash_swaps: bool,
+    /// Borrowers close to liquidation, polled on every watchlist tick.
+    watchlist: HashSet<Address>,
+    watchlist_health_factor_bps: u64,
+    /// Borrowers indexed by the oracle prices that would make them liquidatable.
+    risk_engine: RiskEngine,
+    risk_health_factor_bps: u64,
+    /// Oracle prices the risk engine last looked at
+    last_prices: HashMap<Address, U256>,
+    /// Borrowers with bad debt, with the block they were first seen at
+    bad_debt: HashMap<Address, u64>,
+    /// Latest rates and indexes of each reserve, kept current by `ReserveDataUpdated` logs
+    reserve_data: HashMap<Address, ReserveDataUpdate>,
+    /// Whether submission was paused by an operator
+    paused: bool,
+    /// Native balance (in wei) the sender should keep for gas
+    min_gas_balance: U256,
+    /// Hold submission while the wallet check finds problems
+    pause_on_wallet_problems: bool,
+    /// Outcome of the last wallet check
+    wallet: WalletHealth,
+    /// Stops submission after repeated failures or untrustworthy prices
+    breaker: CircuitBreaker,
+    /// Halts submission while an operator engages it
+    kill_switch: KillSwitch,
+    /// Caps the debt of liquidations in flight, the executor committing to it
+    exposure: ExposureLimiter,
+    /// Oracle exposing `getAssetPrice` the Aave oracle's prices are checked against
+    reference_oracle: Option<Address>,
+    /// Accounts watched on behalf of partners, with how each is handled.
+    partner_accounts: HashMap<Address, PartnerAccount>,
+    partner_accounts_file: PathBuf,
+    /// Repays sent for partner accounts and the block they were sent at, one per account
+    partner_repays: HashMap<Address, (H256, u64)>,
+    repay_adapter: Option<Address>,
+    paraswap_api_url: String,
+    target_health_factor_bps: u64,
+    max_slippage_bps: u64,
+    /// Share of gas fees refunded by the chain, in bps
+    fee_rebate_bps: u64,
+    /// How the chain charges for posting txs to L1, counted against each opportunity
+    l1_fee: L1FeeModel,
+    /// Number of state updates so far, schedules borrower reconciliation
+    update_count: u64,
+    /// Borrowers reconciled on the next update whatever their schedule, those seeded
+    /// from the subgraph
+    reconcile_next: HashSet<Address>,
+    smart_account_reconcile_ticks: u64,
+    eoa_reconcile_ticks: u64,
+    /// Opportunities making less than this (in units of the profit currency) are not submitted
+    min_profit_eth: U256,
+    /// What the min profit is set in and profits are reported in
+    profit_currency: ProfitCurrency,
+    /// Bid and min profit of liquidations involving an asset, set by tunables
+    asset_overrides: HashMap<Address, AssetOverride>,
+    /// Assets and borrowers never liquidated
+    filter: LiquidationFilter,
+    /// Borrowers owing less than this (in USD) are skipped as dust
+    min_debt_usd: u64,
+    /// Underwater borrowers evaluated at once
+    evaluation_concurrency: usize,
+    /// The same threshold in the oracle's base currency
+    min_debt_base: U256,
+    /// What the strategy last saw, shared with the admin API
+    status: SharedStatus,
+    /// Counters of the scan tick in progress
+    tick: TickSummary,
+    /// Harvesting of seized collateral, disabled if unset
+    harvest: Option<HarvestConfig>,
+    /// Large liquidations held for an operator's approval, if set
+    approval: Option<Mutex<ApprovalGate>>,
+    /// Collateral seized as aTokens on the Aave path
+    receive_a_token: HashSet<Address>,
+    /// Buffer (in bps) over each liquidation's debt approved right before it, if set
+    approval_buffer_bps: Option<u64>,
+    /// Picks the best aggregator route for swaps the bot sends itself
+    swap_router: SwapRouter,
+    /// Cross-checks oracle prices with secondary sources before submitting, disabled if unset
+    price_checker: Option<PriceChecker>,
+    /// Traces failed simulations into the opportunity's logs, if set
+    tracer: Option<SimulationTracer>,
+    /// Per-borrower reads liquidation opportunities are built from
+    data_source: Arc<dyn AaveDataSource>,
+    /// Batches reads through Multicall3, or makes them one by one without it
+    multicall: MulticallSite,
+    /// UiPoolDataProvider positions are snapshotted through, if set
+    ui_pool_data_provider: Option<Address>,
+    /// `eth_getLogs` block range learned for the provider
+    log_range: LogRange,
+    /// Intervals of the scan and watchlist ticks, changed by tunables if set
+    scan_interval: Option<PollInterval>,
+    watchlist_interval: Option<PollInterval>,
+    shutdown: Shutdown,
+    /// Borrowers, watchlists and liquidation claims shared with the rest of a cluster, if set
+    shared_state: Option<SharedState>,
+    /// Whether this instance detects, submits, or both
+    role: Role,
+    /// Streams the opportunities considered to gRPC subscribers, if set
+    opportunity_feed: Option<OpportunityFeed>,
+    /// Posts every opportunity evaluated and what became of it, if set
+    opportunity_webhook: Option<OpportunityWebhook>,
 }
 
 impl<M: Middleware + 'static> AaveStrategy<M> {
@@ -225,21 +989,435 @@ impl<M: Middleware + 'static> AaveStrategy<M> {
         liquidator_address: String,
         use_aave_liquidator: bool,
     ) -> Self {
+        let deployment_config = get_deployment_config(deployment.clone())
+            .with_overrides(config.pool_address, config.wrapped_native_address);
+        let fee_rebate_bps = fee_rebate_bps(
+            config.fee_rebate_bps,
+            config.fee_rebate,
+            deployment_config.fee_rebate_bps,
+        );
+        let swap_router = SwapRouter::new(
+            config.chain_id,
+            config.max_slippage_bps,
+            config.paraswap_api_url.clone(),
+            config.one_inch_api_key,
+            config.zero_ex_api_key,
+        );
+        let multicall = MulticallSite::new(
+            config
+                .multicall_address
+                .unwrap_or(deployment_config.multicall_address),
+        );
+        let data_source = Arc::new(ethers_data_source(
+            client.clone(),
+            &deployment_config,
+            multicall.clone(),
+            config.ui_pool_data_provider,
+        ));
+        let price_checker = (!config.price_sources.is_empty()).then(|| {
+            PriceChecker::new(
+                config.chain_id,
+                config.price_sources.clone(),
+                config.max_price_source_deviation_bps,
+            )
+        });
+        let liquidator =
+            Address::from_str(&liquidator_address).expect("invalid liquidator address");
+        let bid_bounds = BidBounds {
+            base: config.bid_percentage,
+            min: config.min_bid_percentage.unwrap_or(config.bid_percentage),
+            max: config.max_bid_percentage.unwrap_or(config.bid_percentage),
+        };
+        let own_accounts: HashSet<Address> = [Some(liquidator), client.default_sender()]
+            .into_iter()
+            .flatten()
+            .collect();
         Self {
             client,
-            bid_percentage: config.bid_percentage,
+            submission_policy: SubmissionPolicy {
+                min_confidence_bps: config.min_confidence_bps,
+                public_confidence_bps: config.public_confidence_bps,
+            },
+            scoring: config.scoring,
+            competitors: CompetitorTracker::new(own_accounts),
+            bid_model: BidModel::new(config.chain_id, bid_bounds),
+            bid_model_file: bid_model_path(&config.state_dir, config.chain_id),
             last_block_number: 0,
+            last_health_check_block: 0,
             borrowers: HashMap::new(),
             tokens: HashMap::new(),
+            reserves: Vec::new(),
+            pool_paused: false,
+            stable_debt: true,
+            pool_configurator: Address::zero(),
+            flash_loan_premium: 0,
+            sequencer_oracle: Address::zero(),
+            sequencer_grace_period: U256::zero(),
+            sequencer_down: false,
             chain_id: config.chain_id,
-            config: get_deployment_config(deployment),
-            liquidator: Address::from_str(&liquidator_address).expect("invalid liquidator address"),
+            state_cache_file: state_cache_path(&config.state_dir, config.chain_id, &deployment),
+            reserve_cache_file: reserve_cache_path(
+                &config.state_dir,
+                config.chain_id,
+                deployment_config.pool_address,
+            ),
+            reserve_cache_ttl_secs: config.reserve_cache_ttl_secs,
+            reserves_fetched_at: HashMap::new(),
+            e_mode_categories: HashMap::new(),
+            e_mode_categories_fetched_at: HashMap::new(),
+            subgraph_url: config.subgraph_url,
+            sync: SyncProgress::default(),
+            max_sync_lag: config.max_sync_lag,
+            synced: false,
+            deployment,
+            config: deployment_config,
+            liquidator,
             use_aave_liquidator,
+            max_liquidation_legs: config.max_liquidation_legs.max(1),
+            multi_hop_swaps: config.multi_hop_swaps,
+            flash_liquidity_pools: config.flash_liquidity_pools,
+            aggregator_flash_swaps: config.aggregator_flash_swaps,
+            flash_liquidity: Vec::new(),
+            watchlist: HashSet::new(),
+            watchlist_health_factor_bps: config.watchlist_health_factor_bps,
+            risk_engine: RiskEngine::new(),
+            risk_health_factor_bps: config.risk_health_factor_bps,
+            last_prices: HashMap::new(),
+            bad_debt: HashMap::new(),
+            reserve_data: HashMap::new(),
+            paused: false,
+            min_gas_balance: config.min_gas_balance_wei,
+            pause_on_wallet_problems: config.pause_on_wallet_problems,
+            wallet: WalletHealth::default(),
+            breaker: CircuitBreaker::default(),
+            kill_switch: KillSwitch::default(),
+            exposure: ExposureLimiter::default(),
+            reference_oracle: config.reference_oracle,
+            partner_accounts: HashMap::new(),
+            partner_accounts_file: config.partner_accounts_file,
+            partner_repays: HashMap::new(),
+            repay_adapter: config.repay_adapter,
+            paraswap_api_url: config.paraswap_api_url,
+            target_health_factor_bps: config.target_health_factor_bps,
+            max_slippage_bps: config.max_slippage_bps,
+            fee_rebate_bps,
+            l1_fee: L1FeeModel::None,
+            update_count: 0,
+            reconcile_next: HashSet::new(),
+            smart_account_reconcile_ticks: config.smart_account_reconcile_ticks.max(1),
+            eoa_reconcile_ticks: config.eoa_reconcile_ticks.max(1),
+            min_profit_eth: config.min_profit_eth,
+            profit_currency: config.profit_currency,
+            asset_overrides: HashMap::new(),
+            filter: config.filter,
+            min_debt_usd: config.min_debt_usd,
+            evaluation_concurrency: config.evaluation_concurrency.max(1),
+            min_debt_base: U256::zero(),
+            status: SharedStatus::default(),
+            tick: TickSummary::default(),
+            harvest: config.harvest,
+            approval: config
+                .approval
+                .map(|config| Mutex::new(ApprovalGate::new(config))),
+            receive_a_token: config.receive_a_token,
+            approval_buffer_bps: config.approval_buffer_bps,
+            swap_router,
+            price_checker,
+            tracer: config
+                .tracer
+                .map(|backend| SimulationTracer::new(backend, config.chain_id)),
+            data_source,
+            multicall,
+            ui_pool_data_provider: config.ui_pool_data_provider,
+            log_range: LogRange::new(config.log_block_range),
+            scan_interval: None,
+            watchlist_interval: None,
+            shutdown: Shutdown::new(),
+            shared_state: None,
+            opportunity_feed: None,
+            opportunity_webhook: None,
+            role: Role::All,
+        }
+    }
+
+    /// Share of gas fees (in bps) the chain refunds, counted when netting gas out of profit.
+    pub fn fee_rebate_bps(&self) -> u64 {
+        self.fee_rebate_bps
+    }
+
+    /// Oracle the executors value landed liquidations with.
+    pub fn valuation(&self) -> Valuation {
+        Valuation {
+            oracle: self.config.oracle_address,
+            weth: self.config.weth_address,
         }
     }
+
+    /// Load reserves and index borrowers up to the latest block, without sending any tx.
+    pub async fn load_state(&mut self) -> Result<()> {
+        self.load_reserves().await?;
+        self.load_min_debt().await?;
+        self.load_cache()?;
+        if self.last_block_number == 0 {
+            self.last_block_number = creation_block(&*self.client, &self.config).await;
+        }
+        self.load_shared_cache().await;
+        self.load_bid_model()?;
+        self.bootstrap_borrowers().await;
+        self.load_partner_accounts()?;
+        self.update_state().await
+    }
+
+    /// Load the reserves and the sequencer uptime feed, enough to liquidate a given borrower.
+    pub async fn load_reserves(&mut self) -> Result<()> {
+        if self.config.pool_data_provider.is_zero() || self.config.oracle_address.is_zero() {
+            resolve_deployment_addresses(self.client.clone(), &mut self.config).await?;
+            self.data_source = Arc::new(ethers_data_source(
+                self.client.clone(),
+                &self.config,
+                self.multicall.clone(),
+                self.ui_pool_data_provider,
+            ));
+        }
+        self.stable_debt = self.data_source.has_stable_debt().await?;
+        self.update_token_configs().await?;
+        self.load_flash_liquidity().await?;
+        self.load_sequencer_oracle().await
+    }
+
+    /// Build and simulate a liquidation of `borrower` seizing `collateral` for `debt`, repaying
+    /// at most `amount` of debt or as much as the close factor allows. The operator picked the
+    /// pair, so the asset filters don't apply.
+    pub async fn build_manual_liquidation(
+        &mut self,
+        borrower: Address,
+        collateral: Address,
+        debt: Address,
+        amount: Option<U256>,
+    ) -> Result<SubmitTx> {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        let (_, total_debt_base, .., health_factor) =
+            pool.get_user_account_data(borrower).call().await?;
+        if health_factor >= U256::exp10(18) {
+            return Err(anyhow!(
+                "Borrower {:?} has health factor {}, not liquidatable",
+                borrower,
+                health_factor
+            ));
+        }
+        let sequencer_up = self.is_sequencer_up().await;
+        if !is_liquidation_allowed(health_factor, sequencer_up) {
+            return Err(anyhow!("Sequencer is down or in its grace period"));
+        }
+
+        let pool_state = self.get_pool_state().await?;
+        let plan = plan_pair_liquidation(
+            &*self.data_source,
+            &self.tokens,
+            self.config.liquidation_rules,
+            &borrower,
+            &health_factor,
+            total_debt_base,
+            &pool_state.prices,
+            &collateral,
+            &debt,
+            amount,
+        )
+        .await?;
+        if plan.debt_to_cover.is_zero() {
+            return Err(anyhow!("Borrower {:?} has no debt to cover", borrower));
+        }
+        let mut op = LiquidationOpportunity {
+            borrower,
+            collateral,
+            debt,
+            debt_to_cover: plan.debt_to_cover,
+            profit_eth: I256::from(0),
+            collateral_symbol: self.tokens[&collateral].symbol.clone(),
+            debt_symbol: self.tokens[&debt].symbol.clone(),
+            profit_factor: I256::from(0),
+            confidence: CONFIDENCE_ONE,
+            correlation_id: new_correlation_id(),
+            uniswap_fee: DEFAULT_UNISWAP_FEE,
+            swap_path: None,
+            flash_swap: None,
+            health_factor,
+            route_quality_bps: CONFIDENCE_ONE,
+            simulation: SimulationResult::Succeeded,
+            legs: Vec::new(),
+            collateral_price: pool_state
+                .prices
+                .get(&collateral)
+                .copied()
+                .unwrap_or_default(),
+            debt_price: pool_state.prices.get(&debt).copied().unwrap_or_default(),
+            profit_rate: self.profit_rate(&pool_state.prices),
+            valued_at: None,
+            l1_fee_eth: U256::zero(),
+        };
+
+        let collateral_price_eth = self.get_asset_price_eth(&collateral, &pool_state).await?;
+        if self.use_aave_liquidator {
+            let debt_price_eth = self.get_asset_price_eth(&debt, &pool_state).await?;
+            (op.profit_eth, op.profit_factor) = oracle_profit(
+                collateral_price_eth,
+                plan.collateral_received(),
+                plan.collateral_unit,
+                debt_price_eth,
+                plan.debt_to_cover,
+                plan.debt_unit,
+            )?;
+            // the pool pays the sender, so simulate from it
+            let sender = self
+                .client
+                .default_sender()
+                .ok_or(anyhow!("No connected sender"))?;
+            self.approve_liquidation(&op).await?;
+            let mut tx = self.build_liquidation(&op).await?;
+            tx.set_from(sender);
+            if let Err(e) = self.client.call(&tx, None).await {
+                return Err(self.explain_failure(&tx, e).await);
+            }
+        } else {
+            if self.config.uniswap_quoter != Address::zero() {
+                self.select_route(&mut op, plan.collateral_received())
+                    .await?;
+            }
+            let call = self.build_liquidation_call(&op).await?;
+            let gain = match call.call().await {
+                Ok(gain) => gain,
+                Err(e) => return Err(self.explain_failure(&call.tx, e).await),
+            };
+            op.profit_eth = gain
+                .checked_mul(I256::try_from(collateral_price_eth)?)
+                .ok_or(anyhow!("Profit overflow"))?
+                / I256::from(PRICE_ONE);
+        }
+        info!("Simulated manual liquidation: {:?}", op);
+
+        Ok(SubmitTx {
+            submission: SubmitTxToMempool {
+                tx: self.build_liquidation(&op).await?,
+                // sent whatever the estimated profit, the operator asked for it
+                gas_bid_info: None,
+            },
+            liquidation: Some(op.liquidation_info(self.debt_value_base(&op), &self.tokens)),
+            correlation_id: op.correlation_id,
+            private: true,
+            valued_at: None,
+        })
+    }
+
+    /// Scan every borrower once, returning the underwater ones that can be liquidated,
+    /// most profitable first.
+    pub async fn scan_once(&mut self) -> Result<Vec<LiquidatableAccount>> {
+        let underwater = self.get_underwater_borrowers().await?;
+        if underwater.is_empty() {
+            return Ok(vec![]);
+        }
+        let health_factors: HashMap<Address, U256> = underwater.iter().copied().collect();
+        let pool_state = self.get_pool_state().await?;
+        let mut ops = self.evaluate_ops(underwater, &pool_state).await?;
+        ops.sort_by_key(|op| std::cmp::Reverse(op.profit_eth));
+
+        Ok(ops
+            .into_iter()
+            .map(|op| LiquidatableAccount {
+                borrower: op.borrower,
+                health_factor: health_factors[&op.borrower],
+                collateral: op.collateral,
+                collateral_symbol: op.collateral_symbol,
+                debt: op.debt,
+                debt_symbol: op.debt_symbol,
+                debt_to_cover: op.debt_to_cover,
+                debt_decimals: self
+                    .tokens
+                    .get(&op.debt)
+                    .map(|token| token.decimals)
+                    .unwrap_or_default(),
+                profit_eth: op.profit_eth,
+            })
+            .collect())
+    }
+
+    /// Handle on the status the strategy publishes, read by the admin API.
+    pub fn status(&self) -> SharedStatus {
+        self.status.clone()
+    }
+
+    /// Stop processing events once shutdown is requested.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Let tunables change the intervals of the collectors ticking scans and watchlist polls.
+    pub fn with_poll_intervals(mut self, scan: PollInterval, watchlist: PollInterval) -> Self {
+        self.scan_interval = Some(scan);
+        self.watchlist_interval = Some(watchlist);
+        self
+    }
+
+    /// Share borrowers and watchlists with the other instances of a cluster, and claim each
+    /// liquidation before submitting it so only one of them does.
+    pub fn with_shared_state(mut self, shared_state: SharedState) -> Self {
+        self.shared_state = Some(shared_state);
+        self
+    }
+
+    /// Only detect and queue opportunities, or only submit queued ones, through the shared
+    /// state set with [Self::with_shared_state].
+    pub fn with_role(mut self, role: Role) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Take the L1 data fee of rollups out of each opportunity's profit.
+    pub fn with_l1_fee(mut self, l1_fee: L1FeeModel) -> Self {
+        self.l1_fee = l1_fee;
+        self
+    }
+
+    /// Stream the opportunities considered to `feed`, as listed on the admin API.
+    pub fn with_opportunity_feed(mut self, feed: OpportunityFeed) -> Self {
+        self.opportunity_feed = Some(feed);
+        self
+    }
+
+    /// Post every opportunity evaluated for submission to `webhook`, with whether it was
+    /// submitted and why not.
+    pub fn with_opportunity_webhook(mut self, webhook: OpportunityWebhook) -> Self {
+        self.opportunity_webhook = Some(webhook);
+        self
+    }
+
+    /// Pass on opportunities whose debt wouldn't fit in `exposure` besides the liquidations
+    /// in flight, the executor committing to it.
+    pub fn with_exposure_limiter(mut self, exposure: ExposureLimiter) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Hold submission while `kill_switch` is engaged.
+    pub fn with_kill_switch(mut self, kill_switch: KillSwitch) -> Self {
+        self.kill_switch = kill_switch;
+        self
+    }
+
+    /// Hold submission while `breaker` is tripped, the executor reporting outcomes to it.
+    pub fn with_circuit_breaker(mut self, breaker: CircuitBreaker) -> Self {
+        self.breaker = breaker;
+        self
+    }
+
+    /// Build liquidation opportunities from another data source, e.g. a mock.
+    pub fn with_data_source(mut self, data_source: Arc<dyn AaveDataSource>) -> Self {
+        self.data_source = data_source;
+        self
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct LiquidationOpportunity {
     borrower: Address,
     collateral: Address,
@@ -249,6 +1427,80 @@ struct LiquidationOpportunity {
     collateral_symbol: String,
     debt_symbol: String,
     profit_factor: I256,
+    /// Confidence score in bps
+    confidence: u64,
+    /// Ties together the logs of this opportunity, from detection to receipt
+    correlation_id: String,
+    /// Fee tier of the Uniswap V3 pool the Liquidator contract flash-swaps through
+    uniswap_fee: u32,
+    /// Route through other tokens when no single pool swaps the collateral for the debt,
+    /// `uniswap_fee` is unused then
+    swap_path: Option<SwapPath>,
+    /// Balancer flash loan repaid through a Balancer or Curve pool, when Uniswap V3 can't
+    /// swap the collateral for the debt at all
+    flash_swap: Option<FlashSwap>,
+    health_factor: U256,
+    /// Quality of the swap route out of the collateral, in bps
+    route_quality_bps: u64,
+    simulation: SimulationResult,
+    /// Further pairs of the borrower liquidated after this one in the same tx, while it
+    /// stays liquidatable
+    legs: Vec<LiquidationLeg>,
+    /// Oracle prices the opportunity was valued at, in the oracle's base currency
+    collateral_price: U256,
+    debt_price: U256,
+    /// Converts the profit into the profit currency at the same prices, none without them
+    profit_rate: Option<ProfitRate>,
+    /// Chain head the prices were read at, none for liquidations the operator asked for
+    valued_at: Option<ValuedAt>,
+    /// L1 data fee of the tx in ETH with 8 decimals, already taken out of `profit_eth`
+    #[serde(default)]
+    l1_fee_eth: U256,
+}
+
+/// An opportunity a detect-only instance decided to submit, queued for an execute-only one.
+#[derive(Debug, Serialize, Deserialize)]
+struct OpportunityMessage {
+    op: LiquidationOpportunity,
+    decision: SubmissionDecision,
+}
+
+impl LiquidationOpportunity {
+    fn first_leg(&self) -> LiquidationLeg {
+        LiquidationLeg {
+            collateral: self.collateral,
+            debt: self.debt,
+            debt_to_cover: self.debt_to_cover,
+            uniswap_fee: self.uniswap_fee,
+        }
+    }
+
+    // the opportunity's own pair, then the pairs liquidated after it
+    fn all_legs(&self) -> Vec<LiquidationLeg> {
+        let mut legs = vec![self.first_leg()];
+        legs.extend(self.legs.iter().cloned());
+        legs
+    }
+
+    fn liquidation_info(
+        &self,
+        debt_value_base: U256,
+        tokens: &HashMap<Address, TokenConfig>,
+    ) -> LiquidationInfo {
+        let decimals = |asset: &Address| tokens.get(asset).map_or(18, |token| token.decimals);
+        LiquidationInfo {
+            borrower: self.borrower,
+            collateral: self.collateral,
+            debt: self.debt,
+            collateral_symbol: self.collateral_symbol.clone(),
+            debt_symbol: self.debt_symbol.clone(),
+            collateral_decimals: decimals(&self.collateral),
+            debt_decimals: decimals(&self.debt),
+            debt_to_cover: self.debt_to_cover,
+            debt_value_base,
+            expected_profit_eth: self.profit_eth,
+        }
+    }
 }
 
 #[async_trait]
@@ -257,10 +1509,22 @@ impl<M: Middleware + 'static> Strategy<Event, Action> for AaveStrategy<M> {
     async fn sync_state(&mut self) -> Result<()> {
         info!("syncing state");
 
-        self.update_token_configs().await?;
-        self.approve_tokens().await?;
-        self.load_cache()?;
-        self.update_state().await?;
+        match self.role {
+            // the detectors index the market, the executor only builds their liquidations
+            Role::Execute => {
+                self.load_reserves().await?;
+                self.load_min_debt().await?;
+            }
+            _ => self.load_state().await?,
+        }
+        // detectors hold no key
+        if self.role != Role::Detect {
+            self.approve_tokens().await?;
+            if let Err(e) = self.check_wallet().await {
+                error!("Error checking wallet: {}", e);
+            }
+        }
+        self.publish_config();
 
         info!("done syncing state");
         Ok(())
@@ -268,9 +1532,27 @@ impl<M: Middleware + 'static> Strategy<Event, Action> for AaveStrategy<M> {
 
     // Process incoming events, seeing if we can arb new orders, and updating the internal state on new blocks.
     async fn process_event(&mut self, event: Event) -> Vec<Action> {
+        // finish the event before exiting, so the state cache is never left half written
+        let Some(_in_flight) = self.shutdown.start() else {
+            info!("Shutting down, ignoring {:?}", event);
+            return vec![];
+        };
         match event {
             // Event::NewBlock(block) => self.process_new_block_event(block).await,
             Event::NewTick(block) => self.process_new_tick_event(block).await,
+            Event::WatchlistTick(block) => self.process_watchlist_tick_event(block).await,
+            Event::PendingPriceUpdate(update) => {
+                self.process_pending_price_update_event(update).await
+            }
+            Event::BackrunPriceUpdate(update) => {
+                self.process_backrun_price_update_event(update).await
+            }
+            Event::Control(command) => self.process_control_event(command).await,
+            Event::HarvestTick(tick) => self.process_harvest_tick_event(tick).await,
+            Event::WalletCheckTick(tick) => self.process_wallet_check_tick_event(tick).await,
+            Event::ReserveDataUpdate(update) => self.process_reserve_data_update_event(update),
+            Event::LiquidationCall(call) => self.process_liquidation_call_event(call).await,
+            Event::QueuedOpportunity(queued) => self.process_queued_opportunity_event(queued).await,
         }
     }
 }
@@ -285,686 +1567,5195 @@ impl<M: Middleware + 'static> AaveStrategy<M> {
 
     /// Process new block events, updating the internal state.
     async fn process_new_tick_event(&mut self, event: NewTick) -> Vec<Action> {
+        let started = Instant::now();
+        self.tick = TickSummary {
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs(),
+            ..Default::default()
+        };
+
+        let actions = self.scan(event).await;
+
+        self.tick.block_number = self.last_block_number;
+        self.tick.duration_ms = started.elapsed().as_millis() as u64;
+        self.tick.ops_submitted = actions.len() as u64;
+        self.publish_tick_summary();
+        actions
+    }
+
+    // update state, scan all borrowers and liquidate the best underwater one
+    async fn scan(&mut self, event: NewTick) -> Vec<Action> {
         info!("received new tick: {:?}", event);
         if let Err(e) = self.update_state().await {
             error!("Update State error: {}", e);
+            self.tick.errors += 1;
+            return vec![];
+        }
+
+        if let Err(e) = self.update_reserve_flags().await {
+            error!("Error refreshing reserve flags: {}", e);
+            self.tick.errors += 1;
+        }
+        if self.pool_paused {
+            info!("Pool is paused, skipping liquidations");
+            self.record_successful_tick();
             return vec![];
         }
 
         info!("Total borrower count: {}", self.borrowers.len());
-        let op = match self
-            .get_best_liquidation_op()
-            .await
-            .map_err(|e| error!("Error finding liq ops: {}", e))
-            .ok()
-            .flatten()
-        {
-            Some(op) => op,
-            None => {
-                info!("No profitable ops, passing");
+        let underwater = match self.get_underwater_borrowers().await {
+            Ok(underwater) => underwater,
+            Err(e) => {
+                error!("Error finding underwater borrowers: {}", e);
+                self.tick.errors += 1;
                 return vec![];
             }
         };
+        self.tick.underwater_found = underwater.len() as u64;
+        if let Ok(mut status) = self.status.write() {
+            status.underwater = underwater.clone();
+        }
+        self.record_successful_tick();
 
-        info!("Best op: {:?}", op);
+        self.liquidate_best_op(underwater).await
+    }
 
-        if op.profit_eth < I256::from(0) {
-            info!("No profitable ops, passing");
-            return vec![];
+    // the state is up to date and the borrowers scanned, for the liveness watchdog
+    fn record_successful_tick(&self) {
+        if let Ok(mut status) = self.status.write() {
+            status.last_successful_tick_at = self.tick.started_at;
         }
-
-        return vec![Action::SubmitTx(SubmitTxToMempool {
-            tx: match self
-                .build_liquidation(&op)
-                .await
-                .map_err(|e| error!("Error building liquidation: {}", e))
-                .ok()
-            {
-                Some(tx) => tx,
-                None => return vec![],
-            },
-            gas_bid_info: match U256::from_dec_str(&op.profit_eth.to_string()) {
-                Ok(total_profit) => Some(GasBidInfo {
-                    bid_percentage: self.bid_percentage,
-                    total_profit,
-                }),
-                Err(e) => {
-                    error!("Failed to bid: {}", e);
-                    return vec![];
-                }
-            },
-        })];
     }
 
-    // for all known borrowers, return a sorted set of those with health factor < 1
-    async fn get_underwater_borrowers(&mut self) -> Result<Vec<(Address, U256)>> {
-        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+    // log the tick summary as one structured record and expose it to the admin API
+    fn publish_tick_summary(&self) {
+        let tick = &self.tick;
+        info!(
+            block_number = tick.block_number,
+            started_at = tick.started_at,
+            duration_ms = tick.duration_ms,
+            borrowers_scanned = tick.borrowers_scanned,
+            dust_skipped = tick.dust_skipped,
+            bad_debt_found = tick.bad_debt_found,
+            bad_debt_base = %tick.bad_debt_base,
+            underwater_found = tick.underwater_found,
+            ops_evaluated = tick.ops_evaluated,
+            ops_submitted = tick.ops_submitted,
+            errors = tick.errors,
+            reverts = ?tick.reverts,
+            "tick summary"
+        );
+        if let Ok(mut status) = self.status.write() {
+            status.last_tick = Some(tick.clone());
+            // the executor trips the breaker as outcomes come in
+            status.paused = self.submission_paused();
+            status.circuit_breaker = self.breaker.status();
+            status.exposure = self.exposure.status();
+        }
+    }
 
-        let mut underwater_borrowers = Vec::new();
-
-        // call pool.getUserAccountData(user) for each borrower
-        let mut multicall = Multicall::new(
-            self.client.clone(),
-            Some(H160::from_str(
-                "0xcA11bde05977b3631167028862bE2a173976CA11",
-            )?),
-        )
-        .await?;
-        let borrowers: Vec<&Borrower> = self
-            .borrowers
-            .values()
-            .filter(|b| b.debt.len() > 0)
-            .collect();
-        let n = borrowers.len();
-        let mut i = 0;
-        info!("Found {} borrowers with debt", n);
-
-        for chunk in borrowers.chunks(MULTICALL_CHUNK_SIZE) {
-            multicall.clear_calls();
-
-            for borrower in chunk {
-                multicall.add_call(pool.get_user_account_data(borrower.address), false);
+    /// Process watchlist tick events, polling only borrowers close to liquidation
+    /// and those the latest oracle prices pushed past their trigger price.
+    async fn process_watchlist_tick_event(&mut self, event: NewTick) -> Vec<Action> {
+        let triggered = self
+            .get_price_triggered_borrowers()
+            .await
+            .map_err(|e| error!("Error checking price triggers: {}", e))
+            .unwrap_or_default();
+        self.protect_partner_accounts().await;
+        let mut actions = vec![];
+        // borrowers another instance of the shard found close to liquidation
+        if let Some(shared_state) = &self.shared_state {
+            match shared_state.read_watchlist(self.filter.shard).await {
+                Ok(shared) => self.watchlist.extend(shared),
+                Err(e) => error!("Error reading shared watchlist: {}", e),
             }
+        }
+        if self.watchlist.is_empty() && triggered.is_empty() {
+            return actions;
+        }
 
-            let result: Vec<(U256, U256, U256, U256, U256, U256)> = multicall.call_array().await?;
-            for (borrower, (_, _, _, _, _, health_factor)) in zip(chunk, result) {
-                if health_factor.lt(&U256::from_dec_str("1000000000000000000").unwrap()) {
-                    info!(
-                        "Found underwater borrower {:?} -  healthFactor: {}",
-                        borrower, health_factor
-                    );
-                    underwater_borrowers.push((borrower.address, health_factor));
-                }
+        info!(
+            "received watchlist tick: {:?}, watching {} borrowers, {} triggered by price updates",
+            event,
+            self.watchlist.len(),
+            triggered.len()
+        );
+        let watched: Vec<Address> = self.watchlist.union(&triggered).copied().collect();
+        let scan = match self.get_health_factors(&watched).await {
+            Ok(scan) => scan,
+            Err(e) => {
+                error!("Error polling watchlist: {}", e);
+                return actions;
             }
-            info!(
-                "Found {} underwater borrowers, total progress: {}%",
-                underwater_borrowers.len(),
-                100 * (MULTICALL_CHUNK_SIZE * i) / n,
-            );
-            i += 1;
+        };
+        self.watchlist = scan.watchlist;
 
-            // sleep to avoid hitting rate limits
-            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        if !scan.underwater.is_empty() {
+            actions.extend(self.liquidate_best_op(scan.underwater).await);
+        }
+        actions
+    }
 
-            // FIXME:
-            if underwater_borrowers.len() >= 50 {
-                info!("Too many underwater borrowers, stopping search");
-                break;
-            }
+    /// Process pending oracle updates, pre-computing liquidations of the borrowers
+    /// the new price will push under water.
+    async fn process_pending_price_update_event(
+        &mut self,
+        event: PendingPriceUpdate,
+    ) -> Vec<Action> {
+        match self.get_price_update_op(&event).await {
+            Some(op) => info!(
+                "Pre-computed op for oracle update {:?}: {:?}",
+                event.tx_hash, op
+            ),
+            None => info!("No profitable ops for oracle update {:?}", event.tx_hash),
         }
+        vec![]
+    }
 
-        // sort borrowers by health factor
-        underwater_borrowers.sort_by(|a, b| a.1.cmp(&b.1));
-        Ok(underwater_borrowers)
+    /// Process oracle updates seen on MEV-Share, backrunning them with the best liquidation.
+    async fn process_backrun_price_update_event(
+        &mut self,
+        event: PendingPriceUpdate,
+    ) -> Vec<Action> {
+        let Some(op) = self.get_price_update_op(&event).await else {
+            info!("No profitable ops for oracle update {:?}", event.tx_hash);
+            return vec![];
+        };
+        let (borrower, profit_eth) = (op.borrower, op.profit_eth);
+        let actions = self.submit_backrun(op, event.tx_hash).await;
+        if !actions.is_empty() {
+            self.record_attempt(borrower, profit_eth);
+        }
+        actions
     }
 
-    // load borrower state cache from file if exists
-    fn load_cache(&mut self) -> Result<()> {
-        match File::open(STATE_CACHE_FILE) {
-            Ok(file) => {
-                let cache: StateCache = match serde_json::from_reader(file) {
-                    Ok(cache) => cache,
-                    Err(e) => {
-                        error!("Failed to parse state cache: {}", e);
-                        return Err(anyhow!("Failed to parse state cache: {}", e));
-                    }
-                };
-                info!("read state cache from file");
-                self.last_block_number = cache.last_block_number;
-                self.borrowers = cache.borrowers;
+    #[instrument(skip_all, fields(correlation_id = %op.correlation_id))]
+    async fn submit_backrun(&self, op: LiquidationOpportunity, target_tx: H256) -> Vec<Action> {
+        info!("Backrunning oracle update {:?} with {:?}", target_tx, op);
+        self.publish_opportunity(&op);
+
+        if op.profit_eth <= I256::from(0) || self.below_min_profit(&op) {
+            return self.pass_opportunity(&op, "No profitable ops");
+        }
+        if self.submission_paused() {
+            return self.pass_opportunity(&op, "Submission paused");
+        }
+        if self.sequencer_down {
+            return self.pass_opportunity(&op, "Sequencer is down");
+        }
+        if !self.prices_agree(&op).await {
+            return self.pass_opportunity(&op, "Oracle prices off");
+        }
+        if let Some(reason) = self.held_for_approval(&op) {
+            return self.pass_opportunity(&op, reason);
+        }
+        if self.over_exposure_cap(&op) {
+            return self.pass_opportunity(&op, "Exposure cap reached");
+        }
+        if let Err(e) = self.approve_liquidation(&op).await {
+            error!("Error approving backrun: {}", e);
+            return self.pass_opportunity(&op, "Approval failed");
+        }
+
+        match self.build_liquidation(&op).await {
+            Ok(tx) => {
+                self.report_opportunity(&op, Verdict::Submitted, None);
+                let liquidation = op.liquidation_info(self.debt_value_base(&op), &self.tokens);
+                vec![Action::SubmitBundle(SubmitBackrunBundle {
+                    correlation_id: op.correlation_id,
+                    target_tx,
+                    tx,
+                    liquidation: Some(liquidation),
+                })]
             }
-            Err(_) => {
-                info!("no state cache file found, creating new one");
-                self.last_block_number = self.config.creation_block;
+            Err(e) => {
+                error!("Error building backrun: {}", e);
+                self.pass_opportunity(&op, "Building the liquidation failed")
             }
-        };
-
-        Ok(())
+        }
     }
 
-    // update known borrower state from last block to latest block
-    async fn update_state(&mut self) -> Result<()> {
-        let latest_block = self.client.get_block_number().await?;
+    // find the best liquidation once the update lands, and watch the borrowers it triggers
+    async fn get_price_update_op(
+        &mut self,
+        event: &PendingPriceUpdate,
+    ) -> Option<LiquidationOpportunity> {
+        let triggered: HashSet<Address> = event
+            .assets
+            .iter()
+            .flat_map(|asset| self.risk_engine.on_price_update(asset, event.price))
+            .collect();
+        if triggered.is_empty() {
+            return None;
+        }
         info!(
-            "Updating state from block {} to {}",
-            self.last_block_number, latest_block
+            "Pending price update {:?} triggers {} borrowers",
+            event,
+            triggered.len()
         );
 
-        self.get_borrow_logs(self.last_block_number.into(), latest_block)
-            .await?
-            .into_iter()
-            .for_each(|log| {
-                let user = log.on_behalf_of;
-                // fetch assets if user already a borrower
-                if self.borrowers.contains_key(&user) {
-                    let borrower = self.borrowers.get_mut(&user).unwrap();
-                    borrower.debt.insert(log.reserve);
-                    return;
-                } else {
-                    self.borrowers.insert(
-                        user,
-                        Borrower {
-                            address: user,
-                            collateral: HashSet::new(),
-                            debt: HashSet::from([log.reserve]),
-                        },
-                    );
-                }
-            });
+        let mut pool_state = match self.get_pool_state().await {
+            Ok(pool_state) => pool_state,
+            Err(e) => {
+                error!("Error getting pool state: {}", e);
+                return None;
+            }
+        };
+        for asset in event.assets.iter() {
+            pool_state.prices.insert(*asset, event.price);
+        }
+        pool_state.pending = true;
 
-        self.get_supply_logs(self.last_block_number.into(), latest_block)
-            .await?
-            .into_iter()
-            .for_each(|log| {
-                let user = log.on_behalf_of;
-                // fetch assets if user already a supplier
-                if self.borrowers.contains_key(&user) {
-                    let borrower = self.borrowers.get_mut(&user).unwrap();
-                    borrower.collateral.insert(log.reserve);
-                    return;
-                } else {
-                    self.borrowers.insert(
-                        user,
-                        Borrower {
-                            address: user,
-                            collateral: HashSet::from([log.reserve]),
-                            debt: HashSet::new(),
-                        },
-                    );
-                }
-            });
+        // the pool still sees the old price, so assume the default close factor
+        let health_factor = U256::from(LIQUIDATION_CLOSE_FACTOR_THRESHOLD) + 1;
+        let candidates = triggered
+            .iter()
+            .map(|borrower| (*borrower, health_factor))
+            .collect();
+        let op = self
+            .get_best_liquidation_op(candidates, &pool_state)
+            .await
+            .map_err(|e| error!("Error pre-computing liq ops: {}", e))
+            .ok()
+            .flatten();
 
-        // write state cache to file
-        let cache = StateCache {
-            last_block_number: latest_block.as_u64(),
-            borrowers: self.borrowers.clone(),
+        // poll them on the next watchlist tick, once the update has landed
+        self.watchlist.extend(triggered);
+        op
+    }
+
+    /// Process wallet check tick events, making sure the sender can keep liquidating.
+    async fn process_wallet_check_tick_event(&mut self, event: NewTick) -> Vec<Action> {
+        info!("received wallet check tick: {:?}", event);
+        if let Err(e) = self.check_wallet().await {
+            error!("Error checking wallet: {}", e);
+        }
+        self.publish_config();
+        vec![]
+    }
+
+    // check the sender can pay for gas, owns the Liquidator contract and that the pool
+    // can pull repayments, warning of whatever it can't
+    async fn check_wallet(&mut self) -> Result<()> {
+        let sender = self
+            .client
+            .default_sender()
+            .ok_or(anyhow!("No connected sender"))?;
+        let gas_balance = self.client.get_balance(sender, None).await?;
+        // the pool pulls repayments from whoever calls it
+        let (liquidator_owner, payer) = if self.use_aave_liquidator {
+            (None, sender)
+        } else {
+            let liquidator = Liquidator::new(self.liquidator, self.client.clone());
+            (Some(liquidator.owner().call().await?), self.liquidator)
         };
-        self.last_block_number = latest_block.as_u64();
-        let mut file = File::create(STATE_CACHE_FILE)?;
-        file.write_all(serde_json::to_string(&cache)?.as_bytes())?;
+        // capped approvals are sent with each liquidation instead
+        let mut missing_allowances = if self.capped_approvals() {
+            Vec::new()
+        } else {
+            self.unapproved_tokens(payer, self.approved_tokens())
+                .await?
+        };
+        missing_allowances.sort();
 
+        let wallet = WalletHealth {
+            checked_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs(),
+            sender,
+            gas_balance,
+            liquidator_owner,
+            missing_allowances,
+            problems: Vec::new(),
+        }
+        .diagnose(self.min_gas_balance);
+        for problem in wallet.problems.iter() {
+            warn!("Wallet check: {}", problem);
+        }
+        if wallet.is_healthy() && !self.wallet.is_healthy() {
+            info!("Wallet check passed");
+        }
+        self.wallet = wallet;
         Ok(())
     }
 
-    // fetch all borrow events from the from_block to to_block
-    async fn get_borrow_logs(&self, from_block: U64, to_block: U64) -> Result<Vec<BorrowFilter>> {
-        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+    // paused by an operator, by a wallet that can't keep liquidating, or by the circuit breaker
+    fn submission_paused(&self) -> bool {
+        self.paused
+            || (self.pause_on_wallet_problems && !self.wallet.is_healthy())
+            || self.breaker.is_tripped()
+            || self.kill_switch.is_engaged()
+            || self.sync_lagging()
+    }
 
-        let mut res = Vec::new();
-        for start_block in
-            (from_block.as_u64()..to_block.as_u64()).step_by(LOG_BLOCK_RANGE as usize)
-        {
-            let end_block = std::cmp::min(start_block + LOG_BLOCK_RANGE - 1, to_block.as_u64());
-            pool.borrow_filter()
-                .from_block(start_block)
-                .to_block(end_block)
-                .address(ValueOrArray::Value(self.config.pool_address))
-                .query()
-                .await?
-                .into_iter()
-                .for_each(|log| {
-                    res.push(log);
-                });
+    // not yet indexed within `max_sync_lag` blocks of the head, executors index nothing
+    // and leave it to the detectors
+    fn sync_lagging(&self) -> bool {
+        self.max_sync_lag.is_some() && !self.synced && self.role != Role::Execute
+    }
+
+    fn publish_sync_progress(&self, progress: &SyncProgress) {
+        if let Ok(mut status) = self.status.write() {
+            status.sync = progress.clone();
         }
+    }
 
-        Ok(res)
+    // trip the breaker if the oracle's prices stray too far from the reference oracle's
+    async fn check_reference_prices(&self, prices: &HashMap<Address, U256>) -> Result<()> {
+        let Some(reference_oracle) = self.reference_oracle else {
+            return Ok(());
+        };
+        let reference_prices = self.get_prices_from(reference_oracle).await?;
+        for (asset, price) in prices {
+            if let Some(reference) = reference_prices.get(asset) {
+                self.breaker.check_price(*asset, *price, *reference);
+            }
+        }
+        Ok(())
     }
 
-    // fetch all borrow events from the from_block to to_block
-    async fn get_supply_logs(&self, from_block: U64, to_block: U64) -> Result<Vec<SupplyFilter>> {
-        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+    /// Process harvest tick events, collecting and converting liquidation proceeds.
+    async fn process_harvest_tick_event(&mut self, event: NewTick) -> Vec<Action> {
+        let Some(harvest) = self.harvest.clone() else {
+            return vec![];
+        };
+        if self.submission_paused() {
+            info!("Submission paused, not harvesting");
+            return vec![];
+        }
 
-        let mut res = Vec::new();
-        for start_block in
-            (from_block.as_u64()..to_block.as_u64()).step_by(LOG_BLOCK_RANGE as usize)
-        {
-            let end_block = std::cmp::min(start_block + LOG_BLOCK_RANGE - 1, to_block.as_u64());
-            pool.supply_filter()
-                .from_block(start_block)
-                .to_block(end_block)
-                .address(ValueOrArray::Value(self.config.pool_address))
-                .query()
-                .await?
+        info!("received harvest tick: {:?}", event);
+        match self.build_harvest_txs(&harvest).await {
+            Ok(txs) => txs
                 .into_iter()
-                .for_each(|log| {
-                    res.push(log);
-                });
+                .map(|tx| {
+                    Action::SubmitTx(SubmitTx {
+                        correlation_id: new_correlation_id(),
+                        submission: SubmitTxToMempool {
+                            tx,
+                            gas_bid_info: None,
+                        },
+                        liquidation: None,
+                        private: false,
+                        valued_at: None,
+                    })
+                })
+                .collect(),
+            Err(e) => {
+                error!("Error harvesting: {}", e);
+                vec![]
+            }
         }
-
-        Ok(res)
     }
 
-    async fn approve_tokens(&mut self) -> Result<()> {
-        let liquidator = Liquidator::new(self.liquidator, self.client.clone());
-
+    // recover what the liquidator contract holds, swap non-core assets held by the sender
+    // into the target and sweep the target above the threshold. Each step works on current
+    // balances, so proceeds move one step further on every harvest tick.
+    async fn build_harvest_txs(&self, harvest: &HarvestConfig) -> Result<Vec<TypedTransaction>> {
         let sender = self
             .client
             .default_sender()
             .ok_or(anyhow!("No connected sender"))?;
-        let mut nonce = self.client.get_transaction_count(sender, None).await?;
-        for token_address in self.tokens.keys() {
-            let token = IERC20::new(token_address.clone(), self.client.clone());
-            match self.use_aave_liquidator {
-                true => {
-                    match token
-                        .allowance(sender, self.config.pool_address)
-                        .call()
-                        .await
-                    {
-                        Ok(allowance) => {
-                            if allowance == U256::zero() {
-                                match token
-                                    .approve(self.config.pool_address, U256::MAX)
-                                    .nonce(nonce)
-                                    .send()
-                                    .await
-                                {
-                                    Ok(_) => nonce = nonce + 1,
-                                    Err(e) => {
-                                        error!("approve failed: {:?}", e);
-                                        return Err(anyhow!("approve failed: {:?}", e));
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("allowance check failed: {:?}", e);
-                            return Err(anyhow!("allowance check failed: {:?}", e));
-                        }
-                    }
+        let target = harvest.target.unwrap_or(self.config.weth_address);
+        let target_decimals = self
+            .tokens
+            .get(&target)
+            .ok_or(anyhow!("Harvest target {:?} is not a reserve", target))?
+            .decimals;
+        let liquidator = Liquidator::new(self.liquidator, self.client.clone());
+        let pool = Pool::new(self.config.pool_address, self.client.clone());
+        let pool_state = self.get_pool_state().await?;
+
+        let mut txs = vec![];
+        for (address, token) in self.tokens.iter() {
+            let erc20 = IERC20::new(*address, self.client.clone());
+            if !self.use_aave_liquidator {
+                let held = erc20.balance_of(self.liquidator).call().await?;
+                if !held.is_zero() {
+                    info!("Recovering {} {} from the liquidator", held, token.symbol);
+                    txs.push(liquidator.recover(*address, held).tx);
                 }
-                false => {
-                    match token
-                        .allowance(self.liquidator, self.config.pool_address)
-                        .call()
-                        .await
-                    {
-                        Ok(allowance) => {
-                            if allowance == U256::zero() {
-                                match liquidator
-                                    .approve_pool(*token_address)
-                                    .nonce(nonce)
-                                    .send()
-                                    .await
-                                {
-                                    Ok(_) => nonce = nonce + 1,
-                                    Err(e) => {
-                                        error!("approve failed: {:?}", e);
-                                        return Err(anyhow!("approve failed: {:?}", e));
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("allowance check failed: {:?}", e);
-                            return Err(anyhow!("allowance check failed: {:?}", e));
-                        }
-                    }
+            }
+
+            // the Aave path repays debt out of the sender's balance
+            if harvest.keeps(
+                address,
+                self.use_aave_liquidator && self.filter.allows_debt(address),
+            ) {
+                continue;
+            }
+            // collateral seized as aTokens is withdrawn as far as the reserve's liquidity
+            // allows, and swapped on a later tick
+            if self.receive_a_token.contains(address) {
+                let a_balance = IERC20::new(token.a_address, self.client.clone())
+                    .balance_of(sender)
+                    .call()
+                    .await?;
+                let liquidity = erc20.balance_of(token.a_address).call().await?;
+                if let Some(amount) = withdraw_amount(a_balance, liquidity) {
+                    info!(
+                        "Withdrawing {} of {} a{} held",
+                        amount.min(a_balance),
+                        a_balance,
+                        token.symbol
+                    );
+                    txs.push(pool.withdraw(*address, amount, sender).tx);
                 }
             }
-        }
 
-        Ok(())
-    }
+            if *address == target {
+                continue;
+            }
+            let balance = erc20.balance_of(sender).call().await?;
+            let price = self.get_asset_price_eth(address, &pool_state).await?;
+            if balance.is_zero() || value_wei(balance, price, token.decimals) < harvest.min_swap_wei
+            {
+                continue;
+            }
+            let quote = self
+                .swap_router
+                .best_quote(SwapRequest {
+                    src_token: *address,
+                    src_decimals: token.decimals,
+                    dest_token: target,
+                    dest_decimals: target_decimals,
+                    amount: balance,
+                    from: sender,
+                })
+                .await?;
+            // the swap can't be estimated until the approval lands, so it waits for the next tick
+            let allowance = erc20.allowance(sender, quote.spender).call().await?;
+            if allowance < balance {
+                info!(
+                    "Approving {} for swapping on {}",
+                    token.symbol, quote.aggregator
+                );
+                txs.push(erc20.approve(quote.spender, U256::MAX).tx);
+            } else {
+                info!(
+                    "Swapping {} {} into at least {} of {:?} on {}",
+                    balance, token.symbol, quote.min_amount_out, target, quote.aggregator
+                );
+                txs.push(
+                    TransactionRequest::new()
+                        .from(sender)
+                        .to(quote.to)
+                        .data(quote.data)
+                        .into(),
+                );
+            }
+        }
 
-    async fn liquidation_call(&mut self, op: &LiquidationOpportunity) -> Result<()> {
-        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        if let Some(cold_wallet) = harvest.cold_wallet {
+            let erc20 = IERC20::new(target, self.client.clone());
+            let balance = erc20.balance_of(sender).call().await?;
+            if let Some(amount) = sweep_amount(balance, harvest.sweep_threshold) {
+                info!("Sweeping {} of {:?} to {:?}", amount, target, cold_wallet);
+                txs.push(erc20.transfer(cold_wallet, amount).tx);
+            }
+        }
 
-        let sender = self
-            .client
-            .default_sender()
-            .ok_or(anyhow!("No connected sender"))?;
-        let nonce = self.client.get_transaction_count(sender, None).await?;
-        if self.use_aave_liquidator {
-            pool.liquidation_call(op.collateral, op.debt, op.borrower, op.debt_to_cover, false)
-                .nonce(nonce)
-                .send()
-                .await?
-                .await?;
-        } else {
-            // TODO remove unwrap once we figure out whats broken
+        for tx in txs.iter_mut() {
+            tx.set_chain_id(self.chain_id);
         }
+        Ok(txs)
+    }
 
-        Ok(())
+    /// Process reserve data updates, moving the positions the risk engine tracks in the
+    /// reserve to its new rates.
+    fn process_reserve_data_update_event(&mut self, update: ReserveDataUpdate) -> Vec<Action> {
+        self.risk_engine.update_reserve(
+            update.reserve,
+            update.timestamp,
+            update.liquidity_rate,
+            update.variable_borrow_rate,
+            update.liquidity_index,
+            update.variable_borrow_index,
+        );
+        self.reserve_data.insert(update.reserve, update);
+        vec![]
     }
 
-    async fn update_token_configs(&mut self) -> Result<()> {
-        let pool_data =
-            IPoolDataProvider::<M>::new(self.config.pool_data_provider, self.client.clone());
-        let all_tokens = pool_data.get_all_reserves_tokens().await?;
-        let all_a_tokens = pool_data.get_all_a_tokens().await?;
-        info!("all_tokens: {:?}", all_tokens);
-        for (token, a_token) in zip(all_tokens, all_a_tokens) {
-            match pool_data
-                .get_reserve_configuration_data(token.token_address)
-                .await
-            {
-                Ok((decimals, ltv, threshold, bonus, reserve, _, _, _, _, _)) => {
-                    match pool_data
-                        .get_liquidation_protocol_fee(token.token_address)
-                        .await
-                    {
-                        Ok(protocol_fee) => {
-                            self.tokens.insert(
-                                token.token_address,
-                                TokenConfig {
-                                    address: token.token_address,
-                                    a_address: a_token.token_address,
-                                    decimals: decimals.low_u64(),
-                                    ltv: ltv.low_u64(),
-                                    liquidation_threshold: threshold.low_u64(),
-                                    liquidation_bonus: bonus.low_u64(),
-                                    reserve_factor: reserve.low_u64(),
-                                    protocol_fee: protocol_fee.low_u64(),
-                                    symbol: token.symbol,
-                                },
-                            );
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to get liquidation protocol fee for token {}: {}",
-                                token.token_address, e
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!(
-                        "Failed to get reserve configuration data for token {}: {}",
-                        token.token_address, e
-                    );
-                }
+    /// Process liquidations landing on the pool, attributing them to us or a competitor
+    /// and bidding more as we lose races.
+    async fn process_liquidation_call_event(&mut self, call: LiquidationCall) -> Vec<Action> {
+        let tx = match self.client.get_transaction(call.tx_hash).await {
+            Ok(Some(tx)) => tx,
+            Ok(None) => {
+                warn!("Liquidation tx {:?} not found", call.tx_hash);
+                return vec![];
             }
-        }
+            Err(e) => {
+                error!("Error getting liquidation tx {:?}: {}", call.tx_hash, e);
+                return vec![];
+            }
+        };
+        let gas_price = tx.gas_price.unwrap_or_default();
+        let liquidation = ObservedLiquidation {
+            tx_hash: call.tx_hash,
+            block_number: call.block_number,
+            timestamp: call.timestamp,
+            liquidator: call.liquidator,
+            sender: tx.from,
+            borrower: call.borrower,
+            gas_price,
+            priority_fee: tx.max_priority_fee_per_gas.unwrap_or(gas_price),
+        };
 
-        Ok(())
-    }
+        let Ok(mut status) = self.status.write() else {
+            return vec![];
+        };
+        // the last opportunity we published on the borrower, if it's recent enough to race for
+        let opportunity = status
+            .opportunities
+            .iter()
+            .find(|op| {
+                op.borrower == call.borrower && op.timestamp + MATCH_WINDOW_SECS > call.timestamp
+            })
+            .map(|op| op.correlation_id.clone());
+        let attribution = self.competitors.record(&liquidation, opportunity);
+        status.competitors = self.competitors.report();
+        drop(status);
 
-    // 8 decimals of precision
-    async fn get_asset_price_eth(&self, asset: &Address, pool_state: &PoolState) -> Result<U256> {
-        // 1:1 for weth
-        let weth_address = self.config.weth_address;
-        if asset.eq(&weth_address) {
-            return Ok(U256::from(PRICE_ONE));
+        if let Some(sniped) = attribution.sniped {
+            info!(
+                "Borrower {:?} sniped by {:?} in {:?}, submitted: {}",
+                sniped.borrower, sniped.competitor, sniped.tx_hash, sniped.submitted
+            );
         }
+        if let Some(race) = attribution.race {
+            if race.won {
+                self.bid_model.record_win(
+                    race.profit_eth,
+                    race.gas_price,
+                    race.competitor_gas_price,
+                );
+            } else {
+                self.bid_model.record_loss(race.profit_eth);
+            }
+            info!(
+                "Race for {:?} won: {}, bidding {}% on similar profits",
+                call.borrower,
+                race.won,
+                self.bid_model.bid_percentage(race.profit_eth)
+            );
+            if let Err(e) = self.bid_model.write(&self.bid_model_file) {
+                error!("Error writing bid model: {}", e);
+            }
+        }
+        vec![]
+    }
 
-        // usd / token
-        let usd_price = pool_state
-            .prices
-            .get(asset)
-            .ok_or(anyhow!("No price found for asset {:?}", asset))?;
-        let asset_symbol = self
-            .tokens
-            .get(asset)
-            .map(|t| t.symbol.clone())
-            .unwrap_or_else(|| "Unknown".to_string());
-        info!("{}/USD: {}, asset: {:?}", asset_symbol, usd_price, asset);
-        // usd / eth
-        let usd_price_eth = pool_state
-            .prices
-            .get(&weth_address)
-            .ok_or(anyhow!("No price found for asset {}", weth_address))?;
-        info!("WETH/USD: {}, asset: {:?}", usd_price_eth, weth_address);
-        // usd / token * eth / usd = eth / token
-        let x = usd_price * U256::from(PRICE_ONE) / usd_price_eth;
-        info!("{}/ETH: {}", asset_symbol, x);
-        Ok(x)
+    /// Process operator commands.
+    async fn process_control_event(&mut self, command: ControlCommand) -> Vec<Action> {
+        match command {
+            ControlCommand::Pause => {
+                info!("Pausing submission");
+                self.paused = true;
+                self.publish_config();
+                vec![]
+            }
+            ControlCommand::Resume => {
+                info!("Resuming submission");
+                self.paused = false;
+                self.breaker.reset();
+                self.publish_config();
+                vec![]
+            }
+            ControlCommand::Resync => {
+                info!("Resyncing state");
+                if let Err(e) = self.resync().await {
+                    error!("Error resyncing state: {}", e);
+                }
+                vec![]
+            }
+            ControlCommand::SetMinProfit { min_profit_eth } => {
+                info!("Setting min profit to {} wei", min_profit_eth);
+                self.min_profit_eth = min_profit_eth;
+                self.publish_config();
+                vec![]
+            }
+            ControlCommand::SetTunables { tunables } => {
+                info!("Applying tunables: {:?}", tunables);
+                self.apply_tunables(&tunables);
+                self.publish_config();
+                vec![]
+            }
+            ControlCommand::SetKillSwitch { engaged } => {
+                if engaged {
+                    warn!("Kill switch engaged, halting submission");
+                } else {
+                    info!("Kill switch released");
+                }
+                self.kill_switch.set_engaged(engaged);
+                self.publish_config();
+                vec![]
+            }
+            ControlCommand::Approve { borrower } => {
+                self.decide_approval(borrower, true);
+                vec![]
+            }
+            ControlCommand::Reject { borrower } => {
+                self.decide_approval(borrower, false);
+                vec![]
+            }
+            ControlCommand::Liquidate {
+                borrower,
+                collateral,
+                debt,
+                debt_to_cover,
+            } => {
+                if self.submission_paused() {
+                    warn!("Submission paused, not liquidating {:?}", borrower);
+                    return vec![];
+                }
+                match self
+                    .build_manual_liquidation(borrower, collateral, debt, debt_to_cover)
+                    .await
+                {
+                    Ok(tx) => vec![Action::SubmitTx(tx)],
+                    Err(e) => {
+                        error!("Error building manual liquidation of {:?}: {}", borrower, e);
+                        vec![]
+                    }
+                }
+            }
+        }
     }
 
-    async fn get_best_liquidation_op(&mut self) -> Result<Option<LiquidationOpportunity>> {
-        let underwater = self.get_underwater_borrowers().await?;
+    // refresh reserve configs and partner accounts, and reconcile every known borrower
+    async fn resync(&mut self) -> Result<()> {
+        self.update_token_configs().await?;
+        self.load_partner_accounts()?;
+        let borrowers: Vec<Address> = self.borrowers.keys().copied().collect();
+        self.reconcile_borrowers(&borrowers).await?;
+        self.update_state().await
+    }
 
-        if underwater.len() == 0 {
-            return Err(anyhow!("No underwater borrowers found"));
+    // whether the secondary price sources agree with the rate the opportunity was valued at
+    async fn prices_agree(&self, op: &LiquidationOpportunity) -> bool {
+        let Some(price_checker) = &self.price_checker else {
+            return true;
+        };
+        let collateral = OraclePrice {
+            asset: op.collateral,
+            symbol: &op.collateral_symbol,
+            price: op.collateral_price,
+        };
+        let debt = OraclePrice {
+            asset: op.debt,
+            symbol: &op.debt_symbol,
+            price: op.debt_price,
+        };
+        match price_checker.check(collateral, debt).await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Price cross-check failed: {}", e);
+                false
+            }
         }
+    }
 
-        info!("Found {} underwater borrowers", underwater.len());
-        let pool_data =
-            IPoolDataProvider::<M>::new(self.config.pool_data_provider, self.client.clone());
+    // tunables left unset keep their current value
+    fn apply_tunables(&mut self, tunables: &Tunables) {
+        self.bid_model
+            .set_bounds(tunables.bid_bounds(self.bid_model.bounds()));
+        if let Some(min_profit_eth) = tunables.min_profit_eth {
+            self.min_profit_eth = min_profit_eth;
+        }
+        if let Some(overrides) = &tunables.asset_overrides {
+            self.asset_overrides = overrides.clone();
+        }
+        tunables.apply_filter(&mut self.filter);
+        let intervals = [
+            (&self.scan_interval, tunables.poll_interval_secs),
+            (
+                &self.watchlist_interval,
+                tunables.watchlist_poll_interval_secs,
+            ),
+        ];
+        for (interval, secs) in intervals {
+            if let (Some(interval), Some(secs)) = (interval, secs) {
+                interval.set(secs);
+            }
+        }
+    }
 
-        let mut best_bonus: I256 = I256::MIN;
-        let mut best_op: Option<LiquidationOpportunity> = None;
-        let pool_state = self.get_pool_state().await?;
+    fn asset_override(&self, op: &LiquidationOpportunity) -> AssetOverride {
+        asset_override(&self.asset_overrides, op.collateral, op.debt)
+    }
 
-        for (borrower, health_factor) in underwater {
-            if let Some(op) = self
-                .get_liquidation_opportunity(
-                    self.borrowers
-                        .get(&borrower)
-                        .ok_or(anyhow!("Borrower not found"))?,
-                    &pool_data,
-                    &health_factor,
-                    &pool_state,
-                )
-                .await
-                .map_err(|e| info!("Liquidation op failed {}", e))
-                .ok()
-            {
-                if op.profit_eth > best_bonus {
-                    best_bonus = op.profit_eth;
-                    best_op = Some(op);
-                }
+    fn below_min_profit(&self, op: &LiquidationOpportunity) -> bool {
+        let min_profit_eth = self
+            .asset_override(op)
+            .min_profit_eth
+            .unwrap_or(self.min_profit_eth);
+        if min_profit_eth.is_zero() {
+            return op.profit_eth < I256::zero();
+        }
+        match self.profit(op) {
+            Some(profit) => profit < I256::try_from(min_profit_eth).unwrap_or(I256::MAX),
+            // a profit that can't be converted can't be held to the threshold
+            None => {
+                error!(
+                    "No {} price to check the min profit against, passing",
+                    self.profit_currency
+                );
+                true
             }
         }
+    }
 
-        Ok(best_op)
+    // the rate converting profits into the profit currency at oracle `prices`
+    fn profit_rate(&self, prices: &HashMap<Address, U256>) -> Option<ProfitRate> {
+        self.profit_currency
+            .rate(prices, self.config.weth_address, |asset| {
+                self.tokens.get(asset).map(|token| token.decimals)
+            })
     }
 
-    async fn get_pool_state(&self) -> Result<PoolState> {
-        let mut multicall = Multicall::<M>::new(
-            self.client.clone(),
-            Some(H160::from_str(
-                "0xcA11bde05977b3631167028862bE2a173976CA11",
-            )?),
-        )
-        .await?;
-        let mut prices = HashMap::new();
-        let price_oracle = IAaveOracle::<M>::new(self.config.oracle_address, self.client.clone());
+    // profit of `op` in units of the profit currency
+    fn profit(&self, op: &LiquidationOpportunity) -> Option<I256> {
+        op.profit_rate.and_then(|rate| rate.convert(op.profit_eth))
+    }
 
-        for token_address in self.tokens.keys() {
-            multicall.add_call(price_oracle.get_asset_price(*token_address), false);
+    // expose the runtime config and pause state to the admin API
+    fn publish_config(&self) {
+        let Ok(mut status) = self.status.write() else {
+            return;
+        };
+        status.paused = self.submission_paused();
+        status.wallet = self.wallet.clone();
+        status.circuit_breaker = self.breaker.status();
+        status.exposure = self.exposure.status();
+        status.config = serde_json::json!({
+            "chain_id": self.chain_id,
+            "pool_address": self.config.pool_address,
+            "liquidator": self.liquidator,
+            "use_aave_liquidator": self.use_aave_liquidator,
+            "max_liquidation_legs": self.max_liquidation_legs,
+            "multi_hop_swaps": self.multi_hop_swaps,
+            "aggregator_flash_swaps": self.aggregator_flash_swaps,
+            "flash_liquidity_pools": self
+                .flash_liquidity_pools
+                .iter()
+                .map(|pool| pool.to_string())
+                .collect::<Vec<_>>(),
+            "role": self.role,
+            "shard": self.filter.shard.to_string(),
+            "shared_state_instance": self.shared_state.as_ref().map(SharedState::instance),
+            "multicall_address": self.multicall.address(),
+            "log_block_range": self.log_range.range(),
+            "approval_buffer_bps": self.approval_buffer_bps,
+            "paused": self.paused,
+            "min_gas_balance": self.min_gas_balance,
+            "pause_on_wallet_problems": self.pause_on_wallet_problems,
+            "max_sync_lag": self.max_sync_lag,
+            "price_sources": self.price_checker.as_ref().map(|checker| checker.sources()),
+            "max_price_source_deviation_bps": self
+                .price_checker
+                .as_ref()
+                .map(|checker| checker.max_deviation_bps()),
+            "reference_oracle": self.reference_oracle,
+            "max_consecutive_reverts": self.breaker.limits().max_consecutive_reverts,
+            "max_consecutive_losses": self.breaker.limits().max_consecutive_losses,
+            "max_price_deviation_bps": self.breaker.limits().max_price_deviation_bps,
+            "breaker_cooldown_secs": self.breaker.limits().cooldown_secs,
+            "min_profit_eth": self.min_profit_eth,
+            "asset_overrides": self.asset_overrides,
+            "profit_currency": self.profit_currency,
+            "poll_interval_secs": self.scan_interval.as_ref().map(PollInterval::secs),
+            "watchlist_poll_interval_secs": self
+                .watchlist_interval
+                .as_ref()
+                .map(PollInterval::secs),
+            "min_debt_usd": self.min_debt_usd,
+            "approval_notional_usd": self.approval.as_ref().map(|approval| {
+                approval
+                    .lock()
+                    .expect("approval gate poisoned")
+                    .config()
+                    .notional_usd
+            }),
+            "max_exposure_usd": self.exposure.max_usd(),
+            "min_confidence_bps": self.submission_policy.min_confidence_bps,
+            "public_confidence_bps": self.submission_policy.public_confidence_bps,
+            "bid_percentage": self.bid_model.bounds().base,
+            "min_bid_percentage": self.bid_model.bounds().min,
+            "max_bid_percentage": self.bid_model.bounds().max,
+            "watchlist_health_factor_bps": self.watchlist_health_factor_bps,
+            "risk_health_factor_bps": self.risk_health_factor_bps,
+            "fee_rebate_bps": self.fee_rebate_bps,
+            "l1_fee": format!("{:?}", self.l1_fee),
+            "repay_adapter": self.repay_adapter,
+            "partner_accounts": self.partner_accounts.len(),
+            "smart_account_reconcile_ticks": self.smart_account_reconcile_ticks,
+            "eoa_reconcile_ticks": self.eoa_reconcile_ticks,
+            "cap_utilization_bps": self
+                .tokens
+                .values()
+                .filter(|token| !token.supply_cap.is_zero() || !token.borrow_cap.is_zero())
+                .map(|token| {
+                    (
+                        token.symbol.clone(),
+                        serde_json::json!({
+                            "supply": token.supply_cap_utilization_bps,
+                            "borrow": token.borrow_cap_utilization_bps,
+                        }),
+                    )
+                })
+                .collect::<serde_json::Map<_, _>>(),
+        });
+    }
+
+    fn opportunity_status(&self, op: &LiquidationOpportunity) -> OpportunityStatus {
+        OpportunityStatus {
+            correlation_id: op.correlation_id.clone(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs(),
+            borrower: op.borrower,
+            collateral: op.collateral,
+            debt: op.debt,
+            debt_to_cover: op.debt_to_cover,
+            profit_eth: op.profit_eth.to_string(),
+            profit: self.profit(op).map(|profit| profit.to_string()),
+            confidence: op.confidence,
         }
+    }
 
-        let result: Vec<U256> = multicall.call_array().await?;
-        for (token_address, price) in zip(self.tokens.keys(), result) {
-            prices.insert(*token_address, price);
+    fn publish_opportunity(&self, op: &LiquidationOpportunity) {
+        let opportunity = self.opportunity_status(op);
+        if let Some(feed) = &self.opportunity_feed {
+            feed.publish(opportunity.clone());
         }
-        multicall.clear_calls();
+        if let Ok(mut status) = self.status.write() {
+            status.push_opportunity(opportunity);
+        }
+    }
 
-        Ok(PoolState { prices })
+    // hold a liquidation over the approval notional until an operator approves it, with
+    // why it's passed meanwhile
+    fn held_for_approval(&self, op: &LiquidationOpportunity) -> Option<&'static str> {
+        let approval = self.approval.as_ref()?;
+        let notional_base = self.debt_value_base(op);
+        let mut gate = approval.lock().expect("approval gate poisoned");
+        if !gate.needs_approval(notional_base) {
+            return None;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        let request = PendingApproval {
+            correlation_id: op.correlation_id.clone(),
+            borrower: op.borrower,
+            collateral: op.collateral,
+            debt: op.debt,
+            debt_to_cover: op.debt_to_cover,
+            notional_base,
+            profit_eth: op.profit_eth.to_string(),
+            since: now,
+        };
+        let approval = gate.check(request, now);
+        self.publish_pending_approvals(&gate);
+        match approval {
+            Approval::Approved => {
+                info!("Liquidation of {:?} approved", op.borrower);
+                None
+            }
+            Approval::Rejected => Some("Rejected by the operator"),
+            Approval::Pending => Some("Waiting for approval"),
+        }
     }
 
-    async fn get_liquidation_opportunity(
-        &self,
-        borrower: &Borrower,
-        pool_data: &IPoolDataProvider<M>,
-        health_factor: &U256,
-        pool_state: &PoolState,
-    ) -> Result<LiquidationOpportunity> {
-        let Borrower {
-            address: borrower_address,
-            collateral,
-            debt,
-        } = borrower;
-        // TODO: handle users with multiple collateral / debt
-        // get first item out of the set
-        let collateral_address = collateral
-            .iter()
-            .next()
-            .ok_or(anyhow!("No collateral found"))?;
-        let debt_address = debt.iter().next().ok_or(anyhow!("No debt found"))?;
-        let collateral_asset_price = pool_state
-            .prices
-            .get(collateral_address)
-            .ok_or(anyhow!("No collateral price"))?;
-        let debt_asset_price = pool_state
-            .prices
-            .get(debt_address)
-            .ok_or(anyhow!("No debt price"))?;
-        let collateral_config = self
-            .tokens
-            .get(collateral_address)
-            .ok_or(anyhow!("Failed to get collateral address"))?;
-        let debt_config = self
-            .tokens
-            .get(debt_address)
-            .ok_or(anyhow!("Failed to get debt address"))?;
-        let collateral_unit = U256::from(10).pow(collateral_config.decimals.into());
-        let debt_unit = U256::from(10).pow(debt_config.decimals.into());
-        let liquidation_bonus = collateral_config.liquidation_bonus;
-        let a_token = IERC20::new(collateral_config.a_address.clone(), self.client.clone());
-
-        let (_, stable_debt, variable_debt, _, _, _, _, _, _) = pool_data
-            .get_user_reserve_data(*debt_address, *borrower_address)
-            .await?;
-        let close_factor = if health_factor.gt(&U256::from(LIQUIDATION_CLOSE_FACTOR_THRESHOLD)) {
-            U256::from(DEFAULT_LIQUIDATION_CLOSE_FACTOR)
-        } else {
-            U256::from(MAX_LIQUIDATION_CLOSE_FACTOR)
+    // an operator's decision on the liquidations of `borrower` over the approval notional
+    fn decide_approval(&self, borrower: Address, approved: bool) {
+        let Some(approval) = &self.approval else {
+            error!("Approvals are off, set --approval-notional-usd");
+            return;
         };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        let mut gate = approval.lock().expect("approval gate poisoned");
+        let pending = gate.decide(borrower, approved, now);
+        info!(
+            "{} liquidations of {:?} for {}s{}",
+            if approved { "Approved" } else { "Rejected" },
+            borrower,
+            gate.config().ttl_secs,
+            if pending { "" } else { ", none were pending" }
+        );
+        self.publish_pending_approvals(&gate);
+    }
 
-        let mut debt_to_cover =
-            (stable_debt + variable_debt) * close_factor / MAX_LIQUIDATION_CLOSE_FACTOR;
-        let base_collateral = (debt_asset_price * debt_to_cover * collateral_unit)
-            / (collateral_asset_price * debt_unit);
-        let mut collateral_to_liquidate = percent_mul(base_collateral, liquidation_bonus);
-        let user_collateral_balance = a_token.balance_of(*borrower_address).await?;
+    // value of the debt an opportunity covers in base currency, as large as can be if it's
+    // unknown or overflows
+    fn debt_value_base(&self, op: &LiquidationOpportunity) -> U256 {
+        self.tokens
+            .get(&op.debt)
+            .and_then(|debt| {
+                position_value(op.debt_to_cover, Some(&op.debt_price), debt.decimals).ok()
+            })
+            .unwrap_or(U256::MAX)
+    }
 
-        if collateral_to_liquidate > user_collateral_balance {
-            collateral_to_liquidate = user_collateral_balance;
-            debt_to_cover = (collateral_asset_price * collateral_to_liquidate * debt_unit)
-                / percent_div(debt_asset_price * collateral_unit, liquidation_bonus);
+    // pass on opportunities the liquidations in flight leave no room for
+    fn over_exposure_cap(&self, op: &LiquidationOpportunity) -> bool {
+        match self.exposure.check(self.debt_value_base(op)) {
+            Some(reason) => {
+                info!("{}", reason);
+                true
+            }
+            None => false,
         }
+    }
 
-        let collateral_symbol = self
-            .tokens
-            .get(collateral_address)
-            .map(|c| c.symbol.clone())
-            .unwrap_or_default();
-        let debt_symbol = self
-            .tokens
-            .get(&debt_address)
-            .map(|d| d.symbol.clone())
-            .unwrap_or_default();
+    fn publish_pending_approvals(&self, gate: &ApprovalGate) {
+        if let Ok(mut status) = self.status.write() {
+            status.pending_approvals = gate.pending();
+        }
+    }
 
-        let mut op = LiquidationOpportunity {
-            borrower: borrower_address.clone(),
-            collateral: collateral_address.clone(),
-            debt: debt_address.clone(),
-            debt_to_cover,
-            profit_eth: I256::from(0),
-            collateral_symbol,
-            debt_symbol,
-            profit_factor: I256::from(0),
-        };
+    // post what became of an opportunity to the webhook, if set
+    fn report_opportunity(
+        &self,
+        op: &LiquidationOpportunity,
+        verdict: Verdict,
+        reason: Option<&str>,
+    ) {
+        if let Some(webhook) = &self.opportunity_webhook {
+            webhook.post(OpportunityReport {
+                opportunity: self.opportunity_status(op),
+                verdict,
+                reason: reason.map(str::to_string),
+            });
+        }
+    }
 
-        let asset_price_in_eth = self
-            .get_asset_price_eth(collateral_address, pool_state)
-            .await?;
-        let debt_price_in_eth = self.get_asset_price_eth(debt_address, pool_state).await?;
+    // log why an opportunity is passed on, and report it
+    fn pass_opportunity(&self, op: &LiquidationOpportunity, reason: &str) -> Vec<Action> {
+        info!("{}, passing", reason);
+        self.report_opportunity(op, Verdict::Passed, Some(reason));
+        vec![]
+    }
 
-        if self.use_aave_liquidator {
-            let asset_value_in_eth = I256::from_dec_str(&asset_price_in_eth.to_string())?
-                .checked_mul(I256::from_dec_str(&collateral_to_liquidate.to_string())?)
-                .unwrap()
-                .checked_div(I256::from_dec_str(&collateral_unit.to_string())?)
-                .unwrap();
-            let debt_value_in_eth = I256::from_dec_str(&debt_price_in_eth.to_string())?
-                .checked_mul(I256::from_dec_str(&debt_to_cover.to_string())?)
-                .unwrap()
-                .checked_div(I256::from_dec_str(&debt_unit.to_string())?)
-                .unwrap();
-            op.profit_eth = asset_value_in_eth.checked_sub(debt_value_in_eth).unwrap();
-            op.profit_factor = asset_value_in_eth
-                .checked_mul(I256::from(100))
-                .unwrap()
-                .checked_div(debt_value_in_eth)
-                .unwrap_or(I256::from(0));
-            if debt_to_cover == U256::zero() {
-                op.profit_eth = I256::from(0);
-                op.profit_factor = I256::from(0);
-                return Err(anyhow!(
-                    "No debt to cover for borrower {:?}, collateral {:?}, debt {:?}",
-                    borrower_address,
-                    collateral_address,
-                    debt_address
-                ));
+    /// Find the best liquidation among the given underwater borrowers and build its action.
+    async fn liquidate_best_op(&mut self, underwater: Vec<(Address, U256)>) -> Vec<Action> {
+        // partner accounts with a repay in flight are left to it, the others are
+        // liquidated like any borrower once repaying no longer beats it
+        let underwater: Vec<(Address, U256)> = underwater
+            .into_iter()
+            .filter(|(borrower, _)| !self.partner_repays.contains_key(borrower))
+            .collect();
+        if underwater.is_empty() {
+            return vec![];
+        }
+        // the sentinel only holds back borrowers above its health factor threshold
+        let sequencer_up = self.is_sequencer_up().await;
+        let underwater: Vec<(Address, U256)> = underwater
+            .into_iter()
+            .filter(|(_, health_factor)| is_liquidation_allowed(*health_factor, sequencer_up))
+            .collect();
+        if underwater.is_empty() {
+            return vec![];
+        }
+
+        let pool_state = match self.get_pool_state().await {
+            Ok(pool_state) => pool_state,
+            Err(e) => {
+                error!("Error getting pool state: {}", e);
+                self.tick.errors += 1;
+                return vec![];
+            }
+        };
+        if let Err(e) = self.check_reference_prices(&pool_state.prices).await {
+            error!("Error checking reference prices: {}", e);
+            self.tick.errors += 1;
+        }
+        let op = match self
+            .get_best_liquidation_op(underwater, &pool_state)
+            .await
+            .map_err(|e| {
+                error!("Error finding liq ops: {}", e);
+                self.tick.errors += 1;
+            })
+            .ok()
+            .flatten()
+        {
+            Some(op) => op,
+            None => {
+                info!("No profitable ops, passing");
+                return vec![];
             }
-            if collateral_address == debt_address {
+        };
+
+        let (borrower, profit_eth) = (op.borrower, op.profit_eth);
+        let actions = self.submit_op(op).await;
+        if !actions.is_empty() {
+            self.record_attempt(borrower, profit_eth);
+        }
+        actions
+    }
+
+    // claim the borrower's liquidation for the lease, so no other instance of the cluster
+    // submits one meanwhile. Without Redis there is nobody to race.
+    async fn claim(&self, op: &LiquidationOpportunity) -> bool {
+        let Some(shared_state) = &self.shared_state else {
+            return true;
+        };
+        match shared_state.claim(op.borrower).await {
+            Ok(true) => true,
+            Ok(false) => {
                 info!(
-                    "Collateral and debt are the same for borrower {:?}, collateral {:?}, debt {:?}",
-                    borrower_address, collateral_address, debt_address
+                    "Borrower {:?} claimed by another instance, passing",
+                    op.borrower
                 );
-                return Err(anyhow!(
-                    "Collateral and debt are the same for borrower {:?}, collateral {:?}, debt {:?}, not support yet",
-                    borrower_address,
-                    collateral_address,
-                    debt_address
-                ));
+                false
+            }
+            Err(e) => {
+                // submitting unclaimed could race another instance for the same borrower
+                error!("Error claiming liquidation, passing: {}", e);
+                false
             }
-            info!(
-                "Using Aave liquidator - profit in ETH: {}, asset_value_in_eth: {}, debt_value_in_eth: {}, profit factor: {}%",
-                op.profit_eth, asset_value_in_eth, debt_value_in_eth, op.profit_factor
-            );
-            self.build_liquidation(&op)
-                .await
-                .map_err(|e| error!("Error building liquidation: {}", e))
-                .unwrap();
-        } else {
-            let gain = self.build_liquidation_call(&op).await?.call().await?;
-            op.profit_eth =
-                gain * I256::from_dec_str(&asset_price_in_eth.to_string())? / I256::from(PRICE_ONE);
         }
+    }
 
-        info!(
-            "Found opportunity - borrower: {:?}, collateral: {:?}({}), debt: {:?}({}), collateral_to_liquidate: {:?}, debt_to_cover: {:?}, profit_eth: {:?}",
-            op.borrower, collateral_address, op.collateral_symbol, debt_address, op.debt_symbol, collateral_to_liquidate, debt_to_cover, op.profit_eth
-        );
+    // remember a submitted liquidation, to tell whether a competitor beats it
+    fn record_attempt(&mut self, borrower: Address, profit_eth: I256) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        self.competitors
+            .record_attempt(borrower, unsigned_profit(profit_eth), now);
+    }
 
-        Ok(op)
+    // share of the profit bid in gas at full confidence, as learned for similar profits unless
+    // an asset of the op has its own
+    fn bid_percentage(&self, op: &LiquidationOpportunity) -> u64 {
+        self.asset_override(op).bid_percentage.unwrap_or_else(|| {
+            self.bid_model
+                .bid_percentage(unsigned_profit(op.profit_eth))
+        })
     }
 
-    async fn build_liquidation_call(
-        &self,
-        op: &LiquidationOpportunity,
-    ) -> Result<ContractCall<M, I256>> {
-        if self.config.l2_encoder == Address::zero() {
-            return Err(anyhow!(
-                "L2 Encoder address is not deployed on this network"
-            ));
+    /// Decide whether and how to submit an opportunity, and build its action.
+    #[instrument(skip_all, fields(correlation_id = %op.correlation_id))]
+    async fn submit_op(&self, op: LiquidationOpportunity) -> Vec<Action> {
+        info!("Best op: {:?}", op);
+        self.publish_opportunity(&op);
+
+        if op.profit_eth < I256::from(0) || self.below_min_profit(&op) {
+            return self.pass_opportunity(&op, "No profitable ops");
         }
-        let liquidator = Liquidator::new(self.liquidator, self.client.clone());
-        let encoder = L2Encoder::new(self.config.l2_encoder, self.client.clone());
-        let (data0, data1) = encoder
-            .encode_liquidation_call(op.collateral, op.debt, op.borrower, op.debt_to_cover, false)
-            .call()
-            .await?;
 
-        // TODO: handle arbitrary pool fees
-        Ok(liquidator.liquidate(op.collateral, op.debt, 500, op.debt_to_cover, data0, data1))
+        let decision = match self
+            .submission_policy
+            .decide(op.confidence, self.bid_percentage(&op))
+        {
+            Some(decision) => decision,
+            None => {
+                let reason = format!(
+                    "Confidence {} below minimum {}",
+                    op.confidence, self.submission_policy.min_confidence_bps
+                );
+                return self.pass_opportunity(&op, &reason);
+            }
+        };
+        info!("Submission decision: {:?}", decision);
+
+        if self.submission_paused() {
+            return self.pass_opportunity(&op, "Submission paused");
+        }
+        if !self.prices_agree(&op).await {
+            return self.pass_opportunity(&op, "Oracle prices off");
+        }
+        if let Some(reason) = self.held_for_approval(&op) {
+            return self.pass_opportunity(&op, reason);
+        }
+        if self.role == Role::Detect {
+            self.queue_op(op, decision).await;
+            return vec![];
+        }
+        self.submit_decided_op(op, decision).await
     }
 
-    async fn build_liquidation(
-        &self,
-        op: &LiquidationOpportunity,
-    ) -> Result<TypedTransaction, anyhow::Error> {
-        if self.use_aave_liquidator {
-            let pool = Pool::new(self.config.pool_address, self.client.clone());
-            let mut call =
-                pool.liquidation_call(op.collateral, op.debt, op.borrower, op.debt_to_cover, false);
-            Ok(call.tx.set_chain_id(self.chain_id).clone())
-        } else {
-            let mut call = self.build_liquidation_call(op).await?;
-            Ok(call.tx.set_chain_id(self.chain_id).clone())
+    // queue an opportunity for the executors, the rest of the cluster could submit it
+    async fn queue_op(&self, op: LiquidationOpportunity, decision: SubmissionDecision) {
+        let Some(shared_state) = &self.shared_state else {
+            error!("No shared state to queue the opportunity on");
+            return;
+        };
+        let message = OpportunityMessage { op, decision };
+        match shared_state.publish_opportunity(&message).await {
+            Ok(()) => {
+                info!("Queued op for the executors");
+                self.report_opportunity(&message.op, Verdict::Queued, None);
+            }
+            Err(e) => {
+                error!("Error queueing op: {}", e);
+                self.pass_opportunity(&message.op, "Queueing failed");
+            }
         }
     }
-}
 
-fn percent_mul(a: U256, bps: u64) -> U256 {
-    (U256::from(5000) + (a * bps)) / U256::from(10000)
-}
+    /// Process an opportunity a detect-only instance queued, submitting it as decided there
+    /// unless submission is paused here.
+    async fn process_queued_opportunity_event(&mut self, queued: QueuedOpportunity) -> Vec<Action> {
+        let message: OpportunityMessage = match serde_json::from_str(&queued.0) {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Invalid queued opportunity: {}", e);
+                return vec![];
+            }
+        };
+        let OpportunityMessage { op, decision } = message;
+        info!(correlation_id = %op.correlation_id, "Queued op: {:?}", op);
+        if self.submission_paused() {
+            info!(correlation_id = %op.correlation_id, "Submission paused, passing");
+            self.report_opportunity(&op, Verdict::Passed, Some("Submission paused"));
+            return vec![];
+        }
+        let (borrower, profit_eth) = (op.borrower, op.profit_eth);
+        let actions = self.submit_decided_op(op, decision).await;
+        if !actions.is_empty() {
+            self.record_attempt(borrower, profit_eth);
+        }
+        actions
+    }
 
-fn percent_div(a: U256, bps: u64) -> U256 {
-    let half_bps = bps / 2;
-    (U256::from(half_bps) + (a * 10000)) / bps
+    // claim the borrower, approve the pool and build the tx of an opportunity decided on
+    async fn submit_decided_op(
+        &self,
+        op: LiquidationOpportunity,
+        decision: SubmissionDecision,
+    ) -> Vec<Action> {
+        if self.over_exposure_cap(&op) {
+            return self.pass_opportunity(&op, "Exposure cap reached");
+        }
+        if !self.claim(&op).await {
+            return self.pass_opportunity(&op, "Borrower not claimed");
+        }
+        if let Err(e) = self.approve_liquidation(&op).await {
+            error!("Error approving liquidation: {}", e);
+            return self.pass_opportunity(&op, "Approval failed");
+        }
+
+        let tx = match self.build_liquidation(&op).await {
+            Ok(tx) => tx,
+            Err(e) => {
+                error!("Error building liquidation: {}", e);
+                return self.pass_opportunity(&op, "Building the liquidation failed");
+            }
+        };
+        // the executor takes the L1 fee out of the bid itself
+        let gross_profit_eth = op.profit_eth + I256::from_raw(op.l1_fee_eth);
+        let Some(gas_bid_info) = gas_bid_info(gross_profit_eth, decision.bid_percentage) else {
+            error!("Failed to bid on a profit of {}", op.profit_eth);
+            return self.pass_opportunity(&op, "Bidding failed");
+        };
+        self.report_opportunity(&op, Verdict::Submitted, None);
+
+        vec![Action::SubmitTx(SubmitTx {
+            submission: SubmitTxToMempool {
+                tx,
+                gas_bid_info: Some(gas_bid_info),
+            },
+            liquidation: Some(op.liquidation_info(self.debt_value_base(&op), &self.tokens)),
+            correlation_id: op.correlation_id,
+            private: decision.route == SubmissionRoute::Private,
+            valued_at: op.valued_at,
+        })]
+    }
+
+    /// Repay debt with collateral for partner accounts on the repay path that are close to liquidation.
+    ///
+    /// The repays are signed and sent by each partner's `signer_rpc`, not by the executor,
+    /// and only one is in flight per account.
+    async fn protect_partner_accounts(&mut self) {
+        // a detector can't sign, an executor doesn't poll
+        let Some(adapter) = self.repay_adapter.filter(|_| self.role == Role::All) else {
+            return;
+        };
+        let partners: Vec<PartnerAccount> = self
+            .partner_accounts
+            .values()
+            .filter(|partner| partner.path == ExecutionPath::RepayWithCollateral)
+            .cloned()
+            .collect();
+
+        for partner in partners {
+            if self.partner_repay_in_flight(partner.address).await {
+                continue;
+            }
+            let correlation_id = new_correlation_id();
+            let tx = match self
+                .build_repay_with_collateral(&partner, adapter, &correlation_id)
+                .await
+            {
+                Ok(Some(tx)) if self.submission_paused() => {
+                    info!(
+                        "Submission paused, not protecting partner account {:?}: {:?}",
+                        partner.address, tx
+                    );
+                    continue;
+                }
+                Ok(Some(tx)) => tx,
+                Ok(None) => continue,
+                Err(e) => {
+                    error!(
+                        "Error protecting partner account {:?}: {}",
+                        partner.address, e
+                    );
+                    continue;
+                }
+            };
+            match send_partner_repay(&partner, tx).await {
+                Ok(hash) => {
+                    info!(
+                        "Sent repay {:?} for partner account {:?}",
+                        hash, partner.address
+                    );
+                    self.partner_repays
+                        .insert(partner.address, (hash, self.last_block_number));
+                }
+                Err(e) => error!(
+                    "Error sending repay for partner account {:?}: {}",
+                    partner.address, e
+                ),
+            }
+        }
+    }
+
+    // whether a repay sent for the partner account is still waiting to be mined
+    async fn partner_repay_in_flight(&mut self, partner: Address) -> bool {
+        let Some((hash, sent_at)) = self.partner_repays.get(&partner).copied() else {
+            return false;
+        };
+        match self.client.get_transaction_receipt(hash).await {
+            Ok(None) if self.last_block_number < sent_at + PARTNER_REPAY_TIMEOUT_BLOCKS => true,
+            Ok(_) => {
+                self.partner_repays.remove(&partner);
+                false
+            }
+            Err(e) => {
+                error!("Error getting receipt of partner repay {:?}: {}", hash, e);
+                true
+            }
+        }
+    }
+
+    // for all known borrowers, return a sorted set of those with health factor < 1
+    // and refresh the watchlist with those close to it
+    async fn get_underwater_borrowers(&mut self) -> Result<Vec<(Address, U256)>> {
+        let borrowers: Vec<&Borrower> = self
+            .borrowers
+            .values()
+            .filter(|b| !b.debt.is_empty() && self.filter.allows_borrower(&b.address))
+            .collect();
+        // dust when last checked, rechecked once their reserves are reconciled
+        let (dust, borrowers): (Vec<&Borrower>, Vec<&Borrower>) = borrowers
+            .into_iter()
+            .partition(|b| b.is_dust(self.min_debt_base));
+        let borrowers: Vec<Address> = borrowers.iter().map(|b| b.address).collect();
+        self.tick.dust_skipped += dust.len() as u64;
+        info!(
+            "Found {} borrowers with debt, {} more with dust",
+            borrowers.len(),
+            dust.len()
+        );
+        self.tick.borrowers_scanned = borrowers.len() as u64;
+
+        let scan = self.get_health_factors(&borrowers).await?;
+        info!("Watching {} borrowers", scan.watchlist.len());
+        self.watchlist = scan.watchlist;
+        if let Some(shared_state) = &self.shared_state {
+            if let Err(e) = shared_state
+                .write_watchlist(self.filter.shard, &self.watchlist)
+                .await
+            {
+                error!("Error sharing watchlist: {}", e);
+            }
+        }
+        self.update_bad_debt(scan.bad_debt);
+        if let Err(e) = self.update_risk_engine(&scan.at_risk).await {
+            error!("Error updating risk engine: {}", e);
+        }
+        self.publish_histogram(scan.histogram);
+
+        Ok(scan.underwater)
+    }
+
+    // log the borrowers closest to liquidation, and publish the histogram
+    fn publish_histogram(&self, histogram: HealthFactorHistogram) {
+        for bucket in histogram.buckets.iter() {
+            info!(
+                max_health_factor_bps = ?bucket.max_bps,
+                borrowers = bucket.borrowers,
+                total_debt_base = %bucket.total_debt_base,
+                "health factor bucket"
+            );
+        }
+        for account in histogram.closest.iter() {
+            info!(
+                "Close to liquidation: {:?}, health factor {}, debt {}",
+                account.borrower, account.health_factor, account.total_debt_base
+            );
+        }
+        if let Ok(mut status) = self.status.write() {
+            status.health_factors = histogram;
+        }
+    }
+
+    // keep when each bad debt was first seen, warning about new ones, and publish them
+    fn update_bad_debt(&mut self, bad_debt: HashMap<Address, (U256, U256)>) {
+        let mut since_blocks = HashMap::new();
+        for borrower in bad_debt.keys() {
+            let since_block = match self.bad_debt.get(borrower) {
+                Some(since_block) => *since_block,
+                None => {
+                    let (collateral_base, debt_base) = bad_debt[borrower];
+                    warn!(
+                        "Borrower {:?} has bad debt: {} of collateral for {} of debt",
+                        borrower, collateral_base, debt_base
+                    );
+                    self.last_health_check_block
+                }
+            };
+            since_blocks.insert(*borrower, since_block);
+        }
+        self.bad_debt = since_blocks;
+
+        if let Ok(mut status) = self.status.write() {
+            status.bad_debt = bad_debt
+                .iter()
+                .map(|(borrower, (collateral_base, debt_base))| BadDebtStatus {
+                    borrower: *borrower,
+                    collateral_base: *collateral_base,
+                    debt_base: *debt_base,
+                    since_block: self.bad_debt[borrower],
+                })
+                .collect();
+        }
+    }
+
+    // call pool.getUserAccountData(user) for each borrower, bucketing them by health factor
+    async fn get_health_factors(&mut self, borrowers: &[Address]) -> Result<HealthFactorScan> {
+        self.last_health_check_block = self.client.get_block_number().await?.as_u64();
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        let health_factor_one = U256::from_dec_str("1000000000000000000").unwrap();
+        let watchlist_threshold =
+            health_factor_one * self.watchlist_health_factor_bps / MAX_LIQUIDATION_CLOSE_FACTOR;
+        let risk_threshold =
+            health_factor_one * self.risk_health_factor_bps / MAX_LIQUIDATION_CLOSE_FACTOR;
+
+        let mut underwater_borrowers = Vec::new();
+        let mut watchlist = HashSet::new();
+        let mut at_risk = HashSet::new();
+        let mut bad_debt = HashMap::new();
+        let mut histogram = HealthFactorHistogram::new(self.last_health_check_block);
+
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+        let n = borrowers.len();
+
+        for (i, chunk) in borrowers.chunks(MULTICALL_CHUNK_SIZE).enumerate() {
+            multicall.clear_calls();
+
+            for borrower in chunk {
+                multicall.add_call(pool.get_user_account_data(*borrower), false);
+            }
+
+            let result: Vec<(U256, U256, U256, U256, U256, U256)> = multicall.call_array().await?;
+            for (borrower, (total_collateral_base, total_debt_base, _, _, _, health_factor)) in
+                zip(chunk, result)
+            {
+                if let Some(cached) = self.borrowers.get_mut(borrower) {
+                    cached.total_debt_base = Some(total_debt_base);
+                }
+                // dust positions are never worth liquidating, don't track them any further
+                if total_debt_base < self.min_debt_base {
+                    self.tick.dust_skipped += 1;
+                    continue;
+                }
+                // neither is bad debt, which no price move short of a recovery fixes
+                if health_factor < health_factor_one
+                    && is_bad_debt(total_collateral_base, total_debt_base, self.min_debt_base)
+                {
+                    self.tick.bad_debt_found += 1;
+                    self.tick.bad_debt_base += total_debt_base - total_collateral_base;
+                    bad_debt.insert(*borrower, (total_collateral_base, total_debt_base));
+                    continue;
+                }
+                histogram.record(*borrower, health_factor, total_debt_base);
+                if health_factor.lt(&risk_threshold) {
+                    at_risk.insert(*borrower);
+                }
+                if health_factor.lt(&watchlist_threshold) {
+                    watchlist.insert(*borrower);
+                }
+                if health_factor.lt(&health_factor_one) {
+                    info!(
+                        "Found underwater borrower {:?} -  healthFactor: {}",
+                        borrower, health_factor
+                    );
+                    underwater_borrowers.push((*borrower, health_factor));
+                }
+            }
+            info!(
+                "Found {} underwater borrowers, total progress: {}%",
+                underwater_borrowers.len(),
+                100 * (MULTICALL_CHUNK_SIZE * i) / n,
+            );
+        }
+
+        // sort borrowers by health factor
+        underwater_borrowers.sort_by(|a, b| a.1.cmp(&b.1));
+        Ok(HealthFactorScan {
+            underwater: underwater_borrowers,
+            watchlist,
+            at_risk,
+            bad_debt,
+            histogram: histogram.finish(),
+        })
+    }
+
+    // snapshot the positions of at-risk borrowers and index their liquidation prices
+    async fn update_risk_engine(&mut self, at_risk: &HashSet<Address>) -> Result<()> {
+        let prices = self.get_prices().await?;
+        let pool_data =
+            IPoolDataProvider::<M>::new(self.config.pool_data_provider, self.client.clone());
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+
+        let reserves: Vec<(Address, Address)> = at_risk
+            .iter()
+            .filter_map(|borrower| self.borrowers.get(borrower))
+            .flat_map(|borrower| {
+                let mut assets = borrower.debt_assets();
+                assets.extend(&borrower.collateral);
+                assets
+                    .into_iter()
+                    .map(move |asset| (borrower.address, asset))
+            })
+            .collect();
+
+        // variable debt accrues at the reserve rate, which getUserReserveData doesn't return,
+        // and balances are normalized to the reserve indexes, so the rates and indexes are
+        // only polled for reserves no `ReserveDataUpdated` log was seen for yet
+        let mut reserve_data = self.reserve_data.clone();
+        let unseen: Vec<Address> = reserves
+            .iter()
+            .map(|(_, asset)| *asset)
+            .filter(|asset| !reserve_data.contains_key(asset))
+            .collect::<HashSet<Address>>()
+            .into_iter()
+            .collect();
+        for chunk in unseen.chunks(MULTICALL_CHUNK_SIZE) {
+            multicall.clear_calls();
+            for asset in chunk {
+                multicall.add_call(pool_data.get_reserve_data(*asset), false);
+            }
+            let result: Vec<ReserveData> = multicall.call_array().await?;
+            for (asset, data) in zip(chunk, result) {
+                reserve_data.insert(
+                    *asset,
+                    ReserveDataUpdate {
+                        reserve: *asset,
+                        liquidity_rate: data.5,
+                        variable_borrow_rate: data.6,
+                        liquidity_index: data.9,
+                        variable_borrow_index: data.10,
+                        timestamp: data.11,
+                    },
+                );
+            }
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        let mut snapshots: HashMap<Address, PositionSnapshot> = HashMap::new();
+        for chunk in reserves.chunks(MULTICALL_CHUNK_SIZE) {
+            multicall.clear_calls();
+            for (borrower, asset) in chunk {
+                multicall.add_call(pool_data.get_user_reserve_data(*asset, *borrower), false);
+            }
+
+            let result: Vec<UserReserveData> = multicall.call_array().await?;
+            for ((borrower, asset), user_reserve_data) in zip(chunk, result) {
+                let (
+                    a_balance,
+                    stable_debt,
+                    variable_debt,
+                    _,
+                    _,
+                    stable_borrow_rate,
+                    liquidity_rate,
+                    _,
+                    usage_as_collateral,
+                ) = user_reserve_data;
+                let Some(token) = self.tokens.get(asset) else {
+                    continue;
+                };
+                // the indexes the balances are normalized to, the reserve's last ones
+                // accrued up to now as the pool does
+                let reserve = reserve_data.get(asset);
+                let normalized = |index: U256, growth: Result<U256>| {
+                    growth
+                        .and_then(|growth| checked_ray_mul(index, growth))
+                        .ok()
+                };
+                let liquidity_index = reserve.and_then(|reserve| {
+                    normalized(
+                        reserve.liquidity_index,
+                        linear_interest(
+                            reserve.liquidity_rate,
+                            timestamp.saturating_sub(reserve.timestamp),
+                        ),
+                    )
+                });
+                let variable_borrow_index = reserve.and_then(|reserve| {
+                    normalized(
+                        reserve.variable_borrow_index,
+                        compounded_interest(
+                            reserve.variable_borrow_rate,
+                            timestamp.saturating_sub(reserve.timestamp),
+                        ),
+                    )
+                });
+                let unit = U256::from(10).pow(token.decimals.into());
+                let snapshot = snapshots
+                    .entry(*borrower)
+                    .or_insert_with(|| PositionSnapshot {
+                        borrower: *borrower,
+                        collateral: vec![],
+                        debt: vec![],
+                        timestamp,
+                    });
+                if usage_as_collateral && !a_balance.is_zero() {
+                    snapshot.collateral.push(AssetPosition {
+                        asset: *asset,
+                        amount: a_balance,
+                        unit,
+                        liquidation_threshold: token.liquidation_threshold,
+                        rate: liquidity_rate,
+                        fixed_rate: false,
+                        index: liquidity_index,
+                    });
+                }
+                // stable debt accrues at the rate the borrower locked in, and is ignored
+                // once the pool removed it
+                let stable_debt = if self.stable_debt {
+                    stable_debt
+                } else {
+                    U256::zero()
+                };
+                for (amount, rate, fixed_rate, index) in [
+                    (
+                        variable_debt,
+                        reserve.map_or(U256::zero(), |reserve| reserve.variable_borrow_rate),
+                        false,
+                        variable_borrow_index,
+                    ),
+                    (stable_debt, stable_borrow_rate, true, None),
+                ] {
+                    if !amount.is_zero() {
+                        snapshot.debt.push(AssetPosition {
+                            asset: *asset,
+                            amount,
+                            unit,
+                            liquidation_threshold: 0,
+                            rate,
+                            fixed_rate,
+                            index,
+                        });
+                    }
+                }
+            }
+        }
+
+        self.risk_engine.retain(at_risk);
+        for snapshot in snapshots.values() {
+            self.risk_engine.update(snapshot, &prices);
+        }
+        self.last_prices = prices;
+        info!("Risk engine tracking {} borrowers", self.risk_engine.len());
+
+        Ok(())
+    }
+
+    // look up the borrowers the risk engine expects to be liquidatable at current oracle prices
+    async fn get_price_triggered_borrowers(&mut self) -> Result<HashSet<Address>> {
+        if self.risk_engine.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let prices = self.get_prices().await?;
+        // interest keeps accruing between snapshots, so re-project before checking prices
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        let mut triggered = self.risk_engine.project(now, &prices);
+        for (asset, price) in prices.iter() {
+            if self.last_prices.get(asset) != Some(price) {
+                triggered.extend(self.risk_engine.on_price_update(asset, *price));
+            }
+        }
+        self.last_prices = prices;
+
+        Ok(triggered)
+    }
+
+    // load the bid model learned from past races, if any
+    fn load_bid_model(&mut self) -> Result<()> {
+        self.bid_model =
+            BidModel::read(&self.bid_model_file, self.chain_id, self.bid_model.bounds())?;
+        info!("read bid model from {}", self.bid_model_file.display());
+        Ok(())
+    }
+
+    // load borrower state cache from file if exists
+    fn load_cache(&mut self) -> Result<()> {
+        match StateCache::read(&self.state_cache_file) {
+            Ok(Some(cache)) if cache.is_compatible(self.chain_id, &self.deployment) => {
+                info!(
+                    "read state cache from {} written by {:?}",
+                    self.state_cache_file.display(),
+                    cache.liquidator
+                );
+                self.last_block_number = cache.last_block_number;
+                self.borrowers = cache.borrowers;
+            }
+            Ok(Some(cache)) => {
+                info!(
+                    "ignoring state cache of version {} for chain {} {:?}, creating new one",
+                    cache.version, cache.chain_id, cache.deployment
+                );
+                self.last_block_number = self.config.creation_block;
+            }
+            Ok(None) => {
+                info!("no state cache file found, creating new one");
+                self.last_block_number = self.config.creation_block;
+            }
+            Err(e) => {
+                error!("{}", e);
+                return Err(e);
+            }
+        };
+
+        Ok(())
+    }
+
+    // take the cluster's cache over our own when another instance indexed further
+    async fn load_shared_cache(&mut self) {
+        let Some(shared_state) = &self.shared_state else {
+            return;
+        };
+        match shared_state.read_borrowers::<StateCache>().await {
+            Ok(Some(cache))
+                if cache.is_compatible(self.chain_id, &self.deployment)
+                    && cache.last_block_number > self.last_block_number =>
+            {
+                info!(
+                    "read shared state cache at block {} written by {:?}",
+                    cache.last_block_number, cache.liquidator
+                );
+                self.last_block_number = cache.last_block_number;
+                self.borrowers = cache.borrowers;
+            }
+            Ok(_) => {}
+            Err(e) => error!("Error reading shared state cache: {}", e),
+        }
+    }
+
+    // without a cache, seed the borrowers from the subgraph and replay logs from the block it
+    // had indexed, falling back to replaying every log if it can't be read
+    async fn bootstrap_borrowers(&mut self) {
+        let Some(url) = &self.subgraph_url else {
+            return;
+        };
+        if !self.borrowers.is_empty() {
+            return;
+        }
+        match get_subgraph_borrowers(url).await {
+            Ok((block, borrowers)) if block > self.last_block_number => {
+                info!(
+                    "bootstrapped {} borrowers from the subgraph at block {}",
+                    borrowers.len(),
+                    block
+                );
+                self.last_block_number = block;
+                self.borrowers = borrowers
+                    .into_iter()
+                    .map(|borrower| {
+                        (
+                            borrower.address,
+                            Borrower {
+                                address: borrower.address,
+                                collateral: borrower.collateral,
+                                debt: borrower.debt,
+                                kind: None,
+                                total_debt_base: None,
+                                siloed_debt: None,
+                            },
+                        )
+                    })
+                    .collect();
+                self.reconcile_next = self.borrowers.keys().copied().collect();
+            }
+            Ok((block, _)) => warn!(
+                "subgraph is at block {}, before block {}, replaying logs instead",
+                block, self.last_block_number
+            ),
+            Err(e) => warn!(
+                "Error bootstrapping from the subgraph, replaying logs instead: {}",
+                e
+            ),
+        }
+    }
+
+    fn load_partner_accounts(&mut self) -> Result<()> {
+        match File::open(&self.partner_accounts_file) {
+            Ok(file) => {
+                let partners: Vec<PartnerAccount> = serde_json::from_reader(file)
+                    .map_err(|e| anyhow!("Failed to parse partner accounts: {}", e))?;
+                info!("read {} partner accounts from file", partners.len());
+                for partner in &partners {
+                    partner.validate()?;
+                }
+                self.partner_accounts = partners
+                    .into_iter()
+                    .map(|partner| (partner.address, partner))
+                    .collect();
+            }
+            Err(_) => info!("no partner accounts file found"),
+        };
+
+        Ok(())
+    }
+
+    // update known borrower state from last block to latest block
+    async fn update_state(&mut self) -> Result<()> {
+        let latest_block = self.client.get_block_number().await?;
+        info!(
+            "Updating state from block {} to {}",
+            self.last_block_number, latest_block
+        );
+
+        // catching up on many blocks goes chunk by chunk, reporting progress after each
+        let started = Instant::now();
+        let mut progress = SyncProgress::new(self.last_block_number, latest_block.as_u64());
+        let catching_up = progress.blocks() > SYNC_CHUNK_BLOCKS;
+        let mut touched = HashSet::new();
+        let mut start_block = self.last_block_number;
+        while start_block < latest_block.as_u64() {
+            let end_block = std::cmp::min(start_block + SYNC_CHUNK_BLOCKS, latest_block.as_u64());
+            let known = self.borrowers.len();
+            let events = self
+                .index_logs(start_block.into(), end_block.into(), &mut touched)
+                .await?;
+            progress.advance(
+                end_block,
+                events,
+                self.borrowers.len() - known,
+                started.elapsed(),
+            );
+            if catching_up {
+                info!(
+                    "Indexed up to block {} of {}, {} blocks/s, ETA {:?}s, {} events, {} new borrowers",
+                    progress.indexed_block,
+                    progress.to_block,
+                    progress.blocks_per_sec,
+                    progress.eta_secs,
+                    progress.events,
+                    progress.new_borrowers
+                );
+                self.publish_sync_progress(&progress);
+            }
+            start_block = end_block + 1;
+        }
+        if catching_up {
+            info!(
+                "Indexed {} blocks in {}s, {} blocks/s, {} events, {} new borrowers",
+                progress.blocks(),
+                progress.elapsed_secs,
+                progress.blocks_per_sec,
+                progress.events,
+                progress.new_borrowers
+            );
+        }
+
+        // reserves listed or reconfigured since startup
+        let (changed, changed_e_modes) = self
+            .get_reserve_config_changes(self.last_block_number.into(), latest_block)
+            .await?;
+        if !changed.is_empty() || !changed_e_modes.is_empty() {
+            info!(
+                "Refreshing the configs of reserves {:?} and e-mode categories {:?}",
+                changed, changed_e_modes
+            );
+            self.refresh_token_configs(Some(&changed)).await?;
+            self.refresh_e_mode_categories(&changed_e_modes).await;
+            self.write_reserve_cache(latest_block.as_u64());
+        }
+
+        self.classify_borrowers().await?;
+        // other shards keep their own borrowers in sync
+        let due: Vec<Address> = self
+            .borrowers
+            .values()
+            .filter(|borrower| self.filter.shard.contains(&borrower.address))
+            .filter(|borrower| {
+                let every = match borrower.kind {
+                    Some(AccountKind::SmartAccount) => self.smart_account_reconcile_ticks,
+                    _ => self.eoa_reconcile_ticks,
+                };
+                // counted from the first update, not due all at once at startup
+                touched.contains(&borrower.address)
+                    || self.reconcile_next.contains(&borrower.address)
+                    || (self.update_count + 1).is_multiple_of(every)
+            })
+            .map(|borrower| borrower.address)
+            .collect();
+        self.reconcile_borrowers(&due).await?;
+        self.reconcile_next.clear();
+        self.update_count += 1;
+
+        // write state cache to file
+        let cache = StateCache::new(
+            self.chain_id,
+            self.deployment.clone(),
+            self.liquidator,
+            latest_block.as_u64(),
+            self.borrowers.clone(),
+        );
+        self.last_block_number = latest_block.as_u64();
+        // the update took a while, see how far the head has moved on since
+        if self.sync_lagging() {
+            let head = self.client.get_block_number().await?.as_u64();
+            let lag = head.saturating_sub(self.last_block_number);
+            progress.head_lag_blocks = Some(lag);
+            if self.max_sync_lag.is_some_and(|max| lag <= max) {
+                info!("Synced within {} blocks of the head, submitting", lag);
+                self.synced = true;
+            } else {
+                info!(
+                    "{} blocks behind the head, not submitting until within {:?}",
+                    lag, self.max_sync_lag
+                );
+            }
+        }
+        self.sync = progress;
+        if let Ok(mut status) = self.status.write() {
+            status.last_block_number = self.last_block_number;
+            status.sync = self.sync.clone();
+            status.paused = self.submission_paused();
+            status.last_update_timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs();
+            status.borrowers = Arc::new(cache.borrowers.clone());
+        }
+        cache.write(&self.state_cache_file)?;
+        if let Some(shared_state) = &self.shared_state {
+            if let Err(e) = shared_state.write_borrowers(&cache).await {
+                error!("Error sharing state cache: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    // index the borrow, supply and collateral logs from from_block to to_block, adding the
+    // borrowers they reveal and collecting who they touched. The number of logs indexed.
+    async fn index_logs(
+        &mut self,
+        from_block: U64,
+        to_block: U64,
+        touched: &mut HashSet<Address>,
+    ) -> Result<usize> {
+        // events only tell who to look at, the reserves come from the user configuration
+        let borrows = self.get_borrow_logs(from_block, to_block).await?;
+        let supplies = self.get_supply_logs(from_block, to_block).await?;
+        let toggles = self.get_collateral_toggles(from_block, to_block).await?;
+        let events = borrows.len() + supplies.len() + toggles.len();
+        for log in borrows {
+            self.borrowers
+                .entry(log.on_behalf_of)
+                .or_insert_with(|| Borrower {
+                    address: log.on_behalf_of,
+                    collateral: HashSet::new(),
+                    debt: HashSet::from([log.reserve]),
+                    kind: None,
+                    total_debt_base: None,
+                    siloed_debt: None,
+                });
+            touched.insert(log.on_behalf_of);
+        }
+        for log in supplies {
+            self.borrowers
+                .entry(log.on_behalf_of)
+                .or_insert_with(|| Borrower {
+                    address: log.on_behalf_of,
+                    collateral: HashSet::from([log.reserve]),
+                    debt: HashSet::new(),
+                    kind: None,
+                    total_debt_base: None,
+                    siloed_debt: None,
+                });
+            touched.insert(log.on_behalf_of);
+        }
+        touched.extend(
+            toggles
+                .into_iter()
+                .filter(|user| self.borrowers.contains_key(user)),
+        );
+        Ok(events)
+    }
+
+    // check which borrowers not classified yet have code at their address
+    async fn classify_borrowers(&mut self) -> Result<()> {
+        let unclassified: Vec<Address> = self
+            .borrowers
+            .values()
+            .filter(|borrower| {
+                borrower.kind.is_none() && self.filter.shard.contains(&borrower.address)
+            })
+            .map(|borrower| borrower.address)
+            .collect();
+        if unclassified.is_empty() {
+            return Ok(());
+        }
+
+        let mut smart_accounts = 0;
+        for chunk in unclassified.chunks(CODE_CHECK_CHUNK_SIZE) {
+            let codes = futures::future::try_join_all(
+                chunk
+                    .iter()
+                    .map(|address| self.client.get_code(*address, None)),
+            )
+            .await?;
+            for (address, code) in zip(chunk, codes) {
+                // delegated EOAs (EIP-7702) have code too and behave like smart accounts
+                let kind = if code.is_empty() {
+                    AccountKind::Eoa
+                } else {
+                    smart_accounts += 1;
+                    AccountKind::SmartAccount
+                };
+                if let Some(borrower) = self.borrowers.get_mut(address) {
+                    borrower.kind = Some(kind);
+                }
+            }
+        }
+        info!(
+            "Classified {} borrowers, {} smart accounts",
+            unclassified.len(),
+            smart_accounts
+        );
+
+        Ok(())
+    }
+
+    // overwrite the reserves of borrowers with their on-chain user configuration,
+    // dropping borrowers that no longer have a position
+    async fn reconcile_borrowers(&mut self, borrowers: &[Address]) -> Result<()> {
+        if borrowers.is_empty() {
+            return Ok(());
+        }
+        let configurations = get_user_configurations(
+            self.client.clone(),
+            &self.multicall,
+            self.config.pool_address,
+            borrowers,
+        )
+        .await?;
+
+        let tokens = &self.tokens;
+        let is_siloed = |asset: &Address| {
+            tokens
+                .get(asset)
+                .is_some_and(|token| token.siloed_borrowing)
+        };
+        let mut closed = 0;
+        for (address, configuration) in zip(borrowers, configurations) {
+            let (collateral, debt) = decode_user_configuration(configuration, &self.reserves);
+            if collateral.is_empty() && debt.is_empty() {
+                self.borrowers.remove(address);
+                closed += 1;
+            } else if let Some(borrower) = self.borrowers.get_mut(address) {
+                borrower.collateral = collateral;
+                borrower.debt = debt;
+                borrower.mark_siloed(is_siloed);
+                // their debt may have grown past dust
+                borrower.total_debt_base = None;
+            }
+        }
+        info!(
+            "Reconciled {} borrowers, dropped {} without a position",
+            borrowers.len(),
+            closed
+        );
+
+        Ok(())
+    }
+
+    // fetch all borrow events from the from_block to to_block
+    async fn get_borrow_logs(&self, from_block: U64, to_block: U64) -> Result<Vec<BorrowFilter>> {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        self.log_range
+            .query(from_block, to_block, |start_block, end_block| {
+                let event = pool
+                    .borrow_filter()
+                    .from_block(start_block)
+                    .to_block(end_block)
+                    .address(ValueOrArray::Value(self.config.pool_address));
+                async move { event.query().await }
+            })
+            .await
+    }
+
+    // fetch all supply events from the from_block to to_block
+    async fn get_supply_logs(&self, from_block: U64, to_block: U64) -> Result<Vec<SupplyFilter>> {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        self.log_range
+            .query(from_block, to_block, |start_block, end_block| {
+                let event = pool
+                    .supply_filter()
+                    .from_block(start_block)
+                    .to_block(end_block)
+                    .address(ValueOrArray::Value(self.config.pool_address));
+                async move { event.query().await }
+            })
+            .await
+    }
+
+    // users who enabled or disabled a reserve as collateral from from_block to to_block
+    async fn get_collateral_toggles(
+        &self,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<HashSet<Address>> {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        let enabled = self
+            .log_range
+            .query(from_block, to_block, |start_block, end_block| {
+                let event = pool
+                    .reserve_used_as_collateral_enabled_filter()
+                    .from_block(start_block)
+                    .to_block(end_block)
+                    .address(ValueOrArray::Value(self.config.pool_address));
+                async move { event.query().await }
+            })
+            .await?;
+        let disabled = self
+            .log_range
+            .query(from_block, to_block, |start_block, end_block| {
+                let event = pool
+                    .reserve_used_as_collateral_disabled_filter()
+                    .from_block(start_block)
+                    .to_block(end_block)
+                    .address(ValueOrArray::Value(self.config.pool_address));
+                async move { event.query().await }
+            })
+            .await?;
+
+        Ok(enabled
+            .into_iter()
+            .map(|log| log.user)
+            .chain(disabled.into_iter().map(|log| log.user))
+            .collect())
+    }
+
+    // reserves the pool configurator listed, dropped or reconfigured, and e-mode categories it
+    // changed, from from_block to to_block
+    async fn get_reserve_config_changes(
+        &self,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(HashSet<Address>, HashSet<u8>)> {
+        if self.pool_configurator.is_zero() {
+            return Ok((HashSet::new(), HashSet::new()));
+        }
+        let logs = self
+            .log_range
+            .query(from_block, to_block, |start_block, end_block| {
+                let filter = Filter::new()
+                    .address(self.pool_configurator)
+                    .topic0([reserve_config_topics(), e_mode_category_topics()].concat())
+                    .from_block(start_block)
+                    .to_block(end_block);
+                async move { self.client.get_logs(&filter).await }
+            })
+            .await?;
+
+        Ok((
+            logs.iter().filter_map(changed_reserve).collect(),
+            logs.iter().filter_map(changed_e_mode_category).collect(),
+        ))
+    }
+
+    // approve the pool for the debt assets the filter lets us repay, reading allowances in
+    // one batch and approving through one Liquidator tx, or concurrent txs from the sender
+    async fn approve_tokens(&mut self) -> Result<()> {
+        let sender = self
+            .client
+            .default_sender()
+            .ok_or(anyhow!("No connected sender"))?;
+        if self.capped_approvals() {
+            info!("Approving each liquidation's debt before sending it");
+            return Ok(());
+        }
+        // the pool pulls the repaid debt from whoever calls it
+        let owner = match self.use_aave_liquidator {
+            true => sender,
+            false => self.liquidator,
+        };
+        let unapproved = self
+            .unapproved_tokens(owner, self.approved_tokens())
+            .await?;
+        if unapproved.is_empty() {
+            return Ok(());
+        }
+        info!("Approving the pool for {} tokens", unapproved.len());
+
+        if !self.use_aave_liquidator {
+            Liquidator::new(self.liquidator, self.client.clone())
+                .approve_pool_batch(unapproved)
+                .send()
+                .await
+                .map_err(|e| anyhow!("approve failed: {:?}", e))?;
+            return Ok(());
+        }
+
+        // the nonce manager numbers the approvals as they're sent, so they can go at once
+        let approvals = unapproved.iter().map(|token| {
+            let approve = IERC20::new(*token, self.client.clone())
+                .approve(self.config.pool_address, U256::MAX);
+            async move {
+                approve
+                    .send()
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| anyhow!("approve of {:?} failed: {:?}", token, e))
+            }
+        });
+        futures::future::try_join_all(approvals).await?;
+        Ok(())
+    }
+
+    // the debt assets the filter lets us repay, which the pool is approved for
+    fn approved_tokens(&self) -> Vec<Address> {
+        self.tokens
+            .keys()
+            .filter(|token| self.filter.allows_debt(token))
+            .copied()
+            .collect()
+    }
+
+    // those of `tokens` `owner` hasn't approved the pool for, read in one batch
+    async fn unapproved_tokens(
+        &self,
+        owner: Address,
+        tokens: Vec<Address>,
+    ) -> Result<Vec<Address>> {
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+        for token in tokens.iter() {
+            let token = IERC20::new(*token, self.client.clone());
+            multicall.add_call(token.allowance(owner, self.config.pool_address), false);
+        }
+        let allowances: Vec<U256> = multicall
+            .call_array()
+            .await
+            .map_err(|e| anyhow!("allowance check failed: {:?}", e))?;
+        Ok(zip(tokens, allowances)
+            .filter(|(_, allowance)| allowance.is_zero())
+            .map(|(token, _)| token)
+            .collect())
+    }
+
+    fn capped_approvals(&self) -> bool {
+        self.use_aave_liquidator && self.approval_buffer_bps.is_some()
+    }
+
+    // with capped approvals, approve the pool for the liquidation's debt plus the buffer
+    // and wait for it to land, since the liquidation is estimated against the allowance
+    async fn approve_liquidation(&self, op: &LiquidationOpportunity) -> Result<()> {
+        let Some(buffer_bps) = self
+            .approval_buffer_bps
+            .filter(|_| self.use_aave_liquidator)
+        else {
+            return Ok(());
+        };
+        let sender = self
+            .client
+            .default_sender()
+            .ok_or(anyhow!("No connected sender"))?;
+        let pool = self.config.pool_address;
+        let token = IERC20::new(op.debt, self.client.clone());
+        // covering the max debt repays at most what the sender holds
+        let amount = if op.debt_to_cover == U256::MAX {
+            token.balance_of(sender).call().await?
+        } else {
+            op.debt_to_cover
+        };
+        let allowance = token.allowance(sender, pool).call().await?;
+        if allowance >= amount {
+            return Ok(());
+        }
+        let cap = capped_approval(amount, buffer_bps);
+        info!(
+            correlation_id = %op.correlation_id,
+            "Approving {} of {:?} for the liquidation", cap, op.debt
+        );
+        // tokens like USDT refuse to change an allowance that isn't zero
+        let approvals = match allowance.is_zero() {
+            true => vec![cap],
+            false => vec![U256::zero(), cap],
+        };
+        for approval in approvals {
+            let receipt = token
+                .approve(pool, approval)
+                .send()
+                .await
+                .map_err(|e| anyhow!("approve failed: {:?}", e))?
+                .await?
+                .ok_or(anyhow!("Approval of {:?} dropped", op.debt))?;
+            if receipt.status != Some(1.into()) {
+                return Err(anyhow!("Approval of {:?} reverted", op.debt));
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_token_configs(&mut self) -> Result<()> {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        let addresses_provider = IPoolAddressesProvider::<M>::new(
+            pool.addresses_provider().call().await?,
+            self.client.clone(),
+        );
+        self.pool_configurator = addresses_provider.get_pool_configurator().call().await?;
+        self.flash_loan_premium = pool.flashloan_premium_total().call().await?.try_into()?;
+        let block_number = self.client.get_block_number().await?.as_u64();
+        // only the reserves missing from the cache are fetched
+        self.load_reserve_cache(block_number).await;
+        self.refresh_token_configs(Some(&HashSet::new())).await?;
+        self.refresh_e_mode_categories(&HashSet::new()).await;
+        self.write_reserve_cache(block_number);
+        // only reserves are priced by the oracle
+        if let ProfitCurrency::Asset(asset) = self.profit_currency {
+            if !self.tokens.contains_key(&asset) {
+                return Err(anyhow!(
+                    "Profit currency {:?} isn't a reserve of the pool",
+                    asset
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // reuse the reserve configs and e-mode categories cached within the TTL that no
+    // configurator event changed since
+    async fn load_reserve_cache(&mut self, block_number: u64) {
+        if self.reserve_cache_ttl_secs == 0 {
+            return;
+        }
+        let cache = match ReserveCache::<TokenConfig, EModeCategory>::read(
+            &self.reserve_cache_file,
+            self.chain_id,
+            self.config.pool_address,
+        ) {
+            Ok(Some(cache)) => cache,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Ignoring reserve cache: {}", e);
+                return;
+            }
+        };
+        let from_block = cache.block_number();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        let (mut fresh, mut fresh_e_modes) = cache.into_fresh(now, self.reserve_cache_ttl_secs);
+        if fresh.is_empty() && fresh_e_modes.is_empty() {
+            return;
+        }
+        match self
+            .get_reserve_config_changes(from_block.into(), block_number.into())
+            .await
+        {
+            Ok((changed, changed_e_modes)) => {
+                fresh.retain(|asset, _| !changed.contains(asset));
+                fresh_e_modes.retain(|id, _| !changed_e_modes.contains(id));
+            }
+            Err(e) => {
+                warn!(
+                    "Ignoring reserve cache, failed to get config changes since block {}: {}",
+                    from_block, e
+                );
+                return;
+            }
+        }
+        info!(
+            "Reusing {} reserve configs and {} e-mode categories cached from {}",
+            fresh.len(),
+            fresh_e_modes.len(),
+            self.reserve_cache_file.display()
+        );
+        for (asset, reserve) in fresh {
+            self.reserves_fetched_at.insert(asset, reserve.fetched_at);
+            self.tokens.insert(asset, reserve.config);
+        }
+        for (id, category) in fresh_e_modes {
+            self.e_mode_categories_fetched_at
+                .insert(id, category.fetched_at);
+            self.e_mode_categories.insert(id, category.config);
+        }
+    }
+
+    // keep the reserve configs, valid up to `block_number`, for the next run
+    fn write_reserve_cache(&self, block_number: u64) {
+        if self.reserve_cache_ttl_secs == 0 {
+            return;
+        }
+        let mut cache = ReserveCache::new(self.chain_id, self.config.pool_address, block_number);
+        for (asset, token) in &self.tokens {
+            if let Some(fetched_at) = self.reserves_fetched_at.get(asset) {
+                cache.insert(*asset, token.clone(), *fetched_at);
+            }
+        }
+        for (id, category) in &self.e_mode_categories {
+            if let Some(fetched_at) = self.e_mode_categories_fetched_at.get(id) {
+                cache.insert_e_mode_category(*id, category.clone(), *fetched_at);
+            }
+        }
+        if let Err(e) = cache.write(&self.reserve_cache_file) {
+            error!("Error writing reserve cache: {}", e);
+        }
+    }
+
+    // reload the reserve list and the configs of `assets` and of reserves not loaded yet,
+    // every reserve's if unset
+    async fn refresh_token_configs(&mut self, assets: Option<&HashSet<Address>>) -> Result<()> {
+        let pool_data =
+            IPoolDataProvider::<M>::new(self.config.pool_data_provider, self.client.clone());
+        let price_oracle = IAaveOracle::<M>::new(self.config.oracle_address, self.client.clone());
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        self.reserves = pool.get_reserves_list().call().await?;
+        let all_tokens = pool_data.get_all_reserves_tokens().await?;
+        let all_a_tokens = pool_data.get_all_a_tokens().await?;
+        info!("all_tokens: {:?}", all_tokens);
+        // dropped reserves
+        self.tokens.retain(|address, _| {
+            all_tokens
+                .iter()
+                .any(|token| token.token_address == *address)
+        });
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        for (token, a_token) in zip(all_tokens, all_a_tokens).filter(|(token, _)| {
+            assets.is_none_or(|assets| assets.contains(&token.token_address))
+                || !self.tokens.contains_key(&token.token_address)
+        }) {
+            match pool_data
+                .get_reserve_configuration_data(token.token_address)
+                .await
+            {
+                Ok((decimals, ltv, threshold, bonus, reserve, _, _, _, active, frozen)) => {
+                    match pool_data
+                        .get_liquidation_protocol_fee(token.token_address)
+                        .await
+                    {
+                        Ok(protocol_fee) => {
+                            let price_source = price_oracle
+                                .get_source_of_asset(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let debt_ceiling = pool_data
+                                .get_debt_ceiling(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let siloed_borrowing = pool_data
+                                .get_siloed_borrowing(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let paused = pool_data
+                                .get_paused(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let e_mode_category = pool_data
+                                .get_reserve_e_mode_category(token.token_address)
+                                .await
+                                .unwrap_or_default()
+                                .low_u32() as u8;
+                            let (borrow_cap, supply_cap) = pool_data
+                                .get_reserve_caps(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let total_supply = pool_data
+                                .get_a_token_total_supply(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let total_debt = pool_data
+                                .get_total_debt(token.token_address)
+                                .await
+                                .unwrap_or_default();
+                            let decimals = decimals.low_u64();
+                            let supply_cap_utilization_bps =
+                                cap_utilization_bps(total_supply, supply_cap, decimals);
+                            let borrow_cap_utilization_bps =
+                                cap_utilization_bps(total_debt, borrow_cap, decimals);
+                            info!(
+                                "{} supply cap utilization: {:?} bps, borrow cap utilization: {:?} bps",
+                                token.symbol, supply_cap_utilization_bps, borrow_cap_utilization_bps
+                            );
+                            if self.receive_a_token.contains(&token.token_address)
+                                && supply_cap_utilization_bps.is_some_and(|bps| bps >= 10000)
+                            {
+                                warn!(
+                                    "{} is at its supply cap, aTokens seized from it can't be supplied back once withdrawn",
+                                    token.symbol
+                                );
+                            }
+                            self.reserves_fetched_at.insert(token.token_address, now);
+                            self.tokens.insert(
+                                token.token_address,
+                                TokenConfig {
+                                    address: token.token_address,
+                                    a_address: a_token.token_address,
+                                    decimals,
+                                    ltv: ltv.low_u64(),
+                                    liquidation_threshold: threshold.low_u64(),
+                                    liquidation_bonus: bonus.low_u64(),
+                                    reserve_factor: reserve.low_u64(),
+                                    protocol_fee: protocol_fee.low_u64(),
+                                    symbol: token.symbol,
+                                    price_source,
+                                    debt_ceiling,
+                                    siloed_borrowing,
+                                    active,
+                                    frozen,
+                                    paused,
+                                    supply_cap,
+                                    borrow_cap,
+                                    supply_cap_utilization_bps,
+                                    borrow_cap_utilization_bps,
+                                    e_mode_category,
+                                    e_mode_liquidation_bonus: None,
+                                },
+                            );
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to get liquidation protocol fee for token {}: {}",
+                                token.token_address, e
+                            );
+                        }
+                    }
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to get reserve configuration data for token {}: {}",
+                        token.token_address, e
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // reload the e-mode categories in `ids` and those of reserves not loaded yet, drop those
+    // no reserve is in anymore, then give each reserve its category's bonus
+    async fn refresh_e_mode_categories(&mut self, ids: &HashSet<u8>) {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        let categories: HashSet<u8> = self
+            .tokens
+            .values()
+            .map(|token| token.e_mode_category)
+            .filter(|id| *id != 0)
+            .collect();
+        self.e_mode_categories
+            .retain(|id, _| categories.contains(id));
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        for id in categories {
+            if self.e_mode_categories.contains_key(&id) && !ids.contains(&id) {
+                continue;
+            }
+            match pool.get_e_mode_category_data(id).call().await {
+                Ok(category) => {
+                    info!(
+                        "E-mode category {} {}: liquidation bonus {}",
+                        id, category.label, category.liquidation_bonus
+                    );
+                    self.e_mode_categories_fetched_at.insert(id, now);
+                    self.e_mode_categories.insert(
+                        id,
+                        EModeCategory {
+                            ltv: category.ltv.into(),
+                            liquidation_threshold: category.liquidation_threshold.into(),
+                            liquidation_bonus: category.liquidation_bonus.into(),
+                            label: category.label,
+                        },
+                    );
+                }
+                Err(e) => {
+                    error!("Failed to get e-mode category {}: {}", id, e);
+                }
+            }
+        }
+        for token in self.tokens.values_mut() {
+            token.e_mode_liquidation_bonus = self
+                .e_mode_categories
+                .get(&token.e_mode_category)
+                .map(|category| category.liquidation_bonus);
+        }
+    }
+
+    // 8 decimals of precision
+    async fn get_asset_price_eth(&self, asset: &Address, pool_state: &PoolState) -> Result<U256> {
+        // 1:1 for weth
+        let weth_address = self.config.weth_address;
+        if asset.eq(&weth_address) {
+            return Ok(U256::from(PRICE_ONE));
+        }
+
+        // usd / token
+        let usd_price = pool_state
+            .prices
+            .get(asset)
+            .ok_or(anyhow!("No price found for asset {:?}", asset))?;
+        let asset_symbol = self
+            .tokens
+            .get(asset)
+            .map(|t| t.symbol.clone())
+            .unwrap_or_else(|| "Unknown".to_string());
+        info!("{}/USD: {}, asset: {:?}", asset_symbol, usd_price, asset);
+        // usd / eth
+        let usd_price_eth = pool_state
+            .prices
+            .get(&weth_address)
+            .ok_or(anyhow!("No price found for asset {}", weth_address))?;
+        info!("WETH/USD: {}, asset: {:?}", usd_price_eth, weth_address);
+        // usd / token * eth / usd = eth / token
+        let x = usd_price * U256::from(PRICE_ONE) / usd_price_eth;
+        info!("{}/ETH: {}", asset_symbol, x);
+        Ok(x)
+    }
+
+    async fn get_best_liquidation_op(
+        &mut self,
+        underwater: Vec<(Address, U256)>,
+        pool_state: &PoolState,
+    ) -> Result<Option<LiquidationOpportunity>> {
+        if underwater.is_empty() {
+            return Err(anyhow!("No underwater borrowers found"));
+        }
+
+        let ops = self.evaluate_ops(underwater, pool_state).await?;
+        Ok(ops.into_iter().max_by_key(|op| self.score(op)))
+    }
+
+    // profit discounted by the weighted risks of not landing, illiquid collateral and reverting
+    fn score(&self, op: &LiquidationOpportunity) -> I256 {
+        let gas_bid_bps = self
+            .submission_policy
+            .decide(op.confidence, self.bid_percentage(op))
+            .map_or(0, |decision| decision.bid_percentage * 100);
+        score(
+            op.profit_eth,
+            &ScoreSignals {
+                health_factor: op.health_factor,
+                gas_bid_bps,
+                route_quality_bps: op.route_quality_bps,
+                simulation: op.simulation,
+            },
+            &self.scoring,
+        )
+    }
+
+    // every opportunity that could be built for the underwater borrowers
+    async fn evaluate_ops(
+        &mut self,
+        underwater: Vec<(Address, U256)>,
+        pool_state: &PoolState,
+    ) -> Result<Vec<LiquidationOpportunity>> {
+        info!("Found {} underwater borrowers", underwater.len());
+        self.tick.ops_evaluated += underwater.len() as u64;
+
+        // read the positions of the whole set up front in a few multicalls
+        let users: Vec<Address> = underwater.iter().map(|(borrower, _)| *borrower).collect();
+        let a_tokens: HashMap<Address, Address> = self
+            .tokens
+            .iter()
+            .map(|(asset, token)| (*asset, token.a_address))
+            .collect();
+        let snapshot = &self
+            .data_source
+            .snapshot(&users, &self.reserves, &a_tokens)
+            .await?;
+
+        // evaluations still quote swaps and prices, so run a few at once
+        let this = &*self;
+        let results: Vec<Result<LiquidationOpportunity>> = stream::iter(underwater)
+            .map(|(borrower, health_factor)| async move {
+                let borrower = this
+                    .borrowers
+                    .get(&borrower)
+                    .ok_or(anyhow!("Borrower not found"))?;
+                this.get_liquidation_opportunity(
+                    snapshot,
+                    borrower,
+                    &health_factor,
+                    pool_state,
+                    new_correlation_id(),
+                )
+                .await
+            })
+            .buffer_unordered(self.evaluation_concurrency)
+            .collect()
+            .await;
+
+        let mut ops = Vec::new();
+        for result in results {
+            match result {
+                Ok(op) => ops.push(op),
+                Err(e) => {
+                    let message = e.to_string();
+                    info!("Liquidation op failed {}", describe_error(&message));
+                    if let Some(reason) = revert_reason(&message) {
+                        *self.tick.reverts.entry(reason).or_default() += 1;
+                    }
+                }
+            }
+        }
+        Ok(ops)
+    }
+
+    // convert the minimum position size, approval notional and exposure cap to the oracle's
+    // base currency, USD on every deployment
+    async fn load_min_debt(&mut self) -> Result<()> {
+        if self.min_debt_usd == 0 && self.approval.is_none() && self.exposure.max_usd().is_none() {
+            return Ok(());
+        }
+        let price_oracle = IAaveOracle::<M>::new(self.config.oracle_address, self.client.clone());
+        let base_currency_unit = price_oracle.base_currency_unit().call().await?;
+        if let Some(approval) = &self.approval {
+            approval
+                .lock()
+                .expect("approval gate poisoned")
+                .set_base_currency_unit(base_currency_unit);
+        }
+        self.exposure.set_base_currency_unit(base_currency_unit);
+        if self.min_debt_usd == 0 {
+            return Ok(());
+        }
+        self.min_debt_base = base_currency_unit * self.min_debt_usd;
+        info!(
+            "Skipping borrowers owing less than ${} ({} in base currency)",
+            self.min_debt_usd, self.min_debt_base
+        );
+        Ok(())
+    }
+
+    // L2 pools only allow liquidations once their sequencer has been up for a grace period
+    async fn load_sequencer_oracle(&mut self) -> Result<()> {
+        let pool = Pool::<M>::new(self.config.pool_address, self.client.clone());
+        let addresses_provider = IPoolAddressesProvider::<M>::new(
+            pool.addresses_provider().call().await?,
+            self.client.clone(),
+        );
+        let sentinel_address = addresses_provider
+            .get_price_oracle_sentinel()
+            .call()
+            .await?;
+        if sentinel_address == Address::zero() {
+            return Ok(());
+        }
+
+        let sentinel = IPriceOracleSentinel::<M>::new(sentinel_address, self.client.clone());
+        self.sequencer_oracle = sentinel.get_sequencer_oracle().call().await?;
+        self.sequencer_grace_period = sentinel.get_grace_period().call().await?;
+        info!(
+            "Using sequencer uptime feed {:?}, grace period {}s",
+            self.sequencer_oracle, self.sequencer_grace_period
+        );
+        Ok(())
+    }
+
+    // check the sequencer uptime feed, flagging downtime to the logs and the admin API
+    async fn is_sequencer_up(&mut self) -> bool {
+        if self.sequencer_oracle == Address::zero() {
+            return true;
+        }
+
+        let oracle = ISequencerOracle::<M>::new(self.sequencer_oracle, self.client.clone());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Invalid timestamp")
+            .as_secs();
+        let up = match oracle.latest_round_data().call().await {
+            Ok((_, answer, started_at, _, _)) => {
+                is_up_and_grace_period_passed(answer, started_at, self.sequencer_grace_period, now)
+            }
+            Err(e) => {
+                error!("Error reading sequencer uptime feed: {}", e);
+                false
+            }
+        };
+
+        if !up {
+            warn!(
+                sequencer_down = true,
+                "Sequencer is down or in its grace period, only liquidating health factors below 0.95"
+            );
+        } else if self.sequencer_down {
+            info!("Sequencer is back up");
+        }
+        self.sequencer_down = !up;
+        if let Ok(mut status) = self.status.write() {
+            status.sequencer_down = self.sequencer_down;
+        }
+        up
+    }
+
+    // refresh the active, frozen and paused flags, which the pool admin can flip at any time
+    async fn update_reserve_flags(&mut self) -> Result<()> {
+        let pool_data =
+            IPoolDataProvider::<M>::new(self.config.pool_data_provider, self.client.clone());
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+        let assets: Vec<Address> = self.tokens.keys().copied().collect();
+
+        for asset in assets.iter() {
+            multicall.add_call(pool_data.get_reserve_configuration_data(*asset), false);
+        }
+        let configurations: Vec<ReserveConfigurationData> = multicall.call_array().await?;
+        multicall.clear_calls();
+        for asset in assets.iter() {
+            multicall.add_call(pool_data.get_paused(*asset), false);
+        }
+        let paused: Vec<bool> = multicall.call_array().await?;
+
+        for ((asset, configuration), paused) in zip(zip(assets.iter(), configurations), paused) {
+            let (_, _, _, _, _, _, _, _, active, frozen) = configuration;
+            if let Some(token) = self.tokens.get_mut(asset) {
+                if token.is_liquidatable() != (active && !paused) {
+                    info!(
+                        "Reserve {} is now active: {}, frozen: {}, paused: {}",
+                        token.symbol, active, frozen, paused
+                    );
+                }
+                token.active = active;
+                token.frozen = frozen;
+                token.paused = paused;
+            }
+        }
+        // a pool-wide pause pauses every reserve
+        self.pool_paused = !self.tokens.is_empty() && self.tokens.values().all(|t| t.paused);
+        Ok(())
+    }
+
+    async fn get_prices(&self) -> Result<HashMap<Address, U256>> {
+        self.get_prices_from(self.config.oracle_address).await
+    }
+
+    // prices of every reserve from an oracle exposing `getAssetPrice`
+    async fn get_prices_from(&self, oracle: Address) -> Result<HashMap<Address, U256>> {
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+        let mut prices = HashMap::new();
+        let price_oracle = IAaveOracle::<M>::new(oracle, self.client.clone());
+
+        for token_address in self.tokens.keys() {
+            multicall.add_call(price_oracle.get_asset_price(*token_address), false);
+        }
+
+        let result: Vec<U256> = multicall.call_array().await?;
+        for (token_address, price) in zip(self.tokens.keys(), result) {
+            prices.insert(*token_address, price);
+        }
+
+        Ok(prices)
+    }
+
+    async fn get_pool_state(&self) -> Result<PoolState> {
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+        let prices = self.get_prices().await?;
+
+        // not every price source is a chainlink aggregator, so allow these to fail
+        let mut price_timestamps = HashMap::new();
+        for token in self.tokens.values() {
+            let source = AggregatorInterface::<M>::new(token.price_source, self.client.clone());
+            multicall.add_call(source.latest_timestamp(), true);
+        }
+        let result = multicall.call_raw().await?;
+        for (token_address, timestamp) in zip(self.tokens.keys(), result) {
+            if let Ok(Token::Uint(timestamp)) = timestamp {
+                price_timestamps.insert(*token_address, timestamp.low_u64());
+            }
+        }
+        multicall.clear_calls();
+
+        Ok(PoolState {
+            prices,
+            price_timestamps,
+            block_number: self.client.get_block_number().await?.as_u64(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Invalid timestamp")
+                .as_secs(),
+            pending: false,
+        })
+    }
+
+    #[instrument(skip_all, fields(correlation_id = %correlation_id, borrower = ?borrower.address))]
+    async fn get_liquidation_opportunity(
+        &self,
+        data_source: &dyn AaveDataSource,
+        borrower: &Borrower,
+        health_factor: &U256,
+        pool_state: &PoolState,
+        correlation_id: String,
+    ) -> Result<LiquidationOpportunity> {
+        let borrower_address = &borrower.address;
+        if !self.filter.allows_borrower(borrower_address) {
+            return Err(anyhow!(
+                "Borrower {:?} is blacklisted or outside shard {}",
+                borrower_address,
+                self.filter.shard
+            ));
+        }
+        // further pairs ride along only where the batch can be simulated
+        let batch = !self.use_aave_liquidator && !pool_state.pending;
+        let mut plans = plan_liquidations(
+            data_source,
+            &self.tokens,
+            &self.reserves,
+            &self.filter,
+            self.config.liquidation_rules,
+            borrower_address,
+            health_factor,
+            &pool_state.prices,
+            if batch { self.max_liquidation_legs } else { 1 },
+        )
+        .await?;
+        let leg_plans = plans.split_off(1);
+        let plan = plans.remove(0);
+        let collateral_received = plan.collateral_received();
+        let LiquidationPlan {
+            collateral: collateral_address,
+            debt: debt_address,
+            debt_to_cover,
+            repaid,
+            collateral_to_liquidate,
+            collateral_unit,
+            debt_unit,
+            ..
+        } = plan;
+        let collateral_address = &collateral_address;
+        let debt_address = &debt_address;
+
+        let collateral_symbol = self
+            .tokens
+            .get(collateral_address)
+            .map(|c| c.symbol.clone())
+            .unwrap_or_default();
+        let debt_symbol = self
+            .tokens
+            .get(debt_address)
+            .map(|d| d.symbol.clone())
+            .unwrap_or_default();
+
+        let mut op = LiquidationOpportunity {
+            borrower: borrower_address.clone(),
+            collateral: collateral_address.clone(),
+            debt: debt_address.clone(),
+            debt_to_cover,
+            profit_eth: I256::from(0),
+            collateral_symbol,
+            debt_symbol,
+            profit_factor: I256::from(0),
+            confidence: 0,
+            correlation_id,
+            uniswap_fee: DEFAULT_UNISWAP_FEE,
+            swap_path: None,
+            flash_swap: None,
+            health_factor: *health_factor,
+            route_quality_bps: 0,
+            simulation: SimulationResult::Skipped,
+            legs: Vec::new(),
+            collateral_price: pool_state
+                .prices
+                .get(collateral_address)
+                .copied()
+                .unwrap_or_default(),
+            debt_price: pool_state
+                .prices
+                .get(debt_address)
+                .copied()
+                .unwrap_or_default(),
+            profit_rate: self.profit_rate(&pool_state.prices),
+            valued_at: Some(ValuedAt {
+                block_number: pool_state.block_number,
+                timestamp: pool_state.timestamp,
+            }),
+            l1_fee_eth: U256::zero(),
+        };
+
+        let asset_price_in_eth = self
+            .get_asset_price_eth(collateral_address, pool_state)
+            .await?;
+        let debt_price_in_eth = self.get_asset_price_eth(debt_address, pool_state).await?;
+
+        // flash-swap through the cheapest Uniswap V3 pool that can be repaid with the collateral
+        let mut flash_swap_route_quality_bps = FLASH_SWAP_ROUTE_QUALITY_BPS;
+        // collateral the quoted route swaps for the debt, price impact included
+        let mut quoted_amount_in = None;
+        if !self.use_aave_liquidator && self.config.uniswap_quoter != Address::zero() {
+            let amount_in = self.select_route(&mut op, collateral_received).await?;
+            quoted_amount_in = Some(amount_in);
+            match (&op.swap_path, &op.flash_swap) {
+                (Some(path), _) => info!(
+                    "Using the path through {:?} on fee tiers {:?}, swapping {} of {} collateral received",
+                    path.tokens, path.fees, amount_in, collateral_received
+                ),
+                (_, Some(swap)) => info!(
+                    "Using a flash loan repaid on {}, swapping {} of {} collateral received",
+                    swap, amount_in, collateral_received
+                ),
+                _ => info!(
+                    "Using the {} fee tier, swapping {} of {} collateral received",
+                    op.uniswap_fee, amount_in, collateral_received
+                ),
+            }
+            flash_swap_route_quality_bps = CONFIDENCE_ONE;
+        }
+
+        let simulation;
+        let route_quality_bps;
+        if self.use_aave_liquidator || pool_state.pending {
+            (op.profit_eth, op.profit_factor) = oracle_profit(
+                asset_price_in_eth,
+                collateral_received,
+                collateral_unit,
+                debt_price_in_eth,
+                self.debt_repaid(&op.first_leg())?,
+                debt_unit,
+            )?;
+            if debt_to_cover == U256::zero() {
+                op.profit_eth = I256::from(0);
+                op.profit_factor = I256::from(0);
+                return Err(anyhow!(
+                    "No debt to cover for borrower {:?}, collateral {:?}, debt {:?}",
+                    borrower_address,
+                    collateral_address,
+                    debt_address
+                ));
+            }
+            info!(
+                "Using oracle prices - profit in ETH: {}, profit factor: {}%",
+                op.profit_eth, op.profit_factor
+            );
+            // mid prices overestimate what thin pools pay out, so value the swap as quoted
+            if let Some(amount_in) = quoted_amount_in {
+                let oracle_profit_eth = op.profit_eth;
+                (op.profit_eth, op.profit_factor) = quoted_profit(
+                    asset_price_in_eth,
+                    collateral_received,
+                    amount_in,
+                    collateral_unit,
+                )?;
+                info!(
+                    "Using the quoted swap - profit in ETH: {} ({} at oracle prices), profit factor: {}%",
+                    op.profit_eth, oracle_profit_eth, op.profit_factor
+                );
+            }
+            self.build_liquidation(&op).await?;
+            simulation = SimulationResult::Skipped;
+            // with the Aave liquidator the debt is repaid from the EOA balance, so there is no swap route
+            route_quality_bps = if self.use_aave_liquidator {
+                CONFIDENCE_ONE
+            } else {
+                flash_swap_route_quality_bps
+            };
+        } else {
+            let call = self.build_liquidation_call(&op).await?;
+            let gain = match call.call().await {
+                Ok(gain) => gain,
+                Err(e) => return Err(self.explain_failure(&call.tx, e).await),
+            };
+            op.profit_eth = gain
+                .checked_mul(I256::try_from(asset_price_in_eth)?)
+                .ok_or(anyhow!("Profit overflow"))?
+                / I256::from(PRICE_ONE);
+            simulation = SimulationResult::Succeeded;
+            route_quality_bps = flash_swap_route_quality_bps;
+            // batches only swap through single Uniswap V3 pools
+            if !leg_plans.is_empty() && op.swap_path.is_none() && op.flash_swap.is_none() {
+                self.add_liquidation_legs(&mut op, leg_plans, pool_state)
+                    .await;
+            }
+        }
+
+        // rollups charge for posting the tx to L1 on top of its gas, so the min profit and
+        // the score see what's left after it
+        if self.l1_fee != L1FeeModel::None {
+            let tx = self.build_liquidation(&op).await?;
+            op.l1_fee_eth = self.l1_fee_eth(&tx).await?;
+            op.profit_eth -= I256::from_raw(op.l1_fee_eth);
+            info!(
+                "L1 data fee in ETH: {}, profit in ETH after it: {}",
+                op.l1_fee_eth, op.profit_eth
+            );
+        }
+
+        // the older of the two prices bounds how fresh the opportunity is
+        let price_age_secs = [collateral_address, debt_address]
+            .iter()
+            .map(|asset| match pool_state.price_timestamps.get(asset) {
+                Some(timestamp) => pool_state.timestamp.saturating_sub(*timestamp),
+                None => MAX_PRICE_AGE_SECS,
+            })
+            .max()
+            .unwrap_or(MAX_PRICE_AGE_SECS);
+        let blocks_since_health_check = pool_state
+            .block_number
+            .checked_sub(self.last_health_check_block)
+            .unwrap_or(MAX_HEALTH_CHECK_AGE_BLOCKS);
+        op.confidence = confidence_score(&ConfidenceInputs {
+            price_age_secs,
+            blocks_since_health_check,
+            simulation,
+            route_quality_bps,
+        });
+        op.route_quality_bps = route_quality_bps;
+        op.simulation = simulation;
+
+        info!(
+            "Found opportunity - borrower: {:?}, collateral: {:?}({}), debt: {:?}({}), collateral_to_liquidate: {:?}, debt_to_cover: {:?} (variable: {:?}, stable: {:?}), profit_eth: {:?}, confidence: {}",
+            op.borrower, collateral_address, op.collateral_symbol, debt_address, op.debt_symbol, collateral_to_liquidate, debt_to_cover, repaid.variable, repaid.stable, op.profit_eth, op.confidence
+        );
+
+        Ok(op)
+    }
+
+    // L1 data fee of sending `tx` in ETH with 8 decimals, rounded up
+    async fn l1_fee_eth(&self, tx: &TypedTransaction) -> Result<U256> {
+        let Some(l1_cost) = self.l1_fee.estimate(self.client.clone(), tx).await? else {
+            return Ok(U256::zero());
+        };
+        let gas_price = match l1_cost {
+            L1Cost::Fee(_) => U256::zero(),
+            L1Cost::Gas(_) => self.client.get_gas_price().await?,
+        };
+        let unit = U256::from(PROFIT_UNIT_WEI);
+        Ok((l1_cost.wei(gas_price) + unit - 1) / unit)
+    }
+
+    // the error of a failed simulation of `tx` with its revert reason named, and where it
+    // reverted if the tracer is set up. The whole call tree goes to the opportunity's log.
+    async fn explain_failure(&self, tx: &TypedTransaction, error: impl Display) -> anyhow::Error {
+        let error = describe_error(&error.to_string());
+        let Some(tracer) = &self.tracer else {
+            return anyhow!(error);
+        };
+        match tracer.trace(&*self.client, tx).await {
+            Ok(trace) => {
+                info!("Trace of the failed simulation:\n{}", trace.render());
+                match trace.root_cause() {
+                    Some(cause) if !error.contains(&cause) => {
+                        anyhow!("{}, reverted with {}", error, cause)
+                    }
+                    _ => anyhow!(error),
+                }
+            }
+            Err(e) => {
+                warn!("Failed to trace the simulation: {}", e);
+                anyhow!(error)
+            }
+        }
+    }
+
+    async fn build_liquidation_call(
+        &self,
+        op: &LiquidationOpportunity,
+    ) -> Result<ContractCall<M, I256>> {
+        let liquidator = Liquidator::new(self.liquidator, self.client.clone());
+        let (data0, data1) = pack_liquidation_args(op.borrower, op.debt_to_cover, false);
+
+        if let Some(swap) = &op.flash_swap {
+            return Ok(liquidator.liquidate_flash_loan(
+                op.collateral,
+                op.debt,
+                op.debt_to_cover,
+                data0,
+                data1,
+                swap.encode(),
+            ));
+        }
+        if let Some(path) = &op.swap_path {
+            return Ok(liquidator.liquidate_path(
+                op.collateral,
+                op.debt,
+                path.encode_exact_output(),
+                op.debt_to_cover,
+                data0,
+                data1,
+            ));
+        }
+        Ok(liquidator.liquidate(
+            op.collateral,
+            op.debt,
+            op.uniswap_fee,
+            op.debt_to_cover,
+            data0,
+            data1,
+        ))
+    }
+
+    // liquidate the borrower's other pairs in the same tx if the batch simulates at a
+    // higher profit than the first pair alone
+    async fn add_liquidation_legs(
+        &self,
+        op: &mut LiquidationOpportunity,
+        plans: Vec<LiquidationPlan>,
+        pool_state: &PoolState,
+    ) {
+        for plan in plans {
+            let mut leg = LiquidationLeg {
+                collateral: plan.collateral,
+                debt: plan.debt,
+                debt_to_cover: plan.debt_to_cover,
+                uniswap_fee: DEFAULT_UNISWAP_FEE,
+            };
+            if self.config.uniswap_quoter != Address::zero() {
+                match self
+                    .select_uniswap_fee(&leg, plan.collateral_received())
+                    .await
+                {
+                    Ok((fee, _)) => leg.uniswap_fee = fee,
+                    Err(e) => {
+                        info!("Skipping leg of borrower {:?}: {}", op.borrower, e);
+                        continue;
+                    }
+                }
+            }
+            op.legs.push(leg);
+        }
+        if op.legs.is_empty() {
+            return;
+        }
+
+        let profit_eth = match self.simulate_liquidation_batch(op, pool_state).await {
+            Ok(profit_eth) => profit_eth,
+            Err(e) => {
+                info!("Batch liquidation of {:?} failed: {}", op.borrower, e);
+                I256::from(0)
+            }
+        };
+        if profit_eth > op.profit_eth {
+            info!(
+                "Liquidating {} pairs of {:?} in one tx, profit_eth: {:?}",
+                op.legs.len() + 1,
+                op.borrower,
+                profit_eth
+            );
+            op.profit_eth = profit_eth;
+        } else {
+            op.legs.clear();
+        }
+    }
+
+    // profit of liquidating every leg of the opportunity in turn, in ETH
+    async fn simulate_liquidation_batch(
+        &self,
+        op: &LiquidationOpportunity,
+        pool_state: &PoolState,
+    ) -> Result<I256> {
+        let call = self.build_liquidation_batch(op).await?;
+        let gains = match call.call().await {
+            Ok(gains) => gains,
+            Err(e) => return Err(self.explain_failure(&call.tx, e).await),
+        };
+        let mut profit_eth = I256::from(0);
+        for (leg, gain) in zip(op.all_legs(), gains) {
+            let price = self
+                .get_asset_price_eth(&leg.collateral, pool_state)
+                .await?;
+            profit_eth += gain
+                .checked_mul(I256::try_from(price)?)
+                .ok_or(anyhow!("Profit overflow"))?
+                / I256::from(PRICE_ONE);
+        }
+        Ok(profit_eth)
+    }
+
+    // `liquidationCall` args of a leg packed by the deployment's L2Encoder, as L2Pool takes them
+    async fn encode_liquidation_args(
+        &self,
+        leg: &LiquidationLeg,
+        borrower: Address,
+        receive_a_token: bool,
+    ) -> Result<([u8; 32], [u8; 32])> {
+        let encoder = L2Encoder::new(self.config.l2_encoder, self.client.clone());
+        Ok(encoder
+            .encode_liquidation_call(
+                leg.collateral,
+                leg.debt,
+                borrower,
+                leg.debt_to_cover,
+                receive_a_token,
+            )
+            .call()
+            .await?)
+    }
+
+    // `liquidateBatch` of the opportunity's pair followed by its legs
+    async fn build_liquidation_batch(
+        &self,
+        op: &LiquidationOpportunity,
+    ) -> Result<ContractCall<M, Vec<I256>>> {
+        let mut legs = Vec::new();
+        for leg in op.all_legs() {
+            let (data0, data1) = pack_liquidation_args(op.borrower, leg.debt_to_cover, false);
+            legs.push(leg.to_batch_leg(data0, data1));
+        }
+        let liquidator = Liquidator::new(self.liquidator, self.client.clone());
+        Ok(liquidator.liquidate_batch(legs, op.borrower))
+    }
+
+    // flash-swap the opportunity's debt through the cheapest pool, or through a path via the
+    // wrapped native token if no pool can and multi-hop swaps are on. Returns the
+    // collateral the swap costs.
+    async fn select_route(
+        &self,
+        op: &mut LiquidationOpportunity,
+        max_amount_in: U256,
+    ) -> Result<U256> {
+        let leg = op.first_leg();
+        let e = match self.select_uniswap_fee(&leg, max_amount_in).await {
+            Ok((fee, amount_in)) => {
+                op.uniswap_fee = fee;
+                return Ok(amount_in);
+            }
+            Err(e) => e,
+        };
+        if leg.collateral == leg.debt {
+            return Err(e);
+        }
+        let mut errors = vec![e.to_string()];
+        if self.multi_hop_swaps {
+            match self.select_swap_path(&leg, max_amount_in).await {
+                Ok((path, amount_in)) => {
+                    op.swap_path = Some(path);
+                    return Ok(amount_in);
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if !self.flash_liquidity.is_empty() {
+            match self.select_flash_swap(&leg, max_amount_in).await {
+                Ok((swap, amount_in)) => {
+                    op.flash_swap = Some(swap);
+                    return Ok(amount_in);
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        if self.aggregator_flash_swaps {
+            match self.select_aggregator_swap(&leg, max_amount_in).await {
+                Ok((swap, amount_in)) => {
+                    op.flash_swap = Some(swap);
+                    return Ok(amount_in);
+                }
+                Err(e) => errors.push(e.to_string()),
+            }
+        }
+        Err(anyhow!(errors.join(", ")))
+    }
+
+    // sell the collateral for a Balancer flash loan of the debt along the best aggregator
+    // route, quoted for the Liquidator to send. Aggregators quote exact inputs, so the input
+    // is sized from a quote of all the collateral seized, then quoted again for calldata
+    // selling just that
+    async fn select_aggregator_swap(
+        &self,
+        leg: &LiquidationLeg,
+        max_amount_in: U256,
+    ) -> Result<(FlashSwap, U256)> {
+        let (Some(collateral), Some(debt)) =
+            (self.tokens.get(&leg.collateral), self.tokens.get(&leg.debt))
+        else {
+            return Err(anyhow!(
+                "Unknown reserves {:?} / {:?}",
+                leg.collateral,
+                leg.debt
+            ));
+        };
+        let request = SwapRequest {
+            src_token: leg.collateral,
+            src_decimals: collateral.decimals,
+            dest_token: leg.debt,
+            dest_decimals: debt.decimals,
+            amount: max_amount_in,
+            from: self.liquidator,
+        };
+        let quote = self.swap_router.best_quote(request).await?;
+        let amount_in = curve_amount_in(leg.debt_to_cover, max_amount_in, quote.min_amount_out)
+            .ok_or(anyhow!(
+                "No aggregator route of {}/{} swaps for {} of debt",
+                collateral.symbol,
+                debt.symbol,
+                leg.debt_to_cover
+            ))?;
+        let quote = self
+            .swap_router
+            .best_quote(SwapRequest {
+                amount: amount_in,
+                ..request
+            })
+            .await?;
+        if quote.min_amount_out < leg.debt_to_cover {
+            return Err(anyhow!(
+                "{} route of {}/{} swaps for at least {}, short of {} of debt",
+                quote.aggregator,
+                collateral.symbol,
+                debt.symbol,
+                quote.min_amount_out,
+                leg.debt_to_cover
+            ));
+        }
+        let swap = FlashSwap::Aggregator {
+            router: quote.to,
+            spender: quote.spender,
+            amount_in,
+            data: quote.data,
+        };
+        Ok((swap, amount_in))
+    }
+
+    // quote repaying a Balancer flash loan of the debt with the collateral on every flash
+    // liquidity pool of the pair, picking the one needing the least collateral
+    async fn select_flash_swap(
+        &self,
+        leg: &LiquidationLeg,
+        max_amount_in: U256,
+    ) -> Result<(FlashSwap, U256)> {
+        let quotes = futures::future::join_all(
+            self.flash_liquidity
+                .iter()
+                .filter_map(|venue| Some((venue.pool, venue.indexes(leg.collateral, leg.debt)?)))
+                .map(|(pool, (i, j))| self.quote_flash_swap(pool, i, j, leg, max_amount_in)),
+        )
+        .await;
+        quotes
+            .into_iter()
+            .filter_map(|quote| quote.ok())
+            .filter(|(_, amount_in)| *amount_in <= max_amount_in)
+            .min_by_key(|(_, amount_in)| *amount_in)
+            .ok_or(anyhow!(
+                "No flash liquidity pool of {}/{} can swap for {} of debt",
+                self.symbol(&leg.collateral),
+                self.symbol(&leg.debt),
+                leg.debt_to_cover
+            ))
+    }
+
+    // the swap repaying a flash loan of the leg's debt with coin `i` of `pool` for coin `j`,
+    // and the collateral it costs
+    async fn quote_flash_swap(
+        &self,
+        pool: FlashLiquidityPool,
+        i: usize,
+        j: usize,
+        leg: &LiquidationLeg,
+        max_amount_in: U256,
+    ) -> Result<(FlashSwap, U256)> {
+        match pool {
+            FlashLiquidityPool::Balancer(pool_id) => {
+                // the Vault can't be swapped on during its own flash loan, so the Liquidator
+                // borrows from the pool and owes its premium
+                let owed =
+                    leg.debt_to_cover + percent_mul(leg.debt_to_cover, self.flash_loan_premium)?;
+                let output = self
+                    .call_flash_liquidity(
+                        Address::from_str(BALANCER_VAULT_ADDRESS)?,
+                        encode_query_given_out(
+                            pool_id,
+                            leg.collateral,
+                            leg.debt,
+                            owed,
+                            self.liquidator,
+                        ),
+                    )
+                    .await?;
+                let swap = FlashSwap::Balancer {
+                    pool_id,
+                    max_amount_in,
+                };
+                Ok((swap, decode_query_amount_in(&output)?))
+            }
+            FlashLiquidityPool::Curve(pool) => {
+                let (i, j) = (i as i128, j as i128);
+                // quoted on all the collateral seized, as Curve only quotes inputs
+                let output = self
+                    .call_flash_liquidity(pool, encode_curve_get_dy(i, j, max_amount_in))
+                    .await?;
+                let amount_in = curve_amount_in(
+                    leg.debt_to_cover,
+                    max_amount_in,
+                    decode_curve_get_dy(&output)?,
+                )
+                .ok_or(anyhow!("Not enough liquidity on Curve pool {:?}", pool))?;
+                let swap = FlashSwap::Curve {
+                    pool,
+                    i,
+                    j,
+                    amount_in,
+                };
+                Ok((swap, amount_in))
+            }
+        }
+    }
+
+    async fn call_flash_liquidity(&self, to: Address, data: Bytes) -> Result<Bytes> {
+        let tx = TransactionRequest::new().to(to).data(data).into();
+        Ok(self.client.call(&tx, None).await?)
+    }
+
+    // read the tokens of the flash liquidity pools
+    async fn load_flash_liquidity(&mut self) -> Result<()> {
+        let mut venues = Vec::new();
+        for pool in &self.flash_liquidity_pools {
+            let tokens = match pool {
+                FlashLiquidityPool::Balancer(pool_id) => decode_pool_tokens(
+                    &self
+                        .call_flash_liquidity(
+                            Address::from_str(BALANCER_VAULT_ADDRESS)?,
+                            encode_get_pool_tokens(*pool_id),
+                        )
+                        .await?,
+                )?,
+                FlashLiquidityPool::Curve(address) => {
+                    let mut coins = Vec::new();
+                    // coins past the last one revert
+                    for i in 0..MAX_CURVE_COINS {
+                        match self
+                            .call_flash_liquidity(*address, encode_curve_coins(i))
+                            .await
+                        {
+                            Ok(output) => coins.push(decode_curve_coin(&output)?),
+                            Err(_) => break,
+                        }
+                    }
+                    coins
+                }
+            };
+            if tokens.len() < 2 {
+                return Err(anyhow!(
+                    "Flash liquidity pool {} has {} tokens",
+                    pool,
+                    tokens.len()
+                ));
+            }
+            info!(
+                "Flash liquidity pool {}: {:?}",
+                pool,
+                tokens
+                    .iter()
+                    .map(|token| self.symbol(token))
+                    .collect::<Vec<_>>()
+            );
+            venues.push(FlashLiquidityVenue {
+                pool: *pool,
+                tokens,
+            });
+        }
+        self.flash_liquidity = venues;
+        Ok(())
+    }
+
+    // quote flash-borrowing the debt against the collateral through the wrapped native token,
+    // on the cheapest fee tier of each hop
+    async fn select_swap_path(
+        &self,
+        leg: &LiquidationLeg,
+        max_amount_in: U256,
+    ) -> Result<(SwapPath, U256)> {
+        let via = self.config.weth_address;
+        if via == leg.collateral || via == leg.debt {
+            return Err(anyhow!("No token to route through"));
+        }
+        // exact output swaps are quoted from the last hop back
+        let (debt_fee, via_amount) = pick_fee_tier(
+            &self.quote_fee_tiers(via, leg.debt, leg.debt_to_cover).await,
+            U256::MAX,
+        )
+        .ok_or(anyhow!(
+            "No Uniswap V3 pool of {}/{} can swap {} of debt",
+            self.symbol(&via),
+            self.symbol(&leg.debt),
+            leg.debt_to_cover
+        ))?;
+        let (collateral_fee, amount_in) = pick_fee_tier(
+            &self.quote_fee_tiers(leg.collateral, via, via_amount).await,
+            max_amount_in,
+        )
+        .ok_or(anyhow!(
+            "No Uniswap V3 pool of {}/{} can swap {} of {}",
+            self.symbol(&leg.collateral),
+            self.symbol(&via),
+            via_amount,
+            self.symbol(&via)
+        ))?;
+        let path = SwapPath {
+            tokens: vec![leg.collateral, via, leg.debt],
+            fees: vec![collateral_fee, debt_fee],
+        };
+        Ok((path, amount_in))
+    }
+
+    // quote flash-borrowing the debt against the collateral on every fee tier
+    async fn select_uniswap_fee(
+        &self,
+        leg: &LiquidationLeg,
+        max_amount_in: U256,
+    ) -> Result<(u32, U256)> {
+        // same-asset legs flash-loan the debt from the pool rather than swapping for it
+        if leg.collateral == leg.debt {
+            let amount_in = self.debt_repaid(leg)?;
+            if amount_in > max_amount_in {
+                return Err(anyhow!(
+                    "Flash loan of {} of debt costs {}, more than the {} of collateral seized",
+                    leg.debt_to_cover,
+                    amount_in,
+                    max_amount_in
+                ));
+            }
+            return Ok((DEFAULT_UNISWAP_FEE, amount_in));
+        }
+        let quotes = self
+            .quote_fee_tiers(leg.collateral, leg.debt, leg.debt_to_cover)
+            .await;
+        pick_fee_tier(&quotes, max_amount_in).ok_or(anyhow!(
+            "No Uniswap V3 pool of {}/{} can flash-swap {} of debt",
+            self.symbol(&leg.collateral),
+            self.symbol(&leg.debt),
+            leg.debt_to_cover
+        ))
+    }
+
+    // `token_in` swapped for `amount_out` of `token_out` on each fee tier, none where the
+    // pool doesn't exist
+    async fn quote_fee_tiers(
+        &self,
+        token_in: Address,
+        token_out: Address,
+        amount_out: U256,
+    ) -> Vec<(u32, Option<U256>)> {
+        let quoter = IQuoterV2::new(self.config.uniswap_quoter, self.client.clone());
+        futures::future::join_all(UNISWAP_FEE_TIERS.iter().map(|fee| {
+            let call = quoter.quote_exact_output_single(QuoteExactOutputSingleParams {
+                token_in,
+                token_out,
+                amount: amount_out,
+                fee: *fee,
+                sqrt_price_limit_x96: U256::zero(),
+            });
+            // pools that don't exist or lack liquidity revert
+            async move {
+                (
+                    *fee,
+                    call.call().await.ok().map(|(amount_in, ..)| amount_in),
+                )
+            }
+        }))
+        .await
+    }
+
+    fn symbol(&self, asset: &Address) -> String {
+        self.tokens
+            .get(asset)
+            .map(|token| token.symbol.clone())
+            .unwrap_or_default()
+    }
+
+    // debt a leg costs the liquidator: the Liquidator contract flash-loans same-asset debt from
+    // the pool, paying its premium on top
+    fn debt_repaid(&self, leg: &LiquidationLeg) -> Result<U256> {
+        if self.use_aave_liquidator || leg.collateral != leg.debt {
+            return Ok(leg.debt_to_cover);
+        }
+        Ok(leg.debt_to_cover + percent_mul(leg.debt_to_cover, self.flash_loan_premium)?)
+    }
+
+    // the Liquidator contract swaps the seized collateral to repay its flash swap, so only
+    // the Aave path can keep it as aTokens
+    fn receive_a_token(&self, collateral: &Address) -> bool {
+        self.use_aave_liquidator && self.receive_a_token.contains(collateral)
+    }
+
+    async fn build_liquidation(
+        &self,
+        op: &LiquidationOpportunity,
+    ) -> Result<TypedTransaction, anyhow::Error> {
+        if self.use_aave_liquidator && self.config.l2_encoder != Address::zero() {
+            // the packed L2Pool call takes two words of calldata instead of five, cutting the
+            // L1 data fee
+            let pool = L2Pool::new(self.config.pool_address, self.client.clone());
+            let (data0, data1) = self
+                .encode_liquidation_args(
+                    &op.first_leg(),
+                    op.borrower,
+                    self.receive_a_token(&op.collateral),
+                )
+                .await?;
+            let mut call = pool.liquidation_call(data0, data1);
+            Ok(call.tx.set_chain_id(self.chain_id).clone())
+        } else if self.use_aave_liquidator {
+            let pool = Pool::new(self.config.pool_address, self.client.clone());
+            let mut call = pool.liquidation_call(
+                op.collateral,
+                op.debt,
+                op.borrower,
+                op.debt_to_cover,
+                self.receive_a_token(&op.collateral),
+            );
+            Ok(call.tx.set_chain_id(self.chain_id).clone())
+        } else if !op.legs.is_empty() {
+            let mut call = self.build_liquidation_batch(op).await?;
+            Ok(call.tx.set_chain_id(self.chain_id).clone())
+        } else {
+            let mut call = self.build_liquidation_call(op).await?;
+            Ok(call.tx.set_chain_id(self.chain_id).clone())
+        }
+    }
+
+    // swap collateral for enough debt to bring the partner back to the target health factor
+    #[instrument(skip_all, fields(correlation_id = %correlation_id, partner = ?partner.address))]
+    async fn build_repay_with_collateral(
+        &self,
+        partner: &PartnerAccount,
+        adapter: Address,
+        correlation_id: &str,
+    ) -> Result<Option<TypedTransaction>> {
+        let (Some(collateral), Some(debt)) = (partner.collateral, partner.debt) else {
+            return Err(anyhow!("Repay path needs a collateral and a debt asset"));
+        };
+        let pool = Pool::new(self.config.pool_address, self.client.clone());
+        let (total_collateral_base, total_debt_base, _, liquidation_threshold, _, health_factor) =
+            pool.get_user_account_data(partner.address).call().await?;
+        let watchlist_health_factor =
+            U256::from(self.watchlist_health_factor_bps) * U256::exp10(14);
+        if total_debt_base.is_zero() || health_factor >= watchlist_health_factor {
+            return Ok(None);
+        }
+
+        let repay_base = repay_amount_base(
+            total_collateral_base,
+            total_debt_base,
+            liquidation_threshold.as_u64(),
+            self.target_health_factor_bps,
+        );
+        if repay_base.is_zero() {
+            return Ok(None);
+        }
+        let (Some(collateral_config), Some(debt_config)) =
+            (self.tokens.get(&collateral), self.tokens.get(&debt))
+        else {
+            return Err(anyhow!("Unknown reserves {:?} / {:?}", collateral, debt));
+        };
+        let prices = self.get_prices().await?;
+        let debt_price = prices
+            .get(&debt)
+            .filter(|price| !price.is_zero())
+            .ok_or(anyhow!("No price for {}", debt_config.symbol))?;
+        let collateral_price = prices
+            .get(&collateral)
+            .filter(|price| !price.is_zero())
+            .ok_or(anyhow!("No price for {}", collateral_config.symbol))?;
+        let debt_amount = repay_base * U256::exp10(debt_config.decimals as usize) / debt_price;
+
+        let buy = get_paraswap_buy(
+            &self.paraswap_api_url,
+            self.chain_id,
+            collateral,
+            collateral_config.decimals,
+            debt,
+            debt_config.decimals,
+            debt_amount,
+            adapter,
+            self.max_slippage_bps,
+        )
+        .await?;
+        let collateral_sold_base = buy.max_src_amount * collateral_price
+            / U256::exp10(collateral_config.decimals as usize);
+        if !repay_beats_liquidation(
+            repay_base,
+            collateral_sold_base,
+            collateral_config.liquidation_bonus,
+        ) {
+            info!(
+                "Repaying partner account {:?} would cost more than being liquidated, leaving it",
+                partner.address
+            );
+            return Ok(None);
+        }
+        info!(
+            "Repaying {} {} with at most {} {} for partner account {:?}, health factor: {}",
+            debt_amount,
+            debt_config.symbol,
+            buy.max_src_amount,
+            collateral_config.symbol,
+            partner.address,
+            health_factor
+        );
+
+        let adapter = ParaSwapRepayAdapter::new(adapter, self.client.clone());
+        let mut call = adapter
+            .swap_and_repay(
+                collateral,
+                debt,
+                buy.max_src_amount,
+                debt_amount,
+                U256::from(VARIABLE_RATE_MODE),
+                U256::zero(),
+                buy.paraswap_data,
+                // no permit, the partner approves its aTokens to the adapter upfront
+                PermitSignature::default(),
+            )
+            // the partner's signer fills in the nonce and gas
+            .from(partner.address);
+        Ok(Some(call.tx.set_chain_id(self.chain_id).clone()))
+    }
+}
+
+// have the partner's signer sign and send its repay
+async fn send_partner_repay(partner: &PartnerAccount, tx: TypedTransaction) -> Result<H256> {
+    let signer_rpc = partner.signer_rpc.as_deref().ok_or(anyhow!(
+        "No signer for partner account {:?}",
+        partner.address
+    ))?;
+    let provider = Provider::<Http>::try_from(signer_rpc)?;
+    let pending = provider.send_transaction(tx, None).await?;
+    Ok(pending.tx_hash())
+}
+
+/// What a liquidation of `borrower` would repay and seize at `prices`, the close
+/// factor applied and capped by the collateral the borrower holds.
+#[allow(clippy::too_many_arguments)]
+pub async fn plan_liquidation(
+    data_source: &dyn AaveDataSource,
+    tokens: &HashMap<Address, TokenConfig>,
+    reserves: &[Address],
+    filter: &LiquidationFilter,
+    rules: LiquidationRules,
+    borrower_address: &Address,
+    health_factor: &U256,
+    prices: &HashMap<Address, U256>,
+) -> Result<LiquidationPlan> {
+    let mut plans = plan_liquidations(
+        data_source,
+        tokens,
+        reserves,
+        filter,
+        rules,
+        borrower_address,
+        health_factor,
+        prices,
+        1,
+    )
+    .await?;
+    Ok(plans.remove(0))
+}
+
+/// Liquidations of up to `max_pairs` (collateral, debt) pairs of `borrower`, pairing
+/// the largest allowed collateral with the largest allowed debt, then the next largest
+/// of each, so no two share a reserve. Pairs after the first that can't be liquidated
+/// are skipped.
+#[allow(clippy::too_many_arguments)]
+pub async fn plan_liquidations(
+    data_source: &dyn AaveDataSource,
+    tokens: &HashMap<Address, TokenConfig>,
+    reserves: &[Address],
+    filter: &LiquidationFilter,
+    rules: LiquidationRules,
+    borrower_address: &Address,
+    health_factor: &U256,
+    prices: &HashMap<Address, U256>,
+    max_pairs: usize,
+) -> Result<Vec<LiquidationPlan>> {
+    // supplied assets may not be enabled as collateral, so read what actually backs the debt
+    let configuration = data_source.user_configuration(*borrower_address).await?;
+    let (collateral, debt) = decode_user_configuration(configuration, reserves);
+    let debt_ceiling = |asset: &Address| {
+        tokens
+            .get(asset)
+            .map(|token| token.debt_ceiling)
+            .unwrap_or_default()
+    };
+    let backing = backing_collateral(&collateral, debt_ceiling);
+    if backing.len() < collateral.len() {
+        info!(
+            "Borrower {:?} holds isolated collateral next to other collateral, leaving out {:?}",
+            borrower_address,
+            collateral.difference(&backing).collect::<Vec<_>>()
+        );
+    }
+    // siloed debt is the only debt the borrower can hold
+    let siloed = siloed_debt(&debt, |asset| {
+        tokens
+            .get(asset)
+            .is_some_and(|token| token.siloed_borrowing)
+    });
+    if let Some(siloed) = siloed {
+        info!(
+            "Borrower {:?} holds siloed debt {:?}",
+            borrower_address, siloed
+        );
+    }
+    // seize the largest allowed collateral for the largest allowed debt, by value
+    let mut collaterals: Vec<(Address, U256)> = Vec::new();
+    for asset in backing {
+        let Some(token) = tokens
+            .get(&asset)
+            .filter(|token| token.is_seizable() && filter.allows_collateral(&asset))
+        else {
+            continue;
+        };
+        let balance = data_source
+            .balance_of(token.a_address, *borrower_address)
+            .await?;
+        let value = position_value(balance, prices.get(&asset), token.decimals)?;
+        collaterals.push((asset, value));
+    }
+    // every debt counts towards the total v3.3 caps the close factor by, allowed or not
+    let mut debts: Vec<(Address, U256)> = Vec::new();
+    let mut total_debt_base = U256::zero();
+    for asset in siloed.map_or_else(|| debt, |siloed| HashSet::from([siloed])) {
+        let Some(token) = tokens.get(&asset) else {
+            continue;
+        };
+        let user_debt = data_source.user_debt(asset, *borrower_address).await?;
+        let value = position_value(user_debt.total(), prices.get(&asset), token.decimals)?;
+        total_debt_base += value;
+        if filter.allows_debt(&asset) {
+            debts.push((asset, value));
+        }
+    }
+    if collaterals.is_empty() {
+        return Err(anyhow!("No allowed collateral found"));
+    }
+    if debts.is_empty() {
+        return Err(anyhow!("No allowed debt found"));
+    }
+    collaterals.sort_by(|a, b| b.1.cmp(&a.1));
+    debts.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let mut plans = Vec::new();
+    for (i, ((collateral_address, _), (debt_address, _))) in
+        zip(collaterals, debts).take(max_pairs.max(1)).enumerate()
+    {
+        let plan = plan_pair_liquidation(
+            data_source,
+            tokens,
+            rules,
+            borrower_address,
+            health_factor,
+            total_debt_base,
+            prices,
+            &collateral_address,
+            &debt_address,
+            None,
+        )
+        .await;
+        match plan {
+            Ok(plan) => plans.push(plan),
+            Err(e) if i == 0 => return Err(e),
+            Err(e) => info!("Skipping leg of borrower {:?}: {}", borrower_address, e),
+        }
+    }
+    Ok(plans)
+}
+
+/// What a liquidation of `borrower` seizing `collateral_address` for `debt_address` would
+/// repay and seize at `prices`, repaying at most `max_debt_to_cover` if set. The borrower's
+/// debt across all reserves, `total_debt_base`, bounds it under the v3.3 rules.
+#[allow(clippy::too_many_arguments)]
+pub async fn plan_pair_liquidation(
+    data_source: &dyn AaveDataSource,
+    tokens: &HashMap<Address, TokenConfig>,
+    rules: LiquidationRules,
+    borrower_address: &Address,
+    health_factor: &U256,
+    total_debt_base: U256,
+    prices: &HashMap<Address, U256>,
+    collateral_address: &Address,
+    debt_address: &Address,
+    max_debt_to_cover: Option<U256>,
+) -> Result<LiquidationPlan> {
+    let collateral_asset_price = prices
+        .get(collateral_address)
+        .ok_or(anyhow!("No collateral price"))?;
+    let debt_asset_price = prices.get(debt_address).ok_or(anyhow!("No debt price"))?;
+    let collateral_config = tokens
+        .get(collateral_address)
+        .ok_or(anyhow!("Failed to get collateral address"))?;
+    let debt_config = tokens
+        .get(debt_address)
+        .ok_or(anyhow!("Failed to get debt address"))?;
+    // the pool reverts liquidations of paused or inactive reserves
+    for config in [collateral_config, debt_config] {
+        if !config.is_liquidatable() {
+            return Err(anyhow!("Reserve {} is paused or inactive", config.symbol));
+        }
+    }
+    if !collateral_config.is_seizable() {
+        return Err(anyhow!(
+            "Reserve {} has no liquidation threshold, it can't be seized",
+            collateral_config.symbol
+        ));
+    }
+    let collateral_unit = U256::from(10).pow(collateral_config.decimals.into());
+    let debt_unit = U256::from(10).pow(debt_config.decimals.into());
+    // a borrower in the collateral's e-mode category is liquidated at the category's bonus
+    let mut liquidation_bonus = collateral_config.liquidation_bonus;
+    if let Some(e_mode_bonus) = collateral_config.e_mode_liquidation_bonus {
+        if data_source.user_e_mode(*borrower_address).await? == collateral_config.e_mode_category {
+            liquidation_bonus = e_mode_bonus;
+        }
+    }
+
+    let (user_debt, user_collateral_balance) = data_source
+        .liquidation_balances(
+            *borrower_address,
+            *debt_address,
+            collateral_config.a_address,
+        )
+        .await?;
+    let collateral_base = position_value(
+        user_collateral_balance,
+        Some(collateral_asset_price),
+        collateral_config.decimals,
+    )?;
+    let debt_base = position_value(
+        user_debt.total(),
+        Some(debt_asset_price),
+        debt_config.decimals,
+    )?;
+    let max_liquidatable = match rules {
+        LiquidationRules::V3_0 => max_liquidatable_debt(user_debt, *health_factor)?,
+        LiquidationRules::V3_3 => max_liquidatable_debt_v3_3(
+            user_debt,
+            *health_factor,
+            PositionSizes {
+                total_debt_base,
+                collateral_base,
+                debt_base,
+            },
+            *debt_asset_price,
+            debt_unit,
+        )?,
+    };
+    let mut debt_to_cover = max_liquidatable;
+    if let Some(max_debt_to_cover) = max_debt_to_cover {
+        debt_to_cover = debt_to_cover.min(max_debt_to_cover);
+    }
+    if collateral_asset_price.is_zero() {
+        return Err(anyhow!("No collateral price"));
+    }
+    let base_collateral = checked_mul_div(
+        &[*debt_asset_price, debt_to_cover, collateral_unit],
+        &[*collateral_asset_price, debt_unit],
+    )?;
+    let mut collateral_to_liquidate = percent_mul(base_collateral, liquidation_bonus)?;
+
+    if collateral_to_liquidate > user_collateral_balance {
+        collateral_to_liquidate = user_collateral_balance;
+        let bonus_debt_price = percent_div(
+            checked_mul_div(&[*debt_asset_price, collateral_unit], &[])?,
+            liquidation_bonus,
+        )?;
+        if bonus_debt_price.is_zero() {
+            return Err(anyhow!("No debt price"));
+        }
+        debt_to_cover = checked_mul_div(
+            &[*collateral_asset_price, collateral_to_liquidate, debt_unit],
+            &[bonus_debt_price],
+        )?
+        .min(max_liquidatable);
+    }
+
+    // the pool reverts a partial liquidation leaving dust of either reserve behind
+    if rules == LiquidationRules::V3_3
+        && debt_to_cover < user_debt.total()
+        && collateral_to_liquidate < user_collateral_balance
+    {
+        let debt_left = position_value(
+            user_debt.total() - debt_to_cover,
+            Some(debt_asset_price),
+            debt_config.decimals,
+        )?;
+        let collateral_left = position_value(
+            user_collateral_balance - collateral_to_liquidate,
+            Some(collateral_asset_price),
+            collateral_config.decimals,
+        )?;
+        let min_leftover = U256::from(MIN_LEFTOVER_BASE);
+        if debt_left < min_leftover || collateral_left < min_leftover {
+            return Err(anyhow!(
+                "Liquidating {} of {} of borrower {:?} would leave {} of debt and {} of collateral, below the minimum leftover",
+                debt_to_cover,
+                debt_config.symbol,
+                borrower_address,
+                debt_left,
+                collateral_left
+            ));
+        }
+    }
+
+    let protocol_fee_amount = liquidation_protocol_fee(
+        collateral_to_liquidate,
+        liquidation_bonus,
+        collateral_config.protocol_fee,
+    )?;
+
+    Ok(LiquidationPlan {
+        collateral: *collateral_address,
+        debt: *debt_address,
+        debt_to_cover,
+        repaid: user_debt.repaid(debt_to_cover),
+        collateral_to_liquidate,
+        protocol_fee_amount,
+        collateral_unit,
+        debt_unit,
+    })
+}
+
+/// The protocol's share of the bonus in `collateral_to_liquidate`, seized from the borrower
+/// but sent to the treasury instead of the liquidator.
+pub fn liquidation_protocol_fee(
+    collateral_to_liquidate: U256,
+    liquidation_bonus: u64,
+    protocol_fee: u64,
+) -> Result<U256> {
+    if protocol_fee == 0 {
+        return Ok(U256::zero());
+    }
+    let bonus_collateral = collateral_to_liquidate
+        .saturating_sub(percent_div(collateral_to_liquidate, liquidation_bonus)?);
+    percent_mul(bonus_collateral, protocol_fee)
+}
+
+/// Most of `debt` a liquidation may repay at `health_factor`: the close factor of the
+/// variable and stable debt together. Rounded down where the pool rounds half up, so a
+/// liquidation sized by it is never capped by the pool.
+pub fn max_liquidatable_debt(debt: UserDebt, health_factor: U256) -> Result<U256> {
+    checked_mul_div(
+        &[debt.total(), close_factor(health_factor)],
+        &[MAX_LIQUIDATION_CLOSE_FACTOR.into()],
+    )
+}
+
+/// Values (in base currency) a v3.3 pool sizes a liquidation by.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PositionSizes {
+    /// The borrower's debt across every reserve.
+    pub total_debt_base: U256,
+    /// The collateral reserve seized.
+    pub collateral_base: U256,
+    /// The debt reserve repaid.
+    pub debt_base: U256,
+}
+
+/// Most of `debt` a v3.3 pool lets a liquidation repay: all of it, unless the health factor
+/// is above 0.95 and both reserves are worth at least MIN_BASE_MAX_CLOSE_FACTOR_THRESHOLD,
+/// where it is capped at half the borrower's total debt, converted at `debt_price`.
+pub fn max_liquidatable_debt_v3_3(
+    debt: UserDebt,
+    health_factor: U256,
+    sizes: PositionSizes,
+    debt_price: U256,
+    debt_unit: U256,
+) -> Result<U256> {
+    let threshold = U256::from(MIN_BASE_MAX_CLOSE_FACTOR_THRESHOLD);
+    if close_factor(health_factor) == U256::from(MAX_LIQUIDATION_CLOSE_FACTOR)
+        || sizes.collateral_base < threshold
+        || sizes.debt_base < threshold
+    {
+        return Ok(debt.total());
+    }
+    let max_debt_base = checked_mul_div(
+        &[
+            sizes.total_debt_base,
+            DEFAULT_LIQUIDATION_CLOSE_FACTOR.into(),
+        ],
+        &[MAX_LIQUIDATION_CLOSE_FACTOR.into()],
+    )?;
+    if sizes.debt_base <= max_debt_base {
+        return Ok(debt.total());
+    }
+    if debt_price.is_zero() {
+        return Err(anyhow!("No debt price"));
+    }
+    Ok(checked_mul_div(&[max_debt_base, debt_unit], &[debt_price])?.min(debt.total()))
+}
+
+// value of `amount` of an asset with `decimals` at its oracle price, zero if it has none
+fn position_value(amount: U256, price: Option<&U256>, decimals: u64) -> Result<U256> {
+    let Some(price) = price else {
+        return Ok(U256::zero());
+    };
+    checked_mul_div(&[amount, *price], &[U256::exp10(decimals as usize)])
+}
+
+// product of `numerator` over the product of `denominator`, erroring instead of panicking
+// on overflow. Products are kept at 512 bits, so only a result over 256 bits overflows.
+fn checked_mul_div(numerator: &[U256], denominator: &[U256]) -> Result<U256> {
+    product_div(numerator, denominator).ok_or(anyhow!("Multiplication overflow"))
+}
+
+/// Share of the debt (in bps) a liquidation may repay at `health_factor`.
+pub fn close_factor(health_factor: U256) -> U256 {
+    if health_factor > U256::from(LIQUIDATION_CLOSE_FACTOR_THRESHOLD) {
+        U256::from(DEFAULT_LIQUIDATION_CLOSE_FACTOR)
+    } else {
+        U256::from(MAX_LIQUIDATION_CLOSE_FACTOR)
+    }
+}
+
+/// What the executor prices gas from: `bid_percentage` of `profit_eth`, none for a loss.
+pub fn gas_bid_info(profit_eth: I256, bid_percentage: u64) -> Option<GasBidInfo> {
+    (!profit_eth.is_negative()).then(|| GasBidInfo {
+        total_profit: profit_eth.into_raw(),
+        bid_percentage,
+    })
+}
+
+/// Profit in ETH (8 decimals) of seizing the collateral for the debt at oracle prices,
+/// along with the collateral value as a percentage of the debt value.
+pub fn oracle_profit(
+    collateral_price_eth: U256,
+    collateral_to_liquidate: U256,
+    collateral_unit: U256,
+    debt_price_eth: U256,
+    debt_to_cover: U256,
+    debt_unit: U256,
+) -> Result<(I256, I256)> {
+    let collateral_value = I256::try_from(checked_mul_div(
+        &[collateral_price_eth, collateral_to_liquidate],
+        &[collateral_unit],
+    )?)?;
+    let debt_value = I256::try_from(checked_mul_div(
+        &[debt_price_eth, debt_to_cover],
+        &[debt_unit],
+    )?)?;
+    let profit_factor = (collateral_value * I256::from(100))
+        .checked_div(debt_value)
+        .unwrap_or_default();
+    Ok((collateral_value - debt_value, profit_factor))
+}
+
+/// Profit in ETH (8 decimals) of a flash-swap liquidation as quoted: the collateral left
+/// once `amount_in` of it is swapped for the debt, along with the collateral received as a
+/// percentage of the collateral swapped.
+pub fn quoted_profit(
+    collateral_price_eth: U256,
+    collateral_received: U256,
+    amount_in: U256,
+    collateral_unit: U256,
+) -> Result<(I256, I256)> {
+    let value = |amount: U256| -> Result<I256> {
+        Ok(I256::try_from(checked_mul_div(
+            &[collateral_price_eth, amount],
+            &[collateral_unit],
+        )?)?)
+    };
+    let profit = value(collateral_received)? - value(amount_in)?;
+    let profit_factor = I256::try_from(
+        checked_mul_div(&[collateral_received, U256::from(100)], &[amount_in]).unwrap_or_default(),
+    )?;
+    Ok((profit, profit_factor))
+}
+
+/// The collateral a liquidation can seize. An asset with a debt ceiling only backs debt in
+/// isolation mode, as the borrower's sole collateral: the pool never enables it next to other
+/// collateral, so one held alongside others predates its ceiling and is left out.
+pub fn backing_collateral(
+    collateral: &HashSet<Address>,
+    debt_ceiling: impl Fn(&Address) -> U256,
+) -> HashSet<Address> {
+    if collateral.len() == 1 {
+        return collateral.clone();
+    }
+    collateral
+        .iter()
+        .filter(|asset| debt_ceiling(asset).is_zero())
+        .copied()
+        .collect()
+}
+
+/// Whether the sequencer is up and past its grace period: the sequencer uptime feed
+/// answers 0 while the sequencer is up.
+pub fn is_up_and_grace_period_passed(
+    answer: I256,
+    started_at: U256,
+    grace_period: U256,
+    now: u64,
+) -> bool {
+    answer.is_zero() && U256::from(now).saturating_sub(started_at) > grace_period
+}
+
+/// Whether the pool's price oracle sentinel allows liquidating a borrower at `health_factor`.
+/// It's only consulted above `MINIMUM_HEALTH_FACTOR_LIQUIDATION_THRESHOLD`, so deeply
+/// underwater borrowers can be liquidated while the sequencer is down or in its grace period.
+pub fn is_liquidation_allowed(health_factor: U256, sequencer_up: bool) -> bool {
+    sequencer_up || health_factor < U256::from(MINIMUM_HEALTH_FACTOR_LIQUIDATION_THRESHOLD)
+}
+
+// profit an opportunity is bid on, nothing for a loss
+fn unsigned_profit(profit_eth: I256) -> U256 {
+    profit_eth.max(I256::zero()).into_raw()
+}
+
+/// Allowance covering `amount` and a buffer of `buffer_bps` over it.
+pub fn capped_approval(amount: U256, buffer_bps: u64) -> U256 {
+    amount.saturating_add(amount.saturating_mul(buffer_bps.into()) / 10_000)
+}
+
+/// Share (in bps) of a cap of whole tokens that `total` of a `decimals` token takes up,
+/// none if uncapped.
+pub fn cap_utilization_bps(total: U256, cap: U256, decimals: u64) -> Option<u64> {
+    if cap.is_zero() {
+        return None;
+    }
+    let cap = cap.saturating_mul(U256::exp10(decimals as usize));
+    let bps = total.saturating_mul(10_000.into()) / cap;
+    Some(bps.min(u64::MAX.into()).as_u64())
+}
+
+/// Whether a borrower is left with bad debt: their collateral doesn't cover their debt and
+/// is worth less than the minimum position size, or nothing, so seizing all of it can't pay
+/// for a liquidation.
+pub fn is_bad_debt(collateral_base: U256, debt_base: U256, min_debt_base: U256) -> bool {
+    collateral_base < debt_base && (collateral_base.is_zero() || collateral_base < min_debt_base)
+}
+
+/// The siloed asset among a borrower's debt, if they borrowed one.
+pub fn siloed_debt(
+    debt: &HashSet<Address>,
+    is_siloed: impl Fn(&Address) -> bool,
+) -> Option<Address> {
+    debt.iter().find(|asset| is_siloed(asset)).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executors::protect_executor::bid_gas_price;
+    use crate::strategies::data_source::SnapshotDataSource;
+    use crate::strategies::math_reference::big_signed;
+    use num_bigint::BigInt;
+    use proptest::prelude::{any, prop_assert_eq, proptest};
+
+    fn token(address: Address, decimals: u64, symbol: &str) -> TokenConfig {
+        TokenConfig {
+            address,
+            a_address: Address::from_low_u64_be(address.to_low_u64_be() + 100),
+            decimals,
+            ltv: 8000,
+            liquidation_threshold: 8300,
+            liquidation_bonus: 10500,
+            reserve_factor: 1000,
+            protocol_fee: 1000,
+            symbol: symbol.to_string(),
+            price_source: Address::zero(),
+            debt_ceiling: U256::zero(),
+            siloed_borrowing: false,
+            active: true,
+            frozen: false,
+            paused: false,
+            supply_cap: U256::zero(),
+            borrow_cap: U256::zero(),
+            supply_cap_utilization_bps: None,
+            borrow_cap_utilization_bps: None,
+            e_mode_category: 0,
+            e_mode_liquidation_bonus: None,
+        }
+    }
+
+    #[test]
+    fn test_fee_rebate_bps() {
+        // Sonic's rebate isn't counted unless the liquidator is registered
+        assert_eq!(fee_rebate_bps(None, false, SONIC_FEE_REBATE_BPS), 0);
+        assert_eq!(
+            fee_rebate_bps(None, true, SONIC_FEE_REBATE_BPS),
+            SONIC_FEE_REBATE_BPS
+        );
+        assert_eq!(
+            fee_rebate_bps(Some(5000), false, SONIC_FEE_REBATE_BPS),
+            5000
+        );
+        assert_eq!(fee_rebate_bps(Some(0), true, SONIC_FEE_REBATE_BPS), 0);
+    }
+
+    #[test]
+    fn test_pack_liquidation_args() {
+        let borrower = Address::repeat_byte(0xab);
+        let (args1, args2) = pack_liquidation_args(borrower, U256::from(1234), true);
+        // | 0-padding | user address | debtAssetId | collateralAssetId |
+        assert_eq!(&args1[..8], &[0u8; 8]);
+        assert_eq!(&args1[8..28], borrower.as_bytes());
+        assert_eq!(&args1[28..], &[0u8; 4]);
+        // | 0-padding | receiveAToken | shortenedDebtToCover |
+        assert_eq!(
+            U256::from_big_endian(&args2),
+            U256::from(1234) | U256::one() << 128
+        );
+
+        // the max debt is shortened to the max uint128, which the contract expands back
+        let (_, args2) = pack_liquidation_args(borrower, U256::MAX, false);
+        assert_eq!(U256::from_big_endian(&args2), U256::from(u128::MAX));
+    }
+
+    #[test]
+    fn test_asset_override_bid() {
+        let (weth, usdc) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let overrides = HashMap::from([(
+            weth,
+            AssetOverride {
+                bid_percentage: Some(95),
+                min_profit_eth: None,
+            },
+        )]);
+        let policy = SubmissionPolicy {
+            min_confidence_bps: 0,
+            public_confidence_bps: 0,
+        };
+        let gas_price = |collateral: Address| {
+            let bid_percentage = asset_override(&overrides, collateral, usdc)
+                .bid_percentage
+                .unwrap_or(50);
+            let decision = policy.decide(CONFIDENCE_ONE, bid_percentage).unwrap();
+            let info = gas_bid_info(I256::exp10(6), decision.bid_percentage).unwrap();
+            bid_gas_price(&info, U256::from(500_000), None, 0)
+        };
+        // the override's bid is the one the tx is priced at
+        assert_eq!(gas_price(weth), U256::from(19_000_000_000u64));
+        assert_eq!(gas_price(Address::zero()), U256::from(10_000_000_000u64));
+        assert_eq!(gas_bid_info(I256::from(-1), 50), None);
+    }
+
+    #[test]
+    fn test_close_factor() {
+        let health_factor = |bps: u64| U256::exp10(14) * bps;
+        assert_eq!(close_factor(health_factor(9600)), U256::from(5000));
+        assert_eq!(close_factor(health_factor(9500)), U256::from(10000));
+        assert_eq!(close_factor(health_factor(9000)), U256::from(10000));
+    }
+
+    #[test]
+    fn test_close_factor_threshold() {
+        // read as hex the threshold was 0x95 followed by 16 zeros, over 2700, so every
+        // borrower got the full close factor
+        assert_eq!(
+            U256::from(LIQUIDATION_CLOSE_FACTOR_THRESHOLD),
+            U256::exp10(16) * 95
+        );
+        assert_eq!(
+            close_factor(U256::from(LIQUIDATION_CLOSE_FACTOR_THRESHOLD) + 1),
+            U256::from(DEFAULT_LIQUIDATION_CLOSE_FACTOR)
+        );
+    }
+
+    #[test]
+    fn test_max_liquidatable_debt() {
+        let debt = UserDebt {
+            variable: U256::from(101),
+            stable: U256::from(50),
+        };
+        // half of both debt types together, rounded down where the pool gets 76
+        assert_eq!(
+            max_liquidatable_debt(debt, U256::exp10(14) * 9700).unwrap(),
+            U256::from(75)
+        );
+        assert_eq!(
+            max_liquidatable_debt(debt, U256::exp10(14) * 9000).unwrap(),
+            U256::from(151)
+        );
+        // debt times the close factor overflows 256 bits, half of it doesn't
+        let debt = UserDebt {
+            variable: U256::MAX,
+            stable: U256::zero(),
+        };
+        assert_eq!(
+            max_liquidatable_debt(debt, U256::exp10(14) * 9700).unwrap(),
+            U256::MAX / 2
+        );
+    }
+
+    #[test]
+    fn test_max_liquidatable_debt_v3_3() {
+        let dollars = |amount: u64| U256::from(amount) * U256::from(PRICE_ONE);
+        let usdc_price = U256::from(PRICE_ONE);
+        let usdc_unit = U256::exp10(6);
+        // 3000 USDC of a 4000 USD total debt, backed by 5000 USD of collateral
+        let debt = UserDebt::variable(U256::from(3_000_000_000u64));
+        let sizes = PositionSizes {
+            total_debt_base: dollars(4000),
+            collateral_base: dollars(5000),
+            debt_base: dollars(3000),
+        };
+        let max = |health_factor_bps: u64, sizes: PositionSizes| {
+            max_liquidatable_debt_v3_3(
+                debt,
+                U256::exp10(14) * health_factor_bps,
+                sizes,
+                usdc_price,
+                usdc_unit,
+            )
+            .unwrap()
+        };
+
+        // half of the total debt, not of the reserve's
+        assert_eq!(max(9700, sizes), U256::from(2_000_000_000u64));
+        assert_eq!(max(9500, sizes), U256::from(3_000_000_000u64));
+        // small positions are fully liquidatable at any health factor
+        let small_collateral = PositionSizes {
+            collateral_base: dollars(1999),
+            ..sizes
+        };
+        assert_eq!(max(9700, small_collateral), U256::from(3_000_000_000u64));
+        // a reserve holding at most half the total debt is repaid whole
+        let spread = PositionSizes {
+            total_debt_base: dollars(6000),
+            ..sizes
+        };
+        assert_eq!(max(9700, spread), U256::from(3_000_000_000u64));
+    }
+
+    #[tokio::test]
+    async fn test_plan_liquidation_v3_3() {
+        let weth = Address::from_low_u64_be(1);
+        let usdc = Address::from_low_u64_be(2);
+        let borrower = Address::from_low_u64_be(3);
+        let tokens = HashMap::from([
+            (weth, token(weth, 18, "WETH")),
+            (usdc, token(usdc, 6, "USDC")),
+        ]);
+        let reserves = [weth, usdc];
+        // 2000 USD and 1 USD
+        let prices = HashMap::from([
+            (weth, U256::from(PRICE_ONE) * 2000),
+            (usdc, U256::from(PRICE_ONE)),
+        ]);
+        let a_weth = tokens[&weth].a_address;
+
+        let mut data_source = SnapshotDataSource::default();
+        data_source
+            .configurations
+            .insert(borrower, U256::from(0b0110));
+        data_source
+            .balances
+            .insert((a_weth, borrower), U256::exp10(18));
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(1_800_000_000u64)),
+        );
+        let plan = |data_source: SnapshotDataSource| {
+            let tokens = &tokens;
+            let prices = &prices;
+            async move {
+                plan_liquidation(
+                    &data_source,
+                    tokens,
+                    &reserves,
+                    &LiquidationFilter::default(),
+                    LiquidationRules::V3_3,
+                    &borrower,
+                    &(U256::exp10(14) * 9700),
+                    prices,
+                )
+                .await
+            }
+        };
+
+        // under 2000 USD of debt the whole of it is repaid, above 0.95 too
+        let small = plan(data_source.clone()).await.unwrap();
+        assert_eq!(small.debt_to_cover, U256::from(1_800_000_000u64));
+        assert_eq!(small.collateral_to_liquidate, U256::exp10(15) * 945);
+        // a tenth of the 45 of bonus goes to the protocol
+        assert_eq!(small.protocol_fee_amount, U256::exp10(14) * 45);
+        assert_eq!(small.collateral_received(), U256::exp10(14) * 9405);
+
+        // 5000 USD of debt against 6000 USD of collateral: half of it is repaid
+        data_source
+            .balances
+            .insert((a_weth, borrower), U256::exp10(18) * 3);
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(5_000_000_000u64)),
+        );
+        let large = plan(data_source.clone()).await.unwrap();
+        assert_eq!(large.debt_to_cover, U256::from(2_500_000_000u64));
+
+        // 2100 USD of debt against 2050 USD of collateral: repaying half would leave less
+        // than 1000 USD of collateral
+        data_source
+            .balances
+            .insert((a_weth, borrower), U256::exp10(15) * 1025);
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(2_100_000_000u64)),
+        );
+        assert!(plan(data_source).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plan_liquidation() {
+        let weth = Address::from_low_u64_be(1);
+        let usdc = Address::from_low_u64_be(2);
+        let borrower = Address::from_low_u64_be(3);
+        let tokens = HashMap::from([
+            (weth, token(weth, 18, "WETH")),
+            (usdc, token(usdc, 6, "USDC")),
+        ]);
+        let reserves = [weth, usdc];
+        // 1 ETH and 0.0005 ETH
+        let prices = HashMap::from([(weth, U256::from(PRICE_ONE)), (usdc, U256::from(50_000))]);
+        let a_weth = tokens[&weth].a_address;
+
+        // WETH collateral, USDC debt
+        let mut data_source = SnapshotDataSource::default();
+        data_source
+            .configurations
+            .insert(borrower, U256::from(0b0110));
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(2_000_000_000u64)),
+        );
+        data_source
+            .balances
+            .insert((a_weth, borrower), U256::exp10(19));
+
+        let plan = |data_source: SnapshotDataSource, health_factor_bps: u64| {
+            let tokens = &tokens;
+            let prices = &prices;
+            async move {
+                plan_liquidation(
+                    &data_source,
+                    tokens,
+                    &reserves,
+                    &LiquidationFilter::default(),
+                    LiquidationRules::V3_0,
+                    &borrower,
+                    &(U256::exp10(14) * health_factor_bps),
+                    prices,
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // half the debt, plus the bonus in collateral
+        let half = plan(data_source.clone(), 9700).await;
+        assert_eq!(half.collateral, weth);
+        assert_eq!(half.debt, usdc);
+        assert_eq!(half.debt_to_cover, U256::from(1_000_000_000u64));
+        assert_eq!(half.collateral_to_liquidate, U256::exp10(15) * 525);
+
+        assert_eq!(
+            half.repaid,
+            UserDebt::variable(U256::from(1_000_000_000u64))
+        );
+
+        let all = plan(data_source.clone(), 9000).await;
+        assert_eq!(all.debt_to_cover, U256::from(2_000_000_000u64));
+        assert_eq!(all.collateral_to_liquidate, U256::exp10(15) * 1050);
+
+        // a manual liquidation repays at most the amount asked for
+        let manual = plan_pair_liquidation(
+            &data_source,
+            &tokens,
+            LiquidationRules::V3_0,
+            &borrower,
+            &(U256::exp10(14) * 9700),
+            U256::zero(),
+            &prices,
+            &weth,
+            &usdc,
+            Some(U256::from(500_000_000u64)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(manual.debt_to_cover, U256::from(500_000_000u64));
+        assert_eq!(manual.collateral_to_liquidate, U256::exp10(14) * 2625);
+
+        // an excluded debt asset leaves nothing to liquidate
+        let filter = LiquidationFilter {
+            debt_denylist: HashSet::from([usdc]),
+            ..Default::default()
+        };
+        let excluded = plan_liquidation(
+            &data_source,
+            &tokens,
+            &reserves,
+            &filter,
+            LiquidationRules::V3_0,
+            &borrower,
+            &(U256::exp10(14) * 9700),
+            &prices,
+        )
+        .await;
+        assert!(excluded.is_err());
+
+        // capped by the collateral the borrower holds
+        data_source
+            .balances
+            .insert((a_weth, borrower), U256::exp10(17) * 2);
+        let capped = plan(data_source, 9700).await;
+        assert_eq!(capped.collateral_to_liquidate, U256::exp10(17) * 2);
+        assert!(capped.debt_to_cover < half.debt_to_cover);
+    }
+
+    #[tokio::test]
+    async fn test_plan_liquidation_e_mode() {
+        let weth = Address::from_low_u64_be(1);
+        let usdc = Address::from_low_u64_be(2);
+        let borrower = Address::from_low_u64_be(3);
+        let mut tokens = HashMap::from([
+            (weth, token(weth, 18, "WETH")),
+            (usdc, token(usdc, 6, "USDC")),
+        ]);
+        // WETH is in e-mode category 1, liquidated at a 1% bonus in it
+        let weth_config = tokens.get_mut(&weth).unwrap();
+        weth_config.e_mode_category = 1;
+        weth_config.e_mode_liquidation_bonus = Some(10100);
+        // 1 ETH and 0.0005 ETH
+        let prices = HashMap::from([(weth, U256::from(PRICE_ONE)), (usdc, U256::from(50_000))]);
+
+        let mut data_source = SnapshotDataSource::default();
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(2_000_000_000u64)),
+        );
+        data_source
+            .balances
+            .insert((tokens[&weth].a_address, borrower), U256::exp10(19));
+        let plan = |data_source: SnapshotDataSource| {
+            let tokens = &tokens;
+            let prices = &prices;
+            async move {
+                plan_pair_liquidation(
+                    &data_source,
+                    tokens,
+                    LiquidationRules::V3_0,
+                    &borrower,
+                    &(U256::exp10(14) * 9700),
+                    U256::zero(),
+                    prices,
+                    &weth,
+                    &usdc,
+                    None,
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // outside the category the reserve's bonus applies
+        let outside = plan(data_source.clone()).await;
+        assert_eq!(outside.collateral_to_liquidate, U256::exp10(15) * 525);
+        data_source.e_modes.insert(borrower, 2);
+        assert_eq!(plan(data_source.clone()).await, outside);
+
+        data_source.e_modes.insert(borrower, 1);
+        let inside = plan(data_source).await;
+        assert_eq!(inside.debt_to_cover, outside.debt_to_cover);
+        assert_eq!(inside.collateral_to_liquidate, U256::exp10(15) * 505);
+    }
+
+    #[tokio::test]
+    async fn test_plan_liquidation_largest_position() {
+        let weth = Address::from_low_u64_be(1);
+        let usdc = Address::from_low_u64_be(2);
+        let wbtc = Address::from_low_u64_be(3);
+        let dai = Address::from_low_u64_be(4);
+        let borrower = Address::from_low_u64_be(5);
+        let tokens = HashMap::from([
+            (weth, token(weth, 18, "WETH")),
+            (usdc, token(usdc, 6, "USDC")),
+            (wbtc, token(wbtc, 8, "WBTC")),
+            (dai, token(dai, 18, "DAI")),
+        ]);
+        let reserves = [weth, usdc, wbtc, dai];
+        // 1 ETH, 0.0005 ETH, 20 ETH and 0.0005 ETH
+        let prices = HashMap::from([
+            (weth, U256::from(PRICE_ONE)),
+            (usdc, U256::from(50_000)),
+            (wbtc, U256::from(PRICE_ONE) * 20),
+            (dai, U256::from(50_000)),
+        ]);
+
+        // 1 WETH and 1 WBTC of collateral, 2000 USDC and 100 DAI of debt
+        let mut data_source = SnapshotDataSource::default();
+        data_source
+            .configurations
+            .insert(borrower, U256::from(0b0110_0110));
+        data_source
+            .balances
+            .insert((tokens[&weth].a_address, borrower), U256::exp10(18));
+        data_source
+            .balances
+            .insert((tokens[&wbtc].a_address, borrower), U256::exp10(8));
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(2_000_000_000u64)),
+        );
+        data_source
+            .debts
+            .insert((dai, borrower), UserDebt::variable(U256::exp10(20)));
+
+        let plan = plan_liquidation(
+            &data_source,
+            &tokens,
+            &reserves,
+            &LiquidationFilter::default(),
+            LiquidationRules::V3_0,
+            &borrower,
+            &(U256::exp10(14) * 9700),
+            &prices,
+        )
+        .await
+        .unwrap();
+        assert_eq!(plan.collateral, wbtc);
+        assert_eq!(plan.debt, usdc);
+
+        // the next largest of each make a second pair sharing no reserve with the first
+        let plans = plan_liquidations(
+            &data_source,
+            &tokens,
+            &reserves,
+            &LiquidationFilter::default(),
+            LiquidationRules::V3_0,
+            &borrower,
+            &(U256::exp10(14) * 9700),
+            &prices,
+            3,
+        )
+        .await
+        .unwrap();
+        assert_eq!(plans.len(), 2);
+        assert_eq!(plans[0], plan);
+        assert_eq!((plans[1].collateral, plans[1].debt), (weth, dai));
+
+        // collateral with no liquidation threshold can't be seized, however large
+        let mut tokens = tokens;
+        tokens.get_mut(&wbtc).unwrap().liquidation_threshold = 0;
+        let plans = plan_liquidations(
+            &data_source,
+            &tokens,
+            &reserves,
+            &LiquidationFilter::default(),
+            LiquidationRules::V3_0,
+            &borrower,
+            &(U256::exp10(14) * 9700),
+            &prices,
+            3,
+        )
+        .await
+        .unwrap();
+        assert_eq!(plans.len(), 1);
+        assert_eq!((plans[0].collateral, plans[0].debt), (weth, usdc));
+    }
+
+    #[test]
+    fn test_oracle_profit() {
+        let (profit, profit_factor) = oracle_profit(
+            U256::from(PRICE_ONE),
+            U256::exp10(15) * 525,
+            U256::exp10(18),
+            U256::from(50_000),
+            U256::from(1_000_000_000u64),
+            U256::exp10(6),
+        )
+        .unwrap();
+        assert_eq!(profit, I256::from(2_500_000));
+        assert_eq!(profit_factor, I256::from(105));
+    }
+
+    #[test]
+    fn test_quoted_profit() {
+        // 0.525 collateral received, 0.51 of it swapped for the debt
+        let (profit, profit_factor) = quoted_profit(
+            U256::from(PRICE_ONE),
+            U256::exp10(15) * 525,
+            U256::exp10(16) * 51,
+            U256::exp10(18),
+        )
+        .unwrap();
+        assert_eq!(profit, I256::from(1_500_000));
+        assert_eq!(profit_factor, I256::from(102));
+        let (profit, _) = quoted_profit(
+            U256::from(PRICE_ONE),
+            U256::exp10(18),
+            U256::exp10(18) * 2,
+            U256::exp10(18),
+        )
+        .unwrap();
+        assert_eq!(profit, -I256::from(PRICE_ONE));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_oracle_profit_matches_reference(
+            collateral_price in any::<u64>(),
+            collateral_to_liquidate in any::<u128>(),
+            collateral_decimals in 0usize..=18,
+            debt_price in any::<u64>(),
+            debt_to_cover in any::<u128>(),
+            debt_decimals in 0usize..=18,
+        ) {
+            let value = |price: u64, amount: u128, decimals: usize| {
+                BigInt::from(price) * BigInt::from(amount) / BigInt::from(10u8).pow(decimals as u32)
+            };
+            let collateral_value =
+                value(collateral_price, collateral_to_liquidate, collateral_decimals);
+            let debt_value = value(debt_price, debt_to_cover, debt_decimals);
+            let expected_factor = if debt_value == BigInt::from(0u8) {
+                BigInt::from(0u8)
+            } else {
+                collateral_value.clone() * 100 / debt_value.clone()
+            };
+
+            let (profit, profit_factor) = oracle_profit(
+                U256::from(collateral_price),
+                U256::from(collateral_to_liquidate),
+                U256::exp10(collateral_decimals),
+                U256::from(debt_price),
+                U256::from(debt_to_cover),
+                U256::exp10(debt_decimals),
+            )
+            .unwrap();
+            prop_assert_eq!(big_signed(profit), collateral_value - debt_value);
+            prop_assert_eq!(big_signed(profit_factor), expected_factor);
+        }
+    }
+
+    #[test]
+    fn test_borrower_is_dust() {
+        let min_debt_base = U256::from(10 * PRICE_ONE);
+        let mut borrower = Borrower {
+            address: Address::repeat_byte(1),
+            collateral: HashSet::new(),
+            debt: HashSet::new(),
+            kind: None,
+            total_debt_base: None,
+            siloed_debt: None,
+        };
+        // never checked
+        assert!(!borrower.is_dust(min_debt_base));
+        borrower.total_debt_base = Some(U256::from(PRICE_ONE));
+        assert!(borrower.is_dust(min_debt_base));
+        borrower.total_debt_base = Some(min_debt_base);
+        assert!(!borrower.is_dust(min_debt_base));
+    }
+
+    #[test]
+    fn test_borrower_mark_siloed() {
+        let (siloed, usdc) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let is_siloed = |asset: &Address| *asset == siloed;
+        let mut borrower = Borrower {
+            address: Address::repeat_byte(3),
+            collateral: HashSet::new(),
+            debt: HashSet::from([siloed, usdc]),
+            kind: None,
+            total_debt_base: None,
+            siloed_debt: None,
+        };
+        borrower.mark_siloed(is_siloed);
+        assert_eq!(borrower.siloed_debt, Some(siloed));
+        // the siloed asset is the only debt looked at
+        assert_eq!(borrower.debt_assets(), HashSet::from([siloed]));
+        assert!(borrower.owes(&siloed));
+        assert!(!borrower.owes(&usdc));
+
+        // repaid and borrowing something else
+        borrower.debt = HashSet::from([usdc]);
+        borrower.mark_siloed(is_siloed);
+        assert_eq!(borrower.siloed_debt, None);
+        assert_eq!(borrower.debt_assets(), HashSet::from([usdc]));
+        assert!(borrower.owes(&usdc));
+    }
+
+    #[test]
+    fn test_is_up_and_grace_period_passed() {
+        let grace_period = U256::from(3600);
+        let started_at = U256::from(1_000_000);
+        assert!(is_up_and_grace_period_passed(
+            I256::zero(),
+            started_at,
+            grace_period,
+            1_003_601
+        ));
+        // back up, but still in the grace period
+        assert!(!is_up_and_grace_period_passed(
+            I256::zero(),
+            started_at,
+            grace_period,
+            1_003_600
+        ));
+        // down
+        assert!(!is_up_and_grace_period_passed(
+            I256::one(),
+            started_at,
+            grace_period,
+            2_000_000
+        ));
+    }
+
+    #[test]
+    fn test_is_liquidation_allowed() {
+        let threshold = U256::from(MINIMUM_HEALTH_FACTOR_LIQUIDATION_THRESHOLD);
+        // in the grace period, the sentinel holds back borrowers at or above 0.95
+        assert!(!is_liquidation_allowed(threshold, false));
+        assert!(!is_liquidation_allowed(U256::exp10(18) - 1, false));
+        // but not those below it
+        assert!(is_liquidation_allowed(threshold - 1, false));
+        assert!(is_liquidation_allowed(U256::exp10(17), false));
+        // sequencer up, everyone underwater
+        assert!(is_liquidation_allowed(threshold, true));
+        assert!(is_liquidation_allowed(threshold - 1, true));
+    }
+
+    #[test]
+    fn test_capped_approval() {
+        assert_eq!(
+            capped_approval(U256::from(1_000_000), 50),
+            U256::from(1_005_000)
+        );
+        assert_eq!(
+            capped_approval(U256::from(1_000_000), 0),
+            U256::from(1_000_000)
+        );
+        assert_eq!(capped_approval(U256::MAX, 50), U256::MAX);
+    }
+
+    #[test]
+    fn test_cap_utilization_bps() {
+        let usdc = U256::exp10(6);
+        assert_eq!(cap_utilization_bps(usdc * 750, 1_000.into(), 6), Some(7500));
+        // supply can run past a cap lowered under it
+        assert_eq!(
+            cap_utilization_bps(usdc * 2_000, 1_000.into(), 6),
+            Some(20000)
+        );
+        assert_eq!(cap_utilization_bps(usdc, U256::zero(), 6), None);
+    }
+
+    #[test]
+    fn test_is_bad_debt() {
+        let min_debt_base = U256::from(10 * PRICE_ONE);
+        // no collateral left at all
+        assert!(is_bad_debt(
+            U256::zero(),
+            U256::from(PRICE_ONE),
+            U256::zero()
+        ));
+        // dust collateral against larger debt
+        assert!(is_bad_debt(
+            U256::from(PRICE_ONE),
+            U256::from(100 * PRICE_ONE),
+            min_debt_base
+        ));
+        // insolvent, but the collateral still pays for its liquidation
+        assert!(!is_bad_debt(
+            U256::from(50 * PRICE_ONE),
+            U256::from(100 * PRICE_ONE),
+            min_debt_base
+        ));
+        // solvent dust
+        assert!(!is_bad_debt(
+            U256::from(PRICE_ONE),
+            U256::from(PRICE_ONE / 2),
+            min_debt_base
+        ));
+    }
+
+    #[test]
+    fn test_siloed_debt() {
+        let siloed = Address::repeat_byte(1);
+        let regular = Address::repeat_byte(2);
+        let is_siloed = |asset: &Address| *asset == siloed;
+
+        assert_eq!(
+            siloed_debt(&HashSet::from([siloed]), is_siloed),
+            Some(siloed)
+        );
+        assert_eq!(siloed_debt(&HashSet::from([regular]), is_siloed), None);
+        assert_eq!(siloed_debt(&HashSet::new(), is_siloed), None);
+    }
+
+    #[tokio::test]
+    async fn test_plan_liquidation_isolated_collateral() {
+        let weth = Address::from_low_u64_be(1);
+        let usdc = Address::from_low_u64_be(2);
+        let wbtc = Address::from_low_u64_be(3);
+        let borrower = Address::from_low_u64_be(4);
+        let mut tokens = HashMap::from([
+            (weth, token(weth, 18, "WETH")),
+            (usdc, token(usdc, 6, "USDC")),
+            (wbtc, token(wbtc, 8, "WBTC")),
+        ]);
+        tokens.get_mut(&wbtc).unwrap().debt_ceiling = U256::from(1_000_000);
+        let reserves = [weth, usdc, wbtc];
+        // 1 ETH, 0.0005 ETH and 20 ETH
+        let prices = HashMap::from([
+            (weth, U256::from(PRICE_ONE)),
+            (usdc, U256::from(50_000)),
+            (wbtc, U256::from(PRICE_ONE) * 20),
+        ]);
+
+        // 1 WETH and 1 WBTC of collateral, 2000 USDC of debt
+        let mut data_source = SnapshotDataSource::default();
+        data_source
+            .configurations
+            .insert(borrower, U256::from(0b10_0110));
+        data_source
+            .balances
+            .insert((tokens[&weth].a_address, borrower), U256::exp10(18));
+        data_source
+            .balances
+            .insert((tokens[&wbtc].a_address, borrower), U256::exp10(8));
+        data_source.debts.insert(
+            (usdc, borrower),
+            UserDebt::variable(U256::from(2_000_000_000u64)),
+        );
+        let plan = |data_source: SnapshotDataSource| {
+            let tokens = &tokens;
+            let prices = &prices;
+            async move {
+                plan_liquidation(
+                    &data_source,
+                    tokens,
+                    &reserves,
+                    &LiquidationFilter::default(),
+                    LiquidationRules::V3_0,
+                    &borrower,
+                    &(U256::exp10(14) * 9700),
+                    prices,
+                )
+                .await
+            }
+        };
+
+        // WBTC is worth more, but its debt ceiling keeps it from backing debt next to WETH
+        assert_eq!(plan(data_source.clone()).await.unwrap().collateral, weth);
+
+        // on its own it is the isolation mode collateral
+        data_source
+            .configurations
+            .insert(borrower, U256::from(0b10_0100));
+        assert_eq!(plan(data_source).await.unwrap().collateral, wbtc);
+    }
+
+    #[test]
+    fn test_state_cache_round_trip() {
+        let path = std::env::temp_dir().join(format!("state-cache-{}.json.gz", std::process::id()));
+        StateCache::new(8453, Deployment::AAVE, Address::zero(), 100, HashMap::new())
+            .write(&path)
+            .unwrap();
+
+        let cache = StateCache::read(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(cache.last_block_number, 100);
+        assert!(cache.is_compatible(8453, &Deployment::AAVE));
+        // a cache of another network is never reused
+        assert!(!cache.is_compatible(10, &Deployment::AAVE));
+        assert!(!cache.is_compatible(8453, &Deployment::SEASHELL));
+        assert!(StateCache::read(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_state_cache_path() {
+        assert_eq!(
+            state_cache_path(Path::new("state"), 146, &Deployment::AaveV3Sonic),
+            PathBuf::from("state/borrowers-146-aave-v3-sonic.json.gz")
+        );
+    }
+
+    #[test]
+    fn test_opportunity_message() {
+        let (collateral, debt, weth) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let op = LiquidationOpportunity {
+            borrower: Address::repeat_byte(4),
+            collateral,
+            debt,
+            debt_to_cover: U256::exp10(20),
+            profit_eth: I256::from(-5),
+            collateral_symbol: "WBTC".to_string(),
+            debt_symbol: "USDC".to_string(),
+            profit_factor: I256::from(3),
+            confidence: 9000,
+            correlation_id: "abc".to_string(),
+            uniswap_fee: 500,
+            swap_path: Some(SwapPath {
+                tokens: vec![collateral, weth, debt],
+                fees: vec![500, 3000],
+            }),
+            flash_swap: Some(FlashSwap::Curve {
+                pool: Address::repeat_byte(5),
+                i: 0,
+                j: 2,
+                amount_in: U256::MAX,
+            }),
+            health_factor: U256::exp10(17),
+            route_quality_bps: 8000,
+            simulation: SimulationResult::Skipped,
+            legs: vec![LiquidationLeg {
+                collateral: weth,
+                debt,
+                debt_to_cover: U256::one(),
+                uniswap_fee: 3000,
+            }],
+            collateral_price: U256::from(6_000_000_000_000u64),
+            debt_price: U256::from(100_000_000),
+            profit_rate: None,
+            valued_at: Some(ValuedAt {
+                block_number: 100,
+                timestamp: 1_700_000_000,
+            }),
+            l1_fee_eth: U256::zero(),
+        };
+        let message = OpportunityMessage {
+            op,
+            decision: SubmissionDecision {
+                route: SubmissionRoute::Private,
+                bid_percentage: 80,
+            },
+        };
+
+        // what a detector queues, an executor reads back unchanged
+        let queued = serde_json::to_string(&message).unwrap();
+        let read: OpportunityMessage = serde_json::from_str(&queued).unwrap();
+        assert_eq!(format!("{:?}", read), format!("{:?}", message));
+    }
 }
@@ -0,0 +1,247 @@
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Large liquidations held for an operator's confirmation before they're submitted.
+#[derive(Debug, Clone)]
+pub struct ApprovalConfig {
+    /// Liquidations repaying more debt than this (in USD) need approval, smaller ones are
+    /// submitted right away.
+    pub notional_usd: u64,
+    /// Directory where a file named after a borrower, e.g. `0xab…`, approves it once.
+    pub dir: Option<PathBuf>,
+    /// Seconds an approval or rejection of a borrower lasts.
+    pub ttl_secs: u64,
+}
+
+/// A liquidation waiting for approval.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingApproval {
+    /// Of the latest opportunity found on the borrower.
+    pub correlation_id: String,
+    pub borrower: Address,
+    pub collateral: Address,
+    pub debt: Address,
+    pub debt_to_cover: U256,
+    /// Debt repaid, in the oracle's base currency.
+    pub notional_base: U256,
+    pub profit_eth: String,
+    /// Unix time the borrower was first held at.
+    pub since: u64,
+}
+
+/// Whether a liquidation may be submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Approved,
+    Rejected,
+    Pending,
+}
+
+/// Operator decisions on the liquidations above the approval notional, by borrower. A
+/// decision holds for the approval TTL, so a borrower found again on the next tick, with
+/// a new correlation id and fresh prices, is submitted or passed as decided.
+#[derive(Debug)]
+pub struct ApprovalGate {
+    config: ApprovalConfig,
+    /// The notional in base currency, every liquidation needs approval until it's known.
+    threshold_base: U256,
+    pending: HashMap<Address, PendingApproval>,
+    /// Whether each borrower decided on was approved, and until when.
+    decisions: HashMap<Address, (bool, u64)>,
+}
+
+impl ApprovalGate {
+    pub fn new(config: ApprovalConfig) -> Self {
+        Self {
+            config,
+            threshold_base: U256::zero(),
+            pending: HashMap::new(),
+            decisions: HashMap::new(),
+        }
+    }
+
+    pub fn config(&self) -> &ApprovalConfig {
+        &self.config
+    }
+
+    /// Set the notional in base currency from the oracle's unit of one USD.
+    pub fn set_base_currency_unit(&mut self, base_currency_unit: U256) {
+        self.threshold_base = base_currency_unit * self.config.notional_usd;
+        info!(
+            "Liquidations over ${} ({} in base currency) need approval",
+            self.config.notional_usd, self.threshold_base
+        );
+    }
+
+    pub fn needs_approval(&self, notional_base: U256) -> bool {
+        notional_base > self.threshold_base
+    }
+
+    /// Whether the liquidation of `request`'s borrower was approved, holding it until it's
+    /// decided on. An approval file for the borrower is taken as an approval, and removed.
+    pub fn check(&mut self, request: PendingApproval, now: u64) -> Approval {
+        self.decisions.retain(|_, (_, until)| *until > now);
+        let borrower = request.borrower;
+        if !self.decisions.contains_key(&borrower) && self.take_approval_file(borrower) {
+            self.decisions
+                .insert(borrower, (true, now + self.config.ttl_secs));
+        }
+        match self.decisions.get(&borrower) {
+            Some((approved, _)) => {
+                self.pending.remove(&borrower);
+                if *approved {
+                    Approval::Approved
+                } else {
+                    Approval::Rejected
+                }
+            }
+            None => {
+                let since = self
+                    .pending
+                    .get(&borrower)
+                    .map_or(request.since, |pending| pending.since);
+                self.pending
+                    .insert(borrower, PendingApproval { since, ..request });
+                Approval::Pending
+            }
+        }
+    }
+
+    /// Approve or reject the liquidation of `borrower` for the approval TTL, whether or not
+    /// it's pending. False if it wasn't.
+    pub fn decide(&mut self, borrower: Address, approved: bool, now: u64) -> bool {
+        self.decisions
+            .insert(borrower, (approved, now + self.config.ttl_secs));
+        self.pending.remove(&borrower).is_some()
+    }
+
+    /// Liquidations waiting for approval, the longest waiting first.
+    pub fn pending(&self) -> Vec<PendingApproval> {
+        let mut pending: Vec<PendingApproval> = self.pending.values().cloned().collect();
+        pending.sort_by_key(|pending| pending.since);
+        pending
+    }
+
+    // consume the approval file of `borrower`, if there is one
+    fn take_approval_file(&self, borrower: Address) -> bool {
+        let Some(dir) = &self.config.dir else {
+            return false;
+        };
+        let path = approval_file(dir, borrower);
+        if !path.exists() {
+            return false;
+        }
+        if let Err(e) = std::fs::remove_file(&path) {
+            // an approval left in place could be taken again after it expires
+            warn!("Failed to remove approval file {:?}: {}", path, e);
+            return false;
+        }
+        info!("Borrower {:?} approved by file", borrower);
+        true
+    }
+}
+
+fn approval_file(dir: &Path, borrower: Address) -> PathBuf {
+    dir.join(format!("{:?}", borrower))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(borrower: Address, correlation_id: &str, since: u64) -> PendingApproval {
+        PendingApproval {
+            correlation_id: correlation_id.to_string(),
+            borrower,
+            collateral: Address::repeat_byte(0xc),
+            debt: Address::repeat_byte(0xd),
+            debt_to_cover: U256::exp10(24),
+            notional_base: U256::exp10(14),
+            profit_eth: "1".to_string(),
+            since,
+        }
+    }
+
+    fn gate(dir: Option<PathBuf>) -> ApprovalGate {
+        let mut gate = ApprovalGate::new(ApprovalConfig {
+            notional_usd: 100_000,
+            dir,
+            ttl_secs: 300,
+        });
+        gate.set_base_currency_unit(U256::exp10(8));
+        gate
+    }
+
+    #[test]
+    fn test_needs_approval() {
+        let mut gate = ApprovalGate::new(ApprovalConfig {
+            notional_usd: 100_000,
+            dir: None,
+            ttl_secs: 300,
+        });
+        // nothing is small until the notional is known
+        assert!(gate.needs_approval(U256::one()));
+        gate.set_base_currency_unit(U256::exp10(8));
+        assert!(!gate.needs_approval(U256::exp10(13)));
+        assert!(gate.needs_approval(U256::exp10(13) + 1));
+    }
+
+    #[test]
+    fn test_decisions() {
+        let (a, b) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut gate = gate(None);
+        assert_eq!(gate.check(request(a, "1", 100), 100), Approval::Pending);
+        assert_eq!(gate.check(request(b, "2", 110), 110), Approval::Pending);
+        // found again on a later tick
+        assert_eq!(gate.check(request(a, "3", 120), 120), Approval::Pending);
+        let pending = gate.pending();
+        assert_eq!(pending.len(), 2);
+        assert_eq!((pending[0].borrower, pending[0].since), (a, 100));
+        assert_eq!(pending[0].correlation_id, "3");
+
+        assert!(gate.decide(a, true, 130));
+        assert!(gate.decide(b, false, 130));
+        assert!(!gate.decide(b, false, 130));
+        assert!(gate.pending().is_empty());
+        assert_eq!(gate.check(request(a, "4", 140), 140), Approval::Approved);
+        assert_eq!(gate.check(request(b, "5", 140), 140), Approval::Rejected);
+        // decisions expire
+        assert_eq!(gate.check(request(a, "6", 430), 430), Approval::Pending);
+    }
+
+    #[test]
+    fn test_approval_file() {
+        let dir = std::env::temp_dir().join(format!("approvals-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let borrower = Address::repeat_byte(1);
+        let mut gate = gate(Some(dir.clone()));
+        assert_eq!(
+            gate.check(request(borrower, "1", 100), 100),
+            Approval::Pending
+        );
+
+        let path = approval_file(&dir, borrower);
+        assert!(path.ends_with("0x0101010101010101010101010101010101010101"));
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(
+            gate.check(request(borrower, "2", 110), 110),
+            Approval::Approved
+        );
+        assert!(!path.exists());
+        assert!(gate.pending().is_empty());
+        // the file approves once, for the TTL
+        assert_eq!(
+            gate.check(request(borrower, "3", 120), 120),
+            Approval::Approved
+        );
+        assert_eq!(
+            gate.check(request(borrower, "4", 410), 410),
+            Approval::Pending
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,60 @@
+use bindings_liquidator::liquidator::Leg;
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+
+/// One (collateral, debt) pair of a borrower liquidated in a batch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiquidationLeg {
+    pub collateral: Address,
+    pub debt: Address,
+    pub debt_to_cover: U256,
+    /// Fee tier of the Uniswap V3 pool the leg flash-swaps through
+    pub uniswap_fee: u32,
+}
+
+impl LiquidationLeg {
+    /// The leg as `liquidateBatch` of the Liquidator contract takes it, with its
+    /// L2Encoder-packed `liquidationCall` args.
+    pub fn to_batch_leg(&self, liquidation_arg_1: [u8; 32], liquidation_arg_2: [u8; 32]) -> Leg {
+        Leg {
+            collateral: self.collateral,
+            debt: self.debt,
+            uniswap_fee: self.uniswap_fee,
+            debt_to_cover: self.debt_to_cover,
+            liquidation_arg_1,
+            liquidation_arg_2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bindings_liquidator::liquidator::LiquidateBatchCall;
+    use ethers::abi::{AbiDecode, AbiEncode};
+    use ethers::utils::id;
+
+    #[test]
+    fn test_to_batch_leg() {
+        let leg = LiquidationLeg {
+            collateral: Address::repeat_byte(1),
+            debt: Address::repeat_byte(2),
+            debt_to_cover: U256::from(1000),
+            uniswap_fee: 500,
+        };
+        let call = LiquidateBatchCall {
+            legs: vec![leg.to_batch_leg([4; 32], [5; 32])],
+            user: Address::repeat_byte(3),
+        };
+        let data = call.clone().encode();
+        assert_eq!(
+            data[..4],
+            id("liquidateBatch((address,address,uint24,uint256,bytes32,bytes32)[],address)")
+        );
+        let decoded = LiquidateBatchCall::decode(&data).unwrap();
+        assert_eq!(decoded, call);
+        assert_eq!(decoded.legs[0].uniswap_fee, 500);
+        assert_eq!(decoded.legs[0].debt_to_cover, U256::from(1000));
+        assert_eq!(decoded.legs[0].liquidation_arg_2, [5; 32]);
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{anyhow, Result};
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Percentage points the bid of a profit bucket moves by after each race.
+pub const BID_STEP_PERCENTAGE: u64 = 5;
+
+/// A win paying at least this many times the gas price competitors usually pay was won by
+/// a large margin, and bids less next time.
+pub const LARGE_MARGIN_MULTIPLE: u64 = 2;
+
+/// Bid model of a chain in `state_dir`, so instances for different networks can share
+/// a directory.
+pub fn bid_model_path(state_dir: &Path, chain_id: u64) -> PathBuf {
+    state_dir.join(format!("bid-model-{}.json", chain_id))
+}
+
+/// Range the bid moves in, in percentage of profit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BidBounds {
+    /// Bid of a profit bucket with no races yet.
+    pub base: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+/// Percentage of profit bid in gas, learned per profit bucket from the races we won and lost.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BidModel {
+    pub chain_id: u64,
+    /// Bid of each profit bucket that raced, by the bit length of the profit.
+    bids: BTreeMap<u32, u64>,
+    #[serde(skip)]
+    bounds: BidBounds,
+}
+
+impl BidModel {
+    pub fn new(chain_id: u64, bounds: BidBounds) -> Self {
+        Self {
+            chain_id,
+            bids: BTreeMap::new(),
+            bounds,
+        }
+    }
+
+    /// Read the model at `path`, starting a new one if there is none for `chain_id`.
+    pub fn read(path: &Path, chain_id: u64, bounds: BidBounds) -> Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::new(chain_id, bounds)),
+            Err(e) => return Err(e.into()),
+        };
+        let model: Self = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("Failed to parse bid model: {}", e))?;
+        if model.chain_id != chain_id {
+            return Ok(Self::new(chain_id, bounds));
+        }
+        Ok(Self { bounds, ..model })
+    }
+
+    /// Write the model to a temp file renamed over `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn bounds(&self) -> BidBounds {
+        self.bounds
+    }
+
+    /// Move the range bids are kept in, learned bids are clamped into it.
+    pub fn set_bounds(&mut self, bounds: BidBounds) {
+        self.bounds = bounds;
+    }
+
+    /// Percentage of profit to bid at full confidence on an opportunity making `profit_eth`.
+    pub fn bid_percentage(&self, profit_eth: U256) -> u64 {
+        self.bids
+            .get(&bucket(profit_eth))
+            .copied()
+            .unwrap_or(self.bounds.base)
+            .clamp(self.bounds.min, self.bounds.max.max(self.bounds.min))
+    }
+
+    /// A competitor landed first, bid more on similar profits.
+    pub fn record_loss(&mut self, profit_eth: U256) {
+        let bid = self.bid_percentage(profit_eth) + BID_STEP_PERCENTAGE;
+        self.set(profit_eth, bid);
+    }
+
+    /// We landed first paying `gas_price`, bid less on similar profits if competitors
+    /// usually pay far less.
+    pub fn record_win(&mut self, profit_eth: U256, gas_price: U256, competitor_gas_price: U256) {
+        if competitor_gas_price.is_zero()
+            || gas_price < competitor_gas_price * LARGE_MARGIN_MULTIPLE
+        {
+            return;
+        }
+        let bid = self
+            .bid_percentage(profit_eth)
+            .saturating_sub(BID_STEP_PERCENTAGE);
+        self.set(profit_eth, bid);
+    }
+
+    fn set(&mut self, profit_eth: U256, bid: u64) {
+        let bid = bid.clamp(self.bounds.min, self.bounds.max.max(self.bounds.min));
+        self.bids.insert(bucket(profit_eth), bid);
+    }
+}
+
+// profits within a factor of two of each other race alike
+fn bucket(profit_eth: U256) -> u32 {
+    profit_eth.bits() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds() -> BidBounds {
+        BidBounds {
+            base: 50,
+            min: 30,
+            max: 60,
+        }
+    }
+
+    #[test]
+    fn test_bid_model() {
+        let mut model = BidModel::new(8453, bounds());
+        let profit = U256::from(1_000_000);
+        assert_eq!(model.bid_percentage(profit), 50);
+
+        model.record_loss(profit);
+        assert_eq!(model.bid_percentage(profit), 55);
+        // a similar profit shares the bucket, a much larger one doesn't
+        assert_eq!(model.bid_percentage(U256::from(800_000)), 55);
+        assert_eq!(model.bid_percentage(U256::from(100_000_000)), 50);
+
+        model.record_loss(profit);
+        model.record_loss(profit);
+        assert_eq!(model.bid_percentage(profit), 60);
+
+        // a narrow win keeps the bid
+        model.record_win(profit, U256::from(150), U256::from(100));
+        assert_eq!(model.bid_percentage(profit), 60);
+        // nothing to compare with
+        model.record_win(profit, U256::from(150), U256::zero());
+        assert_eq!(model.bid_percentage(profit), 60);
+        // a large margin lowers it, down to the minimum
+        for _ in 0..10 {
+            model.record_win(profit, U256::from(200), U256::from(100));
+        }
+        assert_eq!(model.bid_percentage(profit), 30);
+    }
+
+    #[test]
+    fn test_lost_races_raise_gas_price() {
+        use crate::executors::protect_executor::bid_gas_price;
+        use artemis_core::executors::mempool_executor::GasBidInfo;
+
+        let mut model = BidModel::new(8453, bounds());
+        let profit = U256::from(1_000_000);
+        let gas_price = |model: &BidModel| {
+            let info = GasBidInfo {
+                total_profit: profit,
+                bid_percentage: model.bid_percentage(profit),
+            };
+            bid_gas_price(&info, U256::from(500_000), None, 0)
+        };
+        let base = gas_price(&model);
+        model.record_loss(profit);
+        assert!(gas_price(&model) > base);
+        // up to the max bid percentage
+        for _ in 0..10 {
+            model.record_loss(profit);
+        }
+        assert_eq!(gas_price(&model), U256::from(12_000_000_000u64));
+    }
+
+    #[test]
+    fn test_bid_model_round_trip() {
+        let dir = std::env::temp_dir().join(format!("bid-model-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = bid_model_path(&dir, 8453);
+        assert_eq!(path, dir.join("bid-model-8453.json"));
+
+        let profit = U256::from(1_000_000);
+        let mut model = BidModel::new(8453, bounds());
+        model.record_loss(profit);
+        model.write(&path).unwrap();
+
+        let read = BidModel::read(&path, 8453, bounds()).unwrap();
+        assert_eq!(read.bid_percentage(profit), 55);
+        // a model of another chain isn't reused
+        let other = BidModel::read(&path, 1, bounds()).unwrap();
+        assert_eq!(other.bid_percentage(profit), 50);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
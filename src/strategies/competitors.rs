@@ -0,0 +1,334 @@
+use super::confidence::CONFIDENCE_ONE;
+use ethers::types::{Address, H256, U256};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Liquidations landing this long (or longer) after we saw or submitted an opportunity
+/// on the same borrower aren't counted against it.
+pub const MATCH_WINDOW_SECS: u64 = 120;
+
+/// Races the loss rate, and so the bid, is computed over.
+pub const MAX_RECENT_OUTCOMES: usize = 20;
+
+/// Sniped opportunities kept for the report.
+pub const MAX_SNIPED: usize = 100;
+
+/// Gas prices kept per competitor for the report.
+const MAX_GAS_PRICES: usize = 50;
+
+/// A liquidation seen on chain, by the bot or anyone else.
+#[derive(Debug, Clone)]
+pub struct ObservedLiquidation {
+    pub tx_hash: H256,
+    pub block_number: u64,
+    pub timestamp: u64,
+    /// Account that called the pool, a liquidator contract or the tx sender.
+    pub liquidator: Address,
+    pub sender: Address,
+    pub borrower: Address,
+    /// Gas price the tx paid, in wei.
+    pub gas_price: U256,
+    /// Priority fee the tx paid, in wei.
+    pub priority_fee: U256,
+}
+
+/// An opportunity on a borrower someone else liquidated.
+#[derive(Debug, Clone, Serialize)]
+pub struct SnipedOpportunity {
+    /// Of the opportunity we saw, if it was published.
+    pub correlation_id: Option<String>,
+    pub borrower: Address,
+    pub competitor: Address,
+    pub tx_hash: H256,
+    pub block_number: u64,
+    /// We sent a liquidation of our own, which lost the race.
+    pub submitted: bool,
+    pub gas_price: U256,
+    pub priority_fee: U256,
+}
+
+/// What one competitor has liquidated, and what it paid to.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompetitorSummary {
+    pub address: Address,
+    pub liquidations: u64,
+    /// Of those, borrowers we had an opportunity on.
+    pub sniped: u64,
+    pub median_gas_price: U256,
+    pub median_priority_fee: U256,
+    pub last_block_number: u64,
+}
+
+/// A liquidation we submitted, settled by whoever landed first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Race {
+    pub won: bool,
+    /// Expected profit of our liquidation, in ETH with 8 decimals.
+    pub profit_eth: U256,
+    /// Gas price the winner paid, in wei.
+    pub gas_price: U256,
+    /// Median gas price of recent competitor liquidations, in wei.
+    pub competitor_gas_price: U256,
+}
+
+/// What a liquidation meant for us.
+#[derive(Debug, Clone, Default)]
+pub struct Attribution {
+    /// The race it settled, if we submitted a liquidation of the same borrower.
+    pub race: Option<Race>,
+    /// The opportunity it took from us, if it was a competitor's.
+    pub sniped: Option<SnipedOpportunity>,
+}
+
+/// How the bot fares against competitors, published for the admin API.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompetitorReport {
+    /// Our liquidations that landed.
+    pub wins: u64,
+    /// Our liquidations beaten by a competitor.
+    pub losses: u64,
+    /// Share of the recent races we lost, in bps.
+    pub loss_rate_bps: u64,
+    /// Most active first.
+    pub competitors: Vec<CompetitorSummary>,
+    /// Most recent first.
+    pub sniped: Vec<SnipedOpportunity>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct CompetitorActivity {
+    liquidations: u64,
+    sniped: u64,
+    gas_prices: VecDeque<(U256, U256)>,
+    last_block_number: u64,
+}
+
+/// Attributes every liquidation on the pool to us or a competitor, and keeps score.
+#[derive(Debug, Clone, Default)]
+pub struct CompetitorTracker {
+    /// Accounts liquidating on our behalf, the sender and the Liquidator contract.
+    own: HashSet<Address>,
+    /// Borrowers we submitted a liquidation of, with when and the profit expected.
+    attempts: HashMap<Address, (u64, U256)>,
+    /// Outcome of the recent races, true for a win.
+    outcomes: VecDeque<bool>,
+    wins: u64,
+    losses: u64,
+    competitors: HashMap<Address, CompetitorActivity>,
+    sniped: VecDeque<SnipedOpportunity>,
+}
+
+impl CompetitorTracker {
+    pub fn new(own: HashSet<Address>) -> Self {
+        Self {
+            own,
+            ..Default::default()
+        }
+    }
+
+    /// Remember a liquidation we submitted, to tell whether a competitor beat it.
+    pub fn record_attempt(&mut self, borrower: Address, profit_eth: U256, timestamp: u64) {
+        self.attempts
+            .retain(|_, (submitted, _)| *submitted + MATCH_WINDOW_SECS > timestamp);
+        self.attempts.insert(borrower, (timestamp, profit_eth));
+    }
+
+    /// Attribute a liquidation, given the opportunity we last published on its borrower.
+    pub fn record(
+        &mut self,
+        liquidation: &ObservedLiquidation,
+        opportunity: Option<String>,
+    ) -> Attribution {
+        let attempt = self
+            .attempts
+            .remove(&liquidation.borrower)
+            .filter(|(submitted, _)| submitted + MATCH_WINDOW_SECS > liquidation.timestamp);
+        let submitted = attempt.is_some();
+        let won =
+            self.own.contains(&liquidation.liquidator) || self.own.contains(&liquidation.sender);
+        let race = attempt.map(|(_, profit_eth)| Race {
+            won,
+            profit_eth,
+            gas_price: liquidation.gas_price,
+            competitor_gas_price: self.competitor_gas_price(),
+        });
+
+        if won {
+            self.wins += 1;
+            self.push_outcome(true);
+            return Attribution { race, sniped: None };
+        }
+
+        if submitted {
+            self.losses += 1;
+            self.push_outcome(false);
+        }
+        let activity = self.competitors.entry(liquidation.liquidator).or_default();
+        activity.liquidations += 1;
+        activity.last_block_number = liquidation.block_number;
+        activity
+            .gas_prices
+            .push_front((liquidation.gas_price, liquidation.priority_fee));
+        activity.gas_prices.truncate(MAX_GAS_PRICES);
+
+        if !submitted && opportunity.is_none() {
+            return Attribution { race, sniped: None };
+        }
+        activity.sniped += 1;
+        let sniped = SnipedOpportunity {
+            correlation_id: opportunity,
+            borrower: liquidation.borrower,
+            competitor: liquidation.liquidator,
+            tx_hash: liquidation.tx_hash,
+            block_number: liquidation.block_number,
+            submitted,
+            gas_price: liquidation.gas_price,
+            priority_fee: liquidation.priority_fee,
+        };
+        self.sniped.push_front(sniped.clone());
+        self.sniped.truncate(MAX_SNIPED);
+        Attribution {
+            race,
+            sniped: Some(sniped),
+        }
+    }
+
+    /// Median gas price of recent liquidations by any competitor, in wei.
+    pub fn competitor_gas_price(&self) -> U256 {
+        median(
+            self.competitors
+                .values()
+                .flat_map(|activity| activity.gas_prices.iter().map(|(price, _)| *price)),
+        )
+    }
+
+    /// Share of the recent races we lost, in bps.
+    pub fn loss_rate_bps(&self) -> u64 {
+        if self.outcomes.is_empty() {
+            return 0;
+        }
+        let losses = self.outcomes.iter().filter(|won| !**won).count() as u64;
+        losses * CONFIDENCE_ONE / self.outcomes.len() as u64
+    }
+
+    pub fn report(&self) -> CompetitorReport {
+        let mut competitors: Vec<CompetitorSummary> = self
+            .competitors
+            .iter()
+            .map(|(address, activity)| CompetitorSummary {
+                address: *address,
+                liquidations: activity.liquidations,
+                sniped: activity.sniped,
+                median_gas_price: median(activity.gas_prices.iter().map(|(price, _)| *price)),
+                median_priority_fee: median(activity.gas_prices.iter().map(|(_, fee)| *fee)),
+                last_block_number: activity.last_block_number,
+            })
+            .collect();
+        competitors.sort_by(|a, b| b.liquidations.cmp(&a.liquidations));
+        CompetitorReport {
+            wins: self.wins,
+            losses: self.losses,
+            loss_rate_bps: self.loss_rate_bps(),
+            competitors,
+            sniped: self.sniped.iter().cloned().collect(),
+        }
+    }
+
+    fn push_outcome(&mut self, won: bool) {
+        self.outcomes.push_front(won);
+        self.outcomes.truncate(MAX_RECENT_OUTCOMES);
+    }
+}
+
+fn median(values: impl Iterator<Item = U256>) -> U256 {
+    let mut values: Vec<U256> = values.collect();
+    values.sort();
+    values.get(values.len() / 2).copied().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn liquidation(liquidator: Address, borrower: Address, gas_price: u64) -> ObservedLiquidation {
+        ObservedLiquidation {
+            tx_hash: H256::repeat_byte(9),
+            block_number: 100,
+            timestamp: 1_000,
+            liquidator,
+            sender: liquidator,
+            borrower,
+            gas_price: U256::from(gas_price),
+            priority_fee: U256::from(gas_price / 10),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let own = Address::repeat_byte(1);
+        let competitor = Address::repeat_byte(2);
+        let profit = U256::from(1_000_000);
+        let mut tracker = CompetitorTracker::new(HashSet::from([own]));
+
+        // our own liquidation is a win
+        tracker.record_attempt(Address::repeat_byte(10), profit, 990);
+        let attribution = tracker.record(&liquidation(own, Address::repeat_byte(10), 100), None);
+        assert!(attribution.sniped.is_none());
+        assert_eq!(
+            attribution.race,
+            Some(Race {
+                won: true,
+                profit_eth: profit,
+                gas_price: U256::from(100),
+                competitor_gas_price: U256::zero(),
+            })
+        );
+
+        // a competitor liquidating a borrower we submitted on beats us
+        tracker.record_attempt(Address::repeat_byte(11), profit, 990);
+        let attribution = tracker.record(
+            &liquidation(competitor, Address::repeat_byte(11), 300),
+            Some("abc".to_string()),
+        );
+        assert!(!attribution.race.unwrap().won);
+        let sniped = attribution.sniped.unwrap();
+        assert!(sniped.submitted);
+        assert_eq!(sniped.competitor, competitor);
+        assert_eq!(sniped.correlation_id, Some("abc".to_string()));
+
+        // an opportunity we passed on is sniped, but not a race we lost
+        let attribution = tracker.record(
+            &liquidation(competitor, Address::repeat_byte(12), 200),
+            Some("def".to_string()),
+        );
+        assert!(attribution.race.is_none());
+        assert!(!attribution.sniped.unwrap().submitted);
+
+        // a borrower we never looked at
+        let attribution = tracker.record(
+            &liquidation(competitor, Address::repeat_byte(13), 400),
+            None,
+        );
+        assert!(attribution.race.is_none() && attribution.sniped.is_none());
+
+        // a submission too old to have raced
+        tracker.record_attempt(Address::repeat_byte(14), profit, 0);
+        let attribution = tracker.record(
+            &liquidation(competitor, Address::repeat_byte(14), 500),
+            None,
+        );
+        assert!(attribution.race.is_none() && attribution.sniped.is_none());
+
+        let report = tracker.report();
+        assert_eq!(report.wins, 1);
+        assert_eq!(report.losses, 1);
+        assert_eq!(report.loss_rate_bps, 5000);
+        assert_eq!(report.sniped.len(), 2);
+        assert_eq!(report.competitors.len(), 1);
+        assert_eq!(report.competitors[0].liquidations, 4);
+        assert_eq!(report.competitors[0].sniped, 2);
+        assert_eq!(report.competitors[0].median_gas_price, U256::from(400));
+        assert_eq!(report.competitors[0].median_priority_fee, U256::from(40));
+        assert_eq!(tracker.competitor_gas_price(), U256::from(400));
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+/// Scores are expressed in bps, 10000 = full confidence.
+pub const CONFIDENCE_ONE: u64 = 10000;
+
+/// Oracle answers this old (or older) halve the confidence of an opportunity.
+pub const MAX_PRICE_AGE_SECS: u64 = 60 * 60;
+
+/// Health factor checks this many blocks old (or older) halve the confidence of an opportunity.
+pub const MAX_HEALTH_CHECK_AGE_BLOCKS: u64 = 50;
+
+/// Outcome of simulating the liquidation before submission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SimulationResult {
+    Succeeded,
+    Skipped,
+    Failed,
+}
+
+/// Everything we know about how trustworthy an opportunity is.
+#[derive(Debug, Clone)]
+pub struct ConfidenceInputs {
+    pub price_age_secs: u64,
+    pub blocks_since_health_check: u64,
+    pub simulation: SimulationResult,
+    /// Quality of the swap route used to repay the debt, in bps.
+    pub route_quality_bps: u64,
+}
+
+/// Where a transaction should be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SubmissionRoute {
+    Public,
+    Private,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionDecision {
+    pub route: SubmissionRoute,
+    pub bid_percentage: u64,
+}
+
+/// Maps an opportunity's confidence score to how (and whether) it is submitted.
+#[derive(Debug, Clone)]
+pub struct SubmissionPolicy {
+    /// Opportunities scoring below this are not submitted at all.
+    pub min_confidence_bps: u64,
+    /// Opportunities scoring at least this are sent to the public mempool, the rest privately.
+    pub public_confidence_bps: u64,
+}
+
+/// Combine data freshness, simulation outcome and route quality into a single score.
+pub fn confidence_score(inputs: &ConfidenceInputs) -> u64 {
+    let price = decay(inputs.price_age_secs, MAX_PRICE_AGE_SECS);
+    let health_check = decay(
+        inputs.blocks_since_health_check,
+        MAX_HEALTH_CHECK_AGE_BLOCKS,
+    );
+    let simulation = match inputs.simulation {
+        SimulationResult::Succeeded => CONFIDENCE_ONE,
+        SimulationResult::Skipped => CONFIDENCE_ONE / 2,
+        SimulationResult::Failed => 0,
+    };
+    let route = inputs.route_quality_bps.min(CONFIDENCE_ONE);
+
+    [health_check, simulation, route]
+        .iter()
+        .fold(price, |score, factor| score * factor / CONFIDENCE_ONE)
+}
+
+// linearly decay from full confidence at age 0 to half confidence at max_age
+fn decay(age: u64, max_age: u64) -> u64 {
+    let age = age.min(max_age);
+    CONFIDENCE_ONE - CONFIDENCE_ONE * age / max_age / 2
+}
+
+impl SubmissionPolicy {
+    /// Route an opportunity, bidding `bid_percentage` of its profit at full confidence.
+    pub fn decide(&self, confidence_bps: u64, bid_percentage: u64) -> Option<SubmissionDecision> {
+        if confidence_bps < self.min_confidence_bps {
+            return None;
+        }
+
+        let route = if confidence_bps >= self.public_confidence_bps {
+            SubmissionRoute::Public
+        } else {
+            SubmissionRoute::Private
+        };
+
+        Some(SubmissionDecision {
+            route,
+            bid_percentage: bid_percentage * confidence_bps.min(CONFIDENCE_ONE) / CONFIDENCE_ONE,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> ConfidenceInputs {
+        ConfidenceInputs {
+            price_age_secs: 0,
+            blocks_since_health_check: 0,
+            simulation: SimulationResult::Succeeded,
+            route_quality_bps: CONFIDENCE_ONE,
+        }
+    }
+
+    #[test]
+    fn test_confidence_score() {
+        assert_eq!(confidence_score(&inputs()), CONFIDENCE_ONE);
+
+        let stale = ConfidenceInputs {
+            price_age_secs: MAX_PRICE_AGE_SECS * 2,
+            ..inputs()
+        };
+        assert_eq!(confidence_score(&stale), CONFIDENCE_ONE / 2);
+
+        let unsimulated = ConfidenceInputs {
+            simulation: SimulationResult::Skipped,
+            blocks_since_health_check: MAX_HEALTH_CHECK_AGE_BLOCKS,
+            ..inputs()
+        };
+        assert_eq!(confidence_score(&unsimulated), CONFIDENCE_ONE / 4);
+
+        let failed = ConfidenceInputs {
+            simulation: SimulationResult::Failed,
+            ..inputs()
+        };
+        assert_eq!(confidence_score(&failed), 0);
+    }
+
+    #[test]
+    fn test_submission_policy() {
+        let policy = SubmissionPolicy {
+            min_confidence_bps: 2500,
+            public_confidence_bps: 7500,
+        };
+
+        assert_eq!(policy.decide(2000, 50), None);
+        assert_eq!(
+            policy.decide(5000, 50),
+            Some(SubmissionDecision {
+                route: SubmissionRoute::Private,
+                bid_percentage: 25,
+            })
+        );
+        assert_eq!(
+            policy.decide(CONFIDENCE_ONE, 50),
+            Some(SubmissionDecision {
+                route: SubmissionRoute::Public,
+                bid_percentage: 50,
+            })
+        );
+    }
+
+    #[test]
+    fn test_confidence_scales_gas_price() {
+        use crate::executors::protect_executor::bid_gas_price;
+        use artemis_core::executors::mempool_executor::GasBidInfo;
+        use ethers::types::U256;
+
+        let policy = SubmissionPolicy {
+            min_confidence_bps: 0,
+            public_confidence_bps: 0,
+        };
+        let gas_price = |confidence_bps: u64| {
+            let decision = policy.decide(confidence_bps, 80).unwrap();
+            let info = GasBidInfo {
+                total_profit: U256::from(1_000_000),
+                bid_percentage: decision.bid_percentage,
+            };
+            bid_gas_price(&info, U256::from(500_000), None, 0)
+        };
+        // the tx of a doubtful opportunity is priced at a smaller share of its profit
+        assert_eq!(gas_price(CONFIDENCE_ONE), U256::from(16_000_000_000u64));
+        assert_eq!(gas_price(CONFIDENCE_ONE / 2), U256::from(8_000_000_000u64));
+    }
+}
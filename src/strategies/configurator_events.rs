@@ -0,0 +1,100 @@
+use bindings_aave::i_pool_configurator::{
+    BorrowCapChangedFilter, CollateralConfigurationChangedFilter, DebtCeilingChangedFilter,
+    EmodeAssetCategoryChangedFilter, EmodeCategoryAddedFilter, LiquidationProtocolFeeChangedFilter,
+    ReserveActiveFilter, ReserveDroppedFilter, ReserveFactorChangedFilter, ReserveFrozenFilter,
+    ReserveInitializedFilter, ReservePausedFilter, SiloedBorrowingChangedFilter,
+    SupplyCapChangedFilter,
+};
+use ethers::{
+    contract::EthEvent,
+    types::{Address, Log, H256},
+};
+
+/// Topics of the `PoolConfigurator` events changing what a `TokenConfig` holds, every one
+/// indexed by the reserve's asset.
+pub fn reserve_config_topics() -> Vec<H256> {
+    vec![
+        ReserveInitializedFilter::signature(),
+        ReserveDroppedFilter::signature(),
+        CollateralConfigurationChangedFilter::signature(),
+        LiquidationProtocolFeeChangedFilter::signature(),
+        ReserveFactorChangedFilter::signature(),
+        DebtCeilingChangedFilter::signature(),
+        SiloedBorrowingChangedFilter::signature(),
+        ReserveActiveFilter::signature(),
+        ReserveFrozenFilter::signature(),
+        ReservePausedFilter::signature(),
+        SupplyCapChangedFilter::signature(),
+        BorrowCapChangedFilter::signature(),
+        EmodeAssetCategoryChangedFilter::signature(),
+    ]
+}
+
+/// Topics of the `PoolConfigurator` events changing an e-mode category, indexed by its id.
+pub fn e_mode_category_topics() -> Vec<H256> {
+    vec![EmodeCategoryAddedFilter::signature()]
+}
+
+/// The reserve a configurator log changed, none for removed or unrelated logs.
+pub fn changed_reserve(log: &Log) -> Option<Address> {
+    if log.removed == Some(true) || !reserve_config_topics().contains(log.topics.first()?) {
+        return None;
+    }
+    log.topics.get(1).map(|asset| Address::from(*asset))
+}
+
+/// The e-mode category a configurator log changed, none for removed or unrelated logs.
+pub fn changed_e_mode_category(log: &Log) -> Option<u8> {
+    if log.removed == Some(true) || !e_mode_category_topics().contains(log.topics.first()?) {
+        return None;
+    }
+    log.topics.get(1).map(|id| id.to_low_u64_be() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_reserve() {
+        let asset = Address::repeat_byte(1);
+        let log = Log {
+            topics: vec![
+                CollateralConfigurationChangedFilter::signature(),
+                H256::from(asset),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(changed_reserve(&log), Some(asset));
+
+        let unrelated = Log {
+            topics: vec![H256::repeat_byte(9), H256::from(asset)],
+            ..Default::default()
+        };
+        assert_eq!(changed_reserve(&unrelated), None);
+        let removed = Log {
+            removed: Some(true),
+            ..log
+        };
+        assert_eq!(changed_reserve(&removed), None);
+    }
+
+    #[test]
+    fn test_changed_e_mode_category() {
+        let log = Log {
+            topics: vec![
+                EmodeCategoryAddedFilter::signature(),
+                H256::from_low_u64_be(1),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(changed_e_mode_category(&log), Some(1));
+        assert_eq!(changed_reserve(&log), None);
+
+        let removed = Log {
+            removed: Some(true),
+            ..log
+        };
+        assert_eq!(changed_e_mode_category(&removed), None);
+    }
+}
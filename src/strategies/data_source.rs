@@ -0,0 +1,719 @@
+use super::aave_strategy::MULTICALL_CHUNK_SIZE;
+use super::multicall::MulticallSite;
+use super::user_configuration::decode_user_configuration;
+use super::wad_ray_math::{checked_ray_mul, compounded_interest};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use bindings_aave::{
+    i_pool_data_provider::IPoolDataProvider,
+    ierc20::IERC20,
+    pool::{Pool, UserConfigurationMap},
+};
+use ethers::{
+    abi::{ParamType, Token},
+    contract::abigen,
+    providers::Middleware,
+    types::{Address, BlockNumber, U256},
+};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+use tracing::info;
+
+abigen!(
+    UiPoolDataProvider,
+    r#"[
+        struct UserReserveData { address underlyingAsset; uint256 scaledATokenBalance; bool usageAsCollateralEnabledOnUser; uint256 stableBorrowRate; uint256 scaledVariableDebt; uint256 principalStableDebt; uint256 stableBorrowLastUpdateTimestamp; }
+        function getUserReservesData(address provider, address user) external view returns (UserReserveData[], uint8)
+    ]"#
+);
+
+/// Borrowers whose positions are read from the UiPoolDataProvider at once.
+pub const UI_POOL_DATA_CONCURRENCY: usize = 16;
+
+/// `POOL_REVISION` of v3.2, the first pool without stable borrowing. Stable debt reads of
+/// later pools revert or return zero.
+pub const STABLE_DEBT_REMOVED_REVISION: u64 = 6;
+
+/// Whether a pool at `revision` still has stable debt, assumed so for a pool that doesn't
+/// report its revision.
+pub fn has_stable_debt(revision: Option<U256>) -> bool {
+    revision.map_or(true, |revision| {
+        revision < U256::from(STABLE_DEBT_REMOVED_REVISION)
+    })
+}
+
+/// The per-borrower reads liquidation opportunities are built from, so their
+/// math can run against a mock instead of a live RPC.
+#[async_trait]
+pub trait AaveDataSource: Debug + Send + Sync {
+    /// The borrower's user configuration bitmap.
+    async fn user_configuration(&self, user: Address) -> Result<U256>;
+
+    /// The borrower's e-mode category, 0 if it's in none.
+    async fn user_e_mode(&self, user: Address) -> Result<u8>;
+
+    /// The borrower's stable and variable debt of `asset`.
+    async fn user_debt(&self, asset: Address, user: Address) -> Result<UserDebt>;
+
+    /// Whether the pool still has stable debt, which v3.2 removed.
+    async fn has_stable_debt(&self) -> Result<bool> {
+        Ok(true)
+    }
+
+    async fn balance_of(&self, token: Address, owner: Address) -> Result<U256>;
+
+    /// The borrower's debt of `debt_asset` and balance of the collateral's `a_token`,
+    /// the two reads a liquidation is sized from.
+    async fn liquidation_balances(
+        &self,
+        user: Address,
+        debt_asset: Address,
+        a_token: Address,
+    ) -> Result<(UserDebt, U256)> {
+        Ok((
+            self.user_debt(debt_asset, user).await?,
+            self.balance_of(a_token, user).await?,
+        ))
+    }
+
+    /// Everything liquidations of `users` read: their configurations, their debt of
+    /// every asset they borrow and their aToken balance of every collateral.
+    /// `reserves` is the pool's ordered reserves list, `a_tokens` maps a reserve to its aToken.
+    async fn snapshot(
+        &self,
+        users: &[Address],
+        reserves: &[Address],
+        a_tokens: &HashMap<Address, Address>,
+    ) -> Result<SnapshotDataSource> {
+        let mut snapshot = SnapshotDataSource::default();
+        for user in users {
+            let configuration = self.user_configuration(*user).await?;
+            snapshot.configurations.insert(*user, configuration);
+            snapshot
+                .e_modes
+                .insert(*user, self.user_e_mode(*user).await?);
+            let (collateral, debt) = decode_user_configuration(configuration, reserves);
+            for asset in debt {
+                let user_debt = self.user_debt(asset, *user).await?;
+                snapshot.debts.insert((asset, *user), user_debt);
+            }
+            for a_token in collateral.iter().filter_map(|asset| a_tokens.get(asset)) {
+                let balance = self.balance_of(*a_token, *user).await?;
+                snapshot.balances.insert((*a_token, *user), balance);
+            }
+        }
+        Ok(snapshot)
+    }
+}
+
+/// A borrower's debt of one asset. Liquidations repay the variable debt first, then the
+/// stable debt, each accruing at its own rate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserDebt {
+    pub variable: U256,
+    pub stable: U256,
+}
+
+impl UserDebt {
+    pub fn variable(variable: U256) -> Self {
+        Self {
+            variable,
+            stable: U256::zero(),
+        }
+    }
+
+    pub fn total(&self) -> U256 {
+        self.variable.saturating_add(self.stable)
+    }
+
+    /// The variable and stable debt repaid by a liquidation covering `amount`.
+    pub fn repaid(&self, amount: U256) -> Self {
+        let variable = amount.min(self.variable);
+        Self {
+            variable,
+            stable: (amount - variable).min(self.stable),
+        }
+    }
+}
+
+/// Reads the pool and its data provider through an ethers client.
+#[derive(Debug)]
+pub struct EthersDataSource<M> {
+    pool: Pool<M>,
+    pool_data: IPoolDataProvider<M>,
+    /// Reads a borrower's whole position in one call, snapshots use it if set.
+    ui_pool_data: Option<UiPoolDataProvider<M>>,
+    /// Batches reads through Multicall3, or makes them one by one without it.
+    multicall: MulticallSite,
+    /// Whether the pool has stable debt, checked from its revision on first use.
+    stable_debt: OnceCell<bool>,
+    /// Variable debt token of each reserve, debt is read from them once stable debt is gone.
+    variable_debt_tokens: Mutex<HashMap<Address, Address>>,
+    client: Arc<M>,
+}
+
+impl<M: Middleware> EthersDataSource<M> {
+    pub fn new(client: Arc<M>, pool: Address, pool_data_provider: Address) -> Self {
+        Self {
+            pool: Pool::new(pool, client.clone()),
+            pool_data: IPoolDataProvider::new(pool_data_provider, client.clone()),
+            ui_pool_data: None,
+            multicall: MulticallSite::default(),
+            stable_debt: OnceCell::new(),
+            variable_debt_tokens: Mutex::new(HashMap::new()),
+            client,
+        }
+    }
+
+    /// Snapshot positions through the UiPoolDataProvider at `address`, reading every
+    /// reserve balance of a borrower in one call.
+    pub fn with_ui_pool_data_provider(mut self, address: Address) -> Self {
+        self.ui_pool_data = Some(UiPoolDataProvider::new(address, self.client.clone()));
+        self
+    }
+
+    pub fn with_multicall(mut self, multicall: MulticallSite) -> Self {
+        self.multicall = multicall;
+        self
+    }
+}
+
+impl<M: Middleware + 'static> EthersDataSource<M> {
+    // the variable debt token of `asset`, looked up once
+    async fn variable_debt_token(&self, asset: Address) -> Result<Address> {
+        if let Some(token) = self.variable_debt_tokens.lock().unwrap().get(&asset) {
+            return Ok(*token);
+        }
+        let token = self
+            .pool
+            .get_reserve_data(asset)
+            .call()
+            .await?
+            .variable_debt_token_address;
+        self.variable_debt_tokens
+            .lock()
+            .unwrap()
+            .insert(asset, token);
+        Ok(token)
+    }
+
+    // every reserve balance of each user from the UiPoolDataProvider, unscaled by the
+    // reserve indexes, instead of the reserves the configuration bitmap flags
+    async fn ui_snapshot(
+        &self,
+        ui_pool_data: &UiPoolDataProvider<M>,
+        users: &[Address],
+        reserves: &[Address],
+        a_tokens: &HashMap<Address, Address>,
+    ) -> Result<SnapshotDataSource> {
+        let addresses_provider = self.pool.addresses_provider().call().await?;
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+        for reserve in reserves {
+            multicall.add_call(self.pool.get_reserve_normalized_income(*reserve), false);
+            multicall.add_call(
+                self.pool.get_reserve_normalized_variable_debt(*reserve),
+                false,
+            );
+        }
+        let result: Vec<U256> = multicall.call_array().await?;
+        let indexes: HashMap<Address, ReserveIndexes> = reserves
+            .iter()
+            .zip(result.chunks(2))
+            .map(|(reserve, indexes)| {
+                (
+                    *reserve,
+                    ReserveIndexes {
+                        liquidity_index: indexes[0],
+                        variable_borrow_index: indexes[1],
+                    },
+                )
+            })
+            .collect();
+        // stable debt accrues up to the block the liquidation lands in, at least the latest one,
+        // where the wall clock may already be ahead of it
+        let now = self
+            .client
+            .get_block(BlockNumber::Latest)
+            .await?
+            .ok_or(anyhow!("No latest block"))?
+            .timestamp
+            .low_u64();
+
+        let positions: Vec<(Address, (Vec<UserReserve>, u8))> = stream::iter(users)
+            .map(|user| async move {
+                let call = ui_pool_data.get_user_reserves_data(addresses_provider, *user);
+                let data = self.client.call(&call.tx, None).await?;
+                Ok::<_, anyhow::Error>((*user, decode_user_reserves(&data)?))
+            })
+            .buffer_unordered(UI_POOL_DATA_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut snapshot = SnapshotDataSource::default();
+        for (user, (position, e_mode)) in positions {
+            snapshot.e_modes.insert(user, e_mode);
+            let mut configuration = U256::zero();
+            for user_reserve in position {
+                let Some(i) = reserves.iter().position(|r| *r == user_reserve.asset) else {
+                    continue;
+                };
+                let indexes = indexes
+                    .get(&user_reserve.asset)
+                    .ok_or(anyhow!("No indexes of {:?}", user_reserve.asset))?;
+                let (balance, debt) = user_reserve.balances(indexes, now)?;
+                if !debt.total().is_zero() {
+                    configuration |= U256::one() << (2 * i);
+                    snapshot.debts.insert((user_reserve.asset, user), debt);
+                }
+                if user_reserve.usage_as_collateral && !balance.is_zero() {
+                    configuration |= U256::one() << (2 * i + 1);
+                    if let Some(a_token) = a_tokens.get(&user_reserve.asset) {
+                        snapshot.balances.insert((*a_token, user), balance);
+                    }
+                }
+            }
+            snapshot.configurations.insert(user, configuration);
+        }
+        Ok(snapshot)
+    }
+}
+
+#[async_trait]
+impl<M: Middleware + 'static> AaveDataSource for EthersDataSource<M> {
+    async fn user_configuration(&self, user: Address) -> Result<U256> {
+        Ok(self.pool.get_user_configuration(user).call().await?.data)
+    }
+
+    async fn user_e_mode(&self, user: Address) -> Result<u8> {
+        Ok(self.pool.get_user_e_mode(user).call().await?.low_u32() as u8)
+    }
+
+    async fn user_debt(&self, asset: Address, user: Address) -> Result<UserDebt> {
+        if !self.has_stable_debt().await? {
+            let token = self.variable_debt_token(asset).await?;
+            return Ok(UserDebt::variable(self.balance_of(token, user).await?));
+        }
+        let (_, stable, variable, _, _, _, _, _, _) = self
+            .pool_data
+            .get_user_reserve_data(asset, user)
+            .call()
+            .await?;
+        Ok(UserDebt { variable, stable })
+    }
+
+    async fn has_stable_debt(&self) -> Result<bool> {
+        let stable_debt = self
+            .stable_debt
+            .get_or_try_init(|| async {
+                let revision = match self.pool.pool_revision().call().await {
+                    Ok(revision) => Some(revision),
+                    Err(e) if e.is_revert() => None,
+                    Err(e) => return Err(e),
+                };
+                let stable_debt = has_stable_debt(revision);
+                info!(
+                    "Pool revision {:?}, stable debt {}",
+                    revision,
+                    if stable_debt { "read" } else { "removed" }
+                );
+                Ok(stable_debt)
+            })
+            .await?;
+        Ok(*stable_debt)
+    }
+
+    async fn balance_of(&self, token: Address, owner: Address) -> Result<U256> {
+        Ok(IERC20::new(token, self.client.clone())
+            .balance_of(owner)
+            .call()
+            .await?)
+    }
+
+    // the same reads in chunked multicalls, a round trip per chunk instead of per read
+    async fn snapshot(
+        &self,
+        users: &[Address],
+        reserves: &[Address],
+        a_tokens: &HashMap<Address, Address>,
+    ) -> Result<SnapshotDataSource> {
+        if let Some(ui_pool_data) = &self.ui_pool_data {
+            return self
+                .ui_snapshot(ui_pool_data, users, reserves, a_tokens)
+                .await;
+        }
+        let mut snapshot = SnapshotDataSource::default();
+        let mut multicall = self.multicall.batch(self.client.clone()).await?;
+
+        let mut debts = Vec::new();
+        let mut balances = Vec::new();
+        for chunk in users.chunks(MULTICALL_CHUNK_SIZE) {
+            multicall.clear_calls();
+            for user in chunk {
+                multicall.add_call(self.pool.get_user_configuration(*user), false);
+            }
+            let result: Vec<UserConfigurationMap> = multicall.call_array().await?;
+            for (user, configuration) in chunk.iter().zip(result) {
+                snapshot.configurations.insert(*user, configuration.data);
+                let (collateral, debt) = decode_user_configuration(configuration.data, reserves);
+                debts.extend(debt.into_iter().map(|asset| (asset, *user)));
+                balances.extend(
+                    collateral
+                        .iter()
+                        .filter_map(|asset| a_tokens.get(asset))
+                        .map(|a_token| (*a_token, *user)),
+                );
+            }
+            multicall.clear_calls();
+            for user in chunk {
+                multicall.add_call(self.pool.get_user_e_mode(*user), false);
+            }
+            let result: Vec<U256> = multicall.call_array().await?;
+            for (user, e_mode) in chunk.iter().zip(result) {
+                snapshot.e_modes.insert(*user, e_mode.low_u32() as u8);
+            }
+        }
+
+        // without stable debt the variable debt token balance is the whole debt, and the
+        // stable debt token getUserReserveData reads may revert
+        if !self.has_stable_debt().await? {
+            let mut variable_debts = Vec::new();
+            for (asset, user) in &debts {
+                let token = self.variable_debt_token(*asset).await?;
+                variable_debts.push(((*asset, *user), token));
+            }
+            for chunk in variable_debts.chunks(MULTICALL_CHUNK_SIZE) {
+                multicall.clear_calls();
+                for (key, token) in chunk {
+                    multicall.add_call(
+                        IERC20::new(*token, self.client.clone()).balance_of(key.1),
+                        false,
+                    );
+                }
+                let result: Vec<U256> = multicall.call_array().await?;
+                for ((key, _), variable) in chunk.iter().zip(result) {
+                    snapshot.debts.insert(*key, UserDebt::variable(variable));
+                }
+            }
+            debts.clear();
+        }
+        for chunk in debts.chunks(MULTICALL_CHUNK_SIZE) {
+            multicall.clear_calls();
+            for (asset, user) in chunk {
+                multicall.add_call(self.pool_data.get_user_reserve_data(*asset, *user), false);
+            }
+            let result: Vec<(U256, U256, U256, U256, U256, U256, U256, u64, bool)> =
+                multicall.call_array().await?;
+            for (key, (_, stable, variable, _, _, _, _, _, _)) in chunk.iter().zip(result) {
+                snapshot.debts.insert(*key, UserDebt { variable, stable });
+            }
+        }
+
+        for chunk in balances.chunks(MULTICALL_CHUNK_SIZE) {
+            multicall.clear_calls();
+            for (a_token, user) in chunk {
+                multicall.add_call(
+                    IERC20::new(*a_token, self.client.clone()).balance_of(*user),
+                    false,
+                );
+            }
+            let result: Vec<U256> = multicall.call_array().await?;
+            for (key, balance) in chunk.iter().zip(result) {
+                snapshot.balances.insert(*key, balance);
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// Cumulated indexes (in ray) scaled balances of a reserve are multiplied by.
+#[derive(Debug, Clone, Copy)]
+pub struct ReserveIndexes {
+    pub liquidity_index: U256,
+    pub variable_borrow_index: U256,
+}
+
+/// A borrower's position in one reserve as the UiPoolDataProvider reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserReserve {
+    pub asset: Address,
+    pub scaled_a_token_balance: U256,
+    pub usage_as_collateral: bool,
+    pub scaled_variable_debt: U256,
+    /// Principal, rate and last update of the stable debt, which v3.2 removed.
+    pub stable_debt: Option<(U256, U256, u64)>,
+}
+
+impl UserReserve {
+    /// The aToken balance and debt at unix time `now`.
+    pub fn balances(&self, indexes: &ReserveIndexes, now: u64) -> Result<(U256, UserDebt)> {
+        let balance = checked_ray_mul(self.scaled_a_token_balance, indexes.liquidity_index)?;
+        let mut debt = UserDebt::variable(checked_ray_mul(
+            self.scaled_variable_debt,
+            indexes.variable_borrow_index,
+        )?);
+        if let Some((principal, rate, last_update)) = self.stable_debt {
+            if !principal.is_zero() {
+                let interest = compounded_interest(rate, now.saturating_sub(last_update))?;
+                debt.stable = checked_ray_mul(principal, interest)?;
+            }
+        }
+        Ok((balance, debt))
+    }
+}
+
+/// Decode the return data of `getUserReservesData`, the user's reserves and e-mode category.
+/// Its UserReserveData has 7 fields up to v3.1 and 4 once stable debt was removed, told
+/// apart by the size of the data.
+pub fn decode_user_reserves(data: &[u8]) -> Result<(Vec<UserReserve>, u8)> {
+    let word = |offset: usize| -> Result<usize> {
+        let word = offset
+            .checked_add(32)
+            .and_then(|end| data.get(offset..end))
+            .ok_or(anyhow!("getUserReservesData returned {} bytes", data.len()))?;
+        usize::try_from(U256::from_big_endian(word))
+            .map_err(|_| anyhow!("Invalid getUserReservesData return data"))
+    };
+    let len = word(word(0)?)?;
+    let fields = match len {
+        0 => 4,
+        // the array offset, the eMode category, the array length, then the structs
+        _ => (data.len() / 32).saturating_sub(3) / len,
+    };
+    if fields != 7 && fields != 4 {
+        return Err(anyhow!(
+            "Unknown UserReserveData layout of {} fields",
+            fields
+        ));
+    }
+    // up to v3.1 stableBorrowRate, scaledVariableDebt, principalStableDebt and
+    // stableBorrowLastUpdateTimestamp follow, since then only scaledVariableDebt
+    let mut layout = vec![ParamType::Address, ParamType::Uint(256), ParamType::Bool];
+    layout.resize(fields, ParamType::Uint(256));
+    let legacy = fields == 7;
+
+    let tokens = ethers::abi::decode(
+        &[
+            ParamType::Array(Box::new(ParamType::Tuple(layout))),
+            ParamType::Uint(8),
+        ],
+        data,
+    )?;
+    let mut tokens = tokens.into_iter();
+    let (Some(Token::Array(items)), Some(Token::Uint(e_mode))) = (tokens.next(), tokens.next())
+    else {
+        return Err(anyhow!("Invalid getUserReservesData return data"));
+    };
+    let reserves = items
+        .into_iter()
+        .map(|item| -> Result<UserReserve> {
+            let invalid = || anyhow!("Invalid UserReserveData: {:?}", item);
+            let values = item.clone().into_tuple().ok_or_else(invalid)?;
+            let uint = |i: usize| values[i].clone().into_uint().ok_or_else(invalid);
+            Ok(UserReserve {
+                asset: values[0].clone().into_address().ok_or_else(invalid)?,
+                scaled_a_token_balance: uint(1)?,
+                usage_as_collateral: values[2].clone().into_bool().ok_or_else(invalid)?,
+                scaled_variable_debt: uint(if legacy { 4 } else { 3 })?,
+                stable_debt: if legacy {
+                    Some((uint(5)?, uint(3)?, uint(6)?.low_u64()))
+                } else {
+                    None
+                },
+            })
+        })
+        .collect::<Result<_>>()?;
+    Ok((reserves, e_mode.low_u32() as u8))
+}
+
+/// Reads answered from memory, prefetched in bulk by [AaveDataSource::snapshot] or
+/// canned for tests. Anything not set reads as zero.
+#[derive(Debug, Default, Clone)]
+pub struct SnapshotDataSource {
+    pub configurations: HashMap<Address, U256>,
+    /// E-mode category by user.
+    pub e_modes: HashMap<Address, u8>,
+    /// Debt by (asset, user).
+    pub debts: HashMap<(Address, Address), UserDebt>,
+    /// Balances by (token, owner).
+    pub balances: HashMap<(Address, Address), U256>,
+}
+
+#[async_trait]
+impl AaveDataSource for SnapshotDataSource {
+    async fn user_configuration(&self, user: Address) -> Result<U256> {
+        Ok(self.configurations.get(&user).copied().unwrap_or_default())
+    }
+
+    async fn user_e_mode(&self, user: Address) -> Result<u8> {
+        Ok(self.e_modes.get(&user).copied().unwrap_or_default())
+    }
+
+    async fn user_debt(&self, asset: Address, user: Address) -> Result<UserDebt> {
+        Ok(self.debts.get(&(asset, user)).copied().unwrap_or_default())
+    }
+
+    async fn balance_of(&self, token: Address, owner: Address) -> Result<U256> {
+        Ok(self
+            .balances
+            .get(&(token, owner))
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot() {
+        let user = Address::repeat_byte(9);
+        let reserves = [Address::repeat_byte(1), Address::repeat_byte(2)];
+        let a_tokens = HashMap::from([
+            (reserves[0], Address::repeat_byte(11)),
+            (reserves[1], Address::repeat_byte(12)),
+        ]);
+        // collateral in the first reserve, debt in the second
+        let mut source = SnapshotDataSource::default();
+        source.configurations.insert(user, U256::from(0b0110));
+        source.e_modes.insert(user, 1);
+        source
+            .debts
+            .insert((reserves[1], user), UserDebt::variable(U256::from(100)));
+        source
+            .balances
+            .insert((a_tokens[&reserves[0]], user), U256::from(200));
+        // not part of the position, so not read
+        source
+            .debts
+            .insert((reserves[0], user), UserDebt::variable(U256::from(300)));
+
+        let snapshot = source
+            .snapshot(&[user], &reserves, &a_tokens)
+            .await
+            .unwrap();
+        assert_eq!(snapshot.configurations[&user], U256::from(0b0110));
+        assert_eq!(snapshot.e_modes[&user], 1);
+        assert_eq!(snapshot.debts.len(), 1);
+        assert_eq!(
+            snapshot.debts[&(reserves[1], user)],
+            UserDebt::variable(U256::from(100))
+        );
+        assert_eq!(
+            snapshot.balances[&(a_tokens[&reserves[0]], user)],
+            U256::from(200)
+        );
+    }
+
+    #[test]
+    fn test_decode_user_reserves() {
+        let weth = Address::repeat_byte(1);
+        let encode = |fields: Vec<Token>| {
+            ethers::abi::encode(&[
+                Token::Array(vec![Token::Tuple(fields)]),
+                Token::Uint(U256::one()),
+            ])
+        };
+        let uint = |value: u64| Token::Uint(U256::from(value));
+
+        // v3.1 with stable debt
+        let legacy = encode(vec![
+            Token::Address(weth),
+            uint(100),
+            Token::Bool(true),
+            uint(0),
+            uint(200),
+            uint(50),
+            uint(1_700_000_000),
+        ]);
+        let (reserves, e_mode) = decode_user_reserves(&legacy).unwrap();
+        assert_eq!(e_mode, 1);
+        assert_eq!(
+            reserves,
+            vec![UserReserve {
+                asset: weth,
+                scaled_a_token_balance: U256::from(100),
+                usage_as_collateral: true,
+                scaled_variable_debt: U256::from(200),
+                stable_debt: Some((U256::from(50), U256::zero(), 1_700_000_000)),
+            }]
+        );
+
+        // v3.2 without
+        let current = encode(vec![
+            Token::Address(weth),
+            uint(100),
+            Token::Bool(false),
+            uint(200),
+        ]);
+        let (reserves, _) = decode_user_reserves(&current).unwrap();
+        assert_eq!(reserves[0].scaled_variable_debt, U256::from(200));
+        assert_eq!(reserves[0].stable_debt, None);
+
+        assert!(decode_user_reserves(&current[..64]).is_err());
+    }
+
+    #[test]
+    fn test_user_reserve_balances() {
+        let ray = U256::exp10(27);
+        let indexes = ReserveIndexes {
+            liquidity_index: ray * 11 / 10,
+            variable_borrow_index: ray * 12 / 10,
+        };
+        let user_reserve = UserReserve {
+            asset: Address::zero(),
+            scaled_a_token_balance: U256::from(1000),
+            usage_as_collateral: true,
+            scaled_variable_debt: U256::from(1000),
+            // no interest at a zero rate
+            stable_debt: Some((U256::from(500), U256::zero(), 0)),
+        };
+        assert_eq!(
+            user_reserve.balances(&indexes, 1_700_000_000).unwrap(),
+            (
+                U256::from(1100),
+                UserDebt {
+                    variable: U256::from(1200),
+                    stable: U256::from(500),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_user_debt_repaid() {
+        let debt = UserDebt {
+            variable: U256::from(100),
+            stable: U256::from(50),
+        };
+        assert_eq!(debt.total(), U256::from(150));
+        // variable debt goes first
+        assert_eq!(
+            debt.repaid(U256::from(80)),
+            UserDebt::variable(U256::from(80))
+        );
+        assert_eq!(
+            debt.repaid(U256::from(120)),
+            UserDebt {
+                variable: U256::from(100),
+                stable: U256::from(20),
+            }
+        );
+        assert_eq!(debt.repaid(U256::from(500)), debt);
+    }
+
+    #[test]
+    fn test_has_stable_debt() {
+        // v3.0.x and v3.1 pools, and those that don't report a revision
+        assert!(has_stable_debt(Some(U256::one())));
+        assert!(has_stable_debt(Some(U256::from(4))));
+        assert!(has_stable_debt(None));
+        // v3.2 and later
+        assert!(!has_stable_debt(Some(U256::from(6))));
+        assert!(!has_stable_debt(Some(U256::from(7))));
+    }
+}
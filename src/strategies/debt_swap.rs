@@ -0,0 +1,343 @@
+use anyhow::{anyhow, Result};
+use ethers::{
+    abi::{self, Token},
+    contract::abigen,
+    types::{Address, Bytes, U256},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+abigen!(
+    ParaSwapRepayAdapter,
+    r#"[
+        struct PermitSignature { uint256 amount; uint256 deadline; uint8 v; bytes32 r; bytes32 s; }
+        function swapAndRepay(address collateralAsset, address debtAsset, uint256 collateralAmount, uint256 debtRepayAmount, uint256 debtRateMode, uint256 buyAllBalanceOffset, bytes paraswapData, PermitSignature permitSignature) external
+    ]"#
+);
+
+pub const PARTNER_ACCOUNTS_FILE: &str = "partners.json";
+pub const PARASWAP_API_URL: &str = "https://apiv5.paraswap.io";
+
+// variable rate mode of the adapter's debtRateMode
+pub const VARIABLE_RATE_MODE: u64 = 2;
+
+const PERCENTAGE_FACTOR: u64 = 10000;
+
+/// How an underwater or near-liquidatable account is handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionPath {
+    /// Liquidate through the pool, like any other borrower.
+    #[default]
+    Liquidate,
+    /// Repay debt with collateral through the ParaSwap repay adapter before the account
+    /// becomes liquidatable, sparing it the liquidation bonus.
+    RepayWithCollateral,
+}
+
+/// An account watched on behalf of a partner.
+///
+/// The adapter repays the debt of whoever calls it, so the repay path has to be sent
+/// from the account itself. The bot never signs for it: the tx goes to the partner's
+/// `signer_rpc`, which signs and sends it, and the account's aTokens have to be approved
+/// to the adapter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartnerAccount {
+    pub address: Address,
+    #[serde(default)]
+    pub path: ExecutionPath,
+    /// Collateral sold to repay the debt, required for the repay path.
+    pub collateral: Option<Address>,
+    /// Debt repaid, required for the repay path.
+    pub debt: Option<Address>,
+    /// Endpoint signing for the account with `eth_sendTransaction`, e.g. the partner's
+    /// node with the account unlocked or a signing proxy, required for the repay path.
+    pub signer_rpc: Option<String>,
+}
+
+impl PartnerAccount {
+    /// Check the account has what its execution path needs.
+    pub fn validate(&self) -> Result<()> {
+        if self.path == ExecutionPath::RepayWithCollateral
+            && (self.collateral.is_none() || self.debt.is_none() || self.signer_rpc.is_none())
+        {
+            return Err(anyhow!(
+                "Partner account {:?} on the repay path needs a collateral, a debt and a signer_rpc",
+                self.address
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether repaying with collateral costs the account less than a liquidation of the
+/// same debt would, comparing the swap's worst-case slippage (collateral sold over debt
+/// repaid, in base currency) with the liquidation bonus.
+pub fn repay_beats_liquidation(
+    repay_base: U256,
+    collateral_sold_base: U256,
+    liquidation_bonus_bps: u64,
+) -> bool {
+    let slippage = collateral_sold_base.saturating_sub(repay_base);
+    let bonus =
+        repay_base * liquidation_bonus_bps.saturating_sub(PERCENTAGE_FACTOR) / PERCENTAGE_FACTOR;
+    slippage < bonus
+}
+
+/// Debt (in base currency) to repay with collateral to bring the health factor up to
+/// the target, solving (C - x * lt) / (D - x) = target with C the threshold-weighted collateral.
+pub fn repay_amount_base(
+    total_collateral_base: U256,
+    total_debt_base: U256,
+    liquidation_threshold_bps: u64,
+    target_health_factor_bps: u64,
+) -> U256 {
+    if target_health_factor_bps <= liquidation_threshold_bps {
+        return total_debt_base;
+    }
+    let weighted_collateral = total_collateral_base * liquidation_threshold_bps;
+    let target_debt = total_debt_base * target_health_factor_bps;
+    if target_debt <= weighted_collateral {
+        return U256::zero();
+    }
+    let repay = (target_debt - weighted_collateral)
+        / (target_health_factor_bps - liquidation_threshold_bps);
+    repay.min(total_debt_base)
+}
+
+/// A ParaSwap swap buying an exact amount of the debt asset.
+#[derive(Debug, Clone)]
+pub struct ParaSwapBuy {
+    /// Maximum collateral sold, slippage included.
+    pub max_src_amount: U256,
+    /// `paraswapData` argument of the repay adapter.
+    pub paraswap_data: Bytes,
+}
+
+/// Quote and build a ParaSwap swap buying `dest_amount` of `dest_token`, executed by `user`.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_paraswap_buy(
+    api_url: &str,
+    chain_id: u64,
+    src_token: Address,
+    src_decimals: u64,
+    dest_token: Address,
+    dest_decimals: u64,
+    dest_amount: U256,
+    user: Address,
+    max_slippage_bps: u64,
+) -> Result<ParaSwapBuy> {
+    let http = reqwest::Client::new();
+    let price_route = get_price_route(
+        &http,
+        api_url,
+        json!({
+            "srcToken": format!("{:?}", src_token),
+            "srcDecimals": src_decimals,
+            "destToken": format!("{:?}", dest_token),
+            "destDecimals": dest_decimals,
+            "amount": dest_amount.to_string(),
+            "side": "BUY",
+            "network": chain_id,
+        }),
+    )
+    .await?;
+    let src_amount = price_route["srcAmount"]
+        .as_str()
+        .ok_or(anyhow!("ParaSwap route has no srcAmount"))?;
+    let max_src_amount = U256::from_dec_str(src_amount)? * (PERCENTAGE_FACTOR + max_slippage_bps)
+        / PERCENTAGE_FACTOR;
+
+    let (augustus, calldata) = build_transaction(
+        &http,
+        api_url,
+        chain_id,
+        json!({
+            "srcToken": format!("{:?}", src_token),
+            "srcDecimals": src_decimals,
+            "destToken": format!("{:?}", dest_token),
+            "destDecimals": dest_decimals,
+            "srcAmount": max_src_amount.to_string(),
+            "destAmount": dest_amount.to_string(),
+            "priceRoute": price_route,
+            "userAddress": format!("{:?}", user),
+        }),
+    )
+    .await?;
+
+    Ok(ParaSwapBuy {
+        max_src_amount,
+        paraswap_data: Bytes::from(abi::encode(&[
+            Token::Bytes(calldata.to_vec()),
+            Token::Address(augustus),
+        ])),
+    })
+}
+
+/// A ParaSwap swap selling an exact amount of a token.
+#[derive(Debug, Clone)]
+pub struct ParaSwapSell {
+    /// Contract the swap is sent to.
+    pub to: Address,
+    pub data: Bytes,
+    /// Spender the sold token has to be approved to.
+    pub token_transfer_proxy: Address,
+    /// Minimum received, slippage included.
+    pub min_dest_amount: U256,
+}
+
+/// Quote and build a ParaSwap swap selling `src_amount` of `src_token`, sent by `user`.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_paraswap_sell(
+    api_url: &str,
+    chain_id: u64,
+    src_token: Address,
+    src_decimals: u64,
+    dest_token: Address,
+    dest_decimals: u64,
+    src_amount: U256,
+    user: Address,
+    max_slippage_bps: u64,
+) -> Result<ParaSwapSell> {
+    let http = reqwest::Client::new();
+    let price_route = get_price_route(
+        &http,
+        api_url,
+        json!({
+            "srcToken": format!("{:?}", src_token),
+            "srcDecimals": src_decimals,
+            "destToken": format!("{:?}", dest_token),
+            "destDecimals": dest_decimals,
+            "amount": src_amount.to_string(),
+            "side": "SELL",
+            "network": chain_id,
+        }),
+    )
+    .await?;
+    let dest_amount = price_route["destAmount"]
+        .as_str()
+        .ok_or(anyhow!("ParaSwap route has no destAmount"))?;
+    let min_dest_amount = U256::from_dec_str(dest_amount)?
+        * (PERCENTAGE_FACTOR - max_slippage_bps.min(PERCENTAGE_FACTOR))
+        / PERCENTAGE_FACTOR;
+    let token_transfer_proxy: Address =
+        serde_json::from_value(price_route["tokenTransferProxy"].clone())
+            .map_err(|_| anyhow!("ParaSwap route has no tokenTransferProxy"))?;
+
+    let (to, data) = build_transaction(
+        &http,
+        api_url,
+        chain_id,
+        json!({
+            "srcToken": format!("{:?}", src_token),
+            "srcDecimals": src_decimals,
+            "destToken": format!("{:?}", dest_token),
+            "destDecimals": dest_decimals,
+            "srcAmount": src_amount.to_string(),
+            "destAmount": min_dest_amount.to_string(),
+            "priceRoute": price_route,
+            "userAddress": format!("{:?}", user),
+        }),
+    )
+    .await?;
+
+    Ok(ParaSwapSell {
+        to,
+        data,
+        token_transfer_proxy,
+        min_dest_amount,
+    })
+}
+
+async fn get_price_route(http: &reqwest::Client, api_url: &str, params: Value) -> Result<Value> {
+    let query = serde_qs::to_string(&params)?;
+    let mut prices: Value = http
+        .get(format!("{}/prices?{}", api_url, query))
+        .send()
+        .await?
+        .json()
+        .await?;
+    match prices.get_mut("priceRoute") {
+        Some(price_route) => Ok(price_route.take()),
+        None => Err(anyhow!("No ParaSwap route: {}", prices)),
+    }
+}
+
+// build the swap of a quoted route, returning the contract to call and its calldata
+async fn build_transaction(
+    http: &reqwest::Client,
+    api_url: &str,
+    chain_id: u64,
+    body: Value,
+) -> Result<(Address, Bytes)> {
+    let transaction: Value = http
+        .post(format!(
+            "{}/transactions/{}?ignoreChecks=true",
+            api_url, chain_id
+        ))
+        .json(&body)
+        .send()
+        .await?
+        .json()
+        .await?;
+    let to: Address = serde_json::from_value(transaction["to"].clone())
+        .map_err(|_| anyhow!("Invalid ParaSwap transaction: {}", transaction))?;
+    let data: Bytes = serde_json::from_value(transaction["data"].clone())
+        .map_err(|_| anyhow!("Invalid ParaSwap transaction: {}", transaction))?;
+    Ok((to, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repay_amount_base() {
+        // $1000 collateral at 80% LT backing $780 of debt, HF ~1.026
+        let collateral = U256::from(1000);
+        let debt = U256::from(780);
+
+        // (800 - 0.8x) / (780 - x) = 1.1 => x = 193.3
+        assert_eq!(
+            repay_amount_base(collateral, debt, 8000, 11000),
+            U256::from(193)
+        );
+        // already healthy enough
+        assert_eq!(
+            repay_amount_base(collateral, debt, 8000, 10000),
+            U256::zero()
+        );
+        // unreachable target, repay everything
+        assert_eq!(repay_amount_base(collateral, debt, 8000, 8000), debt);
+    }
+
+    #[test]
+    fn test_repay_beats_liquidation() {
+        let repay = U256::from(1000);
+        // 1% slippage against a 5% bonus
+        assert!(repay_beats_liquidation(repay, U256::from(1010), 10500));
+        // 6% slippage, liquidation is cheaper
+        assert!(!repay_beats_liquidation(repay, U256::from(1060), 10500));
+        assert!(!repay_beats_liquidation(repay, U256::from(1000), 10000));
+    }
+
+    #[test]
+    fn test_validate_partner_account() {
+        let mut partner = PartnerAccount {
+            address: Address::repeat_byte(1),
+            path: ExecutionPath::RepayWithCollateral,
+            collateral: Some(Address::repeat_byte(2)),
+            debt: Some(Address::repeat_byte(3)),
+            signer_rpc: None,
+        };
+        // the bot can't sign for the account
+        assert!(partner.validate().is_err());
+        partner.signer_rpc = Some("http://localhost:8545".to_string());
+        assert!(partner.validate().is_ok());
+
+        partner.path = ExecutionPath::Liquidate;
+        partner.collateral = None;
+        partner.signer_rpc = None;
+        assert!(partner.validate().is_ok());
+    }
+}
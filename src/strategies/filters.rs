@@ -0,0 +1,172 @@
+use anyhow::{anyhow, Result};
+use ethers::types::Address;
+use ethers::utils::keccak256;
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// Assets and borrowers left alone, e.g. illiquid long-tail or fee-on-transfer tokens
+/// the liquidation swap can't handle.
+#[derive(Debug, Clone, Default)]
+pub struct LiquidationFilter {
+    /// Only these collateral assets are seized, any if unset.
+    pub collateral_allowlist: Option<HashSet<Address>>,
+    /// Collateral assets never seized.
+    pub collateral_denylist: HashSet<Address>,
+    /// Only these debt assets are repaid, any if unset.
+    pub debt_allowlist: Option<HashSet<Address>>,
+    /// Debt assets never repaid.
+    pub debt_denylist: HashSet<Address>,
+    /// Borrowers never scanned or liquidated.
+    pub borrower_blacklist: HashSet<Address>,
+    /// The borrowers this instance scans and liquidates, all of them by default.
+    pub shard: Shard,
+}
+
+impl LiquidationFilter {
+    pub fn allows_collateral(&self, asset: &Address) -> bool {
+        allowed(&self.collateral_allowlist, &self.collateral_denylist, asset)
+    }
+
+    pub fn allows_debt(&self, asset: &Address) -> bool {
+        allowed(&self.debt_allowlist, &self.debt_denylist, asset)
+    }
+
+    pub fn allows_borrower(&self, borrower: &Address) -> bool {
+        !self.borrower_blacklist.contains(borrower) && self.shard.contains(borrower)
+    }
+}
+
+/// Shard `index` of `count` disjoint slices of the borrowers, split by the hash of their
+/// address, so instances running every shard of a market never scan or race for the same
+/// borrower.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: u64,
+    pub count: u64,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self { index: 0, count: 1 }
+    }
+}
+
+impl Shard {
+    pub fn contains(&self, borrower: &Address) -> bool {
+        self.count <= 1 || shard_of(borrower, self.count) == self.index
+    }
+}
+
+/// The shard of `count` holding `borrower`: the first 8 bytes of the keccak256 hash of its
+/// address, modulo `count`, the same on every instance.
+pub fn shard_of(borrower: &Address, count: u64) -> u64 {
+    let hash = keccak256(borrower.as_bytes());
+    u64::from_be_bytes(hash[..8].try_into().expect("8 bytes")) % count
+}
+
+impl FromStr for Shard {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "Invalid shard {}, expected <index>/<count> with index < count",
+                s
+            )
+        };
+        let (index, count) = s.split_once('/').ok_or_else(invalid)?;
+        let shard = Shard {
+            index: index.trim().parse().map_err(|_| invalid())?,
+            count: count.trim().parse().map_err(|_| invalid())?,
+        };
+        if shard.index >= shard.count {
+            return Err(invalid());
+        }
+        Ok(shard)
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+fn allowed(
+    allowlist: &Option<HashSet<Address>>,
+    denylist: &HashSet<Address>,
+    asset: &Address,
+) -> bool {
+    allowlist
+        .as_ref()
+        .is_none_or(|allowlist| allowlist.contains(asset))
+        && !denylist.contains(asset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_liquidation_filter() {
+        let weth = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        let long_tail = Address::repeat_byte(3);
+
+        let filter = LiquidationFilter {
+            collateral_denylist: HashSet::from([long_tail]),
+            debt_allowlist: Some(HashSet::from([usdc])),
+            borrower_blacklist: HashSet::from([weth]),
+            ..Default::default()
+        };
+        assert!(filter.allows_collateral(&weth));
+        assert!(!filter.allows_collateral(&long_tail));
+        assert!(filter.allows_debt(&usdc));
+        assert!(!filter.allows_debt(&weth));
+        assert!(!filter.allows_borrower(&weth));
+        assert!(filter.allows_borrower(&usdc));
+
+        // denied wins over allowed
+        let filter = LiquidationFilter {
+            debt_allowlist: Some(HashSet::from([usdc])),
+            debt_denylist: HashSet::from([usdc]),
+            ..Default::default()
+        };
+        assert!(!filter.allows_debt(&usdc));
+    }
+
+    #[test]
+    fn test_shard() {
+        assert_eq!(
+            "1/4".parse::<Shard>().unwrap(),
+            Shard { index: 1, count: 4 }
+        );
+        assert_eq!(Shard { index: 1, count: 4 }.to_string(), "1/4");
+        assert!("4/4".parse::<Shard>().is_err());
+        assert!("0/0".parse::<Shard>().is_err());
+        assert!("1".parse::<Shard>().is_err());
+
+        // every borrower is in exactly one shard, and the shards are about even
+        let shards: Vec<Shard> = (0..4).map(|index| Shard { index, count: 4 }).collect();
+        let mut sizes = [0; 4];
+        for i in 0..1000u64 {
+            let borrower = Address::from_low_u64_be(i);
+            let holding: Vec<&Shard> = shards.iter().filter(|s| s.contains(&borrower)).collect();
+            assert_eq!(holding.len(), 1);
+            sizes[holding[0].index as usize] += 1;
+            assert!(Shard::default().contains(&borrower));
+        }
+        assert!(sizes.iter().all(|size| (150..350).contains(size)));
+
+        let borrower = Address::repeat_byte(1);
+        let filter = LiquidationFilter {
+            shard: Shard {
+                index: (shard_of(&borrower, 2) + 1) % 2,
+                count: 2,
+            },
+            ..Default::default()
+        };
+        assert!(!filter.allows_borrower(&borrower));
+    }
+}
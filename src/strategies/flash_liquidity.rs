@@ -0,0 +1,384 @@
+use anyhow::{anyhow, Result};
+use ethers::abi::{self, ParamType, Token};
+use ethers::types::{Address, Bytes, H256, I256, U256};
+use ethers::utils::id;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The Balancer V2 Vault, at the same address on every chain it's deployed on. The
+/// Liquidator contract flash-loans the debt from it.
+pub const BALANCER_VAULT_ADDRESS: &str = "0xBA12222222228d8Ba445958a75a0704d566BF2C8";
+
+const GET_POOL_TOKENS_SIGNATURE: &str = "getPoolTokens(bytes32)";
+const QUERY_BATCH_SWAP_SIGNATURE: &str =
+    "queryBatchSwap(uint8,(bytes32,uint256,uint256,uint256,bytes)[],address[],(address,bool,address,bool))";
+const CURVE_COINS_SIGNATURE: &str = "coins(uint256)";
+const CURVE_GET_DY_SIGNATURE: &str = "get_dy(int128,int128,uint256)";
+
+/// `SwapKind.GIVEN_OUT` of the Balancer Vault.
+const BALANCER_GIVEN_OUT: u8 = 1;
+
+/// Most coins read from a Curve pool, none has more.
+pub const MAX_CURVE_COINS: usize = 8;
+
+/// A Balancer or Curve pool the collateral can be swapped for the debt on, when Uniswap V3
+/// lacks liquidity for the pair. Set as `balancer:<pool id>` or `curve:<pool address>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlashLiquidityPool {
+    Balancer(H256),
+    Curve(Address),
+}
+
+impl FromStr for FlashLiquidityPool {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            Some(("balancer", pool_id)) => Ok(FlashLiquidityPool::Balancer(H256::from_str(pool_id)?)),
+            Some(("curve", pool)) => Ok(FlashLiquidityPool::Curve(Address::from_str(pool)?)),
+            _ => Err(anyhow!(
+                "Invalid flash liquidity pool {}, expected balancer:<pool id> or curve:<pool address>",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FlashLiquidityPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlashLiquidityPool::Balancer(pool_id) => write!(f, "balancer:{:?}", pool_id),
+            FlashLiquidityPool::Curve(pool) => write!(f, "curve:{:?}", pool),
+        }
+    }
+}
+
+/// A flash liquidity pool with its tokens, in the pool's order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashLiquidityVenue {
+    pub pool: FlashLiquidityPool,
+    pub tokens: Vec<Address>,
+}
+
+impl FlashLiquidityVenue {
+    /// Indexes of `token_in` and `token_out` in the pool, none unless it has both.
+    pub fn indexes(&self, token_in: Address, token_out: Address) -> Option<(usize, usize)> {
+        let index = |token| self.tokens.iter().position(|t| *t == token);
+        Some((index(token_in)?, index(token_out)?))
+    }
+}
+
+/// How the collateral seized repays the flash loan of the debt, as the Liquidator contract's
+/// `liquidateFlashLoan` takes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlashSwap {
+    /// Swap for exactly the debt owed on a Balancer pool, spending at most `max_amount_in`. The
+    /// Vault can't be reentered during its own loans, so the pool lends the debt for its premium.
+    Balancer { pool_id: H256, max_amount_in: U256 },
+    /// Swap `amount_in` of coin `i` for coin `j` on a Curve pool, which only swaps exact inputs.
+    Curve {
+        pool: Address,
+        i: i128,
+        j: i128,
+        amount_in: U256,
+    },
+    /// Sell `amount_in` through an aggregator's `router` with the calldata it quoted for the
+    /// Liquidator, approving `spender` to pull it.
+    Aggregator {
+        router: Address,
+        spender: Address,
+        amount_in: U256,
+        data: Bytes,
+    },
+}
+
+impl fmt::Display for FlashSwap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FlashSwap::Balancer { pool_id, .. } => FlashLiquidityPool::Balancer(*pool_id).fmt(f),
+            FlashSwap::Curve { pool, .. } => FlashLiquidityPool::Curve(*pool).fmt(f),
+            FlashSwap::Aggregator { router, .. } => write!(f, "aggregator:{:?}", router),
+        }
+    }
+}
+
+impl FlashSwap {
+    /// `abi.encode(FlashSwapVenue, params)`, the `swap` arg of `liquidateFlashLoan`.
+    pub fn encode(&self) -> Bytes {
+        let (venue, params) = match self {
+            FlashSwap::Balancer {
+                pool_id,
+                max_amount_in,
+            } => (
+                0u8,
+                abi::encode(&[
+                    Token::FixedBytes(pool_id.as_bytes().to_vec()),
+                    Token::Uint(*max_amount_in),
+                ]),
+            ),
+            FlashSwap::Curve {
+                pool,
+                i,
+                j,
+                amount_in,
+            } => (
+                1u8,
+                abi::encode(&[
+                    Token::Address(*pool),
+                    Token::Int(I256::from(*i).into_raw()),
+                    Token::Int(I256::from(*j).into_raw()),
+                    Token::Uint(*amount_in),
+                ]),
+            ),
+            FlashSwap::Aggregator {
+                router,
+                spender,
+                amount_in,
+                data,
+            } => (
+                2u8,
+                abi::encode(&[
+                    Token::Address(*router),
+                    Token::Address(*spender),
+                    Token::Uint(*amount_in),
+                    Token::Bytes(data.to_vec()),
+                ]),
+            ),
+        };
+        abi::encode(&[Token::Uint(venue.into()), Token::Bytes(params)]).into()
+    }
+}
+
+/// Calldata reading the tokens of a Balancer pool from the Vault.
+pub fn encode_get_pool_tokens(pool_id: H256) -> Bytes {
+    let mut data = id(GET_POOL_TOKENS_SIGNATURE).to_vec();
+    data.extend(abi::encode(&[Token::FixedBytes(
+        pool_id.as_bytes().to_vec(),
+    )]));
+    data.into()
+}
+
+pub fn decode_pool_tokens(output: &[u8]) -> Result<Vec<Address>> {
+    let tokens = abi::decode(
+        &[
+            ParamType::Array(Box::new(ParamType::Address)),
+            ParamType::Array(Box::new(ParamType::Uint(256))),
+            ParamType::Uint(256),
+        ],
+        output,
+    )?;
+    match &tokens[0] {
+        Token::Array(tokens) => Ok(tokens
+            .iter()
+            .filter_map(|token| token.clone().into_address())
+            .collect()),
+        _ => Err(anyhow!("Invalid getPoolTokens output")),
+    }
+}
+
+/// Calldata quoting, through the Vault's `queryBatchSwap`, how much `token_in` swaps for
+/// exactly `amount_out` of `token_out` on a Balancer pool.
+pub fn encode_query_given_out(
+    pool_id: H256,
+    token_in: Address,
+    token_out: Address,
+    amount_out: U256,
+    from: Address,
+) -> Bytes {
+    let swap = Token::Tuple(vec![
+        Token::FixedBytes(pool_id.as_bytes().to_vec()),
+        Token::Uint(U256::zero()),
+        Token::Uint(U256::one()),
+        Token::Uint(amount_out),
+        Token::Bytes(Vec::new()),
+    ]);
+    let funds = Token::Tuple(vec![
+        Token::Address(from),
+        Token::Bool(false),
+        Token::Address(from),
+        Token::Bool(false),
+    ]);
+    let mut data = id(QUERY_BATCH_SWAP_SIGNATURE).to_vec();
+    data.extend(abi::encode(&[
+        Token::Uint(BALANCER_GIVEN_OUT.into()),
+        Token::Array(vec![swap]),
+        Token::Array(vec![Token::Address(token_in), Token::Address(token_out)]),
+        funds,
+    ]));
+    data.into()
+}
+
+/// The amount in of a `queryBatchSwap` from `encode_query_given_out`, the Vault's delta of
+/// the first asset.
+pub fn decode_query_amount_in(output: &[u8]) -> Result<U256> {
+    let tokens = abi::decode(&[ParamType::Array(Box::new(ParamType::Int(256)))], output)?;
+    let delta = match &tokens[0] {
+        Token::Array(deltas) => deltas.first().cloned().and_then(Token::into_int),
+        _ => None,
+    }
+    .ok_or(anyhow!("Invalid queryBatchSwap output"))?;
+    let delta = I256::from_raw(delta);
+    if delta.is_negative() {
+        return Err(anyhow!("queryBatchSwap pays out the token in"));
+    }
+    Ok(delta.into_raw())
+}
+
+/// Calldata reading coin `i` of a Curve pool, which reverts past the last coin.
+pub fn encode_curve_coins(i: usize) -> Bytes {
+    let mut data = id(CURVE_COINS_SIGNATURE).to_vec();
+    data.extend(abi::encode(&[Token::Uint(i.into())]));
+    data.into()
+}
+
+pub fn decode_curve_coin(output: &[u8]) -> Result<Address> {
+    abi::decode(&[ParamType::Address], output)?
+        .remove(0)
+        .into_address()
+        .ok_or(anyhow!("Invalid coins output"))
+}
+
+/// Calldata quoting how much of coin `j` swapping `dx` of coin `i` gets on a Curve pool.
+pub fn encode_curve_get_dy(i: i128, j: i128, dx: U256) -> Bytes {
+    let mut data = id(CURVE_GET_DY_SIGNATURE).to_vec();
+    data.extend(abi::encode(&[
+        Token::Int(I256::from(i).into_raw()),
+        Token::Int(I256::from(j).into_raw()),
+        Token::Uint(dx),
+    ]));
+    data.into()
+}
+
+pub fn decode_curve_get_dy(output: &[u8]) -> Result<U256> {
+    abi::decode(&[ParamType::Uint(256)], output)?
+        .remove(0)
+        .into_uint()
+        .ok_or(anyhow!("Invalid get_dy output"))
+}
+
+/// Input swapping on Curve for at least `amount_out`, at the rate of a quote of `quoted_in`
+/// for `quoted_out`. Swapping less than the quote gets a rate at least as good, so this is
+/// enough as long as `amount_out` doesn't exceed `quoted_out`. Rounds up.
+pub fn curve_amount_in(amount_out: U256, quoted_in: U256, quoted_out: U256) -> Option<U256> {
+    if quoted_out.is_zero() || amount_out > quoted_out {
+        return None;
+    }
+    let amount_in = amount_out.checked_mul(quoted_in)?;
+    Some((amount_in + quoted_out - 1) / quoted_out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flash_liquidity_pool() {
+        let pool_id = H256::repeat_byte(0xab);
+        let pool = FlashLiquidityPool::from_str(&format!("balancer:{:?}", pool_id)).unwrap();
+        assert_eq!(pool, FlashLiquidityPool::Balancer(pool_id));
+        assert_eq!(
+            pool.to_string().parse::<FlashLiquidityPool>().unwrap(),
+            pool
+        );
+
+        let curve = Address::repeat_byte(1);
+        assert_eq!(
+            format!("curve:{:?}", curve)
+                .parse::<FlashLiquidityPool>()
+                .unwrap(),
+            FlashLiquidityPool::Curve(curve)
+        );
+        assert!("uniswap:0x01".parse::<FlashLiquidityPool>().is_err());
+        assert!("curve:0x01".parse::<FlashLiquidityPool>().is_err());
+    }
+
+    #[test]
+    fn test_encode_flash_swap() {
+        let swap = FlashSwap::Curve {
+            pool: Address::repeat_byte(1),
+            i: 0,
+            j: 2,
+            amount_in: U256::from(1000),
+        };
+        let tokens = abi::decode(&[ParamType::Uint(8), ParamType::Bytes], &swap.encode()).unwrap();
+        assert_eq!(tokens[0], Token::Uint(U256::one()));
+        let Token::Bytes(params) = &tokens[1] else {
+            panic!("params aren't bytes");
+        };
+        let params = abi::decode(
+            &[
+                ParamType::Address,
+                ParamType::Int(128),
+                ParamType::Int(128),
+                ParamType::Uint(256),
+            ],
+            params,
+        )
+        .unwrap();
+        assert_eq!(params[0], Token::Address(Address::repeat_byte(1)));
+        assert_eq!(params[2], Token::Int(U256::from(2)));
+        assert_eq!(params[3], Token::Uint(U256::from(1000)));
+
+        let swap = FlashSwap::Balancer {
+            pool_id: H256::repeat_byte(2),
+            max_amount_in: U256::from(500),
+        };
+        let tokens = abi::decode(&[ParamType::Uint(8), ParamType::Bytes], &swap.encode()).unwrap();
+        assert_eq!(tokens[0], Token::Uint(U256::zero()));
+
+        let swap = FlashSwap::Aggregator {
+            router: Address::repeat_byte(3),
+            spender: Address::repeat_byte(4),
+            amount_in: U256::from(700),
+            data: Bytes::from(vec![0xde, 0xad]),
+        };
+        let tokens = abi::decode(&[ParamType::Uint(8), ParamType::Bytes], &swap.encode()).unwrap();
+        assert_eq!(tokens[0], Token::Uint(U256::from(2)));
+        let Token::Bytes(params) = &tokens[1] else {
+            panic!("params aren't bytes");
+        };
+        let params = abi::decode(
+            &[
+                ParamType::Address,
+                ParamType::Address,
+                ParamType::Uint(256),
+                ParamType::Bytes,
+            ],
+            params,
+        )
+        .unwrap();
+        assert_eq!(params[1], Token::Address(Address::repeat_byte(4)));
+        assert_eq!(params[2], Token::Uint(U256::from(700)));
+        assert_eq!(params[3], Token::Bytes(vec![0xde, 0xad]));
+    }
+
+    #[test]
+    fn test_decode_query_amount_in() {
+        let output = abi::encode(&[Token::Array(vec![
+            Token::Int(I256::from(1050).into_raw()),
+            Token::Int(I256::from(-1000).into_raw()),
+        ])]);
+        assert_eq!(decode_query_amount_in(&output).unwrap(), U256::from(1050));
+        let output = abi::encode(&[Token::Array(vec![Token::Int(I256::from(-1).into_raw())])]);
+        assert!(decode_query_amount_in(&output).is_err());
+    }
+
+    #[test]
+    fn test_curve_amount_in() {
+        // 1000 in quoted for 990 out
+        assert_eq!(
+            curve_amount_in(U256::from(500), U256::from(1000), U256::from(990)),
+            Some(U256::from(506))
+        );
+        assert_eq!(
+            curve_amount_in(U256::from(990), U256::from(1000), U256::from(990)),
+            Some(U256::from(1000))
+        );
+        // not enough liquidity
+        assert_eq!(
+            curve_amount_in(U256::from(991), U256::from(1000), U256::from(990)),
+            None
+        );
+    }
+}
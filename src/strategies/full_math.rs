@@ -0,0 +1,121 @@
+use ethers::types::{U256, U512};
+
+/// `a * b / denominator` rounded down, with the product kept at 512 bits so that only a
+/// result over 256 bits overflows. None on overflow or a zero denominator.
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    mul_add_div(a, b, U256::zero(), denominator)
+}
+
+/// `(a * b + addend) / denominator` rounded down, at 512 bits like `mul_div`. Adding half
+/// the unit or half the divisor rounds half up, as WadRayMath and PercentageMath do.
+pub fn mul_add_div(a: U256, b: U256, addend: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    // a full 256 x 256 bit product plus a 256 bit addend can't overflow 512 bits
+    let numerator = a.full_mul(b) + U512::from(addend);
+    U256::try_from(numerator / U512::from(denominator)).ok()
+}
+
+/// Product of `numerator` divided by the product of `denominator`, rounded down, at 512
+/// bits. None if either product overflows 512 bits, the result 256 bits, or on a zero
+/// denominator.
+pub fn product_div(numerator: &[U256], denominator: &[U256]) -> Option<U256> {
+    let product = |factors: &[U256]| {
+        factors.iter().try_fold(U512::one(), |product, factor| {
+            product.checked_mul(U512::from(*factor))
+        })
+    };
+    let denominator = product(denominator)?;
+    if denominator.is_zero() {
+        return None;
+    }
+    U256::try_from(product(numerator)? / denominator).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::math_reference::{any_u256, big, to_u256};
+    use num_bigint::BigUint;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_mul_div() {
+        // the product overflows 256 bits but the result fits
+        assert_eq!(
+            mul_div(U256::MAX, U256::exp10(18), U256::exp10(18)),
+            Some(U256::MAX)
+        );
+        assert_eq!(
+            mul_div(U256::MAX, U256::from(3), U256::from(4)),
+            Some(U256::MAX / 4 * 3 + 2)
+        );
+        assert_eq!(mul_div(U256::MAX, U256::from(2), U256::one()), None);
+        assert_eq!(mul_div(U256::one(), U256::one(), U256::zero()), None);
+
+        // rounding half up
+        assert_eq!(
+            mul_add_div(U256::from(3), U256::from(5), U256::from(5), U256::from(10)),
+            Some(U256::from(2))
+        );
+        assert_eq!(
+            mul_add_div(U256::MAX, U256::one(), U256::MAX, U256::from(2)),
+            Some(U256::MAX)
+        );
+    }
+
+    #[test]
+    fn test_product_div() {
+        let price = U256::from(6_000_000_000_000u64);
+        let amount = U256::MAX / 1000;
+        let unit = U256::exp10(18);
+        assert_eq!(
+            product_div(&[price, amount, unit], &[price, unit]),
+            Some(amount)
+        );
+        assert_eq!(product_div(&[price, unit], &[]), Some(price * unit));
+        assert_eq!(product_div(&[U256::MAX, U256::MAX], &[U256::one()]), None);
+        assert_eq!(
+            product_div(&[U256::MAX, U256::MAX, U256::from(2)], &[U256::MAX]),
+            None
+        );
+        assert_eq!(product_div(&[U256::one()], &[U256::zero()]), None);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_mul_add_div_matches_reference(
+            a in any_u256(),
+            b in any_u256(),
+            addend in any_u256(),
+            denominator in any_u256(),
+        ) {
+            prop_assume!(!denominator.is_zero());
+            let expected = to_u256((big(a) * big(b) + big(addend)) / big(denominator));
+            prop_assert_eq!(mul_add_div(a, b, addend, denominator), expected);
+        }
+
+        #[test]
+        fn prop_product_div_matches_reference(
+            numerator in vec(any_u256(), 0..4),
+            denominator in vec(any_u256(), 0..3),
+        ) {
+            let product = |factors: &[U256]| {
+                factors.iter().fold(BigUint::from(1u8), |product, factor| product * big(*factor))
+            };
+            let numerator_product = product(&numerator);
+            let denominator_product = product(&denominator);
+            prop_assume!(denominator_product.bits() > 0);
+            // intermediate products are capped at 512 bits
+            let overflows = numerator_product.bits() > 512 || denominator_product.bits() > 512;
+            let expected = if overflows {
+                None
+            } else {
+                to_u256(numerator_product / denominator_product)
+            };
+            prop_assert_eq!(product_div(&numerator, &denominator), expected);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use ethers::types::{Address, U256};
+
+// the strategy's ETH prices have 8 decimals, wei have 18
+const PRICE_TO_WEI: u64 = 10_000_000_000;
+
+/// How seized collateral is collected, converted and swept.
+#[derive(Debug, Clone, Default)]
+pub struct HarvestConfig {
+    /// Asset proceeds are converted into, WETH if unset.
+    pub target: Option<Address>,
+    /// Core assets held by the sender that are never swapped, the debt assets it repays with
+    /// if empty.
+    pub keep: Vec<Address>,
+    /// Balances worth less than this (in wei) are left alone, the swap would cost more in gas.
+    pub min_swap_wei: U256,
+    /// Wallet the target asset is swept to, sweeping is disabled if unset.
+    pub cold_wallet: Option<Address>,
+    /// Balance of the target asset (in its own units) kept by the sender when sweeping.
+    pub sweep_threshold: U256,
+}
+
+impl HarvestConfig {
+    /// Whether `asset` held by the sender is never swapped. Unless assets are listed, these
+    /// are the debt assets, when the sender repays debt out of its own balance.
+    pub fn keeps(&self, asset: &Address, repays_with: bool) -> bool {
+        if self.keep.is_empty() {
+            repays_with
+        } else {
+            self.keep.contains(asset)
+        }
+    }
+}
+
+/// Value in wei of `balance` of an asset priced at `price_eth` (8 decimals).
+pub fn value_wei(balance: U256, price_eth: U256, decimals: u64) -> U256 {
+    balance * price_eth * PRICE_TO_WEI / U256::exp10(decimals as usize)
+}
+
+/// Amount to sweep to the cold wallet, whatever is held above the threshold.
+pub fn sweep_amount(balance: U256, threshold: U256) -> Option<U256> {
+    (balance > threshold).then(|| balance - threshold)
+}
+
+/// Amount of a held aToken to withdraw given the reserve's available liquidity, everything
+/// (`U256::MAX`, so interest accrued meanwhile isn't left behind) when the reserve can pay
+/// it all out.
+pub fn withdraw_amount(a_balance: U256, available_liquidity: U256) -> Option<U256> {
+    if a_balance.is_zero() || available_liquidity.is_zero() {
+        None
+    } else if available_liquidity >= a_balance {
+        Some(U256::MAX)
+    } else {
+        Some(available_liquidity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_wei() {
+        // 2000 USDC at 0.0005 ETH each
+        let balance = U256::from(2_000_000_000u64);
+        assert_eq!(value_wei(balance, U256::from(50_000), 6), U256::exp10(18));
+    }
+
+    #[test]
+    fn test_sweep_amount() {
+        let threshold = U256::from(100);
+        assert_eq!(
+            sweep_amount(U256::from(150), threshold),
+            Some(U256::from(50))
+        );
+        assert_eq!(sweep_amount(U256::from(100), threshold), None);
+        assert_eq!(
+            sweep_amount(U256::from(7), U256::zero()),
+            Some(U256::from(7))
+        );
+    }
+
+    #[test]
+    fn test_keeps() {
+        let (weth, usdc) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut harvest = HarvestConfig::default();
+        // debt assets the sender repays with are kept by default
+        assert!(harvest.keeps(&usdc, true));
+        assert!(!harvest.keeps(&weth, false));
+        // listed assets replace the default
+        harvest.keep = vec![weth];
+        assert!(harvest.keeps(&weth, false));
+        assert!(!harvest.keeps(&usdc, true));
+    }
+
+    #[test]
+    fn test_withdraw_amount() {
+        assert_eq!(
+            withdraw_amount(U256::from(100), U256::from(1000)),
+            Some(U256::MAX)
+        );
+        // an illiquid reserve is drained of what it has, the rest waits for the next harvest
+        assert_eq!(
+            withdraw_amount(U256::from(100), U256::from(40)),
+            Some(U256::from(40))
+        );
+        assert_eq!(withdraw_amount(U256::from(100), U256::zero()), None);
+        assert_eq!(withdraw_amount(U256::zero(), U256::from(1000)), None);
+    }
+}
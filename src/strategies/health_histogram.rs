@@ -0,0 +1,132 @@
+use ethers::types::{Address, U256};
+use serde::Serialize;
+
+/// Upper bounds of the health factor buckets, in bps, the last bucket taking the rest.
+pub const HEALTH_FACTOR_BUCKETS_BPS: [u64; 7] = [10000, 10200, 10500, 11000, 12500, 15000, 20000];
+
+/// Accounts closest to liquidation kept with the histogram.
+pub const CLOSEST_ACCOUNTS: usize = 10;
+
+const HEALTH_FACTOR_BPS_DIVISOR: u64 = 100_000_000_000_000;
+
+/// Borrowers whose health factor is below `max_bps`, and above the previous bucket's.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HealthFactorBucket {
+    /// Unset for the last bucket.
+    pub max_bps: Option<u64>,
+    pub borrowers: u64,
+    /// Debt of the bucket's borrowers, in the oracle's base currency.
+    pub total_debt_base: U256,
+}
+
+/// A borrower close to liquidation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AtRiskAccount {
+    pub borrower: Address,
+    pub health_factor: U256,
+    pub total_debt_base: U256,
+}
+
+/// Borrowers of a full scan bucketed by health factor, warning of a wave of liquidations
+/// before it comes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HealthFactorHistogram {
+    pub block_number: u64,
+    pub buckets: Vec<HealthFactorBucket>,
+    /// Lowest health factors first, at most `CLOSEST_ACCOUNTS`.
+    pub closest: Vec<AtRiskAccount>,
+}
+
+impl HealthFactorHistogram {
+    pub fn new(block_number: u64) -> Self {
+        let buckets = HEALTH_FACTOR_BUCKETS_BPS
+            .iter()
+            .map(|max_bps| Some(*max_bps))
+            .chain([None])
+            .map(|max_bps| HealthFactorBucket {
+                max_bps,
+                ..Default::default()
+            })
+            .collect();
+        Self {
+            block_number,
+            buckets,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, borrower: Address, health_factor: U256, total_debt_base: U256) {
+        let bps = health_factor / HEALTH_FACTOR_BPS_DIVISOR;
+        if let Some(bucket) = self.buckets.iter_mut().find(|bucket| {
+            bucket
+                .max_bps
+                .is_none_or(|max_bps| bps < U256::from(max_bps))
+        }) {
+            bucket.borrowers += 1;
+            bucket.total_debt_base += total_debt_base;
+        }
+
+        // keep the list short while scanning, trimming it once it doubles
+        self.closest.push(AtRiskAccount {
+            borrower,
+            health_factor,
+            total_debt_base,
+        });
+        if self.closest.len() >= 2 * CLOSEST_ACCOUNTS {
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        self.closest.sort_by_key(|account| account.health_factor);
+        self.closest.truncate(CLOSEST_ACCOUNTS);
+    }
+
+    /// Finish the histogram once every borrower is recorded.
+    pub fn finish(mut self) -> Self {
+        self.trim();
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let health_factor = |bps: u64| U256::from(HEALTH_FACTOR_BPS_DIVISOR) * bps;
+        let mut histogram = HealthFactorHistogram::new(100);
+        for i in 0..30u64 {
+            histogram.record(
+                Address::from_low_u64_be(i),
+                health_factor(10100 + i * 100),
+                U256::from(1000),
+            );
+        }
+        histogram.record(Address::zero(), health_factor(9500), U256::from(50));
+        histogram.record(Address::zero(), U256::MAX, U256::from(7));
+        let histogram = histogram.finish();
+
+        assert_eq!(histogram.buckets.len(), HEALTH_FACTOR_BUCKETS_BPS.len() + 1);
+        // below 1
+        assert_eq!(histogram.buckets[0].borrowers, 1);
+        assert_eq!(histogram.buckets[0].total_debt_base, U256::from(50));
+        // 1.01 and 1.02 fall on either side of the 1.02 bound
+        assert_eq!(histogram.buckets[1].borrowers, 1);
+        assert_eq!(histogram.buckets[2].borrowers, 3);
+        assert_eq!(histogram.buckets[5].borrowers, 6);
+        assert_eq!(histogram.buckets[6].borrowers, 0);
+        // above 2, like borrowers without debt
+        assert_eq!(histogram.buckets[7].max_bps, None);
+        assert_eq!(histogram.buckets[7].borrowers, 1);
+        assert_eq!(
+            histogram.buckets.iter().map(|b| b.borrowers).sum::<u64>(),
+            32
+        );
+
+        assert_eq!(histogram.closest.len(), CLOSEST_ACCOUNTS);
+        assert_eq!(histogram.closest[0].health_factor, health_factor(9500));
+        assert_eq!(histogram.closest[1].health_factor, health_factor(10100));
+    }
+}
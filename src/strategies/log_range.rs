@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result};
+use ethers::types::U64;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info};
+
+/// Block range `eth_getLogs` windows start at.
+pub const LOG_BLOCK_RANGE: u64 = 1024;
+
+/// Largest window tried, however well the provider keeps up.
+pub const MAX_LOG_BLOCK_RANGE: u64 = 100_000;
+
+/// Windows that must succeed in a row before the range grows again.
+const GROW_AFTER: u32 = 8;
+
+// how providers word a range too large or too many results, lowercased
+const RANGE_ERRORS: [&str; 10] = [
+    "query returned more than",
+    "more than 10000 results",
+    "block range",
+    "range is too large",
+    "range too large",
+    "exceed maximum block range",
+    "log response size exceeded",
+    "response size exceeded",
+    "too many results",
+    "limit exceeded",
+];
+
+/// Whether a failed `eth_getLogs` was refused for its range or result count, and may
+/// succeed over a smaller window.
+pub fn is_range_error(error: &str) -> bool {
+    let error = error.to_lowercase();
+    RANGE_ERRORS.iter().any(|pattern| error.contains(pattern))
+}
+
+#[derive(Debug)]
+struct RangeState {
+    range: u64,
+    /// Smallest window the provider refused, the range stays below it
+    ceiling: u64,
+    successes: u32,
+}
+
+/// The `eth_getLogs` block range learned for the provider. Windows the provider refuses
+/// are bisected and the range shrinks, then grows back after a run of successes, staying
+/// below the smallest window that failed.
+#[derive(Debug, Clone)]
+pub struct LogRange {
+    state: Arc<Mutex<RangeState>>,
+}
+
+impl Default for LogRange {
+    fn default() -> Self {
+        Self::new(LOG_BLOCK_RANGE)
+    }
+}
+
+impl LogRange {
+    pub fn new(range: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(RangeState {
+                range: range.clamp(1, MAX_LOG_BLOCK_RANGE),
+                ceiling: MAX_LOG_BLOCK_RANGE + 1,
+                successes: 0,
+            })),
+        }
+    }
+
+    pub fn range(&self) -> u64 {
+        self.state.lock().unwrap().range
+    }
+
+    /// Query logs from `from_block` to `to_block` in windows of the learned range, `query`
+    /// fetching one window's inclusive bounds.
+    pub async fn query<T, E, F, Fut>(
+        &self,
+        from_block: U64,
+        to_block: U64,
+        query: F,
+    ) -> Result<Vec<T>>
+    where
+        E: Display,
+        F: Fn(u64, u64) -> Fut,
+        Fut: Future<Output = Result<Vec<T>, E>>,
+    {
+        let mut logs = Vec::new();
+        let to_block = to_block.as_u64();
+        let mut start_block = from_block.as_u64();
+        while start_block < to_block {
+            let end_block = std::cmp::min(start_block + self.range() - 1, to_block);
+            match query(start_block, end_block).await {
+                Ok(window) => {
+                    logs.extend(window);
+                    self.succeeded();
+                    start_block = end_block + 1;
+                }
+                Err(e) if start_block < end_block && is_range_error(&e.to_string()) => {
+                    self.refused(end_block - start_block + 1, &e);
+                }
+                Err(e) => {
+                    return Err(anyhow!(
+                        "Logs of blocks {} to {} failed: {}",
+                        start_block,
+                        end_block,
+                        e
+                    ))
+                }
+            }
+        }
+        Ok(logs)
+    }
+
+    fn succeeded(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.successes += 1;
+        if state.successes < GROW_AFTER {
+            return;
+        }
+        state.successes = 0;
+        // halfway to the smallest window refused, doubling at most
+        let range = std::cmp::min(state.range * 2, (state.range + state.ceiling) / 2);
+        if range > state.range {
+            debug!("Growing the log block range to {}", range);
+            state.range = range;
+        }
+    }
+
+    fn refused(&self, window: u64, error: &impl Display) {
+        let mut state = self.state.lock().unwrap();
+        state.ceiling = std::cmp::min(state.ceiling, window);
+        state.range = std::cmp::max(window / 2, 1);
+        state.successes = 0;
+        info!(
+            "Provider refused {} blocks of logs ({}), bisecting to {}",
+            window, error, state.range
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_query() {
+        let range = LogRange::new(1000);
+        // a provider serving at most 300 blocks, every block one log
+        let logs = range
+            .query(0.into(), 1000.into(), |from, to| async move {
+                if to - from + 1 > 300 {
+                    Err("query returned more than 10000 results")
+                } else {
+                    Ok((from..=to).collect::<Vec<u64>>())
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(logs, (0..1000).collect::<Vec<u64>>());
+        assert_eq!(range.range(), 250);
+
+        // the range grows back after a run of successes, halfway to the window that failed
+        for _ in 0..GROW_AFTER - 4 {
+            range.succeeded();
+        }
+        assert_eq!(range.range(), 375);
+
+        let error = range
+            .query(0.into(), 10.into(), |_, _| async {
+                Err::<Vec<u64>, _>("connection reset")
+            })
+            .await;
+        assert!(error.is_err());
+        assert!(is_range_error(
+            "eth_getLogs block range is too large, max 2000"
+        ));
+        assert!(!is_range_error("execution reverted"));
+    }
+}
@@ -0,0 +1,34 @@
+//! Arbitrary precision references for the property tests of the math modules.
+
+use ethers::types::{I256, U256};
+use num_bigint::{BigInt, BigUint, Sign};
+use proptest::prelude::*;
+
+pub fn big(value: U256) -> BigUint {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    BigUint::from_bytes_be(&bytes)
+}
+
+pub fn big_signed(value: I256) -> BigInt {
+    let sign = if value.is_negative() {
+        Sign::Minus
+    } else {
+        Sign::Plus
+    };
+    BigInt::from_biguint(sign, big(value.unsigned_abs()))
+}
+
+/// `value` if it fits in 256 bits.
+pub fn to_u256(value: BigUint) -> Option<U256> {
+    (value.bits() <= 256).then(|| U256::from_big_endian(&value.to_bytes_be()))
+}
+
+/// Any 256 bit value, biased towards the small values amounts and prices take.
+pub fn any_u256() -> impl Strategy<Value = U256> {
+    prop_oneof![
+        any::<u64>().prop_map(U256::from),
+        any::<u128>().prop_map(U256::from),
+        any::<[u8; 32]>().prop_map(|bytes| U256::from_big_endian(&bytes)),
+    ]
+}
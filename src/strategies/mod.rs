@@ -1,3 +1,35 @@
 pub mod aave_strategy;
+pub mod approval;
+pub mod batch;
+pub mod bid_model;
+pub mod competitors;
+pub mod confidence;
+pub mod configurator_events;
+pub mod data_source;
+pub mod debt_swap;
+pub mod filters;
+pub mod flash_liquidity;
+pub mod full_math;
+pub mod harvest;
+pub mod health_histogram;
+pub mod log_range;
+#[cfg(test)]
+mod math_reference;
+pub mod multicall;
+pub mod percentage_math;
+pub mod price_check;
+pub mod profit_currency;
+pub mod reserve_cache;
+pub mod revert_errors;
+pub mod risk_engine;
+pub mod scoring;
+pub mod shared_state;
+pub mod simulation_trace;
+pub mod subgraph;
+pub mod swap_router;
+pub mod sync_progress;
 pub mod types;
+pub mod uniswap;
+pub mod user_configuration;
 pub mod wad_ray_math;
+pub mod wallet;
@@ -0,0 +1,178 @@
+use anyhow::{anyhow, Result};
+use ethers::{
+    abi::{Detokenize, Function, Token, Tokenizable},
+    contract::ContractCall,
+    providers::Middleware,
+    types::{transaction::eip2718::TypedTransaction, Address, Bytes},
+};
+use ethers_contract::Multicall;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+/// Where Multicall3 is deployed on most chains.
+pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// The Multicall3 contract of a chain, checked for on first use.
+#[derive(Debug, Clone)]
+pub struct MulticallSite {
+    address: Address,
+    deployed: Arc<OnceCell<bool>>,
+}
+
+impl Default for MulticallSite {
+    fn default() -> Self {
+        Self::new(Address::from_str(MULTICALL3_ADDRESS).unwrap())
+    }
+}
+
+impl MulticallSite {
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            deployed: Arc::new(OnceCell::new()),
+        }
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Start a batch of calls, sent through Multicall3 if it's deployed and one by one
+    /// otherwise.
+    pub async fn batch<M: Middleware + 'static>(&self, client: Arc<M>) -> Result<BatchCall<M>> {
+        let deployed = *self
+            .deployed
+            .get_or_try_init(|| async {
+                let code = client.get_code(self.address, None).await?;
+                if code.is_empty() {
+                    warn!(
+                        "No Multicall3 at {:?}, making calls one by one",
+                        self.address
+                    );
+                }
+                Ok::<_, anyhow::Error>(!code.is_empty())
+            })
+            .await?;
+        let multicall = if deployed {
+            Some(Multicall::new(client.clone(), Some(self.address)).await?)
+        } else {
+            None
+        };
+        Ok(BatchCall {
+            client,
+            multicall,
+            calls: Vec::new(),
+        })
+    }
+}
+
+/// Calls batched like ethers' `Multicall`, falling back to sequential `eth_call`s where
+/// Multicall3 isn't deployed.
+pub struct BatchCall<M> {
+    client: Arc<M>,
+    multicall: Option<Multicall<M>>,
+    /// Calls made one by one, when there is no multicall
+    calls: Vec<(TypedTransaction, Function, bool)>,
+}
+
+impl<M: Middleware + 'static> BatchCall<M> {
+    /// Add a call, the batch failing if it reverts unless `allow_failure` is set.
+    pub fn add_call<D: Detokenize>(
+        &mut self,
+        call: ContractCall<M, D>,
+        allow_failure: bool,
+    ) -> &mut Self {
+        match &mut self.multicall {
+            Some(multicall) => {
+                multicall.add_call(call, allow_failure);
+            }
+            None => self.calls.push((call.tx, call.function, allow_failure)),
+        }
+        self
+    }
+
+    pub fn clear_calls(&mut self) {
+        match &mut self.multicall {
+            Some(multicall) => multicall.clear_calls(),
+            None => self.calls.clear(),
+        }
+    }
+
+    /// The output of every call, or its revert data if it was allowed to fail.
+    pub async fn call_raw(&self) -> Result<Vec<Result<Token, Bytes>>> {
+        if let Some(multicall) = &self.multicall {
+            return Ok(multicall.call_raw().await?);
+        }
+        let mut results = Vec::with_capacity(self.calls.len());
+        for (tx, function, allow_failure) in self.calls.iter() {
+            match self.client.call(tx, None).await {
+                Ok(output) => results.push(Ok(decode_output(function, &output)?)),
+                Err(_) if *allow_failure => results.push(Err(Bytes::new())),
+                Err(e) => return Err(anyhow!("Call to {} failed: {}", function.name, e)),
+            }
+        }
+        Ok(results)
+    }
+
+    /// The output of every call, none of which may have failed.
+    pub async fn call_array<T: Tokenizable>(&self) -> Result<Vec<T>> {
+        if let Some(multicall) = &self.multicall {
+            return Ok(multicall.call_array().await?);
+        }
+        self.call_raw()
+            .await?
+            .into_iter()
+            .map(|result| {
+                let token = result.map_err(|_| anyhow!("Call reverted"))?;
+                Ok(T::from_token(token)?)
+            })
+            .collect()
+    }
+}
+
+// a single output as itself and several as a tuple, as Multicall returns them
+fn decode_output(function: &Function, output: &[u8]) -> Result<Token> {
+    let mut tokens = function.decode_output(output)?;
+    Ok(match tokens.len() {
+        1 => tokens.remove(0),
+        _ => Token::Tuple(tokens),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bindings_aave::i_aave_oracle::IAaveOracle;
+    use ethers::abi;
+    use ethers::providers::Provider;
+    use ethers::types::U256;
+
+    #[tokio::test]
+    async fn test_sequential_calls() {
+        let (provider, mock) = Provider::mocked();
+        let client = Arc::new(provider);
+        let oracle = IAaveOracle::new(Address::zero(), client.clone());
+        let price = |value: u64| Bytes::from(abi::encode(&[Token::Uint(U256::from(value))]));
+
+        // answers pop from the back: no code, then the two prices
+        mock.push(price(2)).unwrap();
+        mock.push(price(1)).unwrap();
+        mock.push(Bytes::new()).unwrap();
+
+        let site = MulticallSite::default();
+        let mut batch = site.batch(client.clone()).await.unwrap();
+        assert!(batch.multicall.is_none());
+        batch.add_call(oracle.get_asset_price(Address::repeat_byte(1)), false);
+        batch.add_call(oracle.get_asset_price(Address::repeat_byte(2)), false);
+        let prices: Vec<U256> = batch.call_array().await.unwrap();
+        assert_eq!(prices, vec![U256::from(1), U256::from(2)]);
+
+        let function = oracle.get_asset_price(Address::zero()).function;
+        assert_eq!(
+            decode_output(&function, &price(7)).unwrap(),
+            Token::Uint(U256::from(7))
+        );
+    }
+}
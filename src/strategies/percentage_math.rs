@@ -0,0 +1,151 @@
+use super::full_math::mul_add_div;
+use anyhow::{anyhow, Result};
+use ethers::types::U256;
+
+/// Aave's PercentageMath: percentages have 2 decimals of precision, 10000 = 100%.
+pub const PERCENTAGE_FACTOR: u64 = 10000;
+pub const HALF_PERCENTAGE_FACTOR: u64 = 5000;
+
+/// `value` times `percentage`, rounded half up (PercentageMath.percentMul). The library
+/// reverts once `value * percentage` overflows, this only errors if the result does.
+pub fn percent_mul(value: U256, percentage: u64) -> Result<U256> {
+    mul_add_div(
+        value,
+        U256::from(percentage),
+        U256::from(HALF_PERCENTAGE_FACTOR),
+        U256::from(PERCENTAGE_FACTOR),
+    )
+    .ok_or(anyhow!("percentMul: multiplication overflow"))
+}
+
+/// `value` divided by `percentage`, rounded half up (PercentageMath.percentDiv). Errors on a
+/// zero percentage, and like `percent_mul` only if the result overflows.
+pub fn percent_div(value: U256, percentage: u64) -> Result<U256> {
+    if percentage == 0 {
+        return Err(anyhow!("percentDiv: division by zero"));
+    }
+    let percentage = U256::from(percentage);
+    mul_add_div(
+        value,
+        U256::from(PERCENTAGE_FACTOR),
+        percentage / 2,
+        percentage,
+    )
+    .ok_or(anyhow!("percentDiv: multiplication overflow"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::math_reference::{any_u256, big, to_u256};
+    use proptest::prelude::*;
+
+    fn dec(s: &str) -> U256 {
+        U256::from_dec_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_percent_mul() {
+        assert_eq!(
+            percent_mul(U256::exp10(18), 5000).unwrap(),
+            dec("500000000000000000")
+        );
+        // 14.2515e18 * 74.42%
+        assert_eq!(
+            percent_mul(dec("14251500000000000000"), 7442).unwrap(),
+            dec("10605966300000000000")
+        );
+        assert_eq!(
+            percent_mul(dec("9087312000000000000000000000000000"), 1333).unwrap(),
+            dec("1211338689600000000000000000000000")
+        );
+        // half up
+        assert_eq!(percent_mul(U256::one(), 5000).unwrap(), U256::one());
+        assert_eq!(percent_mul(U256::one(), 4999).unwrap(), U256::zero());
+        // 1.0005 * 105% = 1.050525
+        assert_eq!(
+            percent_mul(U256::from(10005), 10500).unwrap(),
+            U256::from(10505)
+        );
+        assert_eq!(percent_mul(U256::zero(), 10500).unwrap(), U256::zero());
+        assert_eq!(percent_mul(U256::MAX, 0).unwrap(), U256::zero());
+    }
+
+    #[test]
+    fn test_percent_div() {
+        assert_eq!(
+            percent_div(U256::exp10(18), 5000).unwrap(),
+            dec("2000000000000000000")
+        );
+        assert_eq!(
+            percent_div(dec("14251500000000000000"), 7442).unwrap(),
+            dec("19150094060736361193")
+        );
+        assert_eq!(
+            percent_div(dec("9087312000000000000000000000000000"), 1333).unwrap(),
+            dec("68171882970742685671417854463615904")
+        );
+        // half up
+        assert_eq!(percent_div(U256::one(), 19999).unwrap(), U256::one());
+        assert_eq!(percent_div(U256::one(), 20001).unwrap(), U256::zero());
+        assert_eq!(
+            percent_div(U256::from(10500), 10500).unwrap(),
+            U256::from(10000)
+        );
+    }
+
+    #[test]
+    fn test_percent_math_bounds() {
+        // past the largest values the library accepts, the results still fit
+        let max_mul = (U256::MAX - U256::from(HALF_PERCENTAGE_FACTOR)) / 10500;
+        assert!(percent_mul(max_mul + 1, 10500).is_ok());
+        assert_eq!(percent_mul(U256::MAX, 5000).unwrap(), U256::one() << 255);
+        let max_div = (U256::MAX - U256::from(5250)) / PERCENTAGE_FACTOR;
+        assert!(percent_div(max_div + 1, 10500).is_ok());
+        assert!(percent_div(U256::MAX, 10500).is_ok());
+
+        assert!(percent_mul(U256::MAX, 10500).is_err());
+        assert!(percent_div(U256::MAX, 5000).is_err());
+        assert!(percent_div(U256::one(), 0).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_percent_math_matches_reference(value in any_u256(), percentage in any::<u16>()) {
+            let percentage = u64::from(percentage);
+            let expected = to_u256(
+                (big(value) * percentage + HALF_PERCENTAGE_FACTOR) / PERCENTAGE_FACTOR,
+            );
+            prop_assert_eq!(percent_mul(value, percentage).ok(), expected);
+            prop_assume!(percentage != 0);
+            let expected =
+                to_u256((big(value) * PERCENTAGE_FACTOR + percentage / 2) / percentage);
+            prop_assert_eq!(percent_div(value, percentage).ok(), expected);
+        }
+
+        // dividing by a bonus undoes applying it, to the unit
+        #[test]
+        fn prop_percent_div_inverts_percent_mul(
+            value in any::<u128>(),
+            percentage in PERCENTAGE_FACTOR..2 * PERCENTAGE_FACTOR,
+        ) {
+            let value = U256::from(value);
+            let back = percent_div(percent_mul(value, percentage).unwrap(), percentage).unwrap();
+            let difference = if back > value { back - value } else { value - back };
+            prop_assert!(difference <= U256::one());
+        }
+
+        #[test]
+        fn prop_percent_math_monotonic(
+            a in any::<u128>(),
+            b in any::<u128>(),
+            percentage in 1..2 * PERCENTAGE_FACTOR,
+        ) {
+            let (low, high) = (U256::from(a.min(b)), U256::from(a.max(b)));
+            let mul = |value| percent_mul(value, percentage).unwrap();
+            let div = |value| percent_div(value, percentage).unwrap();
+            prop_assert!(mul(low) <= mul(high));
+            prop_assert!(div(low) <= div(high));
+        }
+    }
+}
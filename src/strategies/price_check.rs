@@ -0,0 +1,319 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+pub const PYTH_HERMES_URL: &str = "https://hermes.pyth.network";
+pub const BINANCE_API_URL: &str = "https://api.binance.com";
+pub const COINGECKO_API_URL: &str = "https://api.coingecko.com/api/v3";
+
+/// Most the oracle's collateral/debt rate may stray from the secondary sources', in bps.
+pub const DEFAULT_MAX_DEVIATION_BPS: u64 = 300;
+
+const PERCENTAGE_FACTOR: f64 = 10000.0;
+
+/// Off-chain prices the Aave oracle is checked against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSource {
+    /// Pyth's Hermes API, feeds looked up by symbol.
+    Pyth,
+    /// Binance spot USDT pairs, by symbol.
+    Binance,
+    /// CoinGecko token prices, by contract address.
+    Coingecko,
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriceSource::Pyth => write!(f, "Pyth"),
+            PriceSource::Binance => write!(f, "Binance"),
+            PriceSource::Coingecko => write!(f, "CoinGecko"),
+        }
+    }
+}
+
+/// An asset of an opportunity, at the price the Aave oracle gives it.
+#[derive(Debug, Clone, Copy)]
+pub struct OraclePrice<'a> {
+    pub asset: Address,
+    pub symbol: &'a str,
+    /// In the oracle's base currency.
+    pub price: U256,
+}
+
+/// Checks the rate between an opportunity's collateral and debt against secondary price
+/// sources, so a stale or manipulated oracle can't make a lossy liquidation look
+/// profitable. Rates are compared rather than prices, so the oracle's base currency
+/// doesn't matter.
+#[derive(Debug, Clone)]
+pub struct PriceChecker {
+    chain_id: u64,
+    /// Tried in turn until one prices the asset
+    sources: Vec<PriceSource>,
+    max_deviation_bps: u64,
+    /// Pyth feed ids by symbol, looked up once
+    pyth_feeds: Arc<Mutex<HashMap<String, String>>>,
+    http: reqwest::Client,
+}
+
+impl PriceChecker {
+    pub fn new(chain_id: u64, sources: Vec<PriceSource>, max_deviation_bps: u64) -> Self {
+        Self {
+            chain_id,
+            sources,
+            max_deviation_bps,
+            pyth_feeds: Arc::new(Mutex::new(HashMap::new())),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    pub fn sources(&self) -> &[PriceSource] {
+        &self.sources
+    }
+
+    pub fn max_deviation_bps(&self) -> u64 {
+        self.max_deviation_bps
+    }
+
+    /// Fail if the oracle's collateral/debt rate deviates too far from the sources'. Assets
+    /// no source prices are let through, the check then can't tell anything.
+    pub async fn check(&self, collateral: OraclePrice<'_>, debt: OraclePrice<'_>) -> Result<()> {
+        if collateral.asset == debt.asset || debt.price.is_zero() {
+            return Ok(());
+        }
+        let (Some(collateral_usd), Some(debt_usd)) =
+            (self.usd_price(collateral).await, self.usd_price(debt).await)
+        else {
+            info!(
+                "No secondary price for {} or {}, not cross-checking",
+                collateral.symbol, debt.symbol
+            );
+            return Ok(());
+        };
+
+        let oracle_rate = to_f64(collateral.price) / to_f64(debt.price);
+        let deviation = deviation_bps(oracle_rate, collateral_usd / debt_usd);
+        info!(
+            "{}/{} rate deviates {} bps from secondary sources",
+            collateral.symbol, debt.symbol, deviation
+        );
+        if deviation > self.max_deviation_bps {
+            return Err(anyhow!(
+                "Oracle {}/{} rate {} deviates {} bps from the secondary sources' {}",
+                collateral.symbol,
+                debt.symbol,
+                oracle_rate,
+                deviation,
+                collateral_usd / debt_usd
+            ));
+        }
+        Ok(())
+    }
+
+    // USD price of the asset from the first source that has one
+    async fn usd_price(&self, asset: OraclePrice<'_>) -> Option<f64> {
+        for source in self.sources.iter() {
+            let price = match source {
+                PriceSource::Pyth => self.pyth_price(asset.symbol).await,
+                PriceSource::Binance => self.binance_price(asset.symbol).await,
+                PriceSource::Coingecko => self.coingecko_price(asset.asset).await,
+            };
+            match price {
+                Ok(price) if price > 0.0 => return Some(price),
+                Ok(_) => info!("{} has no price for {}", source, asset.symbol),
+                Err(e) => info!("{} price of {} failed: {}", source, asset.symbol, e),
+            }
+        }
+        None
+    }
+
+    async fn pyth_price(&self, symbol: &str) -> Result<f64> {
+        let feed_id = self.pyth_feed_id(&base_symbol(symbol)).await?;
+        let response: Value = self
+            .http
+            .get(format!("{}/v2/updates/price/latest", PYTH_HERMES_URL))
+            .query(&[("ids[]", feed_id.as_str()), ("parsed", "true")])
+            .send()
+            .await?
+            .json()
+            .await?;
+        let price = &response["parsed"][0]["price"];
+        let mantissa: f64 = price["price"]
+            .as_str()
+            .ok_or(anyhow!("Unexpected Pyth response: {}", response))?
+            .parse()?;
+        let expo = price["expo"]
+            .as_i64()
+            .ok_or(anyhow!("Unexpected Pyth response: {}", response))?;
+        Ok(mantissa * 10f64.powi(expo as i32))
+    }
+
+    async fn pyth_feed_id(&self, base: &str) -> Result<String> {
+        if let Some(feed_id) = self.pyth_feeds.lock().unwrap().get(base) {
+            return Ok(feed_id.clone());
+        }
+        let feeds: Value = self
+            .http
+            .get(format!("{}/v2/price_feeds", PYTH_HERMES_URL))
+            .query(&[("query", base), ("asset_type", "crypto")])
+            .send()
+            .await?
+            .json()
+            .await?;
+        let symbol = format!("Crypto.{}/USD", base);
+        let feed_id = feeds
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|feed| feed["attributes"]["symbol"].as_str() == Some(symbol.as_str()))
+            .and_then(|feed| feed["id"].as_str())
+            .ok_or(anyhow!("No Pyth feed {}", symbol))?
+            .to_string();
+        self.pyth_feeds
+            .lock()
+            .unwrap()
+            .insert(base.to_string(), feed_id.clone());
+        Ok(feed_id)
+    }
+
+    async fn binance_price(&self, symbol: &str) -> Result<f64> {
+        let base = base_symbol(symbol);
+        // prices are quoted in USDT, taken as a dollar
+        if base == "USDT" {
+            return Ok(1.0);
+        }
+        let response: Value = self
+            .http
+            .get(format!("{}/api/v3/ticker/price", BINANCE_API_URL))
+            .query(&[("symbol", format!("{}USDT", base))])
+            .send()
+            .await?
+            .json()
+            .await?;
+        Ok(response["price"]
+            .as_str()
+            .ok_or(anyhow!("Unexpected Binance response: {}", response))?
+            .parse()?)
+    }
+
+    async fn coingecko_price(&self, asset: Address) -> Result<f64> {
+        let platform = coingecko_platform(self.chain_id)
+            .ok_or(anyhow!("CoinGecko doesn't list chain {}", self.chain_id))?;
+        let address = format!("{:?}", asset);
+        let response: Value = self
+            .http
+            .get(format!(
+                "{}/simple/token_price/{}",
+                COINGECKO_API_URL, platform
+            ))
+            .query(&[
+                ("contract_addresses", address.as_str()),
+                ("vs_currencies", "usd"),
+            ])
+            .send()
+            .await?
+            .json()
+            .await?;
+        response[&address]["usd"]
+            .as_f64()
+            .ok_or(anyhow!("Unexpected CoinGecko response: {}", response))
+    }
+}
+
+/// Symbol of the asset a reserve tracks, as off-chain sources list it: wrapped native
+/// tokens and bridged variants like `USDC.e` are priced as the underlying.
+pub fn base_symbol(symbol: &str) -> String {
+    let symbol = symbol.to_uppercase();
+    let symbol = symbol.split('.').next().unwrap_or_default();
+    match symbol {
+        "WETH" => "ETH",
+        "WBTC" | "BTCB" => "BTC",
+        "WMATIC" | "WPOL" | "MATIC" => "POL",
+        "WAVAX" => "AVAX",
+        "WBNB" => "BNB",
+        "WS" => "S",
+        "WXDAI" => "DAI",
+        "WMETIS" => "METIS",
+        "USDBC" => "USDC",
+        symbol => symbol,
+    }
+    .to_string()
+}
+
+// CoinGecko's id of the chain's token platform
+fn coingecko_platform(chain_id: u64) -> Option<&'static str> {
+    match chain_id {
+        1 => Some("ethereum"),
+        10 => Some("optimistic-ethereum"),
+        56 => Some("binance-smart-chain"),
+        100 => Some("xdai"),
+        137 => Some("polygon-pos"),
+        146 => Some("sonic"),
+        324 => Some("zksync"),
+        1088 => Some("metis-andromeda"),
+        8453 => Some("base"),
+        42161 => Some("arbitrum-one"),
+        42220 => Some("celo"),
+        43114 => Some("avalanche"),
+        59144 => Some("linea"),
+        534352 => Some("scroll"),
+        _ => None,
+    }
+}
+
+/// How far `rate` is from `reference`, in bps of the reference.
+pub fn deviation_bps(rate: f64, reference: f64) -> u64 {
+    if reference <= 0.0 {
+        return u64::MAX;
+    }
+    ((rate - reference).abs() / reference * PERCENTAGE_FACTOR).round() as u64
+}
+
+fn to_f64(value: U256) -> f64 {
+    value.to_string().parse().unwrap_or(f64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_symbol() {
+        assert_eq!(base_symbol("WETH"), "ETH");
+        assert_eq!(base_symbol("USDC.e"), "USDC");
+        assert_eq!(base_symbol("USDbC"), "USDC");
+        assert_eq!(base_symbol("BTC.b"), "BTC");
+        assert_eq!(base_symbol("WMATIC"), "POL");
+        assert_eq!(base_symbol("wstETH"), "WSTETH");
+    }
+
+    #[tokio::test]
+    async fn test_check() {
+        let checker = PriceChecker::new(1, vec![], 300);
+        let collateral = OraclePrice {
+            asset: Address::repeat_byte(1),
+            symbol: "WETH",
+            price: U256::from(3_000_00000000u64),
+        };
+        let debt = OraclePrice {
+            asset: Address::repeat_byte(2),
+            symbol: "USDC",
+            price: U256::from(1_00000000u64),
+        };
+        // without sources there is nothing to check against
+        assert!(checker.check(collateral, debt).await.is_ok());
+
+        assert_eq!(deviation_bps(3000.0, 3000.0), 0);
+        assert_eq!(deviation_bps(3090.0, 3000.0), 300);
+        assert_eq!(deviation_bps(2850.0, 3000.0), 500);
+        assert_eq!(deviation_bps(1.0, 0.0), u64::MAX);
+        assert_eq!(to_f64(U256::from(1_00000000u64)), 1e8);
+    }
+}
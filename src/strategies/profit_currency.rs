@@ -0,0 +1,145 @@
+use super::aave_strategy::PRICE_ONE;
+use ethers::types::{Address, I256, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Decimals of the native token's smallest unit.
+const NATIVE_DECIMALS: u32 = 18;
+
+/// What the min profit is set in and profits are reported in. Gas is bid in the native
+/// token whatever this is, so profits are valued in it first and converted at oracle prices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfitCurrency {
+    /// The chain's native token (ETH, AVAX, POL, BNB...), priced through the deployment's
+    /// wrapped native token, in wei.
+    #[default]
+    Native,
+    /// The oracle's base currency, USD on Aave V3 markets, with its 8 decimals.
+    Usd,
+    /// A reserve of the pool, in its own units.
+    Asset(Address),
+}
+
+impl fmt::Display for ProfitCurrency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfitCurrency::Native => write!(f, "native"),
+            ProfitCurrency::Usd => write!(f, "USD"),
+            ProfitCurrency::Asset(asset) => write!(f, "{:?}", asset),
+        }
+    }
+}
+
+/// Converts profits valued in the native token into the profit currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfitRate {
+    /// Oracle prices, in the oracle's base currency.
+    native_price: U256,
+    currency_price: U256,
+    currency_decimals: u32,
+}
+
+impl ProfitCurrency {
+    /// The rate at oracle `prices`, none if the wrapped native token or the asset has no
+    /// price or decimals.
+    pub fn rate(
+        &self,
+        prices: &HashMap<Address, U256>,
+        wrapped_native: Address,
+        decimals: impl Fn(&Address) -> Option<u64>,
+    ) -> Option<ProfitRate> {
+        let native_price = *prices.get(&wrapped_native)?;
+        let (currency_price, currency_decimals) = match self {
+            ProfitCurrency::Native => (native_price, NATIVE_DECIMALS),
+            ProfitCurrency::Usd => (U256::from(PRICE_ONE), PRICE_ONE.ilog10()),
+            ProfitCurrency::Asset(asset) => (*prices.get(asset)?, decimals(asset)? as u32),
+        };
+        (!native_price.is_zero() && !currency_price.is_zero()).then_some(ProfitRate {
+            native_price,
+            currency_price,
+            currency_decimals,
+        })
+    }
+}
+
+impl ProfitRate {
+    /// `profit_eth`, in the native token with 8 decimals as the strategy values profits, in
+    /// units of the profit currency. None on overflow.
+    pub fn convert(&self, profit_eth: I256) -> Option<I256> {
+        let scale = I256::exp10(self.currency_decimals as usize);
+        let value = profit_eth
+            .checked_mul(I256::try_from(self.native_price).ok()?)?
+            .checked_mul(scale)?;
+        let per_unit = I256::try_from(self.currency_price)
+            .ok()?
+            .checked_mul(I256::from(PRICE_ONE))?;
+        value.checked_div(per_unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::math_reference::big_signed;
+    use num_bigint::BigInt;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_convert() {
+        let wavax = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        // AVAX at $20
+        let prices = HashMap::from([
+            (wavax, U256::from(20 * PRICE_ONE)),
+            (usdc, U256::from(PRICE_ONE)),
+        ]);
+        let decimals = |asset: &Address| (*asset == usdc).then_some(6);
+        // 0.5 AVAX
+        let profit_eth = I256::from(PRICE_ONE / 2);
+
+        let native = ProfitCurrency::Native
+            .rate(&prices, wavax, decimals)
+            .unwrap();
+        assert_eq!(
+            native.convert(profit_eth),
+            Some(I256::exp10(17) * I256::from(5))
+        );
+        let usd = ProfitCurrency::Usd.rate(&prices, wavax, decimals).unwrap();
+        assert_eq!(usd.convert(profit_eth), Some(I256::from(10 * PRICE_ONE)));
+        let in_usdc = ProfitCurrency::Asset(usdc)
+            .rate(&prices, wavax, decimals)
+            .unwrap();
+        assert_eq!(in_usdc.convert(-profit_eth), Some(I256::from(-10_000_000)));
+
+        // unpriced assets have no rate
+        assert_eq!(
+            ProfitCurrency::Asset(Address::repeat_byte(3)).rate(&prices, wavax, decimals),
+            None
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_convert_matches_reference(
+            profit_eth in any::<i128>(),
+            native_price in 1..u64::MAX,
+            currency_price in 1..u64::MAX,
+            currency_decimals in 0u32..=18,
+        ) {
+            let rate = ProfitRate {
+                native_price: U256::from(native_price),
+                currency_price: U256::from(currency_price),
+                currency_decimals,
+            };
+            // truncated towards zero, as I256 division is
+            let expected = BigInt::from(profit_eth)
+                * native_price
+                * BigInt::from(10u8).pow(currency_decimals)
+                / (BigInt::from(currency_price) * PRICE_ONE);
+            let converted = rate.convert(I256::from(profit_eth)).unwrap();
+            prop_assert_eq!(big_signed(converted), expected);
+        }
+    }
+}
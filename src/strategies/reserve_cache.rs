@@ -0,0 +1,170 @@
+use anyhow::{anyhow, Result};
+use ethers::types::Address;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Default seconds a cached reserve config is trusted for before it's fetched again.
+pub const DEFAULT_RESERVE_CACHE_TTL_SECS: u64 = 86400;
+
+/// Reserve config cache of a pool in `state_dir`, so instances for different networks and
+/// deployments can share a directory.
+pub fn reserve_cache_path(state_dir: &Path, chain_id: u64, pool: Address) -> PathBuf {
+    state_dir.join(format!("reserves-{}-{:?}.json", chain_id, pool))
+}
+
+/// A reserve's config and when it was fetched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedReserve<T> {
+    pub config: T,
+    /// Unix time it was fetched at.
+    pub fetched_at: u64,
+}
+
+/// Reserve configs and e-mode categories fetched on earlier runs, so a restart only fetches
+/// those that expired or that the pool configurator changed since, instead of every one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReserveCache<T, C> {
+    chain_id: u64,
+    pool: Address,
+    /// Configurator events up to this block are reflected in the configs.
+    block_number: u64,
+    reserves: HashMap<Address, CachedReserve<T>>,
+    /// E-mode categories by id, none in caches written before they were cached.
+    #[serde(default)]
+    e_mode_categories: HashMap<u8, CachedReserve<C>>,
+}
+
+impl<T: Serialize + DeserializeOwned, C: Serialize + DeserializeOwned> ReserveCache<T, C> {
+    pub fn new(chain_id: u64, pool: Address, block_number: u64) -> Self {
+        Self {
+            chain_id,
+            pool,
+            block_number,
+            reserves: HashMap::new(),
+            e_mode_categories: HashMap::new(),
+        }
+    }
+
+    /// Read the cache at `path`, `None` if there is none for the pool.
+    pub fn read(path: &Path, chain_id: u64, pool: Address) -> Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let cache: Self = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("Failed to parse reserve cache: {}", e))?;
+        Ok((cache.chain_id == chain_id && cache.pool == pool).then_some(cache))
+    }
+
+    /// Write the cache to a temp file renamed over `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.block_number
+    }
+
+    pub fn insert(&mut self, asset: Address, config: T, fetched_at: u64) {
+        self.reserves
+            .insert(asset, CachedReserve { config, fetched_at });
+    }
+
+    pub fn insert_e_mode_category(&mut self, id: u8, config: C, fetched_at: u64) {
+        self.e_mode_categories
+            .insert(id, CachedReserve { config, fetched_at });
+    }
+
+    /// Take the reserves and e-mode categories fetched less than `ttl_secs` before `now`,
+    /// dropping the rest.
+    pub fn into_fresh(
+        self,
+        now: u64,
+        ttl_secs: u64,
+    ) -> (
+        HashMap<Address, CachedReserve<T>>,
+        HashMap<u8, CachedReserve<C>>,
+    ) {
+        let fresh = |fetched_at: u64| now.saturating_sub(fetched_at) < ttl_secs;
+        (
+            self.reserves
+                .into_iter()
+                .filter(|(_, reserve)| fresh(reserve.fetched_at))
+                .collect(),
+            self.e_mode_categories
+                .into_iter()
+                .filter(|(_, category)| fresh(category.fetched_at))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_cache() {
+        let dir = std::env::temp_dir().join(format!("reserve-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let pool = Address::repeat_byte(0xaa);
+        let path = reserve_cache_path(&dir, 1, pool);
+        assert!(ReserveCache::<u64, u64>::read(&path, 1, pool)
+            .unwrap()
+            .is_none());
+
+        let (a, b) = (Address::repeat_byte(1), Address::repeat_byte(2));
+        let mut cache = ReserveCache::new(1, pool, 100);
+        cache.insert(a, 18u64, 1000);
+        cache.insert(b, 6u64, 5000);
+        cache.insert_e_mode_category(1, 9700u64, 1000);
+        cache.insert_e_mode_category(2, 9800u64, 5000);
+        cache.write(&path).unwrap();
+
+        // another network or pool doesn't reuse it
+        assert!(ReserveCache::<u64, u64>::read(&path, 10, pool)
+            .unwrap()
+            .is_none());
+        assert!(ReserveCache::<u64, u64>::read(&path, 1, Address::zero())
+            .unwrap()
+            .is_none());
+        let cache = ReserveCache::<u64, u64>::read(&path, 1, pool)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cache.block_number(), 100);
+        let (fresh, fresh_e_modes) = cache.into_fresh(5500, 1000);
+        assert_eq!(fresh.len(), 1);
+        assert_eq!(fresh[&b].config, 6);
+        assert_eq!(fresh_e_modes.len(), 1);
+        assert_eq!(fresh_e_modes[&2].config, 9800);
+
+        // caches written before e-mode categories were cached still read
+        std::fs::write(
+            &path,
+            format!(
+                r#"{{"chain_id":1,"pool":"{:?}","block_number":100,"reserves":{{}}}}"#,
+                pool
+            ),
+        )
+        .unwrap();
+        let cache = ReserveCache::<u64, u64>::read(&path, 1, pool)
+            .unwrap()
+            .unwrap();
+        assert!(cache.into_fresh(5500, 1000).1.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,290 @@
+use bindings_aave::l2_pool::L2POOL_ABI;
+use bindings_aave::pool::POOL_ABI;
+use bindings_liquidator::erc20::ERC20_ABI;
+use bindings_liquidator::i_uniswap_v3_flash_callback::IUNISWAPV3FLASHCALLBACK_ABI;
+use bindings_liquidator::i_uniswap_v3_pool_actions::IUNISWAPV3POOLACTIONS_ABI;
+use bindings_liquidator::i_uniswap_v3_swap_callback::IUNISWAPV3SWAPCALLBACK_ABI;
+use bindings_liquidator::liquidator::LIQUIDATOR_ABI;
+use ethers::abi::{self, Abi, ParamType, Token};
+use ethers::types::U256;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Names of the numeric codes Aave V3's `Errors` library reverts with.
+const AAVE_ERRORS: [(&str, &str); 90] = [
+    ("1", "CALLER_NOT_POOL_ADMIN"),
+    ("2", "CALLER_NOT_EMERGENCY_ADMIN"),
+    ("3", "CALLER_NOT_POOL_OR_EMERGENCY_ADMIN"),
+    ("4", "CALLER_NOT_RISK_OR_POOL_ADMIN"),
+    ("5", "CALLER_NOT_ASSET_LISTING_OR_POOL_ADMIN"),
+    ("6", "CALLER_NOT_BRIDGE"),
+    ("7", "ADDRESSES_PROVIDER_NOT_REGISTERED"),
+    ("8", "INVALID_ADDRESSES_PROVIDER_ID"),
+    ("9", "NOT_CONTRACT"),
+    ("10", "CALLER_NOT_POOL_CONFIGURATOR"),
+    ("11", "CALLER_NOT_ATOKEN"),
+    ("12", "INVALID_ADDRESSES_PROVIDER"),
+    ("13", "INVALID_FLASHLOAN_EXECUTOR_RETURN"),
+    ("14", "RESERVE_ALREADY_ADDED"),
+    ("15", "NO_MORE_RESERVES_ALLOWED"),
+    ("16", "EMODE_CATEGORY_RESERVED"),
+    ("17", "INVALID_EMODE_CATEGORY_ASSIGNMENT"),
+    ("18", "RESERVE_LIQUIDITY_NOT_ZERO"),
+    ("19", "FLASHLOAN_PREMIUM_INVALID"),
+    ("20", "INVALID_RESERVE_PARAMS"),
+    ("21", "INVALID_EMODE_CATEGORY_PARAMS"),
+    ("22", "BRIDGE_PROTOCOL_FEE_INVALID"),
+    ("23", "CALLER_MUST_BE_POOL"),
+    ("24", "INVALID_MINT_AMOUNT"),
+    ("25", "INVALID_BURN_AMOUNT"),
+    ("26", "INVALID_AMOUNT"),
+    ("27", "RESERVE_INACTIVE"),
+    ("28", "RESERVE_FROZEN"),
+    ("29", "RESERVE_PAUSED"),
+    ("30", "BORROWING_NOT_ENABLED"),
+    ("31", "STABLE_BORROWING_NOT_ENABLED"),
+    ("32", "NOT_ENOUGH_AVAILABLE_USER_BALANCE"),
+    ("33", "INVALID_INTEREST_RATE_MODE_SELECTED"),
+    ("34", "COLLATERAL_BALANCE_IS_ZERO"),
+    ("35", "HEALTH_FACTOR_LOWER_THAN_LIQUIDATION_THRESHOLD"),
+    ("36", "COLLATERAL_CANNOT_COVER_NEW_BORROW"),
+    ("37", "COLLATERAL_SAME_AS_BORROWING_CURRENCY"),
+    ("38", "AMOUNT_BIGGER_THAN_MAX_LOAN_SIZE_STABLE"),
+    ("39", "NO_DEBT_OF_SELECTED_TYPE"),
+    ("40", "NO_EXPLICIT_AMOUNT_TO_REPAY_ON_BEHALF"),
+    ("41", "NO_OUTSTANDING_STABLE_DEBT"),
+    ("42", "NO_OUTSTANDING_VARIABLE_DEBT"),
+    ("43", "UNDERLYING_BALANCE_ZERO"),
+    ("44", "INTEREST_RATE_REBALANCE_CONDITIONS_NOT_MET"),
+    ("45", "HEALTH_FACTOR_NOT_BELOW_THRESHOLD"),
+    ("46", "COLLATERAL_CANNOT_BE_LIQUIDATED"),
+    ("47", "SPECIFIED_CURRENCY_NOT_BORROWED_BY_USER"),
+    ("48", "SAME_BLOCK_BORROW_REPAY"),
+    ("49", "INCONSISTENT_FLASHLOAN_PARAMS"),
+    ("50", "BORROW_CAP_EXCEEDED"),
+    ("51", "SUPPLY_CAP_EXCEEDED"),
+    ("52", "UNBACKED_MINT_CAP_EXCEEDED"),
+    ("53", "DEBT_CEILING_EXCEEDED"),
+    ("54", "UNDERLYING_CLAIMABLE_RIGHTS_NOT_ZERO"),
+    ("55", "VARIABLE_DEBT_SUPPLY_NOT_ZERO"),
+    ("57", "LTV_VALIDATION_FAILED"),
+    ("58", "INCONSISTENT_EMODE_CATEGORY"),
+    ("59", "PRICE_ORACLE_SENTINEL_CHECK_FAILED"),
+    ("60", "ASSET_NOT_BORROWABLE_IN_ISOLATION"),
+    ("61", "RESERVE_ALREADY_INITIALIZED"),
+    ("62", "USER_IN_ISOLATION_MODE_OR_LTV_ZERO"),
+    ("63", "INVALID_LTV"),
+    ("64", "INVALID_LIQ_THRESHOLD"),
+    ("65", "INVALID_LIQ_BONUS"),
+    ("66", "INVALID_DECIMALS"),
+    ("67", "INVALID_RESERVE_FACTOR"),
+    ("68", "INVALID_BORROW_CAP"),
+    ("69", "INVALID_SUPPLY_CAP"),
+    ("70", "INVALID_LIQUIDATION_PROTOCOL_FEE"),
+    ("71", "INVALID_EMODE_CATEGORY"),
+    ("72", "INVALID_UNBACKED_MINT_CAP"),
+    ("73", "INVALID_DEBT_CEILING"),
+    ("74", "INVALID_RESERVE_INDEX"),
+    ("75", "ACL_ADMIN_CANNOT_BE_ZERO"),
+    ("76", "INCONSISTENT_PARAMS_LENGTH"),
+    ("77", "ZERO_ADDRESS_NOT_VALID"),
+    ("78", "INVALID_EXPIRATION"),
+    ("79", "INVALID_SIGNATURE"),
+    ("80", "OPERATION_NOT_SUPPORTED"),
+    ("81", "DEBT_CEILING_NOT_ZERO"),
+    ("82", "ASSET_NOT_LISTED"),
+    ("83", "INVALID_OPTIMAL_USAGE_RATIO"),
+    ("84", "INVALID_OPTIMAL_STABLE_TO_TOTAL_DEBT_RATIO"),
+    ("85", "UNDERLYING_CANNOT_BE_RESCUED"),
+    ("86", "ADDRESSES_PROVIDER_ALREADY_ADDED"),
+    ("87", "POOL_ADDRESSES_DO_NOT_MATCH"),
+    ("88", "STABLE_BORROWING_ENABLED"),
+    ("89", "SILOED_BORROWING_VIOLATION"),
+    ("90", "RESERVE_DEBT_NOT_ZERO"),
+    ("91", "FLASHLOAN_DISABLED"),
+];
+
+/// What the revert strings of the Liquidator contract and the contracts it calls mean.
+const LIQUIDATOR_ERRORS: [(&str, &str); 12] = [
+    ("UNAUTHORIZED", "sender isn't the Liquidator's owner"),
+    (
+        "invalid flash loan",
+        "flash loan not started by the Liquidator",
+    ),
+    (
+        "invalid pool",
+        "swap callback from another pool than the flash swap's",
+    ),
+    (
+        "invalid path",
+        "swap path doesn't run from the debt to the collateral",
+    ),
+    ("TRANSFER_FAILED", "token transfer failed"),
+    ("TRANSFER_FROM_FAILED", "token transferFrom failed"),
+    ("APPROVE_FAILED", "token approval failed"),
+    ("AS", "Uniswap swap of zero"),
+    ("SPL", "Uniswap price limit out of range"),
+    ("IIA", "Uniswap swap not paid for in full"),
+    ("LOK", "Uniswap pool locked"),
+    ("STF", "Uniswap transferFrom failed"),
+];
+
+/// Panic codes of Solidity's `Panic(uint256)`.
+const PANIC_CODES: [(u64, &str); 9] = [
+    (0x01, "assertion failed"),
+    (0x11, "arithmetic overflow"),
+    (0x12, "division by zero"),
+    (0x21, "invalid enum value"),
+    (0x22, "invalid storage encoding"),
+    (0x31, "pop of an empty array"),
+    (0x32, "index out of bounds"),
+    (0x41, "out of memory"),
+    (0x51, "call to an uninitialized function"),
+];
+
+/// A revert string with what it means, e.g. `45 (HEALTH_FACTOR_NOT_BELOW_THRESHOLD)`. Strings
+/// the bot doesn't know are returned as they are.
+pub fn describe_reason(reason: &str) -> String {
+    let known = AAVE_ERRORS
+        .iter()
+        .chain(LIQUIDATOR_ERRORS.iter())
+        .find(|(code, _)| *code == reason);
+    match known {
+        Some((code, name)) => format!("{} ({})", code, name),
+        None => reason.to_string(),
+    }
+}
+
+/// The reason encoded in revert data: an `Error(string)`, a `Panic(uint256)` or a custom
+/// error of a contract a liquidation goes through.
+pub fn decode_revert(data: &[u8]) -> Option<String> {
+    let (selector, args) = (data.get(..4)?, &data[4..]);
+    if selector == ERROR_SELECTOR {
+        return match abi::decode(&[ParamType::String], args).ok()?.pop()? {
+            Token::String(reason) => Some(describe_reason(&reason)),
+            _ => None,
+        };
+    }
+    if selector == PANIC_SELECTOR {
+        let code = U256::from_big_endian(args.get(..32)?);
+        return Some(
+            match PANIC_CODES
+                .iter()
+                .find(|(known, _)| U256::from(*known) == code)
+            {
+                Some((_, name)) => format!("panic 0x{:x} ({})", code, name),
+                None => format!("panic 0x{:x}", code),
+            },
+        );
+    }
+    known_selectors().errors.get(selector).cloned()
+}
+
+/// The revert reason in an error message, as nodes report it (`execution reverted: 45`) or
+/// as ethers does for revert data (`reverted with data: 0x...`).
+pub fn revert_reason(message: &str) -> Option<String> {
+    if let Some((_, data)) = message.split_once("reverted with data: 0x") {
+        let data: String = data.chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+        return decode_revert(&hex::decode(data).ok()?);
+    }
+    let (_, reason) = message.split_once("execution reverted: ")?;
+    let reason = reason
+        .split([',', ')', '"'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    (!reason.is_empty()).then(|| describe_reason(reason))
+}
+
+/// `message` with the revert reason it holds named, if it holds one and it isn't named yet.
+pub fn describe_error(message: &str) -> String {
+    match revert_reason(message) {
+        Some(reason) if !message.contains(&format!("reverted with {}", reason)) => {
+            format!("{}, reverted with {}", message, reason)
+        }
+        _ => message.to_string(),
+    }
+}
+
+/// The name of the function `input` calls, if it's one of a contract a liquidation goes
+/// through.
+pub fn function_name(input: &[u8]) -> Option<String> {
+    known_selectors().functions.get(input.get(..4)?).cloned()
+}
+
+#[derive(Debug, Default)]
+struct Selectors {
+    functions: HashMap<Vec<u8>, String>,
+    errors: HashMap<Vec<u8>, String>,
+}
+
+// functions and errors of the contracts a liquidation goes through, by selector
+fn known_selectors() -> &'static Selectors {
+    static SELECTORS: OnceLock<Selectors> = OnceLock::new();
+    SELECTORS.get_or_init(|| {
+        let abis: [&Abi; 7] = [
+            &*POOL_ABI,
+            &*L2POOL_ABI,
+            &*LIQUIDATOR_ABI,
+            &*ERC20_ABI,
+            &*IUNISWAPV3POOLACTIONS_ABI,
+            &*IUNISWAPV3FLASHCALLBACK_ABI,
+            &*IUNISWAPV3SWAPCALLBACK_ABI,
+        ];
+        let mut selectors = Selectors::default();
+        for abi in abis {
+            for function in abi.functions() {
+                selectors
+                    .functions
+                    .insert(function.short_signature().to_vec(), function.name.clone());
+            }
+            for error in abi.errors() {
+                selectors.errors.insert(
+                    error.signature().as_bytes()[..4].to_vec(),
+                    error.name.clone(),
+                );
+            }
+        }
+        selectors
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revert_reason() {
+        assert_eq!(
+            revert_reason(
+                "(code: 3, message: execution reverted: 45, data: Some(String(\"0x08c3\")))"
+            ),
+            Some("45 (HEALTH_FACTOR_NOT_BELOW_THRESHOLD)".to_string())
+        );
+        assert_eq!(
+            revert_reason("Contract call reverted with data: 0x08c379a0000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000034949410000000000000000000000000000000000000000000000000000000000"),
+            Some("IIA (Uniswap swap not paid for in full)".to_string())
+        );
+        assert_eq!(
+            revert_reason("Contract call reverted with data: 0x4e487b710000000000000000000000000000000000000000000000000000000000000011"),
+            Some("panic 0x11 (arithmetic overflow)".to_string())
+        );
+        assert_eq!(
+            revert_reason("execution reverted: 1000"),
+            Some("1000".to_string())
+        );
+        assert_eq!(revert_reason("connection refused"), None);
+        assert_eq!(
+            describe_error("execution reverted: 46"),
+            "execution reverted: 46, reverted with 46 (COLLATERAL_CANNOT_BE_LIQUIDATED)"
+        );
+        // named once
+        assert_eq!(
+            describe_error(&describe_error("execution reverted: 46")),
+            describe_error("execution reverted: 46")
+        );
+    }
+}
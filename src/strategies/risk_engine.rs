@@ -0,0 +1,586 @@
+use super::percentage_math::PERCENTAGE_FACTOR;
+use super::wad_ray_math::{checked_ray_div, checked_ray_mul, compounded_interest, linear_interest};
+use anyhow::Result;
+use ethers::types::{Address, U256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// A single reserve balance of a borrower.
+#[derive(Debug, Clone)]
+pub struct AssetPosition {
+    pub asset: Address,
+    pub amount: U256,
+    /// 10 ** decimals of the asset.
+    pub unit: U256,
+    /// Liquidation threshold in bps, only meaningful for collateral.
+    pub liquidation_threshold: u64,
+    /// Annual rate in ray the balance grows at, linearly for collateral and
+    /// compounded for debt, as the pool accrues it.
+    pub rate: U256,
+    /// Stable debt accrues at the borrower's own rate, which reserve updates leave alone.
+    pub fixed_rate: bool,
+    /// Reserve index in ray `amount` is normalized to, the liquidity index for collateral
+    /// and the variable borrow index for variable debt. Reserve updates scale the amount by
+    /// their index exactly, it is projected at `rate` if unknown.
+    pub index: Option<U256>,
+}
+
+/// Collateral and debt balances of a borrower at the time of a snapshot.
+#[derive(Debug, Clone)]
+pub struct PositionSnapshot {
+    pub borrower: Address,
+    pub collateral: Vec<AssetPosition>,
+    pub debt: Vec<AssetPosition>,
+    /// Unix time the balances were read at.
+    pub timestamp: u64,
+}
+
+impl PositionSnapshot {
+    /// The balances with interest accrued up to unix time `now` at the snapshot rates.
+    pub fn projected(&self, now: u64) -> Result<Self> {
+        let elapsed = now.saturating_sub(self.timestamp);
+        let accrue = |positions: &[AssetPosition],
+                      interest: fn(U256, u64) -> Result<U256>|
+         -> Result<Vec<AssetPosition>> {
+            positions
+                .iter()
+                .map(|position| {
+                    // the index the amount is normalized to grows with it
+                    let growth = interest(position.rate, elapsed)?;
+                    Ok(AssetPosition {
+                        amount: checked_ray_mul(position.amount, growth)?,
+                        index: position
+                            .index
+                            .map(|index| checked_ray_mul(index, growth))
+                            .transpose()?,
+                        ..position.clone()
+                    })
+                })
+                .collect()
+        };
+        Ok(Self {
+            borrower: self.borrower,
+            collateral: accrue(&self.collateral, linear_interest)?,
+            debt: accrue(&self.debt, compounded_interest)?,
+            timestamp: self.timestamp.max(now),
+        })
+    }
+}
+
+/// Which way the price of an asset has to move to make a borrower liquidatable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerDirection {
+    Below,
+    Above,
+}
+
+#[derive(Debug, Default)]
+struct AssetTriggers {
+    // borrowers liquidatable once the price falls to or below the trigger price
+    below: BTreeSet<(U256, Address)>,
+    // borrowers liquidatable once the price rises to or above the trigger price
+    above: BTreeSet<(U256, Address)>,
+}
+
+/// Indexes borrowers by the oracle price of each asset that would push their
+/// health factor below 1, assuming all other prices stay where they are.
+#[derive(Debug, Default)]
+pub struct RiskEngine {
+    triggers: HashMap<Address, AssetTriggers>,
+    by_borrower: HashMap<Address, Vec<(Address, TriggerDirection, U256)>>,
+    // the last on-chain snapshot of each borrower, projected forward between refreshes
+    snapshots: HashMap<Address, PositionSnapshot>,
+}
+
+impl RiskEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_borrower.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_borrower.is_empty()
+    }
+
+    /// Recompute the trigger prices of a borrower from a fresh snapshot.
+    pub fn update(&mut self, snapshot: &PositionSnapshot, prices: &HashMap<Address, U256>) {
+        self.remove(&snapshot.borrower);
+        self.index(snapshot, prices);
+        self.snapshots.insert(snapshot.borrower, snapshot.clone());
+    }
+
+    /// Recompute every trigger price with interest accrued up to unix time `now`, so they
+    /// don't go stale between snapshots, returning the borrowers already liquidatable at
+    /// `prices` once it is.
+    pub fn project(&mut self, now: u64, prices: &HashMap<Address, U256>) -> HashSet<Address> {
+        let projected: Vec<PositionSnapshot> = self
+            .snapshots
+            .values()
+            .filter_map(|snapshot| snapshot.projected(now).ok())
+            .collect();
+
+        let mut liquidatable = HashSet::new();
+        for snapshot in projected {
+            self.unindex(&snapshot.borrower);
+            self.index(&snapshot, prices);
+            let crossed = self.by_borrower[&snapshot.borrower].iter().any(
+                |(asset, direction, trigger)| match (prices.get(asset), direction) {
+                    (Some(price), TriggerDirection::Below) => price <= trigger,
+                    (Some(price), TriggerDirection::Above) => price >= trigger,
+                    (None, _) => false,
+                },
+            );
+            if crossed {
+                liquidatable.insert(snapshot.borrower);
+            }
+        }
+        liquidatable
+    }
+
+    /// Move the positions in `reserve` to the rates and indexes it was updated to at unix
+    /// time `timestamp`. Balances with a known index grow by the reserve's index since, the
+    /// others accrue at the previous rates up to then.
+    pub fn update_reserve(
+        &mut self,
+        reserve: Address,
+        timestamp: u64,
+        liquidity_rate: U256,
+        variable_borrow_rate: U256,
+        liquidity_index: U256,
+        variable_borrow_index: U256,
+    ) {
+        for snapshot in self.snapshots.values_mut() {
+            if !snapshot
+                .collateral
+                .iter()
+                .chain(snapshot.debt.iter())
+                .any(|position| position.asset == reserve)
+            {
+                continue;
+            }
+            let Ok(mut projected) = snapshot.projected(timestamp) else {
+                continue;
+            };
+            for (position, read) in projected.collateral.iter_mut().zip(&snapshot.collateral) {
+                if position.asset == reserve {
+                    position.rate = liquidity_rate;
+                    rescale(position, read, liquidity_index);
+                }
+            }
+            for (position, read) in projected.debt.iter_mut().zip(&snapshot.debt) {
+                if position.asset == reserve && !position.fixed_rate {
+                    position.rate = variable_borrow_rate;
+                    rescale(position, read, variable_borrow_index);
+                }
+            }
+            *snapshot = projected;
+        }
+    }
+
+    fn index(&mut self, snapshot: &PositionSnapshot, prices: &HashMap<Address, U256>) {
+        let triggers = trigger_prices(snapshot, prices);
+        for (asset, direction, price) in triggers.iter() {
+            let asset_triggers = self.triggers.entry(*asset).or_default();
+            let set = match direction {
+                TriggerDirection::Below => &mut asset_triggers.below,
+                TriggerDirection::Above => &mut asset_triggers.above,
+            };
+            set.insert((*price, snapshot.borrower));
+        }
+        self.by_borrower.insert(snapshot.borrower, triggers);
+    }
+
+    pub fn remove(&mut self, borrower: &Address) {
+        self.unindex(borrower);
+        self.snapshots.remove(borrower);
+    }
+
+    fn unindex(&mut self, borrower: &Address) {
+        for (asset, direction, price) in self.by_borrower.remove(borrower).unwrap_or_default() {
+            if let Some(asset_triggers) = self.triggers.get_mut(&asset) {
+                match direction {
+                    TriggerDirection::Below => asset_triggers.below.remove(&(price, *borrower)),
+                    TriggerDirection::Above => asset_triggers.above.remove(&(price, *borrower)),
+                };
+            }
+        }
+    }
+
+    /// Drop every borrower not in the given set.
+    pub fn retain(&mut self, borrowers: &HashSet<Address>) {
+        let stale: Vec<Address> = self
+            .by_borrower
+            .keys()
+            .filter(|b| !borrowers.contains(b))
+            .copied()
+            .collect();
+        for borrower in stale {
+            self.remove(&borrower);
+        }
+    }
+
+    /// Borrowers that become liquidatable when the price of `asset` moves to `price`.
+    pub fn on_price_update(&self, asset: &Address, price: U256) -> HashSet<Address> {
+        let mut liquidatable = HashSet::new();
+        if let Some(asset_triggers) = self.triggers.get(asset) {
+            asset_triggers
+                .below
+                .range((price, Address::zero())..)
+                .for_each(|(_, borrower)| {
+                    liquidatable.insert(*borrower);
+                });
+            asset_triggers
+                .above
+                .range(..=(price, Address::repeat_byte(0xff)))
+                .for_each(|(_, borrower)| {
+                    liquidatable.insert(*borrower);
+                });
+        }
+        liquidatable
+    }
+}
+
+// For each asset x, solve W_o + p * c_x = V_o + p * d_x for the price p where the
+// health factor hits 1, with W_o / V_o the threshold-weighted collateral / debt
+// value of every other asset and c_x / d_x the per-price weight of x on each side.
+// the balance `read` at its index, normalized to the reserve's new `index` instead of the
+// projection at the previous rate, which is then only an estimate
+fn rescale(position: &mut AssetPosition, read: &AssetPosition, index: U256) {
+    if let Some(amount) = read
+        .index
+        .filter(|read_index| !read_index.is_zero())
+        .and_then(|read_index| checked_ray_div(index, read_index).ok())
+        .and_then(|growth| checked_ray_mul(read.amount, growth).ok())
+    {
+        position.amount = amount;
+    }
+    position.index = Some(index);
+}
+
+fn trigger_prices(
+    snapshot: &PositionSnapshot,
+    prices: &HashMap<Address, U256>,
+) -> Vec<(Address, TriggerDirection, U256)> {
+    // values are scaled by PERCENTAGE_FACTOR so that thresholds apply without rounding
+    let mut weighted_collateral: HashMap<Address, (U256, U256)> = HashMap::new();
+    let mut weighted_debt: HashMap<Address, (U256, U256)> = HashMap::new();
+    for position in snapshot.collateral.iter() {
+        let entry = weighted_collateral
+            .entry(position.asset)
+            .or_insert((U256::zero(), position.unit));
+        entry.0 += position.amount * position.liquidation_threshold;
+    }
+    for position in snapshot.debt.iter() {
+        let entry = weighted_debt
+            .entry(position.asset)
+            .or_insert((U256::zero(), position.unit));
+        entry.0 += position.amount * PERCENTAGE_FACTOR;
+    }
+
+    let value = |weights: &HashMap<Address, (U256, U256)>, skip: &Address| -> Option<U256> {
+        weights
+            .iter()
+            .filter(|(asset, _)| *asset != skip)
+            .try_fold(U256::zero(), |total, (asset, (amount, unit))| {
+                Some(total + *amount * *prices.get(asset)? / *unit)
+            })
+    };
+
+    let assets: HashSet<Address> = weighted_collateral
+        .keys()
+        .chain(weighted_debt.keys())
+        .copied()
+        .collect();
+
+    let mut triggers = Vec::new();
+    for asset in assets {
+        let (Some(other_collateral), Some(other_debt)) = (
+            value(&weighted_collateral, &asset),
+            value(&weighted_debt, &asset),
+        ) else {
+            continue;
+        };
+        let (collateral_weight, collateral_unit) =
+            weighted_collateral.get(&asset).copied().unwrap_or_default();
+        let (debt_weight, debt_unit) = weighted_debt.get(&asset).copied().unwrap_or_default();
+        let unit = collateral_unit.max(debt_unit);
+
+        if collateral_weight > debt_weight {
+            // net collateral: liquidatable once the price falls below the trigger
+            if other_debt <= other_collateral {
+                continue;
+            }
+            let price = (other_debt - other_collateral) * unit / (collateral_weight - debt_weight);
+            triggers.push((asset, TriggerDirection::Below, price));
+        } else if debt_weight > collateral_weight {
+            // net debt: liquidatable once the price rises above the trigger
+            if other_collateral <= other_debt {
+                continue;
+            }
+            let price = (other_collateral - other_debt) * unit / (debt_weight - collateral_weight);
+            triggers.push((asset, TriggerDirection::Above, price));
+        }
+    }
+
+    triggers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategies::wad_ray_math::SECONDS_PER_YEAR;
+
+    fn position(asset: Address, amount: u64, liquidation_threshold: u64) -> AssetPosition {
+        AssetPosition {
+            asset,
+            amount: U256::from(amount) * U256::exp10(18),
+            unit: U256::exp10(18),
+            liquidation_threshold,
+            rate: U256::zero(),
+            fixed_rate: false,
+            index: None,
+        }
+    }
+
+    #[test]
+    fn test_trigger_prices() {
+        let weth = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        let borrower = Address::repeat_byte(3);
+        // 1 WETH at $2000 with 80% LT backing 1000 USDC of debt
+        let snapshot = PositionSnapshot {
+            borrower,
+            collateral: vec![position(weth, 1, 8000)],
+            debt: vec![position(usdc, 1000, 0)],
+            timestamp: 0,
+        };
+        let prices = HashMap::from([
+            (weth, U256::from(2000) * U256::exp10(8)),
+            (usdc, U256::exp10(8)),
+        ]);
+
+        let mut triggers = trigger_prices(&snapshot, &prices);
+        triggers.sort_by_key(|t| t.0);
+        assert_eq!(
+            triggers,
+            vec![
+                (
+                    weth,
+                    TriggerDirection::Below,
+                    U256::from(1250) * U256::exp10(8)
+                ),
+                (
+                    usdc,
+                    TriggerDirection::Above,
+                    U256::from(16) * U256::exp10(7)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_price_update() {
+        let weth = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        let borrower = Address::repeat_byte(3);
+        let snapshot = PositionSnapshot {
+            borrower,
+            collateral: vec![position(weth, 1, 8000)],
+            debt: vec![position(usdc, 1000, 0)],
+            timestamp: 0,
+        };
+        let prices = HashMap::from([
+            (weth, U256::from(2000) * U256::exp10(8)),
+            (usdc, U256::exp10(8)),
+        ]);
+
+        let mut engine = RiskEngine::new();
+        engine.update(&snapshot, &prices);
+        assert_eq!(engine.len(), 1);
+
+        assert!(engine
+            .on_price_update(&weth, U256::from(1300) * U256::exp10(8))
+            .is_empty());
+        assert_eq!(
+            engine.on_price_update(&weth, U256::from(1250) * U256::exp10(8)),
+            HashSet::from([borrower])
+        );
+
+        engine.retain(&HashSet::new());
+        assert!(engine.is_empty());
+        assert!(engine
+            .on_price_update(&weth, U256::from(1000) * U256::exp10(8))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_project() {
+        let weth = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        let borrower = Address::repeat_byte(3);
+        // 1000 USDC of debt at 10% a year against 1 WETH earning nothing
+        let snapshot = PositionSnapshot {
+            borrower,
+            collateral: vec![position(weth, 1, 8000)],
+            debt: vec![AssetPosition {
+                rate: U256::exp10(26),
+                ..position(usdc, 1000, 0)
+            }],
+            timestamp: 1_000,
+        };
+        let prices = HashMap::from([
+            (weth, U256::from(1300) * U256::exp10(8)),
+            (usdc, U256::exp10(8)),
+        ]);
+
+        let mut engine = RiskEngine::new();
+        engine.update(&snapshot, &prices);
+        assert!(engine.project(1_000, &prices).is_empty());
+        assert!(engine
+            .on_price_update(&weth, U256::from(1300) * U256::exp10(8))
+            .is_empty());
+
+        // a year on the debt has compounded to ~1105 USDC, moving the WETH trigger to ~$1381
+        let year_later = 1_000 + SECONDS_PER_YEAR;
+        let projected = snapshot.projected(year_later).unwrap();
+        assert!(projected.debt[0].amount > U256::from(1105) * U256::exp10(18));
+        assert!(projected.debt[0].amount < U256::from(1106) * U256::exp10(18));
+        assert_eq!(projected.collateral[0].amount, U256::exp10(18));
+
+        assert_eq!(
+            engine.project(year_later, &prices),
+            HashSet::from([borrower])
+        );
+        assert_eq!(
+            engine.on_price_update(&weth, U256::from(1380) * U256::exp10(8)),
+            HashSet::from([borrower])
+        );
+        // the original snapshot is kept, so projecting again doesn't compound twice
+        engine.project(year_later, &prices);
+        assert!(engine
+            .on_price_update(&weth, U256::from(1390) * U256::exp10(8))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_update_reserve() {
+        let weth = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        let borrower = Address::repeat_byte(3);
+        // 500 USDC of variable and 500 of stable debt, both at 10% a year
+        let debt = AssetPosition {
+            rate: U256::exp10(26),
+            ..position(usdc, 500, 0)
+        };
+        let snapshot = PositionSnapshot {
+            borrower,
+            collateral: vec![position(weth, 1, 8000)],
+            debt: vec![
+                debt.clone(),
+                AssetPosition {
+                    fixed_rate: true,
+                    ..debt
+                },
+            ],
+            timestamp: 0,
+        };
+        let prices = HashMap::from([
+            (weth, U256::from(2000) * U256::exp10(8)),
+            (usdc, U256::exp10(8)),
+        ]);
+
+        let mut engine = RiskEngine::new();
+        engine.update(&snapshot, &prices);
+        let ray = U256::exp10(27);
+        // half a year on, the variable rate drops to zero
+        engine.update_reserve(
+            usdc,
+            SECONDS_PER_YEAR / 2,
+            U256::zero(),
+            U256::zero(),
+            ray,
+            ray,
+        );
+        // unrelated reserves leave the positions alone
+        engine.update_reserve(
+            Address::repeat_byte(4),
+            SECONDS_PER_YEAR,
+            U256::zero(),
+            U256::zero(),
+            ray,
+            ray,
+        );
+
+        let snapshot = &engine.snapshots[&borrower];
+        assert_eq!(snapshot.timestamp, SECONDS_PER_YEAR / 2);
+        assert_eq!(snapshot.debt[0].rate, U256::zero());
+        assert_eq!(snapshot.debt[1].rate, U256::exp10(26));
+        assert_eq!(snapshot.collateral[0].rate, U256::zero());
+
+        // the variable debt stops growing at ~525 USDC, the stable one keeps compounding
+        let projected = snapshot.projected(SECONDS_PER_YEAR).unwrap();
+        assert_eq!(projected.debt[0].amount, snapshot.debt[0].amount);
+        assert!(projected.debt[0].amount > U256::from(525) * U256::exp10(18));
+        assert!(projected.debt[0].amount < U256::from(526) * U256::exp10(18));
+        assert!(projected.debt[1].amount > U256::from(552) * U256::exp10(18));
+    }
+
+    #[test]
+    fn test_update_reserve_indexes() {
+        let weth = Address::repeat_byte(1);
+        let usdc = Address::repeat_byte(2);
+        let borrower = Address::repeat_byte(3);
+        let ray = U256::exp10(27);
+        // 1000 USDC of debt projected at 10% a year, read at a borrow index of 1
+        let snapshot = PositionSnapshot {
+            borrower,
+            collateral: vec![AssetPosition {
+                index: Some(ray),
+                ..position(weth, 1, 8000)
+            }],
+            debt: vec![AssetPosition {
+                rate: U256::exp10(26),
+                index: Some(ray),
+                ..position(usdc, 1000, 0)
+            }],
+            timestamp: 0,
+        };
+        let prices = HashMap::from([
+            (weth, U256::from(2000) * U256::exp10(8)),
+            (usdc, U256::exp10(8)),
+        ]);
+
+        let mut engine = RiskEngine::new();
+        engine.update(&snapshot, &prices);
+        // the pool's borrow index grew 20% in half a year, more than the rate projects
+        let variable_borrow_index = ray * 12 / 10;
+        engine.update_reserve(
+            usdc,
+            SECONDS_PER_YEAR / 2,
+            U256::zero(),
+            U256::exp10(26),
+            ray,
+            variable_borrow_index,
+        );
+        let updated = &engine.snapshots[&borrower];
+        assert_eq!(updated.debt[0].amount, U256::from(1200) * U256::exp10(18));
+        assert_eq!(updated.debt[0].index, Some(variable_borrow_index));
+        assert_eq!(updated.collateral[0].amount, U256::exp10(18));
+
+        // the collateral's index still matches its amount, and grows with the next update
+        let liquidity_index = ray * 101 / 100;
+        engine.update_reserve(
+            weth,
+            SECONDS_PER_YEAR / 2,
+            U256::zero(),
+            U256::zero(),
+            liquidity_index,
+            ray,
+        );
+        let updated = &engine.snapshots[&borrower];
+        assert_eq!(updated.collateral[0].amount, U256::exp10(16) * 101);
+        assert_eq!(updated.debt[0].amount, U256::from(1200) * U256::exp10(18));
+    }
+}
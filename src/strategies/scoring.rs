@@ -0,0 +1,129 @@
+use super::confidence::{SimulationResult, CONFIDENCE_ONE};
+use ethers::types::{I256, U256};
+
+/// How far under water (in bps of health factor) a borrower has to be for their
+/// liquidation to count as sure to stay valid until it lands.
+pub const FULL_LANDING_DISTANCE_BPS: u64 = 500;
+
+/// How much each risk discounts the profit of an opportunity when ranking them, in bps.
+/// At 10000 a signal at its worst cancels the profit, at 0 it is ignored, so with no
+/// weights opportunities are ranked by profit alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScoringWeights {
+    /// Chance of landing: how far under water the borrower is and how much gas is bid.
+    pub landing_bps: u64,
+    /// Liquidity of the collateral, by the quality of the swap route out of it.
+    pub liquidity_bps: u64,
+    /// Chance of reverting, by the outcome of the simulation.
+    pub revert_bps: u64,
+}
+
+/// What an opportunity is ranked by besides its profit.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreSignals {
+    pub health_factor: U256,
+    /// Share of the profit bid in gas, in bps.
+    pub gas_bid_bps: u64,
+    /// Quality of the swap route out of the collateral, in bps.
+    pub route_quality_bps: u64,
+    pub simulation: SimulationResult,
+}
+
+/// The profit (in ETH, 8 decimals) of an opportunity discounted by each weighted risk.
+pub fn score(profit_eth: I256, signals: &ScoreSignals, weights: &ScoringWeights) -> I256 {
+    let landing =
+        (landing_distance(signals.health_factor) + signals.gas_bid_bps.min(CONFIDENCE_ONE)) / 2;
+    let liquidity = signals.route_quality_bps.min(CONFIDENCE_ONE);
+    let revert = match signals.simulation {
+        SimulationResult::Succeeded => CONFIDENCE_ONE,
+        SimulationResult::Skipped => CONFIDENCE_ONE / 2,
+        SimulationResult::Failed => 0,
+    };
+
+    [
+        (landing, weights.landing_bps),
+        (liquidity, weights.liquidity_bps),
+        (revert, weights.revert_bps),
+    ]
+    .into_iter()
+    .fold(profit_eth, |score, (signal, weight)| {
+        let discount = weight.min(CONFIDENCE_ONE) * (CONFIDENCE_ONE - signal) / CONFIDENCE_ONE;
+        score * I256::from(CONFIDENCE_ONE - discount) / I256::from(CONFIDENCE_ONE)
+    })
+}
+
+// how far below 1 the health factor is, scaled to full confidence at FULL_LANDING_DISTANCE_BPS
+fn landing_distance(health_factor: U256) -> u64 {
+    let one = U256::exp10(18);
+    if health_factor >= one {
+        return 0;
+    }
+    let distance_bps = ((one - health_factor) / U256::exp10(14)).as_u64();
+    (distance_bps * CONFIDENCE_ONE / FULL_LANDING_DISTANCE_BPS).min(CONFIDENCE_ONE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signals() -> ScoreSignals {
+        ScoreSignals {
+            health_factor: U256::exp10(14) * 9000,
+            gas_bid_bps: CONFIDENCE_ONE,
+            route_quality_bps: CONFIDENCE_ONE,
+            simulation: SimulationResult::Succeeded,
+        }
+    }
+
+    #[test]
+    fn test_score() {
+        let profit = I256::from(1_000_000);
+        let weights = ScoringWeights {
+            landing_bps: 10000,
+            liquidity_bps: 5000,
+            revert_bps: 10000,
+        };
+        // no risk left to discount
+        assert_eq!(score(profit, &signals(), &weights), profit);
+        // no weights rank by profit alone
+        let risky = ScoreSignals {
+            health_factor: U256::exp10(14) * 9990,
+            gas_bid_bps: 0,
+            route_quality_bps: 0,
+            simulation: SimulationResult::Failed,
+        };
+        assert_eq!(score(profit, &risky, &ScoringWeights::default()), profit);
+
+        // an illiquid route costs half the profit at a half weight
+        let illiquid = ScoreSignals {
+            route_quality_bps: 0,
+            ..signals()
+        };
+        assert_eq!(score(profit, &illiquid, &weights), I256::from(500_000));
+        // an unsimulated opportunity is discounted by half its weight
+        let unsimulated = ScoreSignals {
+            simulation: SimulationResult::Skipped,
+            ..signals()
+        };
+        assert_eq!(score(profit, &unsimulated, &weights), I256::from(500_000));
+        // barely under water with no gas bid, landing is all but certain to fail
+        let marginal = ScoreSignals {
+            health_factor: U256::exp10(14) * 9995,
+            gas_bid_bps: 0,
+            ..signals()
+        };
+        assert_eq!(score(profit, &marginal, &weights), I256::from(5_000));
+
+        // a safer opportunity outranks a more profitable risky one
+        assert!(
+            score(I256::from(900_000), &signals(), &weights) > score(profit, &illiquid, &weights)
+        );
+    }
+
+    #[test]
+    fn test_landing_distance() {
+        assert_eq!(landing_distance(U256::exp10(18)), 0);
+        assert_eq!(landing_distance(U256::exp10(14) * 9900), 2000);
+        assert_eq!(landing_distance(U256::exp10(14) * 9000), CONFIDENCE_ONE);
+    }
+}
@@ -0,0 +1,243 @@
+use super::filters::Shard;
+use anyhow::Result;
+use clap::ValueEnum;
+use ethers::core::rand::{thread_rng, Rng};
+use ethers::types::Address;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::stream::{self, Stream};
+use redis::aio::ConnectionManager;
+use redis::{AsyncCommands, Client, Script};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::Duration;
+use tracing::error;
+
+/// How long a published watchlist is kept, so one of an instance that stopped expires.
+pub const WATCHLIST_TTL_SECS: u64 = 300;
+/// Opportunities kept on the queue while no executor takes them, the oldest are dropped.
+pub const MAX_QUEUED_OPPORTUNITIES: isize = 1000;
+// seconds a wait on the queue blocks before it's retried
+const QUEUE_POLL_SECS: u64 = 1;
+
+/// What an instance of a cluster does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Index, detect and submit, on its own.
+    #[default]
+    All,
+    /// Index and detect, queueing the opportunities worth submitting. Holds no key.
+    Detect,
+    /// Build and submit the queued opportunities, without indexing the market.
+    Execute,
+}
+
+// take the claim if it's free or already ours, extending it either way
+const CLAIM_SCRIPT: &str = r"
+local owner = redis.call('GET', KEYS[1])
+if not owner or owner == ARGV[1] then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ARGV[2])
+    return 1
+end
+return 0
+";
+
+/// State shared through Redis by the instances of a cluster running the same market: the
+/// borrower cache, the watchlists, and leases claiming a borrower's liquidation for one
+/// instance at a time.
+#[derive(Clone)]
+pub struct SharedState {
+    client: Client,
+    connection: ConnectionManager,
+    /// Prefix of every key, the chain and pool of the market.
+    namespace: String,
+    /// Owner of the claims this instance takes.
+    instance: String,
+    claim_lease: Duration,
+}
+
+impl fmt::Debug for SharedState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SharedState")
+            .field("namespace", &self.namespace)
+            .field("instance", &self.instance)
+            .field("claim_lease", &self.claim_lease)
+            .finish()
+    }
+}
+
+impl SharedState {
+    /// Connect to Redis at `url`, reconnecting whenever the connection drops.
+    pub async fn connect(
+        url: &str,
+        chain_id: u64,
+        pool: Address,
+        claim_lease: Duration,
+    ) -> Result<Self> {
+        let client = Client::open(url)?;
+        let connection = ConnectionManager::new(client.clone()).await?;
+        Ok(Self {
+            client,
+            connection,
+            namespace: namespace(chain_id, pool),
+            instance: format!("{:016x}", thread_rng().gen::<u64>()),
+            claim_lease,
+        })
+    }
+
+    /// Id of this instance on the claims it takes.
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}:{}", self.namespace, name)
+    }
+
+    /// Read the borrower cache last written by any instance.
+    pub async fn read_borrowers<T: DeserializeOwned>(&self) -> Result<Option<T>> {
+        let bytes: Option<Vec<u8>> = self.connection.clone().get(self.key("borrowers")).await?;
+        bytes.map(|bytes| decode(&bytes)).transpose()
+    }
+
+    pub async fn write_borrowers<T: Serialize>(&self, cache: &T) -> Result<()> {
+        self.connection
+            .clone()
+            .set::<_, _, ()>(self.key("borrowers"), encode(cache)?)
+            .await?;
+        Ok(())
+    }
+
+    /// Borrowers close to liquidation as last scanned by an instance running `shard`.
+    pub async fn read_watchlist(&self, shard: Shard) -> Result<HashSet<Address>> {
+        let bytes: Option<Vec<u8>> = self
+            .connection
+            .clone()
+            .get(self.key(&watchlist_name(shard)))
+            .await?;
+        Ok(bytes
+            .map(|bytes| decode::<Vec<Address>>(&bytes))
+            .transpose()?
+            .unwrap_or_default()
+            .into_iter()
+            .collect())
+    }
+
+    pub async fn write_watchlist(&self, shard: Shard, watchlist: &HashSet<Address>) -> Result<()> {
+        let watchlist: Vec<&Address> = watchlist.iter().collect();
+        self.connection
+            .clone()
+            .set_ex::<_, _, ()>(
+                self.key(&watchlist_name(shard)),
+                encode(&watchlist)?,
+                WATCHLIST_TTL_SECS,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Claim the liquidation of `borrower` for the claim lease. False while another instance
+    /// holds it, this instance's own claim is extended.
+    pub async fn claim(&self, borrower: Address) -> Result<bool> {
+        let claimed: i64 = Script::new(CLAIM_SCRIPT)
+            .key(self.key(&claim_name(borrower)))
+            .arg(&self.instance)
+            .arg(self.claim_lease.as_millis() as u64)
+            .invoke_async(&mut self.connection.clone())
+            .await?;
+        Ok(claimed == 1)
+    }
+
+    /// Queue an opportunity for the executors, as JSON.
+    pub async fn publish_opportunity<T: Serialize>(&self, message: &T) -> Result<()> {
+        let key = self.key("opportunities");
+        redis::pipe()
+            .atomic()
+            .rpush(&key, serde_json::to_string(message)?)
+            .ltrim(&key, -MAX_QUEUED_OPPORTUNITIES, -1)
+            .query_async::<_, ()>(&mut self.connection.clone())
+            .await?;
+        Ok(())
+    }
+
+    /// The queued opportunities in order, each taken by one executor only. Waits on its own
+    /// connection, so the others' requests aren't held up, and ends once it fails.
+    pub async fn queued_opportunities(&self) -> Result<impl Stream<Item = String> + Send> {
+        let connection = self.client.get_multiplexed_tokio_connection().await?;
+        let key = self.key("opportunities");
+        Ok(stream::unfold(
+            (connection, key),
+            |(mut connection, key)| async move {
+                loop {
+                    let popped: redis::RedisResult<Option<(String, String)>> = redis::cmd("BLPOP")
+                        .arg(&key)
+                        .arg(QUEUE_POLL_SECS)
+                        .query_async(&mut connection)
+                        .await;
+                    match popped {
+                        Ok(Some((_, message))) => return Some((message, (connection, key))),
+                        Ok(None) => continue,
+                        Err(e) => {
+                            error!("Error reading queued opportunities: {}", e);
+                            return None;
+                        }
+                    }
+                }
+            },
+        ))
+    }
+}
+
+fn namespace(chain_id: u64, pool: Address) -> String {
+    format!("aave-liquidator:{}:{:?}", chain_id, pool)
+}
+
+fn watchlist_name(shard: Shard) -> String {
+    format!("watchlist:{}", shard)
+}
+
+fn claim_name(borrower: Address) -> String {
+    format!("claim:{:?}", borrower)
+}
+
+// gzipped JSON, like the state cache file
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, value)?;
+    Ok(encoder.finish()?)
+}
+
+fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    Ok(serde_json::from_reader(GzDecoder::new(bytes))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        let pool = Address::repeat_byte(1);
+        assert_eq!(
+            namespace(8453, pool),
+            "aave-liquidator:8453:0x0101010101010101010101010101010101010101"
+        );
+        assert_eq!(
+            watchlist_name(Shard { index: 1, count: 4 }),
+            "watchlist:1/4"
+        );
+        assert_eq!(
+            claim_name(Address::repeat_byte(2)),
+            "claim:0x0202020202020202020202020202020202020202"
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let watchlist = vec![Address::repeat_byte(1), Address::repeat_byte(2)];
+        let decoded: Vec<Address> = decode(&encode(&watchlist).unwrap()).unwrap();
+        assert_eq!(decoded, watchlist);
+        assert!(decode::<Vec<Address>>(b"not gzip").is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use super::revert_errors::{decode_revert, describe_reason, function_name};
+use anyhow::{anyhow, Result};
+use ethers::providers::Middleware;
+use ethers::types::{transaction::eip2718::TypedTransaction, Address, Bytes};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+/// Gas Tenderly simulates a tx with when it has no limit set.
+pub const TENDERLY_SIMULATION_GAS: u64 = 8_000_000;
+
+/// Where failed simulations are traced.
+#[derive(Debug, Clone)]
+pub enum TraceBackend {
+    /// `debug_traceCall` with the call tracer, on the bot's own endpoints.
+    DebugTraceCall,
+    /// Tenderly's simulation API.
+    Tenderly {
+        account: String,
+        project: String,
+        access_key: String,
+    },
+}
+
+/// A call of a traced simulation, with its subcalls. Reads both geth's call tracer and
+/// Tenderly's call trace.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TraceCall {
+    #[serde(rename = "type", alias = "call_type")]
+    pub kind: String,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub input: Bytes,
+    pub output: Option<Bytes>,
+    pub error: Option<String>,
+    #[serde(rename = "revertReason", alias = "error_reason")]
+    pub revert_reason: Option<String>,
+    /// Decoded by Tenderly when it knows the contract.
+    pub function_name: Option<String>,
+    pub contract_name: Option<String>,
+    pub calls: Option<Vec<TraceCall>>,
+}
+
+impl TraceCall {
+    /// The call tree, one call per line indented by depth, with function names and revert
+    /// reasons decoded where the bot knows the contract.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.render_into(&mut out, 0);
+        out
+    }
+
+    fn render_into(&self, out: &mut String, depth: usize) {
+        let target = match (&self.contract_name, self.to) {
+            (Some(contract), _) => contract.clone(),
+            (None, Some(to)) => format!("{:?}", to),
+            (None, None) => "create".to_string(),
+        };
+        let function = self
+            .function_name
+            .clone()
+            .or_else(|| function_name(&self.input))
+            .unwrap_or_else(|| selector_hex(&self.input));
+        let _ = write!(
+            out,
+            "{}{} {}.{}",
+            "  ".repeat(depth),
+            self.kind,
+            target,
+            function
+        );
+        if let Some(reason) = self.failure() {
+            let _ = write!(out, " reverted: {}", reason);
+        }
+        out.push('\n');
+        for call in self.calls.iter().flatten() {
+            call.render_into(out, depth + 1);
+        }
+    }
+
+    /// Why this call failed, none if it didn't.
+    pub fn failure(&self) -> Option<String> {
+        let error = self.error.as_ref()?;
+        Some(
+            self.revert_reason
+                .clone()
+                .map(|reason| describe_reason(&reason))
+                .or_else(|| {
+                    self.output
+                        .as_ref()
+                        .and_then(|output| decode_revert(output))
+                })
+                .unwrap_or_else(|| error.clone()),
+        )
+    }
+
+    /// The failure of the deepest failed call, where the revert started.
+    pub fn root_cause(&self) -> Option<String> {
+        self.error.as_ref()?;
+        self.calls
+            .iter()
+            .flatten()
+            .filter_map(TraceCall::root_cause)
+            .last()
+            .or_else(|| self.failure())
+    }
+}
+
+fn selector_hex(input: &[u8]) -> String {
+    match input.get(..4) {
+        Some(selector) => format!("0x{}", hex::encode(selector)),
+        None => "fallback".to_string(),
+    }
+}
+
+/// Traces simulations that failed, so their logs show which call reverted and why.
+#[derive(Debug, Clone)]
+pub struct SimulationTracer {
+    backend: TraceBackend,
+    chain_id: u64,
+    http: reqwest::Client,
+}
+
+impl SimulationTracer {
+    pub fn new(backend: TraceBackend, chain_id: u64) -> Self {
+        Self {
+            backend,
+            chain_id,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Trace `tx` against the latest block.
+    pub async fn trace<M: Middleware>(
+        &self,
+        client: &M,
+        tx: &TypedTransaction,
+    ) -> Result<TraceCall> {
+        match &self.backend {
+            TraceBackend::DebugTraceCall => client
+                .provider()
+                .request(
+                    "debug_traceCall",
+                    (tx, "latest", json!({ "tracer": "callTracer" })),
+                )
+                .await
+                .map_err(|e| anyhow!("debug_traceCall failed: {}", e)),
+            TraceBackend::Tenderly {
+                account,
+                project,
+                access_key,
+            } => {
+                let url = format!(
+                    "https://api.tenderly.co/api/v1/account/{}/project/{}/simulate",
+                    account, project
+                );
+                let body = json!({
+                    "network_id": self.chain_id.to_string(),
+                    "from": tx.from(),
+                    "to": tx.to_addr(),
+                    "input": tx.data().cloned().unwrap_or_default(),
+                    "gas": tx.gas().map_or(TENDERLY_SIMULATION_GAS, |gas| gas.low_u64()),
+                    "value": tx.value().copied().unwrap_or_default().to_string(),
+                    "save": false,
+                    "simulation_type": "full",
+                });
+                let response: Value = self
+                    .http
+                    .post(url)
+                    .header("X-Access-Key", access_key)
+                    .json(&body)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                let trace = response
+                    .pointer("/transaction/transaction_info/call_trace")
+                    .ok_or(anyhow!("No call trace in Tenderly's response"))?;
+                Ok(serde_json::from_value(trace.clone())?)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        let trace: TraceCall = serde_json::from_value(json!({
+            "type": "CALL",
+            "from": "0x0101010101010101010101010101010101010101",
+            "to": "0x0202020202020202020202020202020202020202",
+            "input": "0x00a718a9",
+            "error": "execution reverted",
+            "calls": [
+                {
+                    "type": "STATICCALL",
+                    "from": "0x0202020202020202020202020202020202020202",
+                    "to": "0x0303030303030303030303030303030303030303",
+                    "input": "0x70a08231"
+                },
+                {
+                    "type": "DELEGATECALL",
+                    "from": "0x0202020202020202020202020202020202020202",
+                    "to": "0x0404040404040404040404040404040404040404",
+                    "input": "0x00a718a9",
+                    "output": "0x08c379a0000000000000000000000000000000000000000000000000000000000000002000000000000000000000000000000000000000000000000000000000000000023435000000000000000000000000000000000000000000000000000000000000",
+                    "error": "execution reverted"
+                }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            trace.root_cause(),
+            Some("45 (HEALTH_FACTOR_NOT_BELOW_THRESHOLD)".to_string())
+        );
+        assert_eq!(
+            trace.render(),
+            "CALL 0x0202020202020202020202020202020202020202.liquidationCall reverted: execution reverted\n  \
+             STATICCALL 0x0303030303030303030303030303030303030303.balanceOf\n  \
+             DELEGATECALL 0x0404040404040404040404040404040404040404.liquidationCall reverted: 45 (HEALTH_FACTOR_NOT_BELOW_THRESHOLD)\n"
+        );
+    }
+}
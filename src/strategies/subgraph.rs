@@ -0,0 +1,175 @@
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, U256};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::str::FromStr;
+use tracing::info;
+
+/// Users fetched per subgraph query, the most The Graph returns at once.
+pub const SUBGRAPH_PAGE_SIZE: usize = 1000;
+
+const META_QUERY: &str = "{ _meta { block { number } } }";
+
+// users with debt after `lastId`, paged by id since The Graph caps `skip`
+const USERS_QUERY: &str = "query Users($block: Int!, $lastId: String!, $first: Int!) {
+  users(
+    block: { number: $block }
+    first: $first
+    orderBy: id
+    where: { id_gt: $lastId, borrowedReservesCount_gt: 0 }
+  ) {
+    id
+    reserves {
+      currentATokenBalance
+      currentTotalDebt
+      usageAsCollateralEnabledOnUser
+      reserve { underlyingAsset }
+    }
+  }
+}";
+
+/// A borrower as the Aave V3 subgraph sees them, seeding the borrower set on first sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubgraphBorrower {
+    pub address: Address,
+    pub collateral: HashSet<Address>,
+    pub debt: HashSet<Address>,
+}
+
+/// Every borrower with debt in the Aave V3 subgraph at `url`, along with the block they
+/// were read at, so logs only need replaying from there.
+pub async fn get_subgraph_borrowers(url: &str) -> Result<(u64, Vec<SubgraphBorrower>)> {
+    let http = reqwest::Client::new();
+    let meta = query(&http, url, META_QUERY, json!({})).await?;
+    let block = meta["_meta"]["block"]["number"]
+        .as_u64()
+        .ok_or(anyhow!("Subgraph has no indexed block: {}", meta))?;
+
+    // pin every page to the same block, so users moving in between aren't missed
+    let mut borrowers = Vec::new();
+    let mut last_id = String::new();
+    loop {
+        let page = query(
+            &http,
+            url,
+            USERS_QUERY,
+            json!({ "block": block, "lastId": last_id, "first": SUBGRAPH_PAGE_SIZE }),
+        )
+        .await?;
+        let users = parse_users(&page["users"])?;
+        let done = users.len() < SUBGRAPH_PAGE_SIZE;
+        match users.last() {
+            Some(user) => last_id = format!("{:?}", user.address),
+            None => break,
+        }
+        borrowers.extend(users);
+        info!("Fetched {} borrowers from the subgraph", borrowers.len());
+        if done {
+            break;
+        }
+    }
+    Ok((block, borrowers))
+}
+
+async fn query(http: &reqwest::Client, url: &str, query: &str, variables: Value) -> Result<Value> {
+    let mut response: Value = http
+        .post(url)
+        .json(&json!({ "query": query, "variables": variables }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(errors) = response.get("errors") {
+        return Err(anyhow!("Subgraph query failed: {}", errors));
+    }
+    match response.get_mut("data") {
+        Some(data) => Ok(data.take()),
+        None => Err(anyhow!("Subgraph returned no data: {}", response)),
+    }
+}
+
+/// The borrowers of a page of subgraph users, with the reserves they supply as
+/// collateral and borrow.
+pub fn parse_users(users: &Value) -> Result<Vec<SubgraphBorrower>> {
+    let users = users
+        .as_array()
+        .ok_or(anyhow!("Invalid subgraph users: {}", users))?;
+    users
+        .iter()
+        .map(|user| -> Result<SubgraphBorrower> {
+            let address = user["id"]
+                .as_str()
+                .map(Address::from_str)
+                .ok_or(anyhow!("Invalid subgraph user: {}", user))??;
+            let mut borrower = SubgraphBorrower {
+                address,
+                collateral: HashSet::new(),
+                debt: HashSet::new(),
+            };
+            for reserve in user["reserves"].as_array().into_iter().flatten() {
+                let asset = reserve["reserve"]["underlyingAsset"]
+                    .as_str()
+                    .map(Address::from_str)
+                    .ok_or(anyhow!("Invalid subgraph reserve: {}", reserve))??;
+                if !amount(&reserve["currentTotalDebt"])?.is_zero() {
+                    borrower.debt.insert(asset);
+                }
+                if reserve["usageAsCollateralEnabledOnUser"].as_bool() == Some(true)
+                    && !amount(&reserve["currentATokenBalance"])?.is_zero()
+                {
+                    borrower.collateral.insert(asset);
+                }
+            }
+            Ok(borrower)
+        })
+        .collect()
+}
+
+// subgraph BigInts are decimal strings
+fn amount(value: &Value) -> Result<U256> {
+    match value.as_str() {
+        Some(amount) => Ok(U256::from_dec_str(amount)?),
+        None => Ok(U256::zero()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_users() {
+        let weth = "0x4200000000000000000000000000000000000006";
+        let usdc = "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913";
+        let users = json!([{
+            "id": "0x0000000000000000000000000000000000000001",
+            "reserves": [
+                {
+                    "currentATokenBalance": "1000000000000000000",
+                    "currentTotalDebt": "0",
+                    "usageAsCollateralEnabledOnUser": true,
+                    "reserve": { "underlyingAsset": weth }
+                },
+                {
+                    "currentATokenBalance": "5000000",
+                    "currentTotalDebt": "2000000000",
+                    "usageAsCollateralEnabledOnUser": false,
+                    "reserve": { "underlyingAsset": usdc }
+                }
+            ]
+        }]);
+
+        let borrowers = parse_users(&users).unwrap();
+        assert_eq!(
+            borrowers,
+            vec![SubgraphBorrower {
+                address: Address::from_low_u64_be(1),
+                // USDC is supplied but not enabled as collateral
+                collateral: HashSet::from([Address::from_str(weth).unwrap()]),
+                debt: HashSet::from([Address::from_str(usdc).unwrap()]),
+            }]
+        );
+        assert!(parse_users(&json!({})).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use super::debt_swap::get_paraswap_sell;
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, Bytes, U256};
+use serde_json::Value;
+use std::fmt;
+use tracing::info;
+
+pub const ONE_INCH_API_URL: &str = "https://api.1inch.dev/swap/v6.0";
+pub const ZERO_EX_API_URL: &str = "https://api.0x.org";
+
+const PERCENTAGE_FACTOR: u64 = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregator {
+    ParaSwap,
+    OneInch,
+    ZeroEx,
+}
+
+impl fmt::Display for Aggregator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Aggregator::ParaSwap => write!(f, "ParaSwap"),
+            Aggregator::OneInch => write!(f, "1inch"),
+            Aggregator::ZeroEx => write!(f, "0x"),
+        }
+    }
+}
+
+/// A swap ready to send, as quoted by an aggregator.
+#[derive(Debug, Clone)]
+pub struct SwapQuote {
+    pub aggregator: Aggregator,
+    /// Contract the swap is sent to.
+    pub to: Address,
+    pub data: Bytes,
+    /// Spender the sold token has to be approved to.
+    pub spender: Address,
+    /// Minimum received, slippage included.
+    pub min_amount_out: U256,
+}
+
+/// A token swap to quote.
+#[derive(Debug, Clone, Copy)]
+pub struct SwapRequest {
+    pub src_token: Address,
+    pub src_decimals: u64,
+    pub dest_token: Address,
+    pub dest_decimals: u64,
+    pub amount: U256,
+    /// Who sends the swap.
+    pub from: Address,
+}
+
+/// Quotes swaps on every configured aggregator and picks the best route.
+#[derive(Debug, Clone)]
+pub struct SwapRouter {
+    chain_id: u64,
+    max_slippage_bps: u64,
+    paraswap_api_url: String,
+    /// 1inch is only queried with an API key.
+    one_inch_api_key: Option<String>,
+    /// 0x is only queried with an API key.
+    zero_ex_api_key: Option<String>,
+    http: reqwest::Client,
+}
+
+impl SwapRouter {
+    pub fn new(
+        chain_id: u64,
+        max_slippage_bps: u64,
+        paraswap_api_url: String,
+        one_inch_api_key: Option<String>,
+        zero_ex_api_key: Option<String>,
+    ) -> Self {
+        Self {
+            chain_id,
+            max_slippage_bps,
+            paraswap_api_url,
+            one_inch_api_key,
+            zero_ex_api_key,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Quote the swap on every aggregator, returning the one paying out the most.
+    pub async fn best_quote(&self, request: SwapRequest) -> Result<SwapQuote> {
+        let mut quotes = vec![self.quote_paraswap(request).await];
+        if let Some(api_key) = &self.one_inch_api_key {
+            quotes.push(self.quote_one_inch(request, api_key).await);
+        }
+        if let Some(api_key) = &self.zero_ex_api_key {
+            quotes.push(self.quote_zero_ex(request, api_key).await);
+        }
+
+        let quotes = quotes
+            .into_iter()
+            .filter_map(|quote| quote.map_err(|e| info!("Swap quote failed: {}", e)).ok())
+            .collect();
+        let best = pick_best(quotes).ok_or(anyhow!(
+            "No swap route from {:?} to {:?}",
+            request.src_token,
+            request.dest_token
+        ))?;
+        info!(
+            "Best swap route: {}, at least {} out",
+            best.aggregator, best.min_amount_out
+        );
+        Ok(best)
+    }
+
+    async fn quote_paraswap(&self, request: SwapRequest) -> Result<SwapQuote> {
+        let sell = get_paraswap_sell(
+            &self.paraswap_api_url,
+            self.chain_id,
+            request.src_token,
+            request.src_decimals,
+            request.dest_token,
+            request.dest_decimals,
+            request.amount,
+            request.from,
+            self.max_slippage_bps,
+        )
+        .await?;
+        Ok(SwapQuote {
+            aggregator: Aggregator::ParaSwap,
+            to: sell.to,
+            data: sell.data,
+            spender: sell.token_transfer_proxy,
+            min_amount_out: sell.min_dest_amount,
+        })
+    }
+
+    async fn quote_one_inch(&self, request: SwapRequest, api_key: &str) -> Result<SwapQuote> {
+        let swap: Value = self
+            .http
+            .get(format!("{}/{}/swap", ONE_INCH_API_URL, self.chain_id))
+            .bearer_auth(api_key)
+            .query(&[
+                ("src", format!("{:?}", request.src_token)),
+                ("dst", format!("{:?}", request.dest_token)),
+                ("amount", request.amount.to_string()),
+                ("from", format!("{:?}", request.from)),
+                ("slippage", slippage_percent(self.max_slippage_bps)),
+                ("disableEstimate", "true".to_string()),
+            ])
+            .send()
+            .await?
+            .json()
+            .await?;
+        let amount_out = swap["dstAmount"]
+            .as_str()
+            .ok_or(anyhow!("Invalid 1inch swap: {}", swap))?;
+        let to: Address = serde_json::from_value(swap["tx"]["to"].clone())
+            .map_err(|_| anyhow!("Invalid 1inch swap: {}", swap))?;
+        let data: Bytes = serde_json::from_value(swap["tx"]["data"].clone())
+            .map_err(|_| anyhow!("Invalid 1inch swap: {}", swap))?;
+        Ok(SwapQuote {
+            aggregator: Aggregator::OneInch,
+            to,
+            data,
+            // the 1inch router pulls the tokens itself
+            spender: to,
+            min_amount_out: U256::from_dec_str(amount_out)?
+                * (PERCENTAGE_FACTOR - self.max_slippage_bps.min(PERCENTAGE_FACTOR))
+                / PERCENTAGE_FACTOR,
+        })
+    }
+
+    async fn quote_zero_ex(&self, request: SwapRequest, api_key: &str) -> Result<SwapQuote> {
+        let quote: Value = self
+            .http
+            .get(format!("{}/swap/allowance-holder/quote", ZERO_EX_API_URL))
+            .header("0x-api-key", api_key)
+            .header("0x-version", "v2")
+            .query(&[
+                ("chainId", self.chain_id.to_string()),
+                ("sellToken", format!("{:?}", request.src_token)),
+                ("buyToken", format!("{:?}", request.dest_token)),
+                ("sellAmount", request.amount.to_string()),
+                ("taker", format!("{:?}", request.from)),
+                ("slippageBps", self.max_slippage_bps.to_string()),
+            ])
+            .send()
+            .await?
+            .json()
+            .await?;
+        let min_amount_out = quote["minBuyAmount"]
+            .as_str()
+            .ok_or(anyhow!("Invalid 0x quote: {}", quote))?;
+        let to: Address = serde_json::from_value(quote["transaction"]["to"].clone())
+            .map_err(|_| anyhow!("Invalid 0x quote: {}", quote))?;
+        let data: Bytes = serde_json::from_value(quote["transaction"]["data"].clone())
+            .map_err(|_| anyhow!("Invalid 0x quote: {}", quote))?;
+        // the allowance holder is both the spender and the entry point
+        let spender =
+            serde_json::from_value(quote["issues"]["allowance"]["spender"].clone()).unwrap_or(to);
+        Ok(SwapQuote {
+            aggregator: Aggregator::ZeroEx,
+            to,
+            data,
+            spender,
+            min_amount_out: U256::from_dec_str(min_amount_out)?,
+        })
+    }
+}
+
+/// The quote paying out the most once slippage is accounted for.
+pub fn pick_best(quotes: Vec<SwapQuote>) -> Option<SwapQuote> {
+    quotes.into_iter().max_by_key(|quote| quote.min_amount_out)
+}
+
+fn slippage_percent(slippage_bps: u64) -> String {
+    format!("{}.{:02}", slippage_bps / 100, slippage_bps % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_best() {
+        let quote = |aggregator, min_amount_out: u64| SwapQuote {
+            aggregator,
+            to: Address::zero(),
+            data: Bytes::default(),
+            spender: Address::zero(),
+            min_amount_out: U256::from(min_amount_out),
+        };
+        let best = pick_best(vec![
+            quote(Aggregator::ParaSwap, 100),
+            quote(Aggregator::OneInch, 120),
+            quote(Aggregator::ZeroEx, 110),
+        ])
+        .unwrap();
+        assert_eq!(best.aggregator, Aggregator::OneInch);
+        assert!(pick_best(vec![]).is_none());
+
+        assert_eq!(slippage_percent(50), "0.50");
+        assert_eq!(slippage_percent(125), "1.25");
+    }
+}
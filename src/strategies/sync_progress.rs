@@ -0,0 +1,82 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// Blocks of logs indexed at a time, progress is reported after each chunk.
+pub const SYNC_CHUNK_BLOCKS: u64 = 50_000;
+
+/// Progress of the last state update indexing the pool's logs up to the chain head,
+/// published for the admin API.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncProgress {
+    pub from_block: u64,
+    /// Head of the chain when the update started.
+    pub to_block: u64,
+    /// Block logs are indexed up to.
+    pub indexed_block: u64,
+    /// Borrow, supply and collateral logs processed.
+    pub events: usize,
+    /// Borrowers found that weren't known before.
+    pub new_borrowers: usize,
+    pub elapsed_secs: u64,
+    pub blocks_per_sec: u64,
+    /// Seconds left at the pace so far, none until a chunk is indexed.
+    pub eta_secs: Option<u64>,
+    /// Blocks the head had moved past the indexed state once the update was done, only
+    /// measured until submission starts under `--max-sync-lag`.
+    pub head_lag_blocks: Option<u64>,
+}
+
+impl SyncProgress {
+    pub fn new(from_block: u64, to_block: u64) -> Self {
+        Self {
+            from_block,
+            to_block,
+            indexed_block: from_block,
+            ..Default::default()
+        }
+    }
+
+    /// Blocks the update covers.
+    pub fn blocks(&self) -> u64 {
+        self.to_block.saturating_sub(self.from_block)
+    }
+
+    /// Record a chunk indexed up to `indexed_block`, `elapsed` after the update started.
+    pub fn advance(
+        &mut self,
+        indexed_block: u64,
+        events: usize,
+        new_borrowers: usize,
+        elapsed: Duration,
+    ) {
+        self.indexed_block = indexed_block;
+        self.events += events;
+        self.new_borrowers += new_borrowers;
+        self.elapsed_secs = elapsed.as_secs();
+        let indexed = indexed_block.saturating_sub(self.from_block);
+        let millis = elapsed.as_millis().max(1) as u64;
+        self.blocks_per_sec = indexed.saturating_mul(1000) / millis;
+        let left = self.to_block.saturating_sub(indexed_block);
+        self.eta_secs = (indexed > 0).then(|| left.saturating_mul(millis) / indexed / 1000);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync_progress() {
+        let mut progress = SyncProgress::new(1000, 101_000);
+        assert_eq!(progress.blocks(), 100_000);
+        assert_eq!(progress.eta_secs, None);
+
+        progress.advance(26_000, 300, 20, Duration::from_secs(10));
+        assert_eq!(progress.blocks_per_sec, 2500);
+        assert_eq!(progress.eta_secs, Some(30));
+        progress.advance(101_000, 900, 40, Duration::from_secs(40));
+        assert_eq!((progress.events, progress.new_borrowers), (1200, 60));
+        assert_eq!(progress.eta_secs, Some(0));
+        assert_eq!(progress.elapsed_secs, 40);
+    }
+}
@@ -1,16 +1,51 @@
+use super::approval::ApprovalConfig;
+use super::filters::LiquidationFilter;
+use super::flash_liquidity::FlashLiquidityPool;
+use super::harvest::HarvestConfig;
+use super::price_check::PriceSource;
+use super::profit_currency::ProfitCurrency;
+use super::scoring::ScoringWeights;
+use super::simulation_trace::TraceBackend;
+use crate::collectors::liquidation_call_collector::LiquidationCall;
+use crate::collectors::opportunity_queue_collector::QueuedOpportunity;
+use crate::collectors::price_update_collector::PendingPriceUpdate;
+use crate::collectors::reserve_data_collector::ReserveDataUpdate;
 use crate::collectors::time_collector::NewTick;
-use artemis_core::executors::mempool_executor::SubmitTxToMempool;
+use crate::control::ControlCommand;
+use crate::executors::mev_share_executor::SubmitBackrunBundle;
+use crate::executors::protect_executor::SubmitTx;
+use ethers::core::rand::{thread_rng, Rng};
+use ethers::types::{Address, U256};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
 
 /// Core Event enum for the current strategy.
 #[derive(Debug, Clone)]
 pub enum Event {
     NewTick(NewTick),
+    WatchlistTick(NewTick),
+    PendingPriceUpdate(PendingPriceUpdate),
+    /// An oracle update seen on MEV-Share, which can be backrun.
+    BackrunPriceUpdate(PendingPriceUpdate),
+    Control(ControlCommand),
+    /// Time to collect and convert liquidation proceeds.
+    HarvestTick(NewTick),
+    /// Time to check the sender's gas balance, the Liquidator's owner and allowances.
+    WalletCheckTick(NewTick),
+    /// New rates and indexes of a reserve, from its `ReserveDataUpdated` log.
+    ReserveDataUpdate(ReserveDataUpdate),
+    /// A liquidation that landed on the pool, ours or a competitor's.
+    LiquidationCall(LiquidationCall),
+    /// An opportunity a detect-only instance queued, for an execute-only one to submit.
+    QueuedOpportunity(QueuedOpportunity),
 }
 
 /// Core Action enum for the current strategy.
 #[derive(Debug, Clone)]
 pub enum Action {
-    SubmitTx(SubmitTxToMempool),
+    SubmitTx(SubmitTx),
+    SubmitBundle(SubmitBackrunBundle),
 }
 
 /// Configuration for variables we need to pass to the strategy.
@@ -18,4 +53,133 @@ pub enum Action {
 pub struct Config {
     pub chain_id: u64,
     pub bid_percentage: u64,
+    /// Bounds the bid moves in as races are lost and won by a large margin, it doesn't
+    /// rise or fall respectively if unset.
+    pub min_bid_percentage: Option<u64>,
+    pub max_bid_percentage: Option<u64>,
+    /// Borrowers with a health factor below this (in bps, 10000 = 1.0) are
+    /// polled on every watchlist tick.
+    pub watchlist_health_factor_bps: u64,
+    /// Borrowers with a health factor below this (in bps) are indexed by the risk engine.
+    pub risk_health_factor_bps: u64,
+    /// Opportunities with a confidence score (in bps) below this are not submitted.
+    pub min_confidence_bps: u64,
+    /// Opportunities with a confidence score (in bps) below this are submitted privately.
+    pub public_confidence_bps: u64,
+    /// How much the risks of an opportunity weigh against its profit when ranking them.
+    pub scoring: ScoringWeights,
+    /// JSON file listing partner accounts and how each is handled.
+    pub partner_accounts_file: PathBuf,
+    /// ParaSwap repay adapter, the repay path is disabled if unset.
+    pub repay_adapter: Option<Address>,
+    pub paraswap_api_url: String,
+    /// Health factor (in bps) partner accounts are brought back to when repaying with collateral.
+    pub target_health_factor_bps: u64,
+    /// Maximum slippage (in bps) accepted when swapping collateral for debt.
+    pub max_slippage_bps: u64,
+    /// Whether the liquidator is registered with the deployment's fee rebate program.
+    pub fee_rebate: bool,
+    /// Share of gas fees (in bps) refunded by the chain, overriding the deployment's rebate.
+    pub fee_rebate_bps: Option<u64>,
+    /// Smart account borrowers are reconciled with their on-chain reserves every this many ticks.
+    pub smart_account_reconcile_ticks: u64,
+    /// EOA borrowers are reconciled with their on-chain reserves every this many ticks.
+    pub eoa_reconcile_ticks: u64,
+    /// Opportunities making less than this (in units of the profit currency) are not submitted.
+    pub min_profit_eth: U256,
+    /// What the min profit is set in and profits are reported in.
+    pub profit_currency: ProfitCurrency,
+    /// Borrowers owing less than this (in USD) are skipped as dust.
+    pub min_debt_usd: u64,
+    /// Underwater borrowers evaluated concurrently.
+    pub evaluation_concurrency: usize,
+    /// Pairs of one borrower liquidated in the same tx through the Liquidator contract,
+    /// 1 liquidates a single pair per tx.
+    pub max_liquidation_legs: usize,
+    /// Route flash swaps through the wrapped native token when no Uniswap V3 pool pairs the
+    /// collateral with the debt, through the Liquidator contract's `liquidatePath`.
+    pub multi_hop_swaps: bool,
+    /// Balancer and Curve pools the collateral is swapped for a Balancer flash loan of the
+    /// debt on, when Uniswap V3 can't swap it at all.
+    pub flash_liquidity_pools: Vec<FlashLiquidityPool>,
+    /// Sell the collateral along the best aggregator route for a Balancer flash loan of the
+    /// debt, when neither Uniswap V3 nor the flash liquidity pools can swap it.
+    pub aggregator_flash_swaps: bool,
+    /// Native balance (in wei) the sender is warned below, 0 disables the check.
+    pub min_gas_balance_wei: U256,
+    /// Hold submission while the wallet check finds problems, instead of only warning.
+    pub pause_on_wallet_problems: bool,
+    /// Hold submission until a state update leaves the indexed state within this many
+    /// blocks of the head, not at all if unset.
+    pub max_sync_lag: Option<u64>,
+    /// Oracle exposing `getAssetPrice` the Aave oracle's prices are checked against by the
+    /// circuit breaker, unchecked if unset.
+    pub reference_oracle: Option<Address>,
+    /// Secondary sources oracle prices are cross-checked with before submitting, in order of
+    /// preference. Empty disables the check.
+    pub price_sources: Vec<PriceSource>,
+    /// Most the oracle's collateral/debt rate may deviate from the sources', in bps.
+    pub max_price_source_deviation_bps: u64,
+    /// Harvesting of seized collateral, disabled if unset.
+    pub harvest: Option<HarvestConfig>,
+    /// Liquidations held for an operator's approval, none are if unset.
+    pub approval: Option<ApprovalConfig>,
+    /// 1inch is quoted for swaps only with an API key.
+    pub one_inch_api_key: Option<String>,
+    /// 0x is quoted for swaps only with an API key.
+    pub zero_ex_api_key: Option<String>,
+    /// Where failed simulations are traced for their logs, untraced if unset.
+    pub tracer: Option<TraceBackend>,
+    /// Directory the borrower state cache is kept in.
+    pub state_dir: PathBuf,
+    /// Seconds reserve configs and e-mode categories cached in `state_dir` are reused for
+    /// on restart, nothing is cached if zero.
+    pub reserve_cache_ttl_secs: u64,
+    /// Aave V3 subgraph seeding the borrower set when there is no cache, instead of
+    /// replaying every log since the pool's creation.
+    pub subgraph_url: Option<String>,
+    /// UiPoolDataProvider positions are snapshotted through, if set.
+    pub ui_pool_data_provider: Option<Address>,
+    /// Multicall3 of the chain, the deployment's if unset.
+    pub multicall_address: Option<Address>,
+    /// Pool of an Aave V3 fork, the deployment's if unset.
+    pub pool_address: Option<Address>,
+    /// Wrapped native token of an Aave V3 fork, the deployment's if unset.
+    pub wrapped_native_address: Option<Address>,
+    /// Block range `eth_getLogs` starts at, adapted to what the provider accepts.
+    pub log_block_range: u64,
+    /// On the Aave path, approve each liquidation's debt plus this buffer (in bps) right
+    /// before it instead of unlimited approvals at startup.
+    pub approval_buffer_bps: Option<u64>,
+    /// Collateral assets seized as aTokens on the Aave path, instead of withdrawn.
+    pub receive_a_token: HashSet<Address>,
+    /// Assets and borrowers never liquidated.
+    pub filter: LiquidationFilter,
+}
+
+/// What happened during one full scan tick, the canonical heartbeat record.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TickSummary {
+    pub block_number: u64,
+    /// Unix time the tick started at.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub borrowers_scanned: u64,
+    /// Borrowers skipped for owing less than the minimum position size.
+    pub dust_skipped: u64,
+    /// Underwater borrowers skipped for bad debt, their collateral can't pay for a liquidation.
+    pub bad_debt_found: u64,
+    /// Debt of those borrowers their collateral doesn't cover, in base currency.
+    pub bad_debt_base: U256,
+    pub underwater_found: u64,
+    pub ops_evaluated: u64,
+    pub ops_submitted: u64,
+    pub errors: u64,
+    /// Opportunities that failed to build, by the revert they hit.
+    pub reverts: BTreeMap<String, u64>,
+}
+
+/// A random id tying together the logs of one opportunity, from detection to receipt.
+pub fn new_correlation_id() -> String {
+    format!("{:016x}", thread_rng().gen::<u64>())
 }
@@ -0,0 +1,83 @@
+use ethers::types::{Address, Bytes, U256};
+use serde::{Deserialize, Serialize};
+
+/// Uniswap V3 fee tiers, in hundredths of a bip.
+pub const UNISWAP_FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+/// Fee tier used when no quoter is deployed to pick one.
+pub const DEFAULT_UNISWAP_FEE: u32 = 500;
+
+/// Pick the tier needing the least collateral to flash-borrow the debt, among those whose
+/// pool exists (quoted) and can be repaid with the collateral seized.
+pub fn pick_fee_tier(quotes: &[(u32, Option<U256>)], max_amount_in: U256) -> Option<(u32, U256)> {
+    quotes
+        .iter()
+        .filter_map(|(fee, amount_in)| Some((*fee, (*amount_in)?)))
+        .filter(|(_, amount_in)| *amount_in <= max_amount_in)
+        .min_by_key(|(_, amount_in)| *amount_in)
+}
+
+/// A Uniswap V3 route from the collateral to the debt through other tokens, for pairs no
+/// pool pairs directly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapPath {
+    /// From the collateral to the debt.
+    pub tokens: Vec<Address>,
+    /// Fee tier of the pool between each token and the next.
+    pub fees: Vec<u32>,
+}
+
+impl SwapPath {
+    /// The route packed as Uniswap's exact output paths are, from the debt back to the
+    /// collateral: `debt, fee, token, fee, ..., collateral`.
+    pub fn encode_exact_output(&self) -> Bytes {
+        let mut path = Vec::new();
+        for (i, token) in self.tokens.iter().rev().enumerate() {
+            if i > 0 {
+                // fees are 3 bytes
+                path.extend_from_slice(&self.fees[self.fees.len() - i].to_be_bytes()[1..]);
+            }
+            path.extend_from_slice(token.as_bytes());
+        }
+        path.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_exact_output() {
+        let path = SwapPath {
+            tokens: vec![
+                Address::repeat_byte(1),
+                Address::repeat_byte(2),
+                Address::repeat_byte(3),
+            ],
+            fees: vec![500, 3000],
+        };
+        let mut expected = vec![3; 20];
+        expected.extend([0x00, 0x0b, 0xb8]);
+        expected.extend([2; 20]);
+        expected.extend([0x00, 0x01, 0xf4]);
+        expected.extend([1; 20]);
+        assert_eq!(path.encode_exact_output(), Bytes::from(expected));
+    }
+
+    #[test]
+    fn test_pick_fee_tier() {
+        let quotes = [
+            (100, None),
+            (500, Some(U256::from(1050))),
+            (3000, Some(U256::from(1010))),
+            (10000, Some(U256::from(1200))),
+        ];
+        assert_eq!(
+            pick_fee_tier(&quotes, U256::from(1100)),
+            Some((3000, U256::from(1010)))
+        );
+        // not enough collateral seized to repay any pool
+        assert_eq!(pick_fee_tier(&quotes, U256::from(1000)), None);
+    }
+}
@@ -0,0 +1,80 @@
+use super::aave_strategy::MULTICALL_CHUNK_SIZE;
+use super::multicall::MulticallSite;
+use anyhow::Result;
+use bindings_aave::pool::{Pool, UserConfigurationMap};
+use ethers::{
+    providers::Middleware,
+    types::{Address, U256},
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Read the user configuration bitmaps of `users` from the pool, in their order. Unlike
+/// the reserves seen in a user's events, the bitmap follows collateral toggles, repays
+/// and withdrawals.
+pub async fn get_user_configurations<M: Middleware + 'static>(
+    client: Arc<M>,
+    multicall: &MulticallSite,
+    pool: Address,
+    users: &[Address],
+) -> Result<Vec<U256>> {
+    let pool = Pool::new(pool, client.clone());
+    let mut multicall = multicall.batch(client).await?;
+    let mut configurations = Vec::with_capacity(users.len());
+    for chunk in users.chunks(MULTICALL_CHUNK_SIZE) {
+        multicall.clear_calls();
+        for user in chunk {
+            multicall.add_call(pool.get_user_configuration(*user), false);
+        }
+        let result: Vec<UserConfigurationMap> = multicall.call_array().await?;
+        configurations.extend(result.into_iter().map(|configuration| configuration.data));
+    }
+    Ok(configurations)
+}
+
+/// Split an Aave user configuration bitmap into the reserves used as collateral
+/// and borrowed, bit 2i flagging a borrow of reserve i and bit 2i+1 its use as collateral.
+pub fn decode_user_configuration(
+    data: U256,
+    reserves: &[Address],
+) -> (HashSet<Address>, HashSet<Address>) {
+    let mut collateral = HashSet::new();
+    let mut debt = HashSet::new();
+    for (i, reserve) in reserves.iter().enumerate().take(128) {
+        if data.bit(2 * i) {
+            debt.insert(*reserve);
+        }
+        if data.bit(2 * i + 1) {
+            collateral.insert(*reserve);
+        }
+    }
+    (collateral, debt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_user_configuration() {
+        let reserves = [
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        ];
+        // collateral in reserve 0, borrowing reserves 1 and 2
+        let data = U256::from(0b01_01_10);
+        let (collateral, debt) = decode_user_configuration(data, &reserves);
+        assert_eq!(collateral, HashSet::from([reserves[0]]));
+        assert_eq!(debt, HashSet::from([reserves[1], reserves[2]]));
+
+        let (collateral, debt) = decode_user_configuration(U256::zero(), &reserves);
+        assert!(collateral.is_empty() && debt.is_empty());
+
+        // supplied but not enabled as collateral, so only the borrow shows
+        let data = U256::from(0b00_00_01);
+        let (collateral, debt) = decode_user_configuration(data, &reserves);
+        assert!(collateral.is_empty());
+        assert_eq!(debt, HashSet::from([reserves[0]]));
+    }
+}
@@ -1,5 +1,7 @@
 // Cargo.toml 需添加依赖:
 
+use super::full_math::mul_add_div;
+use anyhow::{anyhow, Result};
 use ethers::types::U256;
 use lazy_static::lazy_static;
 
@@ -13,60 +15,97 @@ lazy_static! {
 
 /// 两个 wad 相乘，四舍五入到最近的 wad
 pub fn wad_mul(a: U256, b: U256) -> U256 {
-    if b.is_zero() {
-        return U256::zero();
-    }
-
-    let max_a = (U256::max_value() - *HALF_WAD) / b;
-    if a > max_a {
-        panic!("wadMul: multiplication overflow");
-    }
+    checked_wad_mul(a, b).unwrap_or_else(|e| panic!("{}", e))
+}
 
-    (a * b + *HALF_WAD) / *WAD
+/// 两个 wad 相乘，结果超出 256 位时返回错误（乘积按 512 位计算）
+pub fn checked_wad_mul(a: U256, b: U256) -> Result<U256> {
+    checked_half_up_mul(a, b, *WAD, *HALF_WAD).ok_or(anyhow!("wadMul: multiplication overflow"))
 }
 
 /// 两个 wad 相除，四舍五入到最近的 wad
 pub fn wad_div(a: U256, b: U256) -> U256 {
-    if b.is_zero() {
-        panic!("wadDiv: division by zero");
-    }
+    checked_wad_div(a, b).unwrap_or_else(|e| panic!("{}", e))
+}
 
-    let half_b = b / U256::from(2);
-    let max_a = (U256::max_value() - half_b) / *WAD;
-    if a > max_a {
-        panic!("wadDiv: multiplication overflow");
+/// 两个 wad 相除，除数为零或结果超出 256 位时返回错误
+pub fn checked_wad_div(a: U256, b: U256) -> Result<U256> {
+    if b.is_zero() {
+        return Err(anyhow!("wadDiv: division by zero"));
     }
-
-    (a * *WAD + half_b) / b
+    checked_half_up_div(a, b, *WAD).ok_or(anyhow!("wadDiv: multiplication overflow"))
 }
 
 /// 两个 ray 相乘，四舍五入到最近的 ray
 pub fn ray_mul(a: U256, b: U256) -> U256 {
-    if b.is_zero() {
-        return U256::zero();
-    }
-
-    let max_a = (U256::max_value() - *HALF_RAY) / b;
-    if a > max_a {
-        panic!("rayMul: multiplication overflow");
-    }
+    checked_ray_mul(a, b).unwrap_or_else(|e| panic!("{}", e))
+}
 
-    (a * b + *HALF_RAY) / *RAY
+/// 两个 ray 相乘，结果超出 256 位时返回错误（乘积按 512 位计算）
+pub fn checked_ray_mul(a: U256, b: U256) -> Result<U256> {
+    checked_half_up_mul(a, b, *RAY, *HALF_RAY).ok_or(anyhow!("rayMul: multiplication overflow"))
 }
 
 /// 两个 ray 相除，四舍五入到最近的 ray
 pub fn ray_div(a: U256, b: U256) -> U256 {
+    checked_ray_div(a, b).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// 两个 ray 相除，除数为零或结果超出 256 位时返回错误
+pub fn checked_ray_div(a: U256, b: U256) -> Result<U256> {
     if b.is_zero() {
-        panic!("rayDiv: division by zero");
+        return Err(anyhow!("rayDiv: division by zero"));
     }
+    checked_half_up_div(a, b, *RAY).ok_or(anyhow!("rayDiv: multiplication overflow"))
+}
+
+/// 一年的秒数（MathUtils.SECONDS_PER_YEAR）
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
 
-    let half_b = b / U256::from(2);
-    let max_a = (U256::max_value() - half_b) / *RAY;
-    if a > max_a {
-        panic!("rayDiv: multiplication overflow");
+/// 按年利率 `rate`（ray）复利 `elapsed_secs` 秒后的增长系数（MathUtils.calculateCompoundedInterest），
+/// 与合约一样取二项式展开的前三项
+pub fn compounded_interest(rate: U256, elapsed_secs: u64) -> Result<U256> {
+    if elapsed_secs == 0 {
+        return Ok(*RAY);
     }
+    let exp = U256::from(elapsed_secs);
+    let exp_minus_one = exp - 1;
+    let exp_minus_two = exp.saturating_sub(U256::from(2));
+    let seconds_per_year = U256::from(SECONDS_PER_YEAR);
+
+    let base_power_two = checked_ray_mul(rate, rate)? / (seconds_per_year * seconds_per_year);
+    let base_power_three = checked_ray_mul(base_power_two, rate)? / seconds_per_year;
+    let overflow = || anyhow!("compoundedInterest: multiplication overflow");
+    let second_term = (exp * exp_minus_one)
+        .checked_mul(base_power_two)
+        .ok_or_else(overflow)?
+        / 2;
+    let third_term = (exp * exp_minus_one * exp_minus_two)
+        .checked_mul(base_power_three)
+        .ok_or_else(overflow)?
+        / 6;
+    let first_term = rate.checked_mul(exp).ok_or_else(overflow)? / seconds_per_year;
+    Ok(*RAY + first_term + second_term + third_term)
+}
+
+/// 按年利率 `rate`（ray）单利 `elapsed_secs` 秒后的增长系数（MathUtils.calculateLinearInterest），
+/// 存款余额按此累计
+pub fn linear_interest(rate: U256, elapsed_secs: u64) -> Result<U256> {
+    let interest = rate
+        .checked_mul(U256::from(elapsed_secs))
+        .ok_or(anyhow!("linearInterest: multiplication overflow"))?
+        / SECONDS_PER_YEAR;
+    Ok(*RAY + interest)
+}
+
+// (a * b + half) / unit，中间值按 512 位计算，结果溢出时返回 None
+fn checked_half_up_mul(a: U256, b: U256, unit: U256, half: U256) -> Option<U256> {
+    mul_add_div(a, b, half, unit)
+}
 
-    (a * *RAY + half_b) / b
+// (a * unit + b / 2) / b，中间值按 512 位计算，结果溢出时返回 None
+fn checked_half_up_div(a: U256, b: U256, unit: U256) -> Option<U256> {
+    mul_add_div(a, unit, b / 2, b)
 }
 
 /// 将 ray 转换为 wad（四舍五入）
@@ -97,7 +136,9 @@ pub fn wad_to_ray(a: U256) -> U256 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use ethers::types::U256;
+    use crate::strategies::math_reference::{any_u256, big, to_u256};
+    use ethers::types::{U256, U512};
+    use proptest::prelude::*;
 
     // 测试 wadMul 函数
     #[test]
@@ -115,9 +156,16 @@ mod tests {
         assert_eq!(wad_mul(U256::zero(), b), U256::zero());
         assert_eq!(wad_mul(a, U256::zero()), U256::zero());
 
+        // 乘积超出 256 位但结果不超出
+        let large_a = (U256::max_value() - *HALF_WAD) / b + U256::one();
+        assert_eq!(
+            wad_mul(large_a, b),
+            U256::try_from((large_a.full_mul(b) + U512::from(*HALF_WAD)) / U512::from(*WAD))
+                .unwrap()
+        );
+
         // 溢出测试
-        let too_large_a = (U256::max_value() - *HALF_WAD) / b + U256::one();
-        let result = std::panic::catch_unwind(|| wad_mul(too_large_a, b));
+        let result = std::panic::catch_unwind(|| wad_mul(U256::max_value(), *WAD * 2));
         assert!(result.is_err());
     }
 
@@ -134,10 +182,16 @@ mod tests {
         let result = std::panic::catch_unwind(|| wad_div(a, U256::zero()));
         assert!(result.is_err());
 
-        // 溢出测试
+        // 乘积超出 256 位但结果不超出
         let half_b = b / U256::from(2);
-        let too_large_a = (U256::max_value() - half_b) / *WAD + U256::one();
-        let result = std::panic::catch_unwind(|| wad_div(too_large_a, b));
+        let large_a = (U256::max_value() - half_b) / *WAD + U256::one();
+        assert_eq!(
+            wad_div(large_a, b),
+            U256::try_from((large_a.full_mul(*WAD) + U512::from(half_b)) / U512::from(b)).unwrap()
+        );
+
+        // 溢出测试
+        let result = std::panic::catch_unwind(|| wad_div(U256::max_value(), *HALF_WAD));
         assert!(result.is_err());
     }
 
@@ -157,9 +211,16 @@ mod tests {
         assert_eq!(ray_mul(U256::zero(), b), U256::zero());
         assert_eq!(ray_mul(a, U256::zero()), U256::zero());
 
+        // 乘积超出 256 位但结果不超出
+        let large_a = (U256::max_value() - *HALF_RAY) / b + U256::one();
+        assert_eq!(
+            ray_mul(large_a, b),
+            U256::try_from((large_a.full_mul(b) + U512::from(*HALF_RAY)) / U512::from(*RAY))
+                .unwrap()
+        );
+
         // 溢出测试
-        let too_large_a = (U256::max_value() - *HALF_RAY) / b + U256::one();
-        let result = std::panic::catch_unwind(|| ray_mul(too_large_a, b));
+        let result = std::panic::catch_unwind(|| ray_mul(U256::max_value(), *RAY * 2));
         assert!(result.is_err());
     }
 
@@ -200,10 +261,16 @@ mod tests {
         let result = std::panic::catch_unwind(|| ray_div(a, U256::zero()));
         assert!(result.is_err());
 
-        // 溢出测试
+        // 乘积超出 256 位但结果不超出
         let half_b = b / U256::from(2);
-        let too_large_a = (U256::max_value() - half_b) / *RAY + U256::one();
-        let result = std::panic::catch_unwind(|| ray_div(too_large_a, b));
+        let large_a = (U256::max_value() - half_b) / *RAY + U256::one();
+        assert_eq!(
+            ray_div(large_a, b),
+            U256::try_from((large_a.full_mul(*RAY) + U512::from(half_b)) / U512::from(b)).unwrap()
+        );
+
+        // 溢出测试
+        let result = std::panic::catch_unwind(|| ray_div(U256::max_value(), *HALF_RAY));
         assert!(result.is_err());
     }
 
@@ -232,6 +299,60 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_checked_math() {
+        let a = U256::from_dec_str("134534543232342353231234").unwrap();
+        let b = U256::from_dec_str("13265462389132757665657").unwrap();
+        assert_eq!(checked_wad_mul(a, b).unwrap(), wad_mul(a, b));
+        assert_eq!(checked_ray_div(a, b).unwrap(), ray_div(a, b));
+
+        assert!(checked_wad_mul(U256::max_value(), b).is_err());
+        assert!(checked_wad_div(a, U256::zero()).is_err());
+        assert!(checked_ray_div(U256::max_value(), b).is_err());
+        // the product overflows 256 bits, the result doesn't
+        assert_eq!(
+            checked_ray_mul(U256::max_value(), b).unwrap(),
+            U256::try_from(
+                (U256::max_value().full_mul(b) + U512::from(*HALF_RAY)) / U512::from(*RAY)
+            )
+            .unwrap()
+        );
+    }
+
+    // 测试 compoundedInterest 函数
+    #[test]
+    fn test_compounded_interest() {
+        // 零时间不计息
+        assert_eq!(compounded_interest(U256::exp10(26), 0).unwrap(), *RAY);
+
+        // 10% 年利率复利一年，三项展开为 1 + 0.1 + 0.1²/2 + 0.1³/6 ≈ 1.10517
+        let factor = compounded_interest(U256::exp10(26), SECONDS_PER_YEAR).unwrap();
+        assert!(factor > *RAY * 11051 / 10000);
+        assert!(factor < *RAY * 11052 / 10000);
+
+        // 溢出测试
+        assert!(compounded_interest(U256::max_value(), SECONDS_PER_YEAR).is_err());
+    }
+
+    // 测试 linearInterest 函数
+    #[test]
+    fn test_linear_interest() {
+        assert_eq!(linear_interest(U256::exp10(26), 0).unwrap(), *RAY);
+
+        // 10% 年利率单利一年正好是 1.1
+        assert_eq!(
+            linear_interest(U256::exp10(26), SECONDS_PER_YEAR).unwrap(),
+            *RAY * 11 / 10
+        );
+        // 单利半年是 1.05
+        assert_eq!(
+            linear_interest(U256::exp10(26), SECONDS_PER_YEAR / 2).unwrap(),
+            *RAY * 105 / 100
+        );
+
+        assert!(linear_interest(U256::max_value(), SECONDS_PER_YEAR).is_err());
+    }
+
     // 测试 wadToRay 函数
     #[test]
     fn test_wad_to_ray() {
@@ -244,4 +365,59 @@ mod tests {
         let result = std::panic::catch_unwind(|| wad_to_ray(too_large));
         assert!(result.is_err());
     }
+
+    proptest! {
+        // 与任意精度实现一致，结果超出 256 位时才报错
+        #[test]
+        fn prop_mul_matches_reference(a in any_u256(), b in any_u256()) {
+            let expected = to_u256((big(a) * big(b) + big(*HALF_WAD)) / big(*WAD));
+            prop_assert_eq!(checked_wad_mul(a, b).ok(), expected);
+            let expected = to_u256((big(a) * big(b) + big(*HALF_RAY)) / big(*RAY));
+            prop_assert_eq!(checked_ray_mul(a, b).ok(), expected);
+        }
+
+        #[test]
+        fn prop_div_matches_reference(a in any_u256(), b in any_u256()) {
+            prop_assume!(!b.is_zero());
+            let half_b = big(b / 2);
+            let expected = to_u256((big(a) * big(*WAD) + half_b.clone()) / big(b));
+            prop_assert_eq!(checked_wad_div(a, b).ok(), expected);
+            let expected = to_u256((big(a) * big(*RAY) + half_b) / big(b));
+            prop_assert_eq!(checked_ray_div(a, b).ok(), expected);
+        }
+
+        // wad 转 ray 再转回来不变
+        #[test]
+        fn prop_ray_to_wad_inverts_wad_to_ray(a in any_u256()) {
+            let a = a / *WAD_RAY_RATIO;
+            prop_assert_eq!(ray_to_wad(wad_to_ray(a)), a);
+        }
+
+        // ray 转 wad 再转回来最多差半个 wad 精度
+        #[test]
+        fn prop_wad_to_ray_rounds_ray_to_wad(a in any_u256()) {
+            let a = a.min(U256::max_value() - *WAD_RAY_RATIO);
+            let back = wad_to_ray(ray_to_wad(a));
+            let difference = if back > a { back - a } else { a - back };
+            prop_assert!(difference <= *WAD_RAY_RATIO / 2);
+        }
+
+        // 乘法对被乘数单调递增，除法对除数单调递减
+        #[test]
+        fn prop_monotonic(a in any_u256(), b in any_u256(), c in any_u256()) {
+            let (low, high) = (a.min(b), a.max(b));
+            if let (Ok(x), Ok(y)) = (checked_wad_mul(low, c), checked_wad_mul(high, c)) {
+                prop_assert!(x <= y);
+            }
+            if let (Ok(x), Ok(y)) = (checked_ray_mul(low, c), checked_ray_mul(high, c)) {
+                prop_assert!(x <= y);
+            }
+            if let (Ok(x), Ok(y)) = (checked_ray_div(low, c), checked_ray_div(high, c)) {
+                prop_assert!(x <= y);
+            }
+            if let (Ok(x), Ok(y)) = (checked_wad_div(c, low), checked_wad_div(c, high)) {
+                prop_assert!(x >= y);
+            }
+        }
+    }
 }
@@ -0,0 +1,85 @@
+use ethers::types::{Address, U256};
+use serde::Serialize;
+
+/// What the sender needs to keep liquidating, checked at startup and on every wallet tick.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WalletHealth {
+    /// Unix time of the check, 0 until the first one.
+    pub checked_at: u64,
+    pub sender: Address,
+    /// Native balance of the sender, in wei.
+    pub gas_balance: U256,
+    /// Owner of the Liquidator contract, unset on the Aave path.
+    pub liquidator_owner: Option<Address>,
+    /// Debt assets the filters let us repay that the pool can't pull repayments in.
+    pub missing_allowances: Vec<Address>,
+    /// What's wrong, empty if the wallet is healthy.
+    pub problems: Vec<String>,
+}
+
+impl WalletHealth {
+    /// Fill in the problems found, given the least gas balance the sender should hold.
+    pub fn diagnose(mut self, min_gas_balance: U256) -> Self {
+        let mut problems = Vec::new();
+        if self.gas_balance < min_gas_balance {
+            problems.push(format!(
+                "Gas balance {} below the minimum {}",
+                self.gas_balance, min_gas_balance
+            ));
+        }
+        if let Some(owner) = self.liquidator_owner.filter(|owner| *owner != self.sender) {
+            problems.push(format!(
+                "Liquidator contract is owned by {:?}, not the sender {:?}",
+                owner, self.sender
+            ));
+        }
+        if !self.missing_allowances.is_empty() {
+            problems.push(format!(
+                "The pool has no allowance for {:?}",
+                self.missing_allowances
+            ));
+        }
+        self.problems = problems;
+        self
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose() {
+        let sender = Address::repeat_byte(1);
+        let healthy = WalletHealth {
+            sender,
+            gas_balance: U256::exp10(17),
+            liquidator_owner: Some(sender),
+            ..Default::default()
+        }
+        .diagnose(U256::exp10(16));
+        assert!(healthy.is_healthy());
+
+        let unhealthy = WalletHealth {
+            sender,
+            gas_balance: U256::exp10(15),
+            liquidator_owner: Some(Address::repeat_byte(2)),
+            missing_allowances: vec![Address::repeat_byte(3)],
+            ..Default::default()
+        }
+        .diagnose(U256::exp10(16));
+        assert_eq!(unhealthy.problems.len(), 3);
+
+        // no minimum, and no contract on the Aave path
+        let aave = WalletHealth {
+            sender,
+            ..Default::default()
+        }
+        .diagnose(U256::zero());
+        assert!(aave.is_healthy());
+    }
+}
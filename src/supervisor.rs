@@ -0,0 +1,353 @@
+use crate::retry::{ErrorKind, RetryPolicy};
+use anyhow::{anyhow, Result};
+use artemis_core::types::{Collector, CollectorStream, Executor, Strategy};
+use async_trait::async_trait;
+use futures::{FutureExt, StreamExt};
+use serde_json::json;
+use std::any::Any;
+use std::collections::VecDeque;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{error, warn};
+
+/// Restarts a task may go through within [RestartLimits::window] before it's given up on.
+pub const MAX_TASK_RESTARTS: u32 = 5;
+
+/// Seconds over which a task's restarts are counted.
+pub const TASK_RESTART_WINDOW_SECS: u64 = 600;
+
+/// Longest wait for alerts to be posted before exiting.
+pub const ALERT_TIMEOUT_SECS: u64 = 10;
+
+const INITIAL_RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How often a failing task is restarted before the bot gives up and exits.
+#[derive(Debug, Clone)]
+pub struct RestartLimits {
+    pub max_restarts: u32,
+    pub window: Duration,
+}
+
+impl Default for RestartLimits {
+    fn default() -> Self {
+        Self {
+            max_restarts: MAX_TASK_RESTARTS,
+            window: Duration::from_secs(TASK_RESTART_WINDOW_SECS),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    limits: RestartLimits,
+    alert_webhook_url: Option<String>,
+    http: reqwest::Client,
+    /// Alerts being posted
+    sending: Mutex<JoinSet<()>>,
+    /// Why the bot gave up, set once
+    failure: watch::Sender<Option<String>>,
+}
+
+/// Keeps the engine's collectors, strategy and executors running: a task that panics,
+/// or whose stream ends, is restarted with backoff and the operator alerted. A task
+/// failing more than [RestartLimits::max_restarts] times within the window is
+/// unrecoverable, and [Supervisor::failed] resolves so the bot exits non-zero.
+#[derive(Debug, Clone)]
+pub struct Supervisor {
+    inner: Arc<Inner>,
+}
+
+impl Supervisor {
+    pub fn new(limits: RestartLimits, alert_webhook_url: Option<String>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                limits,
+                alert_webhook_url,
+                http: reqwest::Client::new(),
+                sending: Mutex::new(JoinSet::new()),
+                failure: watch::channel(None).0,
+            }),
+        }
+    }
+
+    /// Wrap a collector, strategy or executor, `name` identifying it in alerts.
+    pub fn supervise<T>(&self, name: &str, task: T) -> Supervised<T> {
+        Supervised {
+            name: name.to_string(),
+            task,
+            supervisor: self.clone(),
+            restarts: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Log an alert and post it to the webhook if there is one, as `{"text": ...}` which
+    /// Slack and Discord style webhooks accept.
+    pub fn alert(&self, message: &str) {
+        error!("{}", message);
+        let Some(url) = self.inner.alert_webhook_url.clone() else {
+            return;
+        };
+        let http = self.inner.http.clone();
+        let body = json!({ "text": format!("aave-v3-liquidator: {}", message) });
+        let mut sending = self.inner.sending.lock().unwrap();
+        // reap the alerts already posted
+        while sending.try_join_next().is_some() {}
+        sending.spawn(async move {
+            let sent = http.post(url).json(&body).send().await;
+            if let Err(e) = sent.and_then(|response| response.error_for_status()) {
+                warn!("Failed to send alert: {}", e);
+            }
+        });
+    }
+
+    /// Give up on the bot, `reason` being what [Self::failed] returns.
+    pub fn fail(&self, reason: String) {
+        self.alert(&format!("Unrecoverable: {}", reason));
+        self.inner.failure.send_if_modified(|failure| {
+            let first = failure.is_none();
+            if first {
+                *failure = Some(reason);
+            }
+            first
+        });
+    }
+
+    /// Resolve once a task is unrecoverable, with why.
+    pub async fn failed(&self) -> String {
+        let mut failure = self.inner.failure.subscribe();
+        let reason = failure
+            .wait_for(|failure| failure.is_some())
+            .await
+            .map(|reason| reason.clone());
+        match reason {
+            Ok(reason) => reason.unwrap_or_default(),
+            // the sender lives as long as the supervisor
+            Err(_) => std::future::pending().await,
+        }
+    }
+
+    /// Wait for alerts still being posted, giving up after `timeout`.
+    pub async fn flush_alerts(&self, timeout: Duration) {
+        let mut sending = std::mem::take(&mut *self.inner.sending.lock().unwrap());
+        let _ = tokio::time::timeout(timeout, async {
+            while sending.join_next().await.is_some() {}
+        })
+        .await;
+    }
+
+    // the backoff before restarting a task that failed for `reason`, none once it has
+    // failed too often
+    fn restart(
+        &self,
+        name: &str,
+        reason: &str,
+        restarts: &Mutex<VecDeque<Instant>>,
+    ) -> Option<Duration> {
+        let attempt = {
+            let mut restarts = restarts.lock().unwrap();
+            let now = Instant::now();
+            restarts.retain(|&at| now.duration_since(at) < self.inner.limits.window);
+            restarts.push_back(now);
+            restarts.len() as u32
+        };
+        if attempt > self.inner.limits.max_restarts {
+            self.fail(format!(
+                "{} failed {} times within {}s, last: {}",
+                name,
+                attempt,
+                self.inner.limits.window.as_secs(),
+                reason
+            ));
+            return None;
+        }
+        let backoff = restart_backoff(attempt);
+        self.alert(&format!(
+            "{} failed ({}), restarting in {}ms",
+            name,
+            reason,
+            backoff.as_millis()
+        ));
+        Some(backoff)
+    }
+}
+
+/// Backoff before restart number `attempt` within the window, doubling every attempt.
+pub fn restart_backoff(attempt: u32) -> Duration {
+    RetryPolicy {
+        max_attempts: MAX_TASK_RESTARTS,
+        initial_backoff: INITIAL_RESTART_BACKOFF,
+        max_backoff: MAX_RESTART_BACKOFF,
+    }
+    .max_delay(attempt, ErrorKind::Transient)
+}
+
+/// The message of a caught panic.
+pub fn panic_message(panic: &(dyn Any + Send)) -> String {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("panicked: {}", message)
+}
+
+/// A collector, strategy or executor run under a [Supervisor].
+pub struct Supervised<T> {
+    name: String,
+    task: T,
+    supervisor: Supervisor,
+    /// When the task was last restarted, within the window
+    restarts: Mutex<VecDeque<Instant>>,
+}
+
+impl<T> Supervised<T> {
+    // wait out the backoff before a restart, false if the task should stay down
+    async fn restart(&self, reason: &str) -> bool {
+        match self.supervisor.restart(&self.name, reason, &self.restarts) {
+            Some(backoff) => {
+                tokio::time::sleep(backoff).await;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Resubscribes when the stream fails to open, ends or panics.
+#[async_trait]
+impl<T, E> Collector<E> for Supervised<T>
+where
+    T: Collector<E>,
+    E: Send + 'static,
+{
+    async fn get_event_stream(&self) -> Result<CollectorStream<'_, E>> {
+        let stream = async_stream::stream! {
+            loop {
+                let reason = match AssertUnwindSafe(self.task.get_event_stream())
+                    .catch_unwind()
+                    .await
+                {
+                    Ok(Ok(mut events)) => loop {
+                        match AssertUnwindSafe(events.next()).catch_unwind().await {
+                            Ok(Some(event)) => yield event,
+                            Ok(None) => break "stream ended".to_string(),
+                            Err(panic) => break panic_message(panic.as_ref()),
+                        }
+                    },
+                    Ok(Err(e)) => format!("failed to subscribe: {}", e),
+                    Err(panic) => panic_message(panic.as_ref()),
+                };
+                if !self.restart(&reason).await {
+                    break;
+                }
+            }
+        };
+        Ok(Box::pin(stream))
+    }
+}
+
+/// Resyncs the strategy's state after it panics on an event, its state may have been
+/// left half updated.
+#[async_trait]
+impl<T, E, A> Strategy<E, A> for Supervised<T>
+where
+    T: Strategy<E, A>,
+    E: Send + 'static,
+    A: Send + 'static,
+{
+    async fn sync_state(&mut self) -> Result<()> {
+        loop {
+            let reason = match AssertUnwindSafe(self.task.sync_state())
+                .catch_unwind()
+                .await
+            {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(e)) => format!("failed to sync state: {}", e),
+                Err(panic) => panic_message(panic.as_ref()),
+            };
+            if !self.restart(&reason).await {
+                return Err(anyhow!("{} {}", self.name, reason));
+            }
+        }
+    }
+
+    async fn process_event(&mut self, event: E) -> Vec<A> {
+        let reason = match AssertUnwindSafe(self.task.process_event(event))
+            .catch_unwind()
+            .await
+        {
+            Ok(actions) => return actions,
+            Err(panic) => panic_message(panic.as_ref()),
+        };
+        if self.restart(&reason).await {
+            // a failed resync has already given up on the bot
+            let _ = self.sync_state().await;
+        }
+        vec![]
+    }
+}
+
+/// Fails the action instead of the executor when it panics, pausing the executor for the
+/// backoff.
+#[async_trait]
+impl<T, A> Executor<A> for Supervised<T>
+where
+    T: Executor<A>,
+    A: Send + 'static,
+{
+    async fn execute(&self, action: A) -> Result<()> {
+        match AssertUnwindSafe(self.task.execute(action))
+            .catch_unwind()
+            .await
+        {
+            Ok(result) => result,
+            Err(panic) => {
+                let reason = panic_message(panic.as_ref());
+                self.restart(&reason).await;
+                Err(anyhow!("{} {}", self.name, reason))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_restart() {
+        let supervisor = Supervisor::new(
+            RestartLimits {
+                max_restarts: 2,
+                window: Duration::from_secs(60),
+            },
+            None,
+        );
+        let restarts = Mutex::new(VecDeque::new());
+        assert_eq!(
+            supervisor.restart("collector", "stream ended", &restarts),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            supervisor.restart("collector", "stream ended", &restarts),
+            Some(Duration::from_secs(2))
+        );
+        // the third failure within the window is unrecoverable
+        assert_eq!(
+            supervisor.restart("collector", "stream ended", &restarts),
+            None
+        );
+        assert_eq!(
+            supervisor.failed().await,
+            "collector failed 3 times within 60s, last: stream ended"
+        );
+        assert_eq!(restart_backoff(10), MAX_RESTART_BACKOFF);
+
+        let panic = std::panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        assert_eq!(panic_message(panic.as_ref()), "panicked: boom");
+    }
+}
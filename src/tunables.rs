@@ -0,0 +1,282 @@
+use crate::control::{ControlCommand, ControlHandle, ControlRequest};
+use crate::strategies::bid_model::BidBounds;
+use crate::strategies::filters::LiquidationFilter;
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info};
+
+/// Seconds between checks of the tunables file for changes.
+pub const TUNABLES_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// Bid and min profit of liquidations seizing or repaying an asset, in place of the global
+/// ones, since blue-chip assets take a far higher bid to win than long-tail ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AssetOverride {
+    /// Fixed bid, the bid model isn't consulted for the asset.
+    pub bid_percentage: Option<u64>,
+    /// In wei.
+    pub min_profit_eth: Option<U256>,
+}
+
+/// Override of a liquidation of `collateral` for `debt`, each field taken from the
+/// collateral's override if set there, else from the debt's.
+pub fn asset_override(
+    overrides: &HashMap<Address, AssetOverride>,
+    collateral: Address,
+    debt: Address,
+) -> AssetOverride {
+    let collateral = overrides.get(&collateral).copied().unwrap_or_default();
+    let debt = overrides.get(&debt).copied().unwrap_or_default();
+    AssetOverride {
+        bid_percentage: collateral.bid_percentage.or(debt.bid_percentage),
+        min_profit_eth: collateral.min_profit_eth.or(debt.min_profit_eth),
+    }
+}
+
+/// Settings changed on the running liquidator without a restart, each kept as it is
+/// if unset. An empty allowlist allows any asset, as on the command line.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tunables {
+    pub bid_percentage: Option<u64>,
+    pub min_bid_percentage: Option<u64>,
+    pub max_bid_percentage: Option<u64>,
+    /// In wei.
+    pub min_profit_eth: Option<U256>,
+    pub collateral_allowlist: Option<Vec<Address>>,
+    pub collateral_denylist: Option<Vec<Address>>,
+    pub debt_allowlist: Option<Vec<Address>>,
+    pub debt_denylist: Option<Vec<Address>>,
+    pub borrower_blacklist: Option<Vec<Address>>,
+    /// Bid and min profit by collateral or debt asset, replacing the overrides in use.
+    pub asset_overrides: Option<HashMap<Address, AssetOverride>>,
+    /// Seconds between full scans.
+    pub poll_interval_secs: Option<u64>,
+    /// Seconds between health factor polls of watchlisted borrowers.
+    pub watchlist_poll_interval_secs: Option<u64>,
+}
+
+impl Tunables {
+    /// Read tunables from a JSON file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = std::fs::read_to_string(path)?;
+        let tunables: Self = serde_json::from_str(&file)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))?;
+        tunables.validate()?;
+        Ok(tunables)
+    }
+
+    /// Reject settings the strategy can't run with, before any is applied.
+    pub fn validate(&self) -> Result<()> {
+        let percentages = [
+            self.bid_percentage,
+            self.min_bid_percentage,
+            self.max_bid_percentage,
+        ];
+        let overrides = self
+            .asset_overrides
+            .iter()
+            .flat_map(|overrides| overrides.values())
+            .map(|asset| asset.bid_percentage);
+        if percentages
+            .into_iter()
+            .chain(overrides)
+            .flatten()
+            .any(|percentage| percentage > 100)
+        {
+            return Err(anyhow!("Bid percentages can't exceed 100"));
+        }
+        if self.poll_interval_secs == Some(0) || self.watchlist_poll_interval_secs == Some(0) {
+            return Err(anyhow!("Poll intervals must be at least a second"));
+        }
+        Ok(())
+    }
+
+    /// `bounds` with the bid percentages set, the base one also moving a bound it was
+    /// equal to when that bound isn't set itself.
+    pub fn bid_bounds(&self, bounds: BidBounds) -> BidBounds {
+        let base = self.bid_percentage.unwrap_or(bounds.base);
+        let bound = |set: Option<u64>, current: u64| match set {
+            Some(set) => set,
+            None if current == bounds.base => base,
+            None => current,
+        };
+        BidBounds {
+            base,
+            min: bound(self.min_bid_percentage, bounds.min),
+            max: bound(self.max_bid_percentage, bounds.max),
+        }
+    }
+
+    /// Replace the lists of `filter` that are set.
+    pub fn apply_filter(&self, filter: &mut LiquidationFilter) {
+        let set = |list: &Vec<Address>| list.iter().copied().collect::<HashSet<_>>();
+        let allowlist = |list: &Vec<Address>| (!list.is_empty()).then(|| set(list));
+        if let Some(list) = &self.collateral_allowlist {
+            filter.collateral_allowlist = allowlist(list);
+        }
+        if let Some(list) = &self.collateral_denylist {
+            filter.collateral_denylist = set(list);
+        }
+        if let Some(list) = &self.debt_allowlist {
+            filter.debt_allowlist = allowlist(list);
+        }
+        if let Some(list) = &self.debt_denylist {
+            filter.debt_denylist = set(list);
+        }
+        if let Some(list) = &self.borrower_blacklist {
+            filter.borrower_blacklist = set(list);
+        }
+    }
+}
+
+/// Apply the tunables file through `control` at startup, whenever it changes and on
+/// SIGHUP, until the process exits. A file that fails to parse is logged and skipped.
+pub async fn watch(path: PathBuf, control: ControlHandle, check_interval: Duration) -> Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut interval = tokio::time::interval(check_interval);
+    let mut last_modified = None;
+    loop {
+        let actor = tokio::select! {
+            _ = interval.tick() => {
+                let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+                if modified.is_none() || modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                "tunables file"
+            }
+            _ = hangup.recv() => "SIGHUP",
+        };
+        if let Err(e) = reload(&path, &control, actor) {
+            error!("Failed to reload tunables: {}", e);
+        }
+    }
+}
+
+fn reload(path: &Path, control: &ControlHandle, actor: &str) -> Result<()> {
+    let tunables = Tunables::read(path)?;
+    info!("Reloading tunables from {} on {}", path.display(), actor);
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Invalid timestamp")
+        .as_nanos();
+    control.submit(ControlRequest {
+        idempotency_key: format!("tunables-{}", timestamp),
+        actor: actor.to_string(),
+        command: ControlCommand::SetTunables { tunables },
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let tunables: Tunables = serde_json::from_str(
+            r#"{
+                "bid_percentage": 60,
+                "collateral_allowlist": [],
+                "debt_denylist": ["0x0101010101010101010101010101010101010101"],
+                "poll_interval_secs": 120
+            }"#,
+        )
+        .unwrap();
+        assert!(tunables.validate().is_ok());
+
+        // bounds left at the base follow it, set ones stay
+        let bounds = tunables.bid_bounds(BidBounds {
+            base: 50,
+            min: 50,
+            max: 90,
+        });
+        assert_eq!(
+            bounds,
+            BidBounds {
+                base: 60,
+                min: 60,
+                max: 90
+            }
+        );
+
+        let mut filter = LiquidationFilter {
+            collateral_allowlist: Some(HashSet::from([Address::repeat_byte(2)])),
+            borrower_blacklist: HashSet::from([Address::repeat_byte(3)]),
+            ..Default::default()
+        };
+        tunables.apply_filter(&mut filter);
+        assert_eq!(filter.collateral_allowlist, None);
+        assert_eq!(
+            filter.debt_denylist,
+            HashSet::from([Address::repeat_byte(1)])
+        );
+        assert_eq!(
+            filter.borrower_blacklist,
+            HashSet::from([Address::repeat_byte(3)])
+        );
+
+        let invalid = Tunables {
+            max_bid_percentage: Some(150),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_asset_override() {
+        let (weth, usdc, pepe) = (
+            Address::repeat_byte(1),
+            Address::repeat_byte(2),
+            Address::repeat_byte(3),
+        );
+        let tunables: Tunables = serde_json::from_str(
+            r#"{
+                "asset_overrides": {
+                    "0x0101010101010101010101010101010101010101": {"bid_percentage": 95},
+                    "0x0202020202020202020202020202020202020202": {"bid_percentage": 90, "min_profit_eth": "0x10"},
+                    "0x0303030303030303030303030303030303030303": {"bid_percentage": 30}
+                }
+            }"#,
+        )
+        .unwrap();
+        assert!(tunables.validate().is_ok());
+        let overrides = tunables.asset_overrides.unwrap();
+
+        // the collateral's fields come first, the debt's fill the rest
+        assert_eq!(
+            asset_override(&overrides, weth, usdc),
+            AssetOverride {
+                bid_percentage: Some(95),
+                min_profit_eth: Some(U256::from(16)),
+            }
+        );
+        assert_eq!(
+            asset_override(&overrides, pepe, usdc).bid_percentage,
+            Some(30)
+        );
+        assert_eq!(
+            asset_override(&overrides, Address::zero(), Address::zero()),
+            AssetOverride::default()
+        );
+
+        let invalid = Tunables {
+            asset_overrides: Some(HashMap::from([(
+                weth,
+                AssetOverride {
+                    bid_percentage: Some(101),
+                    min_profit_eth: None,
+                },
+            )])),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
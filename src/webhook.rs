@@ -0,0 +1,141 @@
+use crate::api::OpportunityStatus;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::warn;
+
+/// Seconds a post to the webhook may take before it's dropped.
+pub const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+/// Posts in flight at once, opportunities evaluated past this aren't posted.
+pub const MAX_PENDING_POSTS: usize = 64;
+
+/// What became of an evaluated opportunity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// A liquidation was built for the executor to send.
+    Submitted,
+    /// Queued for the execute-only instances of the cluster.
+    Queued,
+    /// Not submitted, for the report's reason.
+    Passed,
+}
+
+/// The JSON body posted for an evaluated opportunity.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpportunityReport {
+    #[serde(flatten)]
+    pub opportunity: OpportunityStatus,
+    pub verdict: Verdict,
+    /// Why it was passed on, none once submitted or queued.
+    pub reason: Option<String>,
+}
+
+/// A sink posting every opportunity the strategy evaluates, and what became of it, to a
+/// webhook, for analytics or approval workflows outside the bot. Posts are best effort:
+/// a failed one is only logged.
+#[derive(Debug, Clone)]
+pub struct OpportunityWebhook {
+    url: String,
+    http: reqwest::Client,
+    pending: Arc<Mutex<JoinSet<()>>>,
+}
+
+impl OpportunityWebhook {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            http: reqwest::Client::builder()
+                .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+                .build()
+                .expect("Failed to build HTTP client"),
+            pending: Default::default(),
+        }
+    }
+
+    /// Post a report in the background, without holding up the strategy.
+    pub fn post(&self, report: OpportunityReport) {
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        // reap the posts already sent
+        while pending.try_join_next().is_some() {}
+        if pending.len() >= MAX_PENDING_POSTS {
+            warn!(
+                correlation_id = %report.opportunity.correlation_id,
+                "Opportunity webhook backed up, dropping report"
+            );
+            return;
+        }
+        let (http, url) = (self.http.clone(), self.url.clone());
+        pending.spawn(async move {
+            let sent = http.post(url).json(&report).send().await;
+            if let Err(e) = sent.and_then(|response| response.error_for_status()) {
+                warn!(
+                    correlation_id = %report.opportunity.correlation_id,
+                    "Failed to post opportunity: {}", e
+                );
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Address, U256};
+    use serde_json::json;
+
+    fn report(verdict: Verdict, reason: Option<&str>) -> OpportunityReport {
+        OpportunityReport {
+            opportunity: OpportunityStatus {
+                correlation_id: "abc".to_string(),
+                timestamp: 1700000000,
+                borrower: Address::repeat_byte(1),
+                collateral: Address::repeat_byte(2),
+                debt: Address::repeat_byte(3),
+                debt_to_cover: U256::from(1000),
+                profit_eth: "-5".to_string(),
+                profit: None,
+                confidence: 9000,
+            },
+            verdict,
+            reason: reason.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_report_body() {
+        let body = serde_json::to_value(report(Verdict::Passed, Some("No profit"))).unwrap();
+        assert_eq!(body["correlation_id"], json!("abc"));
+        assert_eq!(
+            body["borrower"],
+            json!("0x0101010101010101010101010101010101010101")
+        );
+        assert_eq!(body["profit_eth"], json!("-5"));
+        assert_eq!(body["verdict"], json!("passed"));
+        assert_eq!(body["reason"], json!("No profit"));
+        let body = serde_json::to_value(report(Verdict::Submitted, None)).unwrap();
+        assert_eq!(body["verdict"], json!("submitted"));
+        assert!(body["reason"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_post() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/opportunities")
+            .match_body(mockito::Matcher::PartialJson(json!({
+                "correlation_id": "abc",
+                "verdict": "queued",
+            })))
+            .create_async()
+            .await;
+        let webhook = OpportunityWebhook::new(format!("{}/opportunities", server.url()));
+        webhook.post(report(Verdict::Queued, None));
+        let mut pending = std::mem::take(&mut *webhook.pending.lock().unwrap());
+        while pending.join_next().await.is_some() {}
+        mock.assert_async().await;
+    }
+}
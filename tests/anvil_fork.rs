@@ -0,0 +1,290 @@
+//! End-to-end liquidation on an anvil fork of Base: a borrower is pushed under water by
+//! lowering the WETH price, and the strategy has to find, build and land its liquidation,
+//! both straight on the pool and through the Liquidator contract.
+//!
+//! Needs `anvil` on the PATH and a Base RPC to fork from:
+//!
+//!     FORK_RPC_URL=<Base RPC url> cargo test --test anvil_fork -- --ignored
+
+use aave_v3_liquidator::collectors::time_collector::NewTick;
+use aave_v3_liquidator::executors::l1_fee::L1FeeModel;
+use aave_v3_liquidator::executors::protect_executor::ProtectExecutor;
+use aave_v3_liquidator::history::{LiquidationHistory, LiquidationStatus};
+use aave_v3_liquidator::strategies::aave_strategy::{
+    state_cache_path, AaveStrategy, Deployment, StateCache, BASE_UNISWAP_FACTORY,
+};
+use aave_v3_liquidator::strategies::log_range::LOG_BLOCK_RANGE;
+use aave_v3_liquidator::strategies::types::{Action, Config, Event};
+use anyhow::{anyhow, Result};
+use artemis_core::types::{Executor, Strategy};
+use bindings_aave::{
+    aave_oracle::AaveOracle, aggregator_interface::AggregatorInterface, ierc20::IERC20,
+    iweth::IWETH, mock_aggregator::MOCKAGGREGATOR_DEPLOYED_BYTECODE, pool::Pool,
+};
+use bindings_liquidator::liquidator::Liquidator;
+use ethers::{
+    middleware::SignerMiddleware,
+    providers::{Http, Middleware, Provider},
+    signers::{LocalWallet, Signer},
+    types::{Address, H256, U256},
+    utils::{parse_ether, Anvil},
+};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CHAIN_ID: u64 = 8453;
+const POOL: &str = "0xA238Dd80C259a72e81d7e4664a9801593F98d1c5";
+const ORACLE: &str = "0x2Cc0Fc26eD4563A5ce5e8bdcfe1A2878676Ae156";
+const WETH: &str = "0x4200000000000000000000000000000000000006";
+const USDC: &str = "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913";
+
+// variable rate mode of Pool.borrow
+const VARIABLE_RATE_MODE: u64 = 2;
+
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+// supply WETH wrapped from ETH and borrow `borrow_bps` of what it allows in USDC
+async fn open_position(client: Arc<Client>, supply: U256, borrow_bps: u64) -> Result<()> {
+    let account = client.address();
+    let pool = Pool::new(Address::from_str(POOL)?, client.clone());
+    let weth = IWETH::new(Address::from_str(WETH)?, client.clone());
+    weth.deposit().value(supply).send().await?.await?;
+    IERC20::new(Address::from_str(WETH)?, client.clone())
+        .approve(pool.address(), U256::MAX)
+        .send()
+        .await?
+        .await?;
+    pool.supply(weth.address(), supply, account, 0)
+        .send()
+        .await?
+        .await?;
+
+    // the pool's base currency is USD with 8 decimals, USDC has 6
+    let (_, _, available_borrows_base, _, _, _) =
+        pool.get_user_account_data(account).call().await?;
+    let amount = available_borrows_base * borrow_bps / 10000 / 100;
+    pool.borrow(
+        Address::from_str(USDC)?,
+        amount,
+        U256::from(VARIABLE_RATE_MODE),
+        0,
+        account,
+    )
+    .send()
+    .await?
+    .await?;
+    Ok(())
+}
+
+// swap the WETH price source for a mock aggregator answering `bps` of the current price
+async fn move_weth_price(provider: &Provider<Http>, bps: u64) -> Result<()> {
+    let client = Arc::new(provider.clone());
+    let oracle = AaveOracle::new(Address::from_str(ORACLE)?, client.clone());
+    let source = oracle
+        .get_source_of_asset(Address::from_str(WETH)?)
+        .call()
+        .await?;
+    let price = AggregatorInterface::new(source, client)
+        .latest_answer()
+        .call()
+        .await?;
+    let price = price.into_raw() * bps / 10000;
+
+    provider
+        .request::<_, ()>(
+            "anvil_setCode",
+            (source, MOCKAGGREGATOR_DEPLOYED_BYTECODE.clone()),
+        )
+        .await?;
+    // the mock answers the int256 in its first slot
+    provider
+        .request::<_, bool>(
+            "anvil_setStorageAt",
+            (source, H256::zero(), H256::from(<[u8; 32]>::from(price))),
+        )
+        .await?;
+    provider.request::<_, String>("evm_mine", ()).await?;
+    Ok(())
+}
+
+fn config(dir: &Path) -> Config {
+    Config {
+        chain_id: CHAIN_ID,
+        bid_percentage: 0,
+        min_bid_percentage: None,
+        max_bid_percentage: None,
+        watchlist_health_factor_bps: 10500,
+        risk_health_factor_bps: 11000,
+        min_confidence_bps: 0,
+        public_confidence_bps: 0,
+        scoring: Default::default(),
+        partner_accounts_file: dir.join("partners.json"),
+        repay_adapter: None,
+        paraswap_api_url: String::new(),
+        target_health_factor_bps: 10500,
+        max_slippage_bps: 50,
+        fee_rebate: false,
+        fee_rebate_bps: None,
+        smart_account_reconcile_ticks: 1,
+        eoa_reconcile_ticks: 1,
+        min_profit_eth: U256::zero(),
+        profit_currency: Default::default(),
+        min_gas_balance_wei: U256::zero(),
+        pause_on_wallet_problems: false,
+        max_sync_lag: None,
+        reference_oracle: None,
+        price_sources: vec![],
+        max_price_source_deviation_bps: 300,
+        min_debt_usd: 0,
+        evaluation_concurrency: 1,
+        max_liquidation_legs: 1,
+        multi_hop_swaps: false,
+        flash_liquidity_pools: Vec::new(),
+        aggregator_flash_swaps: false,
+        harvest: None,
+        approval: None,
+        one_inch_api_key: None,
+        zero_ex_api_key: None,
+        tracer: None,
+        state_dir: dir.to_path_buf(),
+        reserve_cache_ttl_secs: 0,
+        subgraph_url: None,
+        ui_pool_data_provider: None,
+        multicall_address: None,
+        pool_address: None,
+        wrapped_native_address: None,
+        log_block_range: LOG_BLOCK_RANGE,
+        approval_buffer_bps: None,
+        receive_a_token: HashSet::new(),
+        filter: Default::default(),
+    }
+}
+
+// land the liquidation of a borrower pushed under water, straight on the pool or through a
+// freshly deployed Liquidator contract
+async fn liquidate_on_fork(use_aave_liquidator: bool) -> Result<()> {
+    let fork_url = std::env::var("FORK_RPC_URL")?;
+    let anvil = Anvil::new().fork(fork_url).chain_id(CHAIN_ID).spawn();
+    let provider =
+        Provider::<Http>::try_from(anvil.endpoint())?.interval(Duration::from_millis(100));
+    let wallet = |i: usize| LocalWallet::from(anvil.keys()[i].clone()).with_chain_id(CHAIN_ID);
+    let borrower = Arc::new(SignerMiddleware::new(provider.clone(), wallet(0)));
+    let liquidator = Arc::new(SignerMiddleware::new(provider.clone(), wallet(1)));
+
+    let liquidator_address = if use_aave_liquidator {
+        Address::zero()
+    } else {
+        Liquidator::deploy(
+            liquidator.clone(),
+            (
+                Address::from_str(POOL)?,
+                Address::from_str(BASE_UNISWAP_FACTORY)?,
+            ),
+        )?
+        .send()
+        .await?
+        .address()
+    };
+
+    // start from a clean state dir that only indexes borrowers from the fork block on
+    let dir: PathBuf = std::env::temp_dir().join(format!(
+        "anvil-fork-{}-{}",
+        std::process::id(),
+        use_aave_liquidator
+    ));
+    std::fs::create_dir_all(&dir)?;
+    let fork_block = provider.get_block_number().await?.as_u64();
+    StateCache::new(
+        CHAIN_ID,
+        Deployment::AAVE,
+        liquidator_address,
+        fork_block,
+        HashMap::new(),
+    )
+    .write(&state_cache_path(&dir, CHAIN_ID, &Deployment::AAVE))?;
+
+    // a position close to its limit, and a healthy one funding the liquidator with USDC
+    open_position(borrower.clone(), parse_ether(10)?, 9900).await?;
+    open_position(liquidator.clone(), parse_ether(40)?, 2000).await?;
+    move_weth_price(&provider, 9000).await?;
+
+    let pool = Pool::new(Address::from_str(POOL)?, liquidator.clone());
+    let (_, debt_before, _, _, _, health_factor) = pool
+        .get_user_account_data(borrower.address())
+        .call()
+        .await?;
+    assert!(
+        health_factor < parse_ether(1)?,
+        "borrower is not under water"
+    );
+
+    let mut strategy = AaveStrategy::new(
+        liquidator.clone(),
+        config(&dir),
+        Deployment::AAVE,
+        format!("{:?}", liquidator_address),
+        use_aave_liquidator,
+    );
+    strategy.sync_state().await?;
+    let actions = strategy
+        .process_event(Event::NewTick(NewTick { timestamp: 0 }))
+        .await;
+    let tx = actions
+        .into_iter()
+        .find_map(|action| match action {
+            Action::SubmitTx(tx) => Some(tx),
+            _ => None,
+        })
+        .ok_or(anyhow!("no liquidation submitted"))?;
+    let liquidation = tx.liquidation.clone().ok_or(anyhow!("not a liquidation"))?;
+    assert_eq!(liquidation.borrower, borrower.address());
+    if !use_aave_liquidator {
+        assert_eq!(tx.submission.tx.to_addr(), Some(&liquidator_address));
+    }
+
+    let history = LiquidationHistory::new(&dir.join("liquidations.jsonl"));
+    let executor = ProtectExecutor::new(liquidator.clone(), liquidator.clone())
+        .with_l1_fee(L1FeeModel::for_chain(CHAIN_ID))
+        .with_history(history.clone(), CHAIN_ID);
+    executor.execute(tx).await?;
+
+    // the outcome is recorded once the receipt comes in
+    let mut outcome = None;
+    for _ in 0..100 {
+        outcome = history
+            .read()?
+            .into_iter()
+            .find(|record| record.status != LiquidationStatus::Attempted);
+        if outcome.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let outcome = outcome.ok_or(anyhow!("liquidation outcome not recorded"))?;
+    assert_eq!(outcome.status, LiquidationStatus::Landed);
+    assert!(!outcome.debt_covered.is_zero());
+    assert!(!outcome.collateral_seized.is_zero());
+    assert!(outcome.realized_profit > ethers::types::I256::zero());
+
+    let (_, debt_after, _, _, _, _) = pool
+        .get_user_account_data(borrower.address())
+        .call()
+        .await?;
+    assert!(debt_after < debt_before);
+    Ok(())
+}
+
+#[tokio::test]
+#[ignore = "needs anvil and FORK_RPC_URL"]
+async fn test_liquidation_lands_on_fork() -> Result<()> {
+    liquidate_on_fork(true).await
+}
+
+#[tokio::test]
+#[ignore = "needs anvil and FORK_RPC_URL"]
+async fn test_liquidation_through_contract_lands_on_fork() -> Result<()> {
+    liquidate_on_fork(false).await
+}